@@ -60,4 +60,5 @@ echo "📤 To deploy:"
 echo "   linera publish-and-create \\"
 echo "     target/wasm32-unknown-unknown/release/chronos_market_contract.wasm \\"
 echo "     target/wasm32-unknown-unknown/release/chronos_market_service.wasm \\"
-echo "     --json-argument 'null'"
+echo "     --json-argument '{\"admin\": \"<ADMIN_OWNER>\", \"fee_bps\": 0, \"min_initial_liquidity\": \"1\", \"min_market_duration_secs\": 3600, \"resolution_grace_period_secs\": 604800, \"admin_resolution_delay_secs\": 259200, \"claim_window_secs\": 2592000, \"min_trade_shares\": \"0.000001\", \"recent_trades_capacity\": 200, \"share_decimals\": 6}' \\"
+echo "     --json-parameters '{\"registry_chain\": null}'"
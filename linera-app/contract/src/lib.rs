@@ -5,54 +5,322 @@
 
 pub mod state;
 
-use chronos_market::{MarketAbi, Operation, FeedItemType};
+use chronos_market::{
+    math::{cost_to_buy, payout_for_shares, pnl, pool_product, price_impact_bps, probability_move_bps, safe_mul_div, sell_proceeds, shares_to_units, split_fee, ATTO_DECIMALS},
+    allocate_id, check_open, day_index, effective_status, hour_index, market_key, utc_civil_date, volume_history_start, Config, EffectiveStatus, FeeRamp, InstantiationArgument, MarketAbi, MarketError, MarketEvent, MarketKey,
+    MarketCondition, MarketParameters, Message, Operation, OrderSide, FeedItemType, PriceCheckpoint, RecentTrade, SeriesTemplate,
+    MARKET_EVENT_STREAM, MAX_KEEPER_FEE, MAX_PARLAY_LEGS, MAX_PENDING_TRIGGER_ORDERS, MAX_RESOLUTIONS, MAX_PRICE_CHECKPOINTS, MAX_RECENT_TRADES_CAPACITY, POSITION_TRANSFER_TIMEOUT_SECS,
+};
 use linera_sdk::{
-    linera_base_types::{Amount, WithContractAbi, AccountOwner},
-    views::{RootView, View},
+    linera_base_types::{Amount, WithContractAbi, AccountOwner, ChainId, StreamName, TimeDelta, Timestamp},
+    views::{RegisterView, RootView, View},
     Contract, ContractRuntime,
 };
 
-use self::state::{MarketState, OrderStatus, ComboStatus, ComboLegState};
+use std::cmp::Reverse;
+
+use self::state::{BondStatus, MarketState, OrderStatus, ComboStatus, ComboLegState, TriggerStatus};
+
+/// Everything `CreateMarket` needs to open a market, gathered for validation
+struct NewMarket {
+    question: String,
+    categories: Vec<String>,
+    end_time: Timestamp,
+    initial_liquidity: Amount,
+    early_resolution_allowed: bool,
+    max_position_per_account: Option<Amount>,
+    max_position: Option<Amount>,
+    creator_trading_disabled: bool,
+    fee_ramp: Option<FeeRamp>,
+    description: String,
+    resolution_source: String,
+    parent: Option<MarketCondition>,
+    group_id: Option<u64>,
+    resolvers: Vec<AccountOwner>,
+    threshold: u8,
+    max_probability_move_bps: Option<u64>,
+    max_trade_shares: Option<Amount>,
+}
+
+/// A sale of one side's shares to a market's AMM
+struct AmmSale {
+    market_id: u64,
+    is_yes: bool,
+    shares: Amount,
+    /// Least the seller accepts after the trading fee
+    min_proceeds: Amount,
+    deadline: Option<Timestamp>,
+}
+
+/// What a sale to the AMM paid and the pools it left
+struct AmmReceipt {
+    proceeds: Amount,
+    /// Proceeds minus the cost basis the sale closed out
+    realized_pnl: i128,
+    fee: Amount,
+    fee_bps: u64,
+    new_yes_price_bps: u64,
+    new_no_price_bps: u64,
+    yes_pool: Amount,
+    no_pool: Amount,
+}
+
+/// What a resolution leaves the market's traders to claim, exactly as the
+/// claims will pay it
+struct Settlement {
+    /// Collateral the claims pay out together: payouts, or refunds of cost
+    /// basis in refund mode
+    total_payout: Amount,
+    /// Trader-held shares of the winning side; zero in refund mode
+    winning_shares: Amount,
+    /// Payout per whole winning share, rounded down; zero in refund mode
+    payout_per_share: Amount,
+    /// Positions whose claim pays anything
+    eligible_positions: u64,
+}
+
+/// What a book fill was worth, the taker's fee on it and the part of that
+/// fee left for the market's LPs after the maker's rebate
+struct BookFill {
+    value: Amount,
+    fee: Amount,
+    lp_fee: Amount,
+}
+
+/// The next market of a series, opening at `timestamp`
+fn series_market(template: &SeriesTemplate, timestamp: Timestamp) -> NewMarket {
+    let end_time = timestamp.saturating_add(TimeDelta::from_secs(template.duration_secs));
+    NewMarket {
+        question: template.question.replace("{date}", &utc_date(end_time)),
+        categories: template.categories.clone(),
+        end_time,
+        initial_liquidity: template.liquidity_per_instance,
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: String::new(),
+        resolution_source: String::new(),
+        parent: None,
+        group_id: None,
+        resolvers: Vec::new(),
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    }
+}
+
+/// Calendar date of a timestamp as YYYY-MM-DD in UTC
+fn utc_date(timestamp: Timestamp) -> String {
+    let (year, month, day) = utc_civil_date(timestamp);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Longest accepted `CreateMarket` description, in bytes
+const MAX_DESCRIPTION_LEN: usize = 2_000;
+/// Longest accepted `CreateMarket` resolution source, in bytes
+const MAX_RESOLUTION_SOURCE_LEN: usize = 500;
+/// Longest accepted `ResolveMarket` evidence, in bytes
+const MAX_EVIDENCE_LEN: usize = 1_024;
+/// Most members a `CreateMarketGroup` may open
+const MAX_GROUP_MEMBERS: usize = 20;
+/// Most resolvers a market may list
+const MAX_RESOLVERS: usize = 16;
+/// Most accounts that may fund one market's incentives
+const MAX_SPONSORS: usize = 10;
+/// Longest accepted sponsor name, in bytes
+const MAX_SPONSOR_NAME_LEN: usize = 64;
+/// Longest accepted sponsor link, in bytes
+const MAX_SPONSOR_URL_LEN: usize = 256;
+
+/// Refuse a sponsor name or link over its length limit, or a link without a name
+fn check_sponsor_branding(name: &str, url: &str) -> Result<(), MarketError> {
+    if name.len() > MAX_SPONSOR_NAME_LEN {
+        return Err(MarketError::TextTooLong { field: "sponsor_name", max: MAX_SPONSOR_NAME_LEN });
+    }
+    if url.len() > MAX_SPONSOR_URL_LEN {
+        return Err(MarketError::TextTooLong { field: "sponsor_url", max: MAX_SPONSOR_URL_LEN });
+    }
+    if name.is_empty() && !url.is_empty() {
+        return Err(MarketError::SponsorUrlWithoutName);
+    }
+    Ok(())
+}
+
+fn unbranded_sponsor(account: AccountOwner, amount: Amount) -> state::Sponsor {
+    state::Sponsor { account, amount, name: String::new(), url: String::new() }
+}
+
+/// Split `returned` over `sponsors` in proportion to what each funded,
+/// rounding down, with the dust going to the first
+fn split_return(sponsors: &[state::Sponsor], returned: Amount) -> Result<Vec<(AccountOwner, Amount)>, String> {
+    let total = sponsors.iter().fold(Amount::ZERO, |total, sponsor| total.saturating_add(sponsor.amount));
+    let mut shares = sponsors.iter()
+        .map(|sponsor| {
+            let share = safe_mul_div(u128::from(returned), u128::from(sponsor.amount), u128::from(total))?;
+            Ok((sponsor.account, Amount::from_attos(share)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let paid = shares.iter().fold(Amount::ZERO, |paid, (_, share)| paid.saturating_add(*share));
+    shares[0].1 = shares[0].1.saturating_add(returned.saturating_sub(paid));
+    Ok(shares)
+}
 
-/// Safely compute (a * b) / c without u128 overflow.
-fn safe_mul_div(a: u128, b: u128, c: u128) -> Result<u128, String> {
-    if c == 0 {
-        return Err("Division by zero in AMM calculation".to_string());
+/// Marginal AMM cost of the next share in basis points: pool_in / pool_out.
+fn amm_buy_price_bps(pool_in: u128, pool_out: u128) -> Result<u64, String> {
+    if pool_out == 0 {
+        return Ok(u64::MAX);
     }
+    Ok(u64::try_from(safe_mul_div(pool_in, 10_000, pool_out)?).unwrap_or(u64::MAX))
+}
+
+/// Add `amount` to a running total, or take it off
+fn adjust_total(total: &mut RegisterView<Amount>, amount: Amount, increase: bool) {
+    let value = *total.get();
+    total.set(if increase { value.saturating_add(amount) } else { value.saturating_sub(amount) });
+}
 
-    // Fast path: if a * b fits in u128, do it directly
-    if let Some(product) = a.checked_mul(b) {
-        return Ok(product / c);
+/// Refuse a trade that left `market`'s pools with a smaller product than
+/// `before`, its (yes, no) pools going in. The rounding in `math` rules
+/// this out; the check guards against a pricing bug draining the pools.
+fn check_pool_product(market: &state::Market, before: (Amount, Amount)) -> Result<(), String> {
+    let product = |yes: Amount, no: Amount| pool_product(yes.into(), no.into());
+    if product(market.yes_pool, market.no_pool) < product(before.0, before.1) {
+        return Err(MarketError::PoolProductDecreased { market_id: market.id }.into());
     }
+    Ok(())
+}
 
-    // Overflow path: decompose to avoid overflow
-    let quotient = a / c;
-    let remainder = a % c;
+/// Average price paid per share in basis points, rounded down
+fn average_price_bps(cost: Amount, shares: Amount) -> Result<u64, String> {
+    let price = safe_mul_div(u128::from(cost), 10_000, u128::from(shares))?;
+    Ok(u64::try_from(price).unwrap_or(u64::MAX))
+}
 
-    let term1 = quotient.checked_mul(b)
-        .ok_or_else(|| format!("AMM overflow: quotient={} * b={}", quotient, b))?;
+/// Value of `shares` at `price_bps` basis points of a token each, rounded down.
+fn order_cost(shares: Amount, price_bps: u64) -> Result<Amount, String> {
+    Ok(Amount::from_attos(safe_mul_div(u128::from(shares), u128::from(price_bps), 10_000)?))
+}
 
-    let term2 = match remainder.checked_mul(b) {
-        Some(rem_product) => rem_product / c,
-        None => {
-            let b_div_c = b / c;
-            let b_mod_c = b % c;
-            let sub1 = remainder.checked_mul(b_div_c).unwrap_or_else(|| {
-                let scale = 1_000_000_000u128;
-                (remainder / scale) * (b_div_c / scale) * scale
-            });
-            let sub2 = match remainder.checked_mul(b_mod_c) {
-                Some(p) => p / c,
-                None => {
-                    let scale = 1_000_000_000u128;
-                    (remainder / scale) * (b_mod_c / scale) / (c / (scale * scale)).max(1)
-                }
+/// Record a fill of `shares` against an order, releasing `cost` tokens of a
+/// buy's escrow or the matching shares and cost basis of a sell's. Returns
+/// the cost basis a sell released.
+fn settle_fill(order: &mut state::LimitOrder, shares: Amount, cost: Amount) -> Result<Amount, String> {
+    let relief = match order.side {
+        OrderSide::Buy => {
+            order.escrow = order.escrow.saturating_sub(cost);
+            Amount::ZERO
+        }
+        OrderSide::Sell => {
+            let relief = if order.escrow == Amount::ZERO {
+                order.escrowed_cost_basis
+            } else {
+                let sold = u128::from(shares).min(u128::from(order.escrow));
+                Amount::from_attos(safe_mul_div(u128::from(order.escrowed_cost_basis), sold, u128::from(order.escrow))?)
             };
-            sub1 + sub2
+            order.escrow = order.escrow.saturating_sub(shares);
+            order.escrowed_cost_basis = order.escrowed_cost_basis.saturating_sub(relief);
+            relief
         }
     };
+    order.filled_shares = order.filled_shares.saturating_add(shares);
+    if order.filled_shares >= order.shares {
+        // Rounding dust left in a buy's escrow goes back with the last fill
+        order.status = OrderStatus::Filled;
+        order.escrow = Amount::ZERO;
+        order.escrowed_cost_basis = Amount::ZERO;
+    } else {
+        order.status = OrderStatus::PartiallyFilled;
+    }
+    Ok(relief)
+}
+
+/// Reject an operation whose deadline is before the block time `now`.
+fn check_deadline(deadline: Option<Timestamp>, now: Timestamp) -> Result<(), String> {
+    match deadline {
+        Some(deadline) if now > deadline => Err(MarketError::DeadlineExpired { deadline_micros: deadline.micros() }.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Pay out the fees `lp` has accrued and move its checkpoint to the current
+/// accumulator. Must run before any change to `lp.shares`, so liquidity
+/// removal settles pending fees first. Returns the amount paid.
+fn settle_lp_fees(market: &mut state::Market, lp: &mut state::LpPosition) -> Amount {
+    let owed = market.pending_lp_fees(lp);
+    lp.fee_checkpoint = market.fees_per_lp_share;
+    market.fees_distributed = market.fees_distributed.saturating_add(owed);
+    owed
+}
+
+/// The distinct lowercased keys `categories` are indexed and counted under
+fn category_keys(categories: &[String]) -> Vec<String> {
+    let mut keys: Vec<String> = categories.iter().map(|c| c.to_lowercase()).collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
 
-    Ok(term1 + term2)
+/// Check an instantiation config, returning a description of the first bad value.
+fn validate_config(config: &Config) -> Result<(), String> {
+    if config.fee_bps > 10_000 {
+        return Err(format!("fee_bps {} exceeds 10000 (100%)", config.fee_bps));
+    }
+    let zero_admin = match config.admin {
+        AccountOwner::Reserved(_) => true,
+        AccountOwner::Address32(hash) => hash == [0u8; 32].into(),
+        AccountOwner::Address20(bytes) => bytes == [0u8; 20],
+    };
+    if zero_admin {
+        return Err("admin must be a real account, not a reserved or zero address".to_string());
+    }
+    if config.admin_resolution_delay_secs > config.resolution_grace_period_secs {
+        return Err(format!(
+            "admin_resolution_delay_secs {} exceeds resolution_grace_period_secs {}",
+            config.admin_resolution_delay_secs, config.resolution_grace_period_secs
+        ));
+    }
+    let decimals = config.share_decimals();
+    if decimals > ATTO_DECIMALS {
+        return Err(format!("share_decimals {} exceeds {}", decimals, ATTO_DECIMALS));
+    }
+    if shares_to_units(config.min_trade_shares.into(), decimals).is_none() {
+        return Err(format!(
+            "min_trade_shares {} has more than share_decimals {} decimal places",
+            config.min_trade_shares, decimals
+        ));
+    }
+    if let Some(limit) = config.max_probability_move_bps {
+        if limit == 0 || limit > 10_000 {
+            return Err(format!("max_probability_move_bps {} must be between 1 and 10000", limit));
+        }
+    }
+    if config.maker_rebate_bps() > 10_000 {
+        return Err(format!("maker_rebate_bps {} exceeds 10000 (100%)", config.maker_rebate_bps()));
+    }
+    if config.bond_trader_share_bps() > 10_000 {
+        return Err(format!("bond_trader_share_bps {} exceeds 10000 (100%)", config.bond_trader_share_bps()));
+    }
+    for (i, tier) in config.fee_tiers.iter().enumerate() {
+        if tier.discount_bps > 10_000 {
+            return Err(format!("fee tier discount_bps {} exceeds 10000 (100%)", tier.discount_bps));
+        }
+        if i > 0 && tier.min_volume <= config.fee_tiers[i - 1].min_volume {
+            return Err(format!("fee tier min_volume {} does not exceed the tier before it", tier.min_volume));
+        }
+    }
+    if config.keeper_fee() > MAX_KEEPER_FEE {
+        return Err(format!("keeper_fee {} exceeds {}", config.keeper_fee(), MAX_KEEPER_FEE));
+    }
+    let capacity = config.recent_trades_capacity();
+    if capacity == 0 || capacity > MAX_RECENT_TRADES_CAPACITY {
+        return Err(format!(
+            "recent_trades_capacity {} must be between 1 and {}",
+            capacity, MAX_RECENT_TRADES_CAPACITY
+        ));
+    }
+    Ok(())
 }
 
 linera_sdk::contract!(MarketContract);
@@ -67,10 +335,10 @@ impl WithContractAbi for MarketContract {
 }
 
 impl Contract for MarketContract {
-    type Message = ();
-    type InstantiationArgument = ();
-    type Parameters = ();
-    type EventValue = ();
+    type Message = Message;
+    type InstantiationArgument = InstantiationArgument;
+    type Parameters = MarketParameters;
+    type EventValue = MarketEvent;
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
         let state = MarketState::load(runtime.root_view_storage_context())
@@ -79,14 +347,28 @@ impl Contract for MarketContract {
         MarketContract { state, runtime }
     }
 
-    async fn instantiate(&mut self, _argument: ()) {
+    async fn instantiate(&mut self, argument: InstantiationArgument) {
         self.runtime.application_parameters();
+        // Panicking here aborts the instantiation
+        if let Err(e) = validate_config(&argument) {
+            panic!("Invalid instantiation argument: {}", e);
+        }
+        self.state.config.set(argument);
         self.state.next_market_id.set(0);
-        self.state.next_order_id.set(0);
+        self.state.next_series_id.set(0);
         self.state.next_combo_id.set(0);
         self.state.next_agent_id.set(0);
         self.state.next_feed_id.set(0);
         self.state.total_volume.set(Amount::ZERO);
+        self.state.unique_traders.set(0);
+        self.state.total_value_locked.set(Amount::ZERO);
+        self.state.total_open_interest.set(Amount::ZERO);
+        self.state.total_active_liquidity.set(Amount::ZERO);
+        self.state.contract_balance.set(Amount::ZERO);
+        self.state.active_market_count.set(0);
+        self.state.closed_market_count.set(0);
+        self.state.resolved_market_count.set(0);
+        self.state.voided_market_count.set(0);
     }
 
     /// Execute an operation. Returns a String response.
@@ -94,14 +376,114 @@ impl Contract for MarketContract {
     /// On error: a string starting with "ERROR:" describing the failure.
     /// This function NEVER panics — all errors are caught and returned as strings.
     async fn execute_operation(&mut self, operation: Self::Operation) -> Self::Response {
-        match self.execute_operation_inner(operation).await {
+        let first_new_market = *self.state.next_market_id.get();
+        let group_id = match &operation {
+            Operation::ResolveGroup { group_id, .. } => Some(*group_id),
+            _ => None,
+        };
+        // A parlay or batch resolution is listed under every market it names
+        let leg_markets: Vec<u64> = match &operation {
+            Operation::BuyParlay { legs, .. } => legs.iter().map(|leg| leg.market_id).collect(),
+            Operation::ResolveMany { resolutions } => resolutions.iter().map(|resolution| resolution.market_id).collect(),
+            _ => Vec::new(),
+        };
+        // Upkeep operations may run anonymously and act for the chain
+        let caller = match self.authenticated_caller() {
+            Some(caller) => caller,
+            None if !operation.requires_authentication() => AccountOwner::CHAIN,
+            None => return format!("ERROR: {}", MarketError::Unauthenticated),
+        };
+        let trades_before = match operation.market_id() {
+            Some(market_id) => match self.trade_count(market_id).await {
+                Ok(count) => count,
+                Err(e) => return format!("ERROR: {}", e),
+            },
+            None => 0,
+        };
+        let entry = state::ActivityEntry {
+            kind: operation.kind().to_string(),
+            market_id: operation.market_id(),
+            actor: caller,
+            amount: operation.amount(),
+            shares: operation.shares(),
+            result: String::new(),
+            timestamp: self.runtime.system_time(),
+            is_yes: operation.is_yes(),
+            yes_price_bps: None,
+            trade_seq: None,
+            position_after: None,
+        };
+        let result = match self.execute_operation_inner(operation, caller).await {
             Ok(response) => response,
+            Err(e) => return format!("ERROR: {}", e),
+        };
+        let entry = state::ActivityEntry { result: result.clone(), ..entry };
+        match self.record_activity(entry, first_new_market, group_id, leg_markets, trades_before).await {
+            Ok(()) => result,
             Err(e) => format!("ERROR: {}", e),
         }
     }
 
-    async fn execute_message(&mut self, _message: ()) {
-        // Messages not supported
+    async fn execute_message(&mut self, message: Message) {
+        match message {
+            Message::MarketAnnouncement {
+                market_id,
+                origin_chain,
+                market_key,
+                question,
+                end_time,
+                categories,
+            } => {
+                // Only the configured registry keeps announcements
+                let registry = self.runtime.application_parameters().registry_chain;
+                if registry != Some(self.runtime.chain_id()) {
+                    return;
+                }
+                // Keyed by (chain, market_id), so redelivery just rewrites the same entry
+                let remote = state::RemoteMarket {
+                    market_id,
+                    origin_chain,
+                    question,
+                    end_time,
+                    categories,
+                    market_key: Some(market_key),
+                };
+                self.state.remote_markets.insert(&(origin_chain, market_id), remote)
+                    .expect("Failed to store market announcement");
+            }
+
+            Message::PositionTransfer { transfer_id, market_chain, market_key, position, expires_at } => {
+                if self.runtime.message_is_bouncing() == Some(true) {
+                    // The destination rejected the message outright. A
+                    // restore that fails leaves the transfer outstanding
+                    // for the next answer to retry, rather than trapping.
+                    let _ = self.restore_transfer(transfer_id).await;
+                    return;
+                }
+                let origin = self.runtime.message_origin_chain_id().expect("Messages come from a chain");
+                // A transfer that cannot be credited is refused, so the
+                // sender restores the position instead of the block failing
+                let refusal = self.receive_transfer(origin, transfer_id, market_chain, market_key, position, expires_at)
+                    .await
+                    .unwrap_or_else(|error| Some(format!("could not be credited: {}", error)));
+                // Answer redeliveries too, in case the first answer was lost
+                let answer = match refusal {
+                    None => Message::TransferAccepted { transfer_id },
+                    Some(reason) => Message::TransferRefused { transfer_id, reason },
+                };
+                self.runtime.prepare_message(answer).send_to(origin);
+            }
+
+            // Answers that cannot be applied leave the transfer outstanding
+            // rather than trapping; a redelivered answer applies them
+            Message::TransferAccepted { transfer_id } => {
+                let _ = self.state.outgoing_transfers.remove(&transfer_id);
+            }
+
+            Message::TransferRefused { transfer_id, .. } => {
+                let _ = self.restore_transfer(transfer_id).await;
+            }
+        }
     }
 
     async fn store(mut self) {
@@ -110,12 +492,21 @@ impl Contract for MarketContract {
 }
 
 impl MarketContract {
+    /// Who the operation acts for: the calling application when another
+    /// application composes with the market, else the block's signer.
+    /// Positions, orders and fees are attributed to this owner.
+    fn authenticated_caller(&mut self) -> Option<AccountOwner> {
+        self.runtime
+            .authenticated_caller_id()
+            .map(AccountOwner::from)
+            .or_else(|| self.runtime.authenticated_signer())
+    }
+
     /// Inner implementation that uses Result for clean error propagation.
-    async fn execute_operation_inner(&mut self, operation: Operation) -> Result<String, String> {
+    /// `caller` is the authenticated owner, or the chain for upkeep
+    /// operations run anonymously.
+    async fn execute_operation_inner(&mut self, operation: Operation, caller: AccountOwner) -> Result<String, String> {
         let timestamp = self.runtime.system_time();
-        let caller = self.runtime
-            .authenticated_signer()
-            .ok_or_else(|| "Operation must be authenticated — no signer found".to_string())?;
 
         match operation {
             // === MARKET OPERATIONS ===
@@ -124,38 +515,242 @@ impl MarketContract {
                 categories,
                 end_time,
                 initial_liquidity,
+                early_resolution_allowed,
+                max_position_per_account,
+                max_position,
+                creator_trading_disabled,
+                fee_ramp,
+                description,
+                resolution_source,
+                parent,
+                resolvers,
+                threshold,
+                max_probability_move_bps,
+                max_trade_shares,
             } => {
-                let market_id = *self.state.next_market_id.get();
-                self.state.next_market_id.set(market_id + 1);
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                let new_market = NewMarket {
+                    question,
+                    categories,
+                    end_time,
+                    initial_liquidity,
+                    early_resolution_allowed,
+                    max_position_per_account,
+                    max_position,
+                    creator_trading_disabled,
+                    fee_ramp,
+                    description: description.unwrap_or_default(),
+                    resolution_source: resolution_source.unwrap_or_default(),
+                    parent,
+                    group_id: None,
+                    resolvers,
+                    threshold,
+                    max_probability_move_bps,
+                    max_trade_shares,
+                };
+                self.check_new_market(&new_market, timestamp)?;
+                if let Some(condition) = parent {
+                    let parent_id = condition.market_id;
+                    let parent = self.state.markets.get(&parent_id)
+                        .await
+                        .map_err(|e| format!("Failed to read market {}: {}", parent_id, e))?
+                        .ok_or(MarketError::ParentNotFound { parent_id })?;
+                    if parent.resolved {
+                        return Err(MarketError::ParentResolved { parent_id }.into());
+                    }
+                }
+                let (market_id, market_key) = self.open_market(caller, new_market, timestamp).await?;
+                let bond = self.state.config.get().creator_bond();
+                if bond > Amount::ZERO {
+                    let bond = state::CreatorBond {
+                        market_id,
+                        creator: caller,
+                        amount: bond,
+                        status: BondStatus::Held,
+                        to_treasury: Amount::ZERO,
+                        to_traders: Amount::ZERO,
+                        settled_at: None,
+                    };
+                    self.adjust_contract_balance(bond.amount, true);
+                    self.state.creator_bonds.insert(&market_id, bond)
+                        .map_err(|e| format!("Failed to insert creator bond: {}", e))?;
+                }
+
+                Ok(format!("MarketCreated:{}:{}", market_id, market_key))
+            }
+
+            Operation::CreateMarketSeries { template, interval_secs, count } => {
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                if count == 0 {
+                    return Err("A series needs at least one market".to_string());
+                }
+                if interval_secs < template.duration_secs {
+                    return Err(format!(
+                        "Series interval {}s is shorter than each market's duration {}s",
+                        interval_secs, template.duration_secs
+                    ));
+                }
+                let escrow = u128::from(template.liquidity_per_instance)
+                    .checked_mul(u128::from(count))
+                    .map(Amount::from_attos)
+                    .ok_or_else(|| "Series liquidity overflows".to_string())?;
+                let first = series_market(&template, timestamp);
+                self.check_new_market(&first, timestamp)?;
+
+                let series_id = allocate_id(&mut self.state.next_series_id)
+                    .ok_or(MarketError::IdsExhausted { kind: "series" })?;
+
+                let (market_id, _) = self.open_market(caller, first, timestamp).await?;
+                let held_back = escrow.saturating_sub(template.liquidity_per_instance);
+                self.adjust_contract_balance(held_back, true);
+                let series = state::MarketSeries {
+                    id: series_id,
+                    creator: caller,
+                    escrow: held_back,
+                    template,
+                    interval_secs,
+                    count,
+                    created_at: timestamp,
+                    spawned: vec![market_id],
+                };
+                self.state.series.insert(&series_id, series)
+                    .map_err(|e| format!("Failed to insert series: {}", e))?;
+
+                Ok(format!("MarketSeriesCreated:{}:{}", series_id, market_id))
+            }
 
-                let liq_attos = u128::from(initial_liquidity);
-                if liq_attos == 0 {
-                    return Err("Initial liquidity must be greater than zero".to_string());
+            Operation::SpawnNextInSeries { series_id } => {
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
                 }
-                let half = Amount::from_attos(liq_attos / 2);
+                let mut series = self.state.series.get(&series_id)
+                    .await
+                    .map_err(|e| format!("Failed to read series {}: {}", series_id, e))?
+                    .ok_or_else(|| format!("Series {} not found", series_id))?;
 
-                let market = state::Market {
-                    id: market_id,
+                let spawned = series.spawned.len() as u64;
+                if spawned >= series.count {
+                    return Err(MarketError::SeriesComplete { series_id, count: series.count }.into());
+                }
+                let previous_id = *series.spawned.last().ok_or_else(|| format!("Series {} has no markets", series_id))?;
+                let previous = self.load_market(previous_id).await?;
+
+                // Both the previous market's end and this instance's slot must have passed
+                let slot = series.created_at
+                    .saturating_add(TimeDelta::from_secs(series.interval_secs.saturating_mul(spawned)));
+                let ready_at = slot.max(previous.end_time);
+                if timestamp <= previous.end_time || timestamp < slot {
+                    return Err(MarketError::SeriesNotReady { series_id, ready_at_micros: ready_at.micros() }.into());
+                }
+
+                let next = series_market(&series.template, timestamp);
+                self.check_new_market(&next, timestamp)?;
+                let (market_id, market_key) = self.open_market(series.creator, next, timestamp).await?;
+
+                // The liquidity moves from the series into the market, which
+                // open_market counted in the balance again
+                series.escrow = series.escrow.saturating_sub(series.template.liquidity_per_instance);
+                self.adjust_contract_balance(series.template.liquidity_per_instance, false);
+                series.spawned.push(market_id);
+                self.state.series.insert(&series_id, series)
+                    .map_err(|e| format!("Failed to update series: {}", e))?;
+
+                Ok(format!("MarketCreated:{}:{}", market_id, market_key))
+            }
+
+            Operation::CreateMarketGroup { title, members, end_time, liquidity_per_market } => {
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                if title.trim().is_empty() {
+                    return Err("Group title must not be empty".to_string());
+                }
+                if members.len() < 2 || members.len() > MAX_GROUP_MEMBERS {
+                    return Err(format!("A group needs between 2 and {} members, got {}", MAX_GROUP_MEMBERS, members.len()));
+                }
+                let group_id = *self.state.next_group_id.get();
+                let new_markets: Vec<NewMarket> = members.into_iter()
+                    .map(|member| NewMarket {
+                        question: member.question,
+                        categories: member.categories,
+                        end_time,
+                        initial_liquidity: liquidity_per_market,
+                        early_resolution_allowed: false,
+                        max_position_per_account: None,
+                        max_position: None,
+                        creator_trading_disabled: false,
+                        fee_ramp: None,
+                        description: String::new(),
+                        resolution_source: String::new(),
+                        parent: None,
+                        group_id: Some(group_id),
+                        resolvers: Vec::new(),
+                        threshold: 0,
+                        max_probability_move_bps: None,
+                        max_trade_shares: None,
+                    })
+                    .collect();
+                // Check every member before opening any, so a bad one opens none
+                for new_market in &new_markets {
+                    self.check_new_market(new_market, timestamp)?;
+                }
+
+                // Nor open some members before running out of market IDs
+                let members = new_markets.len() as u64;
+                if self.state.next_market_id.get().checked_add(members).is_none() {
+                    return Err(MarketError::IdsExhausted { kind: "market" }.into());
+                }
+                allocate_id(&mut self.state.next_group_id)
+                    .ok_or(MarketError::IdsExhausted { kind: "group" })?;
+                let mut market_ids = Vec::with_capacity(new_markets.len());
+                for new_market in new_markets {
+                    market_ids.push(self.open_market(caller, new_market, timestamp).await?.0);
+                }
+                let first_market_id = market_ids[0];
+                let group = state::MarketGroup {
+                    id: group_id,
                     creator: caller,
-                    question: question.clone(),
-                    categories: categories.clone(),
-                    end_time,
+                    title,
+                    market_ids,
                     created_at: timestamp,
-                    yes_pool: half,
-                    no_pool: half,
-                    total_yes_shares: half,
-                    total_no_shares: half,
-                    resolved: false,
-                    outcome: None,
-                    volume: Amount::ZERO,
+                    winner: None,
                 };
+                self.state.groups.insert(&group_id, group)
+                    .map_err(|e| format!("Failed to insert group: {}", e))?;
 
-                self.state.markets.insert(&market_id, market)
-                    .map_err(|e| format!("Failed to insert market: {}", e))?;
+                Ok(format!("MarketGroupCreated:{}:{}", group_id, first_market_id))
+            }
+
+            Operation::ResolveGroup { group_id, winner } => {
+                let mut group = self.state.groups.get(&group_id)
+                    .await
+                    .map_err(|e| format!("Failed to read group {}: {}", group_id, e))?
+                    .ok_or(MarketError::GroupNotFound { group_id })?;
+                if !group.market_ids.contains(&winner) {
+                    return Err(MarketError::NotGroupMember { market_id: winner, group_id }.into());
+                }
+
+                // Every member must be resolvable before any is settled
+                let mut members = Vec::with_capacity(group.market_ids.len());
+                for market_id in &group.market_ids {
+                    let market = self.load_market(*market_id).await?;
+                    let by_admin = self.check_resolver(&market, caller, timestamp)?;
+                    members.push((market, by_admin));
+                }
+                for (market, by_admin) in members {
+                    let outcome = market.id == winner;
+                    self.settle_market(market, outcome, caller, by_admin, None, timestamp).await?;
+                }
 
-                self.create_feed_item(caller, FeedItemType::MarketCreated, Some(market_id), question, timestamp).await?;
+                group.winner = Some(winner);
+                self.state.groups.insert(&group_id, group)
+                    .map_err(|e| format!("Failed to update group: {}", e))?;
 
-                Ok(format!("MarketCreated:{}", market_id))
+                Ok(format!("GroupResolved:{}", winner))
             }
 
             Operation::BuyShares {
@@ -163,21 +758,18 @@ impl MarketContract {
                 is_yes,
                 shares,
                 max_cost,
+                deadline,
+                max_price_impact_bps,
             } => {
-                let mut market = self.state.markets.get(&market_id)
-                    .await
-                    .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
-                    .ok_or_else(|| format!("Market {} not found", market_id))?;
-
-                if market.resolved {
-                    return Err(format!("Market {} is already resolved", market_id));
-                }
-                if timestamp > market.end_time {
-                    return Err(format!(
-                        "Market {} has ended (now={}, end={})",
-                        market_id, timestamp.micros(), market.end_time.micros()
-                    ));
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
                 }
+                check_deadline(deadline, timestamp)?;
+                let mut market = self.load_market(market_id).await?;
+
+                check_open(&market, timestamp)?;
+
+                self.state.config.get().check_trade_shares(&market, shares)?;
 
                 let (pool_in, pool_out) = if is_yes {
                     (market.no_pool, market.yes_pool)
@@ -187,23 +779,55 @@ impl MarketContract {
 
                 let pi = u128::from(pool_in);
                 let po = u128::from(pool_out);
-                let s = u128::from(shares);
 
-                if s == 0 {
-                    return Err("Shares amount must be greater than zero".to_string());
+                // Resting asks cheaper than the AMM's marginal price fill
+                // first, each charged the trading fee on its value
+                let fee_bps = self.trader_fee_bps(&market, caller, timestamp).await?;
+                let amm_price_bps = amm_buy_price_bps(pi, po)?;
+                let fills = self
+                    .match_orders(market_id, is_yes, OrderSide::Sell, caller, shares, |price| price < amm_price_bps)
+                    .await?;
+                let mut book_shares = Amount::ZERO;
+                let mut book_cost = Amount::ZERO;
+                let mut book_fee = Amount::ZERO;
+                for (order, fill) in &fills {
+                    let value = order_cost(*fill, order.price_bps)?;
+                    book_shares = book_shares.saturating_add(*fill);
+                    book_cost = book_cost.saturating_add(value);
+                    book_fee = book_fee.saturating_add(self.trading_fee(value, fee_bps)?);
                 }
+
+                let amm_shares = shares.saturating_sub(book_shares);
+                let s = u128::from(amm_shares);
                 if s >= po {
                     return Err(format!(
                         "Not enough liquidity: requested {} shares but pool only has {} (pool_in={}, pool_out={})",
-                        shares, pool_out, pool_in, pool_out
+                        amm_shares, pool_out, pool_in, pool_out
                     ));
                 }
+                // Measured on the AMM leg only, as the quote is
+                let impact_bps = price_impact_bps(po, s)?;
+                if let Some(max_bps) = max_price_impact_bps {
+                    if impact_bps > max_bps {
+                        return Err(MarketError::PriceImpactExceeded { impact_bps, max_bps }.into());
+                    }
+                }
 
-                let denominator = po - s;
-
-                // cost = pool_in * shares / (pool_out - shares)
-                let cost_attos = safe_mul_div(pi, s, denominator)?;
-                let cost = Amount::from_attos(cost_attos);
+                let amm_cost = Amount::from_attos(cost_to_buy(pi, po, s)?);
+                // Book fills trade at resting prices and leave the pools alone
+                if let Some(allowed_bps) = market.probability_move_limit_bps(self.state.config.get()) {
+                    let attempted_bps = probability_move_bps(pi, po, s, amm_cost.into())?;
+                    if attempted_bps > allowed_bps {
+                        return Err(MarketError::MaxImpactExceeded { market_id, allowed_bps, attempted_bps }.into());
+                    }
+                }
+                let amm_fee = self.trading_fee(amm_cost, fee_bps)?;
+                let fee = amm_fee.saturating_add(book_fee);
+                let cost = book_cost.saturating_add(amm_cost).saturating_add(fee);
+                // Rounding must never hand out shares for free
+                if cost == Amount::ZERO {
+                    return Err(MarketError::ZeroCostTrade { shares }.into());
+                }
 
                 if cost > max_cost {
                     return Err(format!(
@@ -212,543 +836,3736 @@ impl MarketContract {
                     ));
                 }
 
+                let (yes_shares, no_shares) = if is_yes { (shares, Amount::ZERO) } else { (Amount::ZERO, shares) };
+                self.check_position_limits(&market, caller, yes_shares, no_shares).await?;
+
+                market.accumulate_price(timestamp);
+                let pools_before = (market.yes_pool, market.no_pool);
                 if is_yes {
-                    market.no_pool = market.no_pool.saturating_add(cost);
-                    market.yes_pool = market.yes_pool.saturating_sub(shares);
-                    market.total_yes_shares = market.total_yes_shares.saturating_add(shares);
+                    market.no_pool = market.no_pool.saturating_add(amm_cost);
+                    market.yes_pool = market.yes_pool.saturating_sub(amm_shares);
+                    market.total_yes_shares = market.total_yes_shares.saturating_add(amm_shares);
                 } else {
-                    market.yes_pool = market.yes_pool.saturating_add(cost);
-                    market.no_pool = market.no_pool.saturating_sub(shares);
-                    market.total_no_shares = market.total_no_shares.saturating_add(shares);
+                    market.yes_pool = market.yes_pool.saturating_add(amm_cost);
+                    market.no_pool = market.no_pool.saturating_sub(amm_shares);
+                    market.total_no_shares = market.total_no_shares.saturating_add(amm_shares);
                 }
+                check_pool_product(&market, pools_before)?;
 
-                market.volume = market.volume.saturating_add(cost);
-                self.state.markets.insert(&market_id, market.clone())
-                    .map_err(|e| format!("Failed to update market: {}", e))?;
-
-                let total = *self.state.total_volume.get();
-                self.state.total_volume.set(total.saturating_add(cost));
+                let mut lp_fee = amm_fee;
+                for (order, fill) in fills {
+                    let filled = self.fill_resting_order(order, fill, caller, fee_bps, timestamp).await?;
+                    lp_fee = lp_fee.saturating_add(filled.lp_fee);
+                }
 
-                self.update_position(caller, market_id, is_yes, shares, true).await?;
+                // Book fills trade existing shares, so only the AMM leg adds
+                // collateral; the LPs get its fee and what the makers'
+                // rebates leave of the book fees
+                let traded = book_cost.saturating_add(amm_cost);
+                market.volume = market.volume.saturating_add(traded);
+                market.escrow = market.escrow.saturating_add(amm_cost);
+                market.accrue_fee(lp_fee);
+                let new_yes_price_bps = market.yes_price_bps();
+                let new_no_price_bps = market.no_price_bps();
+                let (yes_pool, no_pool) = (market.yes_pool, market.no_pool);
+                let question = market.question.clone();
+                self.record_price_checkpoint(&market).await?;
+                self.save_market(market)?;
+
+                self.record_volume(market_id, traded, timestamp).await?;
+                self.record_recent_trade(RecentTrade {
+                    market_id,
+                    question,
+                    trader: caller,
+                    side: OrderSide::Buy,
+                    is_yes,
+                    shares,
+                    amount: cost,
+                    yes_price_bps: new_yes_price_bps,
+                    timestamp,
+                });
+                self.adjust_tvl(amm_cost, true);
+                self.adjust_open_interest(amm_cost, true);
+                self.adjust_contract_balance(amm_cost.saturating_add(fee), true);
+                self.move_active_liquidity(pools_before, (yes_pool, no_pool));
+                self.record_trader(caller, market_id, traded).await?;
+
+                self.update_position(caller, market_id, is_yes, shares, cost, true).await?;
 
                 let content = format!("Bought {} {} shares", shares, if is_yes { "YES" } else { "NO" });
                 self.create_feed_item(caller, FeedItemType::Trade, Some(market_id), content, timestamp).await?;
 
-                Ok(format!("SharesPurchased:{}", cost))
+                if amm_shares > Amount::ZERO {
+                    let position = self.position_or_empty(caller, market_id).await?;
+                    let trade_seq = self.next_trade_seq(market_id).await?;
+                    self.emit_event(MarketEvent::TradeExecuted {
+                        market_id,
+                        trader: caller,
+                        side: OrderSide::Buy,
+                        is_yes,
+                        shares: amm_shares,
+                        cost: amm_cost,
+                        fee: amm_fee,
+                        fee_bps,
+                        yes_pool,
+                        no_pool,
+                        new_yes_price_bps,
+                        trade_seq,
+                        position_after: if is_yes { position.yes_shares } else { position.no_shares },
+                        deadline,
+                        timestamp,
+                    });
+                }
+
+                Ok(format!(
+                    "SharesPurchased:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+                    cost, shares, new_yes_price_bps, new_no_price_bps, yes_pool, no_pool, fee, fee_bps, impact_bps
+                ))
             }
 
-            Operation::SellShares {
-                market_id,
-                is_yes,
-                shares,
-                min_proceeds,
-            } => {
-                let mut market = self.state.markets.get(&market_id)
-                    .await
-                    .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
-                    .ok_or_else(|| format!("Market {} not found", market_id))?;
+            Operation::BuyStraddle { market_id, yes_shares, no_shares, max_total_cost } => {
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                let mut market = self.load_market(market_id).await?;
 
-                if market.resolved {
-                    return Err(format!("Market {} is already resolved", market_id));
+                check_open(&market, timestamp)?;
+                for shares in [yes_shares, no_shares] {
+                    self.state.config.get().check_trade_shares(&market, shares)?;
                 }
 
-                let (pool_in, pool_out) = if is_yes {
-                    (market.yes_pool, market.no_pool)
-                } else {
-                    (market.no_pool, market.yes_pool)
-                };
+                let fee_bps = self.trader_fee_bps(&market, caller, timestamp).await?;
+                let move_limit_bps = market.probability_move_limit_bps(self.state.config.get());
+
+                // Legs apply to this copy of the market in order; nothing is
+                // stored until both have passed every check
+                market.accumulate_price(timestamp);
+                let pools_before = (market.yes_pool, market.no_pool);
+                let mut legs = Vec::with_capacity(2);
+                for (is_yes, shares) in [(true, yes_shares), (false, no_shares)] {
+                    let (amm_cost, fee) = self.buy_from_amm(&mut market, is_yes, shares, fee_bps, move_limit_bps)?;
+                    legs.push((is_yes, shares, amm_cost, fee, market.yes_pool, market.no_pool, market.yes_price_bps()));
+                }
 
-                let po = u128::from(pool_out);
-                let pi = u128::from(pool_in);
-                let s = u128::from(shares);
+                // Each leg's cost includes its fee, as on BuyShares
+                let yes_cost = legs[0].2.saturating_add(legs[0].3);
+                let no_cost = legs[1].2.saturating_add(legs[1].3);
+                let total_cost = yes_cost.saturating_add(no_cost);
+                if total_cost > max_total_cost {
+                    return Err(format!(
+                        "Cost {} exceeds max_total_cost {} (yes_cost={}, no_cost={})",
+                        total_cost, max_total_cost, yes_cost, no_cost
+                    ));
+                }
+                check_pool_product(&market, pools_before)?;
+                self.check_position_limits(&market, caller, yes_shares, no_shares).await?;
+
+                let new_yes_price_bps = market.yes_price_bps();
+                let new_no_price_bps = market.no_price_bps();
+                let traded = legs.iter().fold(Amount::ZERO, |sum, leg| sum.saturating_add(leg.2));
+                let fees = legs.iter().fold(Amount::ZERO, |sum, leg| sum.saturating_add(leg.3));
+                let question = market.question.clone();
+                let pools_after = (market.yes_pool, market.no_pool);
+                self.record_price_checkpoint(&market).await?;
+                self.save_market(market)?;
+
+                self.record_volume(market_id, traded, timestamp).await?;
+                self.adjust_tvl(traded, true);
+                self.adjust_open_interest(traded, true);
+                self.adjust_contract_balance(traded.saturating_add(fees), true);
+                self.move_active_liquidity(pools_before, pools_after);
+                self.record_trader(caller, market_id, traded).await?;
+
+                let mut position = self.position_or_empty(caller, market_id).await?;
+                position.yes_shares = position.yes_shares.saturating_add(yes_shares);
+                position.yes_cost_basis = position.yes_cost_basis.saturating_add(yes_cost);
+                position.no_shares = position.no_shares.saturating_add(no_shares);
+                position.no_cost_basis = position.no_cost_basis.saturating_add(no_cost);
+                let held = (position.yes_shares, position.no_shares);
+                self.save_position(position).await?;
+
+                for (is_yes, shares, amm_cost, fee, yes_pool, no_pool, yes_price_bps) in legs {
+                    self.record_recent_trade(RecentTrade {
+                        market_id,
+                        question: question.clone(),
+                        trader: caller,
+                        side: OrderSide::Buy,
+                        is_yes,
+                        shares,
+                        amount: amm_cost.saturating_add(fee),
+                        yes_price_bps,
+                        timestamp,
+                    });
+                    let trade_seq = self.next_trade_seq(market_id).await?;
+                    self.emit_event(MarketEvent::TradeExecuted {
+                        market_id,
+                        trader: caller,
+                        side: OrderSide::Buy,
+                        is_yes,
+                        shares,
+                        cost: amm_cost,
+                        fee,
+                        fee_bps,
+                        yes_pool,
+                        no_pool,
+                        new_yes_price_bps: yes_price_bps,
+                        trade_seq,
+                        position_after: if is_yes { held.0 } else { held.1 },
+                        deadline: None,
+                        timestamp,
+                    });
+                }
+
+                let content = format!("Bought {} YES and {} NO shares", yes_shares, no_shares);
+                self.create_feed_item(caller, FeedItemType::Trade, Some(market_id), content, timestamp).await?;
+
+                Ok(format!(
+                    "StraddlePurchased:{}:{}:{}:{}:{}:{}:{}:{}",
+                    total_cost,
+                    yes_cost,
+                    no_cost,
+                    average_price_bps(yes_cost, yes_shares)?,
+                    average_price_bps(no_cost, no_shares)?,
+                    new_yes_price_bps,
+                    new_no_price_bps,
+                    fees
+                ))
+            }
 
-                if s == 0 {
-                    return Err("Shares amount must be greater than zero".to_string());
+            Operation::BuyParlay { legs, max_total_cost } => {
+                if *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                if legs.len() < 2 || legs.len() > MAX_PARLAY_LEGS {
+                    return Err(MarketError::ParlayLegCount { count: legs.len(), max: MAX_PARLAY_LEGS }.into());
                 }
 
-                let new_pool_in = pi + s;
-                let proceeds_attos = safe_mul_div(po, s, new_pool_in)?;
-                let proceeds = Amount::from_attos(proceeds_attos);
+                // Every leg is priced on its own copy of its market; nothing
+                // is stored until all of them and the total have passed
+                let config = self.state.config.get().clone();
+                let mut priced = Vec::with_capacity(legs.len());
+                for (index, leg) in legs.iter().enumerate() {
+                    if legs[..index].iter().any(|earlier| earlier.market_id == leg.market_id) {
+                        return Err(MarketError::DuplicateParlayMarket { market_id: leg.market_id }.into());
+                    }
+                    let mut market = self.load_market(leg.market_id).await?;
+                    check_open(&market, timestamp)?;
+                    self.state.config.get().check_trade_shares(&market, leg.shares)?;
+
+                    let fee_bps = self.trader_fee_bps(&market, caller, timestamp).await?;
+                    let move_limit_bps = market.probability_move_limit_bps(&config);
+                    market.accumulate_price(timestamp);
+                    let pools_before = (market.yes_pool, market.no_pool);
+                    let (amm_cost, fee) = self.buy_from_amm(&mut market, leg.is_yes, leg.shares, fee_bps, move_limit_bps)?;
+                    check_pool_product(&market, pools_before)?;
+                    let (yes_shares, no_shares) = if leg.is_yes { (leg.shares, Amount::ZERO) } else { (Amount::ZERO, leg.shares) };
+                    self.check_position_limits(&market, caller, yes_shares, no_shares).await?;
+                    priced.push((market, pools_before, amm_cost, fee, fee_bps));
+                }
 
-                if proceeds < min_proceeds {
+                // Each leg's cost includes its fee, as on BuyShares
+                let costs: Vec<Amount> = priced.iter().map(|(_, _, amm_cost, fee, _)| amm_cost.saturating_add(*fee)).collect();
+                let total_cost = costs.iter().fold(Amount::ZERO, |sum, cost| sum.saturating_add(*cost));
+                if total_cost > max_total_cost {
                     return Err(format!(
-                        "Proceeds {} below minimum {} (pool_in={}, pool_out={}, shares={})",
-                        proceeds, min_proceeds, pool_in, pool_out, shares
+                        "Cost {} exceeds max_total_cost {} across {} legs",
+                        total_cost, max_total_cost, legs.len()
                     ));
                 }
 
-                if is_yes {
-                    market.yes_pool = market.yes_pool.saturating_add(shares);
-                    market.no_pool = market.no_pool.saturating_sub(proceeds);
-                    market.total_yes_shares = market.total_yes_shares.saturating_sub(shares);
-                } else {
-                    market.no_pool = market.no_pool.saturating_add(shares);
-                    market.yes_pool = market.yes_pool.saturating_sub(proceeds);
-                    market.total_no_shares = market.total_no_shares.saturating_sub(shares);
+                for (leg, (market, pools_before, amm_cost, fee, fee_bps)) in legs.into_iter().zip(priced) {
+                    let market_id = market.id;
+                    let cost = amm_cost.saturating_add(fee);
+                    let (yes_pool, no_pool) = (market.yes_pool, market.no_pool);
+                    let new_yes_price_bps = market.yes_price_bps();
+                    let question = market.question.clone();
+                    self.record_price_checkpoint(&market).await?;
+                    self.save_market(market)?;
+
+                    self.record_volume(market_id, amm_cost, timestamp).await?;
+                    self.record_recent_trade(RecentTrade {
+                        market_id,
+                        question,
+                        trader: caller,
+                        side: OrderSide::Buy,
+                        is_yes: leg.is_yes,
+                        shares: leg.shares,
+                        amount: cost,
+                        yes_price_bps: new_yes_price_bps,
+                        timestamp,
+                    });
+                    self.adjust_tvl(amm_cost, true);
+                    self.adjust_open_interest(amm_cost, true);
+                    self.adjust_contract_balance(cost, true);
+                    self.move_active_liquidity(pools_before, (yes_pool, no_pool));
+                    self.record_trader(caller, market_id, amm_cost).await?;
+                    self.update_position(caller, market_id, leg.is_yes, leg.shares, cost, true).await?;
+
+                    let content = format!("Bought {} {} shares in a parlay", leg.shares, if leg.is_yes { "YES" } else { "NO" });
+                    self.create_feed_item(caller, FeedItemType::Trade, Some(market_id), content, timestamp).await?;
+
+                    let position = self.position_or_empty(caller, market_id).await?;
+                    let trade_seq = self.next_trade_seq(market_id).await?;
+                    self.emit_event(MarketEvent::TradeExecuted {
+                        market_id,
+                        trader: caller,
+                        side: OrderSide::Buy,
+                        is_yes: leg.is_yes,
+                        shares: leg.shares,
+                        cost: amm_cost,
+                        fee,
+                        fee_bps,
+                        yes_pool,
+                        no_pool,
+                        new_yes_price_bps,
+                        trade_seq,
+                        position_after: if leg.is_yes { position.yes_shares } else { position.no_shares },
+                        deadline: None,
+                        timestamp,
+                    });
                 }
 
-                market.volume = market.volume.saturating_add(proceeds);
-                self.state.markets.insert(&market_id, market)
-                    .map_err(|e| format!("Failed to update market: {}", e))?;
-
-                self.update_position(caller, market_id, is_yes, shares, false).await?;
-
-                Ok(format!("SharesSold:{}", proceeds))
+                let costs: Vec<String> = costs.iter().map(Amount::to_string).collect();
+                Ok(format!("ParlayPurchased:{}:{}", total_cost, costs.join(":")))
             }
 
-            Operation::ResolveMarket { market_id, outcome } => {
-                let mut market = self.state.markets.get(&market_id)
-                    .await
-                    .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
-                    .ok_or_else(|| format!("Market {} not found", market_id))?;
+            Operation::SellShares {
+                market_id,
+                is_yes,
+                shares,
+                min_proceeds,
+                deadline,
+            } => {
+                check_deadline(deadline, timestamp)?;
+                let sale = AmmSale { market_id, is_yes, shares, min_proceeds, deadline };
+                let sold = self.sell_to_amm(caller, sale, timestamp).await?;
+
+                Ok(format!(
+                    "SharesSold:{}:{}:{}:{}:{}:{}:{}:{}",
+                    sold.proceeds, shares, sold.new_yes_price_bps, sold.new_no_price_bps,
+                    sold.yes_pool, sold.no_pool, sold.fee, sold.fee_bps
+                ))
+            }
 
-                if market.resolved {
-                    return Err(format!("Market {} is already resolved", market_id));
-                }
-                if market.creator != caller {
-                    return Err("Not authorized: only the creator can resolve this market".to_string());
+            Operation::ClosePosition { market_id, is_yes, min_proceeds } => {
+                let position = self.position_or_empty(caller, market_id).await?;
+                let shares = if is_yes { position.yes_shares } else { position.no_shares };
+                if shares == Amount::ZERO {
+                    return Err(MarketError::NothingToClose { market_id, side: if is_yes { "YES" } else { "NO" } }.into());
                 }
+                let sale = AmmSale { market_id, is_yes, shares, min_proceeds, deadline: None };
+                let sold = self.sell_to_amm(caller, sale, timestamp).await?;
+
+                Ok(format!(
+                    "PositionClosed:{}:{}:{}:{}:{}:{}:{}:{}:{}",
+                    sold.proceeds, shares, sold.new_yes_price_bps, sold.new_no_price_bps,
+                    sold.yes_pool, sold.no_pool, sold.fee, sold.fee_bps, sold.realized_pnl
+                ))
+            }
 
-                market.resolved = true;
-                market.outcome = Some(outcome);
+            Operation::FinalizeExpired { limit } => {
+                let next_market_id = *self.state.next_market_id.get();
+                if next_market_id == 0 {
+                    return Ok("ExpiredFinalized:0:0".to_string());
+                }
 
-                self.state.markets.insert(&market_id, market)
-                    .map_err(|e| format!("Failed to update market: {}", e))?;
+                // Walk ids from the cursor on, wrapping around so repeated
+                // calls keep sweeping all of them, at most one lap a call.
+                // Ids of archived markets are skipped rather than counted
+                // against `limit`; each batch reads only the ids still needed.
+                let grace = TimeDelta::from_secs(self.state.config.get().resolution_grace_period_secs);
+                let mut cursor = *self.state.finalize_cursor.get() % next_market_id;
+                let (mut walked, mut visited, mut finalized) = (0u64, 0u64, 0u64);
+
+                while visited < limit && walked < next_market_id {
+                    let batch = (limit - visited).min(next_market_id - walked).min(next_market_id - cursor);
+                    let market_ids: Vec<u64> = (cursor..cursor + batch).collect();
+                    let markets = self.state.markets.multi_get(&market_ids)
+                        .await
+                        .map_err(|e| format!("Failed to read markets: {}", e))?;
+
+                    for (market_id, market) in market_ids.into_iter().zip(markets) {
+                        walked += 1;
+                        cursor = (market_id + 1) % next_market_id;
+                        let Some(mut market) = market else {
+                            continue;
+                        };
+                        visited += 1;
+                        if effective_status(&market, timestamp) != EffectiveStatus::Closed {
+                            continue;
+                        }
+
+                        // Ended markets close on the first sweep to reach them
+                        // and are voided once their grace period is over
+                        if timestamp >= market.end_time.saturating_add(grace) {
+                            // Nobody resolved it: the creator forfeits their bond
+                            self.void_market(&mut market, timestamp).await?;
+                            self.settle_bond(&market, true, timestamp).await?;
+                            finalized += 1;
+                        } else if !market.closed {
+                            self.close_market(&mut market, caller, timestamp);
+                        } else {
+                            continue;
+                        }
+                        market.upgrade();
+
+                        self.save_market(market)?;
+                    }
+                }
 
-                self.update_combos_for_market(market_id, outcome).await?;
+                self.state.finalize_cursor.set(cursor);
 
-                Ok("MarketResolved".to_string())
+                Ok(format!("ExpiredFinalized:{}:{}", finalized, cursor))
             }
 
-            Operation::ClaimWinnings { market_id } => {
-                let market = self.state.markets.get(&market_id)
-                    .await
-                    .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
-                    .ok_or_else(|| format!("Market {} not found", market_id))?;
+            Operation::CloseMarket { market_id } => {
+                let mut market = self.load_market(market_id).await?;
 
-                if !market.resolved {
-                    return Err(format!("Market {} is not yet resolved", market_id));
+                match effective_status(&market, timestamp) {
+                    EffectiveStatus::Resolved => return Err(MarketError::AlreadyResolved { market_id }.into()),
+                    EffectiveStatus::Open => {
+                        return Err(MarketError::CloseBeforeEnd { market_id, end_micros: market.end_time.micros() }.into());
+                    }
+                    EffectiveStatus::Closed if market.closed => return Err(MarketError::AlreadyClosed { market_id }.into()),
+                    EffectiveStatus::Closed => {}
                 }
+                self.close_market(&mut market, caller, timestamp);
+                self.save_market(market)?;
 
-                let position_key = (caller, market_id);
-                let mut position = self.state.positions.get(&position_key)
-                    .await
-                    .map_err(|e| format!("Failed to get position: {}", e))?
-                    .ok_or_else(|| "No position found for this market".to_string())?;
+                Ok(format!("MarketClosed:{}", market_id))
+            }
 
-                if position.claimed {
-                    return Err("Winnings already claimed".to_string());
+            // === ADMIN OPERATIONS ===
+            Operation::SetGlobalPause { paused } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can pause trading".to_string()).into());
                 }
+                self.state.paused.set(paused);
 
-                let winning_shares = match market.outcome {
-                    Some(true) => position.yes_shares,
-                    Some(false) => position.no_shares,
-                    None => return Err("Market outcome not set".to_string()),
-                };
+                Ok(format!("GlobalPauseSet:{}", paused))
+            }
 
-                if winning_shares == Amount::ZERO {
-                    return Err("No winning shares".to_string());
+            Operation::ReconcileBalance { deposit } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can reconcile the balance".to_string()).into());
                 }
+                if deposit == Amount::ZERO {
+                    return Err("Deposit must be greater than zero".to_string());
+                }
+                self.adjust_contract_balance(deposit, true);
+                adjust_total(&mut self.state.reconciled_deposits, deposit, true);
+                let balance = *self.state.contract_balance.get();
+                self.emit_event(MarketEvent::BalanceReconciled { admin: caller, deposit, balance, timestamp });
 
-                let total_winning_shares = if market.outcome == Some(true) {
-                    market.total_yes_shares
-                } else {
-                    market.total_no_shares
-                };
+                Ok(format!("BalanceReconciled:{}:{}", deposit, balance))
+            }
 
-                let total_pool = market.yes_pool.saturating_add(market.no_pool);
-                let payout = Amount::from_attos(
-                    safe_mul_div(u128::from(winning_shares), u128::from(total_pool), u128::from(total_winning_shares))?
-                );
+            // === CROSS-CHAIN POSITION OPERATIONS ===
+            Operation::MigratePosition { market_id, destination } => {
+                let chain_id = self.runtime.chain_id();
+                if destination == chain_id {
+                    return Err("A position cannot migrate to the chain it is on".to_string());
+                }
+                let market_key = self.load_market(market_id).await?.key;
+                let position = self.state.positions.get(&(caller, market_id))
+                    .await
+                    .map_err(|e| format!("Failed to get position: {}", e))?
+                    .filter(|p| !p.is_empty())
+                    .ok_or_else(|| format!("No position on market {} to migrate", market_id))?;
+                if position.claimed {
+                    return Err(format!("Position on market {} has already been claimed", market_id));
+                }
+                self.remove_position(caller, market_id).await?;
+                let transfer_id = self.send_position(chain_id, destination, market_key, position, timestamp)?;
 
-                position.claimed = true;
-                self.state.positions.insert(&position_key, position)
-                    .map_err(|e| format!("Failed to update position: {}", e))?;
+                Ok(format!("PositionMigrating:{}", transfer_id))
+            }
 
-                Ok(format!("WinningsClaimed:{}", payout))
+            Operation::ReturnPosition { market_chain, market_id } => {
+                let key = (market_chain, market_id, caller);
+                let position = self.state.imported_positions.get(&key)
+                    .await
+                    .map_err(|e| format!("Failed to get imported position: {}", e))?
+                    .ok_or_else(|| format!("No position on market {} of chain {} is held here", market_id, market_chain))?;
+                self.state.imported_positions.remove(&key)
+                    .map_err(|e| format!("Failed to remove imported position: {}", e))?;
+                let market_key = self.state.imported_market_keys.get(&(market_chain, market_id))
+                    .await
+                    .map_err(|e| format!("Failed to read imported market key: {}", e))?;
+                let transfer_id = self.send_position(market_chain, market_chain, market_key, position, timestamp)?;
+
+                Ok(format!("PositionReturning:{}", transfer_id))
             }
 
-            // === LIMIT ORDER OPERATIONS ===
-            Operation::PlaceLimitOrder {
-                market_id,
-                is_yes,
-                side,
-                price,
-                amount,
-                duration,
-            } => {
-                let market = self.state.markets.get(&market_id)
-                    .await
-                    .map_err(|e| format!("Failed to read market: {}", e))?
-                    .ok_or_else(|| format!("Market {} not found", market_id))?;
-
-                if market.resolved {
-                    return Err(format!("Market {} is already resolved", market_id));
+            Operation::SetFeatured { market_id, featured, position } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can feature markets".to_string()).into());
                 }
+                let mut market = self.load_market(market_id).await?;
+                let mut order = self.state.featured_markets.get().clone();
+                let current = order.iter().position(|id| *id == market_id);
+                if featured {
+                    let index = match (position, current) {
+                        (None, Some(index)) => index,
+                        (position, _) => {
+                            order.retain(|id| *id != market_id);
+                            let index = position.map_or(order.len(), |p| (p as usize).min(order.len()));
+                            order.insert(index, market_id);
+                            index
+                        }
+                    };
+                    self.state.featured_markets.set(order);
+                    // Resolved markets may stay featured; the response says so
+                    // the admin knows the UI will show them as settled
+                    let resolved = market.resolved;
+                    market.featured = true;
+                    self.state.markets.insert(&market_id, market)
+                        .map_err(|e| format!("Failed to save market: {}", e))?;
+                    if resolved {
+                        return Ok(format!("MarketFeatured:{}:{}:resolved", market_id, index));
+                    }
+                    Ok(format!("MarketFeatured:{}:{}", market_id, index))
+                } else {
+                    if current.is_some() {
+                        order.retain(|id| *id != market_id);
+                        self.state.featured_markets.set(order);
+                    }
+                    market.featured = false;
+                    self.state.markets.insert(&market_id, market)
+                        .map_err(|e| format!("Failed to save market: {}", e))?;
+                    Ok(format!("MarketUnfeatured:{}", market_id))
+                }
+            }
 
-                let order_id = *self.state.next_order_id.get();
-                self.state.next_order_id.set(order_id + 1);
-
-                let order = state::LimitOrder {
-                    id: order_id,
-                    owner: caller,
-                    market_id,
-                    is_yes,
-                    side,
-                    price,
-                    original_amount: amount,
-                    filled_amount: Amount::ZERO,
-                    duration,
-                    created_at: timestamp,
-                    status: OrderStatus::Open,
-                };
-
-                self.state.limit_orders.insert(&order_id, order)
-                    .map_err(|e| format!("Failed to insert order: {}", e))?;
+            Operation::ResolveMarket { market_id, outcome, evidence } => {
+                let evidence = evidence.filter(|e| !e.trim().is_empty());
+                if evidence.as_ref().is_some_and(|e| e.len() > MAX_EVIDENCE_LEN) {
+                    return Err(MarketError::TextTooLong { field: "evidence", max: MAX_EVIDENCE_LEN }.into());
+                }
+                self.resolve_market(market_id, outcome, caller, evidence, timestamp).await
+            }
 
-                Ok(format!("LimitOrderPlaced:{}", order_id))
+            Operation::ResolveMany { resolutions } => {
+                if resolutions.is_empty() || resolutions.len() > MAX_RESOLUTIONS {
+                    return Err(MarketError::ResolutionCount { count: resolutions.len(), max: MAX_RESOLUTIONS }.into());
+                }
+                // Each entry stands alone: a refused one is reported in
+                // place of its response and the rest still apply
+                let mut applied = 0;
+                let mut results = Vec::with_capacity(resolutions.len());
+                for state::Resolution { market_id, outcome } in resolutions {
+                    let result = match self.resolve_market(market_id, outcome, caller, None, timestamp).await {
+                        Ok(response) => {
+                            applied += 1;
+                            response
+                        }
+                        Err(e) => format!("ERROR: {}", e),
+                    };
+                    results.push(format!("{}:{}", market_id, result));
+                }
+                let skipped = results.len() - applied;
+                Ok(format!("MarketsResolved:{}:{};{}", applied, skipped, results.join(";")))
             }
 
-            Operation::CancelLimitOrder { order_id } => {
-                let mut order = self.state.limit_orders.get(&order_id)
-                    .await
-                    .map_err(|e| format!("Failed to read order: {}", e))?
-                    .ok_or_else(|| format!("Order {} not found", order_id))?;
+            Operation::OverrideResolution { market_id, outcome, evidence } => {
+                let evidence = evidence.filter(|e| !e.trim().is_empty());
+                if evidence.as_ref().is_some_and(|e| e.len() > MAX_EVIDENCE_LEN) {
+                    return Err(MarketError::TextTooLong { field: "evidence", max: MAX_EVIDENCE_LEN }.into());
+                }
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can override a resolution".to_string()).into());
+                }
+                let mut market = self.load_market(market_id).await?;
+                let blocked = |reason: &str| MarketError::OverrideBlocked { market_id, reason: reason.to_string() };
 
-                if order.owner != caller {
-                    return Err("Not authorized: not the order owner".to_string());
+                let previous_outcome = match market.outcome {
+                    Some(previous) if market.resolved && !market.voided => previous,
+                    _ if market.voided => return Err(blocked("it was voided").into()),
+                    _ => return Err(blocked("it is not resolved").into()),
+                };
+                if previous_outcome == outcome {
+                    return Err(blocked("it already resolved that way").into());
                 }
-                if order.status != OrderStatus::Open && order.status != OrderStatus::PartiallyFilled {
-                    return Err(format!("Order {} is not cancellable (status: {:?})", order_id, order.status));
+                if let Some(group_id) = market.group_id {
+                    return Err(MarketError::GroupMember { market_id, group_id }.into());
+                }
+                if market.claim_deadline.is_some_and(|deadline| timestamp > deadline) {
+                    return Err(blocked("its claim window has closed").into());
+                }
+                // Markets settled before `claims_started` existed read it as
+                // false, so look for claimed positions too
+                let claimed = market.claims_started
+                    || self.market_positions(market_id).await?.iter().any(|(_, position)| position.claimed);
+                if claimed {
+                    return Err(blocked("claims have been paid out").into());
+                }
+                // Conditional markets settled on the old outcome cannot be unwound
+                let children = self.state.conditional_markets.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read conditional markets: {}", e))?
+                    .unwrap_or_default();
+                let settled_children = self.state.markets.multi_get(&children)
+                    .await
+                    .map_err(|e| format!("Failed to read markets: {}", e))?
+                    .into_iter()
+                    .flatten()
+                    .filter(|child| child.resolved)
+                    .count();
+                if settled_children > 0 {
+                    return Err(blocked(&format!("{} conditional markets have settled on it", settled_children)).into());
                 }
 
-                order.status = OrderStatus::Cancelled;
-                self.state.limit_orders.insert(&order_id, order)
-                    .map_err(|e| format!("Failed to update order: {}", e))?;
+                market.outcome = Some(outcome);
+                market.resolution_time = Some(timestamp);
+                market.resolved_by = Some(caller);
+                market.resolution_evidence = evidence.clone();
+                let claim_window = TimeDelta::from_secs(self.state.config.get().claim_window_secs);
+                let claim_deadline = timestamp.saturating_add(claim_window);
+                market.claim_deadline = Some(claim_deadline);
+                let winning_total = if outcome { market.total_yes_shares } else { market.total_no_shares };
+                market.refund_mode = winning_total.saturating_sub(market.seed_shares) == Amount::ZERO;
+                // The LPs' settled value and the holders' results follow the
+                // new outcome
+                self.settle_lp_ledgers(&market).await?;
+                self.settle_trader_stats(&market, Some(previous_outcome)).await?;
+                self.save_market(market)?;
+
+                self.update_combos_for_market(market_id, outcome).await?;
 
-                Ok("LimitOrderCancelled".to_string())
+                self.emit_event(MarketEvent::OutcomeOverridden {
+                    market_id,
+                    previous_outcome,
+                    outcome,
+                    admin: caller,
+                    evidence,
+                    claim_deadline,
+                    timestamp,
+                });
+
+                Ok("ResolutionOverridden".to_string())
             }
 
-            // === COMBO OPERATIONS ===
-            Operation::CreateCombo { name, legs, stake } => {
-                if legs.len() < 2 {
-                    return Err("Combo must have at least 2 legs".to_string());
+            Operation::ClaimWinnings { market_id } => {
+                let mut market = self.load_market(market_id).await?;
+
+                // A conditional market whose parent went the other way is
+                // voided by the first claim, which then takes its refund
+                let condition = self.parent_condition(&market).await?;
+                if !market.resolved && condition == Some(false) {
+                    self.void_market(&mut market, timestamp).await?;
+                    self.settle_bond(&market, false, timestamp).await?;
+                    self.save_market(market.clone())?;
                 }
-                if legs.len() > 10 {
-                    return Err("Combo cannot have more than 10 legs".to_string());
+                if !market.resolved {
+                    return Err(format!("Market {} is not yet resolved", market_id));
+                }
+                if !market.voided && condition != Some(true) {
+                    let parent_id = market.parent.map_or(market_id, |p| p.market_id);
+                    return Err(MarketError::ParentUnresolved { market_id, parent_id }.into());
+                }
+                if market.claim_deadline.is_some_and(|deadline| timestamp > deadline) {
+                    return Err(MarketError::ClaimWindowExpired { market_id }.into());
                 }
 
-                let combo_id = *self.state.next_combo_id.get();
-                self.state.next_combo_id.set(combo_id + 1);
+                let position_key = (caller, market_id);
+                let mut position = self.state.positions.get(&position_key)
+                    .await
+                    .map_err(|e| format!("Failed to get position: {}", e))?
+                    .ok_or_else(|| "No position found for this market".to_string())?;
+                position.upgrade();
 
-                let mut combo_legs = Vec::new();
-                let mut combined_odds: u128 = 1_000_000_000_000_000_000;
+                if position.claimed {
+                    return Err("Winnings already claimed".to_string());
+                }
 
-                for leg in legs {
-                    let market = self.state.markets.get(&leg.market_id)
+                let basis = position.yes_cost_basis.saturating_add(position.no_cost_basis);
+                if market.refund_mode {
+                    if basis == Amount::ZERO {
+                        return Err("Nothing to refund".to_string());
+                    }
+                    // A share of a forfeited creator bond comes with the refund
+                    let bond_share = self.state.bond_shares.get(&position_key)
                         .await
-                        .map_err(|e| format!("Failed to read market {}: {}", leg.market_id, e))?
-                        .ok_or_else(|| format!("Market {} not found", leg.market_id))?;
-
-                    if market.resolved {
-                        return Err(format!("Market {} in combo is already resolved", leg.market_id));
+                        .map_err(|e| format!("Failed to read bond share: {}", e))?
+                        .unwrap_or_default();
+                    let refund = basis.min(market.escrow);
+                    let paid = refund.saturating_add(bond_share);
+                    self.check_payout(market_id, caller, paid, timestamp)?;
+                    market.escrow = market.escrow.saturating_sub(refund);
+                    if bond_share > Amount::ZERO {
+                        self.state.bond_shares.remove(&position_key)
+                            .map_err(|e| format!("Failed to update bond share: {}", e))?;
                     }
 
-                    let yes_pool: u128 = u128::from(market.yes_pool);
-                    let no_pool: u128 = u128::from(market.no_pool);
-                    let total = yes_pool + no_pool;
-
-                    if total == 0 {
-                        return Err(format!("Market {} has zero liquidity", leg.market_id));
-                    }
+                    let realized = pnl(paid.into(), basis.into());
+                    position.realized_pnl = position.realized_pnl.saturating_add(realized);
+                    self.add_trader_pnl(caller, realized).await?;
+                    position.claimed = true;
+                    market.claims_started = true;
+                    self.state.positions.insert(&position_key, position)
+                        .map_err(|e| format!("Failed to update position: {}", e))?;
+                    self.state.markets.insert(&market_id, market)
+                        .map_err(|e| format!("Failed to update market: {}", e))?;
+                    self.adjust_open_interest(refund, false);
+                    self.adjust_contract_balance(paid, false);
+
+                    return Ok(format!("RefundClaimed:{}", paid));
+                }
 
-                    let odds = if leg.prediction {
-                        (no_pool * 1_000_000_000_000_000_000) / total
-                    } else {
-                        (yes_pool * 1_000_000_000_000_000_000) / total
-                    };
+                let winning_shares = match market.outcome {
+                    Some(true) => position.yes_shares,
+                    Some(false) => position.no_shares,
+                    None => return Err("Market outcome not set".to_string()),
+                };
 
-                    if odds > 0 {
-                        combined_odds = (combined_odds * 1_000_000_000_000_000_000) / odds;
+                if winning_shares == Amount::ZERO {
+                    if position.yes_shares == Amount::ZERO && position.no_shares == Amount::ZERO {
+                        return Err("No winning shares".to_string());
                     }
-
-                    combo_legs.push(ComboLegState {
-                        market_id: leg.market_id,
-                        prediction: leg.prediction,
-                        odds: Amount::from_attos(odds),
-                        resolved: false,
-                        won: None,
-                    });
+                    // Only losing shares: claiming closes the position at a loss of its basis
+                    let realized = pnl(0, basis.into());
+                    position.realized_pnl = position.realized_pnl.saturating_add(realized);
+                    position.claimed = true;
+                    self.state.positions.insert(&position_key, position)
+                        .map_err(|e| format!("Failed to update position: {}", e))?;
+                    self.add_trader_pnl(caller, realized).await?;
+                    // Paid nothing, but settled the position on this outcome
+                    market.claims_started = true;
+                    self.state.markets.insert(&market_id, market)
+                        .map_err(|e| format!("Failed to update market: {}", e))?;
+                    return Ok(format!("LossRealized:{}", basis));
                 }
 
-                let potential_payout = Amount::from_attos(
-                    (u128::from(stake) * combined_odds) / 1_000_000_000_000_000_000
-                );
-
-                let combo = state::Combo {
-                    id: combo_id,
-                    owner: caller,
-                    name,
-                    legs: combo_legs,
-                    stake,
-                    potential_payout,
-                    created_at: timestamp,
-                    status: ComboStatus::Active,
+                let total_winning_shares = if market.outcome == Some(true) {
+                    market.total_yes_shares
+                } else {
+                    market.total_no_shares
                 };
 
-                self.state.combos.insert(&combo_id, combo)
-                    .map_err(|e| format!("Failed to insert combo: {}", e))?;
+                let total_pool = market.yes_pool.saturating_add(market.no_pool);
+                let payout = Amount::from_attos(payout_for_shares(
+                    u128::from(winning_shares),
+                    u128::from(total_winning_shares),
+                    u128::from(total_pool),
+                )?);
+
+                // Never pay out another market's funds: the last claimant
+                // receives whatever escrow remains if rounding left it short.
+                if market.escrow == Amount::ZERO {
+                    return Err(format!("Market {} escrow is exhausted", market_id));
+                }
+                let payout = payout.min(market.escrow);
+                self.check_payout(market_id, caller, payout, timestamp)?;
+                market.escrow = market.escrow.saturating_sub(payout);
+
+                // The claim closes out both sides' basis, the losing side's at nothing
+                let basis_known = !position.basis_unknown();
+                let realized = pnl(payout.into(), basis.into());
+                position.realized_pnl = position.realized_pnl.saturating_add(realized);
+                self.add_trader_pnl(caller, realized).await?;
+                position.claimed = true;
+                market.claims_started = true;
+                self.state.positions.insert(&position_key, position)
+                    .map_err(|e| format!("Failed to update position: {}", e))?;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to update market: {}", e))?;
+                self.adjust_open_interest(payout, false);
+                self.adjust_contract_balance(payout, false);
 
-                Ok(format!("ComboCreated:{}", combo_id))
+                let (cost_basis, profit) = if basis_known { (Some(basis), Some(realized)) } else { (None, None) };
+                self.emit_event(MarketEvent::WinningsClaimed {
+                    market_id,
+                    owner: caller,
+                    payout,
+                    cost_basis,
+                    profit,
+                    timestamp,
+                });
+                let unknown = || "unknown".to_string();
+                Ok(format!(
+                    "WinningsClaimed:{}:{}:{}",
+                    payout,
+                    cost_basis.map_or_else(unknown, |basis| basis.to_string()),
+                    profit.map_or_else(unknown, |profit| profit.to_string())
+                ))
             }
 
-            Operation::CancelCombo { combo_id } => {
-                let mut combo = self.state.combos.get(&combo_id)
-                    .await
-                    .map_err(|e| format!("Failed to read combo: {}", e))?
-                    .ok_or_else(|| format!("Combo {} not found", combo_id))?;
+            Operation::RecoverUnclaimed { market_id } => {
+                let mut market = self.load_market(market_id).await?;
 
-                if combo.owner != caller {
-                    return Err("Not authorized: not the combo owner".to_string());
-                }
-                if combo.status != ComboStatus::Active {
-                    return Err(format!("Combo {} is not active (status: {:?})", combo_id, combo.status));
+                if caller != market.creator && caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the creator or admin can recover unclaimed escrow".to_string()).into());
                 }
-                if !combo.legs.iter().all(|l| !l.resolved) {
-                    return Err("Cannot cancel combo — some markets are already resolved".to_string());
+                match market.claim_deadline {
+                    Some(deadline) if timestamp > deadline => {}
+                    _ => return Err(MarketError::ClaimWindowOpen { market_id }.into()),
                 }
 
-                combo.status = ComboStatus::Cancelled;
-                self.state.combos.insert(&combo_id, combo)
-                    .map_err(|e| format!("Failed to update combo: {}", e))?;
+                let recovered = market.escrow;
+                self.check_payout(market_id, caller, recovered, timestamp)?;
+                market.escrow = Amount::ZERO;
+                market.claims_started = true;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to update market: {}", e))?;
+                self.adjust_open_interest(recovered, false);
+                self.adjust_contract_balance(recovered, false);
 
-                Ok("ComboCancelled".to_string())
+                Ok(format!("UnclaimedRecovered:{}", recovered))
             }
 
-            // === AGENT OPERATIONS ===
-            Operation::CreateAgent {
-                name,
-                strategy,
-                config,
-                initial_capital,
-            } => {
-                let agent_id = *self.state.next_agent_id.get();
-                self.state.next_agent_id.set(agent_id + 1);
+            Operation::ClaimLpFees { market_id } => {
+                let mut market = self.load_market(market_id).await?;
 
-                let agent = state::TradingAgent {
-                    id: agent_id,
-                    owner: caller,
-                    name,
-                    strategy,
-                    config,
-                    capital: initial_capital,
-                    total_volume: Amount::ZERO,
-                    profit_loss: 0,
-                    win_rate: 0,
-                    total_trades: 0,
-                    winning_trades: 0,
-                    followers_count: 0,
-                    is_active: true,
-                    created_at: timestamp,
-                };
+                let lp_key = (caller, market_id);
+                let mut lp = self.state.lp_positions.get(&lp_key)
+                    .await
+                    .map_err(|e| format!("Failed to read LP position: {}", e))?
+                    .ok_or(MarketError::NoLiquidityPosition { market_id })?;
 
-                self.state.agents.insert(&agent_id, agent)
-                    .map_err(|e| format!("Failed to insert agent: {}", e))?;
+                let paid = settle_lp_fees(&mut market, &mut lp);
+                if paid == Amount::ZERO {
+                    return Err(MarketError::NoFeesToClaim { market_id }.into());
+                }
+                self.check_payout(market_id, caller, paid, timestamp)?;
 
-                Ok(format!("AgentCreated:{}", agent_id))
+                self.state.lp_positions.insert(&lp_key, lp)
+                    .map_err(|e| format!("Failed to update LP position: {}", e))?;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to update market: {}", e))?;
+                let mut ledger = self.state.lp_ledgers.get(&lp_key)
+                    .await
+                    .map_err(|e| format!("Failed to read LP ledger: {}", e))?
+                    .unwrap_or_default();
+                ledger.fees_claimed = ledger.fees_claimed.saturating_add(paid);
+                self.state.lp_ledgers.insert(&lp_key, ledger)
+                    .map_err(|e| format!("Failed to update LP ledger: {}", e))?;
+                self.adjust_contract_balance(paid, false);
+
+                Ok(format!("LpFeesClaimed:{}", paid))
             }
 
-            Operation::UpdateAgentConfig { agent_id, config } => {
-                let mut agent = self.state.agents.get(&agent_id)
+            Operation::FundIncentives { market_id, amount, duration_secs, sponsor_name, sponsor_url } => {
+                let mut market = self.load_market(market_id).await?;
+
+                check_open(&market, timestamp)?;
+                if amount == Amount::ZERO {
+                    return Err("Incentive amount must be greater than zero".to_string());
+                }
+                if duration_secs == 0 {
+                    return Err("Incentive duration must be greater than zero".to_string());
+                }
+                check_sponsor_branding(&sponsor_name, &sponsor_url)?;
+
+                // One program per market keeps a missing LP checkpoint
+                // meaning the accumulator's starting value, so later
+                // fundings top it up
+                let program = match self.state.incentives.get(&market_id)
                     .await
-                    .map_err(|e| format!("Failed to read agent: {}", e))?
-                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+                    .map_err(|e| format!("Failed to read incentives: {}", e))?
+                {
+                    Some(mut program) => {
+                        // Programs funded before sponsors were tracked
+                        // credit their whole budget to the first funder
+                        if market.sponsors.is_empty() {
+                            market.sponsors.push(unbranded_sponsor(program.funder, program.budget));
+                        }
+                        program.top_up(amount, duration_secs, timestamp);
+                        program
+                    }
+                    None => state::IncentiveProgram {
+                        market_id,
+                        funder: caller,
+                        budget: amount,
+                        start: timestamp,
+                        duration_secs,
+                        rewards_per_lp_share: 0,
+                        streamed: Amount::ZERO,
+                        claimed: Amount::ZERO,
+                        returned: None,
+                        topped_up_at: None,
+                        streamed_at_top_up: Amount::ZERO,
+                    },
+                };
 
-                if agent.owner != caller {
-                    return Err("Not authorized: not the agent owner".to_string());
+                let index = match market.sponsors.iter().position(|sponsor| sponsor.account == caller) {
+                    Some(index) => index,
+                    None if market.sponsors.len() >= MAX_SPONSORS => {
+                        return Err(MarketError::TooManySponsors { market_id, max: MAX_SPONSORS }.into());
+                    }
+                    None => {
+                        market.sponsors.push(unbranded_sponsor(caller, Amount::ZERO));
+                        market.sponsors.len() - 1
+                    }
+                };
+                let sponsor = &mut market.sponsors[index];
+                sponsor.amount = sponsor.amount.saturating_add(amount);
+                if !sponsor_name.is_empty() {
+                    sponsor.name = sponsor_name;
+                    sponsor.url = sponsor_url;
                 }
 
-                agent.config = config;
-                self.state.agents.insert(&agent_id, agent)
-                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+                self.state.incentives.insert(&market_id, program)
+                    .map_err(|e| format!("Failed to insert incentives: {}", e))?;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to save market: {}", e))?;
+                self.adjust_contract_balance(amount, true);
 
-                Ok("AgentUpdated".to_string())
+                Ok(format!("IncentivesFunded:{}", amount))
             }
 
-            Operation::ToggleAgent { agent_id, active } => {
-                let mut agent = self.state.agents.get(&agent_id)
-                    .await
-                    .map_err(|e| format!("Failed to read agent: {}", e))?
-                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
-
-                if agent.owner != caller {
-                    return Err("Not authorized: not the agent owner".to_string());
+            Operation::EditSponsor { market_id, sponsor, name, url } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can edit sponsors".to_string()).into());
                 }
+                check_sponsor_branding(&name, &url)?;
+                let mut market = self.load_market(market_id).await?;
+                let entry = market.sponsors.iter_mut()
+                    .find(|entry| entry.account == sponsor)
+                    .ok_or(MarketError::NotASponsor { market_id, sponsor })?;
+                entry.name = name;
+                entry.url = url;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to save market: {}", e))?;
 
-                agent.is_active = active;
-                self.state.agents.insert(&agent_id, agent)
-                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+                Ok(format!("SponsorEdited:{}:{}", market_id, sponsor))
+            }
 
-                Ok("AgentToggled".to_string())
+            Operation::RemoveSponsor { market_id, sponsor } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can remove sponsors".to_string()).into());
+                }
+                let mut market = self.load_market(market_id).await?;
+                let entry = market.sponsors.iter_mut()
+                    .find(|entry| entry.account == sponsor)
+                    .ok_or(MarketError::NotASponsor { market_id, sponsor })?;
+                // The entry stays, so its funding still comes back to it
+                entry.name.clear();
+                entry.url.clear();
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to save market: {}", e))?;
+
+                Ok(format!("SponsorRemoved:{}:{}", market_id, sponsor))
             }
 
-            Operation::FollowAgent { agent_id, allocation } => {
-                let mut agent = self.state.agents.get(&agent_id)
+            Operation::ClaimIncentives { market_id } => {
+                let market = self.load_market(market_id).await?;
+                let mut program = self.state.incentives.get(&market_id)
                     .await
-                    .map_err(|e| format!("Failed to read agent: {}", e))?
-                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+                    .map_err(|e| format!("Failed to read incentives: {}", e))?
+                    .ok_or(MarketError::NoIncentives { market_id })?;
 
-                let follower = state::AgentFollower {
-                    agent_id,
-                    follower: caller,
-                    allocation,
-                    copy_trades: true,
-                    started_at: timestamp,
-                    total_copied_pnl: 0,
-                };
+                let lp_key = (caller, market_id);
+                let lp = self.state.lp_positions.get(&lp_key)
+                    .await
+                    .map_err(|e| format!("Failed to read LP position: {}", e))?
+                    .ok_or(MarketError::NoLiquidityPosition { market_id })?;
+                let checkpoint = self.state.incentive_checkpoints.get(&lp_key)
+                    .await
+                    .map_err(|e| format!("Failed to read incentive checkpoint: {}", e))?
+                    .unwrap_or(0);
 
-                let key = (agent_id, caller);
-                self.state.agent_followers.insert(&key, follower)
-                    .map_err(|e| format!("Failed to insert follower: {}", e))?;
+                program.accrue(market.total_lp_shares, timestamp);
+                let paid = program.pending(lp.shares, checkpoint);
+                if paid == Amount::ZERO {
+                    return Err(MarketError::NoIncentivesToClaim { market_id }.into());
+                }
+                self.check_payout(market_id, caller, paid, timestamp)?;
 
-                agent.followers_count += 1;
-                self.state.agents.insert(&agent_id, agent)
-                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+                program.claimed = program.claimed.saturating_add(paid);
+                self.state.incentive_checkpoints.insert(&lp_key, program.rewards_per_lp_share)
+                    .map_err(|e| format!("Failed to update incentive checkpoint: {}", e))?;
+                self.state.incentives.insert(&market_id, program)
+                    .map_err(|e| format!("Failed to update incentives: {}", e))?;
+                self.adjust_contract_balance(paid, false);
 
-                Ok("AgentFollowed".to_string())
+                Ok(format!("IncentivesClaimed:{}", paid))
             }
 
-            Operation::UnfollowAgent { agent_id } => {
-                let mut agent = self.state.agents.get(&agent_id)
+            Operation::ReclaimBond { market_id } => {
+                let mut bond = self.state.creator_bonds.get(&market_id)
                     .await
-                    .map_err(|e| format!("Failed to read agent: {}", e))?
-                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+                    .map_err(|e| format!("Failed to read creator bond: {}", e))?
+                    .ok_or(MarketError::NoBond { market_id })?;
+                if caller != bond.creator {
+                    return Err(MarketError::NotAuthorized("only the market's creator can reclaim its bond".to_string()).into());
+                }
+                match bond.status {
+                    BondStatus::Released => {}
+                    BondStatus::Held => {
+                        // Until then the admin may still override the outcome
+                        let market = self.load_market(market_id).await?;
+                        let window_closed = market.claim_deadline.is_some_and(|deadline| timestamp > deadline);
+                        if !market.resolved || market.voided || !(market.claims_started || window_closed) {
+                            return Err(MarketError::BondLocked { market_id }.into());
+                        }
+                    }
+                    BondStatus::Returned | BondStatus::Forfeited => {
+                        return Err(MarketError::BondSettled { market_id, status: format!("{:?}", bond.status) }.into());
+                    }
+                }
+                let amount = bond.amount;
+                self.check_payout(market_id, caller, amount, timestamp)?;
 
-                let key = (agent_id, caller);
-                self.state.agent_followers.remove(&key)
-                    .map_err(|e| format!("Failed to remove follower: {}", e))?;
+                bond.status = BondStatus::Returned;
+                bond.settled_at = Some(timestamp);
+                self.state.creator_bonds.insert(&market_id, bond)
+                    .map_err(|e| format!("Failed to update creator bond: {}", e))?;
+                self.adjust_contract_balance(amount, false);
 
-                agent.followers_count = agent.followers_count.saturating_sub(1);
-                self.state.agents.insert(&agent_id, agent)
-                    .map_err(|e| format!("Failed to update agent: {}", e))?;
-
-                Ok("AgentUnfollowed".to_string())
+                Ok(format!("BondReturned:{}", amount))
             }
 
-            // === SOCIAL OPERATIONS ===
-            Operation::PostComment { market_id, content } => {
-                let feed_id = self.create_feed_item(caller, FeedItemType::Comment, Some(market_id), content, timestamp).await?;
-                Ok(format!("CommentPosted:{}", feed_id))
+            Operation::VoidMarket { market_id } => {
+                if caller != self.state.config.get().admin {
+                    return Err(MarketError::NotAuthorized("only the admin can void a market".to_string()).into());
+                }
+                let mut market = self.load_market(market_id).await?;
+
+                if market.resolved {
+                    return Err(MarketError::AlreadyResolved { market_id }.into());
+                }
+                if let Some(group_id) = market.group_id {
+                    return Err(MarketError::GroupMember { market_id, group_id }.into());
+                }
+                self.void_market(&mut market, timestamp).await?;
+                self.settle_bond(&market, true, timestamp).await?;
+                self.save_market(market)?;
+
+                Ok("MarketVoided".to_string())
             }
 
-            Operation::FollowUser { user } => {
-                let mut following = self.state.user_following.get(&caller)
+            Operation::CancelMarket { market_id } => {
+                let mut market = self.load_market(market_id).await?;
+                let blocked = |reason: &str| MarketError::CancelBlocked { market_id, reason: reason.to_string() };
+
+                if caller != market.creator {
+                    return Err(MarketError::NotAuthorized("only the creator can cancel a market".to_string()).into());
+                }
+                if market.resolved {
+                    return Err(MarketError::AlreadyResolved { market_id }.into());
+                }
+                if let Some(group_id) = market.group_id {
+                    return Err(MarketError::GroupMember { market_id, group_id }.into());
+                }
+                let traded = market.volume > Amount::ZERO
+                    || market.total_yes_shares != market.seed_shares
+                    || market.total_no_shares != market.seed_shares;
+                if traded {
+                    return Err(blocked("it has been traded").into());
+                }
+                let ordered = self.state.order_counters.contains_key(&market_id)
                     .await
-                    .map_err(|e| format!("Failed to get following: {}", e))?
-                    .unwrap_or_default();
+                    .map_err(|e| format!("Failed to read order counter: {}", e))?;
+                if ordered {
+                    return Err(blocked("it has limit orders").into());
+                }
+                let incentivized = self.state.incentives.contains_key(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read incentives: {}", e))?;
+                if incentivized {
+                    return Err(blocked("it has an incentive program").into());
+                }
+                let mut bond = self.state.creator_bonds.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read creator bond: {}", e))?;
+                let liquidity = market.escrow;
+                let bond_amount = bond.as_ref().map_or(Amount::ZERO, |bond| bond.amount);
+                self.check_payout(market_id, caller, liquidity.saturating_add(bond_amount), timestamp)?;
+
+                self.void_market(&mut market, timestamp).await?;
+                market.escrow = Amount::ZERO;
+                market.claims_started = true;
+                if let Some(mut bond) = bond.take() {
+                    bond.status = BondStatus::Returned;
+                    bond.settled_at = Some(timestamp);
+                    self.state.creator_bonds.insert(&market_id, bond)
+                        .map_err(|e| format!("Failed to update creator bond: {}", e))?;
+                }
+                self.adjust_open_interest(liquidity, false);
+                self.adjust_contract_balance(liquidity.saturating_add(bond_amount), false);
+                self.record_lp_flow(caller, market_id, liquidity, false, timestamp).await?;
+                // Nothing is left to redeem once the liquidity is withdrawn
+                self.settle_lp_ledgers(&market).await?;
+                self.save_market(market)?;
+
+                Ok(format!("MarketCancelled:{}:{}", liquidity, bond_amount))
+            }
 
-                if !following.contains(&user) {
-                    following.push(user);
-                    self.state.user_following.insert(&caller, following)
-                        .map_err(|e| format!("Failed to update following: {}", e))?;
+            Operation::ArchiveMarket { market_id } => {
+                let market = self.load_market(market_id).await?;
+                let blocked = |reason: String| MarketError::ArchiveBlocked { market_id, reason };
 
-                    let mut followers = self.state.user_followers.get(&user)
+                let Some(resolved_at) = market.resolution_time.filter(|_| market.resolved) else {
+                    return Err(blocked("it is not resolved".to_string()).into());
+                };
+                let positions = self.market_positions(market_id).await?;
+                let unclaimed = positions.iter()
+                    .filter(|(_, position)| market.claimable(position, timestamp) > Amount::ZERO)
+                    .count();
+                if unclaimed > 0 {
+                    return Err(blocked(format!("{} positions have unclaimed winnings", unclaimed)).into());
+                }
+                if market.escrow > Amount::ZERO {
+                    return Err(blocked(format!("it still holds {} of collateral", market.escrow)).into());
+                }
+                for is_yes in [true, false] {
+                    let resting = self.state.order_book.contains_key(&(market_id, is_yes))
                         .await
-                        .map_err(|e| format!("Failed to get followers: {}", e))?
-                        .unwrap_or_default();
-                    followers.push(caller);
-                    self.state.user_followers.insert(&user, followers)
-                        .map_err(|e| format!("Failed to update followers: {}", e))?;
+                        .map_err(|e| format!("Failed to read order book: {}", e))?;
+                    if resting {
+                        return Err(blocked("it has resting orders".to_string()).into());
+                    }
+                }
+                let lp_positions = self.market_lp_positions(&market).await?;
+                if lp_positions.iter().any(|(_, lp)| market.pending_lp_fees(lp) > Amount::ZERO) {
+                    return Err(blocked("liquidity providers have fees to claim".to_string()).into());
+                }
+                let program = self.state.incentives.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read incentives: {}", e))?;
+                if program.is_some_and(|program| program.budget > program.claimed.saturating_add(program.returned.unwrap_or(Amount::ZERO))) {
+                    return Err(blocked("liquidity providers have incentives to claim".to_string()).into());
+                }
+                let bond = self.state.creator_bonds.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read creator bond: {}", e))?;
+                if bond.is_some_and(|bond| matches!(bond.status, BondStatus::Held | BondStatus::Released)) {
+                    return Err(blocked("its creator has not reclaimed their bond".to_string()).into());
                 }
 
-                Ok("UserFollowed".to_string())
+                let order_count = self.state.order_counters.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read order counter: {}", e))?
+                    .unwrap_or(0);
+                for order_id in 0..order_count {
+                    self.state.limit_orders.remove(&(market_id, order_id))
+                        .map_err(|e| format!("Failed to remove order: {}", e))?;
+                }
+                // Bond shares left unclaimed past the claim window go to the treasury
+                let mut unclaimed_shares = Amount::ZERO;
+                for (owner, _) in positions {
+                    if let Some(share) = self.state.bond_shares.get(&(owner, market_id))
+                        .await
+                        .map_err(|e| format!("Failed to read bond share: {}", e))?
+                    {
+                        unclaimed_shares = unclaimed_shares.saturating_add(share);
+                        self.state.bond_shares.remove(&(owner, market_id))
+                            .map_err(|e| format!("Failed to remove bond share: {}", e))?;
+                    }
+                    self.remove_position(owner, market_id).await?;
+                }
+                adjust_total(&mut self.state.treasury, unclaimed_shares, true);
+                for (key, _) in lp_positions {
+                    self.state.lp_positions.remove(&key)
+                        .map_err(|e| format!("Failed to remove LP position: {}", e))?;
+                    self.state.incentive_checkpoints.remove(&key)
+                        .map_err(|e| format!("Failed to remove incentive checkpoint: {}", e))?;
+                    self.state.lp_ledgers.remove(&key)
+                        .map_err(|e| format!("Failed to remove LP ledger: {}", e))?;
+                }
+                for removal in [
+                    self.state.order_counters.remove(&market_id),
+                    self.state.incentives.remove(&market_id),
+                    self.state.creator_bonds.remove(&market_id),
+                    self.state.price_checkpoints.remove(&market_id),
+                    self.state.market_volume.remove(&market_id),
+                    self.state.resolution_votes.remove(&market_id),
+                    self.state.conditional_markets.remove(&market_id),
+                    self.state.markets.remove(&market_id),
+                    self.state.summaries.remove(&market_id),
+                ] {
+                    removal.map_err(|e| format!("Failed to prune market {}: {}", market_id, e))?;
+                }
+                let archived = state::ArchivedMarket {
+                    id: market_id,
+                    question: market.question,
+                    outcome: market.outcome,
+                    final_volume: market.volume,
+                    resolved_at,
+                };
+                self.state.archive.insert(&market_id, archived)
+                    .map_err(|e| format!("Failed to archive market: {}", e))?;
+                if market.featured {
+                    let mut order = self.state.featured_markets.get().clone();
+                    order.retain(|id| *id != market_id);
+                    self.state.featured_markets.set(order);
+                }
+
+                Ok(format!("MarketArchived:{}", market_id))
             }
 
-            Operation::UnfollowUser { user } => {
-                let mut following = self.state.user_following.get(&caller)
+            // === LIMIT ORDER OPERATIONS ===
+            Operation::PlaceLimitOrder {
+                market_id,
+                is_yes,
+                side,
+                price_bps,
+                shares,
+            } => {
+                if side == OrderSide::Buy && *self.state.paused.get() {
+                    return Err(MarketError::Paused.into());
+                }
+                let mut market = self.load_market(market_id).await?;
+
+                check_open(&market, timestamp)?;
+                if price_bps == 0 {
+                    return Err("Order price must be greater than zero".to_string());
+                }
+                if shares == Amount::ZERO {
+                    return Err(MarketError::ZeroShares.into());
+                }
+                self.state.config.get().check_share_precision(shares)?;
+
+                let held = self.state.positions.get(&(caller, market_id))
                     .await
-                    .map_err(|e| format!("Failed to get following: {}", e))?
-                    .unwrap_or_default();
+                    .map_err(|e| format!("Failed to get position: {}", e))?
+                    .map(|p| if is_yes { p.yes_shares } else { p.no_shares })
+                    .unwrap_or(Amount::ZERO);
+                let escrow = match side {
+                    OrderSide::Buy => {
+                        let (yes_shares, no_shares) = if is_yes { (shares, Amount::ZERO) } else { (Amount::ZERO, shares) };
+                        self.check_position_limits(&market, caller, yes_shares, no_shares).await?;
+                        order_cost(shares, price_bps)?
+                    }
+                    OrderSide::Sell => {
+                        if held < shares {
+                            return Err(format!("Insufficient shares: holding {} but the order needs {}", held, shares));
+                        }
+                        shares
+                    }
+                };
 
-                following.retain(|u| u != &user);
-                self.state.user_following.insert(&caller, following)
-                    .map_err(|e| format!("Failed to update following: {}", e))?;
+                // Sells lock their shares by moving them off the position
+                let escrowed_cost_basis = if side == OrderSide::Sell {
+                    self.update_position(caller, market_id, is_yes, shares, Amount::ZERO, false).await?
+                } else {
+                    Amount::ZERO
+                };
 
-                let mut followers = self.state.user_followers.get(&user)
+                let order_id = self.state.order_counters.get(&market_id)
                     .await
-                    .map_err(|e| format!("Failed to get followers: {}", e))?
-                    .unwrap_or_default();
-                followers.retain(|u| u != &caller);
-                self.state.user_followers.insert(&user, followers)
-                    .map_err(|e| format!("Failed to update followers: {}", e))?;
+                    .map_err(|e| format!("Failed to read order counter: {}", e))?
+                    .unwrap_or(0);
+                self.state.order_counters.insert(&market_id, order_id + 1)
+                    .map_err(|e| format!("Failed to update order counter: {}", e))?;
 
-                Ok("UserUnfollowed".to_string())
+                let mut order = state::LimitOrder {
+                    id: order_id,
+                    owner: caller,
+                    market_id,
+                    is_yes,
+                    side,
+                    price_bps,
+                    shares,
+                    filled_shares: Amount::ZERO,
+                    escrow,
+                    escrowed_cost_basis,
+                    created_at: timestamp,
+                    status: OrderStatus::Open,
+                };
+
+                // Trade against crossing orders at their resting price
+                let (maker_side, fills) = match side {
+                    OrderSide::Buy => (
+                        OrderSide::Sell,
+                        self.match_orders(market_id, is_yes, OrderSide::Sell, caller, shares, |price| price <= price_bps).await?,
+                    ),
+                    OrderSide::Sell => (
+                        OrderSide::Buy,
+                        self.match_orders(market_id, is_yes, OrderSide::Buy, caller, shares, |price| price >= price_bps).await?,
+                    ),
+                };
+                // The taker pays the trading fee on each fill
+                let fee_bps = self.trader_fee_bps(&market, caller, timestamp).await?;
+                let mut traded = Amount::ZERO;
+                let mut fees = Amount::ZERO;
+                let mut lp_fees = Amount::ZERO;
+                for (resting, fill) in fills {
+                    let BookFill { value, fee, lp_fee } =
+                        self.fill_resting_order(resting, fill, caller, fee_bps, timestamp).await?;
+                    traded = traded.saturating_add(value);
+                    fees = fees.saturating_add(fee);
+                    lp_fees = lp_fees.saturating_add(lp_fee);
+                    if maker_side == OrderSide::Sell {
+                        // Escrow was taken at our own price; the difference is refunded
+                        settle_fill(&mut order, fill, order_cost(fill, price_bps)?)?;
+                        self.update_position(caller, market_id, is_yes, fill, value.saturating_add(fee), true).await?;
+                    } else {
+                        let relief = settle_fill(&mut order, fill, value)?;
+                        let proceeds = value.saturating_sub(fee);
+                        self.record_realized_pnl(caller, market_id, pnl(proceeds.into(), relief.into())).await?;
+                    }
+                }
+
+                if order.status != OrderStatus::Filled {
+                    let key = (market_id, is_yes);
+                    let mut ids = self.state.order_book.get(&key)
+                        .await
+                        .map_err(|e| format!("Failed to read order book: {}", e))?
+                        .unwrap_or_default();
+                    ids.push(order_id);
+                    self.state.order_book.insert(&key, ids)
+                        .map_err(|e| format!("Failed to update order book: {}", e))?;
+                }
+                let filled_shares = order.filled_shares;
+                self.state.limit_orders.insert(&(market_id, order_id), order)
+                    .map_err(|e| format!("Failed to insert order: {}", e))?;
+
+                if traded > Amount::ZERO {
+                    market.volume = market.volume.saturating_add(traded);
+                    market.accrue_fee(lp_fees);
+                    self.adjust_contract_balance(fees, true);
+                    // Book fills leave the AMM price where it was
+                    self.record_recent_trade(RecentTrade {
+                        market_id,
+                        question: market.question.clone(),
+                        trader: caller,
+                        side,
+                        is_yes,
+                        shares: filled_shares,
+                        amount: traded,
+                        yes_price_bps: market.yes_price_bps(),
+                        timestamp,
+                    });
+                    self.save_market(market)?;
+                    self.record_volume(market_id, traded, timestamp).await?;
+                    self.record_trader(caller, market_id, traded).await?;
+                }
+
+                Ok(format!("LimitOrderPlaced:{}", order_id))
             }
 
-            Operation::LikeFeedItem { item_id } => {
-                let mut item = self.state.feed_items.get(&item_id)
+            Operation::CancelOrder { market_id, order_id } => {
+                let mut order = self.state.limit_orders.get(&(market_id, order_id))
                     .await
-                    .map_err(|e| format!("Failed to get feed item: {}", e))?
-                    .ok_or_else(|| format!("Feed item {} not found", item_id))?;
+                    .map_err(|e| format!("Failed to read order: {}", e))?
+                    .ok_or_else(|| format!("Order {} not found on market {}", order_id, market_id))?;
 
-                let mut likes = self.state.item_likes.get(&item_id)
+                if order.owner != caller {
+                    return Err(MarketError::NotAuthorized("not the order owner".to_string()).into());
+                }
+                if order.status != OrderStatus::Open && order.status != OrderStatus::PartiallyFilled {
+                    return Err(format!("Order {} is not cancellable (status: {:?})", order_id, order.status));
+                }
+
+                // Allowed on resolved and expired markets too, so escrow is never stranded
+                let refund = order.escrow;
+                if order.side == OrderSide::Sell {
+                    self.update_position(caller, market_id, order.is_yes, refund, order.escrowed_cost_basis, true).await?;
+                }
+                self.remove_from_book(market_id, order.is_yes, order_id).await?;
+
+                order.escrow = Amount::ZERO;
+                order.escrowed_cost_basis = Amount::ZERO;
+                order.status = OrderStatus::Cancelled;
+                self.state.limit_orders.insert(&(market_id, order_id), order)
+                    .map_err(|e| format!("Failed to update order: {}", e))?;
+
+                Ok(format!("OrderCancelled:{}", refund))
+            }
+
+            Operation::WithdrawRebates => {
+                let rebates = self.state.maker_rebates.get(&caller)
                     .await
-                    .map_err(|e| format!("Failed to get likes: {}", e))?
-                    .unwrap_or_default();
+                    .map_err(|e| format!("Failed to read maker rebates: {}", e))?
+                    .filter(|rebates| *rebates > Amount::ZERO)
+                    .ok_or(MarketError::NoRebatesToWithdraw)?;
+                let balance = *self.state.contract_balance.get();
+                if rebates > balance {
+                    return Err(MarketError::RebatesExceedBalance { rebates, balance }.into());
+                }
 
-                if !likes.contains(&caller) {
-                    likes.push(caller);
-                    item.likes_count += 1;
+                self.state.maker_rebates.remove(&caller)
+                    .map_err(|e| format!("Failed to update maker rebates: {}", e))?;
+                self.adjust_contract_balance(rebates, false);
 
-                    self.state.item_likes.insert(&item_id, likes)
-                        .map_err(|e| format!("Failed to update likes: {}", e))?;
-                    self.state.feed_items.insert(&item_id, item)
-                        .map_err(|e| format!("Failed to update item: {}", e))?;
+                Ok(format!("RebatesWithdrawn:{}", rebates))
+            }
+
+            // === TRIGGER ORDER OPERATIONS ===
+            Operation::PlaceTriggerOrder {
+                market_id,
+                is_yes,
+                shares,
+                trigger_price_bps,
+                direction,
+                min_proceeds,
+            } => {
+                let market = self.load_market(market_id).await?;
+
+                check_open(&market, timestamp)?;
+                if trigger_price_bps == 0 || trigger_price_bps >= 10_000 {
+                    return Err("Trigger price must be between 1 and 9999 bps".to_string());
+                }
+                self.state.config.get().check_trade_shares(&market, shares)?;
+                let position = self.position_or_empty(caller, market_id).await?;
+                let held = if is_yes { position.yes_shares } else { position.no_shares };
+                if shares > held {
+                    return Err(MarketError::InsufficientBalance { needed: shares, available: held }.into());
                 }
 
-                Ok("ItemLiked".to_string())
+                let order_id = self.add_pending_trigger(market_id).await?;
+                let keeper_fee = self.state.config.get().keeper_fee();
+                self.adjust_contract_balance(keeper_fee, true);
+                let order = state::TriggerOrder {
+                    id: order_id,
+                    owner: caller,
+                    market_id,
+                    is_yes,
+                    shares,
+                    trigger_price_bps,
+                    direction,
+                    min_proceeds,
+                    keeper_fee,
+                    created_at: timestamp,
+                    status: TriggerStatus::Pending,
+                    proceeds: Amount::ZERO,
+                    closed_at: None,
+                    max_price_bps: None,
+                    escrow: Amount::ZERO,
+                    cost: Amount::ZERO,
+                };
+                self.state.trigger_orders.insert(&(market_id, order_id), order)
+                    .map_err(|e| format!("Failed to insert trigger order: {}", e))?;
+
+                Ok(format!("TriggerOrderPlaced:{}", order_id))
             }
-        }
-    }
 
-    async fn update_position(
-        &mut self,
-        owner: AccountOwner,
-        market_id: u64,
-        is_yes: bool,
-        shares: Amount,
-        is_buy: bool,
-    ) -> Result<(), String> {
-        let position_key = (owner, market_id);
-        let mut position = self.state.positions.get(&position_key)
-            .await
-            .map_err(|e| format!("Failed to get position: {}", e))?
-            .unwrap_or(state::Position {
+            Operation::PlaceBuyTriggerOrder {
                 market_id,
-                owner,
-                yes_shares: Amount::ZERO,
-                no_shares: Amount::ZERO,
-                claimed: false,
-            });
+                is_yes,
+                shares,
+                trigger_price_bps,
+                direction,
+                max_price_bps,
+            } => {
+                let market = self.load_market(market_id).await?;
 
-        if is_buy {
-            if is_yes {
-                position.yes_shares = position.yes_shares.saturating_add(shares);
-            } else {
-                position.no_shares = position.no_shares.saturating_add(shares);
+                check_open(&market, timestamp)?;
+                if trigger_price_bps == 0 || trigger_price_bps >= 10_000 {
+                    return Err("Trigger price must be between 1 and 9999 bps".to_string());
+                }
+                if max_price_bps == 0 || max_price_bps > 10_000 {
+                    return Err("Max price must be between 1 and 10000 bps".to_string());
+                }
+                self.state.config.get().check_trade_shares(&market, shares)?;
+                // The most the purchase may cost, held until it executes
+                let escrow = order_cost(shares, max_price_bps)?;
+                if escrow == Amount::ZERO {
+                    return Err(MarketError::ZeroCostTrade { shares }.into());
+                }
+
+                let order_id = self.add_pending_trigger(market_id).await?;
+                let keeper_fee = self.state.config.get().keeper_fee();
+                self.adjust_contract_balance(escrow.saturating_add(keeper_fee), true);
+                let order = state::TriggerOrder {
+                    id: order_id,
+                    owner: caller,
+                    market_id,
+                    is_yes,
+                    shares,
+                    trigger_price_bps,
+                    direction,
+                    min_proceeds: Amount::ZERO,
+                    keeper_fee,
+                    created_at: timestamp,
+                    status: TriggerStatus::Pending,
+                    proceeds: Amount::ZERO,
+                    closed_at: None,
+                    max_price_bps: Some(max_price_bps),
+                    escrow,
+                    cost: Amount::ZERO,
+                };
+                self.state.trigger_orders.insert(&(market_id, order_id), order)
+                    .map_err(|e| format!("Failed to insert trigger order: {}", e))?;
+
+                Ok(format!("TriggerOrderPlaced:{}", order_id))
             }
-        } else {
-            if is_yes {
-                position.yes_shares = position.yes_shares.saturating_sub(shares);
-            } else {
-                position.no_shares = position.no_shares.saturating_sub(shares);
+
+            Operation::CancelTriggerOrder { market_id, order_id } => {
+                let order = self.load_trigger_order(market_id, order_id).await?;
+
+                if order.owner != caller {
+                    return Err(MarketError::NotAuthorized("not the order owner".to_string()).into());
+                }
+                if order.status != TriggerStatus::Pending {
+                    return Err(format!("Trigger order {} is not pending (status: {:?})", order_id, order.status));
+                }
+
+                self.remove_pending_trigger(market_id, order_id).await?;
+                let refund = self.close_trigger_order(order, timestamp)?;
+
+                Ok(format!("TriggerOrderCancelled:{}", refund))
             }
-        }
 
-        self.state.positions.insert(&position_key, position)
-            .map_err(|e| format!("Failed to update position: {}", e))?;
+            Operation::ExecuteTriggeredOrders { market_id, max_orders } => {
+                let market = self.load_market(market_id).await?;
+                if market.resolved {
+                    return Err(MarketError::AlreadyResolved { market_id }.into());
+                }
+
+                let pending = self.state.pending_triggers.get(&market_id)
+                    .await
+                    .map_err(|e| format!("Failed to read pending trigger orders: {}", e))?
+                    .unwrap_or_default();
+                let mut executed = 0u64;
+                let mut keeper_fees = Amount::ZERO;
 
-        Ok(())
+                for order_id in pending.into_iter().take(MAX_PENDING_TRIGGER_ORDERS) {
+                    if executed >= max_orders {
+                        break;
+                    }
+                    let mut order = self.load_trigger_order(market_id, order_id).await?;
+                    // Each trade moves the price the next order is checked at
+                    let market = self.load_market(market_id).await?;
+                    let side = order.side();
+                    // An order lapses with its market, refunding its escrow
+                    if effective_status(&market, timestamp) != EffectiveStatus::Open {
+                        self.remove_pending_trigger(market_id, order_id).await?;
+                        self.close_trigger_order(order, timestamp)?;
+                        continue;
+                    }
+                    if !order.is_triggered(&market) {
+                        continue;
+                    }
+
+                    let traded = match side {
+                        OrderSide::Buy => self.buy_for_trigger(&order, market, timestamp).await
+                            .map(|cost| cost.map(|cost| (Amount::ZERO, cost))),
+                        OrderSide::Sell => {
+                            // An owner who no longer holds every share the
+                            // order sells has it cancelled rather than partly
+                            // executed, so min_proceeds always prices the
+                            // full size
+                            let position = self.position_or_empty(order.owner, market_id).await?;
+                            let held = if order.is_yes { position.yes_shares } else { position.no_shares };
+                            if held < order.shares {
+                                self.remove_pending_trigger(market_id, order_id).await?;
+                                let owner = order.owner;
+                                self.close_trigger_order(order, timestamp)?;
+                                self.emit_event(MarketEvent::TriggerOrderCancelled { market_id, order_id, owner, held, timestamp });
+                                continue;
+                            }
+
+                            self.sell_for_trigger(&order, &market, timestamp).await
+                                .map(|proceeds| proceeds.map(|proceeds| (proceeds, Amount::ZERO)))
+                        }
+                    };
+                    let (proceeds, cost) = match traded {
+                        Ok(Some(amounts)) => amounts,
+                        Ok(None) => continue,
+                        // A trade refused by a check, which runs before
+                        // anything is written, cancels its order rather than
+                        // leaving it at the head of the queue
+                        Err(reason) => {
+                            self.remove_pending_trigger(market_id, order_id).await?;
+                            let owner = order.owner;
+                            self.close_trigger_order(order, timestamp)?;
+                            self.emit_event(MarketEvent::TriggerOrderFailed { market_id, order_id, owner, reason, timestamp });
+                            continue;
+                        }
+                    };
+                    self.remove_pending_trigger(market_id, order_id).await?;
+
+                    // The keeper is paid the fee escrowed at placement
+                    self.adjust_contract_balance(order.keeper_fee, false);
+                    keeper_fees = keeper_fees.saturating_add(order.keeper_fee);
+                    executed += 1;
+
+                    order.status = TriggerStatus::Executed;
+                    order.proceeds = proceeds;
+                    order.cost = cost;
+                    order.closed_at = Some(timestamp);
+                    self.emit_event(MarketEvent::TriggerOrderExecuted {
+                        market_id,
+                        order_id,
+                        owner: order.owner,
+                        keeper: caller,
+                        side,
+                        is_yes: order.is_yes,
+                        shares: order.shares,
+                        proceeds,
+                        cost,
+                        keeper_fee: order.keeper_fee,
+                        timestamp,
+                    });
+                    self.state.trigger_orders.insert(&(market_id, order_id), order)
+                        .map_err(|e| format!("Failed to update trigger order: {}", e))?;
+                }
+
+                Ok(format!("TriggeredOrdersExecuted:{}:{}", executed, keeper_fees))
+            }
+
+            // === COMBO OPERATIONS ===
+            Operation::CreateCombo { name, legs, stake } => {
+                if legs.len() < 2 {
+                    return Err("Combo must have at least 2 legs".to_string());
+                }
+                if legs.len() > 10 {
+                    return Err("Combo cannot have more than 10 legs".to_string());
+                }
+
+                let combo_id = allocate_id(&mut self.state.next_combo_id)
+                    .ok_or(MarketError::IdsExhausted { kind: "combo" })?;
+
+                let mut combo_legs = Vec::new();
+                let mut combined_odds: u128 = 1_000_000_000_000_000_000;
+
+                for leg in legs {
+                    let market = self.load_market(leg.market_id).await?;
+
+                    if market.resolved {
+                        return Err(format!("Market {} in combo is already resolved", leg.market_id));
+                    }
+
+                    let yes_pool: u128 = u128::from(market.yes_pool);
+                    let no_pool: u128 = u128::from(market.no_pool);
+                    let total = yes_pool + no_pool;
+
+                    if total == 0 {
+                        return Err(format!("Market {} has zero liquidity", leg.market_id));
+                    }
+
+                    let odds = if leg.prediction {
+                        (no_pool * 1_000_000_000_000_000_000) / total
+                    } else {
+                        (yes_pool * 1_000_000_000_000_000_000) / total
+                    };
+
+                    if let Some(scaled) = (combined_odds * 1_000_000_000_000_000_000).checked_div(odds) {
+                        combined_odds = scaled;
+                    }
+
+                    combo_legs.push(ComboLegState {
+                        market_id: leg.market_id,
+                        prediction: leg.prediction,
+                        odds: Amount::from_attos(odds),
+                        resolved: false,
+                        won: None,
+                    });
+                }
+
+                let potential_payout = Amount::from_attos(
+                    (u128::from(stake) * combined_odds) / 1_000_000_000_000_000_000
+                );
+
+                let combo = state::Combo {
+                    id: combo_id,
+                    owner: caller,
+                    name,
+                    legs: combo_legs,
+                    stake,
+                    potential_payout,
+                    created_at: timestamp,
+                    status: ComboStatus::Active,
+                };
+
+                self.state.combos.insert(&combo_id, combo)
+                    .map_err(|e| format!("Failed to insert combo: {}", e))?;
+
+                Ok(format!("ComboCreated:{}", combo_id))
+            }
+
+            Operation::CancelCombo { combo_id } => {
+                let mut combo = self.state.combos.get(&combo_id)
+                    .await
+                    .map_err(|e| format!("Failed to read combo: {}", e))?
+                    .ok_or_else(|| format!("Combo {} not found", combo_id))?;
+
+                if combo.owner != caller {
+                    return Err("Not authorized: not the combo owner".to_string());
+                }
+                if combo.status != ComboStatus::Active {
+                    return Err(format!("Combo {} is not active (status: {:?})", combo_id, combo.status));
+                }
+                if !combo.legs.iter().all(|l| !l.resolved) {
+                    return Err("Cannot cancel combo — some markets are already resolved".to_string());
+                }
+
+                combo.status = ComboStatus::Cancelled;
+                self.state.combos.insert(&combo_id, combo)
+                    .map_err(|e| format!("Failed to update combo: {}", e))?;
+
+                Ok("ComboCancelled".to_string())
+            }
+
+            // === AGENT OPERATIONS ===
+            Operation::CreateAgent {
+                name,
+                strategy,
+                config,
+                initial_capital,
+            } => {
+                let agent_id = allocate_id(&mut self.state.next_agent_id)
+                    .ok_or(MarketError::IdsExhausted { kind: "agent" })?;
+
+                let agent = state::TradingAgent {
+                    id: agent_id,
+                    owner: caller,
+                    name,
+                    strategy,
+                    config,
+                    capital: initial_capital,
+                    total_volume: Amount::ZERO,
+                    profit_loss: 0,
+                    win_rate: 0,
+                    total_trades: 0,
+                    winning_trades: 0,
+                    followers_count: 0,
+                    is_active: true,
+                    created_at: timestamp,
+                };
+
+                self.state.agents.insert(&agent_id, agent)
+                    .map_err(|e| format!("Failed to insert agent: {}", e))?;
+
+                Ok(format!("AgentCreated:{}", agent_id))
+            }
+
+            Operation::UpdateAgentConfig { agent_id, config } => {
+                let mut agent = self.state.agents.get(&agent_id)
+                    .await
+                    .map_err(|e| format!("Failed to read agent: {}", e))?
+                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+
+                if agent.owner != caller {
+                    return Err("Not authorized: not the agent owner".to_string());
+                }
+
+                agent.config = config;
+                self.state.agents.insert(&agent_id, agent)
+                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+
+                Ok("AgentUpdated".to_string())
+            }
+
+            Operation::ToggleAgent { agent_id, active } => {
+                let mut agent = self.state.agents.get(&agent_id)
+                    .await
+                    .map_err(|e| format!("Failed to read agent: {}", e))?
+                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+
+                if agent.owner != caller {
+                    return Err("Not authorized: not the agent owner".to_string());
+                }
+
+                agent.is_active = active;
+                self.state.agents.insert(&agent_id, agent)
+                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+
+                Ok("AgentToggled".to_string())
+            }
+
+            Operation::FollowAgent { agent_id, allocation } => {
+                let mut agent = self.state.agents.get(&agent_id)
+                    .await
+                    .map_err(|e| format!("Failed to read agent: {}", e))?
+                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+
+                let follower = state::AgentFollower {
+                    agent_id,
+                    follower: caller,
+                    allocation,
+                    copy_trades: true,
+                    started_at: timestamp,
+                    total_copied_pnl: 0,
+                };
+
+                let key = (agent_id, caller);
+                self.state.agent_followers.insert(&key, follower)
+                    .map_err(|e| format!("Failed to insert follower: {}", e))?;
+
+                agent.followers_count += 1;
+                self.state.agents.insert(&agent_id, agent)
+                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+
+                Ok("AgentFollowed".to_string())
+            }
+
+            Operation::UnfollowAgent { agent_id } => {
+                let mut agent = self.state.agents.get(&agent_id)
+                    .await
+                    .map_err(|e| format!("Failed to read agent: {}", e))?
+                    .ok_or_else(|| format!("Agent {} not found", agent_id))?;
+
+                let key = (agent_id, caller);
+                self.state.agent_followers.remove(&key)
+                    .map_err(|e| format!("Failed to remove follower: {}", e))?;
+
+                agent.followers_count = agent.followers_count.saturating_sub(1);
+                self.state.agents.insert(&agent_id, agent)
+                    .map_err(|e| format!("Failed to update agent: {}", e))?;
+
+                Ok("AgentUnfollowed".to_string())
+            }
+
+            // === SOCIAL OPERATIONS ===
+            Operation::PostComment { market_id, content } => {
+                let feed_id = self.create_feed_item(caller, FeedItemType::Comment, Some(market_id), content, timestamp).await?;
+                Ok(format!("CommentPosted:{}", feed_id))
+            }
+
+            Operation::FollowUser { user } => {
+                let mut following = self.state.user_following.get(&caller)
+                    .await
+                    .map_err(|e| format!("Failed to get following: {}", e))?
+                    .unwrap_or_default();
+
+                if !following.contains(&user) {
+                    following.push(user);
+                    self.state.user_following.insert(&caller, following)
+                        .map_err(|e| format!("Failed to update following: {}", e))?;
+
+                    let mut followers = self.state.user_followers.get(&user)
+                        .await
+                        .map_err(|e| format!("Failed to get followers: {}", e))?
+                        .unwrap_or_default();
+                    followers.push(caller);
+                    self.state.user_followers.insert(&user, followers)
+                        .map_err(|e| format!("Failed to update followers: {}", e))?;
+                }
+
+                Ok("UserFollowed".to_string())
+            }
+
+            Operation::UnfollowUser { user } => {
+                let mut following = self.state.user_following.get(&caller)
+                    .await
+                    .map_err(|e| format!("Failed to get following: {}", e))?
+                    .unwrap_or_default();
+
+                following.retain(|u| u != &user);
+                self.state.user_following.insert(&caller, following)
+                    .map_err(|e| format!("Failed to update following: {}", e))?;
+
+                let mut followers = self.state.user_followers.get(&user)
+                    .await
+                    .map_err(|e| format!("Failed to get followers: {}", e))?
+                    .unwrap_or_default();
+                followers.retain(|u| u != &caller);
+                self.state.user_followers.insert(&user, followers)
+                    .map_err(|e| format!("Failed to update followers: {}", e))?;
+
+                Ok("UserUnfollowed".to_string())
+            }
+
+            Operation::LikeFeedItem { item_id } => {
+                let mut item = self.state.feed_items.get(&item_id)
+                    .await
+                    .map_err(|e| format!("Failed to get feed item: {}", e))?
+                    .ok_or_else(|| format!("Feed item {} not found", item_id))?;
+
+                let mut likes = self.state.item_likes.get(&item_id)
+                    .await
+                    .map_err(|e| format!("Failed to get likes: {}", e))?
+                    .unwrap_or_default();
+
+                if !likes.contains(&caller) {
+                    likes.push(caller);
+                    item.likes_count += 1;
+
+                    self.state.item_likes.insert(&item_id, likes)
+                        .map_err(|e| format!("Failed to update likes: {}", e))?;
+                    self.state.feed_items.insert(&item_id, item)
+                        .map_err(|e| format!("Failed to update item: {}", e))?;
+                }
+
+                Ok("ItemLiked".to_string())
+            }
+
+            // Upvotes are interest, not trading: resolved markets take them
+            // too, and nothing here touches volume or trending
+            Operation::Upvote { market_id } => {
+                let mut market = self.load_market(market_id).await?;
+                let key = (caller, market_id);
+                if self.state.market_upvotes.contains_key(&key).await
+                    .map_err(|e| format!("Failed to read upvote: {}", e))?
+                {
+                    return Ok(format!("Upvoted:{}", market.upvotes));
+                }
+                self.state.market_upvotes.insert(&key, ())
+                    .map_err(|e| format!("Failed to save upvote: {}", e))?;
+                market.upvotes += 1;
+                let upvotes = market.upvotes;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to save market: {}", e))?;
+                Ok(format!("Upvoted:{}", upvotes))
+            }
+
+            Operation::RemoveUpvote { market_id } => {
+                let mut market = self.load_market(market_id).await?;
+                let key = (caller, market_id);
+                if !self.state.market_upvotes.contains_key(&key).await
+                    .map_err(|e| format!("Failed to read upvote: {}", e))?
+                {
+                    return Ok(format!("UpvoteRemoved:{}", market.upvotes));
+                }
+                self.state.market_upvotes.remove(&key)
+                    .map_err(|e| format!("Failed to remove upvote: {}", e))?;
+                market.upvotes = market.upvotes.saturating_sub(1);
+                let upvotes = market.upvotes;
+                self.state.markets.insert(&market_id, market)
+                    .map_err(|e| format!("Failed to save market: {}", e))?;
+                Ok(format!("UpvoteRemoved:{}", upvotes))
+            }
+        }
+    }
+
+    /// Append a successful operation to the activity log and index it under
+    /// its actor and every market it named or created: a parlay's legs, a
+    /// batch's resolutions and a resolved group's members included. An
+    /// operation that traded since `trades_before` also notes the price and
+    /// position it left.
+    async fn record_activity(
+        &mut self,
+        mut entry: state::ActivityEntry,
+        first_new_market: u64,
+        group_id: Option<u64>,
+        leg_markets: Vec<u64>,
+        trades_before: u64,
+    ) -> Result<(), String> {
+        let mut market_ids: Vec<u64> = (first_new_market..*self.state.next_market_id.get()).collect();
+        market_ids.extend(leg_markets);
+        if let Some(group_id) = group_id {
+            if let Some(group) = self.state.groups.get(&group_id).await
+                .map_err(|e| format!("Failed to read group {}: {}", group_id, e))?
+            {
+                market_ids.extend(group.market_ids);
+            }
+        }
+        entry.market_id = entry.market_id.or(market_ids.first().copied());
+        market_ids.extend(entry.market_id);
+        market_ids.sort_unstable();
+        market_ids.dedup();
+
+        // An operation that traded notes where it left the market and the actor
+        if let Some(market_id) = entry.market_id {
+            let trades = self.trade_count(market_id).await?;
+            if trades > trades_before {
+                entry.yes_price_bps = Some(self.load_market(market_id).await?.yes_price_bps());
+                entry.trade_seq = Some(trades - 1);
+                if let Some(is_yes) = entry.is_yes {
+                    let position = self.position_or_empty(entry.actor, market_id).await?;
+                    entry.position_after = Some(if is_yes { position.yes_shares } else { position.no_shares });
+                }
+            }
+        }
+
+        let index = self.state.activity.count() as u64;
+        let actor = entry.actor;
+        self.state.activity.push(entry);
+        for market_id in market_ids {
+            let n = self.state.market_activity_counts.get(&market_id).await
+                .map_err(|e| format!("Failed to read activity count: {}", e))?
+                .unwrap_or(0);
+            self.state.market_activity.insert(&(market_id, n), index)
+                .map_err(|e| format!("Failed to index activity: {}", e))?;
+            self.state.market_activity_counts.insert(&market_id, n + 1)
+                .map_err(|e| format!("Failed to update activity count: {}", e))?;
+        }
+        let n = self.state.owner_activity_counts.get(&actor).await
+            .map_err(|e| format!("Failed to read activity count: {}", e))?
+            .unwrap_or(0);
+        self.state.owner_activity.insert(&(actor, n), index)
+            .map_err(|e| format!("Failed to index activity: {}", e))?;
+        self.state.owner_activity_counts.insert(&actor, n + 1)
+            .map_err(|e| format!("Failed to update activity count: {}", e))?;
+        Ok(())
+    }
+
+    /// Read a market, upgraded to the current record version so the next
+    /// write stores the latest layout
+    async fn load_market(&self, market_id: u64) -> Result<state::Market, String> {
+        let mut market = self.state.markets.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
+            .ok_or_else(|| format!("Market {} not found", market_id))?;
+        market.upgrade();
+        Ok(market)
+    }
+
+    /// Write `market` and its listing summary together, so the two never
+    /// diverge. Only writes that move escrow or fees alone, such as claims,
+    /// insert the market by itself.
+    fn save_market(&mut self, market: state::Market) -> Result<(), String> {
+        let market_id = market.id;
+        self.state.summaries.insert(&market_id, state::MarketSummary::from(&market))
+            .map_err(|e| format!("Failed to update market summary: {}", e))?;
+        self.state.markets.insert(&market_id, market)
+            .map_err(|e| format!("Failed to update market: {}", e))
+    }
+
+    /// Validate a market against the config without touching state
+    fn check_new_market(&self, new_market: &NewMarket, timestamp: Timestamp) -> Result<(), String> {
+        if new_market.description.len() > MAX_DESCRIPTION_LEN {
+            return Err(MarketError::TextTooLong { field: "description", max: MAX_DESCRIPTION_LEN }.into());
+        }
+        if new_market.resolution_source.len() > MAX_RESOLUTION_SOURCE_LEN {
+            return Err(MarketError::TextTooLong { field: "resolution_source", max: MAX_RESOLUTION_SOURCE_LEN }.into());
+        }
+        if new_market.initial_liquidity == Amount::ZERO {
+            return Err("Initial liquidity must be greater than zero".to_string());
+        }
+        let config = self.state.config.get();
+        if new_market.initial_liquidity < config.min_initial_liquidity {
+            return Err(MarketError::InitialLiquidityTooLow {
+                provided: new_market.initial_liquidity,
+                minimum: config.min_initial_liquidity,
+            }.into());
+        }
+        let min_duration = TimeDelta::from_secs(config.min_market_duration_secs);
+        if new_market.end_time < timestamp.saturating_add(min_duration) {
+            return Err(MarketError::MarketDurationTooShort {
+                minimum_secs: config.min_market_duration_secs,
+            }.into());
+        }
+        let resolvers = &new_market.resolvers;
+        if resolvers.len() > MAX_RESOLVERS {
+            return Err(format!("A market may have at most {} resolvers", MAX_RESOLVERS));
+        }
+        let threshold_range = if resolvers.is_empty() { 0..=0 } else { 1..=resolvers.len() };
+        if !threshold_range.contains(&usize::from(new_market.threshold)) {
+            return Err(MarketError::InvalidThreshold {
+                threshold: new_market.threshold,
+                resolvers: resolvers.len(),
+            }.into());
+        }
+        for (i, resolver) in resolvers.iter().enumerate() {
+            if resolvers[..i].contains(resolver) {
+                return Err(MarketError::DuplicateResolver(*resolver).into());
+            }
+        }
+        if let Some(ramp) = new_market.fee_ramp {
+            if ramp.window_secs == 0 || ramp.max_fee_bps < config.fee_bps || ramp.max_fee_bps > 10_000 {
+                return Err(MarketError::InvalidFeeRamp { base_bps: config.fee_bps }.into());
+            }
+        }
+        if let Some(limit) = new_market.max_probability_move_bps {
+            let max_bps = config.max_probability_move_bps.unwrap_or(10_000);
+            if limit == 0 || limit > max_bps {
+                return Err(MarketError::InvalidImpactLimit { max_bps }.into());
+            }
+        }
+        if let Some(cap) = new_market.max_trade_shares {
+            let minimum = config.min_trade_shares;
+            if cap == Amount::ZERO || cap < minimum {
+                return Err(MarketError::InvalidTradeCap { minimum }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a validated market, seed its pools and announce it. Returns
+    /// its id and key.
+    async fn open_market(
+        &mut self,
+        creator: AccountOwner,
+        new_market: NewMarket,
+        timestamp: Timestamp,
+    ) -> Result<(u64, MarketKey), String> {
+        let nonce = self.state.market_nonces.get(&creator)
+            .await
+            .map_err(|e| format!("Failed to read market nonce: {}", e))?
+            .unwrap_or(0);
+        let key = market_key(self.runtime.chain_id(), creator, nonce);
+        // Refused before anything is written, rather than overwriting
+        // the other market's entry
+        if let Some(market_id) = self.state.market_keys.get(&key)
+            .await
+            .map_err(|e| format!("Failed to read market keys: {}", e))?
+        {
+            return Err(MarketError::MarketKeyCollision { key, market_id }.into());
+        }
+        let market_id = allocate_id(&mut self.state.next_market_id)
+            .ok_or(MarketError::IdsExhausted { kind: "market" })?;
+        self.state.market_nonces.insert(&creator, nonce + 1)
+            .map_err(|e| format!("Failed to update market nonce: {}", e))?;
+        self.state.market_keys.insert(&key, market_id)
+            .map_err(|e| format!("Failed to index market key: {}", e))?;
+
+        let NewMarket {
+            question,
+            categories,
+            end_time,
+            initial_liquidity,
+            early_resolution_allowed,
+            max_position_per_account,
+            max_position,
+            creator_trading_disabled,
+            fee_ramp,
+            description,
+            resolution_source,
+            parent,
+            group_id,
+            resolvers,
+            threshold,
+            max_probability_move_bps,
+            max_trade_shares,
+        } = new_market;
+        let half = Amount::from_attos(u128::from(initial_liquidity) / 2);
+
+        let market = state::Market {
+            id: market_id,
+            creator,
+            question: question.clone(),
+            categories: categories.clone(),
+            end_time,
+            created_at: timestamp,
+            yes_pool: half,
+            no_pool: half,
+            total_yes_shares: half,
+            total_no_shares: half,
+            resolved: false,
+            outcome: None,
+            volume: Amount::ZERO,
+            escrow: initial_liquidity,
+            early_resolution_allowed,
+            resolution_time: None,
+            seed_shares: half,
+            refund_mode: false,
+            max_position_per_account,
+            max_position,
+            creator_trading_disabled,
+            fee_ramp,
+            voided: false,
+            resolved_by: None,
+            claim_deadline: None,
+            description,
+            resolution_source,
+            total_lp_shares: initial_liquidity,
+            fees_per_lp_share: 0,
+            fees_collected: Amount::ZERO,
+            fees_distributed: Amount::ZERO,
+            parent,
+            group_id,
+            resolvers,
+            threshold,
+            price_cumulative: 0,
+            price_updated_at: Some(timestamp),
+            max_probability_move_bps,
+            resolution_evidence: None,
+            claims_started: false,
+            closed: false,
+            featured: false,
+            max_trade_shares,
+            upvotes: 0,
+            key: Some(key),
+            sponsors: Vec::new(),
+            version: state::MARKET_VERSION,
+        };
+        // The creator's seed liquidity is the market's only LP stake
+        let lp = state::LpPosition {
+            market_id,
+            owner: creator,
+            shares: initial_liquidity,
+            fee_checkpoint: market.fees_per_lp_share,
+        };
+
+        let liquidity = market.liquidity();
+        self.record_price_checkpoint(&market).await?;
+        self.save_market(market)?;
+        self.state.markets_by_creator.insert(&(creator, market_id), ())
+            .map_err(|e| format!("Failed to index market: {}", e))?;
+        if let Some(condition) = parent {
+            let mut children = self.state.conditional_markets.get(&condition.market_id)
+                .await
+                .map_err(|e| format!("Failed to read conditional markets: {}", e))?
+                .unwrap_or_default();
+            children.push(market_id);
+            self.state.conditional_markets.insert(&condition.market_id, children)
+                .map_err(|e| format!("Failed to index conditional market: {}", e))?;
+        }
+        self.state.lp_positions.insert(&(creator, market_id), lp)
+            .map_err(|e| format!("Failed to insert LP position: {}", e))?;
+        self.record_lp_flow(creator, market_id, initial_liquidity, true, timestamp).await?;
+        self.index_categories(market_id, &categories).await?;
+        self.adjust_tvl(initial_liquidity, true);
+        self.adjust_open_interest(initial_liquidity, true);
+        self.adjust_contract_balance(initial_liquidity, true);
+        adjust_total(&mut self.state.total_active_liquidity, liquidity, true);
+        let active = *self.state.active_market_count.get();
+        self.state.active_market_count.set(active + 1);
+
+        self.announce_market(market_id, key, &question, end_time, &categories);
+        self.create_feed_item(creator, FeedItemType::MarketCreated, Some(market_id), question, timestamp).await?;
+
+        Ok((market_id, key))
+    }
+
+    /// Reject a buy of `yes_shares` and `no_shares` by `owner` when they
+    /// created a market closed to its creator, or when it would take them
+    /// past the market's per-side or combined position cap
+    async fn check_position_limits(
+        &self,
+        market: &state::Market,
+        owner: AccountOwner,
+        yes_shares: Amount,
+        no_shares: Amount,
+    ) -> Result<(), String> {
+        if market.creator_trading_disabled && owner == market.creator {
+            return Err(MarketError::CreatorTradingDisabled { market_id: market.id }.into());
+        }
+        if market.max_position_per_account.is_none() && market.max_position.is_none() {
+            return Ok(());
+        }
+        let (yes, no) = self.state.positions.get(&(owner, market.id))
+            .await
+            .map_err(|e| format!("Failed to get position: {}", e))?
+            .map_or((Amount::ZERO, Amount::ZERO), |p| (p.yes_shares, p.no_shares));
+        if let Some(limit) = market.max_position_per_account {
+            for (held, shares) in [(yes, yes_shares), (no, no_shares)] {
+                if shares > Amount::ZERO && held.saturating_add(shares) > limit {
+                    return Err(MarketError::PositionLimitExceeded {
+                        market_id: market.id,
+                        limit,
+                        headroom: limit.saturating_sub(held),
+                    }.into());
+                }
+            }
+        }
+        if let Some(cap) = market.max_position {
+            let held = yes.saturating_add(no);
+            if held.saturating_add(yes_shares).saturating_add(no_shares) > cap {
+                return Err(MarketError::MaxPositionExceeded { market_id: market.id, cap, held }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fee on an AMM trade of `amount`, rounded down
+    fn trading_fee(&self, amount: Amount, fee_bps: u64) -> Result<Amount, String> {
+        Ok(Amount::from_attos(safe_mul_div(u128::from(amount), u128::from(fee_bps), 10_000)?))
+    }
+
+    /// Add `market_id` under each of its categories, case-insensitively
+    async fn index_categories(&mut self, market_id: u64, categories: &[String]) -> Result<(), String> {
+        for key in category_keys(categories) {
+            let mut ids = self.state.markets_by_category.get(&key)
+                .await
+                .map_err(|e| format!("Failed to read category index: {}", e))?
+                .unwrap_or_default();
+            ids.push(market_id);
+            self.state.markets_by_category.insert(&key, ids)
+                .map_err(|e| format!("Failed to update category index: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// ResolveMarket's checks and settlement on one market: settles it,
+    /// records a resolver's vote, or voids a conditional market whose
+    /// parent went the other way, returning the response for it
+    async fn resolve_market(
+        &mut self,
+        market_id: u64,
+        outcome: bool,
+        caller: AccountOwner,
+        evidence: Option<String>,
+        timestamp: Timestamp,
+    ) -> Result<String, String> {
+        let mut market = self.load_market(market_id).await?;
+
+        if let Some(group_id) = market.group_id {
+            return Err(MarketError::GroupMember { market_id, group_id }.into());
+        }
+        let by_admin = self.check_resolver(&market, caller, timestamp)?;
+        match self.parent_condition(&market).await? {
+            Some(true) => {}
+            Some(false) => {
+                self.void_market(&mut market, timestamp).await?;
+                self.settle_bond(&market, false, timestamp).await?;
+                self.save_market(market)?;
+                return Ok("MarketVoided".to_string());
+            }
+            None => {
+                let parent_id = market.parent.map_or(market_id, |p| p.market_id);
+                return Err(MarketError::ParentUnresolved { market_id, parent_id }.into());
+            }
+        }
+        if !market.resolvers.is_empty() {
+            let mut votes = self.state.resolution_votes.get(&market_id)
+                .await
+                .map_err(|e| format!("Failed to read votes on market {}: {}", market_id, e))?
+                .unwrap_or_default();
+            votes.retain(|vote| vote.resolver != caller);
+            votes.push(state::ResolutionVote { resolver: caller, outcome, timestamp });
+            let agreeing = votes.iter().filter(|vote| vote.outcome == outcome).count();
+            self.state.resolution_votes.insert(&market_id, votes)
+                .map_err(|e| format!("Failed to record vote: {}", e))?;
+            if agreeing < usize::from(market.threshold) {
+                return Ok(format!("ResolutionVoteRecorded:{}:{}", agreeing, market.threshold));
+            }
+        }
+        let settlement = self.settle_market(market, outcome, caller, by_admin, evidence, timestamp).await?;
+
+        Ok(format!(
+            "MarketResolved:{}:{}:{}:{}:{}",
+            outcome,
+            settlement.total_payout,
+            settlement.winning_shares,
+            settlement.payout_per_share,
+            settlement.eligible_positions
+        ))
+    }
+
+    /// Whether `caller` may resolve `market` at `timestamp`: its creator once
+    /// it has ended (or early, if allowed), the admin only after the
+    /// resolution delay. Returns whether the admin is resolving.
+    fn check_resolver(&self, market: &state::Market, caller: AccountOwner, timestamp: Timestamp) -> Result<bool, String> {
+        let market_id = market.id;
+        // Resolutions stand; only the admin's OverrideResolution can
+        // replace one
+        if market.resolved {
+            return Err(MarketError::AlreadyResolved { market_id }.into());
+        }
+        // With resolvers, only their votes count; the admin has no override
+        let by_admin = if market.resolvers.is_empty() {
+            market.creator != caller
+        } else if market.resolvers.contains(&caller) {
+            false
+        } else {
+            return Err(MarketError::NotAuthorized("only the market's resolvers can vote on its outcome".to_string()).into());
+        };
+        if by_admin {
+            let config = self.state.config.get();
+            if caller != config.admin {
+                return Err(MarketError::NotAuthorized("only the creator or admin can resolve this market".to_string()).into());
+            }
+            let delay = TimeDelta::from_secs(config.admin_resolution_delay_secs);
+            if timestamp < market.end_time.saturating_add(delay) {
+                return Err(MarketError::AdminResolutionTooEarly {
+                    market_id,
+                    delay_secs: config.admin_resolution_delay_secs,
+                }.into());
+            }
+        }
+        if effective_status(market, timestamp) == EffectiveStatus::Open && !market.early_resolution_allowed {
+            return Err(MarketError::ResolutionBeforeEnd {
+                market_id,
+                end_micros: market.end_time.micros(),
+            }.into());
+        }
+        Ok(by_admin)
+    }
+
+    /// Record `outcome` and the resolver's `evidence` on a market
+    /// `check_resolver` accepted and release its escrow from the locked total
+    async fn settle_market(
+        &mut self,
+        mut market: state::Market,
+        outcome: bool,
+        caller: AccountOwner,
+        by_admin: bool,
+        evidence: Option<String>,
+        timestamp: Timestamp,
+    ) -> Result<Settlement, String> {
+        let market_id = market.id;
+        market.accumulate_price(timestamp);
+        market.resolved = true;
+        market.outcome = Some(outcome);
+        market.resolution_time = Some(timestamp);
+        market.resolved_by = Some(caller);
+        market.resolution_evidence = evidence.clone();
+        let claim_window = TimeDelta::from_secs(self.state.config.get().claim_window_secs);
+        market.claim_deadline = Some(timestamp.saturating_add(claim_window));
+
+        // Nobody but the AMM seed holds the winning side: refund everyone
+        let winning_total = if outcome { market.total_yes_shares } else { market.total_no_shares };
+        market.refund_mode = winning_total.saturating_sub(market.seed_shares) == Amount::ZERO;
+        let settlement = self.settlement(&market).await?;
+
+        // Resolved escrow is no longer locked in live markets, though it
+        // stays open interest until paid out
+        self.adjust_tvl(market.escrow, false);
+        adjust_total(&mut self.state.total_active_liquidity, market.liquidity(), false);
+        self.count_resolution(&market);
+        self.settle_trader_stats(&market, None).await?;
+        self.cancel_pending_triggers(market_id, timestamp).await?;
+        self.close_incentives(&market, timestamp).await?;
+        self.settle_lp_ledgers(&market).await?;
+
+        self.save_market(market)?;
+
+        self.update_combos_for_market(market_id, outcome).await?;
+
+        self.emit_event(MarketEvent::MarketResolved {
+            market_id,
+            outcome,
+            resolved_by: caller,
+            evidence,
+            timestamp,
+        });
+        if by_admin {
+            self.emit_event(MarketEvent::AdminResolved {
+                market_id,
+                admin: caller,
+                outcome,
+                timestamp,
+            });
+        }
+        Ok(settlement)
+    }
+
+    /// What the claims on resolved `market` will pay. Eligible positions are
+    /// counted by reading the market's positions here rather than by a
+    /// holder counter kept while trading: each claim rounds its payout down
+    /// on its own, so only the positions themselves give the exact total.
+    async fn settlement(&self, market: &state::Market) -> Result<Settlement, String> {
+        let outcome = market.outcome.unwrap_or_default();
+        let claims: Vec<_> = self.market_positions(market.id).await?
+            .into_iter()
+            .filter(|(_, position)| !position.claimed)
+            .map(|(_, position)| {
+                let winning = if outcome { position.yes_shares } else { position.no_shares };
+                (winning, position.yes_cost_basis.saturating_add(position.no_cost_basis))
+            })
+            .collect();
+
+        let mut settlement = Settlement {
+            total_payout: Amount::ZERO,
+            winning_shares: Amount::ZERO,
+            payout_per_share: Amount::ZERO,
+            eligible_positions: 0,
+        };
+        if market.refund_mode {
+            for (_, basis) in claims.into_iter().filter(|(_, basis)| *basis > Amount::ZERO) {
+                settlement.total_payout = settlement.total_payout.saturating_add(basis);
+                settlement.eligible_positions += 1;
+            }
+        } else {
+            let total_winning = u128::from(if outcome { market.total_yes_shares } else { market.total_no_shares });
+            let pot = u128::from(market.yes_pool.saturating_add(market.no_pool));
+            for (winning, _) in claims.into_iter().filter(|(winning, _)| *winning > Amount::ZERO) {
+                let payout = Amount::from_attos(payout_for_shares(u128::from(winning), total_winning, pot)?);
+                settlement.total_payout = settlement.total_payout.saturating_add(payout);
+                settlement.winning_shares = settlement.winning_shares.saturating_add(winning);
+                settlement.eligible_positions += 1;
+            }
+            settlement.payout_per_share = Amount::from_attos(safe_mul_div(u128::from(Amount::ONE), pot, total_winning)?);
+        }
+        // Claims stop at the escrow, however they add up
+        settlement.total_payout = settlement.total_payout.min(market.escrow);
+        Ok(settlement)
+    }
+
+    /// Whether `market`'s parent condition holds: Some(true) once the parent
+    /// resolved as required (always, without a parent), Some(false) once it
+    /// resolved any other way, None while it is unresolved
+    async fn parent_condition(&self, market: &state::Market) -> Result<Option<bool>, String> {
+        let Some(condition) = market.parent else {
+            return Ok(Some(true));
+        };
+        let parent = self.state.markets.get(&condition.market_id)
+            .await
+            .map_err(|e| format!("Failed to read market {}: {}", condition.market_id, e))?;
+        if let Some(parent) = parent {
+            return Ok(parent.resolved.then(|| parent.outcome == Some(condition.outcome)));
+        }
+        // An archived parent was fully settled
+        let archived = self.state.archive.get(&condition.market_id)
+            .await
+            .map_err(|e| format!("Failed to read archived market {}: {}", condition.market_id, e))?
+            .ok_or(MarketError::ParentNotFound { parent_id: condition.market_id })?;
+        Ok(Some(archived.outcome == Some(condition.outcome)))
+    }
+
+    /// Resolve `market` without an outcome, refunding every holder's cost basis
+    async fn void_market(&mut self, market: &mut state::Market, timestamp: Timestamp) -> Result<(), String> {
+        let claim_window = TimeDelta::from_secs(self.state.config.get().claim_window_secs);
+        market.accumulate_price(timestamp);
+        market.resolved = true;
+        market.voided = true;
+        market.refund_mode = true;
+        market.resolution_time = Some(timestamp);
+        market.claim_deadline = Some(timestamp.saturating_add(claim_window));
+        self.adjust_tvl(market.escrow, false);
+        adjust_total(&mut self.state.total_active_liquidity, market.liquidity(), false);
+        self.count_resolution(market);
+        self.settle_trader_stats(market, None).await?;
+        self.cancel_pending_triggers(market.id, timestamp).await?;
+        self.close_incentives(market, timestamp).await?;
+        self.settle_lp_ledgers(market).await
+    }
+
+    /// Settle the creator's bond on a just-voided `market`: forfeit it if
+    /// the voiding is the creator's failing, otherwise release it for them
+    /// to reclaim. A forfeited bond's trader share is split pro rata to the
+    /// cost basis of the unclaimed positions other than the creator's,
+    /// rounding down, and the treasury keeps the rest, so the two parts add
+    /// up to the bond exactly.
+    async fn settle_bond(&mut self, market: &state::Market, forfeit: bool, timestamp: Timestamp) -> Result<(), String> {
+        let Some(mut bond) = self.state.creator_bonds.get(&market.id)
+            .await
+            .map_err(|e| format!("Failed to read creator bond: {}", e))?
+            .filter(|bond| bond.status == BondStatus::Held)
+        else {
+            return Ok(());
+        };
+        if !forfeit {
+            bond.status = BondStatus::Released;
+            return self.state.creator_bonds.insert(&market.id, bond)
+                .map_err(|e| format!("Failed to update creator bond: {}", e));
+        }
+
+        let stakes: Vec<_> = self.market_positions(market.id).await?
+            .into_iter()
+            .filter(|(owner, position)| *owner != market.creator && !position.claimed)
+            .map(|(owner, position)| ((owner, market.id), u128::from(position.yes_cost_basis.saturating_add(position.no_cost_basis))))
+            .filter(|(_, basis)| *basis > 0)
+            .collect();
+        let total_stake = stakes.iter().map(|(_, basis)| *basis).fold(0u128, u128::saturating_add);
+        let share_bps = u128::from(self.state.config.get().bond_trader_share_bps());
+        let for_traders = if total_stake == 0 { 0 } else { safe_mul_div(u128::from(bond.amount), share_bps, 10_000)? };
+
+        let mut to_traders = Amount::ZERO;
+        for (key, basis) in stakes {
+            let share = Amount::from_attos(safe_mul_div(for_traders, basis, total_stake)?);
+            if share > Amount::ZERO {
+                self.state.bond_shares.insert(&key, share)
+                    .map_err(|e| format!("Failed to insert bond share: {}", e))?;
+                to_traders = to_traders.saturating_add(share);
+            }
+        }
+        bond.to_traders = to_traders;
+        bond.to_treasury = bond.amount.saturating_sub(to_traders);
+        bond.status = BondStatus::Forfeited;
+        bond.settled_at = Some(timestamp);
+        adjust_total(&mut self.state.treasury, bond.to_treasury, true);
+        self.emit_event(MarketEvent::BondForfeited {
+            market_id: market.id,
+            creator: bond.creator,
+            to_treasury: bond.to_treasury,
+            to_traders,
+            timestamp,
+        });
+        self.state.creator_bonds.insert(&market.id, bond)
+            .map_err(|e| format!("Failed to update creator bond: {}", e))
+    }
+
+    /// Add a deposit into, or a withdrawal from, `market_id`'s liquidity to
+    /// `owner`'s ledger
+    async fn record_lp_flow(&mut self, owner: AccountOwner, market_id: u64, amount: Amount, deposit: bool, timestamp: Timestamp) -> Result<(), String> {
+        let key = (owner, market_id);
+        let mut ledger = self.state.lp_ledgers.get(&key)
+            .await
+            .map_err(|e| format!("Failed to read LP ledger: {}", e))?
+            .unwrap_or_default();
+        ledger.record(state::LpFlow { amount, deposit, timestamp });
+        self.state.lp_ledgers.insert(&key, ledger)
+            .map_err(|e| format!("Failed to update LP ledger: {}", e))
+    }
+
+    /// Fix on each LP's ledger the share of `market`'s LP value its stake
+    /// could redeem at settlement, rounded down
+    async fn settle_lp_ledgers(&mut self, market: &state::Market) -> Result<(), String> {
+        // Only refunds depend on what the traders paid
+        let mut trader_cost_basis = Amount::ZERO;
+        if market.refund_mode {
+            for (_, position) in self.market_positions(market.id).await? {
+                trader_cost_basis = trader_cost_basis
+                    .saturating_add(position.yes_cost_basis)
+                    .saturating_add(position.no_cost_basis);
+            }
+        }
+        let value = u128::from(market.lp_value(trader_cost_basis));
+
+        for (key, lp) in self.market_lp_positions(market).await? {
+            let share = safe_mul_div(value, u128::from(lp.shares), u128::from(market.total_lp_shares)).unwrap_or(0);
+            let mut ledger = self.state.lp_ledgers.get(&key)
+                .await
+                .map_err(|e| format!("Failed to read LP ledger: {}", e))?
+                .unwrap_or_default();
+            ledger.settled_value = Some(Amount::from_attos(share));
+            self.state.lp_ledgers.insert(&key, ledger)
+                .map_err(|e| format!("Failed to update LP ledger: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// `market`'s LP positions. Only its creator provides liquidity, so
+    /// this reads their one key rather than every market's LPs.
+    async fn market_lp_positions(&self, market: &state::Market) -> Result<Vec<((AccountOwner, u64), state::LpPosition)>, String> {
+        let key = (market.creator, market.id);
+        let lp = self.state.lp_positions.get(&key)
+            .await
+            .map_err(|e| format!("Failed to read LP positions: {}", e))?;
+        Ok(lp.map(|lp| (key, lp)).into_iter().collect())
+    }
+
+    /// Stop `market`'s incentive stream at `timestamp`, now that it has
+    /// settled, and return to the funders whatever its LPs are not owed,
+    /// rounding dust included, split in proportion to what each put in.
+    /// What the LPs are owed stays claimable.
+    async fn close_incentives(&mut self, market: &state::Market, timestamp: Timestamp) -> Result<(), String> {
+        let Some(mut program) = self.state.incentives.get(&market.id)
+            .await
+            .map_err(|e| format!("Failed to read incentives: {}", e))?
+        else {
+            return Ok(());
+        };
+        program.accrue(market.total_lp_shares, timestamp);
+
+        let mut owed = Amount::ZERO;
+        for (key, lp) in self.market_lp_positions(market).await? {
+            let checkpoint = self.state.incentive_checkpoints.get(&key)
+                .await
+                .map_err(|e| format!("Failed to read incentive checkpoint: {}", e))?
+                .unwrap_or(0);
+            owed = owed.saturating_add(program.pending(lp.shares, checkpoint));
+        }
+
+        let returned = program.budget.saturating_sub(program.claimed).saturating_sub(owed);
+        program.returned = Some(returned);
+        let shares = match market.sponsors.as_slice() {
+            [] => vec![(program.funder, returned)],
+            sponsors => split_return(sponsors, returned)?,
+        };
+        self.state.incentives.insert(&market.id, program)
+            .map_err(|e| format!("Failed to update incentives: {}", e))?;
+        self.adjust_contract_balance(returned, false);
+        for (funder, amount) in shares {
+            self.emit_event(MarketEvent::IncentivesReturned {
+                market_id: market.id,
+                funder,
+                amount,
+                timestamp,
+            });
+        }
+        Ok(())
+    }
+
+    /// Mark an ended, unresolved market closed, move it from the open to
+    /// the closed count and announce it
+    fn close_market(&mut self, market: &mut state::Market, closed_by: AccountOwner, timestamp: Timestamp) {
+        market.closed = true;
+        let closed = *self.state.closed_market_count.get();
+        self.state.closed_market_count.set(closed + 1);
+        self.emit_event(MarketEvent::MarketClosed { market_id: market.id, closed_by, timestamp });
+    }
+
+    /// Append `market`'s price accumulator reading, dropping the oldest
+    /// beyond `MAX_PRICE_CHECKPOINTS`, and to its untrimmed price log
+    async fn record_price_checkpoint(&mut self, market: &state::Market) -> Result<(), String> {
+        let mut checkpoints = self.state.price_checkpoints.get(&market.id)
+            .await
+            .map_err(|e| format!("Failed to read price checkpoints: {}", e))?
+            .unwrap_or_default();
+        let timestamp = market.price_updated_at.unwrap_or(market.created_at);
+        // Trades sharing a block timestamp only leave the last price
+        if checkpoints.last().is_some_and(|last: &PriceCheckpoint| last.timestamp == timestamp) {
+            checkpoints.pop();
+        }
+        let checkpoint = PriceCheckpoint {
+            timestamp,
+            cumulative: market.price_cumulative,
+            yes_price_bps: market.yes_price_bps(),
+        };
+        let mut n = self.state.price_log_counts.get(&market.id)
+            .await
+            .map_err(|e| format!("Failed to read price log count: {}", e))?
+            .unwrap_or(0);
+        let last = match n.checked_sub(1) {
+            Some(last) => self.state.price_log.get(&(market.id, last))
+                .await
+                .map_err(|e| format!("Failed to read price log: {}", e))?,
+            None => None,
+        };
+        // Likewise the log keeps one entry per block timestamp
+        if last.is_some_and(|last| last.timestamp == timestamp) {
+            n -= 1;
+        }
+        self.state.price_log.insert(&(market.id, n), checkpoint.clone())
+            .map_err(|e| format!("Failed to log price: {}", e))?;
+        self.state.price_log_counts.insert(&market.id, n + 1)
+            .map_err(|e| format!("Failed to update price log count: {}", e))?;
+        checkpoints.push(checkpoint);
+        if checkpoints.len() > MAX_PRICE_CHECKPOINTS {
+            checkpoints.drain(..checkpoints.len() - MAX_PRICE_CHECKPOINTS);
+        }
+        self.state.price_checkpoints.insert(&market.id, checkpoints)
+            .map_err(|e| format!("Failed to record price checkpoint: {}", e))
+    }
+
+    /// Move a market that has just resolved from the active count, and the
+    /// closed one if it was closed, to the resolved count, and the voided
+    /// one if it was voided
+    fn count_resolution(&mut self, market: &state::Market) {
+        let active = *self.state.active_market_count.get();
+        self.state.active_market_count.set(active.saturating_sub(1));
+        if market.closed {
+            let closed = *self.state.closed_market_count.get();
+            self.state.closed_market_count.set(closed.saturating_sub(1));
+        }
+        let resolved = *self.state.resolved_market_count.get();
+        self.state.resolved_market_count.set(resolved + 1);
+        if market.voided {
+            let voided = *self.state.voided_market_count.get();
+            self.state.voided_market_count.set(voided + 1);
+        }
+    }
+
+    /// Add traded volume to the running total, the hour and day it happened
+    /// in and the market's `VolumeRing`
+    async fn record_volume(&mut self, market_id: u64, amount: Amount, timestamp: Timestamp) -> Result<(), String> {
+        let total = *self.state.total_volume.get();
+        self.state.total_volume.set(total.saturating_add(amount));
+        let hour = hour_index(timestamp);
+        let bucket = self.state.hourly_volume.get(&hour)
+            .await
+            .map_err(|e| format!("Failed to read hourly volume: {}", e))?
+            .unwrap_or(Amount::ZERO);
+        self.state.hourly_volume.insert(&hour, bucket.saturating_add(amount))
+            .map_err(|e| format!("Failed to update hourly volume: {}", e))?;
+        let day = day_index(timestamp);
+        let bucket = self.state.volume_by_day.get(&day)
+            .await
+            .map_err(|e| format!("Failed to read daily volume: {}", e))?;
+        if bucket.is_none() {
+            // The day's first trade: drop buckets that fell out of the window
+            self.prune_volume_by_day(day).await?;
+        }
+        let bucket = bucket.unwrap_or(Amount::ZERO);
+        self.state.volume_by_day.insert(&day, bucket.saturating_add(amount))
+            .map_err(|e| format!("Failed to update daily volume: {}", e))?;
+        let mut ring = self.state.market_volume.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read market volume: {}", e))?
+            .unwrap_or_default();
+        ring.add(timestamp, amount);
+        self.state.market_volume.insert(&market_id, ring)
+            .map_err(|e| format!("Failed to update market volume: {}", e))
+    }
+
+    /// Remove daily volume buckets older than `volume_history_start(today)`
+    async fn prune_volume_by_day(&mut self, today: u64) -> Result<(), String> {
+        let start = volume_history_start(today);
+        let stale: Vec<u64> = self.state.volume_by_day.indices()
+            .await
+            .map_err(|e| format!("Failed to list daily volume: {}", e))?
+            .into_iter()
+            .filter(|day| *day < start)
+            .collect();
+        for day in stale {
+            self.state.volume_by_day.remove(&day)
+                .map_err(|e| format!("Failed to prune daily volume: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Append to the global recent-trades queue, dropping the oldest entries
+    /// beyond the configured capacity
+    fn record_recent_trade(&mut self, trade: RecentTrade) {
+        let capacity = self.state.config.get().recent_trades_capacity() as usize;
+        self.state.recent_trades.push_back(trade);
+        while self.state.recent_trades.count() > capacity {
+            self.state.recent_trades.delete_front();
+        }
+    }
+
+    /// Move the total-value-locked register by `amount`.
+    fn adjust_tvl(&mut self, amount: Amount, increase: bool) {
+        adjust_total(&mut self.state.total_value_locked, amount, increase);
+    }
+
+    /// Add escrow paid into a market to the open interest, or take escrow
+    /// paid out of one from it
+    fn adjust_open_interest(&mut self, amount: Amount, increase: bool) {
+        adjust_total(&mut self.state.total_open_interest, amount, increase);
+    }
+
+    /// Add tokens paid in to the tracked collateral balance, or take tokens
+    /// paid out from it
+    fn adjust_contract_balance(&mut self, amount: Amount, increase: bool) {
+        adjust_total(&mut self.state.contract_balance, amount, increase);
+    }
+
+    /// Refuse a payout of `payout` to `owner` that the collateral balance
+    /// cannot cover, which only a bookkeeping bug allows, and raise an alert
+    /// about it. Call before writing anything, so the claim stays untouched
+    /// and can be retried once the admin has reconciled the balance.
+    fn check_payout(&mut self, market_id: u64, owner: AccountOwner, payout: Amount, timestamp: Timestamp) -> Result<(), String> {
+        let balance = *self.state.contract_balance.get();
+        if payout <= balance {
+            return Ok(());
+        }
+        self.emit_event(MarketEvent::PayoutRefused { market_id, owner, payout, balance, timestamp });
+        Err(MarketError::PayoutExceedsBalance { market_id, payout, balance }.into())
+    }
+
+    /// Replace a live market's pools going into a trade, `before`, with the
+    /// ones it left in the active liquidity total
+    fn move_active_liquidity(&mut self, before: (Amount, Amount), after: (Amount, Amount)) {
+        let (before, after) = (before.0.saturating_add(before.1), after.0.saturating_add(after.1));
+        let total = &mut self.state.total_active_liquidity;
+        if after >= before {
+            adjust_total(total, after.saturating_sub(before), true);
+        } else {
+            adjust_total(total, before.saturating_sub(after), false);
+        }
+    }
+
+    /// Count `trader` once among all traders and once in each category of
+    /// `market_id`, whichever markets they trade in, and add a trade of
+    /// `volume` to their stats
+    async fn record_trader(&mut self, trader: AccountOwner, market_id: u64, volume: Amount) -> Result<(), String> {
+        let traded_before = self.state.traded_markets.contains_key(&(trader, market_id))
+            .await
+            .map_err(|e| format!("Failed to read traded markets: {}", e))?;
+        if !traded_before {
+            self.state.traded_markets.insert(&(trader, market_id), ())
+                .map_err(|e| format!("Failed to update traded markets: {}", e))?;
+        }
+        self.update_trader_stats(trader, |stats| {
+            stats.volume = stats.volume.saturating_add(volume);
+            stats.trade_count += 1;
+            stats.markets_traded += u64::from(!traded_before);
+        }).await?;
+        self.record_trader_day(trader, volume, 0, 1).await?;
+
+        let seen = self.state.seen_traders.contains_key(&trader)
+            .await
+            .map_err(|e| format!("Failed to read trader index: {}", e))?;
+        if !seen {
+            self.state.seen_traders.insert(&trader, ())
+                .map_err(|e| format!("Failed to update trader index: {}", e))?;
+            let count = *self.state.unique_traders.get();
+            self.state.unique_traders.set(count + 1);
+        }
+        let market = self.load_market(market_id).await?;
+        for category in category_keys(&market.categories) {
+            let key = (category, trader);
+            let seen = self.state.category_traders.contains_key(&key)
+                .await
+                .map_err(|e| format!("Failed to read category traders: {}", e))?;
+            if seen {
+                continue;
+            }
+            self.state.category_traders.insert(&key, ())
+                .map_err(|e| format!("Failed to update category traders: {}", e))?;
+            let count = self.state.category_trader_counts.get(&key.0)
+                .await
+                .map_err(|e| format!("Failed to read category trader count: {}", e))?
+                .unwrap_or(0);
+            self.state.category_trader_counts.insert(&key.0, count + 1)
+                .map_err(|e| format!("Failed to update category trader count: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Announce a new market to the registry chain, if one is configured
+    /// and it is not this chain.
+    fn announce_market(&mut self, market_id: u64, market_key: MarketKey, question: &str, end_time: Timestamp, categories: &[String]) {
+        let Some(registry) = self.runtime.application_parameters().registry_chain else {
+            return;
+        };
+        let origin_chain = self.runtime.chain_id();
+        if registry == origin_chain {
+            return;
+        }
+        let message = Message::MarketAnnouncement {
+            market_id,
+            origin_chain,
+            market_key,
+            question: question.to_string(),
+            end_time,
+            categories: categories.to_vec(),
+        };
+        self.runtime.prepare_message(message).with_tracking().send_to(registry);
+    }
+
+    /// Record `position` as in flight and send it to `destination`
+    fn send_position(
+        &mut self,
+        market_chain: ChainId,
+        destination: ChainId,
+        market_key: Option<MarketKey>,
+        position: state::Position,
+        timestamp: Timestamp,
+    ) -> Result<u64, String> {
+        let transfer_id = allocate_id(&mut self.state.next_transfer_id)
+            .ok_or(MarketError::IdsExhausted { kind: "transfer" })?;
+        let expires_at = timestamp.saturating_add(TimeDelta::from_secs(POSITION_TRANSFER_TIMEOUT_SECS));
+        let transfer = state::PositionTransfer {
+            transfer_id,
+            market_chain,
+            destination,
+            position: position.clone(),
+            sent_at: timestamp,
+            expires_at,
+        };
+        self.state.outgoing_transfers.insert(&transfer_id, transfer)
+            .map_err(|e| format!("Failed to record transfer: {}", e))?;
+        let message = Message::PositionTransfer { transfer_id, market_chain, market_key, position, expires_at };
+        self.runtime.prepare_message(message).with_tracking().send_to(destination);
+        Ok(transfer_id)
+    }
+
+    /// Answer transfer `transfer_id` from `origin`: the answer already
+    /// recorded for a redelivery, else whether `accept_transfer` credits it,
+    /// recorded. Returns why it was refused, or None once credited.
+    async fn receive_transfer(
+        &mut self,
+        origin: ChainId,
+        transfer_id: u64,
+        market_chain: ChainId,
+        market_key: Option<MarketKey>,
+        position: state::Position,
+        expires_at: Timestamp,
+    ) -> Result<Option<String>, String> {
+        let key = (origin, transfer_id);
+        if let Some(refusal) = self.state.received_transfers.get(&key)
+            .await
+            .map_err(|e| format!("Failed to read received transfers: {}", e))?
+        {
+            return Ok(refusal);
+        }
+        let refusal = self.accept_transfer(origin, market_chain, market_key, position, expires_at).await?;
+        self.state.received_transfers.insert(&key, refusal.clone())
+            .map_err(|e| format!("Failed to record transfer: {}", e))?;
+        Ok(refusal)
+    }
+
+    /// Credit a position transferred from `origin`, if it may land here:
+    /// on time, and either onto a market of this chain with the key it
+    /// names or, straight from its market's chain, into
+    /// `imported_positions`. Returns why it was refused, or None once
+    /// credited.
+    async fn accept_transfer(
+        &mut self,
+        origin: ChainId,
+        market_chain: ChainId,
+        market_key: Option<MarketKey>,
+        position: state::Position,
+        expires_at: Timestamp,
+    ) -> Result<Option<String>, String> {
+        if self.runtime.system_time() > expires_at {
+            return Ok(Some(format!("arrived after it expired at {}", expires_at.micros())));
+        }
+        let market_id = position.market_id;
+        if market_chain == self.runtime.chain_id() {
+            let market = self.state.markets.get(&market_id)
+                .await
+                .map_err(|e| format!("Failed to get market: {}", e))?;
+            let Some(market) = market else {
+                return Ok(Some(format!("market {} is not on this chain", market_id)));
+            };
+            if market_key.is_some_and(|key| market.key != Some(key)) {
+                return Ok(Some(format!("market {} on this chain is not the market the position was taken on", market_id)));
+            }
+            let mut held = self.position_or_empty(position.owner, market_id).await?;
+            held.merge(&position);
+            self.save_position(held).await?;
+            return Ok(None);
+        }
+        // Positions only ever move one hop away from their market
+        if origin != market_chain {
+            return Ok(Some("positions can only be migrated from their market's chain".to_string()));
+        }
+        if let Some(key) = market_key {
+            self.state.imported_market_keys.insert(&(market_chain, market_id), key)
+                .map_err(|e| format!("Failed to record imported market key: {}", e))?;
+        }
+        self.import_position(market_chain, position).await?;
+        Ok(None)
+    }
+
+    /// Put back a position whose transfer was refused or bounced. Does
+    /// nothing once the transfer is settled, so repeated answers are
+    /// harmless.
+    async fn restore_transfer(&mut self, transfer_id: u64) -> Result<(), String> {
+        let Some(transfer) = self.state.outgoing_transfers.get(&transfer_id)
+            .await
+            .map_err(|e| format!("Failed to read transfer: {}", e))?
+        else {
+            return Ok(());
+        };
+        // Credited before it is settled, so a failure leaves it to retry
+        let position = transfer.position;
+        if transfer.market_chain == self.runtime.chain_id() {
+            let mut held = self.position_or_empty(position.owner, position.market_id).await?;
+            held.merge(&position);
+            self.save_position(held).await?;
+        } else {
+            self.import_position(transfer.market_chain, position).await?;
+        }
+        self.state.outgoing_transfers.remove(&transfer_id)
+            .map_err(|e| format!("Failed to settle transfer: {}", e))
+    }
+
+    /// Add `position` to what this chain holds of `market_chain`'s market
+    async fn import_position(&mut self, market_chain: ChainId, position: state::Position) -> Result<(), String> {
+        let key = (market_chain, position.market_id, position.owner);
+        let held = match self.state.imported_positions.get(&key)
+            .await
+            .map_err(|e| format!("Failed to get imported position: {}", e))?
+        {
+            Some(mut held) => {
+                held.merge(&position);
+                held
+            }
+            None => position,
+        };
+        self.state.imported_positions.insert(&key, held)
+            .map_err(|e| format!("Failed to store imported position: {}", e))
+    }
+
+    /// Trade events `market_id` has emitted so far
+    async fn trade_count(&self, market_id: u64) -> Result<u64, String> {
+        Ok(self.state.trade_seqs.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read trade sequence: {}", e))?
+            .unwrap_or(0))
+    }
+
+    /// Hand out `market_id`'s next trade sequence number
+    async fn next_trade_seq(&mut self, market_id: u64) -> Result<u64, String> {
+        let seq = self.trade_count(market_id).await?;
+        self.state.trade_seqs.insert(&market_id, seq + 1)
+            .map_err(|e| format!("Failed to update trade sequence: {}", e))?;
+        Ok(seq)
+    }
+
+    /// Emit an event on the market stream. Call only after every fallible
+    /// step of the operation has succeeded — events cannot be retracted.
+    /// `check_payout`'s alert is the one exception.
+    fn emit_event(&mut self, event: MarketEvent) {
+        self.runtime.emit(StreamName::from(MARKET_EVENT_STREAM), &event);
+    }
+
+    /// `owner`'s position on `market_id`, or an empty one to open
+    async fn position_or_empty(&self, owner: AccountOwner, market_id: u64) -> Result<state::Position, String> {
+        let mut position = self.state.positions.get(&(owner, market_id))
+            .await
+            .map_err(|e| format!("Failed to get position: {}", e))?
+            .unwrap_or(state::Position {
+                market_id,
+                owner,
+                yes_shares: Amount::ZERO,
+                no_shares: Amount::ZERO,
+                claimed: false,
+                yes_cost_basis: Amount::ZERO,
+                no_cost_basis: Amount::ZERO,
+                realized_pnl: 0,
+                version: state::POSITION_VERSION,
+            });
+        position.upgrade();
+        Ok(position)
+    }
+
+    async fn update_position(
+        &mut self,
+        owner: AccountOwner,
+        market_id: u64,
+        is_yes: bool,
+        shares: Amount,
+        amount: Amount,
+        is_buy: bool,
+    ) -> Result<Amount, String> {
+        let mut position = self.position_or_empty(owner, market_id).await?;
+
+        let (held, basis) = if is_yes {
+            (&mut position.yes_shares, &mut position.yes_cost_basis)
+        } else {
+            (&mut position.no_shares, &mut position.no_cost_basis)
+        };
+
+        let moved = if is_buy {
+            // `amount` is the cost paid
+            *held = held.saturating_add(shares);
+            *basis = basis.saturating_add(amount);
+            amount
+        } else {
+            // Relieve basis in proportion to the shares sold
+            let relief = if *held == Amount::ZERO {
+                *basis
+            } else {
+                let sold = u128::from(shares).min(u128::from(*held));
+                Amount::from_attos(safe_mul_div(u128::from(*basis), sold, u128::from(*held))?)
+            };
+            *held = held.saturating_sub(shares);
+            *basis = basis.saturating_sub(relief);
+            relief
+        };
+
+        self.save_position(position).await?;
+
+        Ok(moved)
+    }
+
+    /// Write `position`, listing its market under its owner and its owner
+    /// under the market if it is new
+    async fn save_position(&mut self, position: state::Position) -> Result<(), String> {
+        let (owner, market_id) = (position.owner, position.market_id);
+        let was_open = self.state.positions.get(&(owner, market_id))
+            .await
+            .map_err(|e| format!("Failed to get position: {}", e))?
+            .is_some_and(|held| !held.is_empty());
+        self.track_open_position(owner, market_id, was_open, !position.is_empty()).await?;
+        let mut ids = self.state.positions_by_owner.get(&owner)
+            .await
+            .map_err(|e| format!("Failed to read position index: {}", e))?
+            .unwrap_or_default();
+        if let Err(index) = ids.binary_search(&market_id) {
+            ids.insert(index, market_id);
+            self.state.positions_by_owner.insert(&owner, ids)
+                .map_err(|e| format!("Failed to update position index: {}", e))?;
+        }
+        let mut holders = self.state.market_holders.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read holder index: {}", e))?
+            .unwrap_or_default();
+        if let Err(index) = holders.binary_search(&owner) {
+            holders.insert(index, owner);
+            self.state.market_holders.insert(&market_id, holders)
+                .map_err(|e| format!("Failed to update holder index: {}", e))?;
+        }
+        self.state.positions.insert(&(owner, market_id), position)
+            .map_err(|e| format!("Failed to update position: {}", e))
+    }
+
+    /// Delete `owner`'s position on `market_id` and its index entries
+    async fn remove_position(&mut self, owner: AccountOwner, market_id: u64) -> Result<(), String> {
+        let was_open = self.state.positions.get(&(owner, market_id))
+            .await
+            .map_err(|e| format!("Failed to get position: {}", e))?
+            .is_some_and(|held| !held.is_empty());
+        self.track_open_position(owner, market_id, was_open, false).await?;
+        self.state.positions.remove(&(owner, market_id))
+            .map_err(|e| format!("Failed to remove position: {}", e))?;
+        let mut ids = self.state.positions_by_owner.get(&owner)
+            .await
+            .map_err(|e| format!("Failed to read position index: {}", e))?
+            .unwrap_or_default();
+        ids.retain(|id| *id != market_id);
+        let update = if ids.is_empty() {
+            self.state.positions_by_owner.remove(&owner)
+        } else {
+            self.state.positions_by_owner.insert(&owner, ids)
+        };
+        update.map_err(|e| format!("Failed to update position index: {}", e))?;
+        let mut holders = self.state.market_holders.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read holder index: {}", e))?
+            .unwrap_or_default();
+        holders.retain(|holder| *holder != owner);
+        let update = if holders.is_empty() {
+            self.state.market_holders.remove(&market_id)
+        } else {
+            self.state.market_holders.insert(&market_id, holders)
+        };
+        update.map_err(|e| format!("Failed to update holder index: {}", e))
+    }
+
+    /// Every position on `market_id`, by its holder, read through the
+    /// market's holder index
+    async fn market_positions(&self, market_id: u64) -> Result<Vec<(AccountOwner, state::Position)>, String> {
+        let holders = self.state.market_holders.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read holder index: {}", e))?
+            .unwrap_or_default();
+        let keys: Vec<_> = holders.iter().map(|owner| (*owner, market_id)).collect();
+        let positions = self.state.positions.multi_get(&keys)
+            .await
+            .map_err(|e| format!("Failed to read positions: {}", e))?;
+        Ok(holders.into_iter()
+            .zip(positions)
+            .filter_map(|(owner, position)| Some((owner, position?)))
+            .collect())
+    }
+
+    /// Add a realized gain or loss to `owner`'s position and trader stats
+    async fn record_realized_pnl(&mut self, owner: AccountOwner, market_id: u64, pnl: i128) -> Result<(), String> {
+        let key = (owner, market_id);
+        if let Some(mut position) = self.state.positions.get(&key)
+            .await
+            .map_err(|e| format!("Failed to get position: {}", e))?
+        {
+            position.realized_pnl = position.realized_pnl.saturating_add(pnl);
+            position.upgrade();
+            self.state.positions.insert(&key, position)
+                .map_err(|e| format!("Failed to update position: {}", e))?;
+        }
+        self.add_trader_pnl(owner, pnl).await
+    }
+
+    async fn add_trader_pnl(&mut self, owner: AccountOwner, pnl: i128) -> Result<(), String> {
+        self.update_trader_stats(owner, |stats| stats.realized_pnl = stats.realized_pnl.saturating_add(pnl)).await?;
+        self.record_trader_day(owner, Amount::ZERO, pnl, 0).await
+    }
+
+    /// Add to `owner`'s trading today, which windowed rankings sum
+    async fn record_trader_day(&mut self, owner: AccountOwner, volume: Amount, realized_pnl: i128, trades: u64) -> Result<(), String> {
+        let mut days = self.state.trader_days.get(&owner)
+            .await
+            .map_err(|e| format!("Failed to read trader days: {}", e))?
+            .unwrap_or_default();
+        days.add(self.runtime.system_time(), volume, realized_pnl, trades);
+        self.state.trader_days.insert(&owner, days)
+            .map_err(|e| format!("Failed to update trader days: {}", e))
+    }
+
+    async fn update_trader_stats(&mut self, owner: AccountOwner, update: impl FnOnce(&mut state::TraderStats)) -> Result<(), String> {
+        let mut stats = self.state.trader_stats.get(&owner)
+            .await
+            .map_err(|e| format!("Failed to read trader stats: {}", e))?
+            .unwrap_or_default();
+        update(&mut stats);
+        self.state.trader_stats.insert(&owner, stats)
+            .map_err(|e| format!("Failed to update trader stats: {}", e))
+    }
+
+    /// Count `owner`'s position on `market_id` in or out of their open
+    /// positions as it starts or stops holding shares. Resolution closes
+    /// every position, so what happens to one afterwards does not count.
+    async fn track_open_position(&mut self, owner: AccountOwner, market_id: u64, was_open: bool, is_open: bool) -> Result<(), String> {
+        if was_open == is_open {
+            return Ok(());
+        }
+        let live = self.state.markets.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read market {}: {}", market_id, e))?
+            .is_some_and(|market| !market.resolved);
+        if !live {
+            return Ok(());
+        }
+        self.update_trader_stats(owner, |stats| {
+            stats.open_positions = if is_open { stats.open_positions + 1 } else { stats.open_positions.saturating_sub(1) };
+        }).await
+    }
+
+    /// Close every position on `market` as it resolves, counting the market
+    /// won for holders of the winning side and lost for everyone else
+    /// holding shares; voided, it is neither. Given the outcome an override
+    /// replaces, holders whose result it flips are moved across instead.
+    async fn settle_trader_stats(&mut self, market: &state::Market, overridden: Option<bool>) -> Result<(), String> {
+        let holders: Vec<_> = self.market_positions(market.id).await?
+            .into_iter()
+            .filter(|(_, position)| !position.is_empty())
+            .map(|(owner, position)| (owner, position.yes_shares, position.no_shares))
+            .collect();
+
+        let won = |outcome: bool, yes: Amount, no: Amount| (if outcome { yes } else { no }) > Amount::ZERO;
+        let outcome = (!market.voided).then(|| market.outcome.unwrap_or_default());
+        for (owner, yes, no) in holders {
+            let result = outcome.map(|outcome| won(outcome, yes, no));
+            let previous = overridden.map(|outcome| won(outcome, yes, no));
+            if previous.is_some() && previous == result {
+                continue;
+            }
+            self.update_trader_stats(owner, |stats| {
+                match previous {
+                    Some(true) => stats.markets_won = stats.markets_won.saturating_sub(1),
+                    Some(false) => stats.markets_lost = stats.markets_lost.saturating_sub(1),
+                    None => stats.open_positions = stats.open_positions.saturating_sub(1),
+                }
+                match result {
+                    Some(true) => stats.markets_won += 1,
+                    Some(false) => stats.markets_lost += 1,
+                    None => {}
+                }
+            }).await?;
+        }
+        Ok(())
+    }
+
+    /// Sell `sale.shares` of `seller`'s side to the AMM, checking the sale
+    /// before writing anything
+    async fn sell_to_amm(&mut self, seller: AccountOwner, sale: AmmSale, timestamp: Timestamp) -> Result<AmmReceipt, String> {
+        let AmmSale { market_id, is_yes, shares, min_proceeds, deadline } = sale;
+        let mut market = self.load_market(market_id).await?;
+
+        // Exits close with entries: once the outcome may be known, the
+        // losing side must not be sold back at trading prices
+        check_open(&market, timestamp)?;
+
+        self.state.config.get().check_trade_shares(&market, shares)?;
+        let position = self.position_or_empty(seller, market_id).await?;
+        let held = if is_yes { position.yes_shares } else { position.no_shares };
+        if shares > held {
+            return Err(MarketError::InsufficientBalance { needed: shares, available: held }.into());
+        }
+
+        let (gross, fee, fee_bps) = self.quote_sale(&market, seller, is_yes, shares, timestamp).await?;
+        let proceeds = gross.saturating_sub(fee);
+
+        if proceeds < min_proceeds {
+            let (pool_in, pool_out) = if is_yes {
+                (market.yes_pool, market.no_pool)
+            } else {
+                (market.no_pool, market.yes_pool)
+            };
+            return Err(format!(
+                "Proceeds {} below minimum {} (pool_in={}, pool_out={}, shares={})",
+                proceeds, min_proceeds, pool_in, pool_out, shares
+            ));
+        }
+        if gross > market.escrow {
+            return Err(format!(
+                "Proceeds {} exceed market {} escrow {}",
+                gross, market_id, market.escrow
+            ));
+        }
+        self.check_payout(market_id, seller, proceeds, timestamp)?;
+
+        market.accumulate_price(timestamp);
+        let pools_before = (market.yes_pool, market.no_pool);
+        if is_yes {
+            market.yes_pool = market.yes_pool.saturating_add(shares);
+            market.no_pool = market.no_pool.saturating_sub(gross);
+            market.total_yes_shares = market.total_yes_shares.saturating_sub(shares);
+        } else {
+            market.no_pool = market.no_pool.saturating_add(shares);
+            market.yes_pool = market.yes_pool.saturating_sub(gross);
+            market.total_no_shares = market.total_no_shares.saturating_sub(shares);
+        }
+        check_pool_product(&market, pools_before)?;
+
+        // The fee leaves escrow with the proceeds but stays with the LPs
+        market.volume = market.volume.saturating_add(gross);
+        market.escrow = market.escrow.saturating_sub(gross);
+        market.accrue_fee(fee);
+        self.adjust_tvl(gross, false);
+        self.adjust_open_interest(gross, false);
+        self.adjust_contract_balance(proceeds, false);
+        let new_yes_price_bps = market.yes_price_bps();
+        let new_no_price_bps = market.no_price_bps();
+        let (yes_pool, no_pool) = (market.yes_pool, market.no_pool);
+        self.move_active_liquidity(pools_before, (yes_pool, no_pool));
+        let question = market.question.clone();
+        self.record_price_checkpoint(&market).await?;
+        self.save_market(market)?;
+        self.record_recent_trade(RecentTrade {
+            market_id,
+            question,
+            trader: seller,
+            side: OrderSide::Sell,
+            is_yes,
+            shares,
+            amount: proceeds,
+            yes_price_bps: new_yes_price_bps,
+            timestamp,
+        });
+
+        self.record_trader(seller, market_id, gross).await?;
+        let relief = self.update_position(seller, market_id, is_yes, shares, proceeds, false).await?;
+        let realized_pnl = pnl(proceeds.into(), relief.into());
+        self.record_realized_pnl(seller, market_id, realized_pnl).await?;
+
+        let position = self.position_or_empty(seller, market_id).await?;
+        let trade_seq = self.next_trade_seq(market_id).await?;
+        self.emit_event(MarketEvent::TradeExecuted {
+            market_id,
+            trader: seller,
+            side: OrderSide::Sell,
+            is_yes,
+            shares,
+            cost: proceeds,
+            fee,
+            fee_bps,
+            yes_pool,
+            no_pool,
+            new_yes_price_bps,
+            trade_seq,
+            position_after: if is_yes { position.yes_shares } else { position.no_shares },
+            deadline,
+            timestamp,
+        });
+
+        Ok(AmmReceipt { proceeds, realized_pnl, fee, fee_bps, new_yes_price_bps, new_no_price_bps, yes_pool, no_pool })
+    }
+
+    /// Buy `shares` of a side from `market`'s AMM, applying the trade to the
+    /// pools, escrow and fees of this copy only. Returns the cost before the
+    /// fee and the fee at `fee_bps`.
+    fn buy_from_amm(
+        &self,
+        market: &mut state::Market,
+        is_yes: bool,
+        shares: Amount,
+        fee_bps: u64,
+        move_limit_bps: Option<u64>,
+    ) -> Result<(Amount, Amount), String> {
+        let (pool_in, pool_out) = if is_yes {
+            (market.no_pool, market.yes_pool)
+        } else {
+            (market.yes_pool, market.no_pool)
+        };
+        let (pi, po, s) = (u128::from(pool_in), u128::from(pool_out), u128::from(shares));
+        if s >= po {
+            return Err(format!(
+                "Not enough liquidity: requested {} shares but pool only has {} (pool_in={}, pool_out={})",
+                shares, pool_out, pool_in, pool_out
+            ));
+        }
+        let amm_cost = Amount::from_attos(cost_to_buy(pi, po, s)?);
+        if let Some(allowed_bps) = move_limit_bps {
+            let attempted_bps = probability_move_bps(pi, po, s, amm_cost.into())?;
+            if attempted_bps > allowed_bps {
+                return Err(MarketError::MaxImpactExceeded { market_id: market.id, allowed_bps, attempted_bps }.into());
+            }
+        }
+        if amm_cost == Amount::ZERO {
+            return Err(MarketError::ZeroCostTrade { shares }.into());
+        }
+        let fee = self.trading_fee(amm_cost, fee_bps)?;
+
+        if is_yes {
+            market.no_pool = market.no_pool.saturating_add(amm_cost);
+            market.yes_pool = market.yes_pool.saturating_sub(shares);
+            market.total_yes_shares = market.total_yes_shares.saturating_add(shares);
+        } else {
+            market.yes_pool = market.yes_pool.saturating_add(amm_cost);
+            market.no_pool = market.no_pool.saturating_sub(shares);
+            market.total_no_shares = market.total_no_shares.saturating_add(shares);
+        }
+        market.volume = market.volume.saturating_add(amm_cost);
+        market.escrow = market.escrow.saturating_add(amm_cost);
+        market.accrue_fee(fee);
+        Ok((amm_cost, fee))
+    }
+
+    /// Buy a triggered buy order's shares from `market`'s AMM for its owner,
+    /// paying from the order's escrow and refunding what the purchase leaves
+    /// of it. Returns the cost with the fee, or None, having written
+    /// nothing, while trading is paused or the purchase would fail a check
+    /// or cost more than the order allows; the order then waits.
+    async fn buy_for_trigger(
+        &mut self,
+        order: &state::TriggerOrder,
+        mut market: state::Market,
+        timestamp: Timestamp,
+    ) -> Result<Option<Amount>, String> {
+        if *self.state.paused.get() {
+            return Ok(None);
+        }
+        let fee_bps = self.trader_fee_bps(&market, order.owner, timestamp).await?;
+        let move_limit_bps = market.probability_move_limit_bps(self.state.config.get());
+
+        market.accumulate_price(timestamp);
+        let pools_before = (market.yes_pool, market.no_pool);
+        let Ok((amm_cost, fee)) = self.buy_from_amm(&mut market, order.is_yes, order.shares, fee_bps, move_limit_bps) else {
+            return Ok(None);
+        };
+        let cost = amm_cost.saturating_add(fee);
+        if cost > order.escrow {
+            return Ok(None);
+        }
+        check_pool_product(&market, pools_before)?;
+        let (yes_shares, no_shares) = if order.is_yes { (order.shares, Amount::ZERO) } else { (Amount::ZERO, order.shares) };
+        if self.check_position_limits(&market, order.owner, yes_shares, no_shares).await.is_err() {
+            return Ok(None);
+        }
+
+        let market_id = market.id;
+        let new_yes_price_bps = market.yes_price_bps();
+        let (yes_pool, no_pool) = (market.yes_pool, market.no_pool);
+        let question = market.question.clone();
+        self.record_price_checkpoint(&market).await?;
+        self.save_market(market)?;
+
+        self.record_volume(market_id, amm_cost, timestamp).await?;
+        self.adjust_tvl(amm_cost, true);
+        self.adjust_open_interest(amm_cost, true);
+        // The escrow came in at placement; only what it overpaid goes back
+        self.adjust_contract_balance(order.escrow.saturating_sub(cost), false);
+        self.move_active_liquidity(pools_before, (yes_pool, no_pool));
+        self.record_trader(order.owner, market_id, amm_cost).await?;
+        self.update_position(order.owner, market_id, order.is_yes, order.shares, cost, true).await?;
+        self.record_recent_trade(RecentTrade {
+            market_id,
+            question,
+            trader: order.owner,
+            side: OrderSide::Buy,
+            is_yes: order.is_yes,
+            shares: order.shares,
+            amount: cost,
+            yes_price_bps: new_yes_price_bps,
+            timestamp,
+        });
+
+        let position = self.position_or_empty(order.owner, market_id).await?;
+        let trade_seq = self.next_trade_seq(market_id).await?;
+        self.emit_event(MarketEvent::TradeExecuted {
+            market_id,
+            trader: order.owner,
+            side: OrderSide::Buy,
+            is_yes: order.is_yes,
+            shares: order.shares,
+            cost: amm_cost,
+            fee,
+            fee_bps,
+            yes_pool,
+            no_pool,
+            new_yes_price_bps,
+            trade_seq,
+            position_after: if order.is_yes { position.yes_shares } else { position.no_shares },
+            deadline: None,
+            timestamp,
+        });
+
+        Ok(Some(cost))
+    }
+
+    /// Sell a triggered sell order's shares to `market`'s AMM for its owner.
+    /// Returns the proceeds after the fee, or None, having written nothing,
+    /// while they are quoted below the order's minimum; the order then waits.
+    async fn sell_for_trigger(
+        &mut self,
+        order: &state::TriggerOrder,
+        market: &state::Market,
+        timestamp: Timestamp,
+    ) -> Result<Option<Amount>, String> {
+        let (gross, fee, _) = self.quote_sale(market, order.owner, order.is_yes, order.shares, timestamp).await?;
+        if gross.saturating_sub(fee) < order.min_proceeds {
+            return Ok(None);
+        }
+
+        let sale = AmmSale {
+            market_id: market.id,
+            is_yes: order.is_yes,
+            shares: order.shares,
+            min_proceeds: order.min_proceeds,
+            deadline: None,
+        };
+        Ok(Some(self.sell_to_amm(order.owner, sale, timestamp).await?.proceeds))
+    }
+
+    /// What `seller` selling `shares` of a side to `market`'s AMM at `now`
+    /// is paid before the trading fee, then the fee and its rate in basis
+    /// points
+    async fn quote_sale(
+        &self,
+        market: &state::Market,
+        seller: AccountOwner,
+        is_yes: bool,
+        shares: Amount,
+        now: Timestamp,
+    ) -> Result<(Amount, Amount, u64), String> {
+        let (pool_in, pool_out) = if is_yes {
+            (market.yes_pool, market.no_pool)
+        } else {
+            (market.no_pool, market.yes_pool)
+        };
+        let gross = Amount::from_attos(sell_proceeds(pool_in.into(), pool_out.into(), shares.into())?);
+        let fee_bps = self.trader_fee_bps(market, seller, now).await?;
+        let fee = self.trading_fee(gross, fee_bps)?;
+        Ok((gross, fee, fee_bps))
+    }
+
+    /// The trading fee `trader` pays in `market` at `now`, in basis points:
+    /// the market's rate less the discount of the fee tier the trader's
+    /// lifetime volume reaches. Every share of the fee shrinks with it.
+    async fn trader_fee_bps(&self, market: &state::Market, trader: AccountOwner, now: Timestamp) -> Result<u64, String> {
+        let config = self.state.config.get();
+        let fee_bps = market.fee_bps_at(config.fee_bps, now);
+        if config.fee_tiers.is_empty() {
+            return Ok(fee_bps);
+        }
+        let volume = self.state.trader_stats.get(&trader)
+            .await
+            .map_err(|e| format!("Failed to read trader stats: {}", e))?
+            .map_or(Amount::ZERO, |stats| stats.volume);
+        Ok(config.discounted_fee_bps(fee_bps, volume))
+    }
+
+    /// Opposite-side resting orders that cross, best price first and oldest
+    /// first within a price, paired with the shares each fills. The caller's
+    /// own orders are skipped.
+    async fn match_orders(
+        &self,
+        market_id: u64,
+        is_yes: bool,
+        maker_side: OrderSide,
+        taker: AccountOwner,
+        shares: Amount,
+        crosses: impl Fn(u64) -> bool,
+    ) -> Result<Vec<(state::LimitOrder, Amount)>, String> {
+        let ids = self.state.order_book.get(&(market_id, is_yes))
+            .await
+            .map_err(|e| format!("Failed to read order book: {}", e))?
+            .unwrap_or_default();
+        let mut resting = Vec::new();
+        for id in ids {
+            let order = self.state.limit_orders.get(&(market_id, id))
+                .await
+                .map_err(|e| format!("Failed to read order: {}", e))?;
+            if let Some(order) = order {
+                if order.side == maker_side && order.owner != taker && crosses(order.price_bps) {
+                    resting.push(order);
+                }
+            }
+        }
+        match maker_side {
+            OrderSide::Sell => resting.sort_by_key(|o| (o.price_bps, o.id)),
+            OrderSide::Buy => resting.sort_by_key(|o| (Reverse(o.price_bps), o.id)),
+        }
+
+        let mut remaining = shares;
+        let mut fills = Vec::new();
+        for order in resting {
+            if remaining == Amount::ZERO {
+                break;
+            }
+            let fill = order.shares.saturating_sub(order.filled_shares).min(remaining);
+            remaining = remaining.saturating_sub(fill);
+            fills.push((order, fill));
+        }
+        Ok(fills)
+    }
+
+    /// Settle the maker side of a book trade, crediting the maker's rebate
+    /// out of the taker's fee at `fee_bps`. The taker side, fee included, is
+    /// left to the caller.
+    async fn fill_resting_order(
+        &mut self,
+        mut order: state::LimitOrder,
+        shares: Amount,
+        taker: AccountOwner,
+        fee_bps: u64,
+        timestamp: Timestamp,
+    ) -> Result<BookFill, String> {
+        let value = order_cost(shares, order.price_bps)?;
+        let fee = self.trading_fee(value, fee_bps)?;
+        let lp_fee = self.credit_maker_rebate(order.owner, taker, fee).await?;
+        let relief = settle_fill(&mut order, shares, value)?;
+        match order.side {
+            OrderSide::Buy => {
+                self.update_position(order.owner, order.market_id, order.is_yes, shares, value, true).await?;
+            }
+            OrderSide::Sell => {
+                self.record_realized_pnl(order.owner, order.market_id, pnl(value.into(), relief.into())).await?;
+            }
+        }
+        if order.status == OrderStatus::Filled {
+            self.remove_from_book(order.market_id, order.is_yes, order.id).await?;
+        }
+        self.record_trader(order.owner, order.market_id, value).await?;
+
+        let trade_seq = self.next_trade_seq(order.market_id).await?;
+        self.emit_event(MarketEvent::OrderFilled {
+            market_id: order.market_id,
+            order_id: order.id,
+            maker: order.owner,
+            taker,
+            side: order.side,
+            is_yes: order.is_yes,
+            shares,
+            price_bps: order.price_bps,
+            trade_seq,
+            timestamp,
+        });
+        self.state.limit_orders.insert(&(order.market_id, order.id), order)
+            .map_err(|e| format!("Failed to update order: {}", e))?;
+        Ok(BookFill { value, fee, lp_fee })
+    }
+
+    /// Credit `maker` their rebate out of the `fee` `taker` paid on a book
+    /// fill and return the rest, which goes to the market's LPs. Matching
+    /// skips an account's own orders, but a self-trade would earn nothing.
+    async fn credit_maker_rebate(&mut self, maker: AccountOwner, taker: AccountOwner, fee: Amount) -> Result<Amount, String> {
+        if maker == taker {
+            return Ok(fee);
+        }
+        let rebate_bps = u64::from(self.state.config.get().maker_rebate_bps());
+        let (rebate, rest) = split_fee(fee.into(), rebate_bps)?;
+        if rebate > 0 {
+            let earned = self.state.maker_rebates.get(&maker)
+                .await
+                .map_err(|e| format!("Failed to read maker rebates: {}", e))?
+                .unwrap_or_default();
+            self.state.maker_rebates.insert(&maker, earned.saturating_add(Amount::from_attos(rebate)))
+                .map_err(|e| format!("Failed to update maker rebates: {}", e))?;
+        }
+        Ok(Amount::from_attos(rest))
+    }
+
+    async fn remove_from_book(&mut self, market_id: u64, is_yes: bool, order_id: u64) -> Result<(), String> {
+        let key = (market_id, is_yes);
+        let mut ids = self.state.order_book.get(&key)
+            .await
+            .map_err(|e| format!("Failed to read order book: {}", e))?
+            .unwrap_or_default();
+        ids.retain(|id| *id != order_id);
+        if ids.is_empty() {
+            self.state.order_book.remove(&key)
+        } else {
+            self.state.order_book.insert(&key, ids)
+        }
+        .map_err(|e| format!("Failed to update order book: {}", e))
+    }
+
+    async fn load_trigger_order(&self, market_id: u64, order_id: u64) -> Result<state::TriggerOrder, String> {
+        self.state.trigger_orders.get(&(market_id, order_id))
+            .await
+            .map_err(|e| format!("Failed to read trigger order: {}", e))?
+            .ok_or_else(|| format!("Trigger order {} not found on market {}", order_id, market_id))
+    }
+
+    /// Allocate the next trigger order id on a market and add it to the
+    /// market's pending list, which holds at most
+    /// `MAX_PENDING_TRIGGER_ORDERS` so one keeper call can reach them all
+    async fn add_pending_trigger(&mut self, market_id: u64) -> Result<u64, String> {
+        let mut pending = self.state.pending_triggers.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read pending trigger orders: {}", e))?
+            .unwrap_or_default();
+        if pending.len() >= MAX_PENDING_TRIGGER_ORDERS {
+            return Err(format!(
+                "Market {} already has {} pending trigger orders",
+                market_id, MAX_PENDING_TRIGGER_ORDERS
+            ));
+        }
+
+        let order_id = self.state.trigger_order_counters.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read trigger order counter: {}", e))?
+            .unwrap_or(0);
+        self.state.trigger_order_counters.insert(&market_id, order_id + 1)
+            .map_err(|e| format!("Failed to update trigger order counter: {}", e))?;
+        pending.push(order_id);
+        self.state.pending_triggers.insert(&market_id, pending)
+            .map_err(|e| format!("Failed to update pending trigger orders: {}", e))?;
+        Ok(order_id)
+    }
+
+    async fn remove_pending_trigger(&mut self, market_id: u64, order_id: u64) -> Result<(), String> {
+        let mut ids = self.state.pending_triggers.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read pending trigger orders: {}", e))?
+            .unwrap_or_default();
+        ids.retain(|id| *id != order_id);
+        if ids.is_empty() {
+            self.state.pending_triggers.remove(&market_id)
+        } else {
+            self.state.pending_triggers.insert(&market_id, ids)
+        }
+        .map_err(|e| format!("Failed to update pending trigger orders: {}", e))
+    }
+
+    /// Mark a pending trigger order, already off the pending list, cancelled
+    /// and refund its keeper fee and a buy order's escrow, which it paid in
+    /// itself and so are not checked against the balance. Returns the refund.
+    fn close_trigger_order(&mut self, mut order: state::TriggerOrder, timestamp: Timestamp) -> Result<Amount, String> {
+        let refund = order.keeper_fee.saturating_add(order.escrow);
+        self.adjust_contract_balance(refund, false);
+        order.status = TriggerStatus::Cancelled;
+        order.closed_at = Some(timestamp);
+        self.state.trigger_orders.insert(&(order.market_id, order.id), order)
+            .map_err(|e| format!("Failed to update trigger order: {}", e))?;
+        Ok(refund)
+    }
+
+    /// Cancel every pending trigger order on a market as it resolves
+    async fn cancel_pending_triggers(&mut self, market_id: u64, timestamp: Timestamp) -> Result<(), String> {
+        let ids = self.state.pending_triggers.get(&market_id)
+            .await
+            .map_err(|e| format!("Failed to read pending trigger orders: {}", e))?
+            .unwrap_or_default();
+        for order_id in ids {
+            let order = self.load_trigger_order(market_id, order_id).await?;
+            self.close_trigger_order(order, timestamp)?;
+        }
+        self.state.pending_triggers.remove(&market_id)
+            .map_err(|e| format!("Failed to update pending trigger orders: {}", e))
     }
 
     async fn create_feed_item(
@@ -757,10 +4574,10 @@ impl MarketContract {
         item_type: FeedItemType,
         market_id: Option<u64>,
         content: String,
-        timestamp: linera_sdk::linera_base_types::Timestamp,
+        timestamp: Timestamp,
     ) -> Result<u64, String> {
-        let feed_id = *self.state.next_feed_id.get();
-        self.state.next_feed_id.set(feed_id + 1);
+        let feed_id = allocate_id(&mut self.state.next_feed_id)
+            .ok_or(MarketError::IdsExhausted { kind: "feed item" })?;
 
         let item = state::FeedItem {
             id: feed_id,
@@ -785,7 +4602,7 @@ impl MarketContract {
 
         for combo_id in 0..next_combo_id {
             if let Ok(Some(mut combo)) = self.state.combos.get(&combo_id).await {
-                if combo.status != ComboStatus::Active && combo.status != ComboStatus::PartiallyResolved {
+                if combo.status == ComboStatus::Cancelled {
                     continue;
                 }
 
@@ -793,8 +4610,10 @@ impl MarketContract {
                 let mut all_resolved = true;
                 let mut any_lost = false;
 
+                // Legs already settled on this market are re-settled when
+                // its outcome is overridden
                 for leg in combo.legs.iter_mut() {
-                    if leg.market_id == market_id && !leg.resolved {
+                    if leg.market_id == market_id {
                         leg.resolved = true;
                         leg.won = Some(leg.prediction == outcome);
                         updated = true;
@@ -0,0 +1,45 @@
+// Copyright (c) Chronos Markets
+// Test application contract: forwards operations to a market
+
+#![cfg_attr(target_arch = "wasm32", no_main)]
+
+use chronos_market_test_caller::{CallerAbi, CallerOperation, CallerParameters};
+use linera_sdk::{linera_base_types::WithContractAbi, Contract, ContractRuntime};
+
+linera_sdk::contract!(CallerContract);
+
+pub struct CallerContract {
+    runtime: ContractRuntime<Self>,
+}
+
+impl WithContractAbi for CallerContract {
+    type Abi = CallerAbi;
+}
+
+impl Contract for CallerContract {
+    type Message = ();
+    type InstantiationArgument = ();
+    type Parameters = CallerParameters;
+    type EventValue = ();
+
+    async fn load(runtime: ContractRuntime<Self>) -> Self {
+        CallerContract { runtime }
+    }
+
+    async fn instantiate(&mut self, _argument: ()) {}
+
+    async fn execute_operation(&mut self, operation: CallerOperation) -> String {
+        // An authenticated call shows the market this application as its
+        // caller, and the block's signer too, which it ranks below the caller
+        let (authenticated, operation) = match operation {
+            CallerOperation::Call(operation) => (true, operation),
+            CallerOperation::CallUnauthenticated(operation) => (false, operation),
+        };
+        let market = self.runtime.application_parameters();
+        self.runtime.call_application(authenticated, market, &operation)
+    }
+
+    async fn execute_message(&mut self, _message: ()) {}
+
+    async fn store(self) {}
+}
@@ -0,0 +1,32 @@
+// Copyright (c) Chronos Markets
+// ABI of a test application that trades on a market by calling it
+
+use async_graphql::{Request, Response};
+use chronos_market::{MarketAbi, Operation};
+use linera_sdk::linera_base_types::{ApplicationId, ContractAbi, ServiceAbi};
+use serde::{Deserialize, Serialize};
+
+pub struct CallerAbi;
+
+impl ContractAbi for CallerAbi {
+    type Operation = CallerOperation;
+    /// The market's response, unchanged
+    type Response = String;
+}
+
+/// A market operation to forward
+#[derive(Debug, Serialize, Deserialize)]
+pub enum CallerOperation {
+    /// Call with this application and the block's signer authenticated
+    Call(Operation),
+    /// Call with neither, so the market sees an anonymous caller
+    CallUnauthenticated(Operation),
+}
+
+impl ServiceAbi for CallerAbi {
+    type Query = Request;
+    type QueryResponse = Response;
+}
+
+/// The market every operation is forwarded to
+pub type CallerParameters = ApplicationId<MarketAbi>;
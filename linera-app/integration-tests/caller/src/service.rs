@@ -0,0 +1,43 @@
+// Copyright (c) Chronos Markets
+// Test application service: reports which market it forwards to
+
+#![cfg_attr(target_arch = "wasm32", no_main)]
+
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Response, Schema};
+use chronos_market_test_caller::{CallerAbi, CallerParameters};
+use linera_sdk::{linera_base_types::WithServiceAbi, Service, ServiceRuntime};
+
+linera_sdk::service!(CallerService);
+
+pub struct CallerService {
+    market: CallerParameters,
+}
+
+impl WithServiceAbi for CallerService {
+    type Abi = CallerAbi;
+}
+
+impl Service for CallerService {
+    type Parameters = CallerParameters;
+
+    async fn new(runtime: ServiceRuntime<Self>) -> Self {
+        CallerService { market: runtime.application_parameters() }
+    }
+
+    async fn handle_query(&self, request: Request) -> Response {
+        let query = QueryRoot { market: self.market.forget_abi().to_string() };
+        Schema::build(query, EmptyMutation, EmptySubscription).finish().execute(request).await
+    }
+}
+
+struct QueryRoot {
+    market: String,
+}
+
+#[Object]
+impl QueryRoot {
+    /// Id of the market operations are forwarded to
+    async fn market(&self) -> &str {
+        &self.market
+    }
+}
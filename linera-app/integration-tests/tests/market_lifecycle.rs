@@ -0,0 +1,4652 @@
+// Copyright (c) Chronos Markets
+// End-to-end tests running the contract and service on a local test validator
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use chronos_market::{
+    client::{OperationBuilder, OperationResponse}, Config, FeeRamp, FeeTier, market_key, MarketAbi, MarketCondition, MarketParameters, MarketSpec, Operation, OrderSide, ParlayLeg, Resolution, SeriesTemplate, TriggerDirection,
+    MAX_PENDING_TRIGGER_ORDERS, POSITION_TRANSFER_TIMEOUT_SECS,
+};
+use chronos_market_test_caller::{CallerAbi, CallerOperation, CallerParameters};
+use linera_sdk::{
+    bcs,
+    linera_base_types::{AccountOwner, AccountSecretKey, Amount, ApplicationId, CryptoHash, TimeDelta, Timestamp},
+    serde_json::{self, Value},
+    test::{ActiveChain, TestValidator, TryGraphQLQueryError},
+};
+
+/// Two signers trading against one application on a shared chain
+struct Harness {
+    validator: TestValidator,
+    app_id: ApplicationId<MarketAbi>,
+    alice: ActiveChain,
+    bob: ActiveChain,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    /// Instantiate with the default config, adjusted by `configure`
+    async fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let (validator, module_id) =
+            TestValidator::with_current_module::<MarketAbi, MarketParameters, Config>().await;
+
+        // Alice creates the application and is its admin
+        let mut alice = validator.new_chain().await;
+        let mut config = Config {
+            admin: AccountOwner::from(alice.public_key()),
+            ..Config::default()
+        };
+        configure(&mut config);
+        let app_id = alice
+            .create_application(module_id, MarketParameters::default(), config, vec![])
+            .await;
+
+        // Same chain, different block signer, hence a different authenticated owner
+        let mut bob = alice.clone();
+        bob.set_key_pair(AccountSecretKey::generate());
+
+        Harness { validator, app_id, alice, bob }
+    }
+
+    fn now(&self) -> Timestamp {
+        self.validator.clock().current_time()
+    }
+
+    fn advance(&self, secs: u64) {
+        self.validator.clock().add(TimeDelta::from_secs(secs));
+    }
+
+    /// Run one operation in its own block and return the contract's response
+    async fn execute(&self, chain: &ActiveChain, operation: Operation) -> String {
+        let timestamp = self.now();
+        let certificate = chain
+            .add_block(|block| {
+                block.with_timestamp(timestamp).with_operation(self.app_id, operation);
+            })
+            .await;
+        let result = &certificate.inner().block().body.operation_results[0];
+        bcs::from_bytes(&result.0).expect("Operation response should be a String")
+    }
+
+    /// Run one operation on `chain`, let `transit_secs` pass, then deliver
+    /// the messages it sent to `destination` and the replies back to
+    /// `chain`, each in a block at the current time
+    async fn execute_and_deliver(
+        &self,
+        chain: &ActiveChain,
+        operation: Operation,
+        destination: &ActiveChain,
+        transit_secs: u64,
+    ) -> String {
+        let timestamp = self.now();
+        let sent = chain
+            .add_block(|block| {
+                block.with_timestamp(timestamp).with_operation(self.app_id, operation);
+            })
+            .await;
+        self.advance(transit_secs);
+        let timestamp = self.now();
+        let received = destination
+            .add_block(|block| {
+                block.with_timestamp(timestamp).with_messages_from(&sent);
+            })
+            .await;
+        chain
+            .add_block(|block| {
+                block.with_timestamp(timestamp).with_messages_from(&received);
+            })
+            .await;
+        let result = &sent.inner().block().body.operation_results[0];
+        bcs::from_bytes(&result.0).expect("Operation response should be a String")
+    }
+
+    async fn create_market(&self, chain: &ActiveChain, question: &str, liquidity: Amount, duration_secs: u64) -> u64 {
+        let end_time = self.now().saturating_add(TimeDelta::from_secs(duration_secs));
+        let response = self
+            .execute(
+                chain,
+                Operation::CreateMarket {
+                    question: question.to_string(),
+                    categories: vec!["test".to_string()],
+                    end_time,
+                    initial_liquidity: liquidity,
+                    early_resolution_allowed: false,
+                    max_position_per_account: None,
+                    max_position: None,
+                    creator_trading_disabled: false,
+                    fee_ramp: None,
+                    description: None,
+                    resolution_source: None,
+                    parent: None,
+                    resolvers: vec![],
+                    threshold: 0,
+                    max_probability_move_bps: None,
+                    max_trade_shares: None,
+                },
+            )
+            .await;
+        created_id(&response)
+    }
+
+    /// Run a trade and keep its response only up to the cost or proceeds
+    async fn trade(&self, chain: &ActiveChain, operation: Operation) -> String {
+        let response = self.execute(chain, operation).await;
+        if response.starts_with("ERROR") {
+            return response;
+        }
+        response.splitn(3, ':').take(2).collect::<Vec<_>>().join(":")
+    }
+
+    async fn buy(&self, chain: &ActiveChain, market_id: u64, is_yes: bool, shares: Amount, max_cost: Amount) -> String {
+        self.trade(chain, Operation::BuyShares { market_id, is_yes, shares, max_cost, deadline: None, max_price_impact_bps: None })
+            .await
+    }
+
+    /// Market fields with each amount decoded to its `Amount` display form
+    async fn market(&self, market_id: u64) -> Value {
+        let query = format!(
+            "query {{ market(id: {market_id}) {{ \
+                yesPool {{ {AMOUNT} }} noPool {{ {AMOUNT} }} \
+                totalYesShares {{ {AMOUNT} }} totalNoShares {{ {AMOUNT} }} \
+                volume {{ {AMOUNT} }} escrow {{ {AMOUNT} }} resolved outcome \
+            }} }}"
+        );
+        let mut market = self.alice.graphql_query(self.app_id, query).await.response["market"].clone();
+        for field in ["yesPool", "noPool", "totalYesShares", "totalNoShares", "volume", "escrow"] {
+            market[field] = Value::String(decode_amount(&market[field]).to_string());
+        }
+        market
+    }
+
+    async fn place_order(&self, chain: &ActiveChain, market_id: u64, side: OrderSide, price_bps: u64, shares: &str) -> String {
+        let operation = Operation::PlaceLimitOrder { market_id, is_yes: true, side, price_bps, shares: tokens(shares) };
+        self.execute(chain, operation).await
+    }
+
+    /// YES price levels on one side of the book as (price_bps, shares, orders)
+    async fn yes_levels(&self, market_id: u64, side: &str) -> Vec<(u64, String, u64)> {
+        let query = format!(
+            "query {{ orderBook(marketId: {market_id}) {{ yes {{ {side} {{ priceBps shares {{ {AMOUNT} }} orders }} }} }} }}"
+        );
+        let response = self.alice.graphql_query(self.app_id, query).await.response;
+        response["orderBook"]["yes"][side]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|level| {
+                let shares = decode_amount(&level["shares"]).to_string();
+                (level["priceBps"].as_u64().unwrap(), shares, level["orders"].as_u64().unwrap())
+            })
+            .collect()
+    }
+
+    async fn paused(&self) -> bool {
+        let response = self.alice.graphql_query(self.app_id, "query { paused }").await.response;
+        response["paused"].as_bool().unwrap()
+    }
+
+    async fn total_volume(&self) -> String {
+        let query = format!("query {{ totalVolume {{ {AMOUNT} }} }}");
+        let response = self.alice.graphql_query(self.app_id, query).await.response;
+        decode_amount(&response["totalVolume"]).to_string()
+    }
+}
+
+/// Selection set for the service's `TokenAmount` objects
+const AMOUNT: &str = "attos tokens";
+
+/// Read a `TokenAmount`, checking that both representations agree
+fn decode_amount(value: &Value) -> Amount {
+    let amount = attos(value["attos"].as_str().unwrap().parse().unwrap());
+    let decimal = value["tokens"].as_str().unwrap();
+    assert_eq!(tokens(decimal), amount, "tokens {decimal} disagrees with attos {}", value["attos"]);
+    assert!(!decimal.ends_with('.'), "tokens {decimal} should not end in a bare point");
+    amount
+}
+
+/// The id in a MarketCreated response
+fn created_id(response: &str) -> u64 {
+    match response.parse() {
+        Ok(OperationResponse::MarketCreated { market_id, .. }) => market_id,
+        _ => panic!("Unexpected response: {response}"),
+    }
+}
+
+fn tokens(value: &str) -> Amount {
+    value.parse().unwrap()
+}
+
+fn attos(value: u128) -> Amount {
+    Amount::from_attos(value)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn market_lifecycle_with_two_traders() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Will it rain?", tokens("100"), 3600).await;
+    assert_eq!(market_id, 0);
+
+    let market = h.market(market_id).await;
+    assert_eq!(market["yesPool"], "50.");
+    assert_eq!(market["noPool"], "50.");
+    assert_eq!(market["escrow"], "100.");
+
+    // Alice buys 10 YES: cost = no_pool * 10 / (yes_pool - 10) = 50 * 10 / 40
+    let response = h.buy(&h.alice, market_id, true, tokens("10"), tokens("12.5")).await;
+    assert_eq!(response, "SharesPurchased:12.5");
+
+    let market = h.market(market_id).await;
+    assert_eq!(market["yesPool"], "40.");
+    assert_eq!(market["noPool"], "62.5");
+    assert_eq!(market["totalYesShares"], "60.");
+
+    // Bob buys 10 NO: cost = yes_pool * 10 / (no_pool - 10) = 40 * 10 / 52.5, rounded up
+    let bob_cost = attos(7_619_047_619_047_619_048);
+    let response = h.buy(&h.bob, market_id, false, tokens("10"), tokens("7.62")).await;
+    assert_eq!(response, format!("SharesPurchased:{bob_cost}"));
+
+    let yes_pool = tokens("40").saturating_add(bob_cost);
+    let market = h.market(market_id).await;
+    assert_eq!(market["yesPool"], yes_pool.to_string());
+    assert_eq!(market["noPool"], "52.5");
+    assert_eq!(market["totalNoShares"], "60.");
+    assert_eq!(market["volume"], tokens("12.5").saturating_add(bob_cost).to_string());
+    assert_eq!(market["escrow"], tokens("112.5").saturating_add(bob_cost).to_string());
+    assert_eq!(h.total_volume().await, tokens("12.5").saturating_add(bob_cost).to_string());
+
+    // Resolution is refused until end_time has passed
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("ERROR:"), "{response}");
+
+    h.advance(3601);
+    let response = h.execute(&h.bob, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("ERROR: Not authorized"), "{response}");
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("MarketResolved:"), "{response}");
+
+    // The loser holds only NO shares, and claiming realizes their cost as a loss
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    assert_eq!(response, format!("LossRealized:{bob_cost}"));
+
+    // Winner payout = winning_shares * (yes_pool + no_pool) / total_yes_shares = 10 * pool / 60
+    let total_pool = u128::from(yes_pool) + u128::from(tokens("52.5"));
+    let expected = attos(10 * total_pool / 60);
+    assert_eq!(expected, attos(16_686_507_936_507_936_508));
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with(&format!("WinningsClaimed:{expected}:")), "{response}");
+
+    let market = h.market(market_id).await;
+    assert_eq!(market["resolved"], true);
+    assert_eq!(market["outcome"], true);
+    let escrow_left = tokens("112.5").saturating_add(bob_cost).saturating_sub(expected);
+    assert_eq!(market["escrow"], escrow_left.to_string());
+
+    // A second claim is rejected and pays nothing
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id }).await;
+    assert_eq!(response, "ERROR: Winnings already claimed");
+    assert_eq!(h.market(market_id).await["escrow"], escrow_left.to_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn slippage_rejection_leaves_state_unchanged() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Slippage?", tokens("100"), 3600).await;
+
+    let before = h.market(market_id).await;
+    let volume_before = h.total_volume().await;
+
+    // Exact cost is 12.5; one atto less must be refused
+    let max_cost = tokens("12.5").saturating_sub(attos(1));
+    let response = h.buy(&h.bob, market_id, true, tokens("10"), max_cost).await;
+    assert!(response.starts_with("ERROR: Cost 12.5 exceeds max_cost"), "{response}");
+
+    assert_eq!(h.market(market_id).await, before);
+    assert_eq!(h.total_volume().await, volume_before);
+
+    // Bob never acquired a position, so there is nothing to claim after resolution
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    assert_eq!(response, "ERROR: No position found for this market");
+
+    // The same order at the exact cost goes through on a fresh market
+    let market_id = h.create_market(&h.alice, "Slippage again?", tokens("100"), 3600).await;
+    let response = h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await;
+    assert_eq!(response, "SharesPurchased:12.5");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close_position_sells_the_whole_side_held_at_execution() {
+    let h = Harness::with_config(|c| c.fee_bps = 100).await;
+    let market_id = h.create_market(&h.alice, "Exit?", tokens("100"), 3600).await;
+    let close = |is_yes, min_proceeds: &str| Operation::ClosePosition { market_id, is_yes, min_proceeds: tokens(min_proceeds) };
+    let response = h.execute(&h.bob, close(true, "0")).await;
+    assert_eq!(response, format!("ERROR: No YES shares to close in market {market_id}"));
+
+    // 12.5 plus the 1% fee buys 10 YES, leaving pools of 40 and 62.5
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("13")).await, "SharesPurchased:12.625");
+    let response = h.execute(&h.bob, close(true, "13")).await;
+    assert!(response.starts_with("ERROR: Proceeds 12.375 below minimum 13."), "{response}");
+
+    // Selling all 10 back returns the pools to 50/50; both fees are lost
+    let response = h.execute(&h.bob, close(true, "12")).await;
+    assert_eq!(response, "PositionClosed:12.375:10.:5000:5000:50.:50.:0.125:100:-250000000000000000");
+    let OperationResponse::PositionClosed { receipt, realized_pnl } = response.parse().unwrap() else {
+        panic!("{response}");
+    };
+    assert_eq!((receipt.amount, receipt.shares, realized_pnl), (tokens("12.375"), tokens("10"), -250_000_000_000_000_000));
+    let response = h.execute(&h.bob, close(true, "0")).await;
+    assert_eq!(response, format!("ERROR: No YES shares to close in market {market_id}"));
+
+    // The count is read when the operation runs, whatever was bought since
+    for _ in 0..2 {
+        assert!(h.buy(&h.bob, market_id, false, tokens("3"), tokens("10")).await.starts_with("SharesPurchased:"));
+    }
+    let response = h.execute(&h.bob, close(false, "0")).await;
+    let Ok(OperationResponse::PositionClosed { receipt, .. }) = response.parse() else { panic!("{response}") };
+    assert_eq!(receipt.shares, tokens("6"));
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ position(owner: \"{bob}\", marketId: {market_id}) {{ yesShares {{ tokens }} noShares {{ tokens }} }} \
+            tradesConnection(marketId: {market_id}) {{ edges {{ node {{ isBuy isYes shares {{ tokens }} priceImpactBps }} }} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(response["position"]["yesShares"]["tokens"], "0");
+    assert_eq!(response["position"]["noShares"]["tokens"], "0");
+    // Closes read back as sales in the market's trade history
+    let edges = response["tradesConnection"]["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 5);
+    let last = &edges[4]["node"];
+    assert_eq!((&last["isBuy"], &last["isYes"], &last["shares"]["tokens"]), (&Value::Bool(false), &Value::Bool(false), &Value::from("6")));
+    assert_eq!(last["priceImpactBps"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sell_quotes_mirror_buy_quotes_around_the_marginal_price() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Quoted?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let query = format!(
+        "query {{ market(id: {market_id}) {{ buyQuote(isYes: true, shares: \"10\") {{ cost {{ {AMOUNT} }} }} }} \
+            proceedsFromSell(marketId: {market_id}, isYes: true, shares: \"10\") {{ proceeds {{ {AMOUNT} }} fee {{ {AMOUNT} }} \
+                yesPriceBps noPriceBps priceImpactBps maxSellableShares {{ tokens }} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    let quote = &response["proceedsFromSell"];
+    // Pools of 40 YES and 62.5 NO price YES at 1.5625 a share at the margin:
+    // 10 sell for 62.5 * 10 / 50 and buy for 62.5 * 10 / 30
+    let (proceeds, cost) = (decode_amount(&quote["proceeds"]), decode_amount(&response["market"]["buyQuote"]["cost"]));
+    assert_eq!(proceeds, tokens("12.5"));
+    assert!(proceeds < tokens("15.625") && tokens("15.625") < cost, "{proceeds} vs {cost}");
+    assert_eq!(decode_amount(&quote["fee"]), Amount::ZERO);
+    assert_eq!((&quote["yesPriceBps"], &quote["noPriceBps"]), (&Value::from(5_000), &Value::from(5_000)));
+    assert_eq!(quote["priceImpactBps"], 2_000);
+    assert_eq!(quote["maxSellableShares"], Value::Null);
+
+    // Past a market's trade cap only the most it would take is quoted
+    let capped = OperationBuilder::create_market("Capped?", h.now().saturating_add(TimeDelta::from_secs(3600)), tokens("100"))
+        .category("test")
+        .max_trade_shares(tokens("10"))
+        .build();
+    assert_eq!(created_id(&h.execute(&h.alice, capped).await), 1);
+    let query = "query { proceedsFromSell(marketId: 1, isYes: false, shares: \"11\") { proceeds { tokens } priceImpactBps maxSellableShares { tokens } } }";
+    let quote = h.alice.graphql_query(h.app_id, query).await.response["proceedsFromSell"].clone();
+    assert_eq!(quote["proceeds"], Value::Null);
+    assert_eq!(quote["priceImpactBps"], Value::Null);
+    assert_eq!(quote["maxSellableShares"]["tokens"], "10");
+
+    let query = "query { proceedsFromSell(marketId: 9, isYes: true, shares: \"1\") { feeBps } }";
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for a missing market");
+    };
+    assert_eq!(errors[0].message, "Market 9 not found");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn price_at_reads_the_latest_snapshot_up_to_a_moment() {
+    let h = &Harness::new().await;
+    h.advance(60);
+    let created = h.now().micros();
+    let market_id = h.create_market(&h.alice, "Priced?", tokens("100"), 3600).await;
+    h.advance(60);
+    let traded = h.now().micros();
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let price_at = |micros: u64| async move {
+        let query = format!(
+            "query {{ priceAt(marketId: {market_id}, timestamp: {micros}) {{ asOfMicros yesPriceBps noPriceBps final }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query.as_str()).await.response["priceAt"].clone()
+    };
+
+    assert_eq!(price_at(created - 1).await, Value::Null);
+    let opening = price_at(traded - 1).await;
+    assert_eq!((&opening["yesPriceBps"], &opening["final"]), (&Value::from(5_000), &Value::from(false)));
+    assert_eq!(opening["asOfMicros"], created);
+    // Pools of 40 YES and 62.5 NO: 62.5 / 102.5
+    let after = price_at(traded + 30_000_000).await;
+    assert_eq!((&after["yesPriceBps"], &after["noPriceBps"]), (&Value::from(6_097), &Value::from(3_903)));
+    assert_eq!(after["asOfMicros"], traded);
+
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let resolved = h.now().micros();
+    let settled = price_at(resolved + 1).await;
+    assert_eq!((&settled["yesPriceBps"], &settled["noPriceBps"]), (&Value::from(10_000), &Value::from(0)));
+    assert_eq!((&settled["asOfMicros"], &settled["final"]), (&Value::from(resolved), &Value::from(true)));
+    // Before resolution the market still shows its trading price
+    assert_eq!(price_at(resolved - 1).await["yesPriceBps"], 6_097);
+
+    let query = "query { priceAt(marketId: 9, timestamp: 0) { yesPriceBps } }";
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for a missing market");
+    };
+    assert_eq!(errors[0].message, "Market 9 not found");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trade_sequences_count_each_market_through_interleaved_trades() {
+    let h = &Harness::new().await;
+    let first = h.create_market(&h.alice, "First?", tokens("100"), 3600).await;
+    let second = h.create_market(&h.bob, "Second?", tokens("40"), 3600).await;
+    let sell = |market_id: u64, is_yes: bool, shares: &str| Operation::SellShares {
+        market_id,
+        is_yes,
+        shares: tokens(shares),
+        min_proceeds: Amount::ZERO,
+        deadline: None,
+    };
+
+    assert!(h.buy(&h.alice, first, true, tokens("10"), tokens("20")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.bob, second, false, tokens("4"), tokens("10")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.bob, first, false, tokens("5"), tokens("10")).await.starts_with("SharesPurchased:"));
+    assert!(h.execute(&h.alice, sell(first, true, "4")).await.starts_with("SharesSold:"));
+    assert!(h.buy(&h.alice, second, true, tokens("2"), tokens("10")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.alice, first, true, tokens("1"), tokens("10")).await.starts_with("SharesPurchased:"));
+
+    let trades = |market_id: u64| async move {
+        let query = format!(
+            "query {{ tradesConnection(marketId: {market_id}) {{ edges {{ node {{ tradeSeq positionAfter {{ tokens }} }} }} }} }}"
+        );
+        let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+        response["tradesConnection"]["edges"].as_array().unwrap().iter()
+            .map(|edge| (edge["node"]["tradeSeq"].as_u64().unwrap(), edge["node"]["positionAfter"]["tokens"].clone()))
+            .collect::<Vec<_>>()
+    };
+    // Each market numbers its own trades from 0 without gaps, whatever
+    // traded elsewhere in between
+    assert_eq!(
+        trades(first).await,
+        [(0, Value::from("10")), (1, Value::from("5")), (2, Value::from("6")), (3, Value::from("7"))]
+    );
+    assert_eq!(trades(second).await, [(0, Value::from("4")), (1, Value::from("2"))]);
+
+    // The log carries the price the trade left; non-trades carry nothing
+    let query = format!("query {{ activity(marketId: {first}) {{ kind yesPriceBps tradeSeq }} }}");
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    let entries = response["activity"].as_array().unwrap();
+    let created = entries.iter().find(|e| e["kind"] == "CreateMarket").unwrap();
+    assert_eq!((&created["yesPriceBps"], &created["tradeSeq"]), (&Value::Null, &Value::Null));
+    let opening = entries.iter().find(|e| e["tradeSeq"] == 0).unwrap();
+    // Pools of 40 YES and 62.5 NO: 62.5 / 102.5
+    assert_eq!(opening["yesPriceBps"], 6_097);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolution_reports_what_the_claims_will_pay() {
+    let h = Harness::new().await;
+    let mut carol = h.alice.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+    let market_id = h.create_market(&h.alice, "Settled?", tokens("100"), 3600).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("20")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&carol, market_id, true, tokens("7"), tokens("20")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.alice, market_id, false, tokens("5"), tokens("20")).await.starts_with("SharesPurchased:"));
+    // Nobody holds NO shares in the second market's winning side: refunds
+    let refunded = h.create_market(&h.alice, "Refunded?", tokens("100"), 3600).await;
+    let bob_cost = h.buy(&h.bob, refunded, false, tokens("4"), tokens("20")).await;
+    let carol_cost = h.buy(&carol, refunded, false, tokens("6"), tokens("20")).await;
+
+    h.advance(3601);
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    let Ok(OperationResponse::MarketResolved(settlement)) = response.parse() else { panic!("{response}") };
+    assert!(settlement.outcome);
+    assert_eq!(settlement.winning_shares, tokens("17"));
+    assert_eq!(settlement.eligible_positions, 2);
+    let mut paid = Amount::ZERO;
+    for winner in [&h.bob, &carol] {
+        let response = h.execute(winner, Operation::ClaimWinnings { market_id }).await;
+        let Ok(OperationResponse::WinningsClaimed { payout, .. }) = response.parse() else { panic!("{response}") };
+        paid = paid.saturating_add(payout);
+    }
+    // Exactly what the claims paid; each rounds down on its own
+    assert_eq!(settlement.total_payout, paid);
+    let by_share = u128::from(settlement.payout_per_share) * 17;
+    assert!(by_share >= u128::from(paid) && by_share - u128::from(paid) < 2, "{by_share} vs {paid}");
+    // Losing positions are not counted as claims
+    assert!(h.execute(&h.alice, Operation::ClaimWinnings { market_id }).await.starts_with("LossRealized:"));
+
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id: refunded, outcome: true, evidence: None }).await;
+    let Ok(OperationResponse::MarketResolved(settlement)) = response.parse() else { panic!("{response}") };
+    let cost = |response: &str| -> Amount { response.split(':').nth(1).unwrap().parse().unwrap() };
+    assert_eq!(settlement.total_payout, cost(&bob_cost).saturating_add(cost(&carol_cost)));
+    assert_eq!((settlement.winning_shares, settlement.payout_per_share), (Amount::ZERO, Amount::ZERO));
+    assert_eq!(settlement.eligible_positions, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn parlays_buy_every_leg_within_one_budget_or_none() {
+    let h = Harness::new().await;
+    for question in ["A?", "B?", "C?"] {
+        h.create_market(&h.alice, question, tokens("100"), 3600).await;
+    }
+    let leg = |market_id: u64, is_yes: bool| ParlayLeg { market_id, is_yes, shares: tokens("10") };
+    let parlay = |legs: Vec<ParlayLeg>, max_total_cost: &str| Operation::BuyParlay { legs, max_total_cost: tokens(max_total_cost) };
+
+    // Each leg prices against its own 50/50 pools: 10 shares for 12.5
+    let response = h.execute(&h.bob, parlay(vec![leg(0, true), leg(1, true), leg(2, false)], "37.5")).await;
+    let Ok(OperationResponse::ParlayPurchased { total_cost, leg_costs }) = response.parse() else { panic!("{response}") };
+    assert_eq!(total_cost, tokens("37.5"));
+    assert_eq!(leg_costs, [tokens("12.5"); 3]);
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ position(owner: \"{bob}\", marketId: 2) {{ noShares {{ tokens }} }} activity(marketId: 1) {{ kind }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(response["position"]["noShares"]["tokens"], "10");
+    // Listed under every market it traded in
+    assert_eq!(response["activity"][0]["kind"], "BuyParlay");
+
+    // 62.5 * 10 / 30 for A's leg and 40 * 10 / 52.5 for B's: over budget,
+    // though either leg alone would fit
+    let before = [h.market(0).await, h.market(1).await];
+    let response = h.execute(&h.bob, parlay(vec![leg(0, true), leg(1, false)], "28")).await;
+    assert!(response.starts_with("ERROR: Cost ") && response.ends_with("exceeds max_total_cost 28. across 2 legs"), "{response}");
+    assert_eq!([h.market(0).await, h.market(1).await], before);
+
+    // Nor does anything execute when a later leg fails its own checks
+    let late = h.create_market(&h.alice, "Late?", tokens("100"), 60).await;
+    h.advance(61);
+    let response = h.execute(&h.bob, parlay(vec![leg(0, true), leg(late, true)], "100")).await;
+    assert!(response.starts_with("ERROR: Market 3 has ended"), "{response}");
+    assert_eq!(h.market(0).await, before[0]);
+
+    let response = h.execute(&h.bob, parlay(vec![leg(0, true), leg(1, true), leg(0, false)], "100")).await;
+    assert_eq!(
+        response,
+        "ERROR: Market 0 appears in more than one parlay leg; buy both sides of one market with BuyStraddle"
+    );
+    let response = h.execute(&h.bob, parlay(vec![leg(0, true)], "100")).await;
+    assert_eq!(response, "ERROR: A parlay needs between 2 and 10 legs; got 1");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn interleaved_markets_do_not_share_state() {
+    let h = Harness::new().await;
+    let first = h.create_market(&h.alice, "First?", tokens("100"), 3600).await;
+    let second = h.create_market(&h.bob, "Second?", tokens("40"), 3600).await;
+    assert_eq!((first, second), (0, 1));
+
+    // first: 50/50 pools; second: 20/20 pools
+    assert_eq!(h.buy(&h.alice, first, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    // 20 * 4 / (20 - 4) = 5
+    assert_eq!(h.buy(&h.alice, second, false, tokens("4"), tokens("5")).await, "SharesPurchased:5.");
+    // 40 * 5 / (62.5 - 5) = 3.478...
+    let bob_first_cost = attos(3_478_260_869_565_217_392);
+    assert_eq!(
+        h.buy(&h.bob, first, false, tokens("5"), tokens("3.5")).await,
+        format!("SharesPurchased:{bob_first_cost}")
+    );
+    // 16 * 4 / (25 - 4) = 3.047...
+    let bob_second_cost = attos(3_047_619_047_619_047_620);
+    assert_eq!(
+        h.buy(&h.bob, second, true, tokens("4"), tokens("3.1")).await,
+        format!("SharesPurchased:{bob_second_cost}")
+    );
+
+    let market = h.market(first).await;
+    assert_eq!(market["yesPool"], tokens("40").saturating_add(bob_first_cost).to_string());
+    assert_eq!(market["noPool"], "57.5");
+    assert_eq!(market["escrow"], tokens("112.5").saturating_add(bob_first_cost).to_string());
+
+    let market = h.market(second).await;
+    assert_eq!(market["yesPool"], "21.");
+    assert_eq!(market["noPool"], tokens("16").saturating_add(bob_second_cost).to_string());
+    assert_eq!(market["escrow"], tokens("45").saturating_add(bob_second_cost).to_string());
+
+    let total = tokens("17.5").saturating_add(bob_first_cost).saturating_add(bob_second_cost);
+    assert_eq!(h.total_volume().await, total.to_string());
+
+    // Resolve in opposite directions; only each market's own creator may do it
+    // (Alice is also the admin, but the abandonment delay has not passed)
+    h.advance(3601);
+    let response = h.execute(&h.bob, Operation::ResolveMarket { market_id: first, outcome: false, evidence: None }).await;
+    assert!(response.starts_with("ERROR: Not authorized"), "{response}");
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id: second, outcome: false, evidence: None }).await;
+    assert!(response.starts_with("ERROR: The admin may only resolve"), "{response}");
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: first, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    assert_eq!(h.market(second).await["resolved"], false);
+    assert!(h.execute(&h.bob, Operation::ResolveMarket { market_id: second, outcome: false, evidence: None }).await.starts_with("MarketResolved:"));
+
+    // Alice wins first (YES) and second (NO); Bob loses both, so claiming only realizes the losses
+    assert_eq!(
+        h.execute(&h.bob, Operation::ClaimWinnings { market_id: first }).await,
+        format!("LossRealized:{bob_first_cost}")
+    );
+    assert_eq!(
+        h.execute(&h.bob, Operation::ClaimWinnings { market_id: second }).await,
+        format!("LossRealized:{bob_second_cost}")
+    );
+
+    let first_pool = u128::from(tokens("97.5")) + u128::from(bob_first_cost);
+    let first_payout = attos(10 * first_pool / 60);
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id: first }).await;
+    assert!(response.starts_with(&format!("WinningsClaimed:{first_payout}:")), "{response}");
+
+    // Claiming on the first market leaves the second market's escrow untouched
+    let market = h.market(second).await;
+    assert_eq!(market["escrow"], tokens("45").saturating_add(bob_second_cost).to_string());
+
+    let second_pool = u128::from(tokens("37")) + u128::from(bob_second_cost);
+    let second_payout = attos(4 * second_pool / 24);
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id: second }).await;
+    assert!(response.starts_with(&format!("WinningsClaimed:{second_payout}:")), "{response}");
+
+    let market = h.market(first).await;
+    assert_eq!(
+        market["escrow"],
+        tokens("112.5").saturating_add(bob_first_cost).saturating_sub(first_payout).to_string()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn global_pause_halts_entry_but_not_exit() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Paused?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert!(!h.paused().await);
+
+    // Only the admin may flip the switch
+    let response = h.execute(&h.bob, Operation::SetGlobalPause { paused: true }).await;
+    assert_eq!(response, "ERROR: Not authorized: only the admin can pause trading");
+    assert!(!h.paused().await);
+
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: true }).await, "GlobalPauseSet:true");
+    assert!(h.paused().await);
+
+    // Entry is refused without touching state
+    let before = h.market(market_id).await;
+    let response = h.buy(&h.bob, market_id, true, tokens("1"), tokens("10")).await;
+    assert_eq!(response, "ERROR: Trading is paused by the admin");
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    let response = h
+        .execute(
+            &h.alice,
+            Operation::CreateMarket {
+                question: "Another?".to_string(),
+                categories: vec![],
+                end_time,
+                initial_liquidity: tokens("10"),
+                early_resolution_allowed: false,
+                max_position_per_account: None,
+                max_position: None,
+                creator_trading_disabled: false,
+                fee_ramp: None,
+                description: None,
+                resolution_source: None,
+                parent: None,
+                resolvers: vec![],
+                threshold: 0,
+                max_probability_move_bps: None,
+                max_trade_shares: None,
+            },
+        )
+        .await;
+    assert_eq!(response, "ERROR: Trading is paused by the admin");
+    assert_eq!(h.market(market_id).await, before);
+    let count = h.alice.graphql_query(h.app_id, "query { marketCount }").await.response;
+    assert_eq!(count["marketCount"], 1);
+
+    // Exit paths stay open
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+
+    // Unpausing restores entry
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: false }).await, "GlobalPauseSet:false");
+    h.create_market(&h.alice, "Resumed?", tokens("100"), 3600).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_reports_every_limit_on_submissions() {
+    let h = Harness::with_config(|config| {
+        config.fee_bps = 150;
+        config.min_trade_shares = tokens("0.5");
+        config.min_market_duration_secs = 3600;
+    })
+    .await;
+    let query = format!(
+        "query {{ config {{ feeBps feePercent paused minInitialLiquidity {{ {AMOUNT} }} minTradeShares {{ {AMOUNT} }} \
+            minMarketDurationSecs earliestEndTimeMicros }} }}"
+    );
+    let config = h.alice.graphql_query(h.app_id, query.clone()).await.response["config"].clone();
+    assert_eq!(config["feeBps"], 150);
+    assert_eq!(config["feePercent"], 1.5);
+    assert_eq!(config["paused"], false);
+    assert_eq!(decode_amount(&config["minInitialLiquidity"]), Config::default().min_initial_liquidity);
+    assert_eq!(decode_amount(&config["minTradeShares"]), tokens("0.5"));
+    assert_eq!(config["minMarketDurationSecs"], 3600);
+    let earliest = h.now().saturating_add(TimeDelta::from_secs(3600));
+    assert_eq!(config["earliestEndTimeMicros"], earliest.micros());
+
+    // Admin changes show in the very next query
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: true }).await, "GlobalPauseSet:true");
+    h.advance(60);
+    let config = h.alice.graphql_query(h.app_id, query).await.response["config"].clone();
+    assert_eq!(config["paused"], true);
+    assert_eq!(config["earliestEndTimeMicros"], earliest.saturating_add(TimeDelta::from_secs(60)).micros());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn amounts_round_trip_as_attos_and_decimal_tokens() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Whole supply?", attos(u128::MAX), 3600).await;
+
+    let query = format!("query {{ market(id: {market_id}) {{ escrow {{ {AMOUNT} }} yesPool {{ {AMOUNT} }} }} }}");
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(market["escrow"]["attos"], u128::MAX.to_string());
+    assert_eq!(market["escrow"]["tokens"], "340282366920938463463.374607431768211455");
+    assert_eq!(decode_amount(&market["escrow"]), attos(u128::MAX));
+    assert_eq!(decode_amount(&market["yesPool"]), attos(u128::MAX / 2));
+
+    // Decimal strings are accepted as mutation inputs
+    let second = h.create_market(&h.alice, "Decimal input?", tokens("100"), 3600).await;
+    let mutation = format!(
+        "mutation {{ buyShares(marketId: {second}, isYes: true, shares: \"10\", maxCost: \"12.5\") }}"
+    );
+    h.alice.graphql_mutation(h.app_id, mutation).await;
+    assert_eq!(h.market(second).await["totalYesShares"], "60.");
+
+    // Malformed, negative and sub-atto inputs are rejected with the argument named
+    for shares in ["1.5.3", "-1", "0.0000000000000000001"] {
+        let mutation = format!(
+            "mutation {{ buyShares(marketId: {second}, isYes: true, shares: \"{shares}\", maxCost: \"100\") }}"
+        );
+        let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, mutation).await
+        else {
+            panic!("Expected a GraphQL error for {shares:?}");
+        };
+        assert!(
+            errors.iter().any(|e| e.message.contains("argument \"shares\"")),
+            "Error for {shares:?} should name the argument: {errors:?}"
+        );
+    }
+    assert_eq!(h.market(second).await["totalYesShares"], "60.");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_resolves_abandoned_market_only_after_delay() {
+    let h = Harness::new().await;
+    let delay = Config::default().admin_resolution_delay_secs;
+    let market_id = h.create_market(&h.bob, "Abandoned?", tokens("100"), 3600).await;
+    h.advance(3600 + delay - 1);
+
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert_eq!(
+        response,
+        format!("ERROR: The admin may only resolve market {market_id} {delay} seconds after its end time")
+    );
+    assert_eq!(h.market(market_id).await["resolved"], false);
+
+    h.advance(1);
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("MarketResolved:"), "{response}");
+
+    let query = format!("query {{ market(id: {market_id}) {{ resolved outcome resolvedBy }} }}");
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(market["outcome"], true);
+    assert_eq!(market["resolvedBy"], AccountOwner::from(h.alice.public_key()).to_string());
+
+    // ResolveMarket cannot change a settled outcome, for the creator and
+    // the admin alike
+    let response = h.execute(&h.bob, Operation::ResolveMarket { market_id, outcome: false, evidence: None }).await;
+    assert_eq!(response, format!("ERROR: Market {market_id} is already resolved"));
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: false, evidence: None }).await;
+    assert_eq!(response, format!("ERROR: Market {market_id} is already resolved"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn buys_fill_resting_asks_by_price_then_time_before_the_amm() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Order book?", tokens("1000"), 3600).await;
+
+    // Bob buys 12 YES from the AMM: 500 * 12 / 488
+    let bob_cost = attos(12_295_081_967_213_114_755);
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("12"), tokens("13")).await, format!("SharesPurchased:{bob_cost}"));
+
+    // Asks well below the AMM's marginal price of about 1.05 tokens
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 9_000, "4").await, "LimitOrderPlaced:0");
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 8_000, "4").await, "LimitOrderPlaced:1");
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 8_000, "4").await, "LimitOrderPlaced:2");
+    let response = h.place_order(&h.bob, market_id, OrderSide::Sell, 8_000, "1").await;
+    assert_eq!(response, "ERROR: Insufficient shares: holding 0. but the order needs 1.");
+    assert_eq!(
+        h.yes_levels(market_id, "asks").await,
+        vec![(8_000, "8.".to_string(), 2), (9_000, "4.".to_string(), 1)]
+    );
+
+    // Cheapest level first, and within it the older order fills completely
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("6"), tokens("5")).await, "SharesPurchased:4.8");
+    assert_eq!(
+        h.yes_levels(market_id, "asks").await,
+        vec![(8_000, "2.".to_string(), 1), (9_000, "4.".to_string(), 1)]
+    );
+    let market = h.market(market_id).await;
+    assert_eq!(market["yesPool"], "488.");
+    assert_eq!(market["totalYesShares"], "512.");
+
+    // The book runs dry and the last 2 shares come from the AMM:
+    // 1.6 + 3.6 + (500 + bob_cost) * 2 / 486
+    let expected = attos(7_308_210_213_856_844_094);
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("8"), tokens("8")).await, format!("SharesPurchased:{expected}"));
+    assert!(h.yes_levels(market_id, "asks").await.is_empty());
+    let market = h.market(market_id).await;
+    assert_eq!(market["yesPool"], "486.");
+    assert_eq!(market["escrow"], tokens("1000").saturating_add(bob_cost).saturating_add(attos(2_108_210_213_856_844_094)).to_string());
+
+    let query = format!("query {{ limitOrder(marketId: {market_id}, id: 2) {{ status filledShares {{ {AMOUNT} }} }} }}");
+    let order = h.alice.graphql_query(h.app_id, query).await.response["limitOrder"].clone();
+    assert_eq!(order["status"], "Filled");
+    assert_eq!(decode_amount(&order["filledShares"]), tokens("4"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn book_fills_charge_the_taker_and_rebate_part_of_the_fee_to_the_maker() {
+    let h = &Harness::with_config(|config| {
+        config.fee_bps = 100;
+        config.maker_rebate_bps = Some(3_000);
+    })
+    .await;
+    let market_id = h.create_market(&h.alice, "Rebates?", tokens("200"), 3600).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("20")).await.starts_with("SharesPurchased:"));
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = &format!(
+        "query {{ makerRebates(owner: \"{bob}\") {{ {AMOUNT} }} stats {{ contractBalance {{ {AMOUNT} }} }} \
+            market(id: {market_id}) {{ feesCollected {{ {AMOUNT} }} }} }}"
+    );
+    // (Bob's rebates, contract balance, fees collected for the LPs)
+    let totals = || async move {
+        let response = h.alice.graphql_query(h.app_id, query.clone()).await.response;
+        (
+            decode_amount(&response["makerRebates"]),
+            decode_amount(&response["stats"]["contractBalance"]),
+            decode_amount(&response["market"]["feesCollected"]),
+        )
+    };
+    let (rebates, balance_before, collected_before) = totals().await;
+    assert_eq!(rebates, Amount::ZERO);
+
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 4_000, "3").await, "LimitOrderPlaced:0");
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 4_500, "4").await, "LimitOrderPlaced:1");
+
+    // 2 at 0.4 plus the 1% fee; a 30% share of the 0.008 fee goes to Bob
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("2"), tokens("1")).await, "SharesPurchased:0.808");
+    assert_eq!(totals().await.0, tokens("0.0024"));
+
+    // A crossing bid takes 1 at 0.4 and 4 at 0.45, paying 0.022 in fees
+    assert_eq!(h.place_order(&h.alice, market_id, OrderSide::Buy, 4_500, "5").await, "LimitOrderPlaced:2");
+    assert_eq!(totals().await.0, tokens("0.009"));
+
+    // An odd partial fill rounds the fee and then the rebate down; the LPs
+    // get what is left
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Sell, 4_700, "1").await, "LimitOrderPlaced:3");
+    let operation = Operation::PlaceLimitOrder {
+        market_id,
+        is_yes: true,
+        side: OrderSide::Buy,
+        price_bps: 4_700,
+        shares: tokens("0.333333333333333333"),
+    };
+    assert_eq!(h.execute(&h.alice, operation).await, "LimitOrderPlaced:4");
+
+    // Every taker fee landed in the contract balance and splits exactly
+    // into the maker's rebates and the LPs' share
+    let (rebates, balance, collected) = totals().await;
+    let taker_fees = attos(31_566_666_666_666_666);
+    assert_eq!(balance, balance_before.saturating_add(taker_fees));
+    assert_eq!(rebates, attos(9_469_999_999_999_999));
+    assert_eq!(collected, collected_before.saturating_add(attos(22_096_666_666_666_667)));
+    assert_eq!(rebates.saturating_add(collected.saturating_sub(collected_before)), taker_fees);
+
+    // Bob's bid crossing his own ask rests instead, earning nothing
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Buy, 4_700, "1").await, "LimitOrderPlaced:5");
+    assert_eq!(totals().await, (rebates, balance, collected));
+
+    assert_eq!(h.execute(&h.alice, Operation::WithdrawRebates).await, "ERROR: No maker rebates to withdraw");
+    assert_eq!(h.execute(&h.bob, Operation::WithdrawRebates).await, format!("RebatesWithdrawn:{rebates}"));
+    assert_eq!(h.execute(&h.bob, Operation::WithdrawRebates).await, "ERROR: No maker rebates to withdraw");
+    assert_eq!(totals().await, (Amount::ZERO, balance.saturating_sub(rebates), collected));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn order_book_depth_merges_resting_orders_with_the_amm() {
+    let h = &Harness::new().await;
+    // Pools of 100 YES and 100 NO: YES trades at 50%
+    let market_id = h.create_market(&h.alice, "Depth?", tokens("200"), 3600).await;
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Buy, 4_950, "2").await, "LimitOrderPlaced:0");
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Buy, 4_900, "3").await, "LimitOrderPlaced:1");
+
+    let depth = |levels: u32| async move {
+        let query = format!(
+            "query {{ orderBook(marketId: {market_id}, levels: {levels}) {{ yes {{ \
+                bids {{ priceBps source orders shares {{ {AMOUNT} }} ammShares {{ {AMOUNT} }} ammCostToReach {{ {AMOUNT} }} }} \
+                asks {{ priceBps source orders shares {{ {AMOUNT} }} ammShares {{ {AMOUNT} }} ammCostToReach {{ {AMOUNT} }} }} \
+            }} }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["orderBook"]["yes"].clone()
+    };
+    let prices = |levels: &Value| levels.as_array().unwrap().iter().map(|l| l["priceBps"].as_u64().unwrap()).collect::<Vec<_>>();
+
+    // Bids best first: an order alone, an order beside the AMM, the AMM alone
+    let book = depth(3).await;
+    assert_eq!(prices(&book["bids"]), vec![4_950, 4_900, 4_800]);
+    assert_eq!(book["bids"][0]["source"], "ORDERS");
+    assert_eq!(book["bids"][0]["ammCostToReach"], Value::Null);
+    assert_eq!(decode_amount(&book["bids"][0]["shares"]), tokens("2"));
+    let both = &book["bids"][1];
+    assert_eq!(both["source"], "BOTH");
+    assert_eq!(both["orders"], 1);
+    let amm_shares = decode_amount(&both["ammShares"]);
+    assert!(amm_shares > Amount::ZERO);
+    assert_eq!(decode_amount(&both["shares"]), tokens("3").saturating_add(amm_shares));
+    assert_eq!(book["bids"][2]["source"], "AMM");
+    assert_eq!(book["bids"][2]["orders"], 0);
+    assert_eq!(prices(&book["asks"]), vec![5_100, 5_200, 5_300]);
+
+    // Every step to 99% is quoted; at 80% the AMM has sold 50 YES for 100
+    // tokens, and bought 100 back for 50 by 20%
+    let book = depth(100).await;
+    let asks = book["asks"].as_array().unwrap();
+    assert_eq!(asks.len(), 49);
+    let to_80 = asks.iter().position(|l| l["priceBps"] == 8_000).unwrap();
+    assert_eq!(decode_amount(&asks[to_80]["ammCostToReach"]), tokens("100"));
+    let sold = asks[..=to_80].iter().map(|l| decode_amount(&l["ammShares"])).fold(Amount::ZERO, Amount::saturating_add);
+    assert_eq!(sold, tokens("50"));
+    let bids = book["bids"].as_array().unwrap();
+    assert_eq!(bids.len(), 50);
+    let to_20 = bids.iter().find(|l| l["priceBps"] == 2_000).unwrap();
+    assert_eq!(decode_amount(&to_20["ammCostToReach"]), tokens("50"));
+
+    let query = format!("query {{ orderBook(marketId: {market_id}, levels: 101) {{ marketId }} }}");
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for too many levels");
+    };
+    assert_eq!(errors[0].message, "levels must be at most 100");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crossing_orders_fill_partially_and_cancel_for_refunds() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Crossing?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    // Alice asks 3 at 0.7; Bob bids 5 at 0.75 and trades 3 at Alice's price
+    assert_eq!(h.place_order(&h.alice, market_id, OrderSide::Sell, 7_000, "3").await, "LimitOrderPlaced:0");
+    assert_eq!(h.place_order(&h.bob, market_id, OrderSide::Buy, 7_500, "5").await, "LimitOrderPlaced:1");
+    assert!(h.yes_levels(market_id, "asks").await.is_empty());
+    assert_eq!(h.yes_levels(market_id, "bids").await, vec![(7_500, "2.".to_string(), 1)]);
+    assert_eq!(h.market(market_id).await["volume"], tokens("14.6").to_string());
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ openOrders(owner: \"{bob}\") {{ id status filledShares {{ {AMOUNT} }} escrow {{ {AMOUNT} }} }} }}"
+    );
+    let orders = h.alice.graphql_query(h.app_id, query.as_str()).await.response["openOrders"].clone();
+    assert_eq!(orders.as_array().unwrap().len(), 1);
+    assert_eq!(orders[0]["status"], "PartiallyFilled");
+    assert_eq!(decode_amount(&orders[0]["filledShares"]), tokens("3"));
+    // 5 * 0.75 escrowed, less 3 * 0.75 for the filled part
+    assert_eq!(decode_amount(&orders[0]["escrow"]), tokens("1.5"));
+
+    // Another resting ask, left open into resolution
+    assert_eq!(h.place_order(&h.alice, market_id, OrderSide::Sell, 20_000, "2").await, "LimitOrderPlaced:2");
+
+    // Only the owner may cancel, even after the market resolves
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let cancel = |order_id| Operation::CancelOrder { market_id, order_id };
+    assert_eq!(h.execute(&h.alice, cancel(1)).await, "ERROR: Not authorized: not the order owner");
+    assert_eq!(h.execute(&h.bob, cancel(1)).await, "OrderCancelled:1.5");
+    assert_eq!(h.execute(&h.bob, cancel(1)).await, "ERROR: Order 1 is not cancellable (status: Cancelled)");
+    assert_eq!(h.execute(&h.alice, cancel(2)).await, "OrderCancelled:2.");
+    assert!(h.alice.graphql_query(h.app_id, query.as_str()).await.response["openOrders"].as_array().unwrap().is_empty());
+
+    // Returned shares count toward the payout again: Alice holds 7 of her 10
+    // (3 went to Bob), paid from the 102.5 pool over 60 YES shares
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with(&format!("WinningsClaimed:{}:", attos(11_958_333_333_333_333_333))), "{response}");
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await.starts_with("WinningsClaimed:5.125:"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn profile_lists_created_markets_positions_and_claimables() {
+    let h = Harness::new().await;
+    let create = |description: String| Operation::CreateMarket {
+        question: "Described?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: Some(description),
+        resolution_source: Some("https://example.com/results".to_string()),
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    let response = h.execute(&h.bob, create("x".repeat(2001))).await;
+    assert_eq!(response, "ERROR: description exceeds 2000 bytes");
+    assert_eq!(created_id(&h.execute(&h.bob, create("Settles on the official tally".to_string())).await), 0);
+    assert_eq!(h.create_market(&h.alice, "Alice's?", tokens("100"), 3600).await, 1);
+    assert_eq!(h.create_market(&h.bob, "Bob's second?", tokens("100"), 3600).await, 2);
+
+    let query = "query { market(id: 0) { description resolutionSource } }";
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(market["description"], "Settles on the official tally");
+    assert_eq!(market["resolutionSource"], "https://example.com/results");
+    let query = "query { market(id: 1) { description resolutionSource } }";
+    assert_eq!(h.alice.graphql_query(h.app_id, query).await.response["market"]["description"], "");
+
+    // The creator is matched in the same form the markets report it
+    let bob = h.alice.graphql_query(h.app_id, "query { market(id: 0) { creator } }").await.response["market"]["creator"].clone();
+    assert_eq!(bob, AccountOwner::from(h.bob.public_key()).to_string());
+    let query = format!("query {{ marketsByCreator(creator: {bob}, limit: 1, offset: 1) {{ totalCount markets {{ id creator }} }} }}");
+    let page = h.alice.graphql_query(h.app_id, query).await.response["marketsByCreator"].clone();
+    assert_eq!(page["totalCount"], 2);
+    assert_eq!(page["markets"][0]["id"], 2);
+    assert_eq!(page["markets"][0]["creator"], bob);
+    let query = "query { marketsByCreator(creator: \"Address32(bob)\") { totalCount } }";
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for an unparseable creator");
+    };
+    assert!(errors[0].message.starts_with("Invalid account format for argument \"creator\": \"Address32(bob)\""), "{errors:?}");
+
+    assert_eq!(h.buy(&h.alice, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert_eq!(h.buy(&h.alice, 2, false, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    h.advance(3601);
+    assert!(h.execute(&h.bob, Operation::ResolveMarket { market_id: 0, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+
+    let alice = AccountOwner::from(h.alice.public_key());
+    let query = format!(
+        "query {{ myActivity(owner: \"{alice}\") {{ \
+            marketsCreated {{ id }} openPositions {{ marketId }} \
+            claimable {{ marketId amount {{ {AMOUNT} }} }} totalClaimable {{ {AMOUNT} }} \
+        }} }}"
+    );
+    let activity = h.alice.graphql_query(h.app_id, query.as_str()).await.response["myActivity"].clone();
+    assert_eq!(activity["marketsCreated"], serde_json::json!([{ "id": 1 }]));
+    assert_eq!(activity["openPositions"], serde_json::json!([{ "marketId": 2 }]));
+    // 10 of 60 YES shares against a 102.5 pool
+    let payout = attos(17_083_333_333_333_333_333);
+    assert_eq!(activity["claimable"][0]["marketId"], 0);
+    assert_eq!(decode_amount(&activity["claimable"][0]["amount"]), payout);
+    assert_eq!(decode_amount(&activity["totalClaimable"]), payout);
+
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id: 0 }).await;
+    assert!(response.starts_with(&format!("WinningsClaimed:{payout}:")), "{response}");
+    let activity = h.alice.graphql_query(h.app_id, query.as_str()).await.response["myActivity"].clone();
+    assert_eq!(activity["claimable"], serde_json::json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn claims_close_at_the_deadline_and_leftovers_can_be_recovered() {
+    let h = Harness::new().await;
+    let window = Config::default().claim_window_secs;
+    let mut carol = h.alice.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+
+    let market_id = h.create_market(&h.bob, "Claim window?", tokens("100"), 3600).await;
+    for trader in [&h.alice, &h.bob, &carol] {
+        let response = h.buy(trader, market_id, true, tokens("5"), tokens("10")).await;
+        assert!(response.starts_with("SharesPurchased:"), "{response}");
+    }
+    let recover = || Operation::RecoverUnclaimed { market_id };
+    let still_open = format!("ERROR: Market {market_id} is unresolved or its claim window is still open");
+    assert_eq!(h.execute(&h.bob, recover()).await, still_open);
+
+    h.advance(3601);
+    let deadline = h.now().saturating_add(TimeDelta::from_secs(window));
+    assert!(h.execute(&h.bob, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let query = format!("query {{ market(id: {market_id}) {{ claimDeadline unclaimed {{ {AMOUNT} }} }} }}");
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    assert_eq!(market["claimDeadline"], deadline.micros().to_string());
+
+    // Just before the deadline, and exactly at it
+    h.advance(window - 1);
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+    h.advance(1);
+    assert_eq!(h.now(), deadline);
+    let response = h.execute(&h.alice, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+    assert_eq!(h.execute(&h.bob, recover()).await, still_open);
+
+    // One microsecond late
+    h.validator.clock().add(TimeDelta::from_micros(1));
+    let response = h.execute(&carol, Operation::ClaimWinnings { market_id }).await;
+    assert_eq!(response, format!("ERROR: The claim window for market {market_id} has closed"));
+
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    let unclaimed = decode_amount(&market["unclaimed"]);
+    assert!(unclaimed > Amount::ZERO);
+    let response = h.execute(&carol, recover()).await;
+    assert!(response.starts_with("ERROR: Not authorized"), "{response}");
+    assert_eq!(h.execute(&h.alice, recover()).await, format!("UnclaimedRecovered:{unclaimed}"));
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    assert_eq!(decode_amount(&market["unclaimed"]), Amount::ZERO);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn series_spawn_one_market_per_interval_up_to_count() {
+    let h = Harness::new().await;
+    // 2024-02-28T12:00:00Z, so the first instance ends on a leap day
+    h.validator.clock().set(Timestamp::from(1_709_121_600_000_000));
+    let day = 24 * 60 * 60;
+
+    let operation = Operation::CreateMarketSeries {
+        template: SeriesTemplate {
+            question: "BTC above $100k on {date}?".to_string(),
+            categories: vec!["crypto".to_string()],
+            duration_secs: day,
+            liquidity_per_instance: tokens("50"),
+        },
+        interval_secs: day,
+        count: 2,
+    };
+    assert_eq!(h.execute(&h.alice, operation).await, "MarketSeriesCreated:0:0");
+    let harness = &h;
+    let question = |id: u64| async move {
+        let query = format!("query {{ market(id: {id}) {{ question creator }} }}");
+        harness.alice.graphql_query(harness.app_id, query).await.response["market"].clone()
+    };
+    assert_eq!(question(0).await["question"], "BTC above $100k on 2024-02-29?");
+
+    // Not before the first market's end time has passed
+    let spawn = || Operation::SpawnNextInSeries { series_id: 0 };
+    let first_end = h.now().saturating_add(TimeDelta::from_secs(day));
+    let not_ready = format!("ERROR: Series 0 cannot open its next market until {}", first_end.micros());
+    assert_eq!(h.execute(&h.bob, spawn()).await, not_ready);
+    h.advance(day);
+    assert_eq!(h.execute(&h.bob, spawn()).await, not_ready);
+
+    // Anyone may spawn; the market still belongs to the series creator
+    h.validator.clock().add(TimeDelta::from_micros(1));
+    assert_eq!(created_id(&h.execute(&h.bob, spawn()).await), 1);
+    let market = question(1).await;
+    assert_eq!(market["question"], "BTC above $100k on 2024-03-01?");
+    assert_eq!(market["creator"], AccountOwner::from(h.alice.public_key()).to_string());
+
+    h.advance(day + 1);
+    assert_eq!(h.execute(&h.bob, spawn()).await, "ERROR: Series 0 has already opened all 2 markets");
+
+    let query = format!("query {{ series(id: 0) {{ count spawnedMarketIds escrow {{ {AMOUNT} }} }} }}");
+    let series = h.alice.graphql_query(h.app_id, query).await.response["series"].clone();
+    assert_eq!(series["spawnedMarketIds"], serde_json::json!([0, 1]));
+    assert_eq!(decode_amount(&series["escrow"]), Amount::ZERO);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trading_fees_accrue_to_the_liquidity_provider() {
+    let h = Harness::with_config(|config| config.fee_bps = 100).await;
+    let market_id = h.create_market(&h.bob, "Fees?", tokens("100"), 3600).await;
+
+    // 1% on top of the AMM cost of 12.5; escrow takes only the AMM cost
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("10"), tokens("12.6")).await, "ERROR: Cost 12.625 exceeds max_cost 12.6 (pool_in=50., pool_out=50., shares=10.)");
+    assert_eq!(h.buy(&h.alice, market_id, true, tokens("10"), tokens("12.625")).await, "SharesPurchased:12.625");
+    let market = h.market(market_id).await;
+    assert_eq!(market["escrow"], "112.5");
+    assert_eq!(market["volume"], "12.5");
+
+    // 1% off the gross proceeds = no_pool * 5 / (yes_pool + 5) = 62.5 * 5 / 45
+    let gross = 62_500_000_000_000_000_000u128 * 5 / 45;
+    let sell_fee = gross / 100;
+    let operation = Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    assert_eq!(h.trade(&h.alice, operation).await, format!("SharesSold:{}", attos(gross - sell_fee)));
+    for (shares, is_yes) in [("3.333333333333333333", false), ("0.000000000000000007", true), ("1.1", false)] {
+        let response = h.buy(&h.alice, market_id, is_yes, tokens(shares), tokens("10")).await;
+        assert!(response.starts_with("SharesPurchased:"), "{response}");
+    }
+
+    let lp_query = format!(
+        "query {{ lpPosition(owner: \"{}\", marketId: {market_id}) {{ shares {{ {AMOUNT} }} pendingFees {{ {AMOUNT} }} }} \
+            market(id: {market_id}) {{ feesCollected {{ {AMOUNT} }} feesDistributed {{ {AMOUNT} }} }} }}",
+        AccountOwner::from(h.bob.public_key())
+    );
+    let response = h.alice.graphql_query(h.app_id, lp_query.as_str()).await.response;
+    assert_eq!(decode_amount(&response["lpPosition"]["shares"]), tokens("100"));
+    let collected = decode_amount(&response["market"]["feesCollected"]);
+    let pending = decode_amount(&response["lpPosition"]["pendingFees"]);
+    assert!(collected > tokens("0.125").saturating_add(attos(sell_fee)));
+    assert!(pending > Amount::ZERO && pending <= collected, "{pending} of {collected}");
+
+    // Only the provider can claim, and only once per accrual
+    let claim = || Operation::ClaimLpFees { market_id };
+    assert_eq!(h.execute(&h.alice, claim()).await, format!("ERROR: No liquidity position in market {market_id}"));
+    assert_eq!(h.execute(&h.bob, claim()).await, format!("LpFeesClaimed:{pending}"));
+    assert_eq!(h.execute(&h.bob, claim()).await, format!("ERROR: No LP fees to claim in market {market_id}"));
+
+    let response = h.alice.graphql_query(h.app_id, lp_query.as_str()).await.response;
+    assert_eq!(decode_amount(&response["lpPosition"]["pendingFees"]), Amount::ZERO);
+    let distributed = decode_amount(&response["market"]["feesDistributed"]);
+    assert_eq!(distributed, pending);
+    assert!(distributed <= decode_amount(&response["market"]["feesCollected"]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn incentives_stream_to_lps_and_the_rest_returns_at_resolution() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Incentivized?", tokens("100"), 3600).await;
+
+    let fund = |amount: &str, duration_secs| Operation::FundIncentives {
+        market_id,
+        amount: tokens(amount),
+        duration_secs,
+        sponsor_name: String::new(),
+        sponsor_url: String::new(),
+    };
+    assert_eq!(h.execute(&h.bob, fund("0", 7200)).await, "ERROR: Incentive amount must be greater than zero");
+    assert_eq!(h.execute(&h.bob, fund("36", 0)).await, "ERROR: Incentive duration must be greater than zero");
+    assert_eq!(h.execute(&h.bob, fund("36", 7200)).await, "IncentivesFunded:36.");
+
+    // Alice, the only LP, earns everything streamed: half of 18 after 1800s
+    let claim = || Operation::ClaimIncentives { market_id };
+    h.advance(1800);
+    assert_eq!(h.execute(&h.bob, claim()).await, format!("ERROR: No liquidity position in market {market_id}"));
+    assert_eq!(h.execute(&h.alice, claim()).await, "IncentivesClaimed:9.");
+    let response = h.execute(&h.alice, claim()).await;
+    assert_eq!(response, format!("ERROR: No incentive rewards to claim in market {market_id}"));
+
+    // Resolving stops the stream at 36 * 3601 / 7200 = 18.005; Alice keeps
+    // her 9.005 and the rest goes back to Bob
+    h.advance(1801);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let alice = AccountOwner::from(h.alice.public_key());
+    let query = format!(
+        "query {{ incentives(marketId: {market_id}) {{ streamed {{ {AMOUNT} }} claimed {{ {AMOUNT} }} returned {{ {AMOUNT} }} }} \
+            pendingIncentives(owner: \"{alice}\", marketId: {market_id}) {{ {AMOUNT} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(decode_amount(&response["incentives"]["streamed"]), tokens("18.005"));
+    assert_eq!(decode_amount(&response["incentives"]["claimed"]), tokens("9"));
+    assert_eq!(decode_amount(&response["incentives"]["returned"]), tokens("17.995"));
+    assert_eq!(decode_amount(&response["pendingIncentives"]), tokens("9.005"));
+
+    h.advance(3600);
+    assert_eq!(h.execute(&h.alice, claim()).await, "IncentivesClaimed:9.005");
+    let response = h.execute(&h.alice, claim()).await;
+    assert_eq!(response, format!("ERROR: No incentive rewards to claim in market {market_id}"));
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(decode_amount(&response["incentives"]["claimed"]), tokens("18.005"));
+    assert_eq!(decode_amount(&response["pendingIncentives"]), Amount::ZERO);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sponsors_accumulate_on_the_market_and_only_the_admin_edits_them() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Sponsored?", tokens("100"), 3600).await;
+    let bob = AccountOwner::from(h.bob.public_key());
+    let fund = |amount: &str, name: &str, url: &str| Operation::FundIncentives {
+        market_id,
+        amount: tokens(amount),
+        duration_secs: 3600,
+        sponsor_name: name.to_string(),
+        sponsor_url: url.to_string(),
+    };
+    let sponsors = || async move {
+        let query = format!("query {{ market(id: {market_id}) {{ sponsors {{ account name url amount {{ {AMOUNT} }} }} }} }}");
+        let response = h.alice.graphql_query(h.app_id, query).await.response;
+        response["market"]["sponsors"].as_array().unwrap().iter()
+            .map(|s| {
+                assert_eq!(s["account"].as_str().unwrap(), bob.to_string());
+                (s["name"].as_str().unwrap().to_string(), s["url"].as_str().unwrap().to_string(), decode_amount(&s["amount"]))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let response = h.execute(&h.bob, fund("1", &"x".repeat(65), "")).await;
+    assert_eq!(response, "ERROR: sponsor_name exceeds 64 bytes");
+    let response = h.execute(&h.bob, fund("1", "", "https://acme.example")).await;
+    assert_eq!(response, "ERROR: A sponsor link needs a sponsor name");
+
+    // Both fundings go into the one program; only the named one is shown
+    assert_eq!(h.execute(&h.bob, fund("500", "Acme", "https://acme.example")).await, "IncentivesFunded:500.");
+    assert_eq!(h.execute(&h.alice, fund("100", "", "")).await, "IncentivesFunded:100.");
+    assert_eq!(sponsors().await, vec![("Acme".to_string(), "https://acme.example".to_string(), tokens("500"))]);
+    // Funding again adds to the sponsor's amount and renames it
+    assert_eq!(h.execute(&h.bob, fund("250", "Acme Corp", "")).await, "IncentivesFunded:250.");
+    assert_eq!(sponsors().await, vec![("Acme Corp".to_string(), String::new(), tokens("750"))]);
+    let query = format!("query {{ incentives(marketId: {market_id}) {{ budget {{ {AMOUNT} }} }} }}");
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(decode_amount(&response["incentives"]["budget"]), tokens("850"));
+
+    let edit = |name: &str, url: &str| Operation::EditSponsor {
+        market_id,
+        sponsor: bob,
+        name: name.to_string(),
+        url: url.to_string(),
+    };
+    let remove = || Operation::RemoveSponsor { market_id, sponsor: bob };
+    let response = h.execute(&h.bob, edit("Acme", "https://acme.example")).await;
+    assert_eq!(response, "ERROR: Not authorized: only the admin can edit sponsors");
+    assert_eq!(h.execute(&h.bob, remove()).await, "ERROR: Not authorized: only the admin can remove sponsors");
+    let response = h.execute(&h.alice, edit("Acme", &"x".repeat(257))).await;
+    assert_eq!(response, "ERROR: sponsor_url exceeds 256 bytes");
+    let stranger = Operation::RemoveSponsor { market_id, sponsor: AccountOwner::CHAIN };
+    let response = h.execute(&h.alice, stranger).await;
+    assert_eq!(response, format!("ERROR: {} has not funded market {market_id}", AccountOwner::CHAIN));
+
+    assert_eq!(h.execute(&h.alice, edit("Acme", "https://acme.example")).await, format!("SponsorEdited:{market_id}:{bob}"));
+    assert_eq!(sponsors().await, vec![("Acme".to_string(), "https://acme.example".to_string(), tokens("750"))]);
+    assert_eq!(h.execute(&h.alice, remove()).await, format!("SponsorRemoved:{market_id}:{bob}"));
+    assert!(sponsors().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lp_pnl_marks_the_stake_until_settlement_fixes_it() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "LP PnL?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let alice = AccountOwner::from(h.alice.public_key());
+    let query = format!(
+        "query {{ lpPnl(owner: \"{alice}\", marketId: {market_id}) {{ deposited {{ {AMOUNT} }} value {{ {AMOUNT} }} \
+            feesEarned {{ {AMOUNT} }} pnl {{ attos tokens }} settled history {{ deposit amount {{ {AMOUNT} }} }} }} }}"
+    );
+    // Bob's 10 YES are marked at 62.5 / 102.5 against the 112.5 escrowed,
+    // mirroring his unrealized loss
+    let pnl = h.alice.graphql_query(h.app_id, query.as_str()).await.response["lpPnl"].clone();
+    assert_eq!(decode_amount(&pnl["deposited"]), tokens("100"));
+    assert_eq!(decode_amount(&pnl["value"]), attos(106_402_439_024_390_243_903));
+    assert_eq!(decode_amount(&pnl["feesEarned"]), Amount::ZERO);
+    assert_eq!(pnl["pnl"]["tokens"], "6.402439024390243903");
+    assert_eq!(pnl["settled"], false);
+    assert_eq!(pnl["history"].as_array().unwrap().len(), 1);
+    assert_eq!(pnl["history"][0]["deposit"], true);
+
+    // YES wins: Bob's 10 of the 60 YES shares claim a sixth of 102.5
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let pnl = h.alice.graphql_query(h.app_id, query.as_str()).await.response["lpPnl"].clone();
+    assert_eq!(decode_amount(&pnl["value"]), attos(95_416_666_666_666_666_667));
+    assert_eq!(pnl["pnl"]["attos"], "-4583333333333333333");
+    assert_eq!(pnl["settled"], true);
+
+    // Payouts out of the escrow leave the settled figure alone
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await.starts_with("WinningsClaimed:"));
+    let pnl = h.alice.graphql_query(h.app_id, query.as_str()).await.response["lpPnl"].clone();
+    assert_eq!(decode_amount(&pnl["value"]), attos(95_416_666_666_666_666_667));
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!("query {{ lpPnl(owner: \"{bob}\", marketId: {market_id}) {{ settled }} }}");
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query.as_str()).await else {
+        panic!("Expected a GraphQL error for an account without liquidity");
+    };
+    assert_eq!(errors[0].message, format!("LP position of {bob} in market {market_id} not found"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trades_execute_up_to_their_deadline() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Deadline?", tokens("100"), 3600).await;
+    let deadline = h.now().saturating_add(TimeDelta::from_secs(60));
+    let buy = || Operation::BuyShares {
+        market_id, is_yes: true, shares: tokens("10"), max_cost: tokens("20"), deadline: Some(deadline), max_price_impact_bps: None,
+    };
+    let sell = || Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: Some(deadline) };
+
+    h.advance(60);
+    assert_eq!(h.trade(&h.bob, buy()).await, "SharesPurchased:12.5");
+    assert!(h.execute(&h.bob, sell()).await.starts_with("SharesSold:"));
+
+    h.validator.clock().add(TimeDelta::from_micros(1));
+    let expired = format!("ERROR: Trade deadline {} has passed", deadline.micros());
+    let before = h.market(market_id).await;
+    assert_eq!(h.execute(&h.bob, buy()).await, expired);
+    assert_eq!(h.execute(&h.bob, sell()).await, expired);
+    assert_eq!(h.market(market_id).await, before);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_are_paged_by_id() {
+    let h = Harness::new().await;
+    for question in ["First?", "Second?", "Third?"] {
+        h.create_market(&h.alice, question, tokens("10"), 3600).await;
+    }
+    let harness = &h;
+    let page = |arguments: &str| {
+        let query = format!("query {{ markets{arguments} {{ totalCount markets {{ id }} }} }}");
+        async move { harness.alice.graphql_query(harness.app_id, query).await.response["markets"].clone() }
+    };
+
+    let all = page("").await;
+    assert_eq!(all["totalCount"], 3);
+    assert_eq!(all["markets"], serde_json::json!([{ "id": 0 }, { "id": 1 }, { "id": 2 }]));
+    let middle = page("(limit: 1, offset: 1)").await;
+    assert_eq!(middle["markets"], serde_json::json!([{ "id": 1 }]));
+    let past_end = page("(limit: 10, offset: 7)").await;
+    assert_eq!(past_end["totalCount"], 3);
+    assert_eq!(past_end["markets"], serde_json::json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn category_filters_ignore_case_and_page_the_filtered_set() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    for categories in [vec!["Crypto"], vec!["crypto", "Sports"], vec!["politics"]] {
+        let operation = Operation::CreateMarket {
+            question: "Tagged?".to_string(),
+            categories: categories.into_iter().map(String::from).collect(),
+            end_time,
+            initial_liquidity: tokens("10"),
+            early_resolution_allowed: false,
+            max_position_per_account: None,
+            max_position: None,
+            creator_trading_disabled: false,
+            fee_ramp: None,
+            description: None,
+            resolution_source: None,
+            parent: None,
+            resolvers: vec![],
+            threshold: 0,
+            max_probability_move_bps: None,
+            max_trade_shares: None,
+        };
+        assert!(h.execute(&h.alice, operation).await.starts_with("MarketCreated:"));
+    }
+    let harness = &h;
+    let query = |query: String| async move { harness.alice.graphql_query(harness.app_id, query).await.response };
+
+    let page = query("query { markets(category: \"CRYPTO\", limit: 1, offset: 1) { totalCount markets { id } } }".into()).await;
+    assert_eq!(page["markets"]["totalCount"], 2);
+    assert_eq!(page["markets"]["markets"], serde_json::json!([{ "id": 1 }]));
+    let unknown = query("query { markets(category: \"weather\") { totalCount markets { id } } }".into()).await;
+    assert_eq!(unknown["markets"]["totalCount"], 0);
+    assert_eq!(unknown["markets"]["markets"], serde_json::json!([]));
+    let active = query("query { activeMarkets(category: \"sports\") { id } }".into()).await;
+    assert_eq!(active["activeMarkets"], serde_json::json!([{ "id": 1 }]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traders_count_once_overall_and_once_per_category() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    for categories in [vec!["Crypto"], vec!["crypto", "Sports"], vec!["politics"]] {
+        let operation = Operation::CreateMarket {
+            question: "Tagged?".to_string(),
+            categories: categories.into_iter().map(String::from).collect(),
+            end_time,
+            initial_liquidity: tokens("10"),
+            early_resolution_allowed: false,
+            max_position_per_account: None,
+            max_position: None,
+            creator_trading_disabled: false,
+            fee_ramp: None,
+            description: None,
+            resolution_source: None,
+            parent: None,
+            resolvers: vec![],
+            threshold: 0,
+            max_probability_move_bps: None,
+            max_trade_shares: None,
+        };
+        assert!(h.execute(&h.alice, operation).await.starts_with("MarketCreated:"));
+    }
+    // Bob trades twice in each crypto market, Alice once in the first
+    for (chain, market_id) in [(&h.bob, 0), (&h.bob, 1), (&h.bob, 0), (&h.bob, 1), (&h.alice, 0)] {
+        assert!(h.buy(chain, market_id, true, tokens("1"), tokens("10")).await.starts_with("SharesPurchased:"));
+    }
+
+    let query = "query { uniqueTraders categoryStats { name traderCount marketCount } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["uniqueTraders"], 2);
+    assert_eq!(
+        response["categoryStats"],
+        serde_json::json!([
+            { "name": "crypto", "traderCount": 2, "marketCount": 2 },
+            { "name": "sports", "traderCount": 1, "marketCount": 1 },
+            { "name": "politics", "traderCount": 0, "marketCount": 1 },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn search_ranks_phrase_then_all_terms_then_any_term() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    let markets = [
+        ("Will the bitcoin ETF double its volume?", "crypto"),
+        ("Will ETF inflows beat bitcoin miners?", "finance"),
+        ("Bitcoin above $100k?", "crypto"),
+        ("Rain tomorrow?", "weather"),
+        ("ETF approval by June?", "finance"),
+    ];
+    for (question, category) in markets {
+        let operation = Operation::CreateMarket {
+            question: question.to_string(),
+            categories: vec![category.to_string()],
+            end_time,
+            initial_liquidity: tokens("100"),
+            early_resolution_allowed: false,
+            max_position_per_account: None,
+            max_position: None,
+            creator_trading_disabled: false,
+            fee_ramp: None,
+            description: None,
+            resolution_source: None,
+            parent: None,
+            resolvers: vec![],
+            threshold: 0,
+            max_probability_move_bps: None,
+            max_trade_shares: None,
+        };
+        assert!(h.execute(&h.alice, operation).await.starts_with("MarketCreated:"));
+    }
+    // Volume orders markets of equal relevance
+    assert!(h.buy(&h.bob, 4, true, tokens("1"), tokens("2")).await.starts_with("SharesPurchased:"));
+
+    let harness = &h;
+    let search = |arguments: &str| {
+        let query = format!("query {{ searchMarkets({arguments}) {{ totalCount markets {{ id }} }} }}");
+        async move {
+            let page = harness.alice.graphql_query(harness.app_id, query).await.response["searchMarkets"].clone();
+            let ids: Vec<u64> = page["markets"].as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect();
+            (page["totalCount"].as_u64().unwrap(), ids)
+        }
+    };
+    assert_eq!(search("query: \"BITCOIN  etf\"").await, (4, vec![0, 1, 4, 2]));
+    assert_eq!(search("query: \"bitcoin etf\", limit: 2, offset: 1").await, (4, vec![1, 4]));
+    assert_eq!(search("query: \"weather\"").await, (1, vec![3]));
+    assert_eq!(search("query: \"   \"").await, (0, vec![]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_sort_before_paging_and_break_ties_on_id() {
+    let h = Harness::new().await;
+    // Ids 0..4; market 2 ends first, market 3 holds the most liquidity
+    for (liquidity, duration) in [("10", 7200), ("10", 7200), ("10", 3600), ("40", 7200)] {
+        h.create_market(&h.alice, "Sorted?", tokens(liquidity), duration).await;
+    }
+    assert!(h.buy(&h.bob, 1, true, tokens("1"), tokens("5")).await.starts_with("SharesPurchased:"));
+
+    let harness = &h;
+    let ids = |query: String| async move {
+        let response = harness.alice.graphql_query(harness.app_id, query).await.response;
+        let markets = response.as_object().unwrap().values().next().unwrap();
+        let markets = markets.get("markets").unwrap_or(markets);
+        markets.as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect::<Vec<u64>>()
+    };
+    let sorted = |sort: &str, offset: u64| format!("query {{ markets(sortBy: {sort}, limit: 2, offset: {offset}) {{ markets {{ id }} }} }}");
+    assert_eq!(ids(sorted("VOLUME_DESC", 0)).await, vec![1, 0]);
+    assert_eq!(ids(sorted("VOLUME_DESC", 2)).await, vec![2, 3]);
+    assert_eq!(ids(sorted("END_TIME_ASC", 0)).await, vec![2, 0]);
+    assert_eq!(ids(sorted("CREATED_AT_DESC", 0)).await, vec![3, 2]);
+    assert_eq!(ids(sorted("LIQUIDITY_DESC", 0)).await, vec![3, 1]);
+
+    let query = "query { markets(category: \"TEST\", sortBy: LIQUIDITY_DESC, limit: 1, offset: 1) { markets { id } } }";
+    assert_eq!(ids(query.to_string()).await, vec![1]);
+    let query = "query { activeMarkets(category: \"test\", sortBy: END_TIME_ASC) { id } }";
+    assert_eq!(ids(query.to_string()).await, vec![2, 0, 1, 3]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn position_lookup_values_shares_at_current_prices() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Valued?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let harness = &h;
+    let position = |owner: String| async move {
+        let query = format!(
+            "query {{ position(owner: \"{owner}\", marketId: {market_id}) {{ yesShares {{ {AMOUNT} }} currentValue {{ {AMOUNT} }} \
+                avgYesEntryPriceBps avgNoEntryPriceBps unrealizedPnl {{ attos tokens }} }} }}"
+        );
+        harness.alice.graphql_query(harness.app_id, query).await.response["position"].clone()
+    };
+    // 10 YES at no_pool / (yes_pool + no_pool) = 62.5 / 102.5
+    let bob = position(AccountOwner::from(h.bob.public_key()).to_string()).await;
+    assert_eq!(decode_amount(&bob["yesShares"]), tokens("10"));
+    assert_eq!(decode_amount(&bob["currentValue"]), attos(6_097_560_975_609_756_097));
+    // Paid 12.5 for the 10 shares, so worth 6.402... less than their basis
+    assert_eq!(bob["avgYesEntryPriceBps"], 12_500);
+    assert_eq!(bob["avgNoEntryPriceBps"], Value::Null);
+    assert_eq!(bob["unrealizedPnl"], serde_json::json!({ "attos": "-6402439024390243903", "tokens": "-6.402439024390243903" }));
+    assert_eq!(position(AccountOwner::from(h.alice.public_key()).to_string()).await, Value::Null);
+
+    // Once resolved the position is worth its claim
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let bob = position(AccountOwner::from(h.bob.public_key()).to_string()).await;
+    let claim = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    assert!(claim.starts_with(&format!("WinningsClaimed:{}:", decode_amount(&bob["currentValue"]))), "{claim}");
+
+    let query = format!("query {{ position(owner: \"nobody\", marketId: {market_id}) {{ claimed }} }}");
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for an unparseable owner");
+    };
+    assert!(errors[0].message.starts_with("Invalid account format for argument \"owner\": \"nobody\""), "{errors:?}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn my_positions_split_open_from_claimable_and_sort() {
+    let h = &Harness::new().await;
+    let big = h.create_market(&h.alice, "Big?", tokens("100"), 3600).await;
+    let small = h.create_market(&h.alice, "Small?", tokens("100"), 3600).await;
+    let won = h.create_market(&h.alice, "Won?", tokens("100"), 60).await;
+    let lost = h.create_market(&h.alice, "Lost?", tokens("100"), 60).await;
+    for (market_id, is_yes, shares) in [(big, true, "10"), (small, true, "2"), (won, true, "5"), (lost, false, "5")] {
+        assert!(h.buy(&h.bob, market_id, is_yes, tokens(shares), tokens("100")).await.starts_with("SharesPurchased:"));
+    }
+    h.advance(61);
+    for market_id in [won, lost] {
+        let resolve = Operation::ResolveMarket { market_id, outcome: true, evidence: None };
+        assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    }
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let my_positions = |sort: &'static str| async move {
+        let query = format!(
+            "query {{ myPositions(owner: \"{bob}\", sort: {sort}) {{ \
+                open {{ market {{ id question }} position {{ currentValue {{ {AMOUNT} }} avgYesEntryPriceBps }} }} \
+                toClaim {{ market {{ id }} position {{ currentValue {{ {AMOUNT} }} }} }} \
+                totals {{ totalValue {{ {AMOUNT} }} totalCost {{ {AMOUNT} }} totalClaimable {{ {AMOUNT} }} }} }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["myPositions"].clone()
+    };
+    let ids = |list: &Value| list.as_array().unwrap().iter().map(|e| e["market"]["id"].as_u64().unwrap()).collect::<Vec<_>>();
+    let value = |entry: &Value| decode_amount(&entry["position"]["currentValue"]);
+
+    // The bigger position is worth more but lost more of what it cost
+    let by_value = my_positions("VALUE_DESC").await;
+    assert_eq!(ids(&by_value["open"]), vec![big, small]);
+    assert_eq!(by_value["open"][0]["market"]["question"], "Big?");
+    assert_eq!(ids(&my_positions("PNL_DESC").await["open"]), vec![small, big]);
+    // The losing side of a settled market is neither open nor claimable
+    assert_eq!(ids(&by_value["toClaim"]), vec![won]);
+
+    let totals = &by_value["totals"];
+    let claimable = value(&by_value["toClaim"][0]);
+    assert_eq!(decode_amount(&totals["totalClaimable"]), claimable);
+    let open_value = by_value["open"].as_array().unwrap().iter().map(value).fold(Amount::ZERO, Amount::saturating_add);
+    assert_eq!(decode_amount(&totals["totalValue"]), open_value.saturating_add(claimable));
+
+    let claim = h.execute(&h.bob, Operation::ClaimWinnings { market_id: won }).await;
+    assert!(claim.starts_with(&format!("WinningsClaimed:{claimable}:")), "{claim}");
+    let after = my_positions("VALUE_ASC").await;
+    assert_eq!(ids(&after["open"]), vec![small, big]);
+    assert_eq!(after["toClaim"], serde_json::json!([]));
+    assert_eq!(decode_amount(&after["totals"]["totalClaimable"]), Amount::ZERO);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn portfolio_sums_open_positions_and_unclaimed_winnings() {
+    let h = Harness::new().await;
+    let open = h.create_market(&h.alice, "Open?", tokens("100"), 7200).await;
+    let won = h.create_market(&h.alice, "Won?", tokens("100"), 60).await;
+    let untouched = h.create_market(&h.alice, "Untouched?", tokens("100"), 7200).await;
+    for market_id in [open, won] {
+        assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    }
+    assert!(h.buy(&h.alice, untouched, true, tokens("1"), tokens("2")).await.starts_with("SharesPurchased:"));
+    h.advance(61);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: won, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+
+    let query = format!(
+        "query {{ portfolio(owner: \"{}\") {{ totalValue {{ {AMOUNT} }} claimable {{ {AMOUNT} }} positionCount \
+            costBasis {{ {AMOUNT} }} unrealizedPnl {{ attos tokens }} }} }}",
+        AccountOwner::from(h.bob.public_key())
+    );
+    let portfolio = h.alice.graphql_query(h.app_id, query).await.response["portfolio"].clone();
+    // 10 YES at 62.5 / 102.5, plus a payout of 10 * 102.5 / 60
+    let claimable = attos(17_083_333_333_333_333_333);
+    let total = attos(6_097_560_975_609_756_097).saturating_add(claimable);
+    assert_eq!(decode_amount(&portfolio["totalValue"]), total);
+    assert_eq!(decode_amount(&portfolio["claimable"]), claimable);
+    assert_eq!(portfolio["positionCount"], 2);
+    assert_eq!(decode_amount(&portfolio["costBasis"]), tokens("25"));
+    let loss = tokens("25").saturating_sub(total);
+    assert_eq!(portfolio["unrealizedPnl"]["attos"], format!("-{}", u128::from(loss)));
+    assert_eq!(portfolio["unrealizedPnl"]["tokens"], format!("-{loss}"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ending_soon_lists_open_markets_inside_the_window() {
+    let h = Harness::new().await;
+    for duration in [600, 60, 7200, 300, 30] {
+        h.create_market(&h.alice, "Ending?", tokens("10"), duration).await;
+    }
+    // Market 4 has ended but is unresolved; market 2 is outside the window
+    h.advance(31);
+    // The service reads its clock from the chain's latest block
+    h.create_market(&h.alice, "Later?", tokens("10"), 60).await;
+
+    let query = "query { endingSoon(withinSecs: 1200, limit: 3) { id } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["endingSoon"], serde_json::json!([{ "id": 1 }, { "id": 5 }, { "id": 3 }]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn health_reports_the_build_counters_and_configuration() {
+    const HEALTH: &str = "query { health { version schemaVersion stateLoadedOk stateError marketCount nextMarketId \
+        parametersHash configHash } }";
+    let h = Harness::new().await;
+    h.create_market(&h.alice, "First?", tokens("10"), 60).await;
+    h.create_market(&h.alice, "Second?", tokens("10"), 60).await;
+    let health = h.alice.graphql_query(h.app_id, HEALTH).await.response["health"].clone();
+    assert_eq!(health["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(health["schemaVersion"], 1);
+    assert_eq!(health["stateLoadedOk"], true);
+    assert_eq!(health["stateError"], Value::Null);
+    assert_eq!(health["marketCount"], 2);
+    assert_eq!(health["nextMarketId"], 2);
+    assert_eq!(health["parametersHash"], CryptoHash::new(&MarketParameters::default()).to_string());
+
+    // A deployment configured differently shows a different config hash
+    let other = Harness::with_config(|config| config.fee_bps = 50).await;
+    let other_health = other.alice.graphql_query(other.app_id, HEALTH).await.response["health"].clone();
+    assert_eq!(other_health["parametersHash"], health["parametersHash"]);
+    assert_ne!(other_health["configHash"], health["configHash"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stats_count_markets_and_window_volume_to_24_hours() {
+    let h = Harness::new().await;
+    let short = h.create_market(&h.alice, "Short?", tokens("100"), 3600).await;
+    let early = h.create_market(&h.alice, "Early trade?", tokens("100"), 200_000).await;
+    let late = h.create_market(&h.alice, "Late trade?", tokens("100"), 200_000).await;
+    assert_eq!(h.buy(&h.bob, early, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    h.advance(25 * 60 * 60);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: short, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    assert_eq!(h.buy(&h.bob, late, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let query = format!(
+        "query {{ stats {{ totalVolume {{ {AMOUNT} }} marketCount activeCount resolvedCount \
+            totalOpenInterest {{ {AMOUNT} }} uniqueTraders volume24h {{ {AMOUNT} }} }} }}"
+    );
+    let stats = h.alice.graphql_query(h.app_id, query).await.response["stats"].clone();
+    assert_eq!(decode_amount(&stats["totalVolume"]), tokens("25"));
+    assert_eq!(decode_amount(&stats["volume24h"]), tokens("12.5"));
+    assert_eq!(stats["marketCount"], 3);
+    assert_eq!(stats["activeCount"], 2);
+    assert_eq!(stats["resolvedCount"], 1);
+    // The resolved market's escrow stays open interest until it is claimed
+    assert_eq!(decode_amount(&stats["totalOpenInterest"]), tokens("325"));
+    assert_eq!(stats["uniqueTraders"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn aggregate_registers_match_a_recount() {
+    let h = Harness::with_config(|config| {
+        config.fee_bps = 100;
+        config.resolution_grace_period_secs = 600;
+        config.admin_resolution_delay_secs = 600;
+        config.claim_window_secs = 600;
+    })
+    .await;
+    let harness = &h;
+    // Each register moves by exactly the amounts that change the fields it
+    // sums, so no rounding dust is allowed between them
+    let reconcile = || async move {
+        let query = format!(
+            "query {{ stats {{ totalOpenInterest {{ {AMOUNT} }} totalActiveLiquidity {{ {AMOUNT} }} \
+                    contractBalance {{ {AMOUNT} }} }} \
+                markets(limit: 100) {{ markets {{ resolved escrow {{ {AMOUNT} }} \
+                    yesPool {{ {AMOUNT} }} noPool {{ {AMOUNT} }} \
+                    feesCollected {{ {AMOUNT} }} feesDistributed {{ {AMOUNT} }} }} }} }}"
+        );
+        let response = harness.alice.graphql_query(harness.app_id, query).await.response;
+        let (mut open_interest, mut liquidity, mut balance) = (Amount::ZERO, Amount::ZERO, Amount::ZERO);
+        for market in response["markets"]["markets"].as_array().unwrap() {
+            open_interest = open_interest.saturating_add(decode_amount(&market["escrow"]));
+            let unpaid_fees = decode_amount(&market["feesCollected"]).saturating_sub(decode_amount(&market["feesDistributed"]));
+            balance = balance.saturating_add(decode_amount(&market["escrow"])).saturating_add(unpaid_fees);
+            if market["resolved"] == false {
+                let pools = decode_amount(&market["yesPool"]).saturating_add(decode_amount(&market["noPool"]));
+                liquidity = liquidity.saturating_add(pools);
+            }
+        }
+        let stats = &response["stats"];
+        assert_eq!(decode_amount(&stats["totalOpenInterest"]), open_interest);
+        assert_eq!(decode_amount(&stats["totalActiveLiquidity"]), liquidity);
+        assert_eq!(decode_amount(&stats["contractBalance"]), balance);
+        (open_interest, liquidity)
+    };
+
+    let settled = h.create_market(&h.alice, "Settled?", tokens("100"), 60).await;
+    let expired = h.create_market(&h.alice, "Expired?", tokens("50"), 60).await;
+    let live = h.create_market(&h.alice, "Live?", tokens("80"), 3600).await;
+    assert_eq!(reconcile().await, (tokens("230"), tokens("230")));
+
+    for (market_id, is_yes, shares) in [(settled, true, "10"), (settled, false, "3"), (expired, true, "7"), (live, false, "12")] {
+        let response = h.buy(&h.bob, market_id, is_yes, tokens(shares), tokens("100")).await;
+        assert!(response.starts_with("SharesPurchased:"), "{response}");
+    }
+    let sell = Operation::SellShares { market_id: settled, is_yes: true, shares: tokens("4"), min_proceeds: Amount::ZERO, deadline: None };
+    assert!(h.trade(&h.bob, sell).await.starts_with("SharesSold:"));
+    let straddle = Operation::BuyStraddle {
+        market_id: live,
+        yes_shares: tokens("5"),
+        no_shares: tokens("2"),
+        max_total_cost: tokens("100"),
+    };
+    assert!(h.execute(&h.alice, straddle).await.starts_with("StraddlePurchased:"));
+    reconcile().await;
+
+    // Resolution takes the pools out of the active liquidity only
+    h.advance(61);
+    let resolve = Operation::ResolveMarket { market_id: settled, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    let (open_interest, _) = reconcile().await;
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id: settled }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+    let (after_claim, _) = reconcile().await;
+    assert!(after_claim < open_interest);
+
+    // The sweep closes, then voids; refunds and recovery pay out the rest
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    reconcile().await;
+    h.advance(600);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:1:0");
+    reconcile().await;
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id: expired }).await;
+    assert!(response.starts_with("RefundClaimed:"), "{response}");
+    reconcile().await;
+    h.advance(601);
+    for market_id in [settled, expired] {
+        let response = h.execute(&h.alice, Operation::RecoverUnclaimed { market_id }).await;
+        assert!(response.starts_with("UnclaimedRecovered:"), "{response}");
+    }
+    // Only the live market's escrow is left outstanding
+    let (open_interest, _) = reconcile().await;
+    assert_eq!(h.market(live).await["escrow"], open_interest.to_string());
+
+    let response = h.execute(&h.alice, Operation::ClaimLpFees { market_id: live }).await;
+    assert!(response.starts_with("LpFeesClaimed:"), "{response}");
+    reconcile().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_contract_balance_is_only_reconciled_upwards_by_recorded_deposits() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Reconciled?", tokens("100"), 60).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("100")).await.starts_with("SharesPurchased:"));
+    h.advance(61);
+    let resolve = Operation::ResolveMarket { market_id, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+
+    let stats_query = &format!("query {{ stats {{ contractBalance {{ {AMOUNT} }} reconciledDeposits {{ {AMOUNT} }} }} }}");
+    let stats = || async move {
+        let response = h.alice.graphql_query(h.app_id, stats_query.clone()).await.response;
+        (decode_amount(&response["stats"]["contractBalance"]), decode_amount(&response["stats"]["reconciledDeposits"]))
+    };
+    // Trading alone leaves the balance holding exactly the escrow
+    let (held, deposits) = stats().await;
+    assert_eq!(h.market(market_id).await["escrow"], held.to_string());
+    assert_eq!(deposits, Amount::ZERO);
+
+    // Only the admin may reconcile, and only by a deposit
+    let reconcile = |deposit: &str| Operation::ReconcileBalance { deposit: tokens(deposit) };
+    assert_eq!(h.execute(&h.bob, reconcile("5")).await, "ERROR: Not authorized: only the admin can reconcile the balance");
+    assert_eq!(h.execute(&h.alice, reconcile("0")).await, "ERROR: Deposit must be greater than zero");
+    let raised = held.saturating_add(tokens("5"));
+    assert_eq!(h.execute(&h.alice, reconcile("5")).await, format!("BalanceReconciled:5.:{raised}"));
+    assert_eq!(stats().await, (raised, tokens("5")));
+
+    // The deposit stays on top of what the claim pays out
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await;
+    let Ok(OperationResponse::WinningsClaimed { payout, .. }) = response.parse() else { panic!("{response}") };
+    assert_eq!(stats().await, (raised.saturating_sub(payout), tokens("5")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trigger_orders_sell_once_triggered_and_pay_their_keeper() {
+    let h = &Harness::with_config(|config| config.keeper_fee = Some(tokens("0.1"))).await;
+    let market_id = h.create_market(&h.alice, "Stop?", tokens("100"), 3600).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("100")).await.starts_with("SharesPurchased:"));
+
+    let place = |shares: &str, trigger_price_bps: u64, direction: TriggerDirection| Operation::PlaceTriggerOrder {
+        market_id,
+        is_yes: true,
+        shares: tokens(shares),
+        trigger_price_bps,
+        direction,
+        min_proceeds: Amount::ZERO,
+    };
+    let response = h.execute(&h.bob, place("11", 4_000, TriggerDirection::Below)).await;
+    assert!(response.starts_with("ERROR: Insufficient balance"), "{response}");
+    assert_eq!(h.execute(&h.bob, place("10", 4_000, TriggerDirection::Below)).await, "TriggerOrderPlaced:0");
+    assert_eq!(h.execute(&h.bob, place("5", 9_000, TriggerDirection::Above)).await, "TriggerOrderPlaced:1");
+    assert_eq!(h.execute(&h.bob, place("10", 4_500, TriggerDirection::Below)).await, "TriggerOrderPlaced:2");
+
+    let execute = || Operation::ExecuteTriggeredOrders { market_id, max_orders: 10 };
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:0:0.");
+
+    // Pushing YES under 40% fires both stop-losses; the first sells all
+    // ten shares, so the second finds none left and is cancelled
+    assert!(h.buy(&h.alice, market_id, false, tokens("30"), tokens("1000")).await.starts_with("SharesPurchased:"));
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:1:0.1");
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:0:0.");
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let open_orders = || async move {
+        let query = format!(
+            "query {{ openTriggerOrders(owner: \"{bob}\") {{ id direction keeperFee {{ {AMOUNT} }} }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["openTriggerOrders"].clone()
+    };
+    let open = open_orders().await;
+    assert_eq!(open.as_array().unwrap().len(), 1);
+    assert_eq!(open[0]["id"], 1);
+    assert_eq!(open[0]["direction"], "ABOVE");
+    assert_eq!(decode_amount(&open[0]["keeperFee"]), tokens("0.1"));
+
+    // Only the owner may cancel, and only while the order is pending
+    let cancel = |order_id: u64| Operation::CancelTriggerOrder { market_id, order_id };
+    assert_eq!(h.execute(&h.alice, cancel(1)).await, "ERROR: Not authorized: not the order owner");
+    assert_eq!(h.execute(&h.bob, cancel(1)).await, "TriggerOrderCancelled:0.1");
+    assert_eq!(h.execute(&h.bob, cancel(0)).await, "ERROR: Trigger order 0 is not pending (status: Executed)");
+    assert_eq!(open_orders().await, serde_json::json!([]));
+
+    // Resolution cancels whatever is still pending
+    assert!(h.buy(&h.bob, market_id, false, tokens("1"), tokens("1000")).await.starts_with("SharesPurchased:"));
+    let take_profit = Operation::PlaceTriggerOrder {
+        market_id,
+        is_yes: false,
+        shares: tokens("1"),
+        trigger_price_bps: 9_999,
+        direction: TriggerDirection::Above,
+        min_proceeds: Amount::ZERO,
+    };
+    assert_eq!(h.execute(&h.bob, take_profit).await, "TriggerOrderPlaced:3");
+    assert_eq!(open_orders().await.as_array().unwrap().len(), 1);
+    h.advance(3601);
+    let resolve = Operation::ResolveMarket { market_id, outcome: false, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    assert_eq!(open_orders().await, serde_json::json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn buy_trigger_orders_escrow_their_limit_and_buy_once_triggered() {
+    let h = &Harness::with_config(|config| config.keeper_fee = Some(tokens("0.1"))).await;
+    let market_id = h.create_market(&h.alice, "Dip?", tokens("100"), 3600).await;
+
+    let place = |is_yes: bool, shares: &str, trigger_price_bps: u64, direction: TriggerDirection, max_price_bps: u64| {
+        Operation::PlaceBuyTriggerOrder { market_id, is_yes, shares: tokens(shares), trigger_price_bps, direction, max_price_bps }
+    };
+    let response = h.execute(&h.bob, place(true, "10", 3_000, TriggerDirection::Below, 0)).await;
+    assert_eq!(response, "ERROR: Max price must be between 1 and 10000 bps");
+    // Escrows 8 tokens, then 0.1, far too little for ten NO shares
+    assert_eq!(h.execute(&h.bob, place(true, "10", 3_000, TriggerDirection::Below, 8_000)).await, "TriggerOrderPlaced:0");
+    assert_eq!(h.execute(&h.bob, place(false, "10", 3_000, TriggerDirection::Below, 100)).await, "TriggerOrderPlaced:1");
+
+    let execute = || Operation::ExecuteTriggeredOrders { market_id, max_orders: 10 };
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:0:0.");
+
+    // Pushing YES under 30% fires both; the NO purchase would cost more
+    // than its escrow, so it waits
+    assert!(h.buy(&h.alice, market_id, false, tokens("30"), tokens("1000")).await.starts_with("SharesPurchased:"));
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:1:0.1");
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:0:0.");
+    assert_eq!(h.market(market_id).await["totalYesShares"], "60.");
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ position(owner: \"{bob}\", marketId: {market_id}) {{ yesShares {{ tokens }} }} \
+            openTriggerOrders(owner: \"{bob}\") {{ id side maxPriceBps escrow {{ {AMOUNT} }} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(response["position"]["yesShares"]["tokens"], "10");
+    let open = response["openTriggerOrders"].as_array().unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!((&open[0]["id"], &open[0]["side"], &open[0]["maxPriceBps"]), (&Value::from(1), &Value::from("BUY"), &Value::from(100)));
+    assert_eq!(decode_amount(&open[0]["escrow"]), tokens("0.1"));
+
+    // Cancelling refunds the escrow with the keeper fee
+    let cancel = |order_id: u64| Operation::CancelTriggerOrder { market_id, order_id };
+    assert_eq!(h.execute(&h.bob, cancel(1)).await, "TriggerOrderCancelled:0.2");
+
+    // A purchase still pending when the market ends lapses
+    assert_eq!(h.execute(&h.bob, place(true, "1", 9_000, TriggerDirection::Above, 10_000)).await, "TriggerOrderPlaced:2");
+    h.advance(3601);
+    let response = h.execute(&h.bob, place(true, "1", 9_000, TriggerDirection::Above, 10_000)).await;
+    assert!(response.starts_with(&format!("ERROR: Market {market_id} has ended")), "{response}");
+    assert_eq!(h.execute(&h.alice, execute()).await, "TriggeredOrdersExecuted:0:0.");
+    assert_eq!(h.execute(&h.bob, cancel(2)).await, "ERROR: Trigger order 2 is not pending (status: Cancelled)");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_market_holds_no_more_pending_trigger_orders_than_one_keeper_call_visits() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Crowded?", tokens("100"), 3600).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("1"), tokens("100")).await.starts_with("SharesPurchased:"));
+
+    let place = || Operation::PlaceTriggerOrder {
+        market_id,
+        is_yes: true,
+        shares: tokens("1"),
+        trigger_price_bps: 9_000,
+        direction: TriggerDirection::Above,
+        min_proceeds: Amount::ZERO,
+    };
+    for order_id in 0..MAX_PENDING_TRIGGER_ORDERS {
+        assert_eq!(h.execute(&h.bob, place()).await, format!("TriggerOrderPlaced:{order_id}"));
+    }
+    let response = h.execute(&h.bob, place()).await;
+    assert_eq!(response, format!("ERROR: Market {market_id} already has {MAX_PENDING_TRIGGER_ORDERS} pending trigger orders"));
+
+    // A slot freed by a cancellation takes the next order
+    let cancel = Operation::CancelTriggerOrder { market_id, order_id: 0 };
+    assert!(h.execute(&h.bob, cancel).await.starts_with("TriggerOrderCancelled:"));
+    assert_eq!(h.execute(&h.bob, place()).await, format!("TriggerOrderPlaced:{MAX_PENDING_TRIGGER_ORDERS}"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_queries_see_each_new_state() {
+    let h = Harness::new().await;
+    let query = "query { marketCount markets { totalCount } stats { activeCount } }";
+    for expected in 0..3u64 {
+        for _ in 0..2 {
+            let response = h.alice.graphql_query(h.app_id, query).await.response;
+            assert_eq!(response["marketCount"], expected);
+            assert_eq!(response["markets"]["totalCount"], expected);
+            assert_eq!(response["stats"]["activeCount"], expected);
+        }
+        h.create_market(&h.alice, "Another?", tokens("10"), 3600).await;
+    }
+
+    // Mutations go through the same schema
+    let mutation = "mutation { buyShares(marketId: 0, isYes: true, shares: \"1\", maxCost: \"2\") }";
+    h.alice.graphql_mutation(h.app_id, mutation).await;
+    assert_eq!(h.market(0).await["totalYesShares"], "6.");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn checked_mutations_reject_bad_arguments_before_scheduling() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600)).micros();
+    let create = |end_time: &str, liquidity: &str| {
+        format!(
+            "mutation {{ createMarket(question: \"Via GraphQL?\", categories: [\"api\"], \
+                endTime: \"{end_time}\", initialLiquidity: \"{liquidity}\") }}"
+        )
+    };
+    let harness = &h;
+    let error = |mutation: String| async move {
+        let Err(TryGraphQLQueryError::Service(errors)) = harness.alice.try_graphql_query(harness.app_id, mutation).await
+        else {
+            panic!("Expected a GraphQL error");
+        };
+        errors[0].message.clone()
+    };
+
+    let message = error(create("tomorrow", "100")).await;
+    assert!(message.starts_with("Invalid timestamp for argument \"endTime\""), "{message}");
+    assert_eq!(error(create("0", "100")).await, "endTime must be in the future");
+    assert!(error(create(&end_time.to_string(), "lots")).await.starts_with("Invalid amount for argument \"initialLiquidity\""));
+    let buy = "mutation { buyShares(marketId: 0, isYes: true, shares: \"1\", maxCost: \"2\") }";
+    assert_eq!(error(buy.to_string()).await, "Market 0 not found");
+    let query = "query { marketCount }";
+    assert_eq!(h.alice.graphql_query(h.app_id, query).await.response["marketCount"], 0);
+
+    h.alice.graphql_mutation(h.app_id, create(&end_time.to_string(), "100")).await;
+    let market = h.alice.graphql_query(h.app_id, "query { market(id: 0) { question endTime escrow { attos tokens } } }").await.response["market"].clone();
+    assert_eq!(market["question"], "Via GraphQL?");
+    assert_eq!(market["endTime"], end_time.to_string());
+    assert_eq!(decode_amount(&market["escrow"]), tokens("100"));
+
+    assert_eq!(error("mutation { claimWinnings(marketId: 0) }".to_string()).await, "Market 0 is not yet resolved");
+    h.alice.graphql_mutation(h.app_id, buy).await;
+    assert_eq!(h.market(0).await["totalYesShares"], "51.");
+    // Operations without a checked mutation keep the generated one
+    h.alice.graphql_mutation(h.app_id, "mutation { setGlobalPause(paused: true) }").await;
+    assert!(h.paused().await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn amount_and_timestamp_scalars_round_trip() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600)).micros();
+    // Numbers and strings are both accepted on input
+    let mutation = format!(
+        "mutation {{ createMarket(question: \"Scalars?\", categories: [\"api\"], endTime: {end_time}, \
+            initialLiquidity: 12.5, maxPositionPerAccount: \"0.000000000000000001\") }}"
+    );
+    h.alice.graphql_mutation(h.app_id, mutation).await;
+    let fields = "endTimeMicros yesPool { tokens } maxPositionPerAccount { tokens }";
+    let query = format!("query {{ market(id: 0) {{ {fields} }} }}");
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(market["endTimeMicros"], end_time);
+    assert_eq!(market["yesPool"]["tokens"], "6.25");
+    assert_eq!(market["maxPositionPerAccount"]["tokens"], "0.000000000000000001");
+
+    // Values read back out can be passed straight in again
+    let mutation = format!(
+        "mutation {{ createMarket(question: \"Again?\", categories: [\"api\"], endTime: {}, \
+            initialLiquidity: {}, maxPositionPerAccount: {}) }}",
+        market["endTimeMicros"], market["yesPool"]["tokens"], market["maxPositionPerAccount"]["tokens"],
+    );
+    h.alice.graphql_mutation(h.app_id, mutation).await;
+    let query = format!("query {{ market(id: 1) {{ {fields} }} }}");
+    let copy = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(copy["endTimeMicros"], market["endTimeMicros"]);
+    assert_eq!(copy["yesPool"]["tokens"], "3.125");
+    assert_eq!(copy["maxPositionPerAccount"], market["maxPositionPerAccount"]);
+    let query = format!(
+        "query {{ markets(filter: {{ endsAfter: {}, endsBefore: \"{}\" }}) {{ totalCount }} }}",
+        copy["endTimeMicros"],
+        end_time + 1,
+    );
+    assert_eq!(h.alice.graphql_query(h.app_id, query).await.response["markets"]["totalCount"], 2);
+
+    let harness = &h;
+    let error = |query: String| async move {
+        let Err(TryGraphQLQueryError::Service(errors)) = harness.alice.try_graphql_query(harness.app_id, query).await
+        else {
+            panic!("Expected a GraphQL error");
+        };
+        errors[0].message.clone()
+    };
+    let message = error("query { market(id: 0) { buyQuote(isYes: true, shares: -1) { feeBps } } }".to_string()).await;
+    assert!(message.starts_with("Invalid amount for argument \"shares\""), "{message}");
+    let message = error("query { resolvedMarkets(resolvedAfter: 1.5) { totalCount } }".to_string()).await;
+    assert!(message.starts_with("Invalid timestamp for argument \"resolvedAfter\""), "{message}");
+    assert!(message.contains("not a whole number of microseconds"), "{message}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn timestamps_render_as_rfc3339_and_raw_micros() {
+    let h = Harness::new().await;
+    // Created at the epoch, ending as late as a timestamp can
+    let forever = h.create_market(&h.alice, "Forever?", tokens("10"), u64::MAX).await;
+    // 2024-02-29T23:59:59.5Z, ending one second later on March 1st
+    h.validator.clock().set(Timestamp::from(1_709_251_199_500_000));
+    let leap = h.create_market(&h.alice, "Leap?", tokens("10"), 1).await;
+    h.advance(2);
+    h.create_market(&h.alice, "Later?", tokens("10"), 60).await;
+
+    let harness = &h;
+    let times = |id: u64| async move {
+        let query = format!(
+            "query {{ market(id: {id}) {{ endTime endTimeMicros endTimeRfc3339 \
+                createdAtMicros createdAtRfc3339 isEnded }} }}"
+        );
+        harness.alice.graphql_query(harness.app_id, query).await.response["market"].clone()
+    };
+    let forever = times(forever).await;
+    assert_eq!(forever["endTime"], u64::MAX.to_string());
+    assert_eq!(forever["endTimeMicros"], u64::MAX);
+    assert_eq!(forever["endTimeRfc3339"], "586524-01-19T08:01:49.551615Z");
+    assert_eq!(forever["createdAtMicros"], 0);
+    assert_eq!(forever["createdAtRfc3339"], "1970-01-01T00:00:00.000000Z");
+    assert_eq!(forever["isEnded"], false);
+
+    let leap = times(leap).await;
+    assert_eq!(leap["createdAtRfc3339"], "2024-02-29T23:59:59.500000Z");
+    assert_eq!(leap["endTimeRfc3339"], "2024-03-01T00:00:00.500000Z");
+    assert_eq!(leap["endTimeMicros"], 1_709_251_200_500_000u64);
+    assert_eq!(leap["isEnded"], true);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn claim_previews_match_what_claims_pay() {
+    let h = Harness::new().await;
+    let mut carol = h.alice.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+    let market_id = h.create_market(&h.alice, "Preview?", tokens("100"), 3600).await;
+    let refunded = h.create_market(&h.alice, "Refund preview?", tokens("100"), 3600).await;
+    for (trader, is_yes, shares) in [(&h.bob, true, "10"), (&carol, true, "7"), (&h.alice, false, "5")] {
+        assert!(h.buy(trader, market_id, is_yes, tokens(shares), tokens("20")).await.starts_with("SharesPurchased:"));
+    }
+    assert!(h.buy(&carol, refunded, false, tokens("3"), tokens("20")).await.starts_with("SharesPurchased:"));
+
+    let harness = &h;
+    let preview = |owner: &ActiveChain, market_id: u64| {
+        let owner = AccountOwner::from(owner.public_key());
+        async move {
+            let query = format!(
+                "query {{ claimableWinnings(owner: \"{owner}\", marketId: {market_id}) {{ amount {{ {AMOUNT} }} reason }} }}"
+            );
+            let preview = harness.alice.graphql_query(harness.app_id, query).await.response["claimableWinnings"].clone();
+            match preview["reason"].as_str() {
+                Some(reason) => Err(reason.to_string()),
+                None => Ok(decode_amount(&preview["amount"])),
+            }
+        }
+    };
+    assert_eq!(preview(&h.bob, market_id).await, Err("not resolved".to_string()));
+
+    h.advance(3601);
+    for id in [market_id, refunded] {
+        assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    }
+    assert_eq!(preview(&h.alice, market_id).await, Err("no winning shares".to_string()));
+    let mut dave = h.alice.clone();
+    dave.set_key_pair(AccountSecretKey::generate());
+    assert_eq!(preview(&dave, market_id).await, Err("no position".to_string()));
+
+    let carol_payout = preview(&carol, market_id).await.unwrap();
+    let carol_refund = preview(&carol, refunded).await.unwrap();
+    let query = format!("query {{ claimableTotal(owner: \"{}\") {{ {AMOUNT} }} }}", AccountOwner::from(carol.public_key()));
+    let total = h.alice.graphql_query(h.app_id, query).await.response["claimableTotal"].clone();
+    assert_eq!(decode_amount(&total), carol_payout.saturating_add(carol_refund));
+
+    let bob_payout = preview(&h.bob, market_id).await.unwrap();
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await.starts_with(&format!("WinningsClaimed:{bob_payout}:")));
+    assert_eq!(preview(&h.bob, market_id).await, Err("already claimed".to_string()));
+    assert_eq!(preview(&carol, market_id).await, Ok(carol_payout));
+    assert!(h.execute(&carol, Operation::ClaimWinnings { market_id }).await.starts_with(&format!("WinningsClaimed:{carol_payout}:")));
+    let refund = h.execute(&carol, Operation::ClaimWinnings { market_id: refunded }).await;
+    assert_eq!(refund, format!("RefundClaimed:{carol_refund}"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn categories_count_markets_and_order_by_active_count() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    for categories in [vec!["Politics"], vec!["crypto", "Sports"], vec!["CRYPTO"]] {
+        let operation = Operation::CreateMarket {
+            question: "Tagged?".to_string(),
+            categories: categories.into_iter().map(String::from).collect(),
+            end_time,
+            initial_liquidity: tokens("10"),
+            early_resolution_allowed: false,
+            max_position_per_account: None,
+            max_position: None,
+            creator_trading_disabled: false,
+            fee_ramp: None,
+            description: None,
+            resolution_source: None,
+            parent: None,
+            resolvers: vec![],
+            threshold: 0,
+            max_probability_move_bps: None,
+            max_trade_shares: None,
+        };
+        assert!(h.execute(&h.alice, operation).await.starts_with("MarketCreated:"));
+    }
+    h.advance(3600);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: 0, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+
+    let query = "query { categories { name marketCount activeCount } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["categories"],
+        serde_json::json!([
+            { "name": "crypto", "marketCount": 2, "activeCount": 2 },
+            { "name": "sports", "marketCount": 1, "activeCount": 1 },
+            { "name": "politics", "marketCount": 1, "activeCount": 0 },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolved_markets_filter_by_outcome_and_resolution_time() {
+    let h = Harness::with_config(|config| {
+        config.resolution_grace_period_secs = 600;
+        config.admin_resolution_delay_secs = 600;
+    })
+    .await;
+    for duration in [60, 60, 60, 60, 3600] {
+        h.create_market(&h.alice, "Settled?", tokens("10"), duration).await;
+    }
+    h.advance(60);
+    let resolve = |market_id, outcome| Operation::ResolveMarket { market_id, outcome, evidence: None };
+    assert!(h.execute(&h.alice, resolve(0, true)).await.starts_with("MarketResolved:"));
+    let cutoff = h.now().saturating_add(TimeDelta::from_secs(1));
+    h.advance(300);
+    assert!(h.execute(&h.alice, resolve(1, false)).await.starts_with("MarketResolved:"));
+    assert!(h.execute(&h.alice, resolve(2, true)).await.starts_with("MarketResolved:"));
+    // Market 3 passes its grace period unresolved and is voided; 4 stays open
+    h.advance(300);
+    assert_eq!(h.execute(&h.alice, Operation::FinalizeExpired { limit: 5 }).await, "ExpiredFinalized:1:0");
+
+    let harness = &h;
+    let ids = |arguments: String| async move {
+        let query = format!("query {{ resolvedMarkets({arguments}) {{ totalCount markets {{ id }} }} }}");
+        let page = harness.alice.graphql_query(harness.app_id, query).await.response["resolvedMarkets"].clone();
+        let ids: Vec<u64> = page["markets"].as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect();
+        (page["totalCount"].as_u64().unwrap(), ids)
+    };
+    assert_eq!(ids("limit: 2, offset: 1".into()).await, (4, vec![1, 2]));
+    assert_eq!(ids("outcome: YES".into()).await, (2, vec![0, 2]));
+    assert_eq!(ids("outcome: VOID".into()).await, (1, vec![3]));
+    let cutoff = cutoff.micros();
+    assert_eq!(ids(format!("resolvedAfter: \"{cutoff}\"")).await, (3, vec![1, 2, 3]));
+    assert_eq!(ids(format!("outcome: YES, resolvedBefore: \"{cutoff}\"")).await, (1, vec![0]));
+    assert_eq!(ids(format!("outcome: NO, resolvedBefore: \"{cutoff}\"")).await, (0, vec![]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn market_counts_follow_each_status_transition() {
+    let h = Harness::with_config(|config| {
+        config.resolution_grace_period_secs = 600;
+        config.admin_resolution_delay_secs = 600;
+    })
+    .await;
+    let harness = &h;
+    // The counters must agree with a scan of every market
+    let counts = || async move {
+        let query = "query { marketCounts { open closed resolved voided } \
+            markets(limit: 100) { markets { resolved voided closed } } }";
+        let response = harness.alice.graphql_query(harness.app_id, query).await.response;
+        let mut scanned = [0u64; 4];
+        for market in response["markets"]["markets"].as_array().unwrap() {
+            let status = match (market["resolved"] == true, market["voided"] == true, market["closed"] == true) {
+                (false, _, false) => 0,
+                (false, _, true) => 1,
+                (true, false, _) => 2,
+                (true, true, _) => 3,
+            };
+            scanned[status] += 1;
+        }
+        let counts = &response["marketCounts"];
+        let counted = ["open", "closed", "resolved", "voided"].map(|status| counts[status].as_u64().unwrap());
+        assert_eq!(counted, scanned);
+        counted
+    };
+
+    for duration in [60, 60, 60, 600, 3600] {
+        h.create_market(&h.alice, "Counted?", tokens("10"), duration).await;
+    }
+    assert_eq!(counts().await, [5, 0, 0, 0]);
+
+    // Ended markets stay open until a sweep closes them
+    h.advance(61);
+    assert_eq!(counts().await, [5, 0, 0, 0]);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    assert_eq!(counts().await, [2, 3, 0, 0]);
+    // Sweeping again closes nothing twice
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    assert_eq!(counts().await, [2, 3, 0, 0]);
+
+    let resolve = Operation::ResolveMarket { market_id: 0, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    assert_eq!(counts().await, [2, 2, 1, 0]);
+
+    // Markets 1 and 2 pass their grace period and are voided; 3 closes
+    h.advance(600);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:2:0");
+    assert_eq!(counts().await, [1, 1, 1, 2]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_stop_trading_at_their_end_time_and_anyone_may_close_them() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Closing?", tokens("100"), 60).await;
+    let close = || Operation::CloseMarket { market_id };
+    let status = || async move {
+        let query = format!("query {{ market(id: {market_id}) {{ status closed isTradeable }} marketCounts {{ open closed }} }}");
+        h.alice.graphql_query(h.app_id, query.as_str()).await.response
+    };
+
+    h.advance(59);
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let end_micros = h.now().micros() + 1_000_000;
+    assert_eq!(
+        h.execute(&h.bob, close()).await,
+        format!("ERROR: Market {market_id} cannot be closed before its end time {end_micros}")
+    );
+
+    // At the end time itself the service shows it closed and the contract
+    // refuses to trade, before anything is stored
+    h.advance(1);
+    let response = status().await;
+    assert_eq!(response["market"]["status"], "CLOSED");
+    assert_eq!(response["market"]["closed"], false);
+    assert_eq!(response["market"]["isTradeable"], false);
+    assert_eq!(response["marketCounts"]["open"], 1);
+    let response = h.execute(&h.bob, Operation::BuyShares {
+        market_id,
+        is_yes: true,
+        shares: tokens("1"),
+        max_cost: tokens("10"),
+        deadline: None,
+        max_price_impact_bps: None,
+    }).await;
+    assert_eq!(response, format!("ERROR: Market {market_id} has ended (now={end_micros}, end={end_micros})"));
+    // Exits close with entries, so losing shares cannot be sold back to the
+    // AMM once the outcome may be known
+    let sell = || Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    assert_eq!(h.execute(&h.bob, sell()).await, format!("ERROR: Market {market_id} has ended (now={end_micros}, end={end_micros})"));
+    let query = format!("query {{ proceedsFromSell(marketId: {market_id}, isYes: true, shares: \"5\") {{ fee {{ attos }} }} }}");
+    assert_eq!(h.alice.graphql_query(h.app_id, query).await.response["proceedsFromSell"], Value::Null);
+
+    // Anyone may record it closed, once
+    assert_eq!(h.execute(&h.bob, close()).await, format!("MarketClosed:{market_id}"));
+    let close_position = Operation::ClosePosition { market_id, is_yes: true, min_proceeds: Amount::ZERO };
+    assert!(h.execute(&h.bob, close_position).await.starts_with(&format!("ERROR: Market {market_id} has ended")));
+    let response = status().await;
+    assert_eq!(response["market"]["closed"], true);
+    assert_eq!(response["marketCounts"]["open"], 0);
+    assert_eq!(response["marketCounts"]["closed"], 1);
+    assert_eq!(h.execute(&h.bob, close()).await, format!("ERROR: Market {market_id} is already closed"));
+    // Nor does a later sweep close it twice
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    assert_eq!(status().await["marketCounts"]["closed"], 1);
+
+    let resolve = Operation::ResolveMarket { market_id, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    assert_eq!(status().await["market"]["status"], "RESOLVED");
+    assert_eq!(h.execute(&h.bob, close()).await, format!("ERROR: Market {market_id} is already resolved"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn summaries_track_their_markets() {
+    let h = Harness::with_config(|config| {
+        config.resolution_grace_period_secs = 600;
+        config.admin_resolution_delay_secs = 600;
+        config.claim_window_secs = 60;
+    })
+    .await;
+    let harness = &h;
+    // Every summary must show what its full record does, and only stored
+    // markets have one
+    let phases = || async move {
+        let query = "query { \
+            marketSummaries(limit: 100) { totalCount summaries { \
+                id question yesPriceBps noPriceBps volume { tokens } endTimeMicros phase outcome } } \
+            markets(limit: 100) { totalCount \
+                summaries { id question yesPriceBps noPriceBps volume { tokens } endTimeMicros phase outcome } \
+                markets { id question yesPriceBps noPriceBps volume { tokens } endTimeMicros status voided outcome } } }";
+        let response = harness.alice.graphql_query(harness.app_id, query).await.response;
+        let summaries = response["marketSummaries"]["summaries"].as_array().unwrap().clone();
+        let markets = response["markets"]["markets"].as_array().unwrap().clone();
+        assert_eq!(response["marketSummaries"]["totalCount"], response["markets"]["totalCount"]);
+        // A page of markets lists the same rows without reading full records
+        assert_eq!(response["markets"]["summaries"], response["marketSummaries"]["summaries"]);
+        assert_eq!(summaries.len(), markets.len());
+        for (summary, market) in summaries.iter().zip(&markets) {
+            for field in ["id", "question", "yesPriceBps", "noPriceBps", "volume", "endTimeMicros", "outcome"] {
+                assert_eq!(summary[field], market[field], "{field} of market {}", market["id"]);
+            }
+            let phase = match (market["status"].as_str().unwrap(), market["voided"] == true) {
+                ("RESOLVED", true) => "VOIDED",
+                ("RESOLVED", false) => "RESOLVED",
+                ("CLOSED", _) => "CLOSED",
+                _ => "OPEN",
+            };
+            assert_eq!(summary["phase"], phase, "phase of market {}", market["id"]);
+        }
+        summaries.iter().map(|s| (s["id"].as_u64().unwrap(), s["phase"].as_str().unwrap().to_string())).collect::<Vec<_>>()
+    };
+    let phase = |id: u64, phase: &str| (id, phase.to_string());
+
+    for duration in [60, 60, 3600] {
+        h.create_market(&h.alice, "Summarized?", tokens("100"), duration).await;
+    }
+    assert_eq!(phases().await, vec![phase(0, "OPEN"), phase(1, "OPEN"), phase(2, "OPEN")]);
+
+    // Trades move prices and volume
+    let response = h.buy(&h.bob, 0, true, tokens("10"), tokens("100")).await;
+    assert!(!response.starts_with("ERROR"), "{response}");
+    let sell = Operation::SellShares { market_id: 0, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    let response = h.trade(&h.bob, sell).await;
+    assert!(!response.starts_with("ERROR"), "{response}");
+    let response = h.buy(&h.bob, 2, false, tokens("5"), tokens("100")).await;
+    assert!(!response.starts_with("ERROR"), "{response}");
+    phases().await;
+
+    // Ended markets show as closed before anything records it
+    h.advance(61);
+    assert_eq!(phases().await, vec![phase(0, "CLOSED"), phase(1, "CLOSED"), phase(2, "OPEN")]);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    assert_eq!(phases().await, vec![phase(0, "CLOSED"), phase(1, "CLOSED"), phase(2, "OPEN")]);
+
+    let resolve = Operation::ResolveMarket { market_id: 0, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    let overrule = Operation::OverrideResolution { market_id: 0, outcome: false, evidence: None };
+    assert_eq!(h.execute(&h.alice, overrule).await, "ResolutionOverridden");
+    assert_eq!(phases().await, vec![phase(0, "RESOLVED"), phase(1, "CLOSED"), phase(2, "OPEN")]);
+
+    h.advance(600);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:1:0");
+    assert_eq!(phases().await, vec![phase(0, "RESOLVED"), phase(1, "VOIDED"), phase(2, "OPEN")]);
+
+    // Archiving drops the summary with the market
+    let response = h.execute(&h.alice, Operation::RecoverUnclaimed { market_id: 0 }).await;
+    assert!(response.starts_with("UnclaimedRecovered:"), "{response}");
+    assert_eq!(h.execute(&h.alice, Operation::ArchiveMarket { market_id: 0 }).await, "MarketArchived:0");
+    assert_eq!(phases().await, vec![phase(1, "VOIDED"), phase(2, "OPEN")]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bad_lookups_report_coded_errors() {
+    let h = Harness::new().await;
+    h.create_market(&h.alice, "Exists?", tokens("10"), 3600).await;
+    let harness = &h;
+    let error = |query: &'static str| async move {
+        let Err(TryGraphQLQueryError::Service(errors)) = harness.alice.try_graphql_query(harness.app_id, query).await else {
+            panic!("Expected a GraphQL error for {query}");
+        };
+        let error = serde_json::to_value(&errors[0]).unwrap();
+        (error["message"].as_str().unwrap().to_string(), error["extensions"]["code"].clone())
+    };
+
+    assert_eq!(h.market(0).await["resolved"], false);
+    assert_eq!(error("query { market(id: 7) { id } }").await, ("Market 7 not found".to_string(), "NOT_FOUND".into()));
+    assert_eq!(error("query { combo(id: 0) { id } }").await, ("Combo 0 not found".to_string(), "NOT_FOUND".into()));
+    let (message, code) = error("query { openOrders(owner: \"0xnothex\") { id } }").await;
+    assert!(message.starts_with("Invalid account format for argument \"owner\": \"0xnothex\""), "{message}");
+    assert_eq!(code, "INVALID_ACCOUNT");
+    let (message, code) = error("query { lpPosition(owner: \"\", marketId: 0) { marketId } }").await;
+    assert!(message.starts_with("Invalid account format for argument \"owner\": \"\""), "{message}");
+    assert_eq!(code, "INVALID_ACCOUNT");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tradeable_markets_are_unresolved_unpaused_and_not_ended() {
+    let h = Harness::new().await;
+    h.create_market(&h.alice, "Ended?", tokens("10"), 60).await;
+    let resolved = h.create_market(&h.alice, "Resolved?", tokens("10"), 60).await;
+    let open = h.create_market(&h.alice, "Open?", tokens("10"), 3600).await;
+    h.advance(61);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: resolved, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+
+    let harness = &h;
+    let listing = || async move {
+        let query = "query { markets { markets { isTradeable secondsRemaining } } activeMarkets { id } }";
+        harness.alice.graphql_query(harness.app_id, query).await.response
+    };
+    let response = listing().await;
+    assert_eq!(
+        response["markets"]["markets"],
+        serde_json::json!([
+            { "isTradeable": false, "secondsRemaining": null },
+            { "isTradeable": false, "secondsRemaining": null },
+            { "isTradeable": true, "secondsRemaining": 3539 },
+        ])
+    );
+    // Ended but unresolved markets no longer count as active
+    assert_eq!(response["activeMarkets"], serde_json::json!([{ "id": open }]));
+
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: true }).await, "GlobalPauseSet:true");
+    let response = listing().await;
+    assert_eq!(response["markets"]["markets"][2], serde_json::json!({ "isTradeable": false, "secondsRemaining": 3539 }));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn price_impact_predicts_the_amm_price_move() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Deep?", tokens("100"), 3600).await;
+    let harness = &h;
+    let depth = |id: u64, shares: &'static str| async move {
+        let query = format!(
+            "query {{ market(id: {id}) {{ yesPrice liquidity {{ {AMOUNT} }} priceImpact(shares: \"{shares}\") {{ yes no }} }} }}"
+        );
+        harness.alice.graphql_query(harness.app_id, query).await.response["market"].clone()
+    };
+
+    let before = depth(market_id, "10").await;
+    assert_eq!(decode_amount(&before["liquidity"]), tokens("50"));
+    let impact = before["priceImpact"]["yes"].as_f64().unwrap();
+    assert_eq!(before["priceImpact"]["no"].as_f64(), Some(impact));
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let after = depth(market_id, "50").await;
+    let expected = before["yesPrice"].as_f64().unwrap() * (1.0 + impact / 100.0);
+    assert!((after["yesPrice"].as_f64().unwrap() - expected).abs() < 1e-9, "{before} then {after}");
+    // The YES pool holds only 40 shares now
+    assert_eq!(decode_amount(&after["liquidity"]), tokens("40"));
+    assert_eq!(after["priceImpact"]["yes"], Value::Null);
+    assert!(after["priceImpact"]["no"].as_f64().unwrap() > 0.0);
+
+    h.advance(3600);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    assert_eq!(depth(market_id, "1").await["priceImpact"], serde_json::json!({ "yes": null, "no": null }));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_by_ids_keep_request_order_with_nulls_for_unknown_ids() {
+    let h = Harness::new().await;
+    for question in ["First?", "Second?", "Third?"] {
+        h.create_market(&h.alice, question, tokens("10"), 3600).await;
+    }
+    let query = "query { marketsByIds(ids: [2, 9, 0, 2]) { id question } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["marketsByIds"],
+        serde_json::json!([
+            { "id": 2, "question": "Third?" },
+            null,
+            { "id": 0, "question": "First?" },
+            { "id": 2, "question": "Third?" },
+        ])
+    );
+
+    let ids: Vec<String> = (0..101).map(|id| id.to_string()).collect();
+    let query = format!("query {{ marketsByIds(ids: [{}]) {{ id }} }}", ids.join(", "));
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for too many ids");
+    };
+    assert_eq!(errors[0].message, "Too many ids: 101 requested, at most 100 per query");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conditional_markets_follow_their_parent() {
+    let h = Harness::new().await;
+    let parent_id = h.create_market(&h.alice, "Wins the primary?", tokens("100"), 3600).await;
+    let conditional = |market_id: u64, outcome: bool| Operation::CreateMarket {
+        question: "Wins the general?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: Some(MarketCondition { market_id, outcome }),
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    assert_eq!(h.execute(&h.alice, conditional(9, true)).await, "ERROR: Parent market 9 not found");
+    assert_eq!(created_id(&h.execute(&h.alice, conditional(parent_id, true)).await), 1);
+    assert_eq!(created_id(&h.execute(&h.alice, conditional(parent_id, false)).await), 2);
+    assert_eq!(created_id(&h.execute(&h.alice, conditional(parent_id, false)).await), 3);
+    for market_id in [1, 2] {
+        assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    }
+
+    h.advance(3600);
+    let resolve = |market_id| Operation::ResolveMarket { market_id, outcome: true, evidence: None };
+    assert_eq!(h.execute(&h.alice, resolve(1)).await, "ERROR: Market 1 cannot resolve before its parent market 0");
+    assert_eq!(h.execute(&h.bob, Operation::ClaimWinnings { market_id: 2 }).await, "ERROR: Market 2 is not yet resolved");
+    assert!(h.execute(&h.alice, resolve(parent_id)).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&h.alice, conditional(parent_id, true)).await, "ERROR: Parent market 0 is already resolved");
+
+    // The condition held: market 1 resolves and pays as usual
+    assert!(h.execute(&h.alice, resolve(1)).await.starts_with("MarketResolved:"));
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id: 1 }).await.starts_with("WinningsClaimed:"));
+    // It failed: resolving voids market 3, and the first claim voids market 2
+    assert_eq!(h.execute(&h.alice, resolve(3)).await, "MarketVoided");
+    assert_eq!(h.execute(&h.bob, Operation::ClaimWinnings { market_id: 2 }).await, "RefundClaimed:12.5");
+    assert_eq!(h.execute(&h.alice, resolve(2)).await, "ERROR: Market 2 is already resolved");
+
+    let query = "query { marketsByIds(ids: [0, 2]) { voided parent { marketId outcome } } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["marketsByIds"],
+        serde_json::json!([
+            { "voided": false, "parent": null },
+            { "voided": true, "parent": { "marketId": 0, "outcome": false } },
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn market_groups_open_together_and_resolve_one_winner() {
+    let h = Harness::new().await;
+    let end_time = h.now().saturating_add(TimeDelta::from_secs(3600));
+    let group = |questions: &[&str], liquidity: &str| Operation::CreateMarketGroup {
+        title: "Who wins the race?".to_string(),
+        members: questions
+            .iter()
+            .map(|question| MarketSpec { question: question.to_string(), categories: vec!["politics".to_string()] })
+            .collect(),
+        end_time,
+        liquidity_per_market: tokens(liquidity),
+    };
+    assert_eq!(
+        h.execute(&h.alice, group(&["Only one?"], "100")).await,
+        "ERROR: A group needs between 2 and 20 members, got 1"
+    );
+    let response = h.execute(&h.alice, group(&["A?", "B?", "C?"], "0")).await;
+    assert_eq!(response, "ERROR: Initial liquidity must be greater than zero");
+    // Nothing was opened by the rejected groups
+    assert_eq!(h.create_market(&h.alice, "Standalone?", tokens("100"), 7200).await, 0);
+    assert_eq!(h.execute(&h.alice, group(&["A?", "B?", "C?"], "100")).await, "MarketGroupCreated:0:1");
+    assert_eq!(h.buy(&h.bob, 2, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    h.advance(3600);
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id: 2, outcome: true, evidence: None }).await;
+    assert_eq!(response, "ERROR: Market 2 belongs to group 0; resolve it with ResolveGroup");
+    let response = h.execute(&h.alice, Operation::ResolveGroup { group_id: 0, winner: 0 }).await;
+    assert_eq!(response, "ERROR: Market 0 is not a member of group 0");
+    let response = h.execute(&h.bob, Operation::ResolveGroup { group_id: 0, winner: 2 }).await;
+    assert_eq!(response, "ERROR: Not authorized: only the creator or admin can resolve this market");
+    assert_eq!(h.execute(&h.alice, Operation::ResolveGroup { group_id: 0, winner: 2 }).await, "GroupResolved:2");
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id: 2 }).await.starts_with("WinningsClaimed:"));
+
+    let query = "query { groups { id title winner members { id outcome groupId } } group(id: 0) { probabilitySum } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["groups"],
+        serde_json::json!([{
+            "id": 0,
+            "title": "Who wins the race?",
+            "winner": 2,
+            "members": [
+                { "id": 1, "outcome": false, "groupId": 0 },
+                { "id": 2, "outcome": true, "groupId": 0 },
+                { "id": 3, "outcome": false, "groupId": 0 },
+            ],
+        }])
+    );
+    // 0.5 each, except market 2 where Bob's buy moved YES to 62.5 / 102.5
+    let sum = response["group"]["probabilitySum"].as_f64().unwrap();
+    assert!((sum - (1.0 + 62.5 / 102.5)).abs() < 1e-9, "{sum}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolver_committees_settle_once_the_threshold_agrees() {
+    let h = Harness::new().await;
+    let mut carol = h.bob.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+    let bob = AccountOwner::from(h.bob.public_key());
+    let committee = |resolvers: Vec<AccountOwner>, threshold: u8| Operation::CreateMarket {
+        question: "Does the committee agree?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers,
+        threshold,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    let carol_owner = AccountOwner::from(carol.public_key());
+    assert_eq!(
+        h.execute(&h.alice, committee(vec![bob, carol_owner], 3)).await,
+        "ERROR: Threshold 3 must be between 1 and the number of resolvers (2)"
+    );
+    assert_eq!(
+        h.execute(&h.alice, committee(vec![], 1)).await,
+        "ERROR: Threshold 1 must be between 1 and the number of resolvers (0)"
+    );
+    assert_eq!(
+        h.execute(&h.alice, committee(vec![bob, bob], 1)).await,
+        format!("ERROR: {} is listed as a resolver more than once", bob)
+    );
+    assert_eq!(created_id(&h.execute(&h.alice, committee(vec![bob, carol_owner], 2)).await), 0);
+
+    h.advance(3600);
+    let resolve = |outcome| Operation::ResolveMarket { market_id: 0, outcome, evidence: None };
+    // Not even the creator and admin can bypass the committee
+    assert_eq!(
+        h.execute(&h.alice, resolve(true)).await,
+        "ERROR: Not authorized: only the market's resolvers can vote on its outcome"
+    );
+    assert_eq!(h.execute(&h.bob, resolve(true)).await, "ResolutionVoteRecorded:1:2");
+    assert_eq!(h.execute(&carol, resolve(false)).await, "ResolutionVoteRecorded:1:2");
+
+    let query = "query { resolutionTally(marketId: 0) { threshold yesVotes noVotes votes { outcome } } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["resolutionTally"],
+        serde_json::json!({
+            "threshold": 2,
+            "yesVotes": 1,
+            "noVotes": 1,
+            "votes": [{ "outcome": true }, { "outcome": false }],
+        })
+    );
+
+    // A second vote from Bob replaces the first and completes the threshold
+    assert!(h.execute(&h.bob, resolve(false)).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&carol, resolve(true)).await, "ERROR: Market 0 is already resolved");
+    let query = "query { market(id: 0) { outcome resolvers threshold } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(
+        response["market"],
+        serde_json::json!({
+            "outcome": false,
+            "resolvers": [bob.to_string(), carol_owner.to_string()],
+            "threshold": 2,
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn twap_weights_each_price_by_how_long_it_held() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Averages out?", tokens("100"), 3600).await;
+    h.advance(1000);
+    // Pools go from 50/50 to 40 YES / 62.5 NO: 6097 bps
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    h.advance(1000);
+    // Queries see the time of the chain's latest block
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: false }).await, "GlobalPauseSet:false");
+
+    async fn twap(h: &Harness, market_id: u64, window_secs: u64) -> Value {
+        let query = format!(
+            "query {{ twap(marketId: {market_id}, windowSecs: {window_secs}) {{ windowStart windowEnd yesPrice noPrice }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["twap"].clone()
+    }
+    let assert_price = |twap: &Value, yes: f64| {
+        assert!((twap["yesPrice"].as_f64().unwrap() - yes).abs() < 1e-9, "{twap}");
+        assert!((twap["noPrice"].as_f64().unwrap() - (1.0 - yes)).abs() < 1e-9, "{twap}");
+    };
+    let both = twap(&h, market_id, 2000).await;
+    assert_price(&both, 0.55485);
+    assert_eq!(both["windowStart"], "0");
+    assert_eq!(both["windowEnd"], "2000000000");
+    // No trade in the window: the last price
+    assert_price(&twap(&h, market_id, 500).await, 0.6097);
+    // Clipped to the market's creation
+    let clipped = twap(&h, market_id, 1_000_000).await;
+    assert_price(&clipped, 0.55485);
+    assert_eq!(clipped["windowStart"], "0");
+
+    // Resolution freezes the average
+    h.advance(1600);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    h.advance(1000);
+    assert_eq!(h.execute(&h.alice, Operation::SetGlobalPause { paused: false }).await, "GlobalPauseSet:false");
+    let closed = twap(&h, market_id, 3600).await;
+    assert_price(&closed, (5_000.0 * 1000.0 + 6_097.0 * 2600.0) / 3600.0 / 10_000.0);
+    assert_eq!(closed["windowEnd"], "3600000000");
+
+    let query = "query { twap(marketId: 0, windowSecs: 0) { yesPrice } }";
+    let Err(TryGraphQLQueryError::Service(errors)) = h.alice.try_graphql_query(h.app_id, query).await else {
+        panic!("Expected a GraphQL error for an empty window");
+    };
+    assert_eq!(errors[0].message, "windowSecs must be greater than zero");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dust_trades_are_rejected() {
+    // Without a floor, rounding alone must not give shares away
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Dust?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    // NO is now the cheap side: one atto costs 40 / 62.5 of an atto, rounded up
+    assert_eq!(
+        h.buy(&h.bob, market_id, false, Amount::from_attos(1), tokens("1")).await,
+        "SharesPurchased:0.000000000000000001"
+    );
+
+    let h = Harness::with_config(|c| c.min_trade_shares = tokens("0.01")).await;
+    let market_id = h.create_market(&h.alice, "Dust?", tokens("100"), 3600).await;
+    assert_eq!(
+        h.buy(&h.bob, market_id, true, Amount::from_attos(1), tokens("1")).await,
+        "ERROR: Trade of 0.000000000000000001 shares is below the minimum of 0.01"
+    );
+    let response = h.buy(&h.bob, market_id, true, tokens("0.01"), tokens("1")).await;
+    let cost: Amount = response.strip_prefix("SharesPurchased:").expect(&response).parse().unwrap();
+    assert!(cost >= Amount::from_attos(1));
+    assert_eq!(
+        h.execute(&h.bob, Operation::SellShares {
+            market_id,
+            is_yes: true,
+            shares: tokens("0.005"),
+            min_proceeds: Amount::ZERO,
+            deadline: None,
+        })
+        .await,
+        "ERROR: Trade of 0.005 shares is below the minimum of 0.01"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn realized_pnl_follows_sells_and_claims() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Realized?", tokens("100"), 3600).await;
+    let bob = AccountOwner::from(h.bob.public_key());
+    async fn realized(h: &Harness, query: String) -> String {
+        let response = h.alice.graphql_query(h.app_id, query).await.response;
+        response.as_object().unwrap().values().next().unwrap()["realizedPnl"]["attos"].as_str().unwrap().to_string()
+    }
+    let position_pnl = || format!("query {{ position(owner: \"{bob}\", marketId: 0) {{ realizedPnl {{ attos }} }} }}");
+    let stats_pnl = || format!("query {{ traderStats(owner: \"{bob}\") {{ realizedPnl {{ attos }} }} }}");
+
+    // 10 YES for 12.5, then half of them back for 62.5 * 5 / 45 against half the basis
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let sell = Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    assert_eq!(h.trade(&h.bob, sell).await, "SharesSold:6.944444444444444444");
+    assert_eq!(realized(&h, position_pnl()).await, "694444444444444444");
+
+    // 10 NO for 45 * 10 / 45.555..., rounded up. YES wins: the 5 YES shares
+    // take 5 / 55 of the pools, and the claim closes out both sides' basis.
+    let no_cost = "9.878048780487804878";
+    assert_eq!(h.buy(&h.bob, market_id, false, tokens("10"), tokens("10")).await, format!("SharesPurchased:{no_cost}"));
+    h.advance(3600);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    // The claim reports the basis of both sides against the payout: the
+    // losing NO shares' basis is written off, so the claim is a loss
+    assert_eq!(
+        h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await,
+        "WinningsClaimed:9.130327666913032766:16.128048780487804878:-6997721113574772112"
+    );
+    // 0.694444444444444444 + 9.130327666913032766 - (6.25 + 9.878048780487804878)
+    assert_eq!(realized(&h, position_pnl()).await, "-6303276669130327668");
+    assert_eq!(realized(&h, stats_pnl()).await, "-6303276669130327668");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trader_stats_count_trades_markets_and_results() {
+    let h = &Harness::new().await;
+    let first = h.create_market(&h.alice, "First?", tokens("100"), 3600).await;
+    let second = h.create_market(&h.alice, "Second?", tokens("100"), 3600).await;
+    assert!(h.buy(&h.bob, first, true, tokens("10"), tokens("100")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.bob, second, false, tokens("5"), tokens("100")).await.starts_with("SharesPurchased:"));
+    assert!(h.buy(&h.bob, second, true, tokens("5"), tokens("100")).await.starts_with("SharesPurchased:"));
+
+    let stats = |owner: AccountOwner| async move {
+        let query = format!(
+            "query {{ traderStats(owner: \"{owner}\") {{ volume {{ {AMOUNT} }} tradeCount marketsTraded marketsWon marketsLost \
+                winRateBps openPositions }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["traderStats"].clone()
+    };
+    let counts = |stats: &Value| {
+        ["tradeCount", "marketsTraded", "marketsWon", "marketsLost", "openPositions"].map(|field| stats[field].as_u64().unwrap())
+    };
+    let bob = AccountOwner::from(h.bob.public_key());
+    let traded = stats(bob).await;
+    assert_eq!(counts(&traded), [3, 2, 0, 0, 2]);
+    assert_eq!(traded["winRateBps"], Value::Null);
+    let market_volume = |market_id: u64| async move { h.market(market_id).await["volume"].as_str().unwrap().parse::<Amount>().unwrap() };
+    assert_eq!(decode_amount(&traded["volume"]), market_volume(first).await.saturating_add(market_volume(second).await));
+
+    // Accounts that never traded read as zeros
+    let alice = stats(AccountOwner::from(h.alice.public_key())).await;
+    assert_eq!(counts(&alice), [0, 0, 0, 0, 0]);
+    assert_eq!(decode_amount(&alice["volume"]), Amount::ZERO);
+
+    // Holding any winning shares wins the market, before claiming too
+    h.advance(3601);
+    let resolve = |market_id: u64, outcome: bool| Operation::ResolveMarket { market_id, outcome, evidence: None };
+    assert!(h.execute(&h.alice, resolve(first, false)).await.starts_with("MarketResolved:"));
+    assert!(h.execute(&h.alice, resolve(second, true)).await.starts_with("MarketResolved:"));
+    let resolved = stats(bob).await;
+    assert_eq!(counts(&resolved), [3, 2, 1, 1, 0]);
+    assert_eq!(resolved["winRateBps"], 5_000);
+    let claim = Operation::ClaimWinnings { market_id: second };
+    assert!(h.execute(&h.bob, claim).await.starts_with("WinningsClaimed:"));
+    assert_eq!(counts(&stats(bob).await), [3, 2, 1, 1, 0]);
+
+    // The leaderboard lists only accounts that traded or realized a result
+    let query = "query { leaderboard { owner tradeCount } }";
+    let leaderboard = h.alice.graphql_query(h.app_id, query).await.response["leaderboard"].clone();
+    assert_eq!(leaderboard, serde_json::json!([{ "owner": bob.to_string(), "tradeCount": 3 }]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn top_traders_rank_active_accounts_within_a_window() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Top?", tokens("100"), 30 * 86_400).await;
+    for _ in 0..3 {
+        assert!(h.buy(&h.bob, market_id, true, tokens("3"), tokens("100")).await.starts_with("SharesPurchased:"));
+    }
+    assert!(h.buy(&h.alice, market_id, false, tokens("1"), tokens("100")).await.starts_with("SharesPurchased:"));
+
+    let top = |arguments: &str| {
+        let query = format!("query {{ topTraders({arguments}) {{ rank owner tradeCount value {{ attos }} }} }}");
+        async move { h.alice.graphql_query(h.app_id, query).await.response["topTraders"].clone() }
+    };
+    let bob = AccountOwner::from(h.bob.public_key()).to_string();
+    let alice = AccountOwner::from(h.alice.public_key()).to_string();
+
+    // One trade is below the default minimum of three
+    let week = top("window: DAYS_7, by: VOLUME").await;
+    assert_eq!(week.as_array().unwrap().len(), 1);
+    assert_eq!((&week[0]["rank"], &week[0]["owner"], &week[0]["tradeCount"]), (&Value::from(1), &Value::from(bob.clone()), &Value::from(3)));
+    let query = format!("query {{ traderStats(owner: \"{bob}\") {{ volume {{ attos }} }} }}");
+    let stats = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(week[0]["value"]["attos"], stats["traderStats"]["volume"]["attos"]);
+    let everyone = top("window: DAYS_7, by: VOLUME, minTrades: 1").await;
+    let ranks: Vec<_> = everyone.as_array().unwrap().iter().map(|entry| (entry["rank"].clone(), entry["owner"].clone())).collect();
+    assert_eq!(ranks, [(Value::from(1), Value::from(bob.clone())), (Value::from(2), Value::from(alice))]);
+
+    // Eight days on the trades have left the week but not all time
+    h.advance(8 * 86_400);
+    assert_eq!(top("window: DAYS_7").await, serde_json::json!([]));
+    let all_time = top("window: ALL_TIME, by: VOLUME").await;
+    assert_eq!((&all_time[0]["owner"], &all_time[0]["tradeCount"]), (&Value::from(bob), &Value::from(3)));
+    assert_eq!(top("window: DAYS_30, limit: 0").await, serde_json::json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn max_position_caps_both_sides_combined() {
+    let h = Harness::new().await;
+    let operation = Operation::CreateMarket {
+        question: "Capped?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: Some(tokens("15")),
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    assert_eq!(created_id(&h.execute(&h.alice, operation).await), 0);
+
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let one_atto_over = tokens("5").saturating_add(Amount::from_attos(1));
+    assert_eq!(
+        h.buy(&h.bob, 0, false, one_atto_over, tokens("10")).await,
+        "ERROR: Market 0 caps each account at 15. YES and NO shares combined; holding 10."
+    );
+    assert!(h.buy(&h.bob, 0, false, tokens("5"), tokens("10")).await.starts_with("SharesPurchased:"));
+    // Resting bids count against the cap when placed
+    assert_eq!(
+        h.place_order(&h.bob, 0, OrderSide::Buy, 5_000, "1").await,
+        "ERROR: Market 0 caps each account at 15. YES and NO shares combined; holding 15."
+    );
+    // The cap is per account
+    assert!(h.buy(&h.alice, 0, true, tokens("15"), tokens("30")).await.starts_with("SharesPurchased:"));
+
+    let response = h.alice.graphql_query(h.app_id, "query { market(id: 0) { maxPosition { tokens } } }").await.response;
+    assert_eq!(response["market"]["maxPosition"]["tokens"], "15");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn creator_resolves_from_end_time_unless_early_resolution_is_allowed() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "On time?", tokens("100"), 3600).await;
+    let end_micros = h.now().saturating_add(TimeDelta::from_secs(3600)).micros();
+    let early = h.execute(&h.alice, Operation::CreateMarket {
+        question: "Early?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: true,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    }).await;
+    assert_eq!(created_id(&early), market_id + 1);
+    let query = format!("query {{ market(id: {}) {{ earlyResolutionAllowed }} }}", market_id + 1);
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["market"]["earlyResolutionAllowed"], true);
+
+    // Markets flagged for early resolution may settle at any time
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id: market_id + 1, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("MarketResolved:"), "{response}");
+
+    h.advance(3599);
+    h.validator.clock().add(TimeDelta::from_micros(999_999));
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert_eq!(response, format!("ERROR: Market {market_id} cannot be resolved before its end time {end_micros}"));
+
+    h.validator.clock().add(TimeDelta::from_micros(1));
+    assert_eq!(h.now().micros(), end_micros);
+    let response = h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await;
+    assert!(response.starts_with("MarketResolved:"), "{response}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn creator_trading_disabled_blocks_only_the_creator() {
+    let h = Harness::new().await;
+    let operation = Operation::CreateMarket {
+        question: "Hands off?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: true,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    assert_eq!(created_id(&h.execute(&h.alice, operation).await), 0);
+
+    let refused = "ERROR: The creator of market 0 may not trade in it";
+    assert_eq!(h.buy(&h.alice, 0, true, tokens("10"), tokens("12.5")).await, refused);
+    assert_eq!(h.place_order(&h.alice, 0, OrderSide::Buy, 5_000, "1").await, refused);
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let response = h.alice.graphql_query(h.app_id, "query { market(id: 0) { creatorTradingDisabled } }").await.response;
+    assert_eq!(response["market"]["creatorTradingDisabled"], true);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trade_responses_report_the_resulting_prices_and_pools() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Priced?", tokens("100"), 3600).await;
+    let query = format!(
+        "query {{ market(id: {market_id}) {{ yesPriceBps noPriceBps yesPool {{ {AMOUNT} }} noPool {{ {AMOUNT} }} }} }}"
+    );
+    let expected = |market: &Value, head: &str| {
+        format!(
+            "{head}:{}:{}:{}:{}:0.:0",
+            market["yesPriceBps"],
+            market["noPriceBps"],
+            decode_amount(&market["yesPool"]),
+            decode_amount(&market["noPool"]),
+        )
+    };
+
+    let buy = Operation::BuyShares {
+        market_id, is_yes: true, shares: tokens("10"), max_cost: tokens("12.5"), deadline: None, max_price_impact_bps: None,
+    };
+    let response = h.execute(&h.bob, buy).await;
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    // Buys end with their price impact
+    assert_eq!(response, format!("{}:2500", expected(&market, "SharesPurchased:12.5:10.")));
+    assert_eq!(response, "SharesPurchased:12.5:10.:6097:3902:40.:62.5:0.:0:2500");
+
+    let sell = Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    let response = h.execute(&h.bob, sell).await;
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    assert_eq!(response, expected(&market, "SharesSold:6.944444444444444444:5."));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn settled_markets_can_be_archived_once_nothing_is_owed() {
+    let h = Harness::new().await;
+    let window = Config::default().claim_window_secs;
+    let first = h.create_market(&h.alice, "Archive me?", tokens("100"), 3600).await;
+    let second = h.create_market(&h.alice, "Keep me?", tokens("100"), 7200).await;
+    assert_eq!(h.buy(&h.bob, first, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let archive = || Operation::ArchiveMarket { market_id: first };
+    let blocked = |reason: &str| format!("ERROR: Market {first} cannot be archived: {reason}");
+    assert_eq!(h.execute(&h.bob, archive()).await, blocked("it is not resolved"));
+
+    h.advance(3601);
+    let resolved_at = h.now();
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: first, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&h.bob, archive()).await, blocked("1 positions have unclaimed winnings"));
+
+    let response = h.execute(&h.bob, Operation::ClaimWinnings { market_id: first }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+    let response = h.execute(&h.bob, archive()).await;
+    assert!(response.starts_with(&blocked("it still holds")), "{response}");
+
+    h.advance(window + 1);
+    let response = h.execute(&h.alice, Operation::RecoverUnclaimed { market_id: first }).await;
+    assert!(response.starts_with("UnclaimedRecovered:"), "{response}");
+    assert_eq!(h.execute(&h.bob, archive()).await, format!("MarketArchived:{first}"));
+    assert_eq!(h.execute(&h.bob, archive()).await, format!("ERROR: Market {first} not found"));
+
+    let query = format!(
+        "query {{ archivedMarket(id: {first}) {{ question outcome resolvedAt finalVolume {{ tokens }} }} \
+            archivedMarkets {{ totalCount }} markets {{ totalCount markets {{ id }} }} \
+            position(owner: \"{}\", marketId: {first}) {{ yesShares {{ tokens }} }} }}",
+        AccountOwner::from(h.bob.public_key())
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    assert_eq!(response["archivedMarket"]["question"], "Archive me?");
+    assert_eq!(response["archivedMarket"]["outcome"], true);
+    assert_eq!(response["archivedMarket"]["resolvedAt"], resolved_at.micros().to_string());
+    assert_eq!(response["archivedMarket"]["finalVolume"]["tokens"], "12.5");
+    assert_eq!(response["archivedMarkets"]["totalCount"], 1);
+    assert_eq!(response["markets"]["totalCount"], 1);
+    assert_eq!(response["markets"]["markets"][0]["id"], second);
+    assert_eq!(response["position"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn state_exports_page_through_markets_then_positions() {
+    let h = Harness::new().await;
+    for question in ["One?", "Two?", "Three?"] {
+        h.create_market(&h.alice, question, tokens("100"), 3600).await;
+    }
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert_eq!(h.buy(&h.bob, 2, false, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let h = &h;
+    let export = |cursor: Option<String>, include_positions: bool| {
+        let cursor = cursor.map_or("null".to_string(), |c| format!("\"{c}\""));
+        let query = format!(
+            "query {{ exportState(cursor: {cursor}, pageSize: 2, includePositions: {include_positions}) \
+                {{ schemaVersion payload nextCursor }} }}"
+        );
+        async move {
+            let page = h.alice.graphql_query(h.app_id, query).await.response["exportState"].clone();
+            assert_eq!(page["schemaVersion"], 1);
+            let payload: Value = serde_json::from_str(page["payload"].as_str().unwrap()).unwrap();
+            assert_eq!(payload["schema_version"], 1);
+            (payload, page["nextCursor"].as_str().map(str::to_string))
+        }
+    };
+
+    let (first, cursor) = export(None, true).await;
+    assert_eq!(first["markets"].as_array().unwrap().len(), 2);
+    assert_eq!(first["positions"], serde_json::json!([]));
+    let cursor = cursor.expect("more markets to export");
+    // The same cursor returns the same page
+    assert_eq!(export(Some(cursor.clone()), true).await, export(Some(cursor.clone()), true).await);
+
+    let (second, cursor) = export(Some(cursor), true).await;
+    assert_eq!(second["markets"].as_array().unwrap().len(), 1);
+    assert_eq!(second["positions"].as_array().unwrap().len(), 1);
+    let (third, cursor) = export(cursor, true).await;
+    assert_eq!(third["markets"], serde_json::json!([]));
+    assert_eq!(third["positions"].as_array().unwrap().len(), 1);
+    assert_eq!(cursor, None);
+
+    let mut ids: Vec<u64> = [&first, &second]
+        .iter()
+        .flat_map(|page| page["markets"].as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()))
+        .collect();
+    ids.sort_unstable();
+    assert_eq!(ids, [0, 1, 2]);
+    let mut held: Vec<u64> = [&second, &third]
+        .iter()
+        .map(|page| page["positions"][0]["market_id"].as_u64().unwrap())
+        .collect();
+    held.sort_unstable();
+    assert_eq!(held, [0, 2]);
+
+    // Without positions the export ends with the markets
+    let (_, cursor) = export(None, false).await;
+    let (last, cursor) = export(cursor, false).await;
+    assert_eq!(last["markets"].as_array().unwrap().len(), 1);
+    assert_eq!(last["positions"], serde_json::json!([]));
+    assert_eq!(cursor, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fee_ramps_up_over_the_final_window() {
+    let h = &Harness::with_config(|config| config.fee_bps = 100).await;
+    let create = |fee_ramp| Operation::CreateMarket {
+        question: "Sniped?".to_string(),
+        categories: vec![],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: Some(fee_ramp),
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    let invalid = "ERROR: A fee ramp needs a nonzero window and a max fee between the base fee of 100 bps and 10000";
+    assert_eq!(h.execute(&h.alice, create(FeeRamp { window_secs: 600, max_fee_bps: 50 })).await, invalid);
+    assert_eq!(h.execute(&h.alice, create(FeeRamp { window_secs: 0, max_fee_bps: 500 })).await, invalid);
+    assert_eq!(created_id(&h.execute(&h.alice, create(FeeRamp { window_secs: 600, max_fee_bps: 500 })).await), 0);
+
+    let quote = || async move {
+        let query = "query { market(id: 0) { feeBps feeRamp { windowSecs maxFeeBps } \
+            buyQuote(isYes: true, shares: \"10\") { cost { tokens } fee { tokens } feeBps } } }";
+        h.alice.graphql_query(h.app_id, query).await.response["market"].clone()
+    };
+    let market = quote().await;
+    assert_eq!(market["feeBps"], 100);
+    assert_eq!(market["feeRamp"]["maxFeeBps"], 500);
+    assert_eq!(market["buyQuote"]["feeBps"], 100);
+
+    // Halfway through the window the fee is halfway to the max
+    h.advance(3300);
+    let market = quote().await;
+    assert_eq!(market["feeBps"], 300);
+    assert_eq!(market["buyQuote"]["fee"]["tokens"], "0.375");
+    assert_eq!(market["buyQuote"]["cost"]["tokens"], "12.875");
+
+    let buy = Operation::BuyShares {
+        market_id: 0, is_yes: true, shares: tokens("10"), max_cost: tokens("13"), deadline: None, max_price_impact_bps: None,
+    };
+    let response = h.execute(&h.bob, buy).await;
+    assert!(response.starts_with("SharesPurchased:12.875:"), "{response}");
+    assert!(response.ends_with(":0.375:300:2500"), "{response}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn activity_log_pages_successful_operations_by_market_and_owner() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "First?", tokens("100"), 3600).await;
+    h.create_market(&h.alice, "Second?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    // Failed operations leave no entry
+    assert!(h.buy(&h.bob, 1, true, tokens("10"), tokens("1")).await.starts_with("ERROR"));
+    assert_eq!(h.buy(&h.bob, 1, false, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    let bob = AccountOwner::from(h.bob.public_key()).to_string();
+    let activity = |field: String| async move {
+        let query = format!("query {{ {field} {{ id kind marketId actor shares {{ tokens }} result }} }}");
+        h.alice.graphql_query(h.app_id, query).await.response.as_object().unwrap().values().next().unwrap().clone()
+    };
+    let ids = |entries: &Value| -> Vec<u64> {
+        entries.as_array().unwrap().iter().map(|e| e["id"].as_u64().unwrap()).collect()
+    };
+
+    let all = activity("activity".to_string()).await;
+    assert_eq!(ids(&all), [3, 2, 1, 0]);
+    assert_eq!(all[3]["kind"], "CreateMarket");
+    assert_eq!(all[3]["marketId"], 0);
+    let alice = AccountOwner::from(h.alice.public_key());
+    assert_eq!(all[3]["result"], format!("MarketCreated:0:{}", market_key(h.alice.id(), alice, 0)));
+    assert_eq!(all[0]["kind"], "BuyShares");
+    assert_eq!(all[0]["actor"], bob.as_str());
+    assert_eq!(all[0]["shares"]["tokens"], "10");
+    assert!(all[0]["result"].as_str().unwrap().starts_with("SharesPurchased:12.5:"));
+
+    let market = activity("activity(marketId: 0)".to_string()).await;
+    assert_eq!(ids(&market), [2, 0]);
+    assert_eq!(ids(&activity("activity(marketId: 0, before: 2)".to_string()).await), [0]);
+    assert_eq!(ids(&activity("activity(marketId: 7)".to_string()).await), Vec::<u64>::new());
+
+    let first = activity(format!("activityByOwner(owner: \"{bob}\", limit: 1)")).await;
+    assert_eq!(ids(&first), [3]);
+    let rest = activity(format!("activityByOwner(owner: \"{bob}\", limit: 1, before: 3)")).await;
+    assert_eq!(ids(&rest), [2]);
+    assert_eq!(ids(&activity(format!("activityByOwner(owner: \"{bob}\", before: 2)")).await), Vec::<u64>::new());
+
+    let Err(TryGraphQLQueryError::Service(errors)) =
+        h.alice.try_graphql_query(h.app_id, "query { activity(limit: 101) { id } }").await
+    else {
+        panic!("Expected oversized pages to be rejected");
+    };
+    let error = serde_json::to_value(&errors[0]).unwrap();
+    assert_eq!(error["message"], "Invalid value for argument \"limit\": must be between 1 and 100");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_filter_by_end_time_range_before_paging() {
+    let h = &Harness::new().await;
+    let start = h.now().micros();
+    for (question, hours) in [("One?", 1), ("Two?", 2), ("Three?", 3), ("Four?", 4)] {
+        h.create_market(&h.alice, question, tokens("10"), hours * 3600).await;
+    }
+    let hour = 3_600_000_000;
+    let page = |args: String| async move {
+        let query = format!("query {{ markets({args}) {{ totalCount markets {{ id }} }} }}");
+        let page = h.alice.graphql_query(h.app_id, query).await.response["markets"].clone();
+        let ids: Vec<u64> = page["markets"].as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect();
+        (page["totalCount"].as_u64().unwrap(), ids)
+    };
+
+    // After is inclusive, before exclusive
+    let (after, before) = (start + 2 * hour, start + 4 * hour);
+    assert_eq!(page(format!("endsAfter: \"{after}\", endsBefore: \"{before}\"")).await, (2, vec![1, 2]));
+    assert_eq!(page(format!("endsAfter: \"{after}\"")).await, (3, vec![1, 2, 3]));
+    assert_eq!(page(format!("endsAfter: \"{after}\", limit: 1, offset: 1")).await, (3, vec![2]));
+    assert_eq!(page(format!("endsBefore: \"{after}\", category: \"TEST\"")).await, (1, vec![0]));
+    assert_eq!(page(format!("endsBefore: \"{before}\", sortBy: CREATED_AT_DESC")).await, (3, vec![2, 1, 0]));
+    // Empty and inverted ranges match nothing
+    assert_eq!(page(format!("endsAfter: \"{after}\", endsBefore: \"{after}\"")).await, (0, vec![]));
+    assert_eq!(page(format!("endsAfter: \"{before}\", endsBefore: \"{after}\"")).await, (0, vec![]));
+
+    let query = format!("query {{ activeMarkets(endsAfter: \"{after}\", endsBefore: \"{before}\") {{ id }} }}");
+    let active = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(active["activeMarkets"], serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+
+    let Err(TryGraphQLQueryError::Service(errors)) =
+        h.alice.try_graphql_query(h.app_id, "query { markets(endsAfter: \"tomorrow\") { totalCount } }").await
+    else {
+        panic!("Expected a bad timestamp to be rejected");
+    };
+    let message = serde_json::to_value(&errors[0]).unwrap()["message"].as_str().unwrap().to_string();
+    assert!(message.starts_with("Invalid timestamp for argument \"endsAfter\""), "{message}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn market_filters_combine_status_category_creator_text_and_end_time() {
+    let h = &Harness::new().await;
+    let create = |question: &str, category: &str, hours: u64| Operation::CreateMarket {
+        question: question.to_string(),
+        categories: vec![category.to_string()],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(hours * 3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps: None,
+        max_trade_shares: None,
+    };
+    for (chain, question, category) in [
+        (&h.alice, "Will BTC top 100k?", "Crypto"),
+        (&h.alice, "Will ETH flip BTC?", "crypto"),
+        (&h.bob, "Will SOL hit 500?", "crypto"),
+        (&h.alice, "Will it rain in Paris?", "weather"),
+    ] {
+        let hours = if question.contains("100k") { 1 } else { 5 };
+        assert!(h.execute(chain, create(question, category, hours)).await.starts_with("MarketCreated:"));
+    }
+    assert_eq!(h.buy(&h.bob, 1, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert!(h.buy(&h.bob, 2, true, tokens("5"), tokens("10")).await.starts_with("SharesPurchased:"));
+    h.advance(2 * 3600);
+    let resolve = Operation::ResolveMarket { market_id: 0, outcome: true, evidence: None };
+    assert!(!h.execute(&h.alice, resolve).await.starts_with("ERROR"));
+
+    let alice = AccountOwner::from(h.alice.public_key());
+    let bob = AccountOwner::from(h.bob.public_key());
+    let page = |args: String| async move {
+        let query = format!("query {{ markets({args}) {{ totalCount markets {{ id }} }} }}");
+        let page = h.alice.graphql_query(h.app_id, query).await.response["markets"].clone();
+        let ids: Vec<u64> = page["markets"].as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect();
+        (page["totalCount"].as_u64().unwrap(), ids)
+    };
+
+    // Active crypto markets by volume, page 2
+    let args = "filter: { status: ACTIVE, category: \"CRYPTO\" }, sort: VOLUME_DESC, limit: 1, offset: 1";
+    assert_eq!(page(args.to_string()).await, (2, vec![2]));
+    assert_eq!(page(format!("filter: {{ status: ACTIVE, category: \"crypto\", creator: \"{alice}\" }}")).await, (1, vec![1]));
+    assert_eq!(page("filter: { status: RESOLVED, category: \"crypto\" }".to_string()).await, (1, vec![0]));
+    assert_eq!(page("filter: { text: \"btc\" }".to_string()).await, (2, vec![0, 1]));
+    assert_eq!(page("filter: { text: \"btc\", status: ACTIVE }".to_string()).await, (1, vec![1]));
+
+    // Contradictory filters match nothing
+    assert_eq!(page(format!("filter: {{ creator: \"{bob}\", category: \"weather\" }}")).await, (0, vec![]));
+    assert_eq!(page("filter: { text: \"  \" }".to_string()).await, (0, vec![]));
+    let end = h.now().micros() + 3 * 3_600_000_000;
+    assert_eq!(page(format!("filter: {{ status: RESOLVED, endsAfter: \"{end}\" }}")).await, (0, vec![]));
+    assert_eq!(page(format!("filter: {{ status: ACTIVE, endsBefore: \"{}\" }}", h.now().micros())).await, (0, vec![]));
+
+    // The legacy queries agree with the filter they stand for
+    let query = format!(
+        "query {{ activeMarkets(category: \"crypto\") {{ id }} marketsByCreator(creator: \"{bob}\") {{ markets {{ id }} }} \
+            marketsByCategory(category: \"Weather\") {{ id }} }}"
+    );
+    let legacy = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(legacy["activeMarkets"], serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+    assert_eq!(legacy["marketsByCreator"]["markets"], serde_json::json!([{ "id": 2 }]));
+    assert_eq!(legacy["marketsByCategory"], serde_json::json!([{ "id": 3 }]));
+
+    let Err(TryGraphQLQueryError::Service(errors)) =
+        h.alice.try_graphql_query(h.app_id, "query { markets(filter: { creator: \"nobody\" }) { totalCount } }").await
+    else {
+        panic!("Expected a bad creator to be rejected");
+    };
+    let message = serde_json::to_value(&errors[0]).unwrap()["message"].as_str().unwrap().to_string();
+    assert!(message.starts_with("Invalid account format for argument \"creator\""), "{message}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connections_page_by_key_across_inserts() {
+    let h = &Harness::new().await;
+    for question in ["One?", "Two?", "Three?"] {
+        h.create_market(&h.alice, question, tokens("100"), 3600).await;
+    }
+    let markets = |after: Option<String>| async move {
+        let after = after.map_or("null".to_string(), |c| format!("\"{c}\""));
+        let query = format!(
+            "query {{ marketsConnection(first: 2, after: {after}) {{ \
+                edges {{ cursor node {{ id }} }} pageInfo {{ hasNextPage endCursor }} }} }}"
+        );
+        let page = h.alice.graphql_query(h.app_id, query).await.response["marketsConnection"].clone();
+        let ids: Vec<u64> = page["edges"].as_array().unwrap().iter().map(|e| e["node"]["id"].as_u64().unwrap()).collect();
+        let end = page["pageInfo"]["endCursor"].as_str().map(str::to_string);
+        (ids, page["pageInfo"]["hasNextPage"].as_bool().unwrap(), end)
+    };
+
+    let (ids, more, cursor) = markets(None).await;
+    assert_eq!((ids, more), (vec![0, 1], true));
+    // A market created between fetches lands after the cursor, not in a gap
+    h.create_market(&h.alice, "Four?", tokens("100"), 3600).await;
+    let (ids, more, _) = markets(cursor).await;
+    assert_eq!((ids, more), (vec![2, 3], false));
+
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert_eq!(h.buy(&h.bob, 1, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert!(h.buy(&h.bob, 0, false, tokens("5"), tokens("10")).await.starts_with("SharesPurchased:"));
+    let sell = Operation::SellShares { market_id: 0, is_yes: true, shares: tokens("4"), min_proceeds: Amount::ZERO, deadline: None };
+    assert!(h.trade(&h.bob, sell).await.starts_with("SharesSold:"));
+
+    let trades = |after: Option<String>| async move {
+        let after = after.map_or("null".to_string(), |c| format!("\"{c}\""));
+        let query = format!(
+            "query {{ tradesConnection(marketId: 0, first: 2, after: {after}) {{ \
+                edges {{ node {{ sequence isBuy isYes shares {{ tokens }} amount {{ tokens }} yesPriceBps }} }} \
+                pageInfo {{ hasNextPage endCursor }} }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["tradesConnection"].clone()
+    };
+    let first = trades(None).await;
+    assert_eq!(first["pageInfo"]["hasNextPage"], true);
+    assert_eq!(first["edges"][0]["node"]["isBuy"], true);
+    assert_eq!(first["edges"][0]["node"]["isYes"], true);
+    assert_eq!(first["edges"][0]["node"]["amount"]["tokens"], "12.5");
+    assert_eq!(first["edges"][1]["node"]["isYes"], false);
+    let cursor = first["pageInfo"]["endCursor"].as_str().map(str::to_string);
+    let rest = trades(cursor).await;
+    assert_eq!(rest["pageInfo"]["hasNextPage"], false);
+    let edges = rest["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0]["node"]["isBuy"], false);
+    assert_eq!(edges[0]["node"]["shares"]["tokens"], "4");
+    let live = h.alice.graphql_query(h.app_id, "query { market(id: 0) { yesPriceBps } }").await.response;
+    assert_eq!(edges[0]["node"]["yesPriceBps"], live["market"]["yesPriceBps"]);
+
+    let Err(TryGraphQLQueryError::Service(errors)) =
+        h.alice.try_graphql_query(h.app_id, "query { tradesConnection(marketId: 0, after: \"market:1\") { pageInfo { hasNextPage } } }").await
+    else {
+        panic!("Expected a cursor of the wrong kind to be rejected");
+    };
+    let error = serde_json::to_value(&errors[0]).unwrap();
+    assert_eq!(error["message"], "Invalid value for argument \"after\": \"market:1\"");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn probability_history_replays_trades_and_ends_at_the_live_price() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Quiet?", tokens("100"), 7200).await;
+    h.create_market(&h.alice, "Busy?", tokens("100"), 7200).await;
+    for (is_yes, shares) in [(true, "10"), (true, "5"), (false, "20"), (true, "3"), (false, "1")] {
+        h.advance(60);
+        assert!(h.buy(&h.bob, 1, is_yes, tokens(shares), tokens("100")).await.starts_with("SharesPurchased:"));
+    }
+    let history = |market_id: u64, max_points: u64| async move {
+        let query = format!(
+            "query {{ probabilityHistory(marketId: {market_id}, maxPoints: {max_points}) {{ timestamp yesPriceBps probability }} \
+                market(id: {market_id}) {{ yesPriceBps createdAt }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response
+    };
+
+    let quiet = history(0, 10).await;
+    assert_eq!(quiet["probabilityHistory"], serde_json::json!([
+        { "timestamp": quiet["market"]["createdAt"], "yesPriceBps": 5000, "probability": 0.5 }
+    ]));
+
+    // Every trade fits: the opening point and one per trade
+    let full = history(1, 100).await;
+    let points = full["probabilityHistory"].as_array().unwrap();
+    assert_eq!(points.len(), 6);
+    assert_eq!(points[0]["yesPriceBps"], 5000);
+    assert!(points[1]["yesPriceBps"].as_u64().unwrap() > 5000);
+    assert!(points[3]["yesPriceBps"].as_u64().unwrap() < points[2]["yesPriceBps"].as_u64().unwrap());
+    assert_eq!(points[5]["yesPriceBps"], full["market"]["yesPriceBps"]);
+
+    // Downsampled, still starting at the opening and ending at the live price
+    let sampled = history(1, 3).await;
+    let points = sampled["probabilityHistory"].as_array().unwrap();
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[0]["timestamp"], sampled["market"]["createdAt"]);
+    assert_eq!(points[0]["yesPriceBps"], 5000);
+    assert_eq!(points[2]["yesPriceBps"], sampled["market"]["yesPriceBps"]);
+    let times: Vec<u64> = points.iter().map(|p| p["timestamp"].as_str().unwrap().parse().unwrap()).collect();
+    assert_eq!(times[1] - times[0], times[2] - times[1]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trending_ranks_by_volume_over_the_last_24_hours() {
+    let h = &Harness::new().await;
+    for question in ["Early?", "Later?", "Untraded?"] {
+        h.create_market(&h.alice, question, tokens("100"), 48 * 3600).await;
+    }
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    h.advance(2 * 3600);
+    assert!(h.buy(&h.bob, 1, true, tokens("5"), tokens("10")).await.starts_with("SharesPurchased:"));
+
+    let trending = || async move {
+        let query = "query { trending { id volume24h { tokens } change24h yesPriceBps } }";
+        h.alice.graphql_query(h.app_id, query).await.response["trending"].clone()
+    };
+    let ids = |markets: &Value| -> Vec<u64> {
+        markets.as_array().unwrap().iter().map(|m| m["id"].as_u64().unwrap()).collect()
+    };
+    let markets = trending().await;
+    assert_eq!(ids(&markets), [0, 1, 2]);
+    assert_eq!(markets[0]["volume24h"]["tokens"], "12.5");
+    assert_eq!(markets[2]["volume24h"]["tokens"], "0");
+
+    // The first trade ages out with no later trade in that market to expire it
+    h.advance(23 * 3600);
+    let markets = trending().await;
+    assert_eq!(ids(&markets), [1, 2, 0]);
+    assert_eq!(markets[2]["volume24h"]["tokens"], "0");
+    // Market 0 last moved more than a day ago; market 1 is compared to its opening
+    assert_eq!(markets[2]["change24h"], 0);
+    assert_eq!(markets[0]["change24h"].as_i64().unwrap(), markets[0]["yesPriceBps"].as_i64().unwrap() - 5000);
+    assert_eq!(markets[1]["change24h"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn daily_volume_splits_trades_at_utc_midnight() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Overnight?", tokens("100"), 3 * 24 * 3600).await;
+    // One minute before the next UTC midnight
+    let day = 24 * 3600 * 1_000_000;
+    let to_midnight = day - h.now().micros() % day;
+    h.advance(to_midnight / 1_000_000 - 60);
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    h.advance(120);
+    let response = h.trade(&h.bob, Operation::BuyShares {
+        market_id: 0, is_yes: false, shares: tokens("1"), max_cost: tokens("10"), deadline: None, max_price_impact_bps: None,
+    }).await;
+    let cost = response.strip_prefix("SharesPurchased:").unwrap().to_string();
+
+    let query = "query { volumeHistory(days: 3) { day date volume { tokens } } market(id: 0) { volume24h { tokens } } \
+        stats { volume24h { tokens } } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    let history = response["volumeHistory"].as_array().unwrap();
+    assert_eq!(history.len(), 3);
+    let today = h.now().micros() / day;
+    assert_eq!(history[2]["day"], today);
+    assert_eq!(history[1]["day"], today - 1);
+    assert_eq!(history[0]["volume"]["tokens"], "0");
+    assert_eq!(history[1]["volume"]["tokens"], "12.5");
+    assert_eq!(history[2]["volume"]["tokens"], cost);
+    let total = (tokens("12.5").saturating_add(tokens(&cost))).to_string();
+    assert_eq!(response["market"]["volume24h"]["tokens"], total.trim_end_matches('.'));
+    assert_eq!(response["stats"]["volume24h"]["tokens"], total.trim_end_matches('.'));
+
+    let Err(TryGraphQLQueryError::Service(errors)) =
+        h.alice.try_graphql_query(h.app_id, "query { volumeHistory(days: 0) { day } }").await
+    else {
+        panic!("Expected an empty history to be rejected");
+    };
+    let error = serde_json::to_value(&errors[0]).unwrap();
+    assert_eq!(error["message"], "Invalid value for argument \"days\": must be between 1 and 366");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recent_trades_keep_the_newest_across_markets() {
+    let h = &Harness::with_config(|c| c.recent_trades_capacity = Some(3)).await;
+    h.create_market(&h.alice, "First?", tokens("100"), 24 * 3600).await;
+    h.create_market(&h.alice, "Second?", tokens("100"), 24 * 3600).await;
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert!(h.buy(&h.bob, 1, false, tokens("2"), tokens("10")).await.starts_with("SharesPurchased:"));
+    let response = h.trade(&h.bob, Operation::SellShares {
+        market_id: 0, is_yes: true, shares: tokens("4"), min_proceeds: Amount::ZERO, deadline: None,
+    }).await;
+    let proceeds = response.strip_prefix("SharesSold:").unwrap().to_string();
+    assert!(h.buy(&h.bob, 1, true, tokens("1"), tokens("10")).await.starts_with("SharesPurchased:"));
+
+    let query = "query { recentTrades(limit: 10) { marketId question side isYes shares { tokens } amount { tokens } } \
+        config { recentTradesCapacity } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["config"]["recentTradesCapacity"], 3);
+    // The first buy was evicted; the rest read newest first
+    let trades = response["recentTrades"].as_array().unwrap();
+    let summary: Vec<(u64, &str, &str, bool)> = trades
+        .iter()
+        .map(|t| (
+            t["marketId"].as_u64().unwrap(),
+            t["question"].as_str().unwrap(),
+            t["side"].as_str().unwrap(),
+            t["isYes"].as_bool().unwrap(),
+        ))
+        .collect();
+    assert_eq!(summary, [
+        (1, "Second?", "BUY", true),
+        (0, "First?", "SELL", true),
+        (1, "Second?", "BUY", false),
+    ]);
+    assert_eq!(trades[1]["shares"]["tokens"], "4");
+    assert_eq!(trades[1]["amount"]["tokens"], proceeds);
+
+    let query = "query { recentTrades(limit: 1) { marketId } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["recentTrades"][0]["marketId"], 1);
+    assert_eq!(response["recentTrades"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn applications_trade_under_their_own_account() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Composed?", tokens("100"), 3600).await;
+    let module_id = h.alice.publish_bytecode_files_in::<CallerAbi, CallerParameters, ()>("caller").await;
+    let caller_id = h.alice.create_application(module_id, h.app_id, (), vec![h.app_id.forget_abi()]).await;
+
+    // Bob signs the block, but the market attributes the trade to the calling application
+    let buy = Operation::BuyShares {
+        market_id: 0, is_yes: true, shares: tokens("10"), max_cost: tokens("12.5"), deadline: None, max_price_impact_bps: None,
+    };
+    let certificate = h.bob
+        .add_block(|block| {
+            block.with_timestamp(h.now()).with_operation(caller_id, CallerOperation::Call(buy));
+        })
+        .await;
+    let result = &certificate.inner().block().body.operation_results[0];
+    let response: String = bcs::from_bytes(&result.0).unwrap();
+    assert!(response.starts_with("SharesPurchased:12.5:"), "{response}");
+
+    let position = |owner: AccountOwner| async move {
+        let query = format!("query {{ position(owner: \"{owner}\", marketId: 0) {{ yesShares {{ tokens }} }} }}");
+        h.alice.graphql_query(h.app_id, query).await.response["position"].clone()
+    };
+    let vault = AccountOwner::from(caller_id);
+    assert_eq!(position(vault).await["yesShares"]["tokens"], "10");
+    assert_eq!(position(AccountOwner::from(h.bob.public_key())).await, Value::Null);
+
+    let query = "query { activity(marketId: 0) { actor kind } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["activity"][0]["actor"], vault.to_string());
+    assert_eq!(response["activity"][0]["kind"], "BuyShares");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unauthenticated_operations_are_rejected_unless_upkeep() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Anonymous?", tokens("100"), 3600).await;
+    let module_id = h.alice.publish_bytecode_files_in::<CallerAbi, CallerParameters, ()>("caller").await;
+    let caller_id = h.alice.create_application(module_id, h.app_id, (), vec![h.app_id.forget_abi()]).await;
+    let forward = |operation: Operation| async move {
+        let certificate = h.bob
+            .add_block(|block| {
+                block.with_timestamp(h.now()).with_operation(caller_id, CallerOperation::CallUnauthenticated(operation));
+            })
+            .await;
+        let result = &certificate.inner().block().body.operation_results[0];
+        bcs::from_bytes::<String>(&result.0).unwrap()
+    };
+
+    let buy = Operation::BuyShares {
+        market_id: 0, is_yes: true, shares: tokens("10"), max_cost: tokens("12.5"), deadline: None, max_price_impact_bps: None,
+    };
+    assert_eq!(forward(buy).await, "ERROR: Operation must be authenticated: no signer or calling application");
+    assert_eq!(h.market(0).await["totalYesShares"], "0.");
+
+    // Upkeep runs anonymously and is recorded against the chain
+    assert_eq!(forward(Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:0:0");
+    let query = "query { activity { actor kind } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["activity"][0]["kind"], "FinalizeExpired");
+    assert_eq!(response["activity"][0]["actor"], AccountOwner::CHAIN.to_string());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shares_trade_and_render_in_configured_units() {
+    let h = &Harness::with_config(|c| c.share_decimals = Some(2)).await;
+    h.create_market(&h.alice, "Cents?", tokens("100"), 3600).await;
+    assert_eq!(
+        h.buy(&h.bob, 0, true, tokens("1.005"), tokens("10")).await,
+        "ERROR: 1.005 shares is not a whole number of share units at 2 decimal places"
+    );
+    assert!(h.buy(&h.bob, 0, true, tokens("10.25"), tokens("20")).await.starts_with("SharesPurchased:"));
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ config {{ shareDecimals }} position(owner: \"{bob}\", marketId: 0) {{ yesShares {{ units tokens attos }} }} \
+            market(id: 0) {{ totalYesShares {{ units }} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["config"]["shareDecimals"], 2);
+    let shares = &response["position"]["yesShares"];
+    assert_eq!(shares["units"], "1025");
+    assert_eq!(shares["tokens"], "10.25");
+    assert_eq!(shares["attos"], u128::from(tokens("10.25")).to_string());
+    assert_eq!(response["market"]["totalYesShares"]["units"], "1025");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn buys_report_and_can_cap_their_price_impact() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Impact?", tokens("100"), 3600).await;
+    let query = "query { market(id: 0) { \
+        large: buyQuote(isYes: true, shares: \"10\") { priceImpactBps } \
+        tiny: buyQuote(isYes: true, shares: \"0.000000000000000001\") { priceImpactBps } } }";
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    // 10 of the pool's 50 shares average 12.5 against a marginal price of 1
+    assert_eq!(market["large"]["priceImpactBps"], 2500);
+    assert_eq!(market["tiny"]["priceImpactBps"], 1);
+
+    let buy = |max_price_impact_bps| Operation::BuyShares {
+        market_id: 0, is_yes: true, shares: tokens("10"), max_cost: tokens("20"), deadline: None, max_price_impact_bps,
+    };
+    assert_eq!(
+        h.execute(&h.bob, buy(Some(2499))).await,
+        "ERROR: Price impact of 2500 bps exceeds the maximum of 2499 bps"
+    );
+    let response = h.execute(&h.bob, buy(Some(2500))).await;
+    assert!(response.starts_with("SharesPurchased:12.5:") && response.ends_with(":2500"), "{response}");
+
+    let query = "query { tradesConnection(marketId: 0) { edges { node { priceImpactBps } } } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["tradesConnection"]["edges"][0]["node"]["priceImpactBps"], 2500);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn buys_may_not_move_probability_past_the_configured_cap() {
+    let h = &Harness::with_config(|c| c.max_probability_move_bps = Some(1500)).await;
+    h.create_market(&h.alice, "Global cap?", tokens("100"), 3600).await;
+    let create = |max_probability_move_bps| Operation::CreateMarket {
+        question: "Own cap?".to_string(),
+        categories: vec!["test".to_string()],
+        end_time: h.now().saturating_add(TimeDelta::from_secs(3600)),
+        initial_liquidity: tokens("100"),
+        early_resolution_allowed: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        description: None,
+        resolution_source: None,
+        parent: None,
+        resolvers: vec![],
+        threshold: 0,
+        max_probability_move_bps,
+        max_trade_shares: None,
+    };
+    let invalid = "ERROR: A market's probability move cap must be between 1 and 1500 bps";
+    assert_eq!(h.execute(&h.alice, create(Some(2000))).await, invalid);
+    assert_eq!(h.execute(&h.alice, create(Some(0))).await, invalid);
+    assert_eq!(created_id(&h.execute(&h.alice, create(Some(500))).await), 1);
+
+    // 10 YES from 50/50 pools moves YES from 50% to about 60.98%
+    assert_eq!(
+        h.buy(&h.bob, 1, true, tokens("10"), tokens("20")).await,
+        "ERROR: Buy would move market 1's probability by 1098 bps; at most 500 bps are allowed"
+    );
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert_eq!(
+        h.buy(&h.bob, 0, true, tokens("20"), tokens("100")).await,
+        "ERROR: Buy would move market 0's probability by 2524 bps; at most 1500 bps are allowed"
+    );
+    // Split into smaller buys the same exposure goes through
+    assert!(h.buy(&h.bob, 1, true, tokens("4"), tokens("20")).await.starts_with("SharesPurchased:"));
+
+    let query = "query { config { maxProbabilityMoveBps } \
+        first: market(id: 0) { maxProbabilityMoveBps probabilityMoveLimitBps } \
+        second: market(id: 1) { maxProbabilityMoveBps probabilityMoveLimitBps } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["config"]["maxProbabilityMoveBps"], 1500);
+    assert_eq!(response["first"]["maxProbabilityMoveBps"], Value::Null);
+    assert_eq!(response["first"]["probabilityMoveLimitBps"], 1500);
+    assert_eq!(response["second"]["probabilityMoveLimitBps"], 500);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markets_may_cap_the_size_of_any_single_trade() {
+    let h = Harness::with_config(|c| c.min_trade_shares = tokens("1")).await;
+    let create = |cap: &str| {
+        OperationBuilder::create_market("Thin?", h.now().saturating_add(TimeDelta::from_secs(3600)), tokens("100"))
+            .category("test")
+            .max_trade_shares(tokens(cap))
+            .build()
+    };
+    let invalid = "ERROR: A market's trade size cap must be greater than zero and at least the minimum trade of 1.";
+    assert_eq!(h.execute(&h.alice, create("0")).await, invalid);
+    assert_eq!(h.execute(&h.alice, create("0.5")).await, invalid);
+    assert_eq!(created_id(&h.execute(&h.alice, create("10")).await), 0);
+
+    let too_large = |shares: &str| format!("ERROR: Trade of {shares} shares exceeds market 0's cap of 10. per trade");
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("11"), tokens("100")).await, too_large("11."));
+    assert_eq!(h.buy(&h.bob, 0, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert!(h.buy(&h.bob, 0, true, tokens("10"), tokens("100")).await.starts_with("SharesPurchased:"));
+
+    // Sells and each leg of a straddle are held to the same cap
+    let sell = Operation::SellShares { market_id: 0, is_yes: true, shares: tokens("15"), min_proceeds: Amount::ZERO, deadline: None };
+    assert_eq!(h.trade(&h.bob, sell).await, too_large("15."));
+    let straddle = Operation::BuyStraddle {
+        market_id: 0,
+        yes_shares: tokens("2"),
+        no_shares: tokens("12"),
+        max_total_cost: tokens("100"),
+    };
+    assert_eq!(h.execute(&h.bob, straddle).await, too_large("12."));
+
+    let query = "query { market(id: 0) { maxTradeShares { tokens } \
+        within: buyQuote(isYes: false, shares: \"10\") { withinTradeCap maxTradeShares { tokens } } \
+        beyond: buyQuote(isYes: false, shares: \"10.5\") { withinTradeCap } } }";
+    let market = h.alice.graphql_query(h.app_id, query).await.response["market"].clone();
+    assert_eq!(market["maxTradeShares"]["tokens"], "10");
+    assert_eq!(market["within"]["withinTradeCap"], true);
+    assert_eq!(market["within"]["maxTradeShares"]["tokens"], "10");
+    assert_eq!(market["beyond"]["withinTradeCap"], false);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolvers_may_cite_evidence_once() {
+    let h = &Harness::new().await;
+    h.create_market(&h.alice, "Cited?", tokens("100"), 3600).await;
+    h.create_market(&h.alice, "Uncited?", tokens("100"), 3600).await;
+    h.advance(3601);
+    let resolve = |market_id, evidence: &str| Operation::ResolveMarket {
+        market_id, outcome: true, evidence: Some(evidence.to_string()),
+    };
+    assert_eq!(h.execute(&h.alice, resolve(0, &"x".repeat(1025))).await, "ERROR: evidence exceeds 1024 bytes");
+    assert!(h.execute(&h.alice, resolve(0, "https://example.com/result")).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&h.alice, resolve(0, "changed my mind")).await, "ERROR: Market 0 is already resolved");
+    // Blank evidence is the same as none
+    assert!(h.execute(&h.alice, resolve(1, "   ")).await.starts_with("MarketResolved:"));
+
+    let query = "query { cited: market(id: 0) { resolutionEvidence } uncited: market(id: 1) { resolutionEvidence } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["cited"]["resolutionEvidence"], "https://example.com/result");
+    assert_eq!(response["uncited"]["resolutionEvidence"], Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_overrides_outcomes_until_the_first_claim() {
+    let h = &Harness::new().await;
+    let window = Config::default().claim_window_secs;
+    let mut carol = h.alice.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+
+    let market_id = h.create_market(&h.bob, "Overridden?", tokens("100"), 3600).await;
+    let open_id = h.create_market(&h.bob, "Still open?", tokens("100"), 7200).await;
+    let response = h.buy(&h.alice, market_id, true, tokens("5"), tokens("10")).await;
+    assert!(response.starts_with("SharesPurchased:"), "{response}");
+    let response = h.buy(&carol, market_id, false, tokens("5"), tokens("10")).await;
+    assert!(response.starts_with("SharesPurchased:"), "{response}");
+
+    let over = |market_id, outcome, evidence: Option<&str>| Operation::OverrideResolution {
+        market_id, outcome, evidence: evidence.map(str::to_string),
+    };
+    let blocked = |reason: &str| format!("ERROR: The outcome of market {market_id} cannot be overridden: {reason}");
+    assert_eq!(
+        h.execute(&h.alice, over(open_id, true, None)).await,
+        format!("ERROR: The outcome of market {open_id} cannot be overridden: it is not resolved")
+    );
+
+    h.advance(3601);
+    assert!(h.execute(&h.bob, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    let response = h.execute(&h.bob, over(market_id, false, None)).await;
+    assert_eq!(response, "ERROR: Not authorized: only the admin can override a resolution");
+    assert_eq!(h.execute(&h.alice, over(market_id, true, None)).await, blocked("it already resolved that way"));
+
+    // The override restarts the claim window from its own block
+    h.advance(60);
+    let deadline = h.now().saturating_add(TimeDelta::from_secs(window));
+    let response = h.execute(&h.alice, over(market_id, false, Some("https://example.com/correction"))).await;
+    assert_eq!(response, "ResolutionOverridden");
+    let query = format!(
+        "query {{ market(id: {market_id}) {{ outcome resolvedBy claimDeadline resolutionEvidence claimsStarted }} }}"
+    );
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    assert_eq!(market["outcome"], false);
+    assert_eq!(market["resolvedBy"], AccountOwner::from(h.alice.public_key()).to_string());
+    assert_eq!(market["claimDeadline"], deadline.micros().to_string());
+    assert_eq!(market["resolutionEvidence"], "https://example.com/correction");
+    assert_eq!(market["claimsStarted"], false);
+
+    // The first claim settles the outcome for good
+    let response = h.execute(&carol, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+    let market = h.alice.graphql_query(h.app_id, query.as_str()).await.response["market"].clone();
+    assert_eq!(market["claimsStarted"], true);
+    assert_eq!(h.execute(&h.alice, over(market_id, true, None)).await, blocked("claims have been paid out"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn straddles_buy_both_sides_in_order_or_not_at_all() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Straddled?", tokens("100"), 3600).await;
+    let straddle = |max_total_cost| Operation::BuyStraddle {
+        market_id, yes_shares: tokens("10"), no_shares: tokens("10"), max_total_cost,
+    };
+
+    // The YES leg costs 50 * 10 / 40 = 12.5, leaving pools of 40 and 62.5;
+    // the NO leg then costs 40 * 10 / 52.5, rounded up
+    let response = h.execute(&h.bob, straddle(tokens("20"))).await;
+    assert_eq!(
+        response,
+        "ERROR: Cost 20.119047619047619048 exceeds max_total_cost 20. (yes_cost=12.5, no_cost=7.619047619047619048)"
+    );
+    let market = h.market(market_id).await;
+    assert_eq!(market["totalYesShares"], "0.");
+    assert_eq!(market["yesPool"], "50.");
+
+    let response = h.execute(&h.bob, straddle(tokens("20.2"))).await;
+    assert_eq!(response, "StraddlePurchased:20.119047619047619048:12.5:7.619047619047619048:12500:7619:5243:4756:0.");
+    let market = h.market(market_id).await;
+    assert_eq!(market["totalYesShares"], "10.");
+    assert_eq!(market["totalNoShares"], "10.");
+    assert_eq!(market["yesPool"], "47.619047619047619048");
+    assert_eq!(market["noPool"], "52.5");
+    assert_eq!(market["escrow"], "120.119047619047619048");
+
+    let bob = AccountOwner::from(h.bob.public_key());
+    let query = format!(
+        "query {{ position(owner: \"{bob}\", marketId: {market_id}) {{ \
+            yesShares {{ {AMOUNT} }} noShares {{ {AMOUNT} }} yesCostBasis {{ {AMOUNT} }} noCostBasis {{ {AMOUNT} }} \
+        }} }}"
+    );
+    let position = h.alice.graphql_query(h.app_id, query).await.response["position"].clone();
+    assert_eq!(decode_amount(&position["yesShares"]), tokens("10"));
+    assert_eq!(decode_amount(&position["noShares"]), tokens("10"));
+    assert_eq!(decode_amount(&position["yesCostBasis"]), tokens("12.5"));
+    assert_eq!(decode_amount(&position["noCostBasis"]), tokens("7.619047619047619048"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn listings_and_sweeps_skip_archived_ids() {
+    let h = &Harness::new().await;
+    let window = Config::default().claim_window_secs;
+    let grace = Config::default().resolution_grace_period_secs;
+    let archived = h.create_market(&h.alice, "Archived?", tokens("100"), 3600).await;
+    let kept = h.create_market(&h.alice, "Kept?", tokens("100"), 3600).await;
+
+    h.advance(3601);
+    let resolve = Operation::ResolveMarket { market_id: archived, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    h.advance(window + 1);
+    let response = h.execute(&h.alice, Operation::RecoverUnclaimed { market_id: archived }).await;
+    assert!(response.starts_with("UnclaimedRecovered:"), "{response}");
+    let response = h.execute(&h.alice, Operation::ArchiveMarket { market_id: archived }).await;
+    assert_eq!(response, format!("MarketArchived:{archived}"));
+
+    // IDs keep counting past the gap
+    let later = h.create_market(&h.alice, "Created after?", tokens("100"), 3600).await;
+    assert_eq!(later, 2);
+    let query = "query { marketCount markets { totalCount markets { id } } \
+        marketsConnection(first: 1) { edges { node { id } } pageInfo { hasNextPage } } }";
+    let response = h.alice.graphql_query(h.app_id, query).await.response;
+    assert_eq!(response["marketCount"], 3);
+    assert_eq!(response["markets"]["totalCount"], 2);
+    assert_eq!(response["markets"]["markets"], serde_json::json!([{ "id": kept }, { "id": later }]));
+    assert_eq!(response["marketsConnection"]["edges"][0]["node"]["id"], kept);
+    assert_eq!(response["marketsConnection"]["pageInfo"]["hasNextPage"], true);
+
+    // Each sweep of one market lands on a live one
+    h.advance(3600 + grace + 1);
+    let finalize = || Operation::FinalizeExpired { limit: 1 };
+    assert_eq!(h.execute(&h.bob, finalize()).await, format!("ExpiredFinalized:1:{later}"));
+    assert_eq!(h.execute(&h.bob, finalize()).await, "ExpiredFinalized:1:0");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn featured_markets_follow_the_admins_order() {
+    let h = &Harness::new().await;
+    let first = h.create_market(&h.alice, "First?", tokens("100"), 3600).await;
+    let second = h.create_market(&h.alice, "Second?", tokens("100"), 3600).await;
+    let settled = h.create_market(&h.alice, "Settled?", tokens("100"), 60).await;
+    let feature = |market_id, position| Operation::SetFeatured { market_id, featured: true, position };
+    let unfeature = |market_id| Operation::SetFeatured { market_id, featured: false, position: None };
+    let is_featured = |market_id| async move {
+        let query = format!("query {{ market(id: {market_id}) {{ featured }} }}");
+        h.alice.graphql_query(h.app_id, query).await.response["market"]["featured"].as_bool().unwrap()
+    };
+    let featured = || async move {
+        let query = "query { featuredMarkets { position resolved market { id } } }";
+        let response = h.alice.graphql_query(h.app_id, query).await.response;
+        response["featuredMarkets"].as_array().unwrap().iter()
+            .map(|f| (f["market"]["id"].as_u64().unwrap(), f["resolved"].as_bool().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    let response = h.execute(&h.bob, feature(first, None)).await;
+    assert_eq!(response, "ERROR: Not authorized: only the admin can feature markets");
+    assert!(featured().await.is_empty());
+
+    assert_eq!(h.execute(&h.alice, feature(first, None)).await, format!("MarketFeatured:{first}:0"));
+    assert_eq!(h.execute(&h.alice, feature(second, Some(0))).await, format!("MarketFeatured:{second}:0"));
+    assert_eq!(featured().await, vec![(second, false), (first, false)]);
+    // Re-featuring without a position keeps the market's place
+    assert_eq!(h.execute(&h.alice, feature(second, None)).await, format!("MarketFeatured:{second}:0"));
+
+    // A resolved market can be featured but is flagged
+    h.advance(61);
+    let resolve = Operation::ResolveMarket { market_id: settled, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&h.alice, feature(settled, Some(1))).await, format!("MarketFeatured:{settled}:1:resolved"));
+    assert_eq!(featured().await, vec![(second, false), (settled, true), (first, false)]);
+    assert!(is_featured(settled).await);
+
+    // Unfeaturing takes it off the list straight away
+    assert_eq!(h.execute(&h.alice, unfeature(second)).await, format!("MarketUnfeatured:{second}"));
+    assert_eq!(featured().await, vec![(settled, true), (first, false)]);
+    assert!(!is_featured(second).await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn positions_migrate_to_their_owners_chain_and_back() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Migrate?", tokens("100"), 3600).await;
+    let stays = h.create_market(&h.alice, "Stays?", tokens("100"), 3600).await;
+    // Dave trades on the market chain with the key that owns his own chain
+    let home = &h.validator.new_chain().await;
+    let mut dave = h.alice.clone();
+    dave.set_key_pair(home.key_pair().copy());
+    let owner = AccountOwner::from(home.public_key());
+    assert_eq!(h.buy(&dave, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    assert_eq!(h.buy(&dave, stays, false, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    // Dave's YES shares on the market chain and on his own
+    let held_there = |market_id: u64| async move {
+        let query = format!("query {{ position(owner: \"{owner}\", marketId: {market_id}) {{ yesShares {{ {AMOUNT} }} }} }}");
+        let response = h.alice.graphql_query(h.app_id, query).await.response;
+        response["position"].get("yesShares").map(decode_amount)
+    };
+    let held_home = |market_id: u64| async move {
+        let query = format!("query {{ importedPositions(owner: \"{owner}\") {{ marketId yesShares {{ {AMOUNT} }} }} }}");
+        let response = home.graphql_query(h.app_id, query).await.response;
+        response["importedPositions"].as_array().unwrap().iter()
+            .find(|p| p["marketId"] == market_id)
+            .map(|p| decode_amount(&p["yesShares"]))
+    };
+    let pending = || async move {
+        let response = h.alice.graphql_query(h.app_id, "query { pendingTransfers { transferId } }").await.response;
+        response["pendingTransfers"].as_array().unwrap().len()
+    };
+
+    let to_self = Operation::MigratePosition { market_id, destination: h.alice.id() };
+    assert_eq!(h.execute(&dave, to_self).await, "ERROR: A position cannot migrate to the chain it is on");
+    let migrate = |market_id| Operation::MigratePosition { market_id, destination: home.id() };
+    assert_eq!(h.execute_and_deliver(&dave, migrate(market_id), home, 0).await, "PositionMigrating:0");
+    // It lives on the owner's chain now, and only there
+    assert_eq!(held_there(market_id).await, None);
+    assert_eq!(held_home(market_id).await, Some(tokens("10")));
+    assert_eq!(pending().await, 0);
+
+    // So it cannot be claimed on the market chain
+    h.advance(3601);
+    let resolve = Operation::ResolveMarket { market_id, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    let claim = Operation::ClaimWinnings { market_id };
+    assert_eq!(h.execute(&dave, claim).await, "ERROR: No position found for this market");
+
+    // Until it comes back
+    let back = Operation::ReturnPosition { market_chain: h.alice.id(), market_id };
+    assert_eq!(h.execute_and_deliver(home, back, &dave, 0).await, "PositionReturning:0");
+    assert_eq!(held_home(market_id).await, None);
+    assert_eq!(held_there(market_id).await, Some(tokens("10")));
+    let back = Operation::ReturnPosition { market_chain: h.alice.id(), market_id };
+    let response = h.execute(home, back).await;
+    assert!(response.starts_with("ERROR: No position on market"), "{response}");
+    let response = h.execute(&dave, Operation::ClaimWinnings { market_id }).await;
+    assert!(response.starts_with("WinningsClaimed:"), "{response}");
+
+    // A transfer that arrives too late is refused and credited back
+    let late = POSITION_TRANSFER_TIMEOUT_SECS + 1;
+    assert_eq!(h.execute_and_deliver(&dave, migrate(stays), home, late).await, "PositionMigrating:1");
+    assert_eq!(pending().await, 0);
+    assert_eq!(held_home(stays).await, None);
+    let query = format!("query {{ position(owner: \"{owner}\", marketId: {stays}) {{ noShares {{ {AMOUNT} }} }} }}");
+    let position = h.alice.graphql_query(h.app_id, query).await.response["position"].clone();
+    assert_eq!(decode_amount(&position["noShares"]), tokens("10"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn market_keys_tell_markets_apart_and_find_them() {
+    let h = &Harness::new().await;
+    let (alice, bob) = (AccountOwner::from(h.alice.public_key()), AccountOwner::from(h.bob.public_key()));
+    let create = |question: &str| OperationBuilder::create_market(question, h.now().saturating_add(TimeDelta::from_secs(3600)), tokens("100"))
+        .category("keys")
+        .build();
+    let chain = h.alice.id();
+
+    // Keys follow each creator's own count of markets on the chain
+    let first = market_key(chain, alice, 0);
+    assert_eq!(h.execute(&h.alice, create("First?")).await, format!("MarketCreated:0:{first}"));
+    assert_eq!(h.execute(&h.bob, create("Bob's?")).await, format!("MarketCreated:1:{}", market_key(chain, bob, 0)));
+    let second = market_key(chain, alice, 1);
+    assert_eq!(h.execute(&h.alice, create("Second?")).await, format!("MarketCreated:2:{second}"));
+
+    let by_key = |key: String| async move {
+        let query = format!("query {{ marketByKey(key: \"{key}\") {{ id key question }} }}");
+        h.alice.try_graphql_query(h.app_id, query).await
+    };
+    let market = by_key(second.to_string()).await.unwrap().response["marketByKey"].clone();
+    assert_eq!(market["id"], 2);
+    assert_eq!(market["key"], second.to_string());
+    assert_eq!(market["question"], "Second?");
+    let Err(TryGraphQLQueryError::Service(errors)) = by_key(CryptoHash::from([0; 4]).to_string()).await else {
+        panic!("Expected a GraphQL error");
+    };
+    assert!(errors[0].message.ends_with("not found"), "{}", errors[0].message);
+    let Err(TryGraphQLQueryError::Service(errors)) = by_key("market-0".to_string()).await else {
+        panic!("Expected a GraphQL error");
+    };
+    assert!(errors[0].message.starts_with("Invalid market key \"market-0\""), "{}", errors[0].message);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn selling_more_than_is_held_is_refused_untouched() {
+    let h = Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Overdrawn?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let before = h.market(market_id).await;
+
+    let sell = |is_yes, shares| Operation::SellShares { market_id, is_yes, shares, min_proceeds: Amount::ZERO, deadline: None };
+    let response = h.execute(&h.bob, sell(true, tokens("10.5"))).await;
+    assert_eq!(response, "ERROR: Insufficient balance: the trade needs 10.5 but only 10. is available");
+    let response = h.execute(&h.alice, sell(false, tokens("1"))).await;
+    assert_eq!(response, "ERROR: Insufficient balance: the trade needs 1. but only 0. is available");
+    assert_eq!(h.market(market_id).await, before);
+
+    assert!(h.execute(&h.bob, sell(true, tokens("10"))).await.starts_with("SharesSold:"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spotlight_prefers_a_featured_market_then_draws_by_volume() {
+    let h = &Harness::new().await;
+    let spotlight = |seed: u64| async move {
+        let query = format!("query {{ spotlight(seed: {seed}) {{ id yesPrice }} }}");
+        h.alice.graphql_query(h.app_id, query).await.response["spotlight"].clone()
+    };
+    assert_eq!(spotlight(7).await, Value::Null);
+
+    let quiet = h.create_market(&h.alice, "Quiet?", tokens("100"), 3600).await;
+    let busy = h.create_market(&h.alice, "Busy?", tokens("100"), 3600).await;
+    let ending = h.create_market(&h.alice, "Ending?", tokens("100"), 60).await;
+    assert!(h.buy(&h.bob, busy, true, tokens("20"), tokens("100")).await.starts_with("SharesPurchased:"));
+    // A traded market outweighs an untraded one by its volume in attos
+    for seed in 0..10 {
+        let drawn = spotlight(seed).await;
+        assert_eq!(drawn["id"], busy);
+        assert_eq!(drawn, spotlight(seed).await);
+        assert!(drawn["yesPrice"].as_f64().unwrap() > 0.5);
+    }
+
+    let feature = |market_id, featured| Operation::SetFeatured { market_id, featured, position: None };
+    assert_eq!(h.execute(&h.alice, feature(ending, true)).await, format!("MarketFeatured:{ending}:0"));
+    assert_eq!(h.execute(&h.alice, feature(quiet, true)).await, format!("MarketFeatured:{quiet}:1"));
+    assert_eq!(spotlight(3).await["id"], ending);
+    // A featured market that has ended gives way to the next featured one
+    h.advance(61);
+    assert_eq!(spotlight(3).await["id"], quiet);
+    assert_eq!(h.execute(&h.alice, feature(quiet, false)).await, format!("MarketUnfeatured:{quiet}"));
+    assert_eq!(spotlight(3).await["id"], busy);
+
+    h.advance(3600);
+    assert_eq!(spotlight(3).await, Value::Null);
+}
+
+/// Contract balance and treasury, for the bond conservation checks
+async fn balances(h: &Harness) -> (Amount, Amount) {
+    let query = format!("query {{ stats {{ contractBalance {{ {AMOUNT} }} treasury {{ {AMOUNT} }} }} }}");
+    let stats = h.alice.graphql_query(h.app_id, query.as_str()).await.response["stats"].clone();
+    (decode_amount(&stats["contractBalance"]), decode_amount(&stats["treasury"]))
+}
+
+async fn bond_status(h: &Harness, market_id: u64) -> Value {
+    let query = format!("query {{ creatorBond(marketId: {market_id}) {{ status }} }}");
+    h.alice.graphql_query(h.app_id, query.as_str()).await.response["creatorBond"]["status"].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn creator_bonds_return_after_a_clean_resolution() {
+    let h = Harness::with_config(|c| c.creator_bond = Some(tokens("5"))).await;
+    let (balance, treasury) = balances(&h).await;
+    let market_id = h.create_market(&h.alice, "Bonded?", tokens("100"), 3600).await;
+    assert_eq!(balances(&h).await, (balance.saturating_add(tokens("105")), treasury));
+    assert_eq!(bond_status(&h, market_id).await, "Held");
+
+    let reclaim = || Operation::ReclaimBond { market_id };
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let response = h.execute(&h.alice, reclaim()).await;
+    assert_eq!(response, format!("ERROR: The bond on market {market_id} is held until it resolves and its outcome can no longer be overridden"));
+
+    // Resolved, but the admin may still override until the first claim
+    h.advance(3601);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id, outcome: true, evidence: None }).await.starts_with("MarketResolved:"));
+    assert!(h.execute(&h.alice, reclaim()).await.starts_with("ERROR: The bond on market"));
+    assert!(h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await.starts_with("WinningsClaimed:"));
+
+    let (before, treasury) = balances(&h).await;
+    let response = h.execute(&h.bob, reclaim()).await;
+    assert_eq!(response, "ERROR: Not authorized: only the market's creator can reclaim its bond");
+    assert_eq!(h.execute(&h.alice, reclaim()).await, "BondReturned:5.");
+    assert_eq!(balances(&h).await, (before.saturating_sub(tokens("5")), treasury));
+    assert_eq!(bond_status(&h, market_id).await, "Returned");
+    assert_eq!(h.execute(&h.alice, reclaim()).await, format!("ERROR: The bond on market {market_id} was already Returned"));
+
+    // Without any claims the bond frees up once the claim window closes
+    let quiet = h.create_market(&h.alice, "Quiet?", tokens("100"), 60).await;
+    h.advance(61);
+    assert!(h.execute(&h.alice, Operation::ResolveMarket { market_id: quiet, outcome: false, evidence: None }).await.starts_with("MarketResolved:"));
+    h.advance(30 * 24 * 60 * 60 + 1);
+    assert_eq!(h.execute(&h.alice, Operation::ReclaimBond { market_id: quiet }).await, "BondReturned:5.");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unresolved_markets_forfeit_the_bond_to_the_treasury_and_traders() {
+    let h = Harness::with_config(|c| {
+        c.creator_bond = Some(tokens("10"));
+        c.bond_trader_share_bps = Some(5_000);
+        c.resolution_grace_period_secs = 600;
+        c.admin_resolution_delay_secs = 300;
+    })
+    .await;
+    let mut carol = h.alice.clone();
+    carol.set_key_pair(AccountSecretKey::generate());
+    let market_id = h.create_market(&h.alice, "Abandoned?", tokens("100"), 60).await;
+    assert_eq!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+    let response = h.buy(&carol, market_id, false, tokens("7"), tokens("100")).await;
+    let carol_cost: Amount = response.strip_prefix("SharesPurchased:").unwrap().parse().unwrap();
+    // The creator's own stake earns no part of the bond
+    assert!(h.buy(&h.alice, market_id, false, tokens("3"), tokens("100")).await.starts_with("SharesPurchased:"));
+    let (balance, treasury) = balances(&h).await;
+
+    // The creator never resolves, so the sweep voids the market
+    h.advance(661);
+    assert_eq!(h.execute(&h.bob, Operation::FinalizeExpired { limit: 10 }).await, "ExpiredFinalized:1:0");
+    assert_eq!(bond_status(&h, market_id).await, "Forfeited");
+
+    // Half of the bond goes to the traders by cost basis, rounding down;
+    // the treasury takes the rest and nothing leaves the contract yet
+    let bob = AccountOwner::from(h.bob.public_key());
+    let (carol, alice) = (AccountOwner::from(carol.public_key()), AccountOwner::from(h.alice.public_key()));
+    let query = format!(
+        "query {{ creatorBond(marketId: {market_id}) {{ toTreasury {{ {AMOUNT} }} toTraders {{ {AMOUNT} }} }} \
+            bob: bondShare(owner: \"{bob}\", marketId: {market_id}) {{ {AMOUNT} }} \
+            carol: bondShare(owner: \"{carol}\", marketId: {market_id}) {{ {AMOUNT} }} \
+            alice: bondShare(owner: \"{alice}\", marketId: {market_id}) {{ {AMOUNT} }} }}"
+    );
+    let response = h.alice.graphql_query(h.app_id, query.as_str()).await.response;
+    let stake = u128::from(tokens("12.5")) + u128::from(carol_cost);
+    let bob_share = attos(u128::from(tokens("5")) * u128::from(tokens("12.5")) / stake);
+    let carol_share = attos(u128::from(tokens("5")) * u128::from(carol_cost) / stake);
+    assert_eq!(decode_amount(&response["bob"]), bob_share);
+    assert_eq!(decode_amount(&response["carol"]), carol_share);
+    assert_eq!(decode_amount(&response["alice"]), Amount::ZERO);
+    let to_traders = bob_share.saturating_add(carol_share);
+    let to_treasury = tokens("10").saturating_sub(to_traders);
+    assert_eq!(decode_amount(&response["creatorBond"]["toTraders"]), to_traders);
+    assert_eq!(decode_amount(&response["creatorBond"]["toTreasury"]), to_treasury);
+    assert_eq!(balances(&h).await, (balance, treasury.saturating_add(to_treasury)));
+
+    // Each share is paid with the trader's refund
+    let paid = tokens("12.5").saturating_add(bob_share);
+    assert_eq!(h.execute(&h.bob, Operation::ClaimWinnings { market_id }).await, format!("RefundClaimed:{paid}"));
+    assert_eq!(balances(&h).await, (balance.saturating_sub(paid), treasury.saturating_add(to_treasury)));
+    let response = h.execute(&h.alice, Operation::ReclaimBond { market_id }).await;
+    assert_eq!(response, format!("ERROR: The bond on market {market_id} was already Forfeited"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_voids_forfeit_the_bond_and_cancelling_an_untouched_market_returns_it() {
+    let h = Harness::with_config(|c| c.creator_bond = Some(tokens("5"))).await;
+    let ambiguous = h.create_market(&h.alice, "Ambiguous?", tokens("100"), 3600).await;
+    assert_eq!(h.buy(&h.bob, ambiguous, true, tokens("10"), tokens("12.5")).await, "SharesPurchased:12.5");
+
+    // Without a trader share the whole bond goes to the treasury
+    let void = || Operation::VoidMarket { market_id: ambiguous };
+    assert_eq!(h.execute(&h.bob, void()).await, "ERROR: Not authorized: only the admin can void a market");
+    let (balance, treasury) = balances(&h).await;
+    assert_eq!(h.execute(&h.alice, void()).await, "MarketVoided");
+    assert_eq!(balances(&h).await, (balance, treasury.saturating_add(tokens("5"))));
+    assert_eq!(bond_status(&h, ambiguous).await, "Forfeited");
+    assert_eq!(h.execute(&h.alice, void()).await, format!("ERROR: Market {ambiguous} is already resolved"));
+    assert_eq!(h.execute(&h.bob, Operation::ClaimWinnings { market_id: ambiguous }).await, "RefundClaimed:12.5");
+
+    // Any trade blocks cancellation
+    let traded = h.create_market(&h.alice, "Traded?", tokens("100"), 3600).await;
+    assert!(h.buy(&h.bob, traded, false, tokens("1"), tokens("10")).await.starts_with("SharesPurchased:"));
+    let response = h.execute(&h.alice, Operation::CancelMarket { market_id: traded }).await;
+    assert_eq!(response, format!("ERROR: Market {traded} cannot be cancelled: it has been traded"));
+
+    let untouched = h.create_market(&h.alice, "Untouched?", tokens("100"), 3600).await;
+    let cancel = || Operation::CancelMarket { market_id: untouched };
+    assert_eq!(h.execute(&h.bob, cancel()).await, "ERROR: Not authorized: only the creator can cancel a market");
+    let (balance, treasury) = balances(&h).await;
+    assert_eq!(h.execute(&h.alice, cancel()).await, "MarketCancelled:100.:5.");
+    assert_eq!(balances(&h).await, (balance.saturating_sub(tokens("105")), treasury));
+    assert_eq!(bond_status(&h, untouched).await, "Returned");
+    assert_eq!(h.execute(&h.alice, cancel()).await, format!("ERROR: Market {untouched} is already resolved"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upvotes_count_each_account_once_and_sort_markets() {
+    let h = &Harness::new().await;
+    let quiet = h.create_market(&h.alice, "Quiet?", tokens("100"), 3600).await;
+    let liked = h.create_market(&h.alice, "Liked?", tokens("100"), 3600).await;
+    let settled = h.create_market(&h.alice, "Settled?", tokens("100"), 60).await;
+    let upvotes = |market_id| async move {
+        let query = format!("query {{ market(id: {market_id}) {{ upvotes }} }}");
+        h.alice.graphql_query(h.app_id, query).await.response["market"]["upvotes"].as_u64().unwrap()
+    };
+    let by_upvotes = || async move {
+        let query = "query { markets(sort: UPVOTES_DESC) { markets { id } } }";
+        let response = h.alice.graphql_query(h.app_id, query).await.response;
+        response["markets"]["markets"].as_array().unwrap().iter()
+            .map(|m| m["id"].as_u64().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(h.execute(&h.bob, Operation::Upvote { market_id: liked }).await, "Upvoted:1");
+    // Upvoting again changes nothing
+    assert_eq!(h.execute(&h.bob, Operation::Upvote { market_id: liked }).await, "Upvoted:1");
+    assert_eq!(h.execute(&h.alice, Operation::Upvote { market_id: liked }).await, "Upvoted:2");
+    assert_eq!(upvotes(liked).await, 2);
+    assert_eq!(upvotes(quiet).await, 0);
+    assert_eq!(by_upvotes().await, [liked, quiet, settled]);
+
+    // Resolved markets take upvotes but stay out of trending
+    h.advance(61);
+    let resolve = Operation::ResolveMarket { market_id: settled, outcome: true, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+    assert_eq!(h.execute(&h.bob, Operation::Upvote { market_id: settled }).await, "Upvoted:1");
+    let response = h.alice.graphql_query(h.app_id, "query { trending { id } }").await.response;
+    assert!(response["trending"].as_array().unwrap().iter().all(|m| m["id"] != settled));
+
+    assert_eq!(h.execute(&h.bob, Operation::RemoveUpvote { market_id: liked }).await, "UpvoteRemoved:1");
+    assert_eq!(h.execute(&h.bob, Operation::RemoveUpvote { market_id: liked }).await, "UpvoteRemoved:1");
+    assert_eq!(h.execute(&h.bob, Operation::RemoveUpvote { market_id: quiet }).await, "UpvoteRemoved:0");
+    // Ties fall back to id order
+    assert_eq!(by_upvotes().await, [liked, settled, quiet]);
+    assert_eq!(h.execute(&h.bob, Operation::Upvote { market_id: 99 }).await, "ERROR: Market 99 not found");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fee_tiers_discount_traders_from_their_exact_volume() {
+    let h = &Harness::with_config(|c| {
+        c.fee_bps = 100;
+        c.fee_tiers = vec![
+            FeeTier { min_volume: tokens("12.5"), discount_bps: 5_000 },
+            FeeTier { min_volume: tokens("1000"), discount_bps: 8_000 },
+        ];
+    })
+    .await;
+    let market_id = h.create_market(&h.alice, "Tiered?", tokens("100"), 3600).await;
+    let buy = |is_yes| Operation::BuyShares {
+        market_id, is_yes, shares: tokens("10"), max_cost: tokens("100"), deadline: None, max_price_impact_bps: None,
+    };
+    // The fee and its rate close every trade response
+    let fee = |response: String| {
+        let fields: Vec<&str> = response.split(':').collect();
+        (fields[fields.len() - 2].to_string(), fields[fields.len() - 1].to_string())
+    };
+    let fee_of_buy = |response: String| {
+        let fields: Vec<&str> = response.split(':').collect();
+        (fields[7].to_string(), fields[8].to_string())
+    };
+
+    // Bob's first buy pays the full 1% on 12.5, bringing his volume to
+    // exactly the first tier
+    let response = h.execute(&h.bob, buy(true)).await;
+    assert!(response.starts_with("SharesPurchased:12.625:"), "{response}");
+    assert_eq!(fee_of_buy(response), ("0.125".to_string(), "100".to_string()));
+
+    // From there the rate is halved for his buys and sales alike
+    let response = h.execute(&h.bob, buy(false)).await;
+    assert_eq!(fee_of_buy(response).1, "50");
+    let sell = Operation::SellShares { market_id, is_yes: true, shares: tokens("5"), min_proceeds: Amount::ZERO, deadline: None };
+    let response = h.execute(&h.bob, sell).await;
+    assert!(response.starts_with("SharesSold:"), "{response}");
+    assert_eq!(fee(response).1, "50");
+
+    // Alice has not traded, so she pays the full rate
+    let response = h.execute(&h.alice, buy(true)).await;
+    assert_eq!(fee_of_buy(response).1, "100");
+
+    let query = format!("query {{ config {{ feeTiers {{ minVolume {{ {AMOUNT} }} discountBps }} }} }}");
+    let tiers = h.alice.graphql_query(h.app_id, query).await.response["config"]["feeTiers"].clone();
+    assert_eq!(tiers.as_array().unwrap().len(), 2);
+    assert_eq!(tiers[0]["discountBps"], 5_000);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn estimates_match_what_the_contract_then_charges() {
+    let h = &Harness::with_config(|c| c.fee_bps = 100).await;
+    let market_id = h.create_market(&h.alice, "Estimated?", tokens("100"), 3600).await;
+    let bob = AccountOwner::from(h.bob.public_key());
+    let estimate = |input: String| async move {
+        let query = format!(
+            "query {{ estimate(owner: \"{bob}\", input: {{ {input} }}) {{ \
+                reason shares {{ tokens }} poolCost {{ tokens }} fee {{ tokens }} feeBps lpFee {{ tokens }} \
+                total {{ tokens }} priceImpactBps yesPriceBps yesSharesAfter {{ tokens }} \
+            }} }}"
+        );
+        h.alice.graphql_query(h.app_id, query).await.response["estimate"].clone()
+    };
+
+    // 12.5 into the pools plus the 1% fee buys 10 YES
+    let buy = estimate(format!("buy: {{ marketId: {market_id}, isYes: true, shares: \"10\" }}")).await;
+    assert_eq!(buy["reason"], Value::Null);
+    assert_eq!(buy["poolCost"]["tokens"], "12.5");
+    assert_eq!(buy["fee"]["tokens"], "0.125");
+    assert_eq!(buy["feeBps"], 100);
+    assert_eq!(buy["lpFee"], buy["fee"]);
+    assert_eq!(buy["total"]["tokens"], "12.625");
+    assert_eq!(buy["yesSharesAfter"]["tokens"], "10");
+    // The same budget, fee included, buys the same 10 shares
+    let for_cost = estimate(format!("buyForCost: {{ marketId: {market_id}, isYes: true, budget: \"12.625\" }}")).await;
+    assert_eq!(for_cost["shares"]["tokens"], "10");
+    assert_eq!(for_cost["total"], buy["total"]);
+
+    let response = h.execute(&h.bob, Operation::BuyShares {
+        market_id, is_yes: true, shares: tokens("10"), max_cost: tokens("13"), deadline: None, max_price_impact_bps: None,
+    }).await;
+    assert!(response.starts_with(&format!("SharesPurchased:12.625:10.:{}:", buy["yesPriceBps"])), "{response}");
+
+    let sell = estimate(format!("sell: {{ marketId: {market_id}, isYes: true, shares: \"4\" }}")).await;
+    assert_eq!(sell["yesSharesAfter"]["tokens"], "6");
+    let operation = Operation::SellShares { market_id, is_yes: true, shares: tokens("4"), min_proceeds: Amount::ZERO, deadline: None };
+    let response = h.execute(&h.bob, operation).await;
+    let proceeds = response.strip_prefix("SharesSold:").unwrap().split(':').next().unwrap();
+    assert_eq!(proceeds.trim_end_matches('.'), sell["total"]["tokens"].as_str().unwrap());
+
+    // Refusals carry the contract's reason and no figures
+    let oversold = estimate(format!("sell: {{ marketId: {market_id}, isYes: true, shares: \"7\" }}")).await;
+    assert!(oversold["reason"].as_str().unwrap().starts_with("Insufficient balance"), "{oversold}");
+    assert_eq!(oversold["total"], Value::Null);
+    let missing = estimate("buy: { marketId: 9, isYes: true, shares: \"1\" }".to_string()).await;
+    assert_eq!(missing["reason"], "Market 9 not found");
+    let claim = estimate(format!("claim: {{ marketId: {market_id} }}")).await;
+    assert_eq!(claim["reason"], "not resolved");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolve_many_applies_what_it_may_and_reports_the_rest() {
+    let h = &Harness::new().await;
+    let first = h.create_market(&h.alice, "First?", tokens("100"), 60).await;
+    let settled = h.create_market(&h.alice, "Settled?", tokens("100"), 60).await;
+    let bobs = h.create_market(&h.bob, "Bob's?", tokens("100"), 60).await;
+    assert!(h.buy(&h.bob, first, true, tokens("10"), tokens("12.5")).await.starts_with("SharesPurchased:"));
+    h.advance(61);
+    let resolve = Operation::ResolveMarket { market_id: settled, outcome: false, evidence: None };
+    assert!(h.execute(&h.alice, resolve).await.starts_with("MarketResolved:"));
+
+    let resolution = |market_id, outcome| Resolution { market_id, outcome };
+    let resolutions = vec![resolution(first, true), resolution(settled, true), resolution(bobs, true), resolution(99, false)];
+    let response = h.execute(&h.alice, Operation::ResolveMany { resolutions }).await;
+    let Ok(OperationResponse::MarketsResolved(results)) = response.parse() else { panic!("{response}") };
+    let ids: Vec<u64> = results.iter().map(|(market_id, _)| *market_id).collect();
+    assert_eq!(ids, [first, settled, bobs, 99]);
+    assert!(matches!(&results[0].1, OperationResponse::MarketResolved(settlement) if settlement.outcome), "{response}");
+    assert_eq!(results[1].1, OperationResponse::Error(format!("Market {settled} is already resolved")));
+    // The admin may not resolve another creator's market before the delay
+    assert!(matches!(&results[2].1, OperationResponse::Error(message) if message.contains(&bobs.to_string())), "{response}");
+    assert_eq!(results[3].1, OperationResponse::Error("Market 99 not found".to_string()));
+
+    assert_eq!(h.market(first).await["outcome"], true);
+    assert_eq!(h.market(bobs).await["resolved"], false);
+    // The earlier outcome stands
+    assert_eq!(h.market(settled).await["outcome"], false);
+
+    let response = h.execute(&h.alice, Operation::ResolveMany { resolutions: Vec::new() }).await;
+    assert_eq!(response, "ERROR: ResolveMany takes between 1 and 50 resolutions; got 0");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json_fields_return_what_the_graphql_queries_do() {
+    let h = &Harness::new().await;
+    let market_id = h.create_market(&h.alice, "Plain?", tokens("100"), 3600).await;
+    h.create_market(&h.alice, "Second?", tokens("40"), 3600).await;
+    assert!(h.buy(&h.bob, market_id, true, tokens("10"), tokens("12.5")).await.starts_with("SharesPurchased:"));
+    let bob = AccountOwner::from(h.bob.public_key()).to_string();
+
+    let graphql = |query: String| async move { h.alice.graphql_query(h.app_id, query).await.response };
+    // Each JSON field is a string holding either `data` or `error`
+    let json = |field: String| async move {
+        let response = h.alice.graphql_query(h.app_id, format!("query {{ {field} }}")).await.response;
+        let name = field.split('(').next().unwrap();
+        serde_json::from_str::<Value>(response[name].as_str().unwrap()).unwrap()
+    };
+
+    let market = format!(
+        "id key question description categories creator endTimeMicros createdAtMicros status resolved outcome voided \
+            yesPriceBps noPriceBps yesPool {{ {AMOUNT} }} noPool {{ {AMOUNT} }} volume {{ {AMOUNT} }} \
+            liquidity {{ {AMOUNT} }} feeBps"
+    );
+    let expected = graphql(format!("query {{ market(id: {market_id}) {{ {market} }} }}")).await;
+    assert_eq!(json(format!("marketJson(id: {market_id})")).await, serde_json::json!({ "data": expected["market"] }));
+    let expected = graphql(format!("query {{ markets(limit: 1, offset: 1) {{ totalCount markets {{ {market} }} }} }}")).await;
+    let page = json("marketsJson(limit: 1, offset: 1)".to_string()).await;
+    assert_eq!(page, serde_json::json!({ "data": expected["markets"] }));
+    assert_eq!(page["data"]["totalCount"], 2);
+    assert_eq!(page["data"]["markets"][0]["question"], "Second?");
+
+    let position = json(format!("positionJson(owner: \"{bob}\", marketId: {market_id})")).await;
+    let expected = graphql(format!(
+        "query {{ position(owner: \"{bob}\", marketId: {market_id}) {{ marketId owner yesShares {{ {AMOUNT} }} \
+            noShares {{ {AMOUNT} }} claimed currentValue {{ {AMOUNT} }} realizedPnl {{ {AMOUNT} }} unrealizedPnl {{ {AMOUNT} }} }} }}"
+    )).await;
+    assert_eq!(position, serde_json::json!({ "data": expected["position"] }));
+    assert_eq!(decode_amount(&position["data"]["yesShares"]), tokens("10"));
+    let alice = AccountOwner::from(h.alice.public_key()).to_string();
+    let none = json(format!("positionJson(owner: \"{alice}\", marketId: {market_id})")).await;
+    assert_eq!(none, serde_json::json!({ "data": null }));
+
+    let buy = json(format!("quoteJson(marketId: {market_id}, isYes: true, shares: \"5\")")).await;
+    let expected = graphql(format!(
+        "query {{ market(id: {market_id}) {{ buyQuote(isYes: true, shares: \"5\") {{ cost {{ {AMOUNT} }} fee {{ {AMOUNT} }} \
+            feeBps priceImpactBps withinTradeCap }} }} }}"
+    )).await;
+    assert_eq!(buy, serde_json::json!({ "data": expected["market"]["buyQuote"] }));
+    let sell = json(format!("quoteJson(marketId: {market_id}, isYes: true, shares: \"5\", sell: true)")).await;
+    let expected = graphql(format!(
+        "query {{ proceedsFromSell(marketId: {market_id}, isYes: true, shares: \"5\") {{ proceeds {{ {AMOUNT} }} \
+            fee {{ {AMOUNT} }} feeBps yesPriceBps noPriceBps priceImpactBps maxSellableShares {{ {AMOUNT} }} }} }}"
+    )).await;
+    assert_eq!(sell, serde_json::json!({ "data": expected["proceedsFromSell"] }));
+    assert!(sell["data"]["proceeds"]["attos"].is_string(), "{sell}");
+
+    // Errors come back as JSON with the code GraphQL reports them under
+    let missing = json("marketJson(id: 9)".to_string()).await;
+    assert_eq!(missing, serde_json::json!({ "error": { "code": "NOT_FOUND", "message": "Market 9 not found" } }));
+    let missing = json("quoteJson(marketId: 9, isYes: true, shares: \"1\", sell: true)".to_string()).await;
+    assert_eq!(missing["error"]["code"], "NOT_FOUND");
+    let invalid = json(format!("positionJson(owner: \"nobody\", marketId: {market_id})")).await;
+    assert_eq!(invalid["error"]["code"], "INVALID_ACCOUNT");
+    assert!(invalid["error"]["message"].as_str().unwrap().starts_with("Invalid account format"), "{invalid}");
+
+    // The same query can mix both modes, and a bad JSON field fails only
+    // itself
+    let mixed = graphql(format!("query {{ market(id: {market_id}) {{ question }} marketJson(id: 9) }}")).await;
+    assert_eq!(mixed["market"]["question"], "Plain?");
+    assert!(mixed["marketJson"].as_str().unwrap().contains("NOT_FOUND"));
+}
@@ -3,168 +3,177 @@
 
 #![cfg_attr(target_arch = "wasm32", no_main)]
 
-use std::sync::Arc;
-use async_graphql::{EmptySubscription, Object, Request, Response, Schema, SimpleObject};
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap}, future::Future, pin::Pin, str::FromStr, sync::{Arc, OnceLock}};
+use async_graphql::{
+    connection::{Connection, Edge},
+    parser::{parse_query, types::{OperationType, Selection, SelectionSet}},
+    ComplexObject, Context, EmptySubscription, Enum, ErrorExtensions, InputObject, InputValueError, InputValueResult,
+    MergedObject, Object, OneofObject, Pos, Request, Response, Scalar, ScalarType, Schema, SimpleObject, Value, Variables,
+};
 use linera_sdk::{
-    linera_base_types::{AccountOwner, Amount, Timestamp, WithServiceAbi},
-    views::{linera_views, MapView, RegisterView, RootView, View, ViewStorageContext},
-    graphql::GraphQLMutationRoot as _,
-    Service, ServiceRuntime,
+    bcs,
+    linera_base_types::{AccountOwner, Amount, CryptoHash, TimeDelta, Timestamp, WithServiceAbi},
+    views::{MapView, View, ViewError},
+    graphql::GraphQLMutationRoot,
+    serde_json, Service, ServiceRuntime,
+};
+use serde::Serialize;
+// State layout is shared with the contract so the two can never drift apart
+use chronos_market::{
+    math::{
+        cost_to_buy, max_sell_shares, pool_at_price, price_impact_bps, price_of, probability_move_bps, safe_mul_div, sell_price_impact_bps,
+        sell_proceeds, share_unit_attos, shares_for_cost, PRICE_SCALE,
+    },
+    check_open, day_index, effective_status, hour_index, utc_civil_date, ActivityEntry, ArchivedMarket, Combo, Config, CreatorBond, EffectiveStatus, FeeRamp, FeedItem, IncentiveProgram, LimitOrder, LpFlow, LpLedger, LpPosition, Market, MarketCondition,
+    MarketError, MarketGroup, MarketKey, MarketSummary, MarketParameters, MarketSeries, MarketState, Operation, OrderSide, ParlayLeg, Position, PositionTransfer, RemoteMarket, Resolution,
+    RecentTrade, ResolutionVote, TraderStats, TradingAgent, TriggerDirection, TriggerOrder, TriggerStatus, DAY_MICROS, MAX_PARLAY_LEGS, MAX_RECENT_TRADES_CAPACITY, MAX_RESOLUTIONS,
 };
-use serde::{Deserialize, Serialize};
-use chronos_market::{AgentStrategy, OrderSide, OrderDuration, FeedItemType, Operation};
 
 linera_sdk::service!(MarketService);
 
-// ============ STATE STRUCTURES ============
-
-#[derive(RootView)]
-#[view(context = ViewStorageContext)]
-pub struct MarketState {
-    pub markets: MapView<u64, Market>,
-    pub positions: MapView<(AccountOwner, u64), Position>,
-    pub next_market_id: RegisterView<u64>,
-    pub total_volume: RegisterView<Amount>,
-    pub limit_orders: MapView<u64, LimitOrder>,
-    pub next_order_id: RegisterView<u64>,
-    pub order_book: MapView<(u64, bool), Vec<u64>>,  // Must match contract state layout
-    pub combos: MapView<u64, Combo>,
-    pub next_combo_id: RegisterView<u64>,
-    pub user_combos: MapView<AccountOwner, Vec<u64>>,  // Must match contract state layout
-    pub agents: MapView<u64, TradingAgent>,
-    pub next_agent_id: RegisterView<u64>,
-    pub agent_followers: MapView<(u64, AccountOwner), AgentFollower>,
-    pub feed_items: MapView<u64, FeedItem>,
-    pub next_feed_id: RegisterView<u64>,
-    pub user_followers: MapView<AccountOwner, Vec<AccountOwner>>,
-    pub user_following: MapView<AccountOwner, Vec<AccountOwner>>,
-    pub item_likes: MapView<u64, Vec<AccountOwner>>,  // Must match contract state layout
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Market {
-    pub id: u64,
-    pub creator: AccountOwner,
-    pub question: String,
-    pub categories: Vec<String>,
-    pub end_time: Timestamp,
-    pub created_at: Timestamp,
-    pub yes_pool: Amount,
-    pub no_pool: Amount,
-    pub total_yes_shares: Amount,
-    pub total_no_shares: Amount,
-    pub resolved: bool,
-    pub outcome: Option<bool>,
-    pub volume: Amount,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Position {
-    pub market_id: u64,
-    pub owner: AccountOwner,
-    pub yes_shares: Amount,
-    pub no_shares: Amount,
-    pub claimed: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LimitOrder {
-    pub id: u64,
-    pub owner: AccountOwner,
-    pub market_id: u64,
-    pub is_yes: bool,
-    pub side: OrderSide,
-    pub price: Amount,
-    pub original_amount: Amount,
-    pub filled_amount: Amount,
-    pub duration: OrderDuration,
-    pub created_at: Timestamp,
-    pub status: OrderStatus,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum OrderStatus {
-    Open,
-    PartiallyFilled,
-    Filled,
-    Cancelled,
-    Expired,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Combo {
-    pub id: u64,
-    pub owner: AccountOwner,
-    pub name: String,
-    pub legs: Vec<ComboLeg>,
-    pub stake: Amount,
-    pub potential_payout: Amount,
-    pub created_at: Timestamp,
-    pub status: ComboStatus,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ComboLeg {
-    pub market_id: u64,
-    pub prediction: bool,
-    pub odds: Amount,
-    pub resolved: bool,
-    pub won: Option<bool>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub enum ComboStatus {
-    Active,
-    Won,
-    Lost,
-    PartiallyResolved,
-    Cancelled,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TradingAgent {
-    pub id: u64,
-    pub owner: AccountOwner,
-    pub name: String,
-    pub strategy: AgentStrategy,
-    pub config: String,
-    pub capital: Amount,
-    pub total_volume: Amount,
-    pub profit_loss: i128,
-    pub win_rate: u32,
-    pub total_trades: u64,
-    pub winning_trades: u64,
-    pub followers_count: u64,
-    pub is_active: bool,
-    pub created_at: Timestamp,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AgentFollower {
-    pub agent_id: u64,
-    pub follower: AccountOwner,
-    pub allocation: Amount,
-    pub copy_trades: bool,
-    pub started_at: Timestamp,
-    pub total_copied_pnl: i128,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FeedItem {
-    pub id: u64,
-    pub author: AccountOwner,
-    pub item_type: FeedItemType,
-    pub market_id: Option<u64>,
-    pub content: String,
-    pub data: String,
-    pub likes_count: u64,
-    pub comments_count: u64,
-    pub created_at: Timestamp,
+// ============ GRAPHQL TYPES ============
+
+/// A native-token amount in both raw and human-readable form.
+/// Mutation inputs take decimal token strings such as "1.5".
+#[derive(Clone, SimpleObject)]
+struct TokenAmount {
+    /// Integer count of the smallest unit (10^-18 tokens)
+    attos: String,
+    /// Decimal token value with trailing zeros trimmed, e.g. "1.5"
+    tokens: AmountScalar,
 }
 
-// ============ GRAPHQL TYPES ============
+impl From<Amount> for TokenAmount {
+    fn from(amount: Amount) -> Self {
+        TokenAmount {
+            attos: u128::from(amount).to_string(),
+            tokens: AmountScalar(amount),
+        }
+    }
+}
 
-#[derive(SimpleObject)]
+/// A share amount in the same two forms as `TokenAmount`, plus whole
+/// share units at the configured `share_decimals`
+#[derive(Clone, SimpleObject)]
+#[graphql(complex)]
+struct ShareAmount {
+    /// Integer count of the smallest unit (10^-18 shares)
+    attos: String,
+    /// Decimal share count with trailing zeros trimmed, e.g. "1.5"
+    tokens: AmountScalar,
+    #[graphql(skip)]
+    amount: Amount,
+}
+
+impl From<Amount> for ShareAmount {
+    fn from(amount: Amount) -> Self {
+        let TokenAmount { attos, tokens } = amount.into();
+        ShareAmount { attos, tokens, amount }
+    }
+}
+
+#[ComplexObject]
+impl ShareAmount {
+    /// Integer count of share units (10^-shareDecimals shares), as the ABI
+    /// crate's `shares_from_units` takes them. Rounded down for amounts
+    /// finer than a unit, held from before `share_decimals` was set.
+    async fn units(&self, ctx: &Context<'_>) -> String {
+        let decimals = ctx.data_unchecked::<Arc<QueryData>>().config.share_decimals();
+        let unit = share_unit_attos(decimals).unwrap_or(1);
+        (u128::from(self.amount) / unit).to_string()
+    }
+}
+
+/// A signed token value in the same two forms as `TokenAmount`, each with a
+/// leading '-' when negative
+#[derive(Clone, SimpleObject)]
+struct SignedTokenAmount {
+    attos: String,
+    tokens: String,
+}
+
+impl SignedTokenAmount {
+    /// `gain` minus `loss`
+    fn difference(gain: Amount, loss: Amount) -> Self {
+        let (sign, magnitude) = if gain >= loss {
+            ("", gain.saturating_sub(loss))
+        } else {
+            ("-", loss.saturating_sub(gain))
+        };
+        let TokenAmount { attos, tokens } = magnitude.into();
+        SignedTokenAmount { attos: format!("{sign}{attos}"), tokens: format!("{sign}{}", tokens.decimal()) }
+    }
+}
+
+impl From<i128> for SignedTokenAmount {
+    fn from(attos: i128) -> Self {
+        let magnitude = Amount::from_attos(attos.unsigned_abs());
+        if attos < 0 {
+            SignedTokenAmount::difference(Amount::ZERO, magnitude)
+        } else {
+            SignedTokenAmount::difference(magnitude, Amount::ZERO)
+        }
+    }
+}
+
+/// A token or share amount, shown to clients as the GraphQL `Amount` scalar
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct AmountScalar(Amount);
+
+impl AmountScalar {
+    /// The amount with trailing zeros and any trailing '.' trimmed
+    fn decimal(&self) -> String {
+        self.0.to_string().trim_end_matches('.').to_string()
+    }
+}
+
+/// A non-negative amount as a decimal string with trailing zeros trimmed,
+/// e.g. "1.5". Inputs may also be JSON numbers such as 1.5; at most 18
+/// decimal places are kept exactly.
+#[Scalar(name = "Amount")]
+impl ScalarType for AmountScalar {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let text = match &value {
+            Value::String(text) => text.trim().to_string(),
+            Value::Number(number) => number.to_string(),
+            _ => return Err(InputValueError::expected_type(value)),
+        };
+        Amount::from_str(&text).map(AmountScalar).map_err(|e| {
+            InputValueError::custom(format!("\"{}\" is not a token amount like \"1.5\" ({})", text, e))
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.decimal())
+    }
+}
+
+/// A point in time, shown to clients as the GraphQL `Timestamp` scalar
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TimestampScalar(Timestamp);
+
+/// Microseconds since the Unix epoch as an integer. Inputs may also be
+/// strings of decimal digits, for values past JSON's safe integer range.
+#[Scalar(name = "Timestamp")]
+impl ScalarType for TimestampScalar {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let micros = match &value {
+            Value::Number(number) => number.as_u64().ok_or_else(|| number.to_string()),
+            Value::String(text) => text.trim().parse::<u64>().map_err(|_| format!("\"{}\"", text)),
+            _ => return Err(InputValueError::expected_type(value)),
+        };
+        micros.map(|micros| TimestampScalar(Timestamp::from(micros))).map_err(|text| {
+            InputValueError::custom(format!("{} is not a whole number of microseconds since the Unix epoch", text))
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Number(self.0.micros().into())
+    }
+}
+
+#[derive(Clone, SimpleObject)]
+#[graphql(complex)]
 struct MarketInfo {
     id: u64,
     creator: String,
@@ -172,391 +181,4097 @@ struct MarketInfo {
     categories: Vec<String>,
     end_time: String,
     created_at: String,
-    yes_pool: String,
-    no_pool: String,
-    total_yes_shares: String,
-    total_no_shares: String,
+    end_time_micros: TimestampScalar,
+    created_at_micros: TimestampScalar,
+    end_time_rfc3339: String,
+    created_at_rfc3339: String,
+    yes_pool: TokenAmount,
+    no_pool: TokenAmount,
+    total_yes_shares: ShareAmount,
+    total_no_shares: ShareAmount,
     resolved: bool,
     outcome: Option<bool>,
-    volume: String,
+    volume: TokenAmount,
+    escrow: TokenAmount,
+    early_resolution_allowed: bool,
+    resolution_time: Option<String>,
+    refund_mode: bool,
+    max_position_per_account: Option<TokenAmount>,
+    /// Cap on one account's YES and NO shares combined
+    max_position: Option<TokenAmount>,
+    /// The creator may not buy shares in this market
+    creator_trading_disabled: bool,
+    /// How the trading fee rises near the end time, if it does
+    fee_ramp: Option<FeeRampInfo>,
+    voided: bool,
+    resolved_by: Option<String>,
+    claim_deadline: Option<String>,
+    /// Escrow still waiting to be claimed; None until resolution
+    unclaimed: Option<TokenAmount>,
+    description: String,
+    resolution_source: String,
+    total_lp_shares: TokenAmount,
+    /// Trading fees charged so far, claimed or not
+    fees_collected: TokenAmount,
+    fees_distributed: TokenAmount,
+    /// The market this one is conditional on, if any
+    parent: Option<ParentCondition>,
+    /// The market group this one is an outcome of, if any
+    group_id: Option<u64>,
+    /// Accounts whose votes resolve the market; empty when the creator resolves it
+    resolvers: Vec<String>,
+    /// Agreeing resolver votes needed to settle the market
+    threshold: u8,
+    /// The source its resolver cited when settling it, if any
+    resolution_evidence: Option<String>,
+    /// Whether anything has been paid out since it settled; once set, the
+    /// admin can no longer override its outcome
+    claims_started: bool,
+    /// Whether CloseMarket or a FinalizeExpired sweep has recorded it closed
+    /// after its end time; stays set once it resolves
+    closed: bool,
+    /// Whether the admin lists it in `featuredMarkets`
+    featured: bool,
+    /// This market's own cap on how far one buy may move its probability,
+    /// in basis points; null where only the global cap applies
+    max_probability_move_bps: Option<u64>,
+    /// Most shares one buy or sell may trade; null for no cap
+    max_trade_shares: Option<ShareAmount>,
+    /// Accounts currently upvoting it
+    upvotes: u64,
+    /// Identifies it across chains, unlike `id`; look it up with
+    /// `marketByKey`. Null for markets created before keys.
+    key: Option<String>,
+    /// Accounts that funded its incentives under a name, in funding order
+    sponsors: Vec<SponsorInfo>,
     yes_price: f64,
     no_price: f64,
+    /// Prices in basis points, as reported by trade responses
+    yes_price_bps: u64,
+    no_price_bps: u64,
+    // Raw values kept for server-side sorting
+    #[graphql(skip)]
+    volume_attos: u128,
+    #[graphql(skip)]
+    liquidity_attos: u128,
+    #[graphql(skip)]
+    yes_pool_attos: u128,
+    #[graphql(skip)]
+    no_pool_attos: u128,
+    #[graphql(skip)]
+    resolution_time_micros: Option<u64>,
+}
+
+impl MarketInfo {
+    /// Its status at `now`, by the contract's own cutoff
+    fn status_at(&self, now: Timestamp) -> EffectiveStatus {
+        EffectiveStatus::at(self.resolved, self.closed, self.end_time_micros.0, now)
+    }
+
+    /// The contract's fee rate for a trade at `now`
+    fn fee_bps_at(&self, base_bps: u16, now: Timestamp) -> u64 {
+        let ramp = self.fee_ramp.as_ref().map(|r| FeeRamp { window_secs: r.window_secs, max_fee_bps: r.max_fee_bps });
+        FeeRamp::fee_bps(ramp, base_bps, self.end_time_micros.0, now)
+    }
+}
+
+/// The fee ramp a market was created with
+#[derive(Clone, SimpleObject)]
+struct FeeRampInfo {
+    window_secs: u64,
+    max_fee_bps: u16,
+}
+
+/// The price of a hypothetical AMM buy
+#[derive(SimpleObject)]
+struct BuyQuote {
+    /// Total to pay, fee included
+    cost: TokenAmount,
+    fee: TokenAmount,
+    fee_bps: u64,
+    /// Average price over the marginal price; see `math::price_impact_bps`
+    price_impact_bps: u64,
+    /// The market's cap on one trade's shares, which the contract would
+    /// refuse this buy over; null for no cap
+    max_trade_shares: Option<ShareAmount>,
+    /// Whether the quoted size is within `maxTradeShares`
+    within_trade_cap: bool,
+}
+
+/// The proceeds of a hypothetical AMM sale; every field but `maxSellableShares`
+/// is null for a sale larger than that
+#[derive(SimpleObject)]
+struct SellQuote {
+    /// Paid to the seller, fee deducted
+    proceeds: Option<TokenAmount>,
+    fee: Option<TokenAmount>,
+    fee_bps: u64,
+    /// Prices right after the sale
+    yes_price_bps: Option<u64>,
+    no_price_bps: Option<u64>,
+    /// Average price under the marginal price; see `math::sell_price_impact_bps`
+    price_impact_bps: Option<u64>,
+    /// Most shares one sale can return: the market's trade cap, or what its
+    /// escrow can pay out; null for no limit
+    max_sellable_shares: Option<ShareAmount>,
+}
+
+/// Computed against the latest block's time, the same for every market in a query
+#[ComplexObject]
+impl MarketInfo {
+    /// Whether the end time has been reached
+    async fn is_ended(&self, ctx: &Context<'_>) -> bool {
+        ctx.data_unchecked::<Arc<QueryData>>().now >= self.end_time_micros.0
+    }
+
+    /// Active until its end time, then closed until it resolves, whether or
+    /// not CloseMarket has recorded that yet
+    async fn status(&self, ctx: &Context<'_>) -> MarketStatus {
+        self.status_at(ctx.data_unchecked::<Arc<QueryData>>().now).into()
+    }
+
+    /// Open for trading: unresolved, before its end time and not globally paused
+    async fn is_tradeable(&self, ctx: &Context<'_>) -> bool {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        !data.paused && self.status_at(data.now) == EffectiveStatus::Open
+    }
+
+    /// Whole seconds until the end time; null once ended or resolved
+    async fn seconds_remaining(&self, ctx: &Context<'_>) -> Option<u64> {
+        let now = ctx.data_unchecked::<Arc<QueryData>>().now;
+        (self.status_at(now) == EffectiveStatus::Open).then(|| (self.end_time_micros.0.micros() - now.micros()) / 1_000_000)
+    }
+
+    /// Volume traded in the current hour and the 23 before it
+    #[graphql(name = "volume24h")]
+    async fn volume_24h(&self, ctx: &Context<'_>) -> async_graphql::Result<TokenAmount> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        Ok(data.market_volume_24h(self.id).await?.into())
+    }
+
+    /// YES price now minus roughly 24 hours ago, in basis points. Markets
+    /// younger than that compare against their opening price.
+    #[graphql(name = "change24h")]
+    async fn change_24h(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let checkpoints = data.state.price_checkpoints.get(&self.id).await?.unwrap_or_default();
+        let cutoff = data.now.saturating_sub(TimeDelta::from_secs(24 * 60 * 60));
+        let then = checkpoints.iter()
+            .rev()
+            .find(|c| c.timestamp <= cutoff)
+            .or(checkpoints.first())
+            .map_or(self.yes_price_bps, |c| c.yes_price_bps);
+        Ok(self.yes_price_bps as i64 - then as i64)
+    }
+
+    /// The thinner pool: the most either side can move before running dry
+    async fn liquidity(&self) -> TokenAmount {
+        Amount::from_attos(self.yes_pool_attos.min(self.no_pool_attos)).into()
+    }
+
+    /// The cap on one buy's probability move in effect, the market's own or
+    /// the global one, in basis points; null for no cap
+    async fn probability_move_limit_bps(&self, ctx: &Context<'_>) -> Option<u64> {
+        self.max_probability_move_bps.or(ctx.data_unchecked::<Arc<QueryData>>().config.max_probability_move_bps)
+    }
+
+    /// Trading fee on AMM trades right now, in basis points
+    async fn fee_bps(&self, ctx: &Context<'_>) -> u64 {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        self.fee_bps_at(data.config.fee_bps, data.now)
+    }
+
+    /// What buying `shares` of one side from the AMM would cost right now,
+    /// fee included, ignoring resting orders; null if the AMM could not
+    /// fill it or trading has closed
+    async fn buy_quote(&self, ctx: &Context<'_>, is_yes: bool, shares: AmountScalar) -> async_graphql::Result<Option<BuyQuote>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let shares = u128::from(shares.0);
+        if self.status_at(data.now) != EffectiveStatus::Open {
+            return Ok(None);
+        }
+        let (pool_in, pool_out) = if is_yes {
+            (self.no_pool_attos, self.yes_pool_attos)
+        } else {
+            (self.yes_pool_attos, self.no_pool_attos)
+        };
+        let Ok(cost) = cost_to_buy(pool_in, pool_out, shares) else {
+            return Ok(None);
+        };
+        let price_impact_bps = price_impact_bps(pool_out, shares)?;
+        let fee_bps = self.fee_bps_at(data.config.fee_bps, data.now);
+        let fee = safe_mul_div(cost, u128::from(fee_bps), 10_000)?;
+        Ok(Some(BuyQuote {
+            cost: Amount::from_attos(cost.saturating_add(fee)).into(),
+            fee: Amount::from_attos(fee).into(),
+            fee_bps,
+            price_impact_bps,
+            max_trade_shares: self.max_trade_shares.clone(),
+            within_trade_cap: self.max_trade_shares.as_ref().is_none_or(|cap| shares <= u128::from(cap.amount)),
+        }))
+    }
+
+    /// Percent change in each side's price if `shares` of it were bought
+    /// from the AMM, ignoring resting orders and fees
+    async fn price_impact(&self, shares: AmountScalar) -> async_graphql::Result<PriceImpact> {
+        let shares = u128::from(shares.0);
+        if self.resolved {
+            return Ok(PriceImpact { yes: None, no: None });
+        }
+        Ok(PriceImpact {
+            yes: price_impact(self.no_pool_attos, self.yes_pool_attos, shares),
+            no: price_impact(self.yes_pool_attos, self.no_pool_attos, shares),
+        })
+    }
+}
+
+/// Percent price moves for a hypothetical buy on each side; null where the
+/// AMM could not fill it
+#[derive(SimpleObject)]
+struct PriceImpact {
+    yes: Option<f64>,
+    no: Option<f64>,
+}
+
+/// Relative move of the bought side's price, pool_in / (pool_in + pool_out),
+/// after taking `shares` out of `pool_out`
+fn price_impact(pool_in: u128, pool_out: u128, shares: u128) -> Option<f64> {
+    if pool_in == 0 || pool_out == 0 {
+        return None;
+    }
+    let cost = cost_to_buy(pool_in, pool_out, shares).ok()?;
+    let price = |pool_in: f64, pool_out: f64| pool_in / (pool_in + pool_out);
+    let before = price(pool_in as f64, pool_out as f64);
+    let after = price(pool_in as f64 + cost as f64, (pool_out - shares) as f64);
+    Some((after / before - 1.0) * 100.0).filter(|impact| impact.is_finite())
 }
 
 impl From<Market> for MarketInfo {
     fn from(m: Market) -> Self {
-        let yes_pool_val: f64 = u128::from(m.yes_pool) as f64;
-        let no_pool_val: f64 = u128::from(m.no_pool) as f64;
-        let total = yes_pool_val + no_pool_val;
-        
-        let yes_price = if total > 0.0 { no_pool_val / total } else { 0.5 };
-        let no_price = if total > 0.0 { yes_pool_val / total } else { 0.5 };
-        
+        let (yes_pool, no_pool) = (u128::from(m.yes_pool), u128::from(m.no_pool));
+        let yes_price = price_of(yes_pool, no_pool) as f64 / PRICE_SCALE as f64;
+        let no_price = price_of(no_pool, yes_pool) as f64 / PRICE_SCALE as f64;
+        let (yes_price_bps, no_price_bps) = (m.yes_price_bps(), m.no_price_bps());
+
         MarketInfo {
             id: m.id,
-            creator: format!("{:?}", m.creator),
+            creator: m.creator.to_string(),
             question: m.question,
             categories: m.categories,
             end_time: format!("{}", m.end_time.micros()),
             created_at: format!("{}", m.created_at.micros()),
-            yes_pool: format!("{}", m.yes_pool),
-            no_pool: format!("{}", m.no_pool),
-            total_yes_shares: format!("{}", m.total_yes_shares),
-            total_no_shares: format!("{}", m.total_no_shares),
+            end_time_micros: TimestampScalar(m.end_time),
+            created_at_micros: TimestampScalar(m.created_at),
+            end_time_rfc3339: rfc3339(m.end_time),
+            created_at_rfc3339: rfc3339(m.created_at),
+            yes_pool: m.yes_pool.into(),
+            no_pool: m.no_pool.into(),
+            total_yes_shares: m.total_yes_shares.into(),
+            total_no_shares: m.total_no_shares.into(),
             resolved: m.resolved,
             outcome: m.outcome,
-            volume: format!("{}", m.volume),
+            volume: m.volume.into(),
+            escrow: m.escrow.into(),
+            early_resolution_allowed: m.early_resolution_allowed,
+            resolution_time: m.resolution_time.map(|t| format!("{}", t.micros())),
+            refund_mode: m.refund_mode,
+            max_position_per_account: m.max_position_per_account.map(TokenAmount::from),
+            max_position: m.max_position.map(TokenAmount::from),
+            creator_trading_disabled: m.creator_trading_disabled,
+            fee_ramp: m.fee_ramp.map(|r| FeeRampInfo { window_secs: r.window_secs, max_fee_bps: r.max_fee_bps }),
+            resolution_evidence: m.resolution_evidence,
+            claims_started: m.claims_started,
+            closed: m.closed,
+            featured: m.featured,
+            max_probability_move_bps: m.max_probability_move_bps,
+            max_trade_shares: m.max_trade_shares.map(ShareAmount::from),
+            upvotes: m.upvotes,
+            key: m.key.map(|key| key.to_string()),
+            sponsors: m.sponsors.iter()
+                .filter(|sponsor| !sponsor.name.is_empty())
+                .map(|sponsor| SponsorInfo {
+                    account: sponsor.account.to_string(),
+                    amount: sponsor.amount.into(),
+                    name: sponsor.name.clone(),
+                    url: sponsor.url.clone(),
+                })
+                .collect(),
+            voided: m.voided,
+            resolved_by: m.resolved_by.map(|owner| owner.to_string()),
+            claim_deadline: m.claim_deadline.map(|t| format!("{}", t.micros())),
+            unclaimed: m.resolved.then(|| m.escrow.into()),
+            description: m.description,
+            resolution_source: m.resolution_source,
+            total_lp_shares: m.total_lp_shares.into(),
+            fees_collected: m.fees_collected.into(),
+            fees_distributed: m.fees_distributed.into(),
+            parent: m.parent.map(|p| ParentCondition { market_id: p.market_id, outcome: p.outcome }),
+            group_id: m.group_id,
+            resolvers: m.resolvers.iter().map(|owner| owner.to_string()).collect(),
+            threshold: m.threshold,
             yes_price,
             no_price,
+            yes_price_bps,
+            no_price_bps,
+            volume_attos: u128::from(m.volume),
+            liquidity_attos: u128::from(m.yes_pool).saturating_add(u128::from(m.no_pool)),
+            yes_pool_attos: u128::from(m.yes_pool),
+            no_pool_attos: u128::from(m.no_pool),
+            resolution_time_micros: m.resolution_time.map(|t| t.micros()),
         }
     }
 }
 
-#[derive(SimpleObject)]
-struct PositionInfo {
+/// A named sponsor of a market's incentives and the total it funded
+#[derive(Clone, SimpleObject)]
+struct SponsorInfo {
+    account: String,
+    amount: TokenAmount,
+    name: String,
+    /// Empty when the sponsor gave no link
+    url: String,
+}
+
+/// The outcome a parent market must resolve with for its conditional market to pay out
+#[derive(Clone, SimpleObject)]
+struct ParentCondition {
     market_id: u64,
-    owner: String,
-    yes_shares: String,
-    no_shares: String,
-    claimed: bool,
+    outcome: bool,
 }
 
-impl From<Position> for PositionInfo {
-    fn from(p: Position) -> Self {
-        PositionInfo {
-            market_id: p.market_id,
-            owner: format!("{:?}", p.owner),
-            yes_shares: format!("{}", p.yes_shares),
-            no_shares: format!("{}", p.no_shares),
-            claimed: p.claimed,
+/// A timestamp as an RFC 3339 UTC string with microsecond precision
+fn rfc3339(timestamp: Timestamp) -> String {
+    let (year, month, day) = utc_civil_date(timestamp);
+    let micros_of_day = timestamp.micros() % DAY_MICROS;
+    let secs_of_day = micros_of_day / 1_000_000;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        micros_of_day % 1_000_000,
+    )
+}
+
+/// A market listing tagged with the chain it lives on
+#[derive(Clone, SimpleObject)]
+struct ListedMarket {
+    origin_chain: String,
+    market_id: u64,
+    question: String,
+    categories: Vec<String>,
+    end_time: String,
+    /// Null for markets announced before keys
+    market_key: Option<String>,
+    is_local: bool,
+}
+
+impl From<RemoteMarket> for ListedMarket {
+    fn from(r: RemoteMarket) -> Self {
+        ListedMarket {
+            origin_chain: r.origin_chain.to_string(),
+            market_id: r.market_id,
+            question: r.question,
+            categories: r.categories,
+            end_time: format!("{}", r.end_time.micros()),
+            market_key: r.market_key.map(|key| key.to_string()),
+            is_local: false,
         }
     }
 }
 
+/// Version of this GraphQL schema, bumped whenever a field changes
+/// incompatibly or goes away
+const SCHEMA_VERSION: u32 = 1;
+
+/// Liveness probe for operations, read without scanning any market
+#[derive(Clone, SimpleObject)]
+struct HealthInfo {
+    /// Version of the service crate this build came from
+    version: String,
+    schema_version: u32,
+    state_loaded_ok: bool,
+    /// Why the state failed to load, if it did
+    state_error: Option<String>,
+    /// Markets created, archived ones included; null without state
+    market_count: Option<u64>,
+    next_market_id: Option<u64>,
+    /// Hashes of the application parameters and of the instantiation
+    /// config: deployments configured alike report the same ones
+    parameters_hash: String,
+    config_hash: Option<String>,
+}
+
+/// Landing-page overview in a single query
 #[derive(SimpleObject)]
-struct LimitOrderInfo {
+struct StatsInfo {
+    total_volume: TokenAmount,
+    market_count: u64,
+    active_count: u64,
+    resolved_count: u64,
+    total_value_locked: TokenAmount,
+    /// Escrow not yet paid out, in resolved markets as well as live ones
+    total_open_interest: TokenAmount,
+    /// Shares the AMMs of unresolved markets hold, both sides together
+    total_active_liquidity: TokenAmount,
+    /// Tokens the application tracks as held: escrow, unpaid LP fees and
+    /// series liquidity. Payouts beyond it are refused.
+    contract_balance: TokenAmount,
+    /// Tokens the admin has deposited to cover shortfalls in that balance
+    reconciled_deposits: TokenAmount,
+    /// Forfeited creator bonds kept by the protocol
+    treasury: TokenAmount,
+    unique_traders: u64,
+    /// Volume in the current hour and the 23 before it
+    #[graphql(name = "volume24h")]
+    volume_24h: TokenAmount,
+    top_markets: Vec<MarketInfo>,
+}
+
+/// Markets by stored status, archived ones included. Unlike a market's
+/// `status` or a summary's `phase`, which show it closed from its end time,
+/// these count it as open until CloseMarket or a FinalizeExpired sweep
+/// records it closed.
+#[derive(SimpleObject)]
+struct MarketCountsInfo {
+    open: u64,
+    closed: u64,
+    /// Resolved with an outcome
+    resolved: u64,
+    voided: u64,
+}
+
+/// The summary ArchiveMarket keeps of a settled market
+#[derive(SimpleObject)]
+struct ArchivedMarketInfo {
     id: u64,
-    owner: String,
-    market_id: u64,
-    is_yes: bool,
-    side: String,
-    price: String,
-    original_amount: String,
-    filled_amount: String,
-    status: String,
-    created_at: String,
+    question: String,
+    /// None for a voided market
+    outcome: Option<bool>,
+    final_volume: TokenAmount,
+    resolved_at: String,
 }
 
-impl From<LimitOrder> for LimitOrderInfo {
-    fn from(o: LimitOrder) -> Self {
-        LimitOrderInfo {
-            id: o.id,
-            owner: format!("{:?}", o.owner),
-            market_id: o.market_id,
-            is_yes: o.is_yes,
-            side: format!("{:?}", o.side),
-            price: format!("{}", o.price),
-            original_amount: format!("{}", o.original_amount),
-            filled_amount: format!("{}", o.filled_amount),
-            status: format!("{:?}", o.status),
-            created_at: format!("{}", o.created_at.micros()),
+impl From<ArchivedMarket> for ArchivedMarketInfo {
+    fn from(a: ArchivedMarket) -> Self {
+        ArchivedMarketInfo {
+            id: a.id,
+            question: a.question,
+            outcome: a.outcome,
+            final_volume: a.final_volume.into(),
+            resolved_at: format!("{}", a.resolved_at.micros()),
         }
     }
 }
 
+/// One page of archived markets plus how many there are
 #[derive(SimpleObject)]
-struct ComboInfo {
-    id: u64,
-    owner: String,
-    name: String,
-    legs: Vec<ComboLegInfo>,
-    stake: String,
-    potential_payout: String,
-    status: String,
-    created_at: String,
+struct ArchivePage {
+    total_count: u64,
+    markets: Vec<ArchivedMarketInfo>,
 }
 
+/// One entry of the activity log
 #[derive(SimpleObject)]
-struct ComboLegInfo {
-    market_id: u64,
-    prediction: bool,
-    odds: String,
-    resolved: bool,
-    won: Option<bool>,
+struct ActivityEntryInfo {
+    /// Position in the log; pass as `before` to page further back
+    id: u64,
+    kind: String,
+    market_id: Option<u64>,
+    actor: String,
+    amount: Option<TokenAmount>,
+    shares: Option<ShareAmount>,
+    result: String,
+    timestamp: String,
+    /// For an operation that traded: the YES price after it, in basis
+    /// points, and the market trade sequence number of its last trade
+    yes_price_bps: Option<u64>,
+    trade_seq: Option<u64>,
+    /// For an operation that traded one side: the actor's shares of that
+    /// side after it
+    position_after: Option<ShareAmount>,
 }
 
-impl From<Combo> for ComboInfo {
-    fn from(c: Combo) -> Self {
-        ComboInfo {
-            id: c.id,
-            owner: format!("{:?}", c.owner),
-            name: c.name,
-            legs: c.legs.into_iter().map(|l| ComboLegInfo {
-                market_id: l.market_id,
-                prediction: l.prediction,
-                odds: format!("{}", l.odds),
-                resolved: l.resolved,
-                won: l.won,
-            }).collect(),
-            stake: format!("{}", c.stake),
-            potential_payout: format!("{}", c.potential_payout),
-            status: format!("{:?}", c.status),
-            created_at: format!("{}", c.created_at.micros()),
+impl ActivityEntryInfo {
+    fn new(id: u64, entry: ActivityEntry) -> Self {
+        ActivityEntryInfo {
+            id,
+            kind: entry.kind,
+            market_id: entry.market_id,
+            actor: entry.actor.to_string(),
+            amount: entry.amount.map(TokenAmount::from),
+            shares: entry.shares.map(ShareAmount::from),
+            result: entry.result,
+            timestamp: format!("{}", entry.timestamp.micros()),
+            yes_price_bps: entry.yes_price_bps,
+            trade_seq: entry.trade_seq,
+            position_after: entry.position_after.map(ShareAmount::from),
         }
     }
 }
 
+/// A trade from the global recent-trades queue
 #[derive(SimpleObject)]
-struct AgentInfo {
-    id: u64,
-    owner: String,
-    name: String,
-    strategy: String,
-    config: String,
-    capital: String,
-    total_volume: String,
-    profit_loss: String,
-    win_rate: f64,
-    total_trades: u64,
-    winning_trades: u64,
-    followers_count: u64,
-    is_active: bool,
-    created_at: String,
+struct RecentTradeInfo {
+    market_id: u64,
+    question: String,
+    trader: String,
+    side: OrderSide,
+    is_yes: bool,
+    shares: ShareAmount,
+    /// Cost including the fee for a buy, proceeds for a sell
+    amount: TokenAmount,
+    /// YES price right after the trade
+    yes_price_bps: u64,
+    timestamp: String,
 }
 
-impl From<TradingAgent> for AgentInfo {
-    fn from(a: TradingAgent) -> Self {
-        AgentInfo {
-            id: a.id,
-            owner: format!("{:?}", a.owner),
-            name: a.name,
-            strategy: format!("{:?}", a.strategy),
-            config: a.config,
-            capital: format!("{}", a.capital),
-            total_volume: format!("{}", a.total_volume),
-            profit_loss: a.profit_loss.to_string(),
-            win_rate: a.win_rate as f64 / 100.0,
-            total_trades: a.total_trades,
-            winning_trades: a.winning_trades,
-            followers_count: a.followers_count,
-            is_active: a.is_active,
-            created_at: format!("{}", a.created_at.micros()),
+impl From<RecentTrade> for RecentTradeInfo {
+    fn from(t: RecentTrade) -> Self {
+        RecentTradeInfo {
+            market_id: t.market_id,
+            question: t.question,
+            trader: t.trader.to_string(),
+            side: t.side,
+            is_yes: t.is_yes,
+            shares: t.shares.into(),
+            amount: t.amount.into(),
+            yes_price_bps: t.yes_price_bps,
+            timestamp: t.timestamp.micros().to_string(),
         }
     }
 }
 
+/// A BuyShares, SellShares or ClosePosition trade, read back from the
+/// activity log
 #[derive(SimpleObject)]
-struct FeedItemInfo {
-    id: u64,
-    author: String,
-    item_type: String,
-    market_id: Option<u64>,
-    content: String,
-    data: String,
-    likes_count: u64,
-    comments_count: u64,
-    created_at: String,
+struct TradeInfo {
+    /// The trade's activity log id; a market's trades ascend by it
+    sequence: u64,
+    market_id: u64,
+    trader: String,
+    is_buy: bool,
+    is_yes: Option<bool>,
+    shares: ShareAmount,
+    /// Cost including the fee for a buy, proceeds for a sell
+    amount: TokenAmount,
+    fee: TokenAmount,
+    /// Prices right after the trade
+    yes_price_bps: u64,
+    no_price_bps: u64,
+    /// The AMM leg's average price over its marginal price, for buys
+    /// recorded since responses carried it
+    price_impact_bps: Option<u64>,
+    /// The market's sequence number for the trade, strictly increasing per
+    /// market; null for trades recorded before it was
+    trade_seq: Option<u64>,
+    /// The trader's shares of the traded side after the trade; null for
+    /// trades recorded before it was
+    position_after: Option<ShareAmount>,
+    timestamp: String,
+    #[graphql(skip)]
+    timestamp_micros: u64,
 }
 
-impl From<FeedItem> for FeedItemInfo {
-    fn from(f: FeedItem) -> Self {
-        FeedItemInfo {
-            id: f.id,
-            author: format!("{:?}", f.author),
-            item_type: format!("{:?}", f.item_type),
-            market_id: f.market_id,
-            content: f.content,
-            data: f.data,
-            likes_count: f.likes_count,
-            comments_count: f.comments_count,
-            created_at: format!("{}", f.created_at.micros()),
-        }
+impl TradeInfo {
+    /// Parse a trade response: `SharesPurchased`, `SharesSold` or
+    /// `PositionClosed`, then cost or proceeds, shares, YES and NO prices,
+    /// YES and NO pools, the fee, its rate and, for newer buys, the price
+    /// impact. None for entries that are not trades.
+    fn parse(sequence: u64, entry: ActivityEntry) -> Option<Self> {
+        let mut fields = entry.result.split(':');
+        let is_buy = match fields.next()? {
+            "SharesPurchased" => true,
+            "SharesSold" | "PositionClosed" => false,
+            _ => return None,
+        };
+        let amount = Amount::from_str(fields.next()?).ok()?;
+        let shares = Amount::from_str(fields.next()?).ok()?;
+        let yes_price_bps = fields.next()?.parse().ok()?;
+        let no_price_bps = fields.next()?.parse().ok()?;
+        let fee = Amount::from_str(fields.nth(2)?).ok()?;
+        let price_impact_bps = fields.nth(1).filter(|_| is_buy).and_then(|bps| bps.parse().ok());
+        Some(TradeInfo {
+            sequence,
+            market_id: entry.market_id?,
+            trader: entry.actor.to_string(),
+            is_buy,
+            is_yes: entry.is_yes,
+            shares: shares.into(),
+            amount: amount.into(),
+            fee: fee.into(),
+            yes_price_bps,
+            no_price_bps,
+            price_impact_bps,
+            trade_seq: entry.trade_seq,
+            position_after: entry.position_after.map(ShareAmount::from),
+            timestamp: format!("{}", entry.timestamp.micros()),
+            timestamp_micros: entry.timestamp.micros(),
+        })
     }
 }
 
-// ============ SERVICE ============
+/// How many accounts have traded in a category, from counters the
+/// contract keeps
+#[derive(SimpleObject)]
+struct CategoryStatsInfo {
+    /// Lowercased, as stored in the category index
+    name: String,
+    /// Distinct accounts that have traded in any of its markets
+    trader_count: u64,
+    market_count: u64,
+}
 
-pub struct MarketService {
-    state: Arc<MarketState>,
-    runtime: Arc<ServiceRuntime<Self>>,
+/// A category with how many markets it holds
+#[derive(SimpleObject)]
+struct CategoryInfo {
+    /// Lowercased, as stored in the category index
+    name: String,
+    market_count: u64,
+    /// Markets in the category not yet resolved
+    active_count: u64,
 }
 
-impl WithServiceAbi for MarketService {
-    type Abi = chronos_market::MarketAbi;
+/// Protocol configuration set at instantiation, as it stands now: every
+/// limit a submitted operation is checked against
+#[derive(SimpleObject)]
+struct ConfigInfo {
+    admin: String,
+    fee_bps: u16,
+    /// `feeBps` as a percentage, e.g. 1.5
+    fee_percent: f64,
+    /// True while the admin has halted market creation and buying
+    paused: bool,
+    min_initial_liquidity: TokenAmount,
+    /// Smallest share amount a buy or sell accepts
+    min_trade_shares: ShareAmount,
+    min_market_duration_secs: u64,
+    /// Earliest end time a market created now may have
+    earliest_end_time_micros: TimestampScalar,
+    resolution_grace_period_secs: u64,
+    admin_resolution_delay_secs: u64,
+    claim_window_secs: u64,
+    recent_trades_capacity: u32,
+    /// Decimal places of share amounts; every `ShareAmount.units` is in
+    /// 10^-shareDecimals shares
+    share_decimals: u8,
+    /// Most one buy may move a market's probability, in basis points;
+    /// null for no cap
+    max_probability_move_bps: Option<u64>,
+    /// Escrowed by each trigger order and paid to whoever executes it
+    keeper_fee: TokenAmount,
+    /// Share of the taker's fee on a book fill rebated to the resting
+    /// order's owner, in basis points
+    maker_rebate_bps: u16,
+    /// Escrowed by a market's creator on top of its liquidity
+    creator_bond: TokenAmount,
+    /// Share of a forfeited bond split among the market's traders, in basis
+    /// points; the treasury keeps the rest
+    bond_trader_share_bps: u16,
+    /// Fee discounts by lifetime trading volume, lowest threshold first
+    fee_tiers: Vec<FeeTierInfo>,
 }
 
-impl Service for MarketService {
-    type Parameters = ();
+/// A fee discount for accounts whose lifetime volume reaches `minVolume`
+#[derive(SimpleObject)]
+struct FeeTierInfo {
+    min_volume: TokenAmount,
+    discount_bps: u16,
+}
 
-    async fn new(runtime: ServiceRuntime<Self>) -> Self {
-        let state = MarketState::load(runtime.root_view_storage_context())
-            .await
-            .expect("Failed to load state");
-        MarketService { 
-            state: Arc::new(state),
-            runtime: Arc::new(runtime),
+impl ConfigInfo {
+    fn new(data: &QueryData) -> Self {
+        let c = &data.config;
+        ConfigInfo {
+            admin: c.admin.to_string(),
+            fee_bps: c.fee_bps,
+            fee_percent: f64::from(c.fee_bps) / 100.0,
+            paused: data.paused,
+            min_initial_liquidity: c.min_initial_liquidity.into(),
+            min_trade_shares: c.min_trade_shares.into(),
+            min_market_duration_secs: c.min_market_duration_secs,
+            earliest_end_time_micros: TimestampScalar(data.now.saturating_add(TimeDelta::from_secs(c.min_market_duration_secs))),
+            resolution_grace_period_secs: c.resolution_grace_period_secs,
+            admin_resolution_delay_secs: c.admin_resolution_delay_secs,
+            claim_window_secs: c.claim_window_secs,
+            recent_trades_capacity: c.recent_trades_capacity(),
+            share_decimals: c.share_decimals(),
+            max_probability_move_bps: c.max_probability_move_bps,
+            keeper_fee: c.keeper_fee().into(),
+            maker_rebate_bps: c.maker_rebate_bps(),
+            creator_bond: c.creator_bond().into(),
+            bond_trader_share_bps: c.bond_trader_share_bps(),
+            fee_tiers: c.fee_tiers.iter()
+                .map(|tier| FeeTierInfo { min_volume: tier.min_volume.into(), discount_bps: tier.discount_bps })
+                .collect(),
         }
     }
+}
 
-    async fn handle_query(&self, request: Request) -> Response {
-        let total_volume = *self.state.total_volume.get();
-        let next_market_id = *self.state.next_market_id.get();
-        let next_order_id = *self.state.next_order_id.get();
-        let next_combo_id = *self.state.next_combo_id.get();
-        let next_agent_id = *self.state.next_agent_id.get();
-        let next_feed_id = *self.state.next_feed_id.get();
-        
-        // Collect all markets
-        let mut markets = Vec::new();
-        for id in 0..next_market_id {
-            if let Ok(Some(market)) = self.state.markets.get(&id).await {
-                markets.push(MarketInfo::from(market));
-            }
-        }
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum MarketStatus {
+    /// Trading until its end time
+    Active,
+    /// Past its end time and awaiting resolution
+    Closed,
+    Resolved,
+}
 
-        // Collect all limit orders
-        let mut orders = Vec::new();
-        for id in 0..next_order_id {
-            if let Ok(Some(order)) = self.state.limit_orders.get(&id).await {
-                orders.push(LimitOrderInfo::from(order));
-            }
+impl From<EffectiveStatus> for MarketStatus {
+    fn from(status: EffectiveStatus) -> Self {
+        match status {
+            EffectiveStatus::Open => MarketStatus::Active,
+            EffectiveStatus::Closed => MarketStatus::Closed,
+            EffectiveStatus::Resolved => MarketStatus::Resolved,
         }
+    }
+}
 
-        // Collect all combos
-        let mut combos = Vec::new();
-        for id in 0..next_combo_id {
-            if let Ok(Some(combo)) = self.state.combos.get(&id).await {
-                combos.push(ComboInfo::from(combo));
-            }
+/// Criteria the `markets` query applies together; unset fields match every
+/// market
+#[derive(InputObject, Default)]
+struct MarketFilter {
+    status: Option<MarketStatus>,
+    /// Matched case-insensitively
+    category: Option<String>,
+    /// Written as `MarketInfo.creator` shows it
+    creator: Option<String>,
+    /// Whitespace-separated search terms; results rank by how well the
+    /// question and categories match them. A blank text matches nothing.
+    text: Option<String>,
+    /// End times at or after it
+    ends_after: Option<TimestampScalar>,
+    /// End times before it
+    ends_before: Option<TimestampScalar>,
+}
+
+/// A `MarketFilter` with its arguments parsed
+#[derive(Default)]
+struct MarketQuery {
+    status: Option<MarketStatus>,
+    /// Lowercased; a market tagged with any of them matches
+    categories: Option<Vec<String>>,
+    creator: Option<AccountOwner>,
+    /// Lowercased search terms
+    terms: Option<Vec<String>>,
+    range: EndTimeRange,
+}
+
+impl MarketQuery {
+    fn parse(filter: MarketFilter) -> async_graphql::Result<Self> {
+        Ok(MarketQuery {
+            status: filter.status,
+            categories: filter.category.map(|c| vec![c.to_lowercase()]),
+            creator: filter.creator.map(|c| parse_owner("creator", &c)).transpose()?,
+            terms: filter.text.map(|t| t.split_whitespace().map(str::to_lowercase).collect()),
+            range: EndTimeRange {
+                after: filter.ends_after.map(|t| t.0),
+                before: filter.ends_before.map(|t| t.0),
+            },
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.status.is_none()
+            && self.categories.is_none()
+            && self.creator.is_none()
+            && self.terms.is_none()
+            && self.range.is_unbounded()
+    }
+}
+
+/// How a resolved market settled
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum ResolvedOutcome {
+    Yes,
+    No,
+    /// Voided without an outcome; positions are refunded
+    Void,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum MarketSort {
+    VolumeDesc,
+    EndTimeAsc,
+    CreatedAtDesc,
+    LiquidityDesc,
+    UpvotesDesc,
+}
+
+impl MarketSort {
+    /// Order two markets by this key, breaking ties on id
+    fn compare(self, a: &MarketInfo, b: &MarketInfo) -> Ordering {
+        match self {
+            MarketSort::VolumeDesc => b.volume_attos.cmp(&a.volume_attos).then(a.id.cmp(&b.id)),
+            MarketSort::EndTimeAsc => a.end_time_micros.cmp(&b.end_time_micros).then(a.id.cmp(&b.id)),
+            MarketSort::CreatedAtDesc => b.created_at_micros.cmp(&a.created_at_micros).then(b.id.cmp(&a.id)),
+            MarketSort::LiquidityDesc => b.liquidity_attos.cmp(&a.liquidity_attos).then(a.id.cmp(&b.id)),
+            MarketSort::UpvotesDesc => b.upvotes.cmp(&a.upvotes).then(a.id.cmp(&b.id)),
+        }
+    }
+}
+
+/// How well a market matches a search, weakest first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Relevance {
+    None,
+    AnyTerm,
+    AllTerms,
+    Phrase,
+}
+
+impl Relevance {
+    /// Match lowercased `terms` against the question and categories
+    fn of(market: &MarketInfo, terms: &[String]) -> Self {
+        let question = market.question.to_lowercase();
+        let categories: Vec<String> = market.categories.iter().map(|c| c.to_lowercase()).collect();
+        let found = |needle: &str| question.contains(needle) || categories.iter().any(|c| c.contains(needle));
+        if found(&terms.join(" ")) {
+            Relevance::Phrase
+        } else if terms.iter().all(|t| found(t)) {
+            Relevance::AllTerms
+        } else if terms.iter().any(|t| found(t)) {
+            Relevance::AnyTerm
+        } else {
+            Relevance::None
+        }
+    }
+}
+
+/// One page of markets plus the size of the full filtered set
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct MarketPage {
+    total_count: u64,
+    #[graphql(skip)]
+    rows: PageRows,
+}
+
+/// A page's markets: read in full to filter or sort them, or else known
+/// only by id until a field asks for them
+enum PageRows {
+    Found(Vec<MarketInfo>),
+    Ids(Vec<u64>),
+}
+
+impl MarketPage {
+    /// The `limit` markets from `offset` on, out of all of `markets`
+    fn of(markets: Vec<MarketInfo>, limit: u64, offset: u64) -> Self {
+        MarketPage {
+            total_count: markets.len() as u64,
+            rows: PageRows::Found(markets.into_iter().skip(offset as usize).take(limit as usize).collect()),
+        }
+    }
+
+    fn ids(&self) -> Vec<u64> {
+        match &self.rows {
+            PageRows::Found(markets) => markets.iter().map(|m| m.id).collect(),
+            PageRows::Ids(ids) => ids.clone(),
+        }
+    }
+}
+
+#[ComplexObject]
+impl MarketPage {
+    /// The page's markets in full
+    async fn markets(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<MarketInfo>> {
+        match &self.rows {
+            PageRows::Found(markets) => Ok(markets.clone()),
+            PageRows::Ids(ids) => Ok(ctx.data_unchecked::<Arc<QueryData>>().load_markets(ids.clone()).await?),
+        }
+    }
+
+    /// The page's listing rows, read from the markets' summaries
+    async fn summaries(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<MarketSummaryInfo>> {
+        Ok(ctx.data_unchecked::<Arc<QueryData>>().load_summaries(&self.ids()).await?)
+    }
+}
+
+/// Where a market stands at query time, as its `status` does, with voided
+/// markets told apart from resolved ones
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum MarketPhase {
+    Open,
+    Closed,
+    Resolved,
+    Voided,
+}
+
+/// A listing row, read from the market's stored summary rather than its
+/// full record
+#[derive(SimpleObject)]
+struct MarketSummaryInfo {
+    id: u64,
+    question: String,
+    yes_price_bps: u64,
+    no_price_bps: u64,
+    volume: TokenAmount,
+    end_time_micros: TimestampScalar,
+    phase: MarketPhase,
+    outcome: Option<bool>,
+}
+
+impl MarketSummaryInfo {
+    fn new(summary: MarketSummary, now: Timestamp) -> Self {
+        let phase = match EffectiveStatus::at(summary.resolved, summary.closed, summary.end_time, now) {
+            EffectiveStatus::Resolved if summary.voided => MarketPhase::Voided,
+            EffectiveStatus::Resolved => MarketPhase::Resolved,
+            EffectiveStatus::Closed => MarketPhase::Closed,
+            EffectiveStatus::Open => MarketPhase::Open,
+        };
+        MarketSummaryInfo {
+            id: summary.id,
+            yes_price_bps: summary.yes_price_bps(),
+            no_price_bps: summary.no_price_bps(),
+            question: summary.question,
+            volume: summary.volume.into(),
+            end_time_micros: TimestampScalar(summary.end_time),
+            phase,
+            outcome: summary.outcome,
+        }
+    }
+}
+
+/// One page of market summaries plus the number of stored markets
+#[derive(SimpleObject)]
+struct MarketSummaryPage {
+    total_count: u64,
+    summaries: Vec<MarketSummaryInfo>,
+}
+
+/// A market on the homepage's featured list
+#[derive(SimpleObject)]
+struct FeaturedMarket {
+    /// Place in the admin's order, 0 first
+    position: u64,
+    /// Whether it has settled since being featured, for the UI to show it
+    /// apart from markets still trading
+    resolved: bool,
+    market: MarketInfo,
+}
+
+#[derive(SimpleObject)]
+struct PositionInfo {
+    market_id: u64,
+    owner: String,
+    yes_shares: ShareAmount,
+    no_shares: ShareAmount,
+    claimed: bool,
+    yes_cost_basis: TokenAmount,
+    no_cost_basis: TokenAmount,
+    /// Shares at current prices, or the claimable amount once resolved
+    current_value: TokenAmount,
+    /// Sell and claim proceeds minus the cost basis they closed out
+    realized_pnl: SignedTokenAmount,
+    /// Cost basis per share held, in basis points as `yesPriceBps`; null
+    /// with no shares on that side
+    avg_yes_entry_price_bps: Option<u64>,
+    avg_no_entry_price_bps: Option<u64>,
+    /// `currentValue` minus the cost basis still held; zero once claimed
+    unrealized_pnl: SignedTokenAmount,
+}
+
+/// A position on another chain's market, migrated to this chain
+#[derive(SimpleObject)]
+struct ImportedPositionInfo {
+    /// Chain whose application holds the market
+    market_chain: String,
+    market_id: u64,
+    owner: String,
+    yes_shares: ShareAmount,
+    no_shares: ShareAmount,
+    yes_cost_basis: TokenAmount,
+    no_cost_basis: TokenAmount,
+}
+
+impl ImportedPositionInfo {
+    fn new(market_chain: String, p: Position) -> Self {
+        ImportedPositionInfo {
+            market_chain,
+            market_id: p.market_id,
+            owner: p.owner.to_string(),
+            yes_shares: p.yes_shares.into(),
+            no_shares: p.no_shares.into(),
+            yes_cost_basis: p.yes_cost_basis.into(),
+            no_cost_basis: p.no_cost_basis.into(),
+        }
+    }
+}
+
+/// A position sent to another chain that has not yet accepted it
+#[derive(SimpleObject)]
+struct PositionTransferInfo {
+    transfer_id: u64,
+    market_chain: String,
+    destination: String,
+    market_id: u64,
+    owner: String,
+    yes_shares: ShareAmount,
+    no_shares: ShareAmount,
+    sent_at_micros: TimestampScalar,
+    /// The destination refuses it after this, and it comes back
+    expires_at_micros: TimestampScalar,
+}
+
+impl From<PositionTransfer> for PositionTransferInfo {
+    fn from(t: PositionTransfer) -> Self {
+        PositionTransferInfo {
+            transfer_id: t.transfer_id,
+            market_chain: t.market_chain.to_string(),
+            destination: t.destination.to_string(),
+            market_id: t.position.market_id,
+            owner: t.position.owner.to_string(),
+            yes_shares: t.position.yes_shares.into(),
+            no_shares: t.position.no_shares.into(),
+            sent_at_micros: TimestampScalar(t.sent_at),
+            expires_at_micros: TimestampScalar(t.expires_at),
+        }
+    }
+}
+
+impl PositionInfo {
+    fn new(p: Position, current_value: Amount) -> Self {
+        PositionInfo {
+            market_id: p.market_id,
+            owner: p.owner.to_string(),
+            yes_shares: p.yes_shares.into(),
+            no_shares: p.no_shares.into(),
+            claimed: p.claimed,
+            yes_cost_basis: p.yes_cost_basis.into(),
+            no_cost_basis: p.no_cost_basis.into(),
+            current_value: current_value.into(),
+            realized_pnl: p.realized_pnl.into(),
+            avg_yes_entry_price_bps: p.average_entry_price_bps(true),
+            avg_no_entry_price_bps: p.average_entry_price_bps(false),
+            unrealized_pnl: p.unrealized_pnl(current_value).into(),
+        }
+    }
+}
+
+/// An account's trading results across all markets
+#[derive(SimpleObject)]
+struct TraderStatsInfo {
+    owner: String,
+    realized_pnl: SignedTokenAmount,
+    /// Collateral traded before fees
+    volume: TokenAmount,
+    /// One per operation and market, however many fills it took
+    trade_count: u64,
+    markets_traded: u64,
+    /// Resolved markets in which the account held winning shares, claimed
+    /// or not
+    markets_won: u64,
+    markets_lost: u64,
+    /// Markets won out of those won or lost; null before any resolved
+    win_rate_bps: Option<u64>,
+    /// Positions holding shares in unresolved markets
+    open_positions: u64,
+}
+
+impl TraderStatsInfo {
+    fn new(owner: AccountOwner, stats: TraderStats) -> Self {
+        TraderStatsInfo {
+            owner: owner.to_string(),
+            realized_pnl: stats.realized_pnl.into(),
+            volume: stats.volume.into(),
+            trade_count: stats.trade_count,
+            markets_traded: stats.markets_traded,
+            markets_won: stats.markets_won,
+            markets_lost: stats.markets_lost,
+            win_rate_bps: stats.win_rate_bps(),
+            open_positions: stats.open_positions,
+        }
+    }
+}
+
+/// Most accounts one leaderboard query returns
+const MAX_LEADERBOARD_SIZE: u64 = 100;
+
+/// Period a `topTraders` ranking covers
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum TraderWindow {
+    AllTime,
+    #[graphql(name = "DAYS_30")]
+    Days30,
+    #[graphql(name = "DAYS_7")]
+    Days7,
+}
+
+impl TraderWindow {
+    /// Whole UTC days the window sums, today included; None for all time
+    fn days(self) -> Option<u64> {
+        match self {
+            TraderWindow::AllTime => None,
+            TraderWindow::Days30 => Some(30),
+            TraderWindow::Days7 => Some(7),
+        }
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum TraderMetric {
+    Pnl,
+    Volume,
+}
+
+/// One place in a `topTraders` ranking
+#[derive(SimpleObject)]
+struct TopTraderInfo {
+    /// From 1, without gaps
+    rank: u64,
+    owner: String,
+    /// Realized PnL or volume over the window, whichever was ranked by
+    value: SignedTokenAmount,
+    /// Trades over the window
+    trade_count: u64,
+}
+
+/// Fewest trades in the window that place an account in `topTraders`,
+/// unless the query asks for another minimum
+const MIN_TOP_TRADER_TRADES: u64 = 3;
+
+#[derive(SimpleObject)]
+struct SeriesInfo {
+    id: u64,
+    creator: String,
+    /// Question with its "{date}" placeholder still in place
+    question_template: String,
+    categories: Vec<String>,
+    duration_secs: u64,
+    liquidity_per_instance: TokenAmount,
+    interval_secs: u64,
+    count: u64,
+    created_at: String,
+    spawned_market_ids: Vec<u64>,
+    /// Liquidity reserved for markets not yet opened
+    escrow: TokenAmount,
+}
+
+/// Mutually exclusive markets, one per outcome
+#[derive(SimpleObject)]
+struct GroupInfo {
+    id: u64,
+    creator: String,
+    title: String,
+    created_at: String,
+    members: Vec<MarketInfo>,
+    /// Member that resolved YES, once the group is resolved
+    winner: Option<u64>,
+    /// Sum of the members' YES prices; near 1 when prices are consistent
+    probability_sum: f64,
+}
+
+impl GroupInfo {
+    fn new(group: MarketGroup, markets: &[MarketInfo]) -> Self {
+        let members: Vec<MarketInfo> = group.market_ids.iter()
+            .filter_map(|id| markets.binary_search_by_key(id, |m| m.id).ok().map(|i| markets[i].clone()))
+            .collect();
+        GroupInfo {
+            id: group.id,
+            creator: group.creator.to_string(),
+            title: group.title,
+            created_at: format!("{}", group.created_at.micros()),
+            probability_sum: members.iter().map(|m| m.yes_price).sum(),
+            members,
+            winner: group.winner,
+        }
+    }
+}
+
+/// Time-weighted average price of a market over a window
+#[derive(SimpleObject)]
+struct TwapInfo {
+    market_id: u64,
+    /// Window actually averaged over: it ends now, or at resolution, and
+    /// starts no earlier than the market's oldest price checkpoint
+    window_start: String,
+    window_end: String,
+    yes_price: f64,
+    no_price: f64,
+}
+
+/// A market's implied prices as of one moment
+#[derive(SimpleObject)]
+struct PriceAtInfo {
+    market_id: u64,
+    /// When the price shown was set: the snapshot or trade it comes from,
+    /// or the resolution time for final prices
+    as_of_micros: TimestampScalar,
+    yes_price: f64,
+    no_price: f64,
+    yes_price_bps: u64,
+    no_price_bps: u64,
+    /// Whether the market had resolved by then, so these prices are final
+    #[graphql(name = "final")]
+    is_final: bool,
+}
+
+/// Volume traded on one UTC day
+#[derive(SimpleObject)]
+struct DailyVolume {
+    /// Days since the Unix epoch
+    day: u64,
+    /// YYYY-MM-DD
+    date: String,
+    volume: TokenAmount,
+}
+
+/// Most days one `volumeHistory` covers
+const MAX_VOLUME_HISTORY_DAYS: u64 = 366;
+
+/// A market's YES price at one moment
+#[derive(SimpleObject)]
+struct ProbabilityPoint {
+    timestamp: String,
+    yes_price_bps: u64,
+    /// `yes_price_bps` as a fraction of one
+    probability: f64,
+}
+
+impl ProbabilityPoint {
+    fn new(timestamp: u64, yes_price_bps: u64) -> Self {
+        ProbabilityPoint {
+            timestamp: format!("{}", timestamp),
+            yes_price_bps,
+            probability: yes_price_bps as f64 / 10_000.0,
+        }
+    }
+}
+
+/// Seed liquidity is split evenly between the pools, so every market opens here
+const OPENING_PRICE_BPS: u64 = 5_000;
+
+/// Most points one `probabilityHistory` holds
+const MAX_HISTORY_POINTS: u64 = 1_000;
+
+/// At most `max_points` of a market's prices from its opening at `start` to
+/// its last trade in `trades`, given as (micros, YES bps) in execution order.
+/// Longer histories are sampled at evenly spaced times, each taking the
+/// price of the last trade at or before it; the last trade is always kept.
+fn probability_points(start: u64, trades: &[(u64, u64)], max_points: u64) -> Vec<ProbabilityPoint> {
+    let Some(&(end, last_bps)) = trades.last() else {
+        return vec![ProbabilityPoint::new(start, OPENING_PRICE_BPS)];
+    };
+    if (trades.len() as u64) < max_points {
+        return std::iter::once((start, OPENING_PRICE_BPS))
+            .chain(trades.iter().copied())
+            .map(|(at, bps)| ProbabilityPoint::new(at, bps))
+            .collect();
+    }
+    if max_points == 1 || end <= start {
+        return vec![ProbabilityPoint::new(end, last_bps)];
+    }
+    let span = u128::from(end - start);
+    let steps = u128::from(max_points - 1);
+    (0..max_points)
+        .map(|i| {
+            let at = start + (span * u128::from(i) / steps) as u64;
+            let executed = trades.partition_point(|(time, _)| *time <= at);
+            let bps = executed.checked_sub(1).map_or(OPENING_PRICE_BPS, |last| trades[last].1);
+            ProbabilityPoint::new(at, bps)
+        })
+        .collect()
+}
+
+/// Resolver votes cast so far on a market
+#[derive(SimpleObject)]
+struct ResolutionTally {
+    market_id: u64,
+    threshold: u8,
+    yes_votes: u32,
+    no_votes: u32,
+    votes: Vec<ResolutionVoteInfo>,
+}
+
+#[derive(SimpleObject)]
+struct ResolutionVoteInfo {
+    resolver: String,
+    outcome: bool,
+    timestamp: String,
+}
+
+impl From<ResolutionVote> for ResolutionVoteInfo {
+    fn from(vote: ResolutionVote) -> Self {
+        ResolutionVoteInfo {
+            resolver: vote.resolver.to_string(),
+            outcome: vote.outcome,
+            timestamp: format!("{}", vote.timestamp.micros()),
+        }
+    }
+}
+
+impl From<MarketSeries> for SeriesInfo {
+    fn from(s: MarketSeries) -> Self {
+        SeriesInfo {
+            id: s.id,
+            creator: s.creator.to_string(),
+            question_template: s.template.question,
+            categories: s.template.categories,
+            duration_secs: s.template.duration_secs,
+            liquidity_per_instance: s.template.liquidity_per_instance.into(),
+            interval_secs: s.interval_secs,
+            count: s.count,
+            created_at: format!("{}", s.created_at.micros()),
+            spawned_market_ids: s.spawned,
+            escrow: s.escrow.into(),
+        }
+    }
+}
+
+/// Unclaimed payout or refund on a resolved market
+#[derive(SimpleObject)]
+struct ClaimableInfo {
+    market_id: u64,
+    amount: TokenAmount,
+}
+
+/// What ClaimWinnings would pay, or why it would refuse
+#[derive(SimpleObject)]
+struct ClaimPreview {
+    market_id: u64,
+    amount: Option<TokenAmount>,
+    reason: Option<String>,
+}
+
+/// An operation to estimate; exactly one field is set
+#[derive(OneofObject)]
+enum EstimateInput {
+    /// BuyShares of exactly `shares`
+    Buy(TradeInput),
+    /// BuyShares of the most shares `budget` pays for, fee included
+    BuyForCost(BudgetInput),
+    /// SellShares of exactly `shares`
+    Sell(TradeInput),
+    Claim(ClaimInput),
+}
+
+#[derive(InputObject)]
+struct TradeInput {
+    market_id: u64,
+    is_yes: bool,
+    shares: AmountScalar,
+}
+
+#[derive(InputObject)]
+struct BudgetInput {
+    market_id: u64,
+    is_yes: bool,
+    budget: AmountScalar,
+}
+
+#[derive(InputObject)]
+struct ClaimInput {
+    market_id: u64,
+}
+
+/// What an operation would do if submitted now, by the contract's math and
+/// checks. Trades are priced against the AMM alone, as when no resting
+/// order crosses. If the contract would refuse it, `reason` says why and
+/// every other field is null.
+#[derive(SimpleObject, Default)]
+struct Estimate {
+    /// The contract's error message; null if the operation would succeed
+    reason: Option<String>,
+    /// Shares bought or sold
+    shares: Option<ShareAmount>,
+    /// Paid into the pools for a buy, or out of them for a sale, before the fee
+    pool_cost: Option<TokenAmount>,
+    fee: Option<TokenAmount>,
+    /// The fee rate charged, after any volume discount
+    fee_bps: Option<u64>,
+    /// The part of the fee paid to the market's LPs: all of it on AMM trades
+    lp_fee: Option<TokenAmount>,
+    /// Paid for a buy, fee included, or received for a sale or claim
+    total: Option<TokenAmount>,
+    /// See `math::price_impact_bps` and `math::sell_price_impact_bps`
+    price_impact_bps: Option<u64>,
+    /// Prices right after the trade
+    yes_price_bps: Option<u64>,
+    no_price_bps: Option<u64>,
+    /// The account's shares in the market afterwards
+    yes_shares_after: Option<ShareAmount>,
+    no_shares_after: Option<ShareAmount>,
+}
+
+/// Why an estimate has no figures
+enum Refusal {
+    /// The contract would refuse the operation with this message
+    Reason(String),
+    View(ViewError),
+}
+
+impl From<String> for Refusal {
+    fn from(reason: String) -> Self {
+        Refusal::Reason(reason)
+    }
+}
+
+impl From<MarketError> for Refusal {
+    fn from(error: MarketError) -> Self {
+        Refusal::Reason(error.to_string())
+    }
+}
+
+impl From<ViewError> for Refusal {
+    fn from(error: ViewError) -> Self {
+        Refusal::View(error)
+    }
+}
+
+/// Everything one account owns, for the profile page
+#[derive(SimpleObject)]
+struct ActivityInfo {
+    markets_created: Vec<MarketInfo>,
+    /// Positions with shares on unresolved markets
+    open_positions: Vec<PositionInfo>,
+    claimable: Vec<ClaimableInfo>,
+    total_claimable: TokenAmount,
+}
+
+/// What an account's open and claimable positions are worth now
+#[derive(SimpleObject)]
+struct PortfolioInfo {
+    /// Unresolved positions at current prices plus unclaimed winnings
+    total_value: TokenAmount,
+    /// Unclaimed winnings and refunds on resolved markets
+    claimable: TokenAmount,
+    position_count: u64,
+    /// Cost basis of the counted positions
+    cost_basis: TokenAmount,
+    /// total_value minus cost_basis
+    unrealized_pnl: SignedTokenAmount,
+}
+
+/// One of an account's positions beside its market
+#[derive(SimpleObject)]
+struct PortfolioEntry {
+    market: MarketInfo,
+    position: PositionInfo,
+    #[graphql(skip)]
+    value: Amount,
+    #[graphql(skip)]
+    unrealized_pnl: i128,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum PositionSort {
+    ValueDesc,
+    ValueAsc,
+    PnlDesc,
+    PnlAsc,
+}
+
+impl PositionSort {
+    /// Order two entries by this key, breaking ties on market id
+    fn compare(self, a: &PortfolioEntry, b: &PortfolioEntry) -> Ordering {
+        let by_id = a.market.id.cmp(&b.market.id);
+        match self {
+            PositionSort::ValueDesc => b.value.cmp(&a.value).then(by_id),
+            PositionSort::ValueAsc => a.value.cmp(&b.value).then(by_id),
+            PositionSort::PnlDesc => b.unrealized_pnl.cmp(&a.unrealized_pnl).then(by_id),
+            PositionSort::PnlAsc => a.unrealized_pnl.cmp(&b.unrealized_pnl).then(by_id),
+        }
+    }
+}
+
+/// Sums over both of `MyPositionsInfo`'s lists
+#[derive(SimpleObject)]
+struct PositionTotalsInfo {
+    total_value: TokenAmount,
+    total_cost: TokenAmount,
+    /// total_value minus total_cost
+    total_unrealized_pnl: SignedTokenAmount,
+    total_claimable: TokenAmount,
+}
+
+/// An account's portfolio screen in one query
+#[derive(SimpleObject)]
+struct MyPositionsInfo {
+    /// Positions on unresolved markets
+    open: Vec<PortfolioEntry>,
+    /// Positions on resolved markets with winnings or a refund unclaimed
+    to_claim: Vec<PortfolioEntry>,
+    totals: PositionTotalsInfo,
+}
+
+/// A position with what it could claim, computed while the market was at hand
+struct HeldPosition {
+    position: Position,
+    market_resolved: bool,
+    claimable: Amount,
+    value: Amount,
+}
+
+/// Liquidity an account provides to a market and the fees it can claim
+#[derive(SimpleObject)]
+struct LpPositionInfo {
+    market_id: u64,
+    owner: String,
+    shares: TokenAmount,
+    pending_fees: TokenAmount,
+    #[graphql(skip)]
+    owner_key: AccountOwner,
+}
+
+impl LpPositionInfo {
+    fn new(lp: LpPosition, market: Option<&Market>) -> Self {
+        LpPositionInfo {
+            market_id: lp.market_id,
+            owner: lp.owner.to_string(),
+            shares: lp.shares.into(),
+            pending_fees: market.map(|m| m.pending_lp_fees(&lp)).unwrap_or(Amount::ZERO).into(),
+            owner_key: lp.owner,
+        }
+    }
+}
+
+/// Whether an LP's stake has paid off: what it could redeem plus what it
+/// took out, against what went in
+#[derive(SimpleObject)]
+struct LpPnlInfo {
+    market_id: u64,
+    owner: String,
+    deposited: TokenAmount,
+    withdrawn: TokenAmount,
+    /// What the stake could redeem before fees: marked at the AMM's prices
+    /// while trading, fixed at settlement once resolved
+    value: TokenAmount,
+    /// Fees claimed plus fees pending
+    fees_earned: TokenAmount,
+    pnl: SignedTokenAmount,
+    settled: bool,
+    history: Vec<LpFlowInfo>,
+}
+
+impl LpPnlInfo {
+    fn new(lp: &LpPosition, ledger: LpLedger, market: &Market) -> Self {
+        let marked = || {
+            let value = u128::from(market.lp_value(Amount::ZERO));
+            safe_mul_div(value, u128::from(lp.shares), u128::from(market.total_lp_shares)).unwrap_or(0)
+        };
+        // Markets settled before ledgers were kept fall back to the
+        // current figure
+        let value = ledger.settled_value.unwrap_or_else(|| Amount::from_attos(marked()));
+        let pending_fees = market.pending_lp_fees(lp);
+        LpPnlInfo {
+            market_id: lp.market_id,
+            owner: lp.owner.to_string(),
+            deposited: ledger.deposited.into(),
+            withdrawn: ledger.withdrawn.into(),
+            value: value.into(),
+            fees_earned: ledger.fees_claimed.saturating_add(pending_fees).into(),
+            pnl: ledger.pnl(value, pending_fees).into(),
+            settled: ledger.settled_value.is_some(),
+            history: ledger.history.into_iter().map(LpFlowInfo::from).collect(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct LpFlowInfo {
+    amount: TokenAmount,
+    /// False for a withdrawal
+    deposit: bool,
+    timestamp_micros: TimestampScalar,
+}
+
+impl From<LpFlow> for LpFlowInfo {
+    fn from(flow: LpFlow) -> Self {
+        LpFlowInfo { amount: flow.amount.into(), deposit: flow.deposit, timestamp_micros: TimestampScalar(flow.timestamp) }
+    }
+}
+
+/// A reward budget streaming to a market's LPs
+#[derive(SimpleObject)]
+struct IncentivesInfo {
+    market_id: u64,
+    /// The account that first funded it; `Market.sponsors` lists them all
+    funder: String,
+    budget: TokenAmount,
+    start_micros: TimestampScalar,
+    end_micros: TimestampScalar,
+    /// Budget streamed to the LPs as of now, or as of settlement once
+    /// the market has settled
+    streamed: TokenAmount,
+    claimed: TokenAmount,
+    /// Sent back to the funders when the market settled
+    returned: Option<TokenAmount>,
+}
+
+impl IncentivesInfo {
+    fn new(program: IncentiveProgram, now: Timestamp) -> Self {
+        let streamed = if program.returned.is_some() { program.streamed } else { program.streamed_at(now) };
+        IncentivesInfo {
+            market_id: program.market_id,
+            funder: program.funder.to_string(),
+            budget: program.budget.into(),
+            start_micros: TimestampScalar(program.start),
+            end_micros: TimestampScalar(program.end()),
+            streamed: streamed.into(),
+            claimed: program.claimed.into(),
+            returned: program.returned.map(TokenAmount::from),
+        }
+    }
+}
+
+/// The bond a market's creator escrowed and what became of it
+#[derive(SimpleObject)]
+struct CreatorBondInfo {
+    market_id: u64,
+    creator: String,
+    amount: TokenAmount,
+    /// Held, Released, Returned or Forfeited
+    status: String,
+    to_treasury: TokenAmount,
+    to_traders: TokenAmount,
+    settled_at_micros: Option<TimestampScalar>,
+}
+
+impl From<CreatorBond> for CreatorBondInfo {
+    fn from(bond: CreatorBond) -> Self {
+        CreatorBondInfo {
+            market_id: bond.market_id,
+            creator: bond.creator.to_string(),
+            amount: bond.amount.into(),
+            status: format!("{:?}", bond.status),
+            to_treasury: bond.to_treasury.into(),
+            to_traders: bond.to_traders.into(),
+            settled_at_micros: bond.settled_at.map(TimestampScalar),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct LimitOrderInfo {
+    id: u64,
+    owner: String,
+    market_id: u64,
+    is_yes: bool,
+    side: String,
+    price_bps: u64,
+    shares: ShareAmount,
+    filled_shares: ShareAmount,
+    escrow: TokenAmount,
+    status: String,
+    created_at: String,
+    #[graphql(skip)]
+    owner_key: AccountOwner,
+    #[graphql(skip)]
+    order_side: OrderSide,
+    #[graphql(skip)]
+    open_shares: Amount,
+}
+
+impl From<LimitOrder> for LimitOrderInfo {
+    fn from(o: LimitOrder) -> Self {
+        LimitOrderInfo {
+            id: o.id,
+            owner: o.owner.to_string(),
+            market_id: o.market_id,
+            is_yes: o.is_yes,
+            side: format!("{:?}", o.side),
+            price_bps: o.price_bps,
+            shares: o.shares.into(),
+            filled_shares: o.filled_shares.into(),
+            escrow: o.escrow.into(),
+            status: format!("{:?}", o.status),
+            created_at: format!("{}", o.created_at.micros()),
+            owner_key: o.owner,
+            order_side: o.side,
+            open_shares: o.shares.saturating_sub(o.filled_shares),
+        }
+    }
+}
+
+impl LimitOrderInfo {
+    fn is_open(&self) -> bool {
+        self.status == "Open" || self.status == "PartiallyFilled"
+    }
+}
+
+/// A sale waiting for its side's price to reach a level, or a purchase
+/// waiting for the YES price to
+#[derive(SimpleObject)]
+struct TriggerOrderInfo {
+    id: u64,
+    owner: String,
+    market_id: u64,
+    side: OrderSide,
+    is_yes: bool,
+    shares: ShareAmount,
+    trigger_price_bps: u64,
+    direction: TriggerDirection,
+    min_proceeds: TokenAmount,
+    /// Escrowed for the keeper that executes it
+    keeper_fee: TokenAmount,
+    status: String,
+    created_at_micros: TimestampScalar,
+    /// What the sale paid, once executed
+    proceeds: TokenAmount,
+    closed_at_micros: Option<TimestampScalar>,
+    /// Highest average price a purchase may pay a share, fee included
+    max_price_bps: Option<u64>,
+    /// Held for a purchase until it executes or is cancelled
+    escrow: TokenAmount,
+    /// What the purchase cost, once executed
+    cost: TokenAmount,
+}
+
+impl From<TriggerOrder> for TriggerOrderInfo {
+    fn from(o: TriggerOrder) -> Self {
+        TriggerOrderInfo {
+            id: o.id,
+            owner: o.owner.to_string(),
+            market_id: o.market_id,
+            side: o.side(),
+            is_yes: o.is_yes,
+            shares: o.shares.into(),
+            trigger_price_bps: o.trigger_price_bps,
+            direction: o.direction,
+            min_proceeds: o.min_proceeds.into(),
+            keeper_fee: o.keeper_fee.into(),
+            status: format!("{:?}", o.status),
+            created_at_micros: TimestampScalar(o.created_at),
+            proceeds: o.proceeds.into(),
+            closed_at_micros: o.closed_at.map(TimestampScalar),
+            max_price_bps: o.max_price_bps,
+            escrow: o.escrow.into(),
+            cost: o.cost.into(),
+        }
+    }
+}
+
+/// Where a price level's shares come from
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+enum LiquiditySource {
+    Orders,
+    Amm,
+    Both,
+}
+
+/// Shares on offer at one price: resting orders', and the AMM's when
+/// `orderBook` is asked for `levels`
+#[derive(SimpleObject)]
+struct PriceLevel {
+    price_bps: u64,
+    /// Resting orders' and AMM shares together
+    shares: ShareAmount,
+    orders: u32,
+    source: LiquiditySource,
+    /// Of `shares`, those the AMM trades between the previous AMM level and
+    /// this one
+    amm_shares: ShareAmount,
+    /// Tokens before fees that trading against the AMM alone costs (asks)
+    /// or pays (bids) to move its price from where it is to this level;
+    /// null where the AMM adds nothing
+    amm_cost_to_reach: Option<TokenAmount>,
+}
+
+/// Bids best (highest) first and asks best (lowest) first
+#[derive(SimpleObject)]
+struct BookSide {
+    bids: Vec<PriceLevel>,
+    asks: Vec<PriceLevel>,
+}
+
+#[derive(SimpleObject)]
+struct OrderBookInfo {
+    market_id: u64,
+    yes: BookSide,
+    no: BookSide,
+}
+
+/// Price step between the levels the AMM's depth is quoted at
+const AMM_DEPTH_STEP_BPS: u64 = 100;
+
+/// Most price levels `orderBook` returns per side
+const MAX_BOOK_LEVELS: u32 = 100;
+
+/// A price level as it is summed
+struct LevelTotals {
+    price_bps: u64,
+    order_shares: Amount,
+    orders: u32,
+    amm_shares: Amount,
+    amm_cost_to_reach: Option<Amount>,
+}
+
+impl From<LevelTotals> for PriceLevel {
+    fn from(level: LevelTotals) -> Self {
+        let source = match (level.orders > 0, level.amm_cost_to_reach.is_some()) {
+            (true, true) => LiquiditySource::Both,
+            (false, true) => LiquiditySource::Amm,
+            _ => LiquiditySource::Orders,
+        };
+        PriceLevel {
+            price_bps: level.price_bps,
+            shares: level.order_shares.saturating_add(level.amm_shares).into(),
+            orders: level.orders,
+            source,
+            amm_shares: level.amm_shares.into(),
+            amm_cost_to_reach: level.amm_cost_to_reach.map(TokenAmount::from),
+        }
+    }
+}
+
+/// Sum already-sorted open orders into price levels
+fn price_levels<'a>(orders: impl Iterator<Item = &'a LimitOrderInfo>) -> Vec<LevelTotals> {
+    let mut levels: Vec<LevelTotals> = Vec::new();
+    for order in orders {
+        match levels.iter_mut().find(|level| level.price_bps == order.price_bps) {
+            Some(level) => {
+                level.order_shares = level.order_shares.saturating_add(order.open_shares);
+                level.orders += 1;
+            }
+            None => levels.push(LevelTotals {
+                price_bps: order.price_bps,
+                order_shares: order.open_shares,
+                orders: 1,
+                amm_shares: Amount::ZERO,
+                amm_cost_to_reach: None,
+            }),
+        }
+    }
+    levels
+}
+
+/// The first `count` levels, every `AMM_DEPTH_STEP_BPS` outward from the
+/// current price, that the AMM fills for a side holding `pool` against
+/// `other_pool`: asks sell it the side up to each price, bids buy the side
+/// back down to it. Levels the AMM is too shallow to reach a whole atto of
+/// are skipped.
+fn amm_levels(pool: u128, other_pool: u128, asks: bool, count: usize) -> Vec<LevelTotals> {
+    let steps = (1..10_000 / AMM_DEPTH_STEP_BPS).map(|step| step * AMM_DEPTH_STEP_BPS);
+    let prices: Vec<u64> = if asks { steps.collect() } else { steps.rev().collect() };
+    let mut levels = Vec::new();
+    let mut reached = 0u128;
+    for price_bps in prices {
+        if levels.len() == count {
+            break;
+        }
+        let Ok(target) = pool_at_price(pool, other_pool, price_bps) else {
+            break;
+        };
+        // Asks lie where the pool has shrunk, bids where it has grown
+        let (shares, cost) = if asks && target < pool {
+            let shares = pool - target;
+            (shares, cost_to_buy(other_pool, pool, shares))
+        } else if !asks && target > pool {
+            let shares = target - pool;
+            (shares, sell_proceeds(pool, other_pool, shares))
+        } else {
+            continue;
+        };
+        let Ok(cost) = cost else {
+            break;
+        };
+        if shares > reached {
+            levels.push(LevelTotals {
+                price_bps,
+                order_shares: Amount::ZERO,
+                orders: 0,
+                amm_shares: Amount::from_attos(shares - reached),
+                amm_cost_to_reach: Some(Amount::from_attos(cost)),
+            });
+            reached = shares;
+        }
+    }
+    levels
+}
+
+/// Add `amm` levels into `levels`, both sorted best first, keeping the
+/// order best first
+fn merge_levels(levels: &mut Vec<LevelTotals>, amm: Vec<LevelTotals>, asks: bool) {
+    for level in amm {
+        match levels.iter_mut().find(|existing| existing.price_bps == level.price_bps) {
+            Some(existing) => {
+                existing.amm_shares = level.amm_shares;
+                existing.amm_cost_to_reach = level.amm_cost_to_reach;
+            }
+            None => levels.push(level),
+        }
+    }
+    if asks {
+        levels.sort_by_key(|level| level.price_bps);
+    } else {
+        levels.sort_by_key(|level| std::cmp::Reverse(level.price_bps));
+    }
+}
+
+#[derive(SimpleObject)]
+struct ComboInfo {
+    id: u64,
+    owner: String,
+    name: String,
+    legs: Vec<ComboLegInfo>,
+    stake: TokenAmount,
+    potential_payout: TokenAmount,
+    status: String,
+    created_at: String,
+}
+
+#[derive(SimpleObject)]
+struct ComboLegInfo {
+    market_id: u64,
+    prediction: bool,
+    odds: TokenAmount,
+    resolved: bool,
+    won: Option<bool>,
+}
+
+impl From<Combo> for ComboInfo {
+    fn from(c: Combo) -> Self {
+        ComboInfo {
+            id: c.id,
+            owner: c.owner.to_string(),
+            name: c.name,
+            legs: c.legs.into_iter().map(|l| ComboLegInfo {
+                market_id: l.market_id,
+                prediction: l.prediction,
+                odds: l.odds.into(),
+                resolved: l.resolved,
+                won: l.won,
+            }).collect(),
+            stake: c.stake.into(),
+            potential_payout: c.potential_payout.into(),
+            status: format!("{:?}", c.status),
+            created_at: format!("{}", c.created_at.micros()),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct AgentInfo {
+    id: u64,
+    owner: String,
+    name: String,
+    strategy: String,
+    config: String,
+    capital: TokenAmount,
+    total_volume: TokenAmount,
+    profit_loss: String,
+    win_rate: f64,
+    total_trades: u64,
+    winning_trades: u64,
+    followers_count: u64,
+    is_active: bool,
+    created_at: String,
+}
+
+impl From<TradingAgent> for AgentInfo {
+    fn from(a: TradingAgent) -> Self {
+        AgentInfo {
+            id: a.id,
+            owner: a.owner.to_string(),
+            name: a.name,
+            strategy: format!("{:?}", a.strategy),
+            config: a.config,
+            capital: a.capital.into(),
+            total_volume: a.total_volume.into(),
+            profit_loss: a.profit_loss.to_string(),
+            win_rate: a.win_rate as f64 / 100.0,
+            total_trades: a.total_trades,
+            winning_trades: a.winning_trades,
+            followers_count: a.followers_count,
+            is_active: a.is_active,
+            created_at: format!("{}", a.created_at.micros()),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct FeedItemInfo {
+    id: u64,
+    author: String,
+    item_type: String,
+    market_id: Option<u64>,
+    content: String,
+    data: String,
+    likes_count: u64,
+    comments_count: u64,
+    created_at: String,
+}
+
+impl From<FeedItem> for FeedItemInfo {
+    fn from(f: FeedItem) -> Self {
+        FeedItemInfo {
+            id: f.id,
+            author: f.author.to_string(),
+            item_type: format!("{:?}", f.item_type),
+            market_id: f.market_id,
+            content: f.content,
+            data: f.data,
+            likes_count: f.likes_count,
+            comments_count: f.comments_count,
+            created_at: format!("{}", f.created_at.micros()),
+        }
+    }
+}
+
+// ============ SERVICE ============
+
+type MarketSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+pub struct MarketService {
+    /// The application state, or why it failed to load; without it only
+    /// `health` answers
+    state: Result<Arc<MarketState>, String>,
+    runtime: Arc<ServiceRuntime<Self>>,
+    /// Built once; each request carries its own `QueryData`
+    schema: MarketSchema,
+}
+
+impl WithServiceAbi for MarketService {
+    type Abi = chronos_market::MarketAbi;
+}
+
+impl Service for MarketService {
+    type Parameters = MarketParameters;
+
+    async fn new(runtime: ServiceRuntime<Self>) -> Self {
+        let state = MarketState::load(runtime.root_view_storage_context())
+            .await
+            .map(Arc::new)
+            .map_err(|e| e.to_string());
+        let runtime = Arc::new(runtime);
+        let mutations = MutationRoot(
+            Operation::mutation_root(runtime.clone()),
+            TradingMutations { state: state.as_ref().ok().cloned(), runtime: runtime.clone() },
+        );
+        // The operation mutations bring the SDK's own `Amount` and
+        // `Timestamp` scalars. Ours accept every input theirs do, so both
+        // share the names, with the query types' descriptions.
+        let schema = Schema::build_with_ignore_name_conflicts(
+            QueryRoot,
+            mutations,
+            EmptySubscription,
+            ["Amount", "Timestamp"],
+        )
+        .finish();
+        MarketService { 
+            state,
+            runtime,
+            schema,
+        }
+    }
+
+    async fn handle_query(&self, request: Request) -> Response {
+        let query = request.query.clone();
+        // The schema comes along so the plain JSON fields can route their
+        // queries through it
+        let mut request = request.data(self.health()).data(self.schema.clone());
+        // A health probe skips the snapshot, so it stays cheap and answers
+        // even when the state failed to load
+        if !selects_only_health(&query) {
+            let data = match &self.state {
+                Ok(state) => self.query_data(state).await.map_err(internal_error),
+                Err(error) => Err(state_unavailable(error)),
+            };
+            match data {
+                Ok(data) => request = request.data(Arc::new(data)),
+                Err(error) => return Response::from_errors(vec![error.into_server_error(Pos::default())]),
+            }
+        }
+        let mut response = self.schema.execute(request).await;
+        name_scalar_arguments(&query, &mut response);
+        response
+    }
+}
+
+impl MarketService {
+    /// What the `health` query reports, read from registers alone
+    fn health(&self) -> HealthInfo {
+        let parameters = self.runtime.application_parameters();
+        let state = self.state.as_ref().ok();
+        HealthInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: SCHEMA_VERSION,
+            state_loaded_ok: state.is_some(),
+            state_error: self.state.as_ref().err().cloned(),
+            market_count: state.map(|s| s.active_market_count.get() + s.resolved_market_count.get()),
+            next_market_id: state.map(|s| *s.next_market_id.get()),
+            parameters_hash: CryptoHash::new(&parameters).to_string(),
+            config_hash: state.map(|s| CryptoHash::new(s.config.get()).to_string()),
+        }
+    }
+
+    /// Snapshot of the state every resolver reads from
+    async fn query_data(&self, state: &Arc<MarketState>) -> Result<QueryData, ViewError> {
+        let total_volume = *state.total_volume.get();
+        let next_market_id = *state.next_market_id.get();
+        let next_combo_id = *state.next_combo_id.get();
+        let next_agent_id = *state.next_agent_id.get();
+        let next_feed_id = *state.next_feed_id.get();
+        
+        let now = self.runtime.system_time();
+        let creator_index = state.markets_by_creator.indices()
+            .await?;
+
+        // Collect all limit orders, by market then order id
+        let orders = state.limit_orders.index_values()
+            .await?
+            .into_iter()
+            .map(|(_, order)| LimitOrderInfo::from(order))
+            .collect();
+
+        let next_series_id = *state.next_series_id.get();
+        let mut series = Vec::new();
+        for id in 0..next_series_id {
+            if let Some(s) = state.series.get(&id).await? {
+                series.push(SeriesInfo::from(s));
+            }
+        }
+
+        // Collect all combos
+        let mut combos = Vec::new();
+        for id in 0..next_combo_id {
+            if let Some(combo) = state.combos.get(&id).await? {
+                combos.push(ComboInfo::from(combo));
+            }
+        }
+
+        // Collect all agents
+        let mut agents = Vec::new();
+        for id in 0..next_agent_id {
+            if let Some(agent) = state.agents.get(&id).await? {
+                agents.push(AgentInfo::from(agent));
+            }
+        }
+
+        // Collect recent feed items (last 100)
+        let mut feed_items = Vec::new();
+        let start = next_feed_id.saturating_sub(100);
+        for id in start..next_feed_id {
+            if let Some(item) = state.feed_items.get(&id).await? {
+                feed_items.push(FeedItemInfo::from(item));
+            }
+        }
+        feed_items.reverse(); // Most recent first
+
+        // Markets announced to this chain by others (non-empty only on the registry)
+        let remote_markets = state.remote_markets.index_values()
+            .await?
+            .into_iter()
+            .map(|(_, remote)| ListedMarket::from(remote))
+            .collect();
+        
+        Ok(QueryData {
+            state: state.clone(),
+            now,
+            chain_id: self.runtime.chain_id().to_string(),
+            total_volume,
+            config: state.config.get().clone(),
+            paused: *state.paused.get(),
+            total_value_locked: *state.total_value_locked.get(),
+            unique_traders: *state.unique_traders.get(),
+            market_count: next_market_id,
+            full: OnceLock::new(),
+            creator_index,
+            series,
+            orders,
+            combos,
+            agents,
+            feed_items,
+            remote_markets,
+        })
+    }
+}
+
+/// ClaimWinnings' checks in the contract's order: the payout on success,
+/// otherwise why the claim would be refused
+fn claim_preview(market: Option<&Market>, position: Option<&Position>, now: Timestamp) -> Result<Amount, &'static str> {
+    let market = market.ok_or("market not found")?;
+    if !market.resolved {
+        return Err("not resolved");
+    }
+    if market.claim_deadline.is_some_and(|deadline| now > deadline) {
+        return Err("claim window closed");
+    }
+    let position = position.ok_or("no position")?;
+    if position.claimed {
+        return Err("already claimed");
+    }
+    if market.refund_mode {
+        if position.yes_cost_basis.saturating_add(position.no_cost_basis) == Amount::ZERO {
+            return Err("nothing to refund");
+        }
+    } else {
+        let winning = match market.outcome {
+            Some(true) => position.yes_shares,
+            Some(false) => position.no_shares,
+            None => return Err("outcome not set"),
+        };
+        if winning == Amount::ZERO {
+            return Err("no winning shares");
+        }
+        if market.escrow == Amount::ZERO {
+            return Err("escrow exhausted");
+        }
+    }
+    Ok(market.claimable(position, now))
+}
+
+/// Parse an owner written as the service renders owners, naming `argument`
+/// in the error
+fn parse_owner(argument: &str, value: &str) -> async_graphql::Result<AccountOwner> {
+    AccountOwner::from_str(value.trim()).map_err(|e| {
+        async_graphql::Error::new(format!("Invalid account format for argument \"{}\": \"{}\" ({})", argument, value, e))
+            .extend_with(|_, extensions| extensions.set("code", "INVALID_ACCOUNT"))
+    })
+}
+
+/// Lookup of `what` that matched nothing; the field resolves to null
+fn not_found(what: String) -> async_graphql::Error {
+    async_graphql::Error::new(format!("{} not found", what))
+        .extend_with(|_, extensions| extensions.set("code", "NOT_FOUND"))
+}
+
+/// Why a query other than `health` cannot run: the state failed to load
+fn state_unavailable(error: &str) -> async_graphql::Error {
+    async_graphql::Error::new(format!("State failed to load: {}", error))
+        .extend_with(|_, extensions| extensions.set("code", "STATE_UNAVAILABLE"))
+}
+
+/// Whether every operation in `query` selects only `health` and
+/// introspection fields, which need no state snapshot
+fn selects_only_health(query: &str) -> bool {
+    let Ok(document) = parse_query(query) else {
+        return false;
+    };
+    document.operations.iter().all(|(_, operation)| {
+        operation.node.ty == OperationType::Query
+            && operation.node.selection_set.node.items.iter().all(|selection| match &selection.node {
+                Selection::Field(field) => {
+                    let name = field.node.name.node.as_str();
+                    name == "health" || name.starts_with("__")
+                }
+                _ => false,
+            })
+    })
+}
+
+/// State that could not be read, reported instead of failing the query
+fn internal_error(error: ViewError) -> async_graphql::Error {
+    async_graphql::Error::new(format!("Failed to read state: {}", error))
+        .extend_with(|_, extensions| extensions.set("code", "INTERNAL"))
+}
+
+/// Run `query` through the schema with this request's state snapshot and
+/// render the value at `pointer` in its result as a plain JSON field does.
+/// An error is reported by the code in its extensions, or `BAD_REQUEST`.
+async fn route_json(ctx: &Context<'_>, query: &str, variables: serde_json::Value, pointer: &str) -> String {
+    let schema = ctx.data_unchecked::<MarketSchema>();
+    let request = Request::new(query)
+        .variables(Variables::from_json(variables))
+        .data(ctx.data_unchecked::<Arc<QueryData>>().clone());
+    // Boxed, as the routed query resolves through this same root
+    let response: Pin<Box<dyn Future<Output = Response> + Send + '_>> = Box::pin(schema.execute(request));
+    let response = response.await;
+    let result = match response.errors.first() {
+        Some(error) => {
+            let code = match error.extensions.as_ref().and_then(|extensions| extensions.get("code")) {
+                Some(Value::String(code)) => code.as_str(),
+                _ => "BAD_REQUEST",
+            };
+            json_error(code, &error.message)
+        }
+        None => match response.data.into_json() {
+            Ok(data) => serde_json::json!({ "data": data.pointer(pointer).cloned().unwrap_or_default() }),
+            Err(error) => json_error("INTERNAL", &format!("Failed to encode result: {}", error)),
+        },
+    };
+    result.to_string()
+}
+
+fn json_error(code: &str, message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": { "code": code, "message": message } })
+}
+
+/// Prefixes of the errors async-graphql reports when a scalar input fails
+/// to parse, with the kind of value each one names
+const SCALAR_PARSE_ERRORS: [(&str, &str); 2] = [
+    ("Failed to parse \"Amount\"", "amount"),
+    ("Failed to parse \"Timestamp\"", "timestamp"),
+];
+
+/// The kind of value a scalar parse error is about, if it is one
+fn scalar_parse_error(message: &str) -> Option<&'static str> {
+    SCALAR_PARSE_ERRORS.iter().find(|(prefix, _)| message.starts_with(prefix)).map(|&(_, kind)| kind)
+}
+
+/// Scalar parse errors only carry the position of the bad value. Look the
+/// position up in the query so the message names the offending argument.
+fn name_scalar_arguments(query: &str, response: &mut Response) {
+    if !response.errors.iter().any(|e| scalar_parse_error(&e.message).is_some()) {
+        return;
+    }
+    let Ok(document) = parse_query(query) else {
+        return;
+    };
+    let mut arguments = HashMap::new();
+    for (_, operation) in document.operations.iter() {
+        collect_argument_positions(&operation.node.selection_set.node, &mut arguments);
+    }
+    for error in &mut response.errors {
+        let Some(kind) = scalar_parse_error(&error.message) else {
+            continue;
+        };
+        if let Some(name) = error.locations.first().and_then(|pos| arguments.get(pos)) {
+            error.message = format!("Invalid {} for argument \"{}\": {}", kind, name, error.message);
+        }
+    }
+}
+
+fn collect_argument_positions(selection_set: &SelectionSet, arguments: &mut HashMap<Pos, String>) {
+    for selection in &selection_set.items {
+        if let Selection::Field(field) = &selection.node {
+            for (name, value) in &field.node.arguments {
+                arguments.insert(value.pos, name.node.to_string());
+            }
+            collect_argument_positions(&field.node.selection_set.node, arguments);
+        }
+    }
+}
+
+/// Query root of the cached schema; resolvers read the request's `QueryData`
+struct QueryRoot;
+
+/// State snapshot taken for one request
+struct QueryData {
+    state: Arc<MarketState>,
+    now: Timestamp,
+    chain_id: String,
+    total_volume: Amount,
+    config: Config,
+    paused: bool,
+    total_value_locked: Amount,
+    unique_traders: u64,
+    market_count: u64,
+    /// Read on first use by the resolvers that need it
+    full: OnceLock<FullSnapshot>,
+    /// (creator, market_id) pairs from `markets_by_creator`
+    creator_index: Vec<(AccountOwner, u64)>,
+    series: Vec<SeriesInfo>,
+    orders: Vec<LimitOrderInfo>,
+    combos: Vec<ComboInfo>,
+    agents: Vec<AgentInfo>,
+    feed_items: Vec<FeedItemInfo>,
+    remote_markets: Vec<ListedMarket>,
+}
+
+/// Every stored market and position, decoded in full
+struct FullSnapshot {
+    markets: Vec<MarketInfo>,
+    positions: Vec<HeldPosition>,
+    lp_positions: Vec<LpPositionInfo>,
+}
+
+impl FullSnapshot {
+    async fn load(state: &MarketState, now: Timestamp) -> Result<Self, ViewError> {
+        // Collect every stored market; archived ones leave gaps in the ids
+        let raw_markets: BTreeMap<u64, _> = state.markets.index_values().await?.into_iter().collect();
+
+        let positions = state.positions.index_values()
+            .await?
+            .into_iter()
+            .map(|(_, position)| {
+                let market = raw_markets.get(&position.market_id);
+                HeldPosition {
+                    market_resolved: market.is_some_and(|m| m.resolved),
+                    claimable: market.map(|m| m.claimable(&position, now)).unwrap_or(Amount::ZERO),
+                    value: market.map(|m| m.position_value(&position, now)).unwrap_or(Amount::ZERO),
+                    position,
+                }
+            })
+            .collect();
+        let lp_positions = state.lp_positions.index_values()
+            .await?
+            .into_iter()
+            .map(|(_, lp)| {
+                let market = raw_markets.get(&lp.market_id);
+                LpPositionInfo::new(lp, market)
+            })
+            .collect();
+        let markets = raw_markets.into_values().map(MarketInfo::from).collect();
+        Ok(FullSnapshot { markets, positions, lp_positions })
+    }
+}
+
+impl QueryData {
+    /// Every market and position in full. Decoding them all is the costly
+    /// part of a query, so it waits for the first resolver that needs them.
+    async fn full(&self) -> Result<&FullSnapshot, ViewError> {
+        if let Some(full) = self.full.get() {
+            return Ok(full);
+        }
+        let full = FullSnapshot::load(&self.state, self.now).await?;
+        Ok(self.full.get_or_init(|| full))
+    }
+
+    /// Ids of the markets tagged with `category` in any letter case, ascending
+    async fn category_ids(&self, category: &str) -> Result<Vec<u64>, ViewError> {
+        Ok(self.state.markets_by_category.get(&category.to_lowercase()).await?.unwrap_or_default())
+    }
+
+    /// Part of a forfeited creator bond paid with `owner`'s refund
+    async fn bond_share(&self, owner: AccountOwner, market_id: u64) -> Result<Amount, ViewError> {
+        Ok(self.state.bond_shares.get(&(owner, market_id)).await?.unwrap_or(Amount::ZERO))
+    }
+
+    /// The trading fee `trader` pays in `market` now, after the discount of
+    /// the fee tier their lifetime volume reaches, as the contract charges it
+    async fn trader_fee_bps(&self, market: &Market, trader: AccountOwner) -> Result<u64, ViewError> {
+        let fee_bps = market.fee_bps_at(self.config.fee_bps, self.now);
+        if self.config.fee_tiers.is_empty() {
+            return Ok(fee_bps);
+        }
+        let volume = self.state.trader_stats.get(&trader).await?.map_or(Amount::ZERO, |stats| stats.volume);
+        Ok(self.config.discounted_fee_bps(fee_bps, volume))
+    }
+
+    /// A market as the contract loads it for a trade or claim
+    async fn estimated_market(&self, market_id: u64) -> Result<Market, Refusal> {
+        let mut market = self.state.markets.get(&market_id)
+            .await?
+            .ok_or_else(|| format!("Market {} not found", market_id))?;
+        market.upgrade();
+        Ok(market)
+    }
+
+    /// BuyShares of `shares` by `owner`, through the contract's checks in
+    /// its order
+    async fn estimate_buy(&self, owner: AccountOwner, market_id: u64, is_yes: bool, shares: Amount) -> Result<Estimate, Refusal> {
+        if self.paused {
+            return Err(MarketError::Paused.into());
+        }
+        let mut market = self.estimated_market(market_id).await?;
+        check_open(&market, self.now)?;
+        self.config.check_trade_shares(&market, shares)?;
+
+        let (pool_in, pool_out) = if is_yes { (market.no_pool, market.yes_pool) } else { (market.yes_pool, market.no_pool) };
+        let (pi, po, s) = (u128::from(pool_in), u128::from(pool_out), u128::from(shares));
+        if s >= po {
+            return Err(format!(
+                "Not enough liquidity: requested {} shares but pool only has {} (pool_in={}, pool_out={})",
+                shares, pool_out, pool_in, pool_out
+            ).into());
+        }
+        let price_impact_bps = price_impact_bps(po, s)?;
+        let cost = cost_to_buy(pi, po, s)?;
+        if let Some(allowed_bps) = market.probability_move_limit_bps(&self.config) {
+            let attempted_bps = probability_move_bps(pi, po, s, cost)?;
+            if attempted_bps > allowed_bps {
+                return Err(MarketError::MaxImpactExceeded { market_id, allowed_bps, attempted_bps }.into());
+            }
+        }
+        let fee_bps = self.trader_fee_bps(&market, owner).await?;
+        let fee = safe_mul_div(cost, u128::from(fee_bps), 10_000)?;
+        let total = Amount::from_attos(cost.saturating_add(fee));
+        if total == Amount::ZERO {
+            return Err(MarketError::ZeroCostTrade { shares }.into());
+        }
+
+        let position = self.state.positions.get(&(owner, market_id)).await?;
+        let (yes, no) = position.map_or((Amount::ZERO, Amount::ZERO), |p| (p.yes_shares, p.no_shares));
+        let (yes_after, no_after) = if is_yes { (yes.saturating_add(shares), no) } else { (yes, no.saturating_add(shares)) };
+        if market.creator_trading_disabled && owner == market.creator {
+            return Err(MarketError::CreatorTradingDisabled { market_id }.into());
+        }
+        if let Some(limit) = market.max_position_per_account {
+            let held = if is_yes { yes } else { no };
+            if held.saturating_add(shares) > limit {
+                return Err(MarketError::PositionLimitExceeded { market_id, limit, headroom: limit.saturating_sub(held) }.into());
+            }
+        }
+        if let Some(cap) = market.max_position {
+            if yes_after.saturating_add(no_after) > cap {
+                return Err(MarketError::MaxPositionExceeded { market_id, cap, held: yes.saturating_add(no) }.into());
+            }
+        }
+
+        let cost = Amount::from_attos(cost);
+        if is_yes {
+            market.no_pool = market.no_pool.saturating_add(cost);
+            market.yes_pool = market.yes_pool.saturating_sub(shares);
+        } else {
+            market.yes_pool = market.yes_pool.saturating_add(cost);
+            market.no_pool = market.no_pool.saturating_sub(shares);
+        }
+        Ok(Estimate {
+            reason: None,
+            shares: Some(shares.into()),
+            pool_cost: Some(cost.into()),
+            fee: Some(Amount::from_attos(fee).into()),
+            fee_bps: Some(fee_bps),
+            lp_fee: Some(Amount::from_attos(fee).into()),
+            total: Some(total.into()),
+            price_impact_bps: Some(price_impact_bps),
+            yes_price_bps: Some(market.yes_price_bps()),
+            no_price_bps: Some(market.no_price_bps()),
+            yes_shares_after: Some(yes_after.into()),
+            no_shares_after: Some(no_after.into()),
+        })
+    }
+
+    /// The largest BuyShares in whole share units whose cost, fee included,
+    /// fits in `budget`, capped at the market's trade cap
+    async fn estimate_buy_for_cost(&self, owner: AccountOwner, market_id: u64, is_yes: bool, budget: Amount) -> Result<Estimate, Refusal> {
+        let market = self.estimated_market(market_id).await?;
+        let (pool_in, pool_out) = if is_yes { (market.no_pool, market.yes_pool) } else { (market.yes_pool, market.no_pool) };
+        let (pi, po) = (u128::from(pool_in), u128::from(pool_out));
+        let fee_bps = self.trader_fee_bps(&market, owner).await?;
+        let unit = share_unit_attos(self.config.share_decimals()).unwrap_or(1);
+        let into_pools = safe_mul_div(u128::from(budget), 10_000, 10_000u128.saturating_add(u128::from(fee_bps)))?;
+        let mut shares = shares_for_cost(pi, po, into_pools)?;
+        if let Some(cap) = market.max_trade_shares {
+            shares = shares.min(u128::from(cap));
+        }
+        shares -= shares % unit;
+        // Rounding may leave the total an atto or so over the budget
+        while shares > 0 {
+            let cost = cost_to_buy(pi, po, shares)?;
+            let total = cost.saturating_add(safe_mul_div(cost, u128::from(fee_bps), 10_000)?);
+            if total <= u128::from(budget) {
+                break;
+            }
+            shares -= unit.min(shares);
+        }
+        if shares == 0 {
+            return Err(format!("A budget of {} buys no shares", budget).into());
+        }
+        self.estimate_buy(owner, market_id, is_yes, Amount::from_attos(shares)).await
+    }
+
+    /// SellShares of `shares` by `owner`, through the contract's checks in
+    /// its order
+    async fn estimate_sell(&self, owner: AccountOwner, market_id: u64, is_yes: bool, shares: Amount) -> Result<Estimate, Refusal> {
+        let mut market = self.estimated_market(market_id).await?;
+        check_open(&market, self.now)?;
+        self.config.check_trade_shares(&market, shares)?;
+        let position = self.state.positions.get(&(owner, market_id)).await?;
+        let (yes, no) = position.map_or((Amount::ZERO, Amount::ZERO), |p| (p.yes_shares, p.no_shares));
+        let held = if is_yes { yes } else { no };
+        if shares > held {
+            return Err(MarketError::InsufficientBalance { needed: shares, available: held }.into());
+        }
+
+        let (pool_in, pool_out) = if is_yes { (market.yes_pool, market.no_pool) } else { (market.no_pool, market.yes_pool) };
+        let (pi, po, s) = (u128::from(pool_in), u128::from(pool_out), u128::from(shares));
+        let gross = Amount::from_attos(sell_proceeds(pi, po, s)?);
+        let fee_bps = self.trader_fee_bps(&market, owner).await?;
+        let fee = Amount::from_attos(safe_mul_div(gross.into(), u128::from(fee_bps), 10_000)?);
+        let proceeds = gross.saturating_sub(fee);
+        if gross > market.escrow {
+            return Err(format!("Proceeds {} exceed market {} escrow {}", gross, market_id, market.escrow).into());
+        }
+        let balance = *self.state.contract_balance.get();
+        if proceeds > balance {
+            return Err(MarketError::PayoutExceedsBalance { market_id, payout: proceeds, balance }.into());
+        }
+
+        if is_yes {
+            market.yes_pool = market.yes_pool.saturating_add(shares);
+            market.no_pool = market.no_pool.saturating_sub(gross);
+        } else {
+            market.no_pool = market.no_pool.saturating_add(shares);
+            market.yes_pool = market.yes_pool.saturating_sub(gross);
+        }
+        let (yes_after, no_after) = if is_yes { (yes.saturating_sub(shares), no) } else { (yes, no.saturating_sub(shares)) };
+        Ok(Estimate {
+            reason: None,
+            shares: Some(shares.into()),
+            pool_cost: Some(gross.into()),
+            fee: Some(fee.into()),
+            fee_bps: Some(fee_bps),
+            lp_fee: Some(fee.into()),
+            total: Some(proceeds.into()),
+            price_impact_bps: Some(sell_price_impact_bps(pi, s)?),
+            yes_price_bps: Some(market.yes_price_bps()),
+            no_price_bps: Some(market.no_price_bps()),
+            yes_shares_after: Some(yes_after.into()),
+            no_shares_after: Some(no_after.into()),
+        })
+    }
+
+    /// ClaimWinnings by `owner`: the payout, forfeited-bond share included
+    async fn estimate_claim(&self, owner: AccountOwner, market_id: u64) -> Result<Estimate, Refusal> {
+        let market = self.state.markets.get(&market_id).await?;
+        let position = self.state.positions.get(&(owner, market_id)).await?;
+        let amount = claim_preview(market.as_ref(), position.as_ref(), self.now).map_err(str::to_string)?;
+        let payout = amount.saturating_add(self.bond_share(owner, market_id).await?);
+        let balance = *self.state.contract_balance.get();
+        if payout > balance {
+            return Err(format!(
+                "Payout of {} on market {} exceeds the contract balance of {}; it stays unclaimed until the balance is reconciled",
+                payout, market_id, balance
+            ).into());
+        }
+        Ok(Estimate { total: Some(payout.into()), ..Estimate::default() })
+    }
+
+    /// Every position `owner` holds, read from state
+    async fn positions_of(&self, owner: AccountOwner) -> Result<Vec<Position>, ViewError> {
+        let mut held = Vec::new();
+        self.state.positions.for_each_index_value(|(holder, _), position| {
+            if holder == owner {
+                held.push(position.into_owned());
+            }
+            Ok(())
+        }).await?;
+        Ok(held)
+    }
+
+    /// Read the markets with `ids` from state, skipping any that are missing
+    async fn load_markets(&self, ids: Vec<u64>) -> Result<Vec<MarketInfo>, ViewError> {
+        let mut markets = Vec::new();
+        for id in ids {
+            if let Some(market) = self.state.markets.get(&id).await? {
+                markets.push(MarketInfo::from(market));
+            }
+        }
+        Ok(markets)
+    }
+
+    /// Listing rows for the markets with `ids`, skipping any that are
+    /// missing. A market last saved before summaries were kept has none
+    /// until the contract next saves it, so its row is built from its full
+    /// record meanwhile.
+    async fn load_summaries(&self, ids: &[u64]) -> Result<Vec<MarketSummaryInfo>, ViewError> {
+        let mut rows = Vec::new();
+        for (id, summary) in ids.iter().zip(self.state.summaries.multi_get(ids).await?) {
+            let summary = match summary {
+                Some(summary) => Some(summary),
+                None => self.state.markets.get(id).await?.map(|market| MarketSummary::from(&market)),
+            };
+            rows.extend(summary.map(|summary| MarketSummaryInfo::new(summary, self.now)));
+        }
+        Ok(rows)
+    }
+
+    /// Up to `limit` activity entries below log index `before`, newest
+    /// first: all of them, or those `indexed` lists
+    async fn activity_page<K>(
+        &self,
+        indexed: Option<ActivityIndex<'_, K>>,
+        limit: u64,
+        before: Option<u64>,
+    ) -> Result<Vec<ActivityEntryInfo>, ViewError>
+    where
+        K: Serialize + Clone + Send + Sync,
+    {
+        let log_len = self.state.activity.count() as u64;
+        let before = before.map_or(log_len, |before| before.min(log_len));
+        let ids: Vec<u64> = match indexed {
+            None => (0..before).rev().take(limit as usize).collect(),
+            Some(indexed) => {
+                let mut ids = Vec::new();
+                for n in (0..indexed.count_below(before).await?).rev().take(limit as usize) {
+                    ids.extend(indexed.get(n).await?);
+                }
+                ids
+            }
+        };
+        let entries = self.state.activity.multi_get(ids.iter().map(|id| *id as usize).collect()).await?;
+        Ok(ids.into_iter()
+            .zip(entries)
+            .filter_map(|(id, entry)| entry.map(|entry| ActivityEntryInfo::new(id, entry)))
+            .collect())
+    }
+
+    /// Ids of every live market, ascending. Archived markets leave gaps in
+    /// the range. Keys iterate in serialized (little-endian) order, so they
+    /// are sorted numerically.
+    async fn market_ids(&self) -> Result<Vec<u64>, ViewError> {
+        let mut ids = self.state.markets.indices().await?;
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// The `n`th activity entry of a market, if it is a trade in that market
+    async fn indexed_trade(&self, indexed: &ActivityIndex<'_, u64>, n: u64) -> Result<Option<TradeInfo>, ViewError> {
+        let Some(id) = indexed.get(n).await? else {
+            return Ok(None);
+        };
+        Ok(self.state.activity.get(id as usize).await?
+            .filter(|entry| entry.market_id == Some(indexed.key))
+            .and_then(|entry| TradeInfo::parse(id, entry)))
+    }
+
+    /// A market's volume in the current hour and the 23 before it
+    async fn market_volume_24h(&self, market_id: u64) -> Result<Amount, ViewError> {
+        let ring = self.state.market_volume.get(&market_id).await?.unwrap_or_default();
+        Ok(ring.last_24h(self.now))
+    }
+
+    /// Markets matching every criterion of `query`: by relevance when it
+    /// has search terms, then by `sort`, then by id. Candidates come from the
+    /// category and creator indices when either is set; only otherwise is
+    /// every market read.
+    async fn find_markets(&self, query: &MarketQuery, sort: Option<MarketSort>) -> Result<Vec<MarketInfo>, ViewError> {
+        if query.terms.as_ref().is_some_and(|terms| terms.is_empty()) {
+            return Ok(Vec::new());
+        }
+        let mut ids: Option<Vec<u64>> = None;
+        if let Some(categories) = &query.categories {
+            let mut tagged = Vec::new();
+            for category in categories {
+                tagged.extend(self.category_ids(category).await?);
+            }
+            tagged.sort_unstable();
+            tagged.dedup();
+            ids = Some(tagged);
+        }
+        if let Some(creator) = &query.creator {
+            let created: Vec<u64> = self.creator_index.iter()
+                .filter(|(owner, _)| owner == creator)
+                .map(|(_, id)| *id)
+                .collect();
+            ids = Some(match ids {
+                Some(ids) => ids.into_iter().filter(|id| created.contains(id)).collect(),
+                None => {
+                    let mut created = created;
+                    created.sort_unstable();
+                    created
+                }
+            });
+        }
+        let candidates = match ids {
+            Some(ids) => self.load_markets(ids).await?,
+            None => {
+                let mut markets = Vec::new();
+                self.state.markets.for_each_index_value(|_, market| {
+                    markets.push(MarketInfo::from(market.into_owned()));
+                    Ok(())
+                }).await?;
+                markets
+            }
+        };
+
+        let mut matches: Vec<(Relevance, MarketInfo)> = candidates.into_iter()
+            .filter(|m| match query.status {
+                Some(status) => MarketStatus::from(m.status_at(self.now)) == status,
+                None => true,
+            })
+            .filter(|m| query.range.contains(m))
+            .map(|m| match &query.terms {
+                Some(terms) => (Relevance::of(&m, terms), m),
+                // Without terms every market ranks the same
+                None => (Relevance::Phrase, m),
+            })
+            .filter(|(relevance, _)| *relevance != Relevance::None)
+            .collect();
+        matches.sort_by(|(ra, a), (rb, b)| {
+            rb.cmp(ra).then_with(|| sort.map_or(Ordering::Equal, |sort| sort.compare(a, b))).then(a.id.cmp(&b.id))
+        });
+        Ok(matches.into_iter().map(|(_, market)| market).collect())
+    }
+
+    async fn created_by(&self, owner: &AccountOwner) -> Result<Vec<MarketInfo>, ViewError> {
+        let mut ids: Vec<u64> = self.creator_index.iter()
+            .filter(|(creator, _)| creator == owner)
+            .map(|(_, id)| *id)
+            .collect();
+        ids.sort_unstable();
+        self.load_markets(ids).await
+    }
+}
+
+/// The `activity` entries one market or owner `key` is indexed under
+struct ActivityIndex<'a, K> {
+    counts: &'a MapView<K, u64>,
+    index: &'a MapView<(K, u64), u64>,
+    key: K,
+}
+
+impl<K: Serialize + Clone + Send + Sync> ActivityIndex<'_, K> {
+    async fn len(&self) -> Result<u64, ViewError> {
+        Ok(self.counts.get(&self.key).await?.unwrap_or(0))
+    }
+
+    /// The log id of the `n`th entry indexed under the key
+    async fn get(&self, n: u64) -> Result<Option<u64>, ViewError> {
+        self.index.get(&(self.key.clone(), n)).await
+    }
+
+    /// How many indexed entries have a log id below `id`. Ids ascend with
+    /// n, so this is a binary search.
+    async fn count_below(&self, id: u64) -> Result<u64, ViewError> {
+        let (mut low, mut high) = (0, self.len().await?);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.get(middle).await?.is_some_and(|found| found < id) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+}
+
+/// Most entries one `activity` or `activityByOwner` page holds
+const MAX_ACTIVITY_PAGE_SIZE: u64 = 100;
+
+/// Most edges one `marketsConnection` or `tradesConnection` page holds
+const MAX_CONNECTION_PAGE_SIZE: u64 = 100;
+
+/// Reject a page size of 0 or above `max`, naming `argument`
+fn check_page_size(argument: &str, size: u64, max: u64) -> async_graphql::Result<()> {
+    if size == 0 || size > max {
+        return Err(async_graphql::Error::new(format!(
+            "Invalid value for argument \"{}\": must be between 1 and {}", argument, max
+        )));
+    }
+    Ok(())
+}
+
+/// A connection cursor naming `kind` and a key, e.g. `market:2a`. Cursors
+/// hold keys rather than offsets, so they stay valid as entries are added
+/// or archived.
+fn encode_cursor(kind: &str, key: u64) -> String {
+    format!("{}:{:x}", kind, key)
+}
+
+fn decode_cursor(kind: &str, cursor: &str) -> async_graphql::Result<u64> {
+    cursor.strip_prefix(kind)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|key| u64::from_str_radix(key, 16).ok())
+        .ok_or_else(|| async_graphql::Error::new(format!("Invalid value for argument \"after\": {:?}", cursor)))
+}
+
+/// Layout version of `exportState` payloads, bumped on incompatible changes
+const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Most records one `exportState` page holds
+const MAX_EXPORT_PAGE_SIZE: u64 = 500;
+
+/// One page of a full state export
+#[derive(SimpleObject)]
+struct StateExport {
+    schema_version: u32,
+    /// JSON object with `schema_version`, `markets` and `positions`
+    payload: String,
+    /// Pass back as `cursor` for the next page; null once the export is done
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExportPayload {
+    schema_version: u32,
+    markets: Vec<Market>,
+    positions: Vec<Position>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ExportSection {
+    Markets,
+    Positions,
+}
+
+/// Where an export resumes: after the serialized key `after` of `section`,
+/// or at its start. Written as `markets:<hex key>` or `positions:<hex key>`.
+struct ExportCursor {
+    section: ExportSection,
+    after: Option<Vec<u8>>,
+}
+
+impl ExportCursor {
+    fn parse(cursor: &str) -> async_graphql::Result<Self> {
+        let invalid = || async_graphql::Error::new(format!("Invalid value for argument \"cursor\": {:?}", cursor));
+        let (section, key) = cursor.split_once(':').ok_or_else(invalid)?;
+        let section = match section {
+            "markets" => ExportSection::Markets,
+            "positions" => ExportSection::Positions,
+            _ => return Err(invalid()),
+        };
+        if key.is_empty() {
+            return Ok(ExportCursor { section, after: None });
+        }
+        if key.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let after = (0..key.len())
+            .step_by(2)
+            .map(|i| key.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        Ok(ExportCursor { section, after: Some(after) })
+    }
+}
+
+impl std::fmt::Display for ExportCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.section {
+            ExportSection::Markets => "markets:",
+            ExportSection::Positions => "positions:",
+        })?;
+        for byte in self.after.iter().flatten() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a serialized key sorts after `after`, as storage orders keys
+fn past_cursor(key: &[u8], after: Option<&[u8]>) -> bool {
+    after.is_none_or(|after| key > after)
+}
+
+/// Spread `seed` over all 64 bits (SplitMix64's finalizer), so nearby
+/// seeds draw unrelated markets
+fn mix_seed(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Longest `ids` list `marketsByIds` accepts
+const MAX_IDS_PER_LOOKUP: usize = 100;
+
+/// The market fields `marketJson` and `marketsJson` return
+const MARKET_JSON_FIELDS: &str = "id key question description categories creator endTimeMicros createdAtMicros \
+    status resolved outcome voided yesPriceBps noPriceBps yesPool { attos tokens } noPool { attos tokens } \
+    volume { attos tokens } liquidity { attos tokens } feeBps";
+
+/// The position fields `positionJson` returns
+const POSITION_JSON_FIELDS: &str = "marketId owner yesShares { attos tokens } noShares { attos tokens } claimed \
+    currentValue { attos tokens } realizedPnl { attos tokens } unrealizedPnl { attos tokens }";
+
+/// The quote fields `quoteJson` returns for a buy and for a sale
+const BUY_QUOTE_JSON_FIELDS: &str = "cost { attos tokens } fee { attos tokens } feeBps priceImpactBps withinTradeCap";
+const SELL_QUOTE_JSON_FIELDS: &str = "proceeds { attos tokens } fee { attos tokens } feeBps yesPriceBps noPriceBps \
+    priceImpactBps maxSellableShares { attos tokens }";
+
+#[Object]
+impl QueryRoot {
+    /// Whether the service is up, which build it is and whether its state
+    /// loaded. Cheap, and the only query that answers without state.
+    async fn health(&self, ctx: &Context<'_>) -> HealthInfo {
+        ctx.data_unchecked::<HealthInfo>().clone()
+    }
+
+    // === Market Queries ===
+    
+    async fn total_volume(&self, ctx: &Context<'_>) -> TokenAmount {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.total_volume.into()
+    }
+    
+    async fn market_count(&self, ctx: &Context<'_>) -> u64 {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.market_count
+    }
+    
+    /// The configuration in force, read afresh on every query
+    async fn config(&self, ctx: &Context<'_>) -> ConfigInfo {
+        ConfigInfo::new(ctx.data_unchecked::<Arc<QueryData>>())
+    }
+
+    /// True while the admin has halted market creation and buying
+    async fn paused(&self, ctx: &Context<'_>) -> bool {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.paused
+    }
+
+    /// Distinct accounts that have ever traded, across all markets
+    async fn unique_traders(&self, ctx: &Context<'_>) -> u64 {
+        ctx.data_unchecked::<Arc<QueryData>>().unique_traders
+    }
+
+    async fn stats(&self, ctx: &Context<'_>) -> async_graphql::Result<StatsInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut top: Vec<&MarketInfo> = data.full().await?.markets.iter().filter(|m| !m.resolved).collect();
+        top.sort_by(|a, b| MarketSort::VolumeDesc.compare(a, b));
+
+        let hour = hour_index(data.now);
+        let mut volume_24h = Amount::ZERO;
+        for bucket in hour.saturating_sub(23)..=hour {
+            let volume = data.state.hourly_volume.get(&bucket).await?.unwrap_or(Amount::ZERO);
+            volume_24h = volume_24h.saturating_add(volume);
         }
 
-        // Collect all agents
-        let mut agents = Vec::new();
-        for id in 0..next_agent_id {
-            if let Ok(Some(agent)) = self.state.agents.get(&id).await {
-                agents.push(AgentInfo::from(agent));
+        Ok(StatsInfo {
+            total_volume: data.total_volume.into(),
+            market_count: data.market_count,
+            active_count: *data.state.active_market_count.get(),
+            resolved_count: *data.state.resolved_market_count.get(),
+            total_value_locked: data.total_value_locked.into(),
+            total_open_interest: (*data.state.total_open_interest.get()).into(),
+            total_active_liquidity: (*data.state.total_active_liquidity.get()).into(),
+            contract_balance: (*data.state.contract_balance.get()).into(),
+            reconciled_deposits: (*data.state.reconciled_deposits.get()).into(),
+            treasury: (*data.state.treasury.get()).into(),
+            unique_traders: data.unique_traders,
+            volume_24h: volume_24h.into(),
+            top_markets: top.into_iter().take(5).cloned().collect(),
+        })
+    }
+
+    /// Markets by status, read from counters the contract keeps up to date
+    async fn market_counts(&self, ctx: &Context<'_>) -> MarketCountsInfo {
+        let state = &ctx.data_unchecked::<Arc<QueryData>>().state;
+        let (active, closed) = (*state.active_market_count.get(), *state.closed_market_count.get());
+        let (resolved, voided) = (*state.resolved_market_count.get(), *state.voided_market_count.get());
+        MarketCountsInfo {
+            open: active.saturating_sub(closed),
+            closed,
+            resolved: resolved.saturating_sub(voided),
+            voided,
+        }
+    }
+
+    /// Markets matching `filter`, by relevance when it searches text,
+    /// then by `sort`, then by id. Without a filter or sort, markets are
+    /// read from state one page at a time. The deprecated `category`,
+    /// `sort_by`, `ends_after` and `ends_before` arguments fill in the
+    /// matching filter and sort fields when those are unset.
+    #[allow(clippy::too_many_arguments)]
+    async fn markets(
+        &self, ctx: &Context<'_>,
+        filter: Option<MarketFilter>,
+        sort: Option<MarketSort>,
+        #[graphql(deprecation = "Use filter.category")] category: Option<String>,
+        #[graphql(deprecation = "Use sort")] sort_by: Option<MarketSort>,
+        #[graphql(deprecation = "Use filter.endsAfter")] ends_after: Option<TimestampScalar>,
+        #[graphql(deprecation = "Use filter.endsBefore")] ends_before: Option<TimestampScalar>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<MarketPage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut filter = filter.unwrap_or_default();
+        filter.category = filter.category.or(category);
+        filter.ends_after = filter.ends_after.or(ends_after);
+        filter.ends_before = filter.ends_before.or(ends_before);
+        let sort = sort.or(sort_by);
+        let query = MarketQuery::parse(filter)?;
+
+        if sort.is_none() && query.is_empty() {
+            let ids = data.market_ids().await?;
+            let total_count = ids.len() as u64;
+            let ids = ids.into_iter().skip(offset as usize).take(limit as usize).collect();
+            return Ok(MarketPage { total_count, rows: PageRows::Ids(ids) });
+        }
+        Ok(MarketPage::of(data.find_markets(&query, sort).await?, limit, offset))
+    }
+
+    /// Listing rows for stored markets by id, read from their summaries
+    async fn market_summaries(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<MarketSummaryPage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let ids = data.market_ids().await?;
+        let page: Vec<u64> = ids.iter().skip(offset as usize).take(limit as usize).copied().collect();
+        Ok(MarketSummaryPage { total_count: ids.len() as u64, summaries: data.load_summaries(&page).await? })
+    }
+
+    /// Markets the admin featured, in the admin's order
+    async fn featured_markets(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<FeaturedMarket>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let ids = data.state.featured_markets.get().clone();
+        let markets = data.load_markets(ids).await?;
+        Ok(markets.into_iter().enumerate().map(|(position, market)| FeaturedMarket {
+            position: position as u64,
+            resolved: market.resolved,
+            market,
+        }).collect())
+    }
+
+    /// The one market the embed widget shows: the first featured market
+    /// still open, else an open market drawn by `seed` with odds in
+    /// proportion to its 24-hour volume (plus one atto, so quiet markets
+    /// still get a chance). The same seed draws the same market until the
+    /// state changes; without one the current time is the seed. Null when
+    /// no market is open.
+    async fn spotlight<'a>(&self, ctx: &Context<'a>, seed: Option<u64>) -> async_graphql::Result<Option<&'a MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let markets = &data.full().await?.markets;
+        let open = |id: u64| markets.iter().find(|m| m.id == id && m.status_at(data.now) == EffectiveStatus::Open);
+        if let Some(featured) = data.state.featured_markets.get().iter().find_map(|id| open(*id)) {
+            return Ok(Some(featured));
+        }
+        let mut candidates = Vec::new();
+        let mut total = 0u128;
+        for market in markets.iter().filter(|m| m.status_at(data.now) == EffectiveStatus::Open) {
+            total = total.saturating_add(u128::from(data.market_volume_24h(market.id).await?)).saturating_add(1);
+            candidates.push((total, market));
+        }
+        if total == 0 {
+            return Ok(None);
+        }
+        let high = mix_seed(seed.unwrap_or(data.now.micros()));
+        let point = ((u128::from(high) << 64) | u128::from(mix_seed(high))) % total;
+        Ok(candidates.into_iter().find(|(cumulative, _)| point < *cumulative).map(|(_, market)| market))
+    }
+
+    /// One market's full record
+    async fn market(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<Option<MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let market = data.state.markets.get(&id).await?.ok_or_else(|| not_found(format!("Market {}", id)))?;
+        Ok(Some(MarketInfo::from(market)))
+    }
+
+    /// One market's full record, found by its `key` rather than its id
+    async fn market_by_key(&self, ctx: &Context<'_>, key: String) -> async_graphql::Result<Option<MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let market_key = MarketKey::from_str(key.trim()).map_err(|e| {
+            async_graphql::Error::new(format!("Invalid market key \"{}\" ({})", key, e))
+                .extend_with(|_, extensions| extensions.set("code", "INVALID_KEY"))
+        })?;
+        let id = data.state.market_keys.get(&market_key).await?
+            .ok_or_else(|| not_found(format!("Market with key {}", key)))?;
+        let market = data.state.markets.get(&id).await?.ok_or_else(|| not_found(format!("Market {}", id)))?;
+        Ok(Some(MarketInfo::from(market)))
+    }
+
+    /// Average YES and NO prices over the last `window_secs`, weighted by how
+    /// long each price held. A window with no trades gives the last price.
+    async fn twap(&self, ctx: &Context<'_>, market_id: u64, window_secs: u64) -> async_graphql::Result<Option<TwapInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        if window_secs == 0 {
+            return Err(async_graphql::Error::new("windowSecs must be greater than zero"));
+        }
+        let market = data.state.markets.get(&market_id)
+            .await?
+            .ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        let checkpoints = data.state.price_checkpoints.get(&market_id).await?.unwrap_or_default();
+        let end = market.resolution_time.unwrap_or(data.now);
+        let requested = end.saturating_sub(TimeDelta::from_secs(window_secs));
+        let start = checkpoints.first().map_or(end, |first| requested.max(first.timestamp));
+        let yes_bps = if start < end {
+            // The last checkpoint at or before `start` prices the window's first stretch
+            let index = checkpoints.partition_point(|c| c.timestamp <= start) - 1;
+            let from = checkpoints[index].cumulative_at(start);
+            let to = market.price_cumulative_at(end);
+            to.wrapping_sub(from) as f64 / end.delta_since(start).as_micros() as f64
+        } else {
+            market.yes_price_bps() as f64
+        };
+        let yes_price = yes_bps / 10_000.0;
+        Ok(Some(TwapInfo {
+            market_id,
+            window_start: format!("{}", start.micros()),
+            window_end: format!("{}", end.micros()),
+            yes_price,
+            no_price: 1.0 - yes_price,
+        }))
+    }
+
+    /// A market's implied prices as of `timestamp`, from the latest price
+    /// snapshot at or before it: null before the market was created, the
+    /// final prices from resolution on (100% to the winner; a voided market
+    /// keeps its last AMM prices). A binary search of the market's price
+    /// log, so O(log n) reads however long its history.
+    async fn price_at(&self, ctx: &Context<'_>, market_id: u64, timestamp: TimestampScalar) -> async_graphql::Result<Option<PriceAtInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let market = data.state.markets.get(&market_id)
+            .await?
+            .ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        let at = timestamp.0;
+        if at < market.created_at {
+            return Ok(None);
+        }
+        let (as_of, yes_price_bps, is_final) = match market.resolution_time {
+            Some(resolved_at) if market.resolved && at >= resolved_at => {
+                let yes_bps = match market.outcome {
+                    Some(true) if !market.voided => 10_000,
+                    Some(false) if !market.voided => 0,
+                    _ => market.yes_price_bps(),
+                };
+                (resolved_at, yes_bps, true)
+            }
+            _ => {
+                let (mut low, mut high) = (0, data.state.price_log_counts.get(&market_id).await?.unwrap_or(0));
+                while low < high {
+                    let middle = low + (high - low) / 2;
+                    if data.state.price_log.get(&(market_id, middle)).await?.is_some_and(|c| c.timestamp <= at) {
+                        low = middle + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+                let logged = match low.checked_sub(1) {
+                    Some(n) => data.state.price_log.get(&(market_id, n)).await?,
+                    None => None,
+                };
+                // Markets from before the log only have their retained
+                // checkpoints; failing those, the opening even odds
+                let found = match logged {
+                    Some(checkpoint) => Some(checkpoint),
+                    None => {
+                        let checkpoints = data.state.price_checkpoints.get(&market_id).await?.unwrap_or_default();
+                        checkpoints.partition_point(|c| c.timestamp <= at)
+                            .checked_sub(1)
+                            .map(|index| checkpoints[index].clone())
+                    }
+                };
+                found.map_or((market.created_at, 5_000, false), |c| (c.timestamp, c.yes_price_bps, false))
+            }
+        };
+        let yes_price = yes_price_bps as f64 / 10_000.0;
+        Ok(Some(PriceAtInfo {
+            market_id,
+            as_of_micros: TimestampScalar(as_of),
+            yes_price,
+            no_price: 1.0 - yes_price,
+            yes_price_bps,
+            no_price_bps: 10_000 - yes_price_bps,
+            is_final,
+        }))
+    }
+
+    /// The YES price over a market's life, rebuilt from its trades: the
+    /// opening 50% and then the price after each trade, sampled down to at
+    /// most `max_points` evenly spaced in time. The last point is the price
+    /// after the latest trade.
+    async fn probability_history(
+        &self, ctx: &Context<'_>,
+        market_id: u64,
+        #[graphql(default = 100)] max_points: u64,
+    ) -> async_graphql::Result<Vec<ProbabilityPoint>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("maxPoints", max_points, MAX_HISTORY_POINTS)?;
+        let market = data.state.markets.get(&market_id)
+            .await?
+            .ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        let indexed = ActivityIndex {
+            counts: &data.state.market_activity_counts,
+            index: &data.state.market_activity,
+            key: market_id,
+        };
+        let mut trades = Vec::new();
+        for n in 0..indexed.len().await? {
+            if let Some(trade) = data.indexed_trade(&indexed, n).await? {
+                trades.push((trade.timestamp_micros, trade.yes_price_bps));
             }
         }
+        Ok(probability_points(market.created_at.micros(), &trades, max_points))
+    }
 
-        // Collect recent feed items (last 100)
-        let mut feed_items = Vec::new();
-        let start = if next_feed_id > 100 { next_feed_id - 100 } else { 0 };
-        for id in start..next_feed_id {
-            if let Ok(Some(item)) = self.state.feed_items.get(&id).await {
-                feed_items.push(FeedItemInfo::from(item));
+    /// Markets in the order of `ids`, null where an id matches no market.
+    /// Reads one market per id; at most `MAX_IDS_PER_LOOKUP` ids.
+    async fn markets_by_ids(&self, ctx: &Context<'_>, ids: Vec<u64>) -> async_graphql::Result<Vec<Option<MarketInfo>>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        if ids.len() > MAX_IDS_PER_LOOKUP {
+            return Err(async_graphql::Error::new(format!(
+                "Too many ids: {} requested, at most {} per query", ids.len(), MAX_IDS_PER_LOOKUP
+            )));
+        }
+        let mut markets = Vec::with_capacity(ids.len());
+        for id in ids {
+            markets.push(data.state.markets.get(&id).await?.map(MarketInfo::from));
+        }
+        Ok(markets)
+    }
+
+    /// Unresolved markets whose end time is still ahead, optionally only
+    /// those ending at or after `ends_after` and before `ends_before`
+    async fn active_markets(
+        &self, ctx: &Context<'_>,
+        category: Option<String>,
+        sort_by: Option<MarketSort>,
+        ends_after: Option<TimestampScalar>,
+        ends_before: Option<TimestampScalar>,
+    ) -> async_graphql::Result<Vec<MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let filter = MarketFilter {
+            status: Some(MarketStatus::Active),
+            category,
+            ends_after,
+            ends_before,
+            ..MarketFilter::default()
+        };
+        Ok(data.find_markets(&MarketQuery::parse(filter)?, sort_by).await?)
+    }
+
+    /// Unresolved markets still open and ending within `within_secs`, soonest first
+    async fn ending_soon<'a>(&self, ctx: &Context<'a>, within_secs: u64, #[graphql(default = 10)] limit: u64) -> async_graphql::Result<Vec<&'a MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let now = data.now;
+        let horizon = data.now.saturating_add(TimeDelta::from_secs(within_secs));
+        let mut markets: Vec<&MarketInfo> = data.full().await?.markets.iter()
+            .filter(|m| m.status_at(now) == EffectiveStatus::Open && m.end_time_micros.0 <= horizon)
+            .collect();
+        markets.sort_by(|a, b| MarketSort::EndTimeAsc.compare(a, b));
+        markets.truncate(limit as usize);
+        Ok(markets)
+    }
+
+    /// Volume per UTC day for the last `days` days, today included, oldest
+    /// first; days without trades show zero
+    async fn volume_history(&self, ctx: &Context<'_>, #[graphql(default = 30)] days: u64) -> async_graphql::Result<Vec<DailyVolume>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("days", days, MAX_VOLUME_HISTORY_DAYS)?;
+        let today = day_index(data.now);
+        let mut history = Vec::new();
+        for day in today.saturating_sub(days - 1)..=today {
+            let volume = data.state.volume_by_day.get(&day).await?.unwrap_or(Amount::ZERO);
+            let (year, month, date) = utc_civil_date(Timestamp::from(day * DAY_MICROS));
+            history.push(DailyVolume {
+                day,
+                date: format!("{:04}-{:02}-{:02}", year, month, date),
+                volume: volume.into(),
+            });
+        }
+        Ok(history)
+    }
+
+    /// The latest trades across all markets, newest first
+    async fn recent_trades(&self, ctx: &Context<'_>, #[graphql(default = 20)] limit: u64) -> async_graphql::Result<Vec<RecentTradeInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("limit", limit, u64::from(MAX_RECENT_TRADES_CAPACITY))?;
+        let mut trades = data.state.recent_trades.read_back(limit as usize).await?;
+        trades.reverse();
+        Ok(trades.into_iter().map(RecentTradeInfo::from).collect())
+    }
+
+    /// Open markets with the most volume over the last 24 hours; among
+    /// equal volumes, newer markets first
+    async fn trending(&self, ctx: &Context<'_>, #[graphql(default = 10)] limit: u64) -> async_graphql::Result<Vec<MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut ranked = Vec::new();
+        for market in data.full().await?.markets.iter().filter(|m| m.status_at(data.now) == EffectiveStatus::Open) {
+            ranked.push((data.market_volume_24h(market.id).await?, market));
+        }
+        ranked.sort_by(|(va, a), (vb, b)| vb.cmp(va).then(b.id.cmp(&a.id)));
+        Ok(ranked.into_iter().take(limit as usize).map(|(_, market)| market.clone()).collect())
+    }
+
+    /// Resolved markets by id, optionally only those that settled as
+    /// `outcome` or were resolved at or after `resolved_after` and before
+    /// `resolved_before`
+    async fn resolved_markets(
+        &self, ctx: &Context<'_>,
+        outcome: Option<ResolvedOutcome>,
+        resolved_after: Option<TimestampScalar>,
+        resolved_before: Option<TimestampScalar>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<MarketPage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let (after, before) = (resolved_after.map(|t| t.0), resolved_before.map(|t| t.0));
+        let filter = MarketFilter { status: Some(MarketStatus::Resolved), ..MarketFilter::default() };
+        let matches: Vec<MarketInfo> = data.find_markets(&MarketQuery::parse(filter)?, None).await?
+            .into_iter()
+            .filter(|m| match outcome {
+                None => true,
+                Some(ResolvedOutcome::Yes) => m.outcome == Some(true),
+                Some(ResolvedOutcome::No) => m.outcome == Some(false),
+                Some(ResolvedOutcome::Void) => m.voided,
+            })
+            .filter(|m| {
+                let resolved_at = m.resolution_time_micros.unwrap_or_default();
+                after.is_none_or(|t| resolved_at >= t.micros()) && before.is_none_or(|t| resolved_at < t.micros())
+            })
+            .collect();
+        Ok(MarketPage::of(matches, limit, offset))
+    }
+
+    /// Markets pruned by ArchiveMarket, by id
+    async fn archived_markets(
+        &self, ctx: &Context<'_>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<ArchivePage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut markets = Vec::new();
+        data.state.archive.for_each_index_value(|_, archived| {
+            markets.push(archived.into_owned());
+            Ok(())
+        }).await?;
+        markets.sort_unstable_by_key(|archived| archived.id);
+        Ok(ArchivePage {
+            total_count: markets.len() as u64,
+            markets: markets.into_iter().skip(offset as usize).take(limit as usize).map(ArchivedMarketInfo::from).collect(),
+        })
+    }
+
+    async fn archived_market(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<ArchivedMarketInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.state.archive.get(&id).await?
+            .map(ArchivedMarketInfo::from)
+            .ok_or_else(|| not_found(format!("Archived market {}", id)))
+    }
+
+    /// Local markets followed by markets announced from other chains
+    async fn listed_markets(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<ListedMarket>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let local = data.full().await?.markets.iter().map(|m| ListedMarket {
+            origin_chain: data.chain_id.clone(),
+            market_id: m.id,
+            question: m.question.clone(),
+            categories: m.categories.clone(),
+            end_time: m.end_time.clone(),
+            market_key: m.key.clone(),
+            is_local: true,
+        });
+        Ok(local.chain(data.remote_markets.iter().cloned()).collect())
+    }
+
+    /// Every category in the index, most active markets first, then by name
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CategoryInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let markets = &data.full().await?.markets;
+        let mut categories = Vec::new();
+        data.state.markets_by_category.for_each_index_value(|name, ids| {
+            let active_count = ids.iter()
+                .filter(|id| {
+                    markets.binary_search_by_key(*id, |m| m.id).is_ok_and(|i| !markets[i].resolved)
+                })
+                .count();
+            categories.push(CategoryInfo {
+                name,
+                market_count: ids.len() as u64,
+                active_count: active_count as u64,
+            });
+            Ok(())
+        }).await?;
+        categories.sort_by(|a, b| b.active_count.cmp(&a.active_count).then_with(|| a.name.cmp(&b.name)));
+        Ok(categories)
+    }
+
+    /// Participation per category, most traders first; categories nobody has
+    /// traded in yet count zero
+    async fn category_stats(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<CategoryStatsInfo>> {
+        let state = &ctx.data_unchecked::<Arc<QueryData>>().state;
+        let mut stats = Vec::new();
+        state.markets_by_category.for_each_index_value(|name, ids| {
+            stats.push(CategoryStatsInfo { name, trader_count: 0, market_count: ids.len() as u64 });
+            Ok(())
+        }).await?;
+        for category in &mut stats {
+            category.trader_count = state.category_trader_counts.get(&category.name).await?.unwrap_or(0);
+        }
+        stats.sort_by(|a, b| b.trader_count.cmp(&a.trader_count).then_with(|| a.name.cmp(&b.name)));
+        Ok(stats)
+    }
+
+    /// Markets tagged with `category`, matched case-insensitively, by id
+    async fn markets_by_category(&self, ctx: &Context<'_>, category: String) -> async_graphql::Result<Vec<MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let filter = MarketFilter { category: Some(category), ..MarketFilter::default() };
+        Ok(data.find_markets(&MarketQuery::parse(filter)?, None).await?)
+    }
+
+    /// Filter by status and categories, then rank by how well the question
+    /// and categories match `query`, then sort and paginate. A blank query
+    /// matches nothing.
+    #[allow(clippy::too_many_arguments)]
+    async fn search_markets(
+        &self, ctx: &Context<'_>,
+        query: Option<String>,
+        categories: Option<Vec<String>>,
+        status: Option<MarketStatus>,
+        sort_by: Option<MarketSort>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<MarketPage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let query = MarketQuery {
+            status,
+            categories: categories.map(|cs| cs.iter().map(|c| c.to_lowercase()).collect()),
+            terms: query.map(|q| q.split_whitespace().map(str::to_lowercase).collect()),
+            ..MarketQuery::default()
+        };
+        // Ties on relevance break on the sort and then id so pages stay stable
+        let sort_by = sort_by.unwrap_or(MarketSort::VolumeDesc);
+        Ok(MarketPage::of(data.find_markets(&query, Some(sort_by)).await?, limit, offset))
+    }
+
+    /// Markets created by `creator`, oldest first. The creator is written as
+    /// `MarketInfo.creator` shows it.
+    async fn markets_by_creator(
+        &self, ctx: &Context<'_>,
+        creator: String,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> async_graphql::Result<MarketPage> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let filter = MarketFilter { creator: Some(creator), ..MarketFilter::default() };
+        Ok(MarketPage::of(data.find_markets(&MarketQuery::parse(filter)?, None).await?, limit, offset))
+    }
+
+    /// Created markets, open positions and claimable winnings in one round trip
+    async fn my_activity(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<ActivityInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let held: Vec<&HeldPosition> = data.full().await?.positions.iter()
+            .filter(|h| h.position.owner == owner)
+            .collect();
+        let open_positions = held.iter()
+            .filter(|h| !h.market_resolved)
+            .filter(|h| h.position.yes_shares > Amount::ZERO || h.position.no_shares > Amount::ZERO)
+            .map(|h| PositionInfo::new(h.position.clone(), h.value))
+            .collect();
+        let claimable: Vec<(u64, Amount)> = held.iter()
+            .filter(|h| h.claimable > Amount::ZERO)
+            .map(|h| (h.position.market_id, h.claimable))
+            .collect();
+        let total_claimable = claimable.iter()
+            .fold(Amount::ZERO, |sum, (_, amount)| sum.saturating_add(*amount));
+
+        Ok(ActivityInfo {
+            markets_created: data.created_by(&owner).await?,
+            open_positions,
+            claimable: claimable.into_iter()
+                .map(|(market_id, amount)| ClaimableInfo { market_id, amount: amount.into() })
+                .collect(),
+            total_claimable: total_claimable.into(),
+        })
+    }
+
+    /// `owner`'s position in one market, or null if they never traded it
+    async fn position(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> async_graphql::Result<Option<PositionInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let Some(position) = data.state.positions.get(&(owner, market_id)).await? else {
+            return Ok(None);
+        };
+        let value = data.state.markets.get(&market_id).await?
+            .map(|m| m.position_value(&position, data.now))
+            .unwrap_or(Amount::ZERO);
+        Ok(Some(PositionInfo::new(position, value)))
+    }
+
+    /// Positions `owner` migrated to this chain from the chains of their
+    /// markets, which must be returned there before they can be claimed
+    async fn imported_positions(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<Vec<ImportedPositionInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let mut held = Vec::new();
+        data.state.imported_positions.for_each_index_value(|(market_chain, _, holder), position| {
+            if holder == owner {
+                held.push(ImportedPositionInfo::new(market_chain.to_string(), position.into_owned()));
+            }
+            Ok(())
+        }).await?;
+        Ok(held)
+    }
+
+    /// Positions sent from this chain and not yet accepted, by transfer id
+    async fn pending_transfers(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<PositionTransferInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut transfers = Vec::new();
+        data.state.outgoing_transfers.for_each_index_value(|_, transfer| {
+            transfers.push(PositionTransferInfo::from(transfer.into_owned()));
+            Ok(())
+        }).await?;
+        transfers.sort_by_key(|t| t.transfer_id);
+        Ok(transfers)
+    }
+
+    async fn trader_stats(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<TraderStatsInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let account = parse_owner("owner", &owner)?;
+        let stats = data.state.trader_stats.get(&account).await?.unwrap_or_default();
+        Ok(TraderStatsInfo::new(account, stats))
+    }
+
+    /// Accounts that have traded, by realized PnL and then volume, highest
+    /// first
+    async fn leaderboard(&self, ctx: &Context<'_>, #[graphql(default = 10)] limit: u64) -> async_graphql::Result<Vec<TraderStatsInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("limit", limit, MAX_LEADERBOARD_SIZE)?;
+        let mut ranked = Vec::new();
+        data.state.trader_stats.for_each_index_value(|owner, stats| {
+            ranked.push((owner, stats.into_owned()));
+            Ok(())
+        }).await?;
+        ranked.sort_by(|(a, sa), (b, sb)| {
+            (sb.realized_pnl, sb.volume).cmp(&(sa.realized_pnl, sa.volume)).then_with(|| a.to_string().cmp(&b.to_string()))
+        });
+        Ok(ranked.into_iter().take(limit as usize).map(|(owner, stats)| TraderStatsInfo::new(owner, stats)).collect())
+    }
+
+    /// Accounts ranked by realized PnL or volume over a window, highest
+    /// first and equal values by address, leaving out those with fewer than
+    /// `minTrades` trades in it. The 7- and 30-day windows sum daily buckets,
+    /// so they cover today and the whole UTC days before it.
+    async fn top_traders(
+        &self,
+        ctx: &Context<'_>,
+        window: Option<TraderWindow>,
+        by: Option<TraderMetric>,
+        #[graphql(default = 10)] limit: u64,
+        #[graphql(default_with = "MIN_TOP_TRADER_TRADES")] min_trades: u64,
+    ) -> async_graphql::Result<Vec<TopTraderInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("limit", limit, MAX_LEADERBOARD_SIZE)?;
+        let mut ranked = Vec::new();
+        match window.unwrap_or(TraderWindow::AllTime).days() {
+            None => {
+                data.state.trader_stats.for_each_index_value(|owner, stats| {
+                    ranked.push((owner, stats.volume, stats.realized_pnl, stats.trade_count));
+                    Ok(())
+                }).await?;
+            }
+            Some(days) => {
+                data.state.trader_days.for_each_index_value(|owner, buckets| {
+                    let sum = buckets.window(data.now, days);
+                    ranked.push((owner, sum.volume, sum.realized_pnl, sum.trade_count));
+                    Ok(())
+                }).await?;
             }
         }
-        feed_items.reverse(); // Most recent first
-        
-        let schema = Schema::build(
-            QueryRoot { 
-                total_volume,
-                market_count: next_market_id,
-                markets,
-                orders,
-                combos,
-                agents,
-                feed_items,
+        ranked.retain(|(_, _, _, trades)| *trades >= min_trades);
+
+        let by = by.unwrap_or(TraderMetric::Pnl);
+        let metric = |volume: Amount, pnl: i128| match by {
+            TraderMetric::Pnl => pnl,
+            TraderMetric::Volume => i128::try_from(u128::from(volume)).unwrap_or(i128::MAX),
+        };
+        ranked.sort_by(|(a, va, pa, _), (b, vb, pb, _)| {
+            metric(*vb, *pb).cmp(&metric(*va, *pa)).then_with(|| a.to_string().cmp(&b.to_string()))
+        });
+        Ok(ranked
+            .into_iter()
+            .take(limit as usize)
+            .zip(1..)
+            .map(|((owner, volume, pnl, trade_count), rank)| TopTraderInfo {
+                rank,
+                owner: owner.to_string(),
+                value: metric(volume, pnl).into(),
+                trade_count,
+            })
+            .collect())
+    }
+
+    /// Value of `owner`'s unresolved positions and unclaimed winnings,
+    /// reading only the markets they hold positions in
+    async fn portfolio(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<PortfolioInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let held = data.positions_of(owner).await?;
+
+        let (mut total_value, mut claimable, mut cost_basis) = (Amount::ZERO, Amount::ZERO, Amount::ZERO);
+        let mut position_count = 0;
+        for position in held {
+            let Some(market) = data.state.markets.get(&position.market_id).await? else {
+                continue;
+            };
+            let value = market.position_value(&position, data.now);
+            let counted = if market.resolved {
+                claimable = claimable.saturating_add(value);
+                value > Amount::ZERO
+            } else {
+                position.yes_shares > Amount::ZERO || position.no_shares > Amount::ZERO
+            };
+            if counted {
+                position_count += 1;
+                total_value = total_value.saturating_add(value);
+                cost_basis = cost_basis.saturating_add(position.yes_cost_basis.saturating_add(position.no_cost_basis));
+            }
+        }
+
+        Ok(PortfolioInfo {
+            total_value: total_value.into(),
+            claimable: claimable.into(),
+            position_count,
+            cost_basis: cost_basis.into(),
+            unrealized_pnl: SignedTokenAmount::difference(total_value, cost_basis),
+        })
+    }
+
+    /// `owner`'s open positions and those with something to claim, each
+    /// list ordered by `sort` (highest value first by default), with totals.
+    /// Reads the owner's position index rather than every position.
+    async fn my_positions(&self, ctx: &Context<'_>, owner: String, sort: Option<PositionSort>) -> async_graphql::Result<MyPositionsInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let ids = data.state.positions_by_owner.get(&owner).await?.unwrap_or_default();
+
+        let (mut open, mut to_claim) = (Vec::new(), Vec::new());
+        let (mut total_value, mut total_cost, mut total_claimable) = (Amount::ZERO, Amount::ZERO, Amount::ZERO);
+        for market_id in ids {
+            let Some(market) = data.state.markets.get(&market_id).await? else {
+                continue;
+            };
+            let Some(position) = data.state.positions.get(&(owner, market_id)).await? else {
+                continue;
+            };
+            let value = market.position_value(&position, data.now);
+            let list = if !market.resolved && !position.is_empty() {
+                &mut open
+            } else if market.resolved && value > Amount::ZERO {
+                total_claimable = total_claimable.saturating_add(value);
+                &mut to_claim
+            } else {
+                continue;
+            };
+            total_value = total_value.saturating_add(value);
+            total_cost = total_cost.saturating_add(position.yes_cost_basis.saturating_add(position.no_cost_basis));
+            list.push(PortfolioEntry {
+                unrealized_pnl: position.unrealized_pnl(value),
+                value,
+                position: PositionInfo::new(position, value),
+                market: MarketInfo::from(market),
+            });
+        }
+        let sort = sort.unwrap_or(PositionSort::ValueDesc);
+        open.sort_by(|a, b| sort.compare(a, b));
+        to_claim.sort_by(|a, b| sort.compare(a, b));
+
+        Ok(MyPositionsInfo {
+            open,
+            to_claim,
+            totals: PositionTotalsInfo {
+                total_value: total_value.into(),
+                total_cost: total_cost.into(),
+                total_unrealized_pnl: SignedTokenAmount::difference(total_value, total_cost),
+                total_claimable: total_claimable.into(),
             },
-            Operation::mutation_root(self.runtime.clone()),
-            EmptySubscription,
-        )
-        .finish();
-        schema.execute(request).await
+        })
     }
-}
 
-struct QueryRoot {
-    total_volume: Amount,
-    market_count: u64,
-    markets: Vec<MarketInfo>,
-    orders: Vec<LimitOrderInfo>,
-    combos: Vec<ComboInfo>,
-    agents: Vec<AgentInfo>,
-    feed_items: Vec<FeedItemInfo>,
-}
+    /// What ClaimWinnings would pay `owner` on one market right now
+    async fn claimable_winnings(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> async_graphql::Result<ClaimPreview> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let market = data.state.markets.get(&market_id).await?;
+        let position = data.state.positions.get(&(owner, market_id)).await?;
+        let (amount, reason) = match claim_preview(market.as_ref(), position.as_ref(), data.now) {
+            Ok(amount) => (Some(amount.saturating_add(data.bond_share(owner, market_id).await?).into()), None),
+            Err(reason) => (None, Some(reason.to_string())),
+        };
+        Ok(ClaimPreview { market_id, amount, reason })
+    }
 
-#[Object]
-impl QueryRoot {
-    // === Market Queries ===
-    
-    async fn total_volume(&self) -> String {
-        format!("{}", self.total_volume)
+    /// Sum of what ClaimWinnings would pay `owner` across resolved markets
+    async fn claimable_total(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<TokenAmount> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let mut total = Amount::ZERO;
+        for position in data.positions_of(owner).await? {
+            let market = data.state.markets.get(&position.market_id).await?;
+            if let Ok(amount) = claim_preview(market.as_ref(), Some(&position), data.now) {
+                total = total.saturating_add(amount).saturating_add(data.bond_share(owner, position.market_id).await?);
+            }
+        }
+        Ok(total.into())
     }
-    
-    async fn market_count(&self) -> u64 {
-        self.market_count
+
+    /// Liquidity `owner` provides across all markets
+    async fn lp_positions<'a>(&self, ctx: &Context<'a>, owner: String) -> async_graphql::Result<Vec<&'a LpPositionInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        Ok(data.full().await?.lp_positions.iter().filter(|lp| lp.owner_key == owner).collect())
     }
-    
-    async fn markets(&self) -> &Vec<MarketInfo> {
-        &self.markets
+
+    async fn lp_position<'a>(&self, ctx: &Context<'a>, owner: String, market_id: u64) -> async_graphql::Result<Option<&'a LpPositionInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        Ok(data.full().await?.lp_positions.iter().find(|lp| lp.owner_key == owner && lp.market_id == market_id))
     }
-    
-    async fn market(&self, id: u64) -> Option<&MarketInfo> {
-        self.markets.iter().find(|m| m.id == id)
+
+    /// Profit and loss of `owner`'s liquidity in a market, fees included
+    async fn lp_pnl(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> async_graphql::Result<LpPnlInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let market = data.state.markets.get(&market_id).await?.ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        let lp = data.state.lp_positions.get(&(owner, market_id))
+            .await?
+            .ok_or_else(|| not_found(format!("LP position of {} in market {}", owner, market_id)))?;
+        // Stakes seeded before ledgers were kept deposited one token per share
+        let ledger = data.state.lp_ledgers.get(&(owner, market_id)).await?.unwrap_or_else(|| LpLedger {
+            deposited: lp.shares,
+            ..LpLedger::default()
+        });
+        Ok(LpPnlInfo::new(&lp, ledger, &market))
     }
-    
-    async fn active_markets(&self) -> Vec<&MarketInfo> {
-        self.markets.iter().filter(|m| !m.resolved).collect()
+
+    /// What selling `shares` of one side to a market's AMM would pay right
+    /// now, fee deducted, ignoring resting orders, by the contract's sell
+    /// math; null once trading has closed
+    async fn proceeds_from_sell(
+        &self,
+        ctx: &Context<'_>,
+        market_id: u64,
+        is_yes: bool,
+        shares: AmountScalar,
+    ) -> async_graphql::Result<Option<SellQuote>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let mut market = data.state.markets.get(&market_id).await?.ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        if effective_status(&market, data.now) != EffectiveStatus::Open {
+            return Ok(None);
+        }
+        let shares = u128::from(shares.0);
+        let (pool_in, pool_out) = if is_yes { (market.yes_pool, market.no_pool) } else { (market.no_pool, market.yes_pool) };
+        let (pool_in, pool_out) = (u128::from(pool_in), u128::from(pool_out));
+        let fee_bps = market.fee_bps_at(data.config.fee_bps, data.now);
+        let absorbable = max_sell_shares(pool_in, pool_out, u128::from(market.escrow))?;
+        let max_sellable = match (market.max_trade_shares.map(u128::from), absorbable) {
+            (Some(cap), Some(absorbable)) => Some(cap.min(absorbable)),
+            (cap, absorbable) => cap.or(absorbable),
+        };
+        let mut quote = SellQuote {
+            proceeds: None,
+            fee: None,
+            fee_bps,
+            yes_price_bps: None,
+            no_price_bps: None,
+            price_impact_bps: None,
+            max_sellable_shares: max_sellable.map(|max| Amount::from_attos(max).into()),
+        };
+        if max_sellable.is_some_and(|max| shares > max) {
+            return Ok(Some(quote));
+        }
+        let gross = sell_proceeds(pool_in, pool_out, shares)?;
+        let fee = safe_mul_div(gross, u128::from(fee_bps), 10_000)?;
+        let (sold, paid) = (Amount::from_attos(shares), Amount::from_attos(gross));
+        if is_yes {
+            market.yes_pool = market.yes_pool.saturating_add(sold);
+            market.no_pool = market.no_pool.saturating_sub(paid);
+        } else {
+            market.no_pool = market.no_pool.saturating_add(sold);
+            market.yes_pool = market.yes_pool.saturating_sub(paid);
+        }
+        quote.proceeds = Some(Amount::from_attos(gross - fee).into());
+        quote.fee = Some(Amount::from_attos(fee).into());
+        quote.yes_price_bps = Some(market.yes_price_bps());
+        quote.no_price_bps = Some(market.no_price_bps());
+        quote.price_impact_bps = Some(sell_price_impact_bps(pool_in, shares)?);
+        Ok(Some(quote))
     }
-    
-    async fn resolved_markets(&self) -> Vec<&MarketInfo> {
-        self.markets.iter().filter(|m| m.resolved).collect()
+
+    /// What `input` would do if `owner` submitted it now: the cost or
+    /// payout, fee, price impact and resulting position by the contract's
+    /// math and current config, or why the contract would refuse it
+    async fn estimate(&self, ctx: &Context<'_>, owner: String, input: EstimateInput) -> async_graphql::Result<Estimate> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let estimate = match input {
+            EstimateInput::Buy(TradeInput { market_id, is_yes, shares }) => data.estimate_buy(owner, market_id, is_yes, shares.0).await,
+            EstimateInput::BuyForCost(BudgetInput { market_id, is_yes, budget }) => {
+                data.estimate_buy_for_cost(owner, market_id, is_yes, budget.0).await
+            }
+            EstimateInput::Sell(TradeInput { market_id, is_yes, shares }) => data.estimate_sell(owner, market_id, is_yes, shares.0).await,
+            EstimateInput::Claim(ClaimInput { market_id }) => data.estimate_claim(owner, market_id).await,
+        };
+        match estimate {
+            Ok(estimate) => Ok(estimate),
+            Err(Refusal::Reason(reason)) => Ok(Estimate { reason: Some(reason), ..Estimate::default() }),
+            Err(Refusal::View(error)) => Err(error.into()),
+        }
+    }
+
+    /// The incentive program funded on a market, if any
+    async fn incentives(&self, ctx: &Context<'_>, market_id: u64) -> async_graphql::Result<Option<IncentivesInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let program = data.state.incentives.get(&market_id).await?;
+        Ok(program.map(|program| IncentivesInfo::new(program, data.now)))
+    }
+
+    /// Incentive rewards `owner` could claim on a market now
+    async fn pending_incentives(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> async_graphql::Result<TokenAmount> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let (Some(mut program), Some(market), Some(lp)) = (
+            data.state.incentives.get(&market_id).await?,
+            data.state.markets.get(&market_id).await?,
+            data.state.lp_positions.get(&(owner, market_id)).await?,
+        ) else {
+            return Ok(Amount::ZERO.into());
+        };
+        let checkpoint = data.state.incentive_checkpoints.get(&(owner, market_id)).await?.unwrap_or(0);
+        program.accrue(market.total_lp_shares, data.now);
+        Ok(program.pending(lp.shares, checkpoint).into())
+    }
+
+    /// The bond escrowed by a market's creator, if one was required
+    async fn creator_bond(&self, ctx: &Context<'_>, market_id: u64) -> async_graphql::Result<Option<CreatorBondInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        Ok(data.state.creator_bonds.get(&market_id).await?.map(CreatorBondInfo::from))
+    }
+
+    /// Forfeited-bond share `owner` receives with their refund on a market
+    async fn bond_share(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> async_graphql::Result<TokenAmount> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        Ok(data.bond_share(owner, market_id).await?.into())
+    }
+
+    // === Series Queries ===
+
+    async fn market_series<'a>(&self, ctx: &Context<'a>) -> &'a Vec<SeriesInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        &data.series
+    }
+
+    async fn series<'a>(&self, ctx: &Context<'a>, id: u64) -> async_graphql::Result<Option<&'a SeriesInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.series.iter().find(|s| s.id == id).map(Some).ok_or_else(|| not_found(format!("Series {}", id)))
+    }
+
+    // === Group Queries ===
+
+    async fn groups(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<GroupInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let markets = &data.full().await?.markets;
+        let mut groups = Vec::new();
+        data.state.groups.for_each_index_value(|_, group| {
+            groups.push(GroupInfo::new(group.into_owned(), markets));
+            Ok(())
+        }).await?;
+        Ok(groups)
+    }
+
+    async fn group(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<Option<GroupInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let group = data.state.groups.get(&id).await?.ok_or_else(|| not_found(format!("Group {}", id)))?;
+        Ok(Some(GroupInfo::new(group, &data.full().await?.markets)))
     }
 
-    async fn markets_by_category(&self, category: String) -> Vec<&MarketInfo> {
-        self.markets.iter().filter(|m| m.categories.contains(&category)).collect()
+    // === Resolution Queries ===
+
+    async fn resolution_tally(&self, ctx: &Context<'_>, market_id: u64) -> async_graphql::Result<Option<ResolutionTally>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let market = data.state.markets.get(&market_id).await?
+            .ok_or_else(|| not_found(format!("Market {}", market_id)))?;
+        let votes = data.state.resolution_votes.get(&market_id).await?.unwrap_or_default();
+        let yes_votes = votes.iter().filter(|vote| vote.outcome).count() as u32;
+        Ok(Some(ResolutionTally {
+            market_id,
+            threshold: market.threshold,
+            yes_votes,
+            no_votes: votes.len() as u32 - yes_votes,
+            votes: votes.into_iter().map(ResolutionVoteInfo::from).collect(),
+        }))
     }
 
     // === Limit Order Queries ===
     
-    async fn limit_orders(&self) -> &Vec<LimitOrderInfo> {
-        &self.orders
+    async fn limit_orders<'a>(&self, ctx: &Context<'a>) -> &'a Vec<LimitOrderInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        &data.orders
+    }
+
+    async fn limit_order<'a>(&self, ctx: &Context<'a>, market_id: u64, id: u64) -> async_graphql::Result<Option<&'a LimitOrderInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.orders.iter()
+            .find(|o| o.market_id == market_id && o.id == id)
+            .map(Some)
+            .ok_or_else(|| not_found(format!("Order {} in market {}", id, market_id)))
+    }
+
+    async fn orders_by_market<'a>(&self, ctx: &Context<'a>, market_id: u64) -> Vec<&'a LimitOrderInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.orders.iter().filter(|o| o.market_id == market_id && o.is_open()).collect()
     }
 
-    async fn limit_order(&self, id: u64) -> Option<&LimitOrderInfo> {
-        self.orders.iter().find(|o| o.id == id)
+    /// Open orders belonging to `owner`, across all markets
+    async fn open_orders<'a>(&self, ctx: &Context<'a>, owner: String) -> async_graphql::Result<Vec<&'a LimitOrderInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        Ok(data.orders.iter().filter(|o| o.owner_key == owner && o.is_open()).collect())
     }
 
-    async fn orders_by_market(&self, market_id: u64) -> Vec<&LimitOrderInfo> {
-        self.orders.iter().filter(|o| o.market_id == market_id && o.status == "Open").collect()
+    /// Maker rebates `owner` has earned and not yet withdrawn
+    async fn maker_rebates(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<TokenAmount> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        Ok(data.state.maker_rebates.get(&owner).await?.unwrap_or_default().into())
     }
 
-    async fn open_orders(&self) -> Vec<&LimitOrderInfo> {
-        self.orders.iter().filter(|o| o.status == "Open" || o.status == "PartiallyFilled").collect()
+    /// Pending trigger orders belonging to `owner`, across all markets
+    async fn open_trigger_orders(&self, ctx: &Context<'_>, owner: String) -> async_graphql::Result<Vec<TriggerOrderInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        let mut orders = Vec::new();
+        data.state.trigger_orders.for_each_index_value(|_, order| {
+            if order.owner == owner && order.status == TriggerStatus::Pending {
+                orders.push(TriggerOrderInfo::from(order.into_owned()));
+            }
+            Ok(())
+        }).await?;
+        Ok(orders)
+    }
+
+    /// Open orders on a market summed by price level. With `levels`, the
+    /// AMM's depth is merged in at every `AMM_DEPTH_STEP_BPS` and each side
+    /// keeps its best `levels` levels, at most `MAX_BOOK_LEVELS`; without,
+    /// only the resting orders are listed.
+    async fn order_book(&self, ctx: &Context<'_>, market_id: u64, levels: Option<u32>) -> async_graphql::Result<OrderBookInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        if levels.is_some_and(|levels| levels > MAX_BOOK_LEVELS) {
+            return Err(async_graphql::Error::new(format!("levels must be at most {}", MAX_BOOK_LEVELS)));
+        }
+        // Settled markets trade only through the book
+        let markets = &data.full().await?.markets;
+        let pools = markets.binary_search_by_key(&market_id, |m| m.id)
+            .ok()
+            .map(|index| &markets[index])
+            .filter(|market| !market.resolved)
+            .map(|market| (market.yes_pool_attos, market.no_pool_attos));
+        let side = |is_yes: bool| {
+            let open = |side: OrderSide| {
+                let mut orders: Vec<&LimitOrderInfo> = data.orders.iter()
+                    .filter(|o| o.market_id == market_id && o.is_yes == is_yes && o.order_side == side && o.is_open())
+                    .collect();
+                match side {
+                    OrderSide::Buy => orders.sort_by_key(|o| (std::cmp::Reverse(o.price_bps), o.id)),
+                    OrderSide::Sell => orders.sort_by_key(|o| (o.price_bps, o.id)),
+                }
+                let mut book = price_levels(orders.into_iter());
+                if let Some(levels) = levels {
+                    let count = levels as usize;
+                    let asks = side == OrderSide::Sell;
+                    if let Some((yes_pool, no_pool)) = pools {
+                        let (pool, other_pool) = if is_yes { (yes_pool, no_pool) } else { (no_pool, yes_pool) };
+                        merge_levels(&mut book, amm_levels(pool, other_pool, asks, count), asks);
+                    }
+                    book.truncate(count);
+                }
+                book.into_iter().map(PriceLevel::from).collect()
+            };
+            BookSide { bids: open(OrderSide::Buy), asks: open(OrderSide::Sell) }
+        };
+        Ok(OrderBookInfo { market_id, yes: side(true), no: side(false) })
     }
 
     // === Combo Queries ===
     
-    async fn combos(&self) -> &Vec<ComboInfo> {
-        &self.combos
+    async fn combos<'a>(&self, ctx: &Context<'a>) -> &'a Vec<ComboInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        &data.combos
     }
 
-    async fn combo(&self, id: u64) -> Option<&ComboInfo> {
-        self.combos.iter().find(|c| c.id == id)
+    async fn combo<'a>(&self, ctx: &Context<'a>, id: u64) -> async_graphql::Result<Option<&'a ComboInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.combos.iter().find(|c| c.id == id).map(Some).ok_or_else(|| not_found(format!("Combo {}", id)))
     }
 
-    async fn active_combos(&self) -> Vec<&ComboInfo> {
-        self.combos.iter().filter(|c| c.status == "Active" || c.status == "PartiallyResolved").collect()
+    async fn active_combos<'a>(&self, ctx: &Context<'a>) -> Vec<&'a ComboInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.combos.iter().filter(|c| c.status == "Active" || c.status == "PartiallyResolved").collect()
     }
 
     // === Agent Queries ===
     
-    async fn agents(&self) -> &Vec<AgentInfo> {
-        &self.agents
+    async fn agents<'a>(&self, ctx: &Context<'a>) -> &'a Vec<AgentInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        &data.agents
     }
 
-    async fn agent(&self, id: u64) -> Option<&AgentInfo> {
-        self.agents.iter().find(|a| a.id == id)
+    async fn agent<'a>(&self, ctx: &Context<'a>, id: u64) -> async_graphql::Result<Option<&'a AgentInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.agents.iter().find(|a| a.id == id).map(Some).ok_or_else(|| not_found(format!("Agent {}", id)))
     }
 
-    async fn active_agents(&self) -> Vec<&AgentInfo> {
-        self.agents.iter().filter(|a| a.is_active).collect()
+    async fn active_agents<'a>(&self, ctx: &Context<'a>) -> Vec<&'a AgentInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.agents.iter().filter(|a| a.is_active).collect()
     }
 
-    async fn top_agents(&self, limit: Option<i32>) -> Vec<&AgentInfo> {
+    async fn top_agents<'a>(&self, ctx: &Context<'a>, limit: Option<i32>) -> Vec<&'a AgentInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
         let limit = limit.unwrap_or(10) as usize;
-        let mut sorted: Vec<_> = self.agents.iter().collect();
+        let mut sorted: Vec<_> = data.agents.iter().collect();
         sorted.sort_by(|a, b| {
             let a_pnl: i128 = a.profit_loss.parse().unwrap_or(0);
             let b_pnl: i128 = b.profit_loss.parse().unwrap_or(0);
@@ -567,20 +4282,484 @@ impl QueryRoot {
 
     // === Social Feed Queries ===
     
-    async fn feed(&self, limit: Option<i32>) -> Vec<&FeedItemInfo> {
+    async fn feed<'a>(&self, ctx: &Context<'a>, limit: Option<i32>) -> Vec<&'a FeedItemInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
         let limit = limit.unwrap_or(50) as usize;
-        self.feed_items.iter().take(limit).collect()
+        data.feed_items.iter().take(limit).collect()
+    }
+
+    async fn feed_item<'a>(&self, ctx: &Context<'a>, id: u64) -> Option<&'a FeedItemInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.feed_items.iter().find(|f| f.id == id)
+    }
+
+    async fn feed_by_market<'a>(&self, ctx: &Context<'a>, market_id: u64) -> Vec<&'a FeedItemInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.feed_items.iter().filter(|f| f.market_id == Some(market_id)).collect()
+    }
+
+    async fn feed_by_type<'a>(&self, ctx: &Context<'a>, item_type: String) -> Vec<&'a FeedItemInfo> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        data.feed_items.iter().filter(|f| f.item_type == item_type).collect()
+    }
+
+    // === Connections ===
+
+    /// Live markets by id, `first` at a time after the `after` cursor.
+    /// Markets created or archived between pages neither shift nor repeat
+    /// the ones already seen.
+    async fn markets_connection(
+        &self, ctx: &Context<'_>,
+        #[graphql(default = 20)] first: u64,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, MarketInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("first", first, MAX_CONNECTION_PAGE_SIZE)?;
+        let after = after.map(|cursor| decode_cursor("market", &cursor)).transpose()?;
+        let mut ids: Vec<u64> = data.market_ids().await?
+            .into_iter()
+            .filter(|id| after.is_none_or(|after| *id > after))
+            .take(first as usize + 1)
+            .collect();
+        let has_next_page = ids.len() > first as usize;
+        ids.truncate(first as usize);
+
+        let mut connection = Connection::new(after.is_some(), has_next_page);
+        connection.edges.extend(
+            data.load_markets(ids).await?
+                .into_iter()
+                .map(|market| Edge::new(encode_cursor("market", market.id), market)),
+        );
+        Ok(connection)
+    }
+
+    /// Trades in `market_id` in the order they executed, `first` at a time
+    /// after the `after` cursor
+    async fn trades_connection(
+        &self, ctx: &Context<'_>,
+        market_id: u64,
+        #[graphql(default = 20)] first: u64,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, TradeInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("first", first, MAX_CONNECTION_PAGE_SIZE)?;
+        let after = after.map(|cursor| decode_cursor("trade", &cursor)).transpose()?;
+        let indexed = ActivityIndex {
+            counts: &data.state.market_activity_counts,
+            index: &data.state.market_activity,
+            key: market_id,
+        };
+        let len = indexed.len().await?;
+        let mut n = match after {
+            Some(after) => indexed.count_below(after.saturating_add(1)).await?,
+            None => 0,
+        };
+
+        let mut trades = Vec::new();
+        while n < len && trades.len() <= first as usize {
+            trades.extend(data.indexed_trade(&indexed, n).await?);
+            n += 1;
+        }
+        let has_next_page = trades.len() > first as usize;
+        trades.truncate(first as usize);
+
+        let mut connection = Connection::new(after.is_some(), has_next_page);
+        connection.edges.extend(
+            trades.into_iter().map(|trade| Edge::new(encode_cursor("trade", trade.sequence), trade)),
+        );
+        Ok(connection)
+    }
+
+    // === Activity Log ===
+
+    /// Successful operations, newest first: every one, or those touching
+    /// `market_id`. Pass the last entry's id as `before` for the next page.
+    async fn activity(
+        &self, ctx: &Context<'_>,
+        market_id: Option<u64>,
+        #[graphql(default = 50)] limit: u64,
+        before: Option<u64>,
+    ) -> async_graphql::Result<Vec<ActivityEntryInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        check_page_size("limit", limit, MAX_ACTIVITY_PAGE_SIZE)?;
+        let indexed = market_id.map(|key| ActivityIndex {
+            counts: &data.state.market_activity_counts,
+            index: &data.state.market_activity,
+            key,
+        });
+        Ok(data.activity_page(indexed, limit, before).await?)
     }
 
-    async fn feed_item(&self, id: u64) -> Option<&FeedItemInfo> {
-        self.feed_items.iter().find(|f| f.id == id)
+    /// Successful operations signed by `owner`, newest first
+    async fn activity_by_owner(
+        &self, ctx: &Context<'_>,
+        owner: String,
+        #[graphql(default = 50)] limit: u64,
+        before: Option<u64>,
+    ) -> async_graphql::Result<Vec<ActivityEntryInfo>> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        let owner = parse_owner("owner", &owner)?;
+        check_page_size("limit", limit, MAX_ACTIVITY_PAGE_SIZE)?;
+        let indexed = ActivityIndex {
+            counts: &data.state.owner_activity_counts,
+            index: &data.state.owner_activity,
+            key: owner,
+        };
+        Ok(data.activity_page(Some(indexed), limit, before).await?)
     }
 
-    async fn feed_by_market(&self, market_id: u64) -> Vec<&FeedItemInfo> {
-        self.feed_items.iter().filter(|f| f.market_id == Some(market_id)).collect()
+    // === Export ===
+
+    /// Markets, then positions when `include_positions` is set, in storage
+    /// key order, at most `page_size` records per page. A cursor keeps
+    /// returning the same page while the state is unchanged.
+    async fn export_state(
+        &self, ctx: &Context<'_>,
+        cursor: Option<String>,
+        #[graphql(default = 100)] page_size: u64,
+        #[graphql(default)] include_positions: bool,
+    ) -> async_graphql::Result<StateExport> {
+        let data = ctx.data_unchecked::<Arc<QueryData>>();
+        if page_size == 0 || page_size > MAX_EXPORT_PAGE_SIZE {
+            return Err(async_graphql::Error::new(format!(
+                "Invalid value for argument \"pageSize\": must be between 1 and {}", MAX_EXPORT_PAGE_SIZE
+            )));
+        }
+        let ExportCursor { mut section, mut after } = cursor.as_deref()
+            .map(ExportCursor::parse)
+            .transpose()?
+            .unwrap_or(ExportCursor { section: ExportSection::Markets, after: None });
+        if section == ExportSection::Positions && !include_positions {
+            return Err(async_graphql::Error::new("A positions cursor needs includePositions"));
+        }
+
+        let mut page = ExportPayload { schema_version: EXPORT_SCHEMA_VERSION, markets: Vec::new(), positions: Vec::new() };
+        let mut room = page_size as usize;
+        let mut next_cursor = None;
+        if section == ExportSection::Markets {
+            let mut last = after.clone();
+            let mut more = false;
+            data.state.markets.for_each_index_value_while(|id, market| {
+                let key = bcs::to_bytes(&id)?;
+                if !past_cursor(&key, after.as_deref()) {
+                    return Ok(true);
+                }
+                if room == 0 {
+                    more = true;
+                    return Ok(false);
+                }
+                page.markets.push(market.into_owned());
+                room -= 1;
+                last = Some(key);
+                Ok(true)
+            }).await?;
+            if more {
+                next_cursor = Some(ExportCursor { section, after: last }.to_string());
+            } else if include_positions {
+                section = ExportSection::Positions;
+                after = None;
+            }
+        }
+        if section == ExportSection::Positions && next_cursor.is_none() {
+            let mut last = after.clone();
+            let mut more = false;
+            data.state.positions.for_each_index_value_while(|key, position| {
+                let key = bcs::to_bytes(&key)?;
+                if !past_cursor(&key, after.as_deref()) {
+                    return Ok(true);
+                }
+                if room == 0 {
+                    more = true;
+                    return Ok(false);
+                }
+                page.positions.push(position.into_owned());
+                room -= 1;
+                last = Some(key);
+                Ok(true)
+            }).await?;
+            if more {
+                next_cursor = Some(ExportCursor { section, after: last }.to_string());
+            }
+        }
+
+        let payload = serde_json::to_string(&page)
+            .map_err(|e| async_graphql::Error::new(format!("Failed to encode export: {}", e)))?;
+        Ok(StateExport { schema_version: EXPORT_SCHEMA_VERSION, payload, next_cursor })
+    }
+
+    // === Plain JSON ===
+
+    /// `market(id)` as a JSON string, for clients without a GraphQL
+    /// client: `{"data": {...}}` with a fixed set of market fields, or
+    /// `{"error": {"code": ..., "message": ...}}`
+    async fn market_json(&self, ctx: &Context<'_>, id: u64) -> String {
+        let query = format!("query($id: Int!) {{ market(id: $id) {{ {MARKET_JSON_FIELDS} }} }}");
+        route_json(ctx, &query, serde_json::json!({ "id": id }), "/market").await
+    }
+
+    /// `markets(limit, offset)` as a JSON string in the same form as
+    /// `marketJson`: `{"data": {"totalCount": ..., "markets": [...]}}`
+    async fn markets_json(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 50)] limit: u64,
+        #[graphql(default)] offset: u64,
+    ) -> String {
+        let query = format!(
+            "query($limit: Int!, $offset: Int!) {{ markets(limit: $limit, offset: $offset) {{ totalCount markets {{ {MARKET_JSON_FIELDS} }} }} }}"
+        );
+        route_json(ctx, &query, serde_json::json!({ "limit": limit, "offset": offset }), "/markets").await
+    }
+
+    /// `position(owner, marketId)` as a JSON string in the same form as
+    /// `marketJson`; the data is null if `owner` never traded the market
+    async fn position_json(&self, ctx: &Context<'_>, owner: String, market_id: u64) -> String {
+        let query = format!(
+            "query($owner: String!, $marketId: Int!) {{ position(owner: $owner, marketId: $marketId) {{ {POSITION_JSON_FIELDS} }} }}"
+        );
+        route_json(ctx, &query, serde_json::json!({ "owner": owner, "marketId": market_id }), "/position").await
+    }
+
+    /// The market's `buyQuote`, or `proceedsFromSell` when `sell` is set,
+    /// as a JSON string in the same form as `marketJson`; the data is null
+    /// where the quote is
+    async fn quote_json(
+        &self,
+        ctx: &Context<'_>,
+        market_id: u64,
+        is_yes: bool,
+        shares: AmountScalar,
+        #[graphql(default)] sell: bool,
+    ) -> String {
+        let (query, pointer) = if sell {
+            let query = format!(
+                "query($id: Int!, $isYes: Boolean!, $shares: Amount!) \
+                    {{ proceedsFromSell(marketId: $id, isYes: $isYes, shares: $shares) {{ {SELL_QUOTE_JSON_FIELDS} }} }}"
+            );
+            (query, "/proceedsFromSell")
+        } else {
+            let query = format!(
+                "query($id: Int!, $isYes: Boolean!, $shares: Amount!) \
+                    {{ market(id: $id) {{ buyQuote(isYes: $isYes, shares: $shares) {{ {BUY_QUOTE_JSON_FIELDS} }} }} }}"
+            );
+            (query, "/market/buyQuote")
+        };
+        let variables = serde_json::json!({ "id": market_id, "isYes": is_yes, "shares": shares.decimal() });
+        route_json(ctx, &query, variables, pointer).await
+    }
+}
+
+// ============ MUTATIONS ============
+
+/// The mutations generated from `Operation`, overridden by the checked ones.
+/// Where merged objects share a field the last one wins, both in the schema
+/// and when resolving, so the checked mutations must stay last.
+#[derive(MergedObject)]
+struct MutationRoot(<Operation as GraphQLMutationRoot<MarketService>>::MutationRoot, TradingMutations);
+
+/// Mutations that reject bad arguments with a GraphQL error before
+/// scheduling their operation
+struct TradingMutations {
+    /// None when the state failed to load, in which case no mutation gets
+    /// this far
+    state: Option<Arc<MarketState>>,
+    runtime: Arc<ServiceRuntime<MarketService>>,
+}
+
+impl TradingMutations {
+    fn state(&self) -> async_graphql::Result<&MarketState> {
+        self.state.as_deref().ok_or_else(|| async_graphql::Error::new("State failed to load"))
+    }
+
+    async fn open_market(&self, market_id: u64) -> async_graphql::Result<Market> {
+        let market = self.state()?.markets.get(&market_id).await?
+            .ok_or_else(|| async_graphql::Error::new(format!("Market {} not found", market_id)))?;
+        if market.resolved {
+            return Err(async_graphql::Error::new(MarketError::AlreadyResolved { market_id }.to_string()));
+        }
+        Ok(market)
+    }
+}
+
+#[Object]
+impl TradingMutations {
+    #[allow(clippy::too_many_arguments)]
+    async fn create_market(
+        &self,
+        question: String,
+        categories: Vec<String>,
+        end_time: TimestampScalar,
+        initial_liquidity: AmountScalar,
+        #[graphql(default)] early_resolution_allowed: bool,
+        max_position_per_account: Option<AmountScalar>,
+        max_position: Option<AmountScalar>,
+        #[graphql(default)] creator_trading_disabled: bool,
+        fee_ramp: Option<FeeRamp>,
+        description: Option<String>,
+        resolution_source: Option<String>,
+        parent: Option<MarketCondition>,
+        #[graphql(default)] resolvers: Vec<String>,
+        #[graphql(default)] threshold: u8,
+        max_probability_move_bps: Option<u64>,
+        max_trade_shares: Option<AmountScalar>,
+    ) -> async_graphql::Result<[u8; 0]> {
+        if question.trim().is_empty() {
+            return Err(async_graphql::Error::new("Question must not be empty"));
+        }
+        let end_time = end_time.0;
+        if end_time <= self.runtime.system_time() {
+            return Err(async_graphql::Error::new("endTime must be in the future"));
+        }
+        if let Some(condition) = parent {
+            match self.state()?.markets.get(&condition.market_id).await? {
+                None => return Err(not_found(format!("Parent market {}", condition.market_id))),
+                Some(market) if market.resolved => {
+                    return Err(async_graphql::Error::new(MarketError::ParentResolved { parent_id: market.id }.to_string()));
+                }
+                Some(_) => {}
+            }
+        }
+        let operation = Operation::CreateMarket {
+            question,
+            categories,
+            end_time,
+            initial_liquidity: initial_liquidity.0,
+            early_resolution_allowed,
+            max_position_per_account: max_position_per_account.map(|limit| limit.0),
+            max_position: max_position.map(|cap| cap.0),
+            creator_trading_disabled,
+            fee_ramp,
+            description,
+            resolution_source,
+            parent,
+            resolvers: resolvers.iter()
+                .map(|resolver| parse_owner("resolvers", resolver))
+                .collect::<Result<_, _>>()?,
+            threshold,
+            max_probability_move_bps,
+            max_trade_shares: max_trade_shares.map(|cap| cap.0),
+        };
+        self.runtime.schedule_operation(&operation);
+        Ok([])
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn buy_shares(
+        &self,
+        market_id: u64,
+        is_yes: bool,
+        shares: AmountScalar,
+        max_cost: AmountScalar,
+        deadline: Option<TimestampScalar>,
+        max_price_impact_bps: Option<u64>,
+    ) -> async_graphql::Result<[u8; 0]> {
+        let shares = shares.0;
+        if shares == Amount::ZERO {
+            return Err(async_graphql::Error::new("shares must be greater than zero"));
+        }
+        let operation = Operation::BuyShares {
+            market_id,
+            is_yes,
+            shares,
+            max_cost: max_cost.0,
+            deadline: deadline.map(|d| d.0),
+            max_price_impact_bps,
+        };
+        self.open_market(market_id).await?;
+        self.runtime.schedule_operation(&operation);
+        Ok([])
+    }
+
+    async fn buy_straddle(
+        &self,
+        market_id: u64,
+        yes_shares: AmountScalar,
+        no_shares: AmountScalar,
+        max_total_cost: AmountScalar,
+    ) -> async_graphql::Result<[u8; 0]> {
+        let (yes_shares, no_shares) = (yes_shares.0, no_shares.0);
+        if yes_shares == Amount::ZERO || no_shares == Amount::ZERO {
+            return Err(async_graphql::Error::new("yesShares and noShares must be greater than zero"));
+        }
+        let operation = Operation::BuyStraddle {
+            market_id,
+            yes_shares,
+            no_shares,
+            max_total_cost: max_total_cost.0,
+        };
+        self.open_market(market_id).await?;
+        self.runtime.schedule_operation(&operation);
+        Ok([])
+    }
+
+    async fn buy_parlay(&self, legs: Vec<ParlayLeg>, max_total_cost: AmountScalar) -> async_graphql::Result<[u8; 0]> {
+        if legs.len() < 2 || legs.len() > MAX_PARLAY_LEGS {
+            return Err(async_graphql::Error::new(format!("legs must number between 2 and {}", MAX_PARLAY_LEGS)));
+        }
+        for (index, leg) in legs.iter().enumerate() {
+            if leg.shares == Amount::ZERO {
+                return Err(async_graphql::Error::new("shares must be greater than zero in every leg"));
+            }
+            if legs[..index].iter().any(|earlier| earlier.market_id == leg.market_id) {
+                return Err(async_graphql::Error::new(format!("Market {} appears in more than one leg", leg.market_id)));
+            }
+            self.open_market(leg.market_id).await?;
+        }
+        self.runtime.schedule_operation(&Operation::BuyParlay { legs, max_total_cost: max_total_cost.0 });
+        Ok([])
+    }
+
+    async fn resolve_market(&self, market_id: u64, outcome: bool, evidence: Option<String>) -> async_graphql::Result<[u8; 0]> {
+        self.open_market(market_id).await?;
+        self.runtime.schedule_operation(&Operation::ResolveMarket { market_id, outcome, evidence });
+        Ok([])
+    }
+
+    /// Markets the contract refuses to resolve are skipped, so only the
+    /// batch's size is checked here
+    async fn resolve_many(&self, resolutions: Vec<Resolution>) -> async_graphql::Result<[u8; 0]> {
+        if resolutions.is_empty() || resolutions.len() > MAX_RESOLUTIONS {
+            return Err(async_graphql::Error::new(format!("resolutions must number between 1 and {}", MAX_RESOLUTIONS)));
+        }
+        self.runtime.schedule_operation(&Operation::ResolveMany { resolutions });
+        Ok([])
+    }
+
+    async fn override_resolution(&self, market_id: u64, outcome: bool, evidence: Option<String>) -> async_graphql::Result<[u8; 0]> {
+        let market = self.state()?.markets.get(&market_id).await?
+            .ok_or_else(|| async_graphql::Error::new(format!("Market {} not found", market_id)))?;
+        if !market.resolved {
+            return Err(async_graphql::Error::new(format!("Market {} is not yet resolved", market_id)));
+        }
+        self.runtime.schedule_operation(&Operation::OverrideResolution { market_id, outcome, evidence });
+        Ok([])
+    }
+
+    async fn claim_winnings(&self, market_id: u64) -> async_graphql::Result<[u8; 0]> {
+        let market = self.state()?.markets.get(&market_id).await?
+            .ok_or_else(|| async_graphql::Error::new(format!("Market {} not found", market_id)))?;
+        if !market.resolved {
+            return Err(async_graphql::Error::new(format!("Market {} is not yet resolved", market_id)));
+        }
+        self.runtime.schedule_operation(&Operation::ClaimWinnings { market_id });
+        Ok([])
+    }
+}
+
+/// End times from `endsAfter`, inclusive, up to `endsBefore`, exclusive
+#[derive(Default)]
+struct EndTimeRange {
+    after: Option<Timestamp>,
+    before: Option<Timestamp>,
+}
+
+impl EndTimeRange {
+    fn is_unbounded(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
     }
 
-    async fn feed_by_type(&self, item_type: String) -> Vec<&FeedItemInfo> {
-        self.feed_items.iter().filter(|f| f.item_type == item_type).collect()
+    fn contains(&self, market: &MarketInfo) -> bool {
+        self.after.is_none_or(|t| market.end_time_micros.0 >= t)
+            && self.before.is_none_or(|t| market.end_time_micros.0 < t)
     }
 }
@@ -0,0 +1,90 @@
+// Copyright (c) Chronos Markets
+// Replays random market scenarios against the solvency model
+
+// Usage: cargo run --bin simulate -- [--seed N] [--runs N] [--steps N] [--fee-bps N]
+//
+// Run i replays the scenario of seed + i. On the first broken invariant
+// the scenario is shrunk to the fewest steps that still break one, which
+// are printed with the seed that reproduces them.
+
+use std::{
+    process::ExitCode,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chronos_market::simulation::{generate, run, shrink, Failure};
+
+struct Options {
+    seed: u64,
+    runs: u64,
+    steps: usize,
+    fee_bps: u64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options { seed: 0, runs: 100, steps: 200, fee_bps: 100 };
+    let mut seed = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        let number = |value: &str| value.parse::<u64>().map_err(|e| format!("Invalid value for {}: \"{}\" ({})", flag, value, e));
+        match flag.as_str() {
+            "--seed" => seed = Some(number(&value)?),
+            "--runs" => options.runs = number(&value)?,
+            "--steps" => options.steps = number(&value)? as usize,
+            "--fee-bps" => options.fee_bps = number(&value)?,
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+    // Without a seed every invocation explores new scenarios; the seed is
+    // printed either way so a failure can be replayed
+    options.seed = seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+    });
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            eprintln!("Usage: simulate [--seed N] [--runs N] [--steps N] [--fee-bps N]");
+            return ExitCode::from(2);
+        }
+    };
+    // Panics are caught and reported as failures; keep their messages out
+    // of the output while shrinking replays them
+    std::panic::set_hook(Box::new(|_| {}));
+
+    let mut refund_shortfall = 0u128;
+    for run_index in 0..options.runs {
+        let seed = options.seed.wrapping_add(run_index);
+        let steps = generate(seed, options.steps);
+        match run(&steps, options.fee_bps) {
+            Ok(model) => refund_shortfall = refund_shortfall.saturating_add(model.refund_shortfall),
+            Err(Failure { step, reason }) => {
+                println!("Seed {} broke an invariant at step {}: {}", seed, step, reason);
+                let minimal = shrink(steps[..=step].to_vec(), |steps| run(steps, options.fee_bps).is_err());
+                let failure = run(&minimal, options.fee_bps).expect_err("the shrunk scenario still fails");
+                println!("Minimal failing sequence ({} steps, fails at {}):", minimal.len(), failure);
+                for (index, step) in minimal.iter().enumerate() {
+                    println!("  {:>3}: {:?}", index, step);
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    println!(
+        "{} runs of {} steps passed (seeds {} to {}, fee {} bps)",
+        options.runs,
+        options.steps,
+        options.seed,
+        options.seed.wrapping_add(options.runs.saturating_sub(1)),
+        options.fee_bps
+    );
+    if refund_shortfall > 0 {
+        println!("Refunds left unpaid by short escrows: {} attos", refund_shortfall);
+    }
+    ExitCode::SUCCESS
+}
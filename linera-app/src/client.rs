@@ -0,0 +1,720 @@
+// Copyright (c) Chronos Markets
+// Building operations and reading responses from off-chain Rust clients
+
+// Bots and integrations submit `Operation`s as BCS and get the contract's
+// response string back. The builders here fill in the optional fields, the
+// response parser turns the colon-separated strings into typed receipts,
+// and `MarketSnapshot` mirrors the service's market fields so quotes can be
+// priced locally with the same `math` the contract uses. Nothing here
+// touches the runtime, so it builds for native targets.
+
+use std::{fmt, str::FromStr};
+
+use linera_sdk::{
+    bcs,
+    linera_base_types::{AccountOwner, Amount, Timestamp},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{cost_to_buy, price_impact_bps, safe_mul_div, sell_proceeds},
+    FeeRamp, MarketCondition, MarketKey, Operation,
+};
+
+/// What went wrong building an operation or reading a response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientError {
+    /// A builder was asked to build without a field it needs
+    MissingField(&'static str),
+    /// BCS encoding or decoding failed
+    Bcs(String),
+    /// A response string did not have the fields its kind calls for
+    MalformedResponse(String),
+    /// The market can no longer be traded this way
+    MarketClosed(u64),
+    /// The AMM cannot fill the trade
+    Quote(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::MissingField(field) => write!(f, "missing {}", field),
+            ClientError::Bcs(e) => write!(f, "BCS error: {}", e),
+            ClientError::MalformedResponse(response) => write!(f, "malformed response {:?}", response),
+            ClientError::MarketClosed(market_id) => write!(f, "market {} is closed to this trade", market_id),
+            ClientError::Quote(e) => write!(f, "cannot quote: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+// ==================== BUILDERS ====================
+
+/// Entry point for the operation builders
+///
+/// ```
+/// use chronos_market::{client::OperationBuilder, Operation};
+/// use linera_sdk::linera_base_types::Amount;
+///
+/// let operation = OperationBuilder::buy(7)
+///     .yes()
+///     .shares(Amount::from_tokens(10))
+///     .max_cost(Amount::from_tokens(6))
+///     .build()
+///     .unwrap();
+/// assert!(matches!(operation, Operation::BuyShares { market_id: 7, is_yes: true, deadline: None, .. }));
+/// ```
+pub struct OperationBuilder;
+
+impl OperationBuilder {
+    /// A `BuyShares`; needs a side, `shares` and `max_cost`
+    pub fn buy(market_id: u64) -> BuyBuilder {
+        BuyBuilder { market_id, is_yes: None, shares: None, max_cost: None, deadline: None, max_price_impact_bps: None }
+    }
+
+    /// A `SellShares`; needs a side, `shares` and `min_proceeds`
+    pub fn sell(market_id: u64) -> SellBuilder {
+        SellBuilder { market_id, is_yes: None, shares: None, min_proceeds: None, deadline: None }
+    }
+
+    /// A `CreateMarket` with every option at its default: no caps, a flat
+    /// fee, no categories and the creator resolving alone
+    pub fn create_market(question: impl Into<String>, end_time: Timestamp, initial_liquidity: Amount) -> CreateMarketBuilder {
+        CreateMarketBuilder {
+            question: question.into(),
+            categories: Vec::new(),
+            end_time,
+            initial_liquidity,
+            early_resolution_allowed: false,
+            max_position_per_account: None,
+            max_position: None,
+            creator_trading_disabled: false,
+            fee_ramp: None,
+            description: None,
+            resolution_source: None,
+            parent: None,
+            resolvers: Vec::new(),
+            threshold: 0,
+            max_probability_move_bps: None,
+            max_trade_shares: None,
+        }
+    }
+}
+
+/// Builds `Operation::BuyShares`
+#[derive(Clone, Debug)]
+pub struct BuyBuilder {
+    market_id: u64,
+    is_yes: Option<bool>,
+    shares: Option<Amount>,
+    max_cost: Option<Amount>,
+    deadline: Option<Timestamp>,
+    max_price_impact_bps: Option<u64>,
+}
+
+impl BuyBuilder {
+    pub fn yes(mut self) -> Self {
+        self.is_yes = Some(true);
+        self
+    }
+
+    pub fn no(mut self) -> Self {
+        self.is_yes = Some(false);
+        self
+    }
+
+    pub fn side(mut self, is_yes: bool) -> Self {
+        self.is_yes = Some(is_yes);
+        self
+    }
+
+    pub fn shares(mut self, shares: Amount) -> Self {
+        self.shares = Some(shares);
+        self
+    }
+
+    /// Most the buy may cost, fee included
+    pub fn max_cost(mut self, max_cost: Amount) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    /// Latest block time the trade may execute at, inclusive
+    pub fn deadline(mut self, deadline: Timestamp) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn max_price_impact_bps(mut self, bps: u64) -> Self {
+        self.max_price_impact_bps = Some(bps);
+        self
+    }
+
+    pub fn build(self) -> Result<Operation, ClientError> {
+        Ok(Operation::BuyShares {
+            market_id: self.market_id,
+            is_yes: self.is_yes.ok_or(ClientError::MissingField("side"))?,
+            shares: self.shares.ok_or(ClientError::MissingField("shares"))?,
+            max_cost: self.max_cost.ok_or(ClientError::MissingField("max_cost"))?,
+            deadline: self.deadline,
+            max_price_impact_bps: self.max_price_impact_bps,
+        })
+    }
+}
+
+/// Builds `Operation::SellShares`
+///
+/// ```
+/// use chronos_market::client::{ClientError, OperationBuilder};
+/// use linera_sdk::linera_base_types::Amount;
+///
+/// let missing = OperationBuilder::sell(7).no().shares(Amount::from_tokens(3)).build();
+/// assert_eq!(missing.unwrap_err(), ClientError::MissingField("min_proceeds"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SellBuilder {
+    market_id: u64,
+    is_yes: Option<bool>,
+    shares: Option<Amount>,
+    min_proceeds: Option<Amount>,
+    deadline: Option<Timestamp>,
+}
+
+impl SellBuilder {
+    pub fn yes(mut self) -> Self {
+        self.is_yes = Some(true);
+        self
+    }
+
+    pub fn no(mut self) -> Self {
+        self.is_yes = Some(false);
+        self
+    }
+
+    pub fn side(mut self, is_yes: bool) -> Self {
+        self.is_yes = Some(is_yes);
+        self
+    }
+
+    pub fn shares(mut self, shares: Amount) -> Self {
+        self.shares = Some(shares);
+        self
+    }
+
+    /// Least the sale must pay out, after the fee
+    pub fn min_proceeds(mut self, min_proceeds: Amount) -> Self {
+        self.min_proceeds = Some(min_proceeds);
+        self
+    }
+
+    /// Latest block time the trade may execute at, inclusive
+    pub fn deadline(mut self, deadline: Timestamp) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn build(self) -> Result<Operation, ClientError> {
+        Ok(Operation::SellShares {
+            market_id: self.market_id,
+            is_yes: self.is_yes.ok_or(ClientError::MissingField("side"))?,
+            shares: self.shares.ok_or(ClientError::MissingField("shares"))?,
+            min_proceeds: self.min_proceeds.ok_or(ClientError::MissingField("min_proceeds"))?,
+            deadline: self.deadline,
+        })
+    }
+}
+
+/// Builds `Operation::CreateMarket`; the fields mean what they do there
+#[derive(Clone, Debug)]
+pub struct CreateMarketBuilder {
+    question: String,
+    categories: Vec<String>,
+    end_time: Timestamp,
+    initial_liquidity: Amount,
+    early_resolution_allowed: bool,
+    max_position_per_account: Option<Amount>,
+    max_position: Option<Amount>,
+    creator_trading_disabled: bool,
+    fee_ramp: Option<FeeRamp>,
+    description: Option<String>,
+    resolution_source: Option<String>,
+    parent: Option<MarketCondition>,
+    resolvers: Vec<AccountOwner>,
+    threshold: u8,
+    max_probability_move_bps: Option<u64>,
+    max_trade_shares: Option<Amount>,
+}
+
+impl CreateMarketBuilder {
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    pub fn early_resolution_allowed(mut self) -> Self {
+        self.early_resolution_allowed = true;
+        self
+    }
+
+    pub fn max_position_per_account(mut self, shares: Amount) -> Self {
+        self.max_position_per_account = Some(shares);
+        self
+    }
+
+    pub fn max_position(mut self, shares: Amount) -> Self {
+        self.max_position = Some(shares);
+        self
+    }
+
+    pub fn creator_trading_disabled(mut self) -> Self {
+        self.creator_trading_disabled = true;
+        self
+    }
+
+    pub fn fee_ramp(mut self, fee_ramp: FeeRamp) -> Self {
+        self.fee_ramp = Some(fee_ramp);
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn resolution_source(mut self, source: impl Into<String>) -> Self {
+        self.resolution_source = Some(source.into());
+        self
+    }
+
+    /// Only pay out if market `market_id` resolves as `outcome`
+    pub fn conditional_on(mut self, market_id: u64, outcome: bool) -> Self {
+        self.parent = Some(MarketCondition { market_id, outcome });
+        self
+    }
+
+    /// Let `resolvers` settle the market once `threshold` of them agree
+    pub fn resolvers(mut self, resolvers: Vec<AccountOwner>, threshold: u8) -> Self {
+        self.resolvers = resolvers;
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn max_probability_move_bps(mut self, bps: u64) -> Self {
+        self.max_probability_move_bps = Some(bps);
+        self
+    }
+
+    pub fn max_trade_shares(mut self, shares: Amount) -> Self {
+        self.max_trade_shares = Some(shares);
+        self
+    }
+
+    pub fn build(self) -> Operation {
+        Operation::CreateMarket {
+            question: self.question,
+            categories: self.categories,
+            end_time: self.end_time,
+            initial_liquidity: self.initial_liquidity,
+            early_resolution_allowed: self.early_resolution_allowed,
+            max_position_per_account: self.max_position_per_account,
+            max_position: self.max_position,
+            creator_trading_disabled: self.creator_trading_disabled,
+            fee_ramp: self.fee_ramp,
+            description: self.description,
+            resolution_source: self.resolution_source,
+            parent: self.parent,
+            resolvers: self.resolvers,
+            threshold: self.threshold,
+            max_probability_move_bps: self.max_probability_move_bps,
+            max_trade_shares: self.max_trade_shares,
+        }
+    }
+}
+
+// ==================== WIRE FORMAT ====================
+
+/// The operation's BCS bytes, as the contract receives them
+///
+/// ```
+/// use chronos_market::{client::{decode_operation, encode_operation}, Operation};
+///
+/// let bytes = encode_operation(&Operation::ClaimWinnings { market_id: 3 }).unwrap();
+/// assert!(matches!(decode_operation(&bytes).unwrap(), Operation::ClaimWinnings { market_id: 3 }));
+/// ```
+pub fn encode_operation(operation: &Operation) -> Result<Vec<u8>, ClientError> {
+    bcs::to_bytes(operation).map_err(|e| ClientError::Bcs(e.to_string()))
+}
+
+/// Read an operation back from its BCS bytes
+pub fn decode_operation(bytes: &[u8]) -> Result<Operation, ClientError> {
+    bcs::from_bytes(bytes).map_err(|e| ClientError::Bcs(e.to_string()))
+}
+
+/// Parse the BCS-encoded response string of an executed operation
+///
+/// ```
+/// use chronos_market::{client::{decode_response, OperationResponse}, market_key};
+/// use linera_sdk::{bcs, linera_base_types::{AccountOwner, ChainId, CryptoHash}};
+///
+/// let market_key = market_key(ChainId(CryptoHash::from([1; 4])), AccountOwner::CHAIN, 0);
+/// let bytes = bcs::to_bytes(&format!("MarketCreated:4:{market_key}")).unwrap();
+/// assert_eq!(decode_response(&bytes).unwrap(), OperationResponse::MarketCreated { market_id: 4, market_key });
+/// ```
+pub fn decode_response(bytes: &[u8]) -> Result<OperationResponse, ClientError> {
+    let response: String = bcs::from_bytes(bytes).map_err(|e| ClientError::Bcs(e.to_string()))?;
+    response.parse()
+}
+
+/// An operation's response string, split into its fields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperationResponse {
+    SharesPurchased(TradeReceipt),
+    SharesSold(TradeReceipt),
+    /// A ClosePosition sale of the whole side, and the profit or loss it
+    /// realized against the shares' cost basis, in attos
+    PositionClosed { receipt: TradeReceipt, realized_pnl: i128 },
+    StraddlePurchased(StraddleReceipt),
+    /// A BuyParlay's total cost and each leg's, in the order of its legs;
+    /// every cost includes its fee
+    ParlayPurchased { total_cost: Amount, leg_costs: Vec<Amount> },
+    /// The new market's local id and its key across chains
+    MarketCreated { market_id: u64, market_key: MarketKey },
+    /// A ResolveMarket that settled the market, and what its claims pay
+    MarketResolved(SettlementReceipt),
+    /// A ResolveMany: each market it named, in order, with the response a
+    /// ResolveMarket on it would have given; refused entries are `Error`s
+    MarketsResolved(Vec<(u64, OperationResponse)>),
+    /// A claim's payout, what the position's shares cost on both sides and
+    /// the profit in attos, negative when written-off losing shares cost
+    /// more than the win paid. Basis and profit are None for positions
+    /// opened before cost bases were tracked.
+    WinningsClaimed { payout: Amount, cost_basis: Option<Amount>, profit: Option<i128> },
+    /// The operation failed; the message after "ERROR: "
+    Error(String),
+    /// Any other response: its kind and the fields after it, unparsed
+    Other { kind: String, fields: Vec<String> },
+}
+
+/// What a single-side buy or sell did
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TradeReceipt {
+    /// Paid for a buy, fee included; received for a sale, fee deducted
+    pub amount: Amount,
+    pub shares: Amount,
+    pub yes_price_bps: u64,
+    pub no_price_bps: u64,
+    pub yes_pool: Amount,
+    pub no_pool: Amount,
+    pub fee: Amount,
+    pub fee_bps: u64,
+    /// The AMM leg's price impact; None for sales
+    pub price_impact_bps: Option<u64>,
+}
+
+/// What a BuyStraddle did
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StraddleReceipt {
+    pub total_cost: Amount,
+    pub yes_cost: Amount,
+    pub no_cost: Amount,
+    pub yes_average_price_bps: u64,
+    pub no_average_price_bps: u64,
+    pub yes_price_bps: u64,
+    pub no_price_bps: u64,
+    pub fees: Amount,
+}
+
+/// What a resolution leaves the market's traders to claim
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettlementReceipt {
+    pub outcome: bool,
+    /// Collateral the claims pay out together: payouts, or refunds of cost
+    /// basis when no trader holds the winning side
+    pub total_payout: Amount,
+    /// Trader-held shares of the winning side; zero for refunds
+    pub winning_shares: Amount,
+    /// Payout per whole winning share, rounded down; zero for refunds. Each
+    /// claim rounds its own payout down, so a claim on `n` shares may pay up
+    /// to an atto less than `n` times this.
+    pub payout_per_share: Amount,
+    /// Positions whose claim pays anything
+    pub eligible_positions: u64,
+}
+
+/// Reads the colon-separated fields of one response in order
+struct Fields<'a> {
+    response: &'a str,
+    fields: std::str::Split<'a, char>,
+}
+
+impl Fields<'_> {
+    fn next<T: FromStr>(&mut self) -> Result<T, ClientError> {
+        self.fields
+            .next()
+            .and_then(|field| field.parse().ok())
+            .ok_or_else(|| ClientError::MalformedResponse(self.response.to_string()))
+    }
+
+    /// The next field, or None where the contract wrote "unknown"
+    fn next_known<T: FromStr>(&mut self) -> Result<Option<T>, ClientError> {
+        match self.fields.clone().next() {
+            Some("unknown") => {
+                self.fields.next();
+                Ok(None)
+            }
+            _ => self.next().map(Some),
+        }
+    }
+
+    fn finish(mut self) -> Result<(), ClientError> {
+        match self.fields.next() {
+            None => Ok(()),
+            Some(_) => Err(ClientError::MalformedResponse(self.response.to_string())),
+        }
+    }
+}
+
+impl FromStr for OperationResponse {
+    type Err = ClientError;
+
+    /// ```
+    /// use chronos_market::client::OperationResponse;
+    ///
+    /// // 10 YES shares bought from 50/50 pools for 12.5
+    /// let response = "SharesPurchased:12.5:10.:6097:3902:40.:62.5:0.:0:2500";
+    /// let OperationResponse::SharesPurchased(receipt) = response.parse().unwrap() else { panic!() };
+    /// assert_eq!(receipt.amount, "12.5".parse().unwrap());
+    /// assert_eq!(receipt.yes_price_bps, 6097);
+    /// assert_eq!(receipt.price_impact_bps, Some(2500));
+    ///
+    /// let error: OperationResponse = "ERROR: Trading is paused by the admin".parse().unwrap();
+    /// assert_eq!(error, OperationResponse::Error("Trading is paused by the admin".to_string()));
+    /// ```
+    fn from_str(response: &str) -> Result<Self, ClientError> {
+        if let Some(message) = response.strip_prefix("ERROR:") {
+            return Ok(OperationResponse::Error(message.trim_start().to_string()));
+        }
+        // The applied and skipped counts, then one "id:response" per market
+        if let Some(batch) = response.strip_prefix("MarketsResolved:") {
+            let malformed = || ClientError::MalformedResponse(response.to_string());
+            let mut entries = batch.split(';');
+            let mut counts = Fields { response, fields: entries.next().unwrap_or_default().split(':') };
+            let (applied, skipped): (usize, usize) = (counts.next()?, counts.next()?);
+            counts.finish()?;
+            let mut results = Vec::new();
+            for entry in entries {
+                let (market_id, result) = entry.split_once(':').ok_or_else(malformed)?;
+                results.push((market_id.parse().map_err(|_| malformed())?, result.parse()?));
+            }
+            let refused = results.iter().filter(|(_, result)| matches!(result, OperationResponse::Error(_))).count();
+            if results.len() != applied + skipped || refused != skipped {
+                return Err(malformed());
+            }
+            return Ok(OperationResponse::MarketsResolved(results));
+        }
+        let mut split = response.split(':');
+        let kind = split.next().unwrap_or_default();
+        let mut fields = Fields { response, fields: split };
+        let parsed = match kind {
+            "SharesPurchased" | "SharesSold" | "PositionClosed" => {
+                let receipt = TradeReceipt {
+                    amount: fields.next()?,
+                    shares: fields.next()?,
+                    yes_price_bps: fields.next()?,
+                    no_price_bps: fields.next()?,
+                    yes_pool: fields.next()?,
+                    no_pool: fields.next()?,
+                    fee: fields.next()?,
+                    fee_bps: fields.next()?,
+                    price_impact_bps: if kind == "SharesPurchased" { Some(fields.next()?) } else { None },
+                };
+                match kind {
+                    "SharesPurchased" => OperationResponse::SharesPurchased(receipt),
+                    "SharesSold" => OperationResponse::SharesSold(receipt),
+                    _ => OperationResponse::PositionClosed { receipt, realized_pnl: fields.next()? },
+                }
+            }
+            "StraddlePurchased" => OperationResponse::StraddlePurchased(StraddleReceipt {
+                total_cost: fields.next()?,
+                yes_cost: fields.next()?,
+                no_cost: fields.next()?,
+                yes_average_price_bps: fields.next()?,
+                no_average_price_bps: fields.next()?,
+                yes_price_bps: fields.next()?,
+                no_price_bps: fields.next()?,
+                fees: fields.next()?,
+            }),
+            "ParlayPurchased" => {
+                let total_cost = fields.next()?;
+                let mut leg_costs = Vec::new();
+                while fields.fields.clone().next().is_some() {
+                    leg_costs.push(fields.next()?);
+                }
+                OperationResponse::ParlayPurchased { total_cost, leg_costs }
+            }
+            "MarketCreated" => OperationResponse::MarketCreated { market_id: fields.next()?, market_key: fields.next()? },
+            "WinningsClaimed" => OperationResponse::WinningsClaimed {
+                payout: fields.next()?,
+                cost_basis: fields.next_known()?,
+                profit: fields.next_known()?,
+            },
+            "MarketResolved" => OperationResponse::MarketResolved(SettlementReceipt {
+                outcome: fields.next()?,
+                total_payout: fields.next()?,
+                winning_shares: fields.next()?,
+                payout_per_share: fields.next()?,
+                eligible_positions: fields.next()?,
+            }),
+            _ => {
+                return Ok(OperationResponse::Other {
+                    kind: kind.to_string(),
+                    fields: fields.fields.map(str::to_string).collect(),
+                })
+            }
+        };
+        fields.finish()?;
+        Ok(parsed)
+    }
+}
+
+// ==================== SERVICE RESPONSES ====================
+
+/// (De)serializes an `Amount` as the service's `TokenAmount` object,
+/// `{ "attos": "…" }`; other fields of the object are ignored
+mod token_amount {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct TokenAmount {
+        attos: String,
+    }
+
+    pub fn serialize<S: serde::Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        TokenAmount { attos: u128::from(*amount).to_string() }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let TokenAmount { attos } = TokenAmount::deserialize(deserializer)?;
+        attos.parse::<u128>().map(Amount::from_attos).map_err(serde::de::Error::custom)
+    }
+}
+
+/// GraphQL selection of the `market` fields `MarketSnapshot` reads
+pub const MARKET_SNAPSHOT_FIELDS: &str = "id question yesPool { attos } noPool { attos } volume { attos } \
+    endTimeMicros resolved outcome isTradeable feeBps yesPriceBps noPriceBps";
+
+/// A market as the service's `market` query returns it, selected with
+/// `MARKET_SNAPSHOT_FIELDS`
+///
+/// ```
+/// use chronos_market::client::MarketSnapshot;
+/// use linera_sdk::linera_base_types::Amount;
+///
+/// let json = r#"{
+///     "id": 7, "question": "Will it rain?",
+///     "yesPool": { "attos": "60000000000000000000", "tokens": "60" },
+///     "noPool": { "attos": "40000000000000000000", "tokens": "40" },
+///     "volume": { "attos": "0", "tokens": "0" },
+///     "endTimeMicros": 2000000, "resolved": false, "outcome": null,
+///     "isTradeable": true, "feeBps": 100, "yesPriceBps": 4000, "noPriceBps": 6000
+/// }"#;
+/// let market: MarketSnapshot = serde_json::from_str(json).unwrap();
+/// let quote = market.buy_quote(true, Amount::from_tokens(10)).unwrap();
+/// // 40 * 10 / (60 - 10) = 8 tokens, plus the 1% fee
+/// assert_eq!(quote.cost, "8.08".parse::<Amount>().unwrap());
+/// assert_eq!(quote.price_impact_bps, 2000);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketSnapshot {
+    pub id: u64,
+    pub question: String,
+    #[serde(with = "token_amount")]
+    pub yes_pool: Amount,
+    #[serde(with = "token_amount")]
+    pub no_pool: Amount,
+    #[serde(with = "token_amount")]
+    pub volume: Amount,
+    pub end_time_micros: u64,
+    pub resolved: bool,
+    pub outcome: Option<bool>,
+    pub is_tradeable: bool,
+    /// Trading fee when the snapshot was taken
+    pub fee_bps: u64,
+    pub yes_price_bps: u64,
+    pub no_price_bps: u64,
+}
+
+/// The price of an AMM buy, shaped like the service's `buyQuote`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuyQuote {
+    /// Total to pay, fee included
+    #[serde(with = "token_amount")]
+    pub cost: Amount,
+    #[serde(with = "token_amount")]
+    pub fee: Amount,
+    pub fee_bps: u64,
+    pub price_impact_bps: u64,
+}
+
+/// What an AMM sale would pay
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SellQuote {
+    /// Paid out, fee deducted
+    #[serde(with = "token_amount")]
+    pub proceeds: Amount,
+    #[serde(with = "token_amount")]
+    pub fee: Amount,
+    pub fee_bps: u64,
+}
+
+impl MarketSnapshot {
+    /// What buying `shares` of one side from the AMM costs at the
+    /// snapshot's pools and fee, priced as the contract prices it but
+    /// ignoring resting orders
+    pub fn buy_quote(&self, is_yes: bool, shares: Amount) -> Result<BuyQuote, ClientError> {
+        if !self.is_tradeable {
+            return Err(ClientError::MarketClosed(self.id));
+        }
+        let (pool_in, pool_out) = self.pools(!is_yes);
+        let shares = u128::from(shares);
+        let cost = cost_to_buy(pool_in, pool_out, shares).map_err(ClientError::Quote)?;
+        let fee = safe_mul_div(cost, u128::from(self.fee_bps), 10_000).map_err(ClientError::Quote)?;
+        Ok(BuyQuote {
+            cost: Amount::from_attos(cost.saturating_add(fee)),
+            fee: Amount::from_attos(fee),
+            fee_bps: self.fee_bps,
+            price_impact_bps: price_impact_bps(pool_out, shares).map_err(ClientError::Quote)?,
+        })
+    }
+
+    /// What selling `shares` of one side to the AMM pays at the snapshot's
+    /// pools and fee
+    pub fn sell_quote(&self, is_yes: bool, shares: Amount) -> Result<SellQuote, ClientError> {
+        if self.resolved {
+            return Err(ClientError::MarketClosed(self.id));
+        }
+        let (pool_in, pool_out) = self.pools(is_yes);
+        let gross = sell_proceeds(pool_in, pool_out, u128::from(shares)).map_err(ClientError::Quote)?;
+        let fee = safe_mul_div(gross, u128::from(self.fee_bps), 10_000).map_err(ClientError::Quote)?;
+        Ok(SellQuote {
+            proceeds: Amount::from_attos(gross.saturating_sub(fee)),
+            fee: Amount::from_attos(fee),
+            fee_bps: self.fee_bps,
+        })
+    }
+
+    /// (YES pool, NO pool) when `yes_first`, else (NO pool, YES pool)
+    fn pools(&self, yes_first: bool) -> (u128, u128) {
+        let (yes, no) = (u128::from(self.yes_pool), u128::from(self.no_pool));
+        if yes_first {
+            (yes, no)
+        } else {
+            (no, yes)
+        }
+    }
+}
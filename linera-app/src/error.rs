@@ -0,0 +1,290 @@
+// Copyright (c) Chronos Markets
+// Typed errors of contract operations, which the service's estimates report
+// in the same words. Rendered into the "ERROR: ..." response string.
+
+use linera_sdk::linera_base_types::{AccountOwner, Amount};
+use thiserror::Error;
+
+use crate::MarketKey;
+
+#[derive(Debug, Error)]
+pub enum MarketError {
+    #[error("Position limit exceeded on market {market_id}: limit {limit} shares per side, headroom {headroom}")]
+    PositionLimitExceeded {
+        market_id: u64,
+        limit: Amount,
+        headroom: Amount,
+    },
+    #[error("Market {market_id} caps each account at {cap} YES and NO shares combined; holding {held}")]
+    MaxPositionExceeded {
+        market_id: u64,
+        cap: Amount,
+        held: Amount,
+    },
+    #[error("The creator of market {market_id} may not trade in it")]
+    CreatorTradingDisabled {
+        market_id: u64,
+    },
+    #[error("Operation must be authenticated: no signer or calling application")]
+    Unauthenticated,
+    #[error("Not authorized: {0}")]
+    NotAuthorized(String),
+    #[error("No {kind} IDs are left to allocate")]
+    IdsExhausted {
+        kind: &'static str,
+    },
+    #[error("Trading is paused by the admin")]
+    Paused,
+    #[error("Insufficient balance: the trade needs {needed} but only {available} is available")]
+    InsufficientBalance {
+        needed: Amount,
+        available: Amount,
+    },
+    #[error("Payout of {payout} on market {market_id} exceeds the contract balance of {balance}; it stays unclaimed until the balance is reconciled")]
+    PayoutExceedsBalance {
+        market_id: u64,
+        payout: Amount,
+        balance: Amount,
+    },
+    #[error("Initial liquidity {provided} is below the minimum of {minimum}")]
+    InitialLiquidityTooLow {
+        provided: Amount,
+        minimum: Amount,
+    },
+    #[error("Market {market_id} is already resolved")]
+    AlreadyResolved {
+        market_id: u64,
+    },
+    #[error("Market {market_id} has ended (now={now_micros}, end={end_micros})")]
+    MarketEnded {
+        market_id: u64,
+        now_micros: u64,
+        end_micros: u64,
+    },
+    #[error("Shares amount must be greater than zero")]
+    ZeroShares,
+    #[error("Trade of {shares} shares is below the minimum of {minimum}")]
+    TradeTooSmall {
+        shares: Amount,
+        minimum: Amount,
+    },
+    #[error("No {side} shares to close in market {market_id}")]
+    NothingToClose {
+        market_id: u64,
+        side: &'static str,
+    },
+    #[error("Trade of {shares} shares exceeds market {market_id}'s cap of {cap} per trade")]
+    TradeTooLarge {
+        market_id: u64,
+        shares: Amount,
+        cap: Amount,
+    },
+    #[error("{shares} shares is not a whole number of share units at {decimals} decimal places")]
+    SharePrecision {
+        shares: Amount,
+        decimals: u8,
+    },
+    #[error("Buying {shares} shares would cost nothing; trade a larger amount")]
+    ZeroCostTrade {
+        shares: Amount,
+    },
+    #[error("Market must stay open for at least {minimum_secs} seconds")]
+    MarketDurationTooShort {
+        minimum_secs: u64,
+    },
+    #[error("The claim window for market {market_id} has closed")]
+    ClaimWindowExpired {
+        market_id: u64,
+    },
+    #[error("Market {market_id} is unresolved or its claim window is still open")]
+    ClaimWindowOpen {
+        market_id: u64,
+    },
+    #[error("Series {series_id} cannot open its next market until {ready_at_micros}")]
+    SeriesNotReady {
+        series_id: u64,
+        ready_at_micros: u64,
+    },
+    #[error("Series {series_id} has already opened all {count} markets")]
+    SeriesComplete {
+        series_id: u64,
+        count: u64,
+    },
+    #[error("{field} exceeds {max} bytes")]
+    TextTooLong {
+        field: &'static str,
+        max: usize,
+    },
+    #[error("The admin may only resolve market {market_id} {delay_secs} seconds after its end time")]
+    AdminResolutionTooEarly {
+        market_id: u64,
+        delay_secs: u64,
+    },
+    #[error("Market {market_id} cannot be resolved before its end time {end_micros}")]
+    ResolutionBeforeEnd {
+        market_id: u64,
+        end_micros: u64,
+    },
+    #[error("A fee ramp needs a nonzero window and a max fee between the base fee of {base_bps} bps and 10000")]
+    InvalidFeeRamp {
+        base_bps: u16,
+    },
+    #[error("Price impact of {impact_bps} bps exceeds the maximum of {max_bps} bps")]
+    PriceImpactExceeded {
+        impact_bps: u64,
+        max_bps: u64,
+    },
+    #[error("Buy would move market {market_id}'s probability by {attempted_bps} bps; at most {allowed_bps} bps are allowed")]
+    MaxImpactExceeded {
+        market_id: u64,
+        allowed_bps: u64,
+        attempted_bps: u64,
+    },
+    #[error("A market's probability move cap must be between 1 and {max_bps} bps")]
+    InvalidImpactLimit {
+        max_bps: u64,
+    },
+    #[error("A market's trade size cap must be greater than zero and at least the minimum trade of {minimum}")]
+    InvalidTradeCap {
+        minimum: Amount,
+    },
+    #[error("Internal invariant violated: the trade would shrink market {market_id}'s pool product")]
+    PoolProductDecreased {
+        market_id: u64,
+    },
+    #[error("Trade deadline {deadline_micros} has passed")]
+    DeadlineExpired {
+        deadline_micros: u64,
+    },
+    #[error("Market {market_id} cannot be archived: {reason}")]
+    ArchiveBlocked {
+        market_id: u64,
+        reason: String,
+    },
+    #[error("The outcome of market {market_id} cannot be overridden: {reason}")]
+    OverrideBlocked {
+        market_id: u64,
+        reason: String,
+    },
+    #[error("No liquidity position in market {market_id}")]
+    NoLiquidityPosition {
+        market_id: u64,
+    },
+    #[error("No LP fees to claim in market {market_id}")]
+    NoFeesToClaim {
+        market_id: u64,
+    },
+    #[error("Market {market_id} has no incentive program")]
+    NoIncentives {
+        market_id: u64,
+    },
+    #[error("Market {market_id} already has {max} sponsors")]
+    TooManySponsors {
+        market_id: u64,
+        max: usize,
+    },
+    #[error("{sponsor} has not funded market {market_id}")]
+    NotASponsor {
+        market_id: u64,
+        sponsor: AccountOwner,
+    },
+    #[error("A sponsor link needs a sponsor name")]
+    SponsorUrlWithoutName,
+    #[error("No incentive rewards to claim in market {market_id}")]
+    NoIncentivesToClaim {
+        market_id: u64,
+    },
+    #[error("Market {market_id} has no creator bond")]
+    NoBond {
+        market_id: u64,
+    },
+    #[error("The bond on market {market_id} is held until it resolves and its outcome can no longer be overridden")]
+    BondLocked {
+        market_id: u64,
+    },
+    #[error("The bond on market {market_id} was already {status}")]
+    BondSettled {
+        market_id: u64,
+        status: String,
+    },
+    #[error("Market {market_id} cannot be cancelled: {reason}")]
+    CancelBlocked {
+        market_id: u64,
+        reason: String,
+    },
+    #[error("No maker rebates to withdraw")]
+    NoRebatesToWithdraw,
+    #[error("Maker rebates of {rebates} exceed the contract balance of {balance}; they stay unclaimed until the balance is reconciled")]
+    RebatesExceedBalance {
+        rebates: Amount,
+        balance: Amount,
+    },
+    #[error("Parent market {parent_id} not found")]
+    ParentNotFound {
+        parent_id: u64,
+    },
+    #[error("Parent market {parent_id} is already resolved")]
+    ParentResolved {
+        parent_id: u64,
+    },
+    #[error("Market {market_id} cannot resolve before its parent market {parent_id}")]
+    ParentUnresolved {
+        market_id: u64,
+        parent_id: u64,
+    },
+    #[error("Threshold {threshold} must be between 1 and the number of resolvers ({resolvers})")]
+    InvalidThreshold {
+        threshold: u8,
+        resolvers: usize,
+    },
+    #[error("{0} is listed as a resolver more than once")]
+    DuplicateResolver(AccountOwner),
+    #[error("Market group {group_id} not found")]
+    GroupNotFound {
+        group_id: u64,
+    },
+    #[error("Market {market_id} belongs to group {group_id}; resolve it with ResolveGroup")]
+    GroupMember {
+        market_id: u64,
+        group_id: u64,
+    },
+    #[error("A parlay needs between 2 and {max} legs; got {count}")]
+    ParlayLegCount {
+        count: usize,
+        max: usize,
+    },
+    #[error("ResolveMany takes between 1 and {max} resolutions; got {count}")]
+    ResolutionCount {
+        count: usize,
+        max: usize,
+    },
+    #[error("Market {market_id} appears in more than one parlay leg; buy both sides of one market with BuyStraddle")]
+    DuplicateParlayMarket {
+        market_id: u64,
+    },
+    #[error("Market {market_id} is not a member of group {group_id}")]
+    NotGroupMember {
+        market_id: u64,
+        group_id: u64,
+    },
+    #[error("Market {market_id} cannot be closed before its end time {end_micros}")]
+    CloseBeforeEnd {
+        market_id: u64,
+        end_micros: u64,
+    },
+    #[error("Market {market_id} is already closed")]
+    AlreadyClosed {
+        market_id: u64,
+    },
+    #[error("Market key {key} is already taken by market {market_id}")]
+    MarketKeyCollision {
+        key: MarketKey,
+        market_id: u64,
+    },
+}
+
+impl From<MarketError> for String {
+    fn from(error: MarketError) -> Self {
+        error.to_string()
+    }
+}
@@ -1,14 +1,26 @@
 // Copyright (c) Chronos Markets
 // ABI of the Chronos Markets Prediction Market Application
 
+// The derived GraphQL mutation root mirrors each operation's fields as arguments.
+#![allow(clippy::too_many_arguments)]
+
 use async_graphql::{Request, Response, InputObject, Enum};
 use linera_sdk::{
-    linera_base_types::{Amount, AccountOwner, Timestamp, ContractAbi, ServiceAbi},
+    linera_base_types::{Amount, AccountOwner, BcsHashable, ChainId, CryptoHash, TimeDelta, Timestamp, ContractAbi, ServiceAbi},
     graphql::GraphQLMutationRoot,
-    views::{linera_views, MapView, RegisterView, RootView, ViewStorageContext},
+    views::{linera_views, LogView, MapView, QueueView, RegisterView, RootView, ViewStorageContext},
 };
 use serde::{Deserialize, Serialize};
 
+pub mod client;
+pub mod error;
+pub mod math;
+pub mod simulation;
+
+pub use error::MarketError;
+
+use math::{payout_for_shares, pnl, price_of, ramped_fee_bps, safe_mul_div, shares_to_units, PRICE_SCALE};
+
 pub struct MarketAbi;
 
 impl ContractAbi for MarketAbi {
@@ -28,11 +40,23 @@ pub enum OrderSide {
     Sell,
 }
 
-/// Order duration types - simplified for GraphQL compatibility
+/// Which way a side's price has to move for a trigger order to fire
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Enum)]
-pub enum OrderDuration {
-    GoodTillCancelled,
-    ImmediateOrCancel,
+pub enum TriggerDirection {
+    /// At or below the trigger price: a stop-loss
+    Below,
+    /// At or above the trigger price: a take-profit
+    Above,
+}
+
+impl TriggerDirection {
+    /// Whether a side trading at `price_bps` has reached `trigger_price_bps`
+    pub fn is_met(self, price_bps: u64, trigger_price_bps: u64) -> bool {
+        match self {
+            TriggerDirection::Below => price_bps <= trigger_price_bps,
+            TriggerDirection::Above => price_bps >= trigger_price_bps,
+        }
+    }
 }
 
 /// Agent trading strategies
@@ -57,6 +81,9 @@ pub enum FeedItemType {
 }
 
 /// Operations that can be performed on the market
+// Decoded once per block from its wire form, so CreateMarket's size is no
+// cost worth boxing its fields for
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Deserialize, Serialize, GraphQLMutationRoot)]
 pub enum Operation {
     // === Market Operations ===
@@ -65,39 +92,265 @@ pub enum Operation {
         categories: Vec<String>,
         end_time: Timestamp,
         initial_liquidity: Amount,
+        /// Allow resolving before end_time, for events that can conclude early
+        early_resolution_allowed: bool,
+        /// Cap on shares one account may hold per side; None for unlimited
+        max_position_per_account: Option<Amount>,
+        /// Cap on one account's YES and NO shares combined; None for unlimited
+        max_position: Option<Amount>,
+        /// Refuse buys by the creator, so a creator-resolver holds no position
+        creator_trading_disabled: bool,
+        /// Raise the trading fee as end_time nears; None for a flat fee
+        fee_ramp: Option<FeeRamp>,
+        /// Free-form details, at most 2000 bytes
+        description: Option<String>,
+        /// Where the resolver will look up the outcome, at most 500 bytes
+        resolution_source: Option<String>,
+        /// Only pay out if this unresolved market resolves as given;
+        /// otherwise the new market is voided and refunded
+        parent: Option<MarketCondition>,
+        /// Accounts that vote on the outcome instead of the creator; empty
+        /// for the creator to resolve alone
+        resolvers: Vec<AccountOwner>,
+        /// Matching votes needed to resolve; 0 without resolvers
+        threshold: u8,
+        /// Cap on how far one buy may move the probability, in basis
+        /// points, within `Config::max_probability_move_bps`; None for the
+        /// global cap
+        max_probability_move_bps: Option<u64>,
+        /// Most shares one buy or sell may trade; None for no cap
+        max_trade_shares: Option<Amount>,
     },
     BuyShares {
         market_id: u64,
         is_yes: bool,
         shares: Amount,
         max_cost: Amount,
+        /// Latest block time the trade may execute at, inclusive
+        deadline: Option<Timestamp>,
+        /// Most the AMM leg's average price may exceed its marginal price
+        /// before the trade, in basis points; see `math::price_impact_bps`
+        max_price_impact_bps: Option<u64>,
+    },
+    /// Buy YES and NO shares in one step, from the AMM only. The YES leg
+    /// trades first and the NO leg prices against the pools it leaves, so
+    /// swapping the sizes changes the cost. Both legs execute or neither.
+    BuyStraddle {
+        market_id: u64,
+        yes_shares: Amount,
+        no_shares: Amount,
+        /// Cap on both legs' costs plus fees together
+        max_total_cost: Amount,
+    },
+    /// Buy shares in several markets in one step, from the AMMs only, under
+    /// one budget: every leg executes or none does. Each market may appear
+    /// in one leg only; a market named twice is refused rather than merged,
+    /// as BuyStraddle covers both sides of one market.
+    BuyParlay {
+        legs: Vec<ParlayLeg>,
+        /// Cap on all legs' costs plus fees together
+        max_total_cost: Amount,
     },
     SellShares {
         market_id: u64,
         is_yes: bool,
         shares: Amount,
         min_proceeds: Amount,
+        /// Latest block time the trade may execute at, inclusive
+        deadline: Option<Timestamp>,
+    },
+    /// Sell the caller's whole holding of one side to the AMM, counted when
+    /// the operation executes rather than when it was signed
+    ClosePosition {
+        market_id: u64,
+        is_yes: bool,
+        min_proceeds: Amount,
     },
+    /// Settle a market. On a market with resolvers this casts the caller's
+    /// vote, replacing any earlier one, and settles once `threshold` agree.
     ResolveMarket {
         market_id: u64,
         outcome: bool,
+        /// Where the outcome was looked up, kept on the market once it
+        /// settles; at most 1024 bytes, and empty counts as none
+        evidence: Option<String>,
+    },
+    /// Resolve several markets at once, each as ResolveMarket would without
+    /// evidence. Entries it refuses, such as markets the caller may not
+    /// resolve or already resolved ones, are skipped and reported rather
+    /// than failing the batch.
+    ResolveMany {
+        resolutions: Vec<Resolution>,
+    },
+    /// Replace a settled market's outcome and restart its claim window.
+    /// Admin only, and only until the first ClaimWinnings or
+    /// RecoverUnclaimed pays out of the market.
+    OverrideResolution {
+        market_id: u64,
+        outcome: bool,
+        /// Replaces the evidence cited for the old outcome; same limits as
+        /// on ResolveMarket
+        evidence: Option<String>,
     },
     ClaimWinnings {
         market_id: u64,
     },
+    /// Sweep escrow nobody claimed before the claim deadline. Creator or admin.
+    RecoverUnclaimed {
+        market_id: u64,
+    },
+    /// Collect the caller's share of the trading fees a market has accrued
+    /// since their last claim
+    ClaimLpFees {
+        market_id: u64,
+    },
+    /// Escrow `amount` as a reward budget streamed to the market's LPs, pro
+    /// rata to their LP shares, over `duration_secs`. Funding a market that
+    /// already has a program adds to what is left to stream and runs it to
+    /// the later of the old end and `duration_secs` from now. Whatever the
+    /// LPs have not earned when the market settles goes back to the funders
+    /// in proportion to what each put in.
+    FundIncentives {
+        market_id: u64,
+        amount: Amount,
+        duration_secs: u64,
+        /// Shown on the market as the caller's sponsor name, replacing any
+        /// earlier one; at most 64 bytes, and empty funds it unbranded
+        sponsor_name: String,
+        /// Link shown with the sponsor name, which it needs; at most 256 bytes
+        sponsor_url: String,
+    },
+    /// Collect the caller's share of a market's incentive rewards streamed
+    /// since their last claim
+    ClaimIncentives {
+        market_id: u64,
+    },
+    /// Replace the name and link a market shows for `sponsor`'s incentive
+    /// funding; same limits as on FundIncentives. Admin only.
+    EditSponsor {
+        market_id: u64,
+        sponsor: AccountOwner,
+        name: String,
+        url: String,
+    },
+    /// Take `sponsor`'s name and link off a market. Their funding still
+    /// streams and its unearned part still goes back to them. Admin only.
+    RemoveSponsor {
+        market_id: u64,
+        sponsor: AccountOwner,
+    },
+    /// Return the caller's bond on a market they created, once the market
+    /// resolved cleanly and its outcome can no longer be overridden, or
+    /// was voided through no fault of theirs
+    ReclaimBond {
+        market_id: u64,
+    },
+    /// Void a market whose question turned out ambiguous, forfeiting its
+    /// creator's bond. Admin only.
+    VoidMarket {
+        market_id: u64,
+    },
+    /// Withdraw a market nobody has traded on, returning its liquidity and
+    /// bond to the creator. Creator only.
+    CancelMarket {
+        market_id: u64,
+    },
+    /// Replace a fully settled market with its `ArchivedMarket` summary and
+    /// drop its positions. Anyone may archive once nothing is left to claim.
+    ArchiveMarket {
+        market_id: u64,
+    },
     
+    /// Escrow liquidity for `count` recurring markets and open the first.
+    /// Later instances open at most every `interval_secs`.
+    CreateMarketSeries {
+        template: SeriesTemplate,
+        interval_secs: u64,
+        count: u64,
+    },
+    /// Open a series' next market once the previous one has ended. Anyone may call.
+    SpawnNextInSeries {
+        series_id: u64,
+    },
+    /// Open one market per member, all ending together, as a set of
+    /// mutually exclusive outcomes
+    CreateMarketGroup {
+        title: String,
+        members: Vec<MarketSpec>,
+        end_time: Timestamp,
+        liquidity_per_market: Amount,
+    },
+    /// Resolve `winner` YES and every other member of the group NO. Group
+    /// members cannot be resolved one at a time.
+    ResolveGroup {
+        group_id: u64,
+        winner: u64,
+    },
+
     // === Limit Order Operations ===
+    /// Rest an order on the book after matching any crossing orders. Buys
+    /// escrow their full cost, sells escrow the shares.
     PlaceLimitOrder {
         market_id: u64,
         is_yes: bool,
         side: OrderSide,
-        price: Amount,  // Price per share (0-100 cents)
-        amount: Amount,
-        duration: OrderDuration,
+        /// Price per share in basis points of one token
+        price_bps: u64,
+        shares: Amount,
+    },
+    /// Refund an order's remaining escrow to its owner
+    CancelOrder {
+        market_id: u64,
+        order_id: u64,
+    },
+    /// Pay out the maker rebates the caller's resting orders have earned
+    WithdrawRebates,
+
+    // === Trigger Order Operations ===
+    /// Sell `shares` of a side through the AMM once its price reaches
+    /// `trigger_price_bps` in `direction`. The shares stay in the position
+    /// until then; the configured keeper fee is escrowed now.
+    PlaceTriggerOrder {
+        market_id: u64,
+        is_yes: bool,
+        shares: Amount,
+        /// Price of the side sold, in basis points
+        trigger_price_bps: u64,
+        direction: TriggerDirection,
+        /// Least the sale may return after fees; a sale quoted below it
+        /// waits for a better price
+        min_proceeds: Amount,
     },
-    CancelLimitOrder {
+    /// Buy `shares` of a side from the AMM once the YES price reaches
+    /// `trigger_price_bps` in `direction`, at no more than `max_price_bps` a
+    /// share after fees. The most the purchase may cost is escrowed now
+    /// with the keeper fee; whatever it leaves is refunded on execution.
+    /// Unexecuted, it is cancelled once the market ends.
+    PlaceBuyTriggerOrder {
+        market_id: u64,
+        is_yes: bool,
+        shares: Amount,
+        /// YES price in basis points, whichever side is bought
+        trigger_price_bps: u64,
+        direction: TriggerDirection,
+        /// Highest average price a share may cost, fee included, in basis
+        /// points
+        max_price_bps: u64,
+    },
+    /// Withdraw a pending trigger order and refund its keeper fee, and a
+    /// buy order's escrow
+    CancelTriggerOrder {
+        market_id: u64,
         order_id: u64,
     },
+    /// Execute up to `max_orders` of a market's pending trigger orders whose
+    /// condition holds, oldest first, paying the caller each one's keeper
+    /// fee. Visits at most `MAX_PENDING_TRIGGER_ORDERS` orders. Anyone may
+    /// call.
+    ExecuteTriggeredOrders {
+        market_id: u64,
+        max_orders: u64,
+    },
     
     // === Combo/Parlay Operations ===
     CreateCombo {
@@ -146,8 +399,285 @@ pub enum Operation {
     LikeFeedItem {
         item_id: u64,
     },
+    /// Count the caller's interest in a market without trading; each account
+    /// counts once, so upvoting again changes nothing
+    Upvote {
+        market_id: u64,
+    },
+    /// Withdraw the caller's upvote, if any
+    RemoveUpvote {
+        market_id: u64,
+    },
+
+    // === Maintenance Operations ===
+    /// Void up to `limit` markets left unresolved past their resolution
+    /// deadline. Callable by anyone; resumes from a stored cursor.
+    FinalizeExpired {
+        limit: u64,
+    },
+    /// Mark a market closed once its end time has passed, ahead of any
+    /// FinalizeExpired sweep. Callable by anyone.
+    CloseMarket {
+        market_id: u64,
+    },
+
+    // === Admin Operations ===
+    /// Emergency stop for market creation and buying. Admin only.
+    SetGlobalPause {
+        paused: bool,
+    },
+    /// Put a market on the homepage's featured list at `position` (0 first;
+    /// None appends, or leaves an already featured market where it is), or
+    /// take it off. Admin only.
+    SetFeatured {
+        market_id: u64,
+        featured: bool,
+        position: Option<u32>,
+    },
+    /// Credit the tracked collateral balance with tokens the admin has
+    /// deposited to cover a shortfall, so refused payouts can be retried.
+    /// It can only be raised, and each deposit is recorded. Admin only.
+    ReconcileBalance {
+        deposit: Amount,
+    },
+
+    // === Cross-Chain Position Operations ===
+    /// Move the caller's whole position on a market of this chain to the
+    /// application on `destination`. It leaves here at once and is
+    /// restored if `destination` refuses it or lets it expire unanswered;
+    /// claims need it back here first, through ReturnPosition.
+    MigratePosition {
+        market_id: u64,
+        destination: ChainId,
+    },
+    /// Send a position migrated to this chain back to the chain of its market
+    ReturnPosition {
+        market_chain: ChainId,
+        market_id: u64,
+    },
+}
+
+impl Operation {
+    /// The variant name, as recorded in `ActivityEntry::kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Operation::CreateMarket { .. } => "CreateMarket",
+            Operation::BuyShares { .. } => "BuyShares",
+            Operation::BuyStraddle { .. } => "BuyStraddle",
+            Operation::BuyParlay { .. } => "BuyParlay",
+            Operation::SellShares { .. } => "SellShares",
+            Operation::ClosePosition { .. } => "ClosePosition",
+            Operation::ResolveMarket { .. } => "ResolveMarket",
+            Operation::ResolveMany { .. } => "ResolveMany",
+            Operation::OverrideResolution { .. } => "OverrideResolution",
+            Operation::ClaimWinnings { .. } => "ClaimWinnings",
+            Operation::RecoverUnclaimed { .. } => "RecoverUnclaimed",
+            Operation::ClaimLpFees { .. } => "ClaimLpFees",
+            Operation::FundIncentives { .. } => "FundIncentives",
+            Operation::ClaimIncentives { .. } => "ClaimIncentives",
+            Operation::EditSponsor { .. } => "EditSponsor",
+            Operation::RemoveSponsor { .. } => "RemoveSponsor",
+            Operation::ReclaimBond { .. } => "ReclaimBond",
+            Operation::VoidMarket { .. } => "VoidMarket",
+            Operation::CancelMarket { .. } => "CancelMarket",
+            Operation::ArchiveMarket { .. } => "ArchiveMarket",
+            Operation::CreateMarketSeries { .. } => "CreateMarketSeries",
+            Operation::SpawnNextInSeries { .. } => "SpawnNextInSeries",
+            Operation::CreateMarketGroup { .. } => "CreateMarketGroup",
+            Operation::ResolveGroup { .. } => "ResolveGroup",
+            Operation::PlaceLimitOrder { .. } => "PlaceLimitOrder",
+            Operation::CancelOrder { .. } => "CancelOrder",
+            Operation::WithdrawRebates => "WithdrawRebates",
+            Operation::PlaceTriggerOrder { .. } => "PlaceTriggerOrder",
+            Operation::PlaceBuyTriggerOrder { .. } => "PlaceBuyTriggerOrder",
+            Operation::CancelTriggerOrder { .. } => "CancelTriggerOrder",
+            Operation::ExecuteTriggeredOrders { .. } => "ExecuteTriggeredOrders",
+            Operation::CreateCombo { .. } => "CreateCombo",
+            Operation::CancelCombo { .. } => "CancelCombo",
+            Operation::CreateAgent { .. } => "CreateAgent",
+            Operation::UpdateAgentConfig { .. } => "UpdateAgentConfig",
+            Operation::ToggleAgent { .. } => "ToggleAgent",
+            Operation::FollowAgent { .. } => "FollowAgent",
+            Operation::UnfollowAgent { .. } => "UnfollowAgent",
+            Operation::PostComment { .. } => "PostComment",
+            Operation::FollowUser { .. } => "FollowUser",
+            Operation::UnfollowUser { .. } => "UnfollowUser",
+            Operation::LikeFeedItem { .. } => "LikeFeedItem",
+            Operation::Upvote { .. } => "Upvote",
+            Operation::RemoveUpvote { .. } => "RemoveUpvote",
+            Operation::FinalizeExpired { .. } => "FinalizeExpired",
+            Operation::CloseMarket { .. } => "CloseMarket",
+            Operation::SetGlobalPause { .. } => "SetGlobalPause",
+            Operation::SetFeatured { .. } => "SetFeatured",
+            Operation::ReconcileBalance { .. } => "ReconcileBalance",
+            Operation::MigratePosition { .. } => "MigratePosition",
+            Operation::ReturnPosition { .. } => "ReturnPosition",
+        }
+    }
+
+    /// Whether the operation must act for an owner. False only for the
+    /// upkeep operations anyone may run; a new operation has to be listed
+    /// here one way or the other.
+    pub fn requires_authentication(&self) -> bool {
+        match self {
+            Operation::ArchiveMarket { .. }
+            | Operation::SpawnNextInSeries { .. }
+            | Operation::ExecuteTriggeredOrders { .. }
+            | Operation::FinalizeExpired { .. }
+            | Operation::CloseMarket { .. } => false,
+            Operation::CreateMarket { .. }
+            | Operation::BuyShares { .. }
+            | Operation::BuyStraddle { .. }
+            | Operation::BuyParlay { .. }
+            | Operation::SellShares { .. }
+            | Operation::ClosePosition { .. }
+            | Operation::ResolveMarket { .. }
+            | Operation::ResolveMany { .. }
+            | Operation::OverrideResolution { .. }
+            | Operation::ClaimWinnings { .. }
+            | Operation::RecoverUnclaimed { .. }
+            | Operation::ClaimLpFees { .. }
+            | Operation::FundIncentives { .. }
+            | Operation::ClaimIncentives { .. }
+            | Operation::EditSponsor { .. }
+            | Operation::RemoveSponsor { .. }
+            | Operation::ReclaimBond { .. }
+            | Operation::VoidMarket { .. }
+            | Operation::CancelMarket { .. }
+            | Operation::CreateMarketSeries { .. }
+            | Operation::CreateMarketGroup { .. }
+            | Operation::ResolveGroup { .. }
+            | Operation::PlaceLimitOrder { .. }
+            | Operation::CancelOrder { .. }
+            | Operation::WithdrawRebates
+            | Operation::PlaceTriggerOrder { .. }
+            | Operation::PlaceBuyTriggerOrder { .. }
+            | Operation::CancelTriggerOrder { .. }
+            | Operation::CreateCombo { .. }
+            | Operation::CancelCombo { .. }
+            | Operation::CreateAgent { .. }
+            | Operation::UpdateAgentConfig { .. }
+            | Operation::ToggleAgent { .. }
+            | Operation::FollowAgent { .. }
+            | Operation::UnfollowAgent { .. }
+            | Operation::PostComment { .. }
+            | Operation::FollowUser { .. }
+            | Operation::UnfollowUser { .. }
+            | Operation::LikeFeedItem { .. }
+            | Operation::Upvote { .. }
+            | Operation::RemoveUpvote { .. }
+            | Operation::SetGlobalPause { .. }
+            | Operation::SetFeatured { .. }
+            | Operation::ReconcileBalance { .. }
+            | Operation::MigratePosition { .. }
+            | Operation::ReturnPosition { .. } => true,
+        }
+    }
+
+    /// The market the operation names, if any
+    pub fn market_id(&self) -> Option<u64> {
+        match self {
+            Operation::BuyShares { market_id, .. }
+            | Operation::BuyStraddle { market_id, .. }
+            | Operation::SellShares { market_id, .. }
+            | Operation::ClosePosition { market_id, .. }
+            | Operation::ResolveMarket { market_id, .. }
+            | Operation::OverrideResolution { market_id, .. }
+            | Operation::ClaimWinnings { market_id }
+            | Operation::RecoverUnclaimed { market_id }
+            | Operation::ClaimLpFees { market_id }
+            | Operation::FundIncentives { market_id, .. }
+            | Operation::ClaimIncentives { market_id }
+            | Operation::EditSponsor { market_id, .. }
+            | Operation::RemoveSponsor { market_id, .. }
+            | Operation::ReclaimBond { market_id }
+            | Operation::VoidMarket { market_id }
+            | Operation::CancelMarket { market_id }
+            | Operation::ArchiveMarket { market_id }
+            | Operation::CloseMarket { market_id }
+            | Operation::SetFeatured { market_id, .. }
+            | Operation::MigratePosition { market_id, .. }
+            | Operation::PlaceLimitOrder { market_id, .. }
+            | Operation::CancelOrder { market_id, .. }
+            | Operation::PlaceTriggerOrder { market_id, .. }
+            | Operation::PlaceBuyTriggerOrder { market_id, .. }
+            | Operation::CancelTriggerOrder { market_id, .. }
+            | Operation::ExecuteTriggeredOrders { market_id, .. }
+            | Operation::PostComment { market_id, .. }
+            | Operation::Upvote { market_id }
+            | Operation::RemoveUpvote { market_id } => Some(*market_id),
+            Operation::ResolveGroup { winner, .. } => Some(*winner),
+            Operation::BuyParlay { legs, .. } => legs.first().map(|leg| leg.market_id),
+            Operation::ResolveMany { resolutions } => resolutions.first().map(|resolution| resolution.market_id),
+            _ => None,
+        }
+    }
+
+    /// Tokens the operation commits up front: liquidity, stake, capital or
+    /// allocation. Trade costs and payouts are only known from the response.
+    pub fn amount(&self) -> Option<Amount> {
+        match self {
+            Operation::CreateMarket { initial_liquidity, .. } => Some(*initial_liquidity),
+            Operation::CreateMarketSeries { template, .. } => Some(template.liquidity_per_instance),
+            Operation::CreateMarketGroup { liquidity_per_market, .. } => Some(*liquidity_per_market),
+            Operation::FundIncentives { amount, .. } => Some(*amount),
+            Operation::CreateCombo { stake, .. } => Some(*stake),
+            Operation::CreateAgent { initial_capital, .. } => Some(*initial_capital),
+            Operation::FollowAgent { allocation, .. } => Some(*allocation),
+            _ => None,
+        }
+    }
+
+    /// The side the operation trades or orders
+    pub fn is_yes(&self) -> Option<bool> {
+        match self {
+            Operation::BuyShares { is_yes, .. }
+            | Operation::SellShares { is_yes, .. }
+            | Operation::ClosePosition { is_yes, .. }
+            | Operation::PlaceLimitOrder { is_yes, .. }
+            | Operation::PlaceTriggerOrder { is_yes, .. }
+            | Operation::PlaceBuyTriggerOrder { is_yes, .. } => Some(*is_yes),
+            _ => None,
+        }
+    }
+
+    /// Shares the operation trades or orders; all legs together for a
+    /// straddle or parlay
+    pub fn shares(&self) -> Option<Amount> {
+        match self {
+            Operation::BuyShares { shares, .. }
+            | Operation::SellShares { shares, .. }
+            | Operation::PlaceLimitOrder { shares, .. }
+            | Operation::PlaceTriggerOrder { shares, .. }
+            | Operation::PlaceBuyTriggerOrder { shares, .. } => Some(*shares),
+            Operation::BuyStraddle { yes_shares, no_shares, .. } => Some(yes_shares.saturating_add(*no_shares)),
+            Operation::BuyParlay { legs, .. } => Some(legs.iter().fold(Amount::ZERO, |sum, leg| sum.saturating_add(leg.shares))),
+            _ => None,
+        }
+    }
+}
+
+/// One leg of a `BuyParlay`: shares of one side of one market
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+pub struct ParlayLeg {
+    pub market_id: u64,
+    pub is_yes: bool,
+    pub shares: Amount,
+}
+
+/// Most legs one `BuyParlay` may have
+pub const MAX_PARLAY_LEGS: usize = 10;
+
+/// One entry of a `ResolveMany`
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+pub struct Resolution {
+    pub market_id: u64,
+    pub outcome: bool,
 }
 
+/// Most entries one `ResolveMany` may have
+pub const MAX_RESOLUTIONS: usize = 50;
+
 /// Combo leg definition
 #[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
 pub struct ComboLeg {
@@ -155,10 +685,501 @@ pub struct ComboLeg {
     pub prediction: bool,  // true = YES, false = NO
 }
 
+/// A market resolving with a given outcome, on which a conditional market depends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, InputObject)]
+pub struct MarketCondition {
+    pub market_id: u64,
+    pub outcome: bool,
+}
+
+/// A trading fee that rises from the base fee to `max_fee_bps` over the
+/// final `window_secs` before a market ends, to discourage sniping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, InputObject)]
+pub struct FeeRamp {
+    pub window_secs: u64,
+    pub max_fee_bps: u16,
+}
+
+impl FeeRamp {
+    /// Fee in basis points at `now` for a market ending at `end_time`
+    pub fn fee_bps(ramp: Option<FeeRamp>, base_bps: u16, end_time: Timestamp, now: Timestamp) -> u64 {
+        let Some(ramp) = ramp else {
+            return u64::from(base_bps);
+        };
+        let window_micros = ramp.window_secs.saturating_mul(1_000_000);
+        let remaining_micros = end_time.delta_since(now).as_micros();
+        ramped_fee_bps(u64::from(base_bps), u64::from(ramp.max_fee_bps), window_micros, remaining_micros)
+    }
+}
+
+/// One outcome of a market group
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+pub struct MarketSpec {
+    pub question: String,
+    pub categories: Vec<String>,
+}
+
+/// Blueprint for every market in a series
+#[derive(Debug, Clone, Deserialize, Serialize, InputObject)]
+pub struct SeriesTemplate {
+    /// Question text; "{date}" is replaced by the instance's end date (YYYY-MM-DD, UTC)
+    pub question: String,
+    pub categories: Vec<String>,
+    /// How long each instance stays open
+    pub duration_secs: u64,
+    pub liquidity_per_instance: Amount,
+}
+
+// ==================== PARAMETERS & MESSAGES ====================
+
+/// Application parameters, fixed when the application is created.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MarketParameters {
+    /// Chain that collects announcements of markets created on other chains
+    pub registry_chain: Option<ChainId>,
+}
+
+// Hashed by the service's `health` query, as is `Config`, to spot
+// configuration drift between deployments
+impl BcsHashable<'_> for MarketParameters {}
+
+/// Protocol configuration supplied at instantiation and kept in `MarketState::config`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    /// Account allowed to perform administrative actions
+    pub admin: AccountOwner,
+    /// Fee on AMM trades in basis points (at most 10_000), paid to the
+    /// market's liquidity providers
+    pub fee_bps: u16,
+    /// Smallest initial liquidity CreateMarket accepts
+    pub min_initial_liquidity: Amount,
+    /// Shortest allowed time between creation and end_time
+    pub min_market_duration_secs: u64,
+    /// How long after end_time a market may stay unresolved before
+    /// FinalizeExpired voids it
+    pub resolution_grace_period_secs: u64,
+    /// How long after end_time the admin may resolve a market its creator
+    /// has abandoned; at most the grace period
+    pub admin_resolution_delay_secs: u64,
+    /// How long after resolution winnings and refunds may be claimed
+    pub claim_window_secs: u64,
+    /// Fewest shares BuyShares and SellShares accept, so dust trades
+    /// cannot exploit rounding
+    pub min_trade_shares: Amount,
+    /// Trades kept in `MarketState::recent_trades`, at most
+    /// `MAX_RECENT_TRADES_CAPACITY`; None for `DEFAULT_RECENT_TRADES_CAPACITY`
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub recent_trades_capacity: Option<u32>,
+    /// Decimal places share amounts may carry, at most
+    /// `math::ATTO_DECIMALS`; None for that many. Trades in finer amounts
+    /// are rejected, so every position is a whole number of share units.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub share_decimals: Option<u8>,
+    /// Most one BuyShares may raise the implied probability of the side it
+    /// buys, in basis points, to blunt last-minute manipulation; None for
+    /// no cap. Markets may set a tighter one at creation.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub max_probability_move_bps: Option<u64>,
+    /// Paid to whoever executes a trigger order, escrowed from its owner at
+    /// placement; at most `MAX_KEEPER_FEE`. None for no fee.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub keeper_fee: Option<Amount>,
+    /// Share of the fee a taker pays on each book fill that is rebated to
+    /// the resting order's owner, in basis points of the fee (at most
+    /// 10_000); the rest goes to the market's LPs. None for no rebate.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub maker_rebate_bps: Option<u16>,
+    /// Escrowed from the creator by CreateMarket on top of the liquidity,
+    /// and forfeited if the market is voided for the creator's failings.
+    /// None for no bond.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub creator_bond: Option<Amount>,
+    /// Share of a forfeited bond paid to the market's traders pro rata to
+    /// their cost basis, in basis points (at most 10_000); the rest goes to
+    /// the treasury. None for all of it to the treasury.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub bond_trader_share_bps: Option<u16>,
+    /// Trading fee discounts by lifetime trading volume, in ascending order
+    /// of `FeeTier::min_volume`; empty for none
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub fee_tiers: Vec<FeeTier>,
+}
+
+/// A trading fee discount for accounts that have traded at least
+/// `min_volume` in total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub min_volume: Amount,
+    /// Share of the fee waived, in basis points (at most 10_000)
+    pub discount_bps: u16,
+}
+
+/// Largest `Config::keeper_fee` accepted
+pub const MAX_KEEPER_FEE: Amount = Amount::from_tokens(1);
+
+/// Most trigger orders a market may have pending, and so the most one
+/// `ExecuteTriggeredOrders` call visits
+pub const MAX_PENDING_TRIGGER_ORDERS: usize = 100;
+
+/// `Config::recent_trades_capacity` when unset
+pub const DEFAULT_RECENT_TRADES_CAPACITY: u32 = 200;
+
+/// Largest `Config::recent_trades_capacity` accepted
+pub const MAX_RECENT_TRADES_CAPACITY: u32 = 10_000;
+
+impl Config {
+    /// How many trades `MarketState::recent_trades` keeps
+    pub fn recent_trades_capacity(&self) -> u32 {
+        self.recent_trades_capacity.unwrap_or(DEFAULT_RECENT_TRADES_CAPACITY)
+    }
+
+    /// Decimal places share amounts carry; see `math::shares_to_units`
+    pub fn share_decimals(&self) -> u8 {
+        self.share_decimals.unwrap_or(math::ATTO_DECIMALS)
+    }
+
+    /// Reject share amounts finer than `share_decimals`; the AMM itself
+    /// still works in attos
+    pub fn check_share_precision(&self, shares: Amount) -> Result<(), MarketError> {
+        let decimals = self.share_decimals();
+        if shares_to_units(shares.into(), decimals).is_none() {
+            return Err(MarketError::SharePrecision { shares, decimals });
+        }
+        Ok(())
+    }
+
+    /// The size checks every AMM trade of `shares` in `market` passes:
+    /// nonzero, at least `min_trade_shares`, within the market's own
+    /// `max_trade_shares`, and in whole share units
+    pub fn check_trade_shares(&self, market: &Market, shares: Amount) -> Result<(), MarketError> {
+        if shares == Amount::ZERO {
+            return Err(MarketError::ZeroShares);
+        }
+        let minimum = self.min_trade_shares;
+        if shares < minimum {
+            return Err(MarketError::TradeTooSmall { shares, minimum });
+        }
+        if let Some(cap) = market.max_trade_shares.filter(|cap| shares > *cap) {
+            return Err(MarketError::TradeTooLarge { market_id: market.id, shares, cap });
+        }
+        self.check_share_precision(shares)
+    }
+
+    /// What a trigger order placed now escrows for its keeper
+    pub fn keeper_fee(&self) -> Amount {
+        self.keeper_fee.unwrap_or(Amount::ZERO)
+    }
+
+    /// Basis points of a book fill's taker fee rebated to the maker
+    pub fn maker_rebate_bps(&self) -> u16 {
+        self.maker_rebate_bps.unwrap_or(0)
+    }
+
+    /// What CreateMarket escrows from the creator as a bond
+    pub fn creator_bond(&self) -> Amount {
+        self.creator_bond.unwrap_or(Amount::ZERO)
+    }
+
+    /// Basis points of a forfeited bond shared among the market's traders
+    pub fn bond_trader_share_bps(&self) -> u16 {
+        self.bond_trader_share_bps.unwrap_or(0)
+    }
+
+    /// The discount of the highest tier `volume` reaches, in basis points;
+    /// a tier applies from exactly its `min_volume`
+    pub fn fee_discount_bps(&self, volume: Amount) -> u16 {
+        self.fee_tiers.iter()
+            .take_while(|tier| tier.min_volume <= volume)
+            .last()
+            .map_or(0, |tier| tier.discount_bps)
+    }
+
+    /// `fee_bps` less the discount an account with lifetime `volume` earns,
+    /// rounded down
+    pub fn discounted_fee_bps(&self, fee_bps: u64, volume: Amount) -> u64 {
+        let kept = 10_000u64.saturating_sub(u64::from(self.fee_discount_bps(volume)));
+        fee_bps.saturating_mul(kept) / 10_000
+    }
+}
+
+impl BcsHashable<'_> for Config {}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            admin: AccountOwner::CHAIN,
+            fee_bps: 0,
+            min_initial_liquidity: Amount::ZERO,
+            min_market_duration_secs: 0,
+            resolution_grace_period_secs: 7 * 24 * 60 * 60,
+            admin_resolution_delay_secs: 3 * 24 * 60 * 60,
+            claim_window_secs: 30 * 24 * 60 * 60,
+            min_trade_shares: Amount::ZERO,
+            recent_trades_capacity: None,
+            share_decimals: None,
+            max_probability_move_bps: None,
+            keeper_fee: None,
+            maker_rebate_bps: None,
+            creator_bond: None,
+            bond_trader_share_bps: None,
+            fee_tiers: Vec::new(),
+        }
+    }
+}
+
+/// Argument passed to `instantiate`.
+pub type InstantiationArgument = Config;
+
+/// Cross-chain messages between instances of the application
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum Message {
+    /// Sent from CreateMarket to the registry chain
+    MarketAnnouncement {
+        market_id: u64,
+        origin_chain: ChainId,
+        market_key: MarketKey,
+        question: String,
+        end_time: Timestamp,
+        categories: Vec<String>,
+    },
+    /// A position moving to the receiving chain, sent with tracking so a
+    /// chain that rejects the message bounces it back to the sender
+    PositionTransfer {
+        transfer_id: u64,
+        /// Chain whose application holds the position's market
+        market_chain: ChainId,
+        /// The market's key, checked by its chain when the position comes
+        /// back; None for markets created before keys
+        market_key: Option<MarketKey>,
+        position: Position,
+        /// Refused if it arrives later than this
+        expires_at: Timestamp,
+    },
+    /// The receiver credited transfer `transfer_id` from this chain
+    TransferAccepted {
+        transfer_id: u64,
+    },
+    /// The receiver turned transfer `transfer_id` down, so the sender
+    /// restores the position
+    TransferRefused {
+        transfer_id: u64,
+        reason: String,
+    },
+}
+
+/// How long a `Message::PositionTransfer` may take to arrive before the
+/// receiver refuses it
+pub const POSITION_TRANSFER_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+// ==================== EVENTS ====================
+
+/// Name of the event stream the contract emits `MarketEvent`s on.
+pub const MARKET_EVENT_STREAM: &str = "chronos_market_events";
+
+/// Events emitted by the contract so off-chain indexers can follow trading
+/// without polling GraphQL. Only emitted once an operation has succeeded.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum MarketEvent {
+    TradeExecuted {
+        market_id: u64,
+        trader: AccountOwner,
+        side: OrderSide,
+        is_yes: bool,
+        shares: Amount,
+        cost: Amount,
+        /// Fee paid to LPs on the AMM leg and its rate in basis points
+        fee: Amount,
+        fee_bps: u64,
+        // Pool state and YES price (basis points) after the trade
+        yes_pool: Amount,
+        no_pool: Amount,
+        new_yes_price_bps: u64,
+        /// The market's trade sequence number, see `MarketState::trade_seqs`
+        trade_seq: u64,
+        /// The trader's shares of the traded side after the trade
+        position_after: Amount,
+        /// Deadline the trader signed the operation with, if any
+        deadline: Option<Timestamp>,
+        timestamp: Timestamp,
+    },
+    /// A resting order traded against an incoming one at the resting price
+    OrderFilled {
+        market_id: u64,
+        order_id: u64,
+        maker: AccountOwner,
+        taker: AccountOwner,
+        /// Side of the resting order
+        side: OrderSide,
+        is_yes: bool,
+        shares: Amount,
+        price_bps: u64,
+        /// The market's trade sequence number, see `MarketState::trade_seqs`
+        trade_seq: u64,
+        timestamp: Timestamp,
+    },
+    /// A market settled, by whichever resolver
+    MarketResolved {
+        market_id: u64,
+        outcome: bool,
+        resolved_by: AccountOwner,
+        evidence: Option<String>,
+        timestamp: Timestamp,
+    },
+    /// The admin replaced a settled market's outcome before any payout
+    OutcomeOverridden {
+        market_id: u64,
+        previous_outcome: bool,
+        outcome: bool,
+        admin: AccountOwner,
+        evidence: Option<String>,
+        /// The restarted claim window's end
+        claim_deadline: Timestamp,
+        timestamp: Timestamp,
+    },
+    /// The admin resolved a market its creator left unresolved
+    AdminResolved {
+        market_id: u64,
+        admin: AccountOwner,
+        outcome: bool,
+        timestamp: Timestamp,
+    },
+    /// A keeper executed a trigger order, selling its shares to the AMM or
+    /// buying them from it
+    TriggerOrderExecuted {
+        market_id: u64,
+        order_id: u64,
+        owner: AccountOwner,
+        keeper: AccountOwner,
+        side: OrderSide,
+        is_yes: bool,
+        shares: Amount,
+        /// Paid to the owner by a sale, after the trading fee
+        proceeds: Amount,
+        /// Paid by the owner for a purchase, fee included
+        cost: Amount,
+        keeper_fee: Amount,
+        timestamp: Timestamp,
+    },
+    /// A keeper found a triggered order's owner holding fewer shares than
+    /// it sells, and cancelled it
+    TriggerOrderCancelled {
+        market_id: u64,
+        order_id: u64,
+        owner: AccountOwner,
+        held: Amount,
+        timestamp: Timestamp,
+    },
+    /// A keeper found a triggered order's trade refused by a check, and
+    /// cancelled it, refunding its escrow
+    TriggerOrderFailed {
+        market_id: u64,
+        order_id: u64,
+        owner: AccountOwner,
+        reason: String,
+        timestamp: Timestamp,
+    },
+    /// A winning position was paid out
+    WinningsClaimed {
+        market_id: u64,
+        owner: AccountOwner,
+        payout: Amount,
+        /// What the position's shares cost, both sides; None for positions
+        /// opened before cost bases were tracked
+        cost_basis: Option<Amount>,
+        /// Payout minus cost basis in attos, negative when written-off
+        /// losing shares cost more than the win paid; None with the basis
+        profit: Option<i128>,
+        timestamp: Timestamp,
+    },
+    /// A market was voided for its creator's failings and their bond split
+    /// between the treasury and the traders
+    BondForfeited {
+        market_id: u64,
+        creator: AccountOwner,
+        to_treasury: Amount,
+        to_traders: Amount,
+        timestamp: Timestamp,
+    },
+    /// A market settled and one funder got back its share of the part of
+    /// the incentive budget the LPs had not earned
+    IncentivesReturned {
+        market_id: u64,
+        funder: AccountOwner,
+        amount: Amount,
+        timestamp: Timestamp,
+    },
+    /// A payout was refused because the tracked collateral balance cannot
+    /// cover it: the bookkeeping needs reconciling
+    PayoutRefused {
+        market_id: u64,
+        owner: AccountOwner,
+        payout: Amount,
+        balance: Amount,
+        timestamp: Timestamp,
+    },
+    /// The admin deposited tokens to cover a shortfall in the tracked
+    /// collateral balance
+    BalanceReconciled {
+        admin: AccountOwner,
+        deposit: Amount,
+        balance: Amount,
+        timestamp: Timestamp,
+    },
+    /// A market past its end time was marked closed, by CloseMarket or a
+    /// FinalizeExpired sweep
+    MarketClosed {
+        market_id: u64,
+        closed_by: AccountOwner,
+        timestamp: Timestamp,
+    },
+}
+
 // Note: Response type is now String. The contract returns descriptive strings:
-// - Success: "MarketCreated:42", "SharesPurchased:12.5", etc.
+// - Success: "MarketCreated:42", "SharesPurchased:12.5:10.:5500:4500:40.:62.5", etc.
+//   Trades report cost (or proceeds), shares, the new YES and NO prices in
+//   basis points and the resulting YES and NO pools.
 // - Error: "ERROR: Market not found", "ERROR: Cost exceeds max_cost", etc.
 
+// ==================== CALENDAR ====================
+
+/// Microseconds in one UTC day
+pub const DAY_MICROS: u64 = 86_400_000_000;
+
+/// Hours since the Unix epoch: the key of hourly volume buckets
+pub fn hour_index(timestamp: Timestamp) -> u64 {
+    timestamp.micros() / VOLUME_BUCKET_MICROS
+}
+
+/// Whole UTC days since the Unix epoch: the key of `MarketState::volume_by_day`
+pub fn day_index(timestamp: Timestamp) -> u64 {
+    timestamp.micros() / DAY_MICROS
+}
+
+/// Days of `MarketState::volume_by_day` kept: a year of `volumeHistory` with
+/// some slack
+pub const VOLUME_HISTORY_DAYS: u64 = 400;
+
+/// Oldest day still kept in `MarketState::volume_by_day` on day `today`;
+/// buckets before it get pruned
+pub fn volume_history_start(today: u64) -> u64 {
+    today.saturating_sub(VOLUME_HISTORY_DAYS - 1)
+}
+
+/// UTC calendar date of a timestamp as (year, month, day)
+pub fn utc_civil_date(timestamp: Timestamp) -> (i64, i64, i64) {
+    // Civil-from-days conversion on the proleptic Gregorian calendar
+    let days = day_index(timestamp) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
 // ==================== APPLICATION STATE ====================
 // Shared between contract and service — single source of truth
 // Following Linera-Mine pattern (NeoCrafts-cpu/Linera-Mine)
@@ -168,14 +1189,141 @@ pub struct ComboLeg {
 pub struct MarketState {
     // Core market state
     pub markets: MapView<u64, Market>,
+    /// What listings show of each market, written with it
+    pub summaries: MapView<u64, MarketSummary>,
     pub positions: MapView<(AccountOwner, u64), Position>,
+    /// Ids of the markets each account holds a position on, ascending;
+    /// written with the position, so an account's positions are read
+    /// without scanning everyone's
+    pub positions_by_owner: MapView<AccountOwner, Vec<u64>>,
+    /// Accounts holding a position on each market, ascending; written with
+    /// the position, so settling a market reads only its own holders
+    pub market_holders: MapView<u64, Vec<AccountOwner>>,
+    /// Markets each account has created
+    pub markets_by_creator: MapView<(AccountOwner, u64), ()>,
+    /// Conditional markets created on each parent market
+    pub conditional_markets: MapView<u64, Vec<u64>>,
+    /// The local id of each market by its `Market::key`
+    pub market_keys: MapView<MarketKey, u64>,
+    /// Markets each account has created on this chain, the nonce of the
+    /// next one's key
+    pub market_nonces: MapView<AccountOwner, u64>,
+    /// Keys of the other chains' markets this chain holds positions in, per
+    /// (market chain, market id), so returning positions can name them
+    pub imported_market_keys: MapView<(ChainId, u64), MarketKey>,
+    /// Ids of the markets tagged with each lowercased category, ascending
+    pub markets_by_category: MapView<String, Vec<u64>>,
+    /// Liquidity provided per (owner, market_id)
+    pub lp_positions: MapView<(AccountOwner, u64), LpPosition>,
+    /// Collateral each (owner, market_id) LP has deposited and withdrawn
+    pub lp_ledgers: MapView<(AccountOwner, u64), LpLedger>,
+    /// The bond escrowed by each market's creator, if any
+    pub creator_bonds: MapView<u64, CreatorBond>,
+    /// Traders' shares of forfeited bonds, paid with their refunds, per
+    /// (owner, market_id)
+    pub bond_shares: MapView<(AccountOwner, u64), Amount>,
+    /// The incentive program funded on each market, at most one
+    pub incentives: MapView<u64, IncentiveProgram>,
+    /// Incentive accumulator value each (owner, market_id) LP was last paid
+    /// at; absent reads as zero, the accumulator's value when funded
+    pub incentive_checkpoints: MapView<(AccountOwner, u64), u128>,
     pub next_market_id: RegisterView<u64>,
     pub total_volume: RegisterView<Amount>,
+    /// Protocol configuration set at instantiation
+    pub config: RegisterView<Config>,
+    /// Set by the admin to halt CreateMarket and BuyShares
+    pub paused: RegisterView<bool>,
+    /// Ids of the markets with `Market::featured` set, in the order the
+    /// admin placed them
+    pub featured_markets: RegisterView<Vec<u64>>,
+
+    // Incrementally maintained dashboard aggregates
+    pub seen_traders: MapView<AccountOwner, ()>,
+    pub unique_traders: RegisterView<u64>,
+    /// Accounts that have traded in a market of each lowercased category,
+    /// and how many there are per category
+    pub category_traders: MapView<(String, AccountOwner), ()>,
+    pub category_trader_counts: MapView<String, u64>,
+    /// Per-account trading results across all markets
+    pub trader_stats: MapView<AccountOwner, TraderStats>,
+    /// Markets each account has traded in, so its stats count each once
+    pub traded_markets: MapView<(AccountOwner, u64), ()>,
+    /// Each account's trading per day over the last `TRADER_DAY_SLOTS` days
+    pub trader_days: MapView<AccountOwner, TraderDays>,
+    /// Sum of escrow across unresolved markets
+    pub total_value_locked: RegisterView<Amount>,
+    /// Sum of escrow across all markets, resolved ones included until it
+    /// is claimed or recovered
+    pub total_open_interest: RegisterView<Amount>,
+    /// Sum of `Market::liquidity` across unresolved markets
+    pub total_active_liquidity: RegisterView<Amount>,
+    /// Tokens the application holds: every market's escrow and LP fees not
+    /// yet paid out, the liquidity series have put up for markets still to
+    /// open, the keeper fees of pending trigger orders, the maker rebates
+    /// not yet withdrawn, incentive budgets not yet paid out, creator bonds
+    /// and the treasury. No payout may exceed it.
+    pub contract_balance: RegisterView<Amount>,
+    /// Tokens the admin has deposited through ReconcileBalance, in total
+    pub reconciled_deposits: RegisterView<Amount>,
+    /// Forfeited creator bonds kept by the protocol
+    pub treasury: RegisterView<Amount>,
+    /// Unresolved markets, and those of them marked `Market::closed`
+    pub active_market_count: RegisterView<u64>,
+    pub closed_market_count: RegisterView<u64>,
+    /// Resolved markets, voided ones included, and the voided ones alone
+    pub resolved_market_count: RegisterView<u64>,
+    pub voided_market_count: RegisterView<u64>,
+    /// Traded volume per hour, keyed by `hour_index`
+    pub hourly_volume: MapView<u64, Amount>,
+    /// The latest trades across all markets, oldest first, at most
+    /// `Config::recent_trades_capacity`
+    pub recent_trades: QueueView<RecentTrade>,
+    /// Traded volume per UTC day, keyed by `day_index`, for the last
+    /// `VOLUME_HISTORY_DAYS` days
+    pub volume_by_day: MapView<u64, Amount>,
+    /// Traded volume per market over roughly the last day, by hour
+    pub market_volume: MapView<u64, VolumeRing>,
+    /// Next market id FinalizeExpired will inspect
+    pub finalize_cursor: RegisterView<u64>,
 
-    // Limit orders
-    pub limit_orders: MapView<u64, LimitOrder>,
-    pub next_order_id: RegisterView<u64>,
+    // Limit orders, keyed by (market_id, order_id)
+    pub limit_orders: MapView<(u64, u64), LimitOrder>,
+    /// Next order id per market
+    pub order_counters: MapView<u64, u64>,
+    /// Ids of resting orders per (market_id, is_yes), oldest first
     pub order_book: MapView<(u64, bool), Vec<u64>>,
+    /// Maker rebates earned and not yet withdrawn, per account
+    pub maker_rebates: MapView<AccountOwner, Amount>,
+
+    // Trigger orders, keyed by (market_id, order_id)
+    pub trigger_orders: MapView<(u64, u64), TriggerOrder>,
+    /// Next trigger order id per market
+    pub trigger_order_counters: MapView<u64, u64>,
+    /// Ids of pending trigger orders per market, oldest first
+    pub pending_triggers: MapView<u64, Vec<u64>>,
+
+    // Recurring market series
+    pub series: MapView<u64, MarketSeries>,
+    pub next_series_id: RegisterView<u64>,
+
+    // Mutually exclusive market groups
+    pub groups: MapView<u64, MarketGroup>,
+    pub next_group_id: RegisterView<u64>,
+
+    /// Outcome votes cast so far on markets with resolvers, in voting order
+    pub resolution_votes: MapView<u64, Vec<ResolutionVote>>,
+    /// Trade events (`TradeExecuted` and `OrderFilled`) emitted per market,
+    /// which is the next one's `trade_seq`: a market's trade events number
+    /// 0, 1, 2, ... in order, so a gap means a missed event
+    pub trade_seqs: MapView<u64, u64>,
+    /// Price accumulator readings per market, oldest first, at most
+    /// `MAX_PRICE_CHECKPOINTS`
+    pub price_checkpoints: MapView<u64, Vec<PriceCheckpoint>>,
+    /// Every price checkpoint per (market_id, n), n counting each market's
+    /// checkpoints from 0 in time order. Never trimmed, so point-in-time
+    /// lookups can binary search a market's whole history.
+    pub price_log: MapView<(u64, u64), PriceCheckpoint>,
+    pub price_log_counts: MapView<u64, u64>,
 
     // Combos/Parlays
     pub combos: MapView<u64, Combo>,
@@ -193,11 +1341,228 @@ pub struct MarketState {
     pub user_followers: MapView<AccountOwner, Vec<AccountOwner>>,
     pub user_following: MapView<AccountOwner, Vec<AccountOwner>>,
     pub item_likes: MapView<u64, Vec<AccountOwner>>,
+    /// Accounts upvoting each market, keyed by (account, market id)
+    pub market_upvotes: MapView<(AccountOwner, u64), ()>,
+
+    // Registry: markets announced by other chains, keyed by (origin chain, market id)
+    pub remote_markets: MapView<(ChainId, u64), RemoteMarket>,
+
+    // Positions moved between chains
+    /// Positions sent by MigratePosition or ReturnPosition and not yet
+    /// accepted, by transfer id. Restored if the receiver refuses them.
+    pub outgoing_transfers: MapView<u64, PositionTransfer>,
+    pub next_transfer_id: RegisterView<u64>,
+    /// The answer given to each transfer received, by (sending chain,
+    /// transfer id): None if accepted, else why it was refused. A
+    /// redelivered transfer gets the same answer again and is credited
+    /// only once.
+    pub received_transfers: MapView<(ChainId, u64), Option<String>>,
+    /// Positions on other chains' markets migrated to this chain, by
+    /// (market chain, market id, owner)
+    pub imported_positions: MapView<(ChainId, u64, AccountOwner), Position>,
+
+    /// Summaries of archived markets, which are gone from `markets`. Their
+    /// ids stay in the creator and category indices.
+    pub archive: MapView<u64, ArchivedMarket>,
+
+    /// Every successful operation, oldest first. Never pruned, not even by
+    /// ArchiveMarket.
+    pub activity: LogView<ActivityEntry>,
+    /// `activity` indices per (market_id, n), n counting each market's
+    /// entries from 0
+    pub market_activity: MapView<(u64, u64), u64>,
+    pub market_activity_counts: MapView<u64, u64>,
+    /// `activity` indices per (actor, n), n counting each actor's entries from 0
+    pub owner_activity: MapView<(AccountOwner, u64), u64>,
+    pub owner_activity_counts: MapView<AccountOwner, u64>,
+}
+
+/// Hand out the ID `counter` holds and advance it. None once it reaches
+/// u64::MAX rather than wrapping round to reuse ID 0, so that value itself
+/// is never issued. IDs are only ever handed out in order; archiving can
+/// leave gaps below the counter, so readers must not assume every lower ID
+/// exists.
+pub fn allocate_id<C: linera_views::context::Context>(
+    counter: &mut linera_views::register_view::RegisterView<C, u64>,
+) -> Option<u64> {
+    let id = *counter.get();
+    counter.set(id.checked_add(1)?);
+    Some(id)
 }
 
 // ==================== DATA TYPES ====================
 
+/// A market's identity across chains: `Market::id` counts from 0 on every
+/// chain, so ids collide once markets are mirrored or migrated
+pub type MarketKey = CryptoHash;
+
+/// What a market's key hashes
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketKeySeed {
+    /// The chain the market was created on
+    pub chain_id: ChainId,
+    pub creator: AccountOwner,
+    /// Markets `creator` had created on `chain_id` before this one
+    pub nonce: u64,
+}
+
+impl BcsHashable<'_> for MarketKeySeed {}
+
+/// The key of the market `creator` creates on `chain_id` after `nonce` others
+pub fn market_key(chain_id: ChainId, creator: AccountOwner, nonce: u64) -> MarketKey {
+    CryptoHash::new(&MarketKeySeed { chain_id, creator, nonce })
+}
+
+/// Current `Market::version`
+pub const MARKET_VERSION: u8 = 10;
+
+/// Current `Position::version`
+pub const POSITION_VERSION: u8 = 1;
+
+/// Opens every Market and Position record written with its layout version.
+/// Records from before then open with their id instead, which never comes
+/// near it.
+pub const RECORD_ENVELOPE: u64 = u64::MAX;
+
+/// Whether `error` is BCS running out of bytes, as it does on a field
+/// appended to a struct after the record was written
+fn is_end_of_input(error: &impl std::fmt::Display) -> bool {
+    error.to_string() == linera_sdk::bcs::Error::Eof.to_string()
+}
+
+/// BCS records carry no field names, so a field appended to a struct is
+/// simply missing from records written before it existed, and decoding runs
+/// off the end of the bytes. Used on trailing fields only: it turns that
+/// into the field's default, and leaves every other error standing.
+fn default_if_missing<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    match T::deserialize(deserializer) {
+        Err(error) if is_end_of_input(&error) => Ok(T::default()),
+        result => result,
+    }
+}
+
+/// Reads the fields of a record in a versioned envelope, which holds
+/// exactly those added up to its version
+struct RecordFields<A> {
+    seq: A,
+    /// 0 for a record from before the envelope
+    version: u8,
+}
+
+impl<'de, A: serde::de::SeqAccess<'de>> RecordFields<A> {
+    /// Open a record whose first field is a u64 id, returning that id
+    fn open(mut seq: A, record: &str, latest: u8) -> Result<(Self, u64), A::Error> {
+        let first = Self::next(&mut seq)?;
+        if first != RECORD_ENVELOPE {
+            return Ok((RecordFields { seq, version: 0 }, first));
+        }
+        let version = Self::next(&mut seq)?;
+        if version == 0 || version > latest {
+            return Err(serde::de::Error::custom(format!(
+                "{} record version {} is not one of 1 to {}", record, version, latest
+            )));
+        }
+        let id = Self::next(&mut seq)?;
+        Ok((RecordFields { seq, version }, id))
+    }
+
+    fn next<T: Deserialize<'de>>(seq: &mut A) -> Result<T, A::Error> {
+        seq.next_element()?.ok_or_else(|| serde::de::Error::custom("record ends early"))
+    }
+
+    /// The next field, which every layout has
+    fn field<T: Deserialize<'de>>(&mut self) -> Result<T, A::Error> {
+        Self::next(&mut self.seq)
+    }
+
+    /// The next field, added in version `since`: its default in layouts
+    /// before that. Records from before the envelope end
+    /// wherever the build that wrote them did, so for those the fields of
+    /// version 1 may run out and read as their defaults.
+    fn since<T: Deserialize<'de> + Default>(&mut self, since: u8) -> Result<T, A::Error> {
+        if self.version >= since {
+            return Self::next(&mut self.seq);
+        }
+        if self.version > 0 || since > 1 {
+            return Ok(T::default());
+        }
+        match Self::next(&mut self.seq) {
+            Err(error) if is_end_of_input(&error) => Ok(T::default()),
+            result => result,
+        }
+    }
+}
+
+/// Implements Serialize and Deserialize for a record kept in a versioned
+/// envelope: `RECORD_ENVELOPE`, the layout version, then that layout's
+/// fields in order. The first field is a u64 id, followed by those every
+/// layout has, then those added since, each with the version that added
+/// it. The record's `version` is the one it was read at.
+/// Records are always written at `$latest`; human-readable formats get a
+/// plain struct.
+macro_rules! versioned_record {
+    (
+        $record:ident, $latest:expr, $first:ident,
+        [$($field:ident),* $(,)?],
+        [$($added:ident: $since:literal),* $(,)?] $(,)?
+    ) => {
+        impl Serialize for $record {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::{SerializeStruct, SerializeTuple};
+                const FIELDS: usize = 1 + [$(stringify!($field)),*].len() + [$(stringify!($added)),*].len();
+                if serializer.is_human_readable() {
+                    let mut record = serializer.serialize_struct(stringify!($record), FIELDS + 1)?;
+                    record.serialize_field(stringify!($first), &self.$first)?;
+                    $(record.serialize_field(stringify!($field), &self.$field)?;)*
+                    $(record.serialize_field(stringify!($added), &self.$added)?;)*
+                    record.serialize_field("version", &self.version)?;
+                    return record.end();
+                }
+                let mut record = serializer.serialize_tuple(FIELDS + 2)?;
+                record.serialize_element(&RECORD_ENVELOPE)?;
+                record.serialize_element(&$latest)?;
+                record.serialize_element(&self.$first)?;
+                $(record.serialize_element(&self.$field)?;)*
+                $(record.serialize_element(&self.$added)?;)*
+                record.end()
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $record {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct RecordVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for RecordVisitor {
+                    type Value = $record;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(formatter, "a {} record", stringify!($record))
+                    }
+
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> Result<$record, A::Error> {
+                        let (mut fields, $first) = RecordFields::open(seq, stringify!($record), $latest)?;
+                        Ok($record {
+                            $first,
+                            $($field: fields.field()?,)*
+                            $($added: fields.since($since)?,)*
+                            version: fields.version,
+                        })
+                    }
+                }
+
+                // Reading stops at the last field the record's layout has
+                deserializer.deserialize_tuple(usize::MAX, RecordVisitor)
+            }
+        }
+    };
+}
+
+/// Stored in a versioned envelope; see `versioned_record`
+#[derive(Debug, Clone)]
 pub struct Market {
     pub id: u64,
     pub creator: AccountOwner,
@@ -212,15 +1577,874 @@ pub struct Market {
     pub resolved: bool,
     pub outcome: Option<bool>,
     pub volume: Amount,
+    /// Collateral held for this market alone: liquidity plus buy costs,
+    /// minus sell proceeds and claimed payouts.
+    pub escrow: Amount,
+    pub early_resolution_allowed: bool,
+    pub resolution_time: Option<Timestamp>,
+    /// Shares per side minted to seed the AMM; held by no trader
+    pub seed_shares: Amount,
+    /// Set when the winning side has no trader-held shares: every position
+    /// holder claims back their cost basis instead of a payout.
+    pub refund_mode: bool,
+    pub max_position_per_account: Option<Amount>,
+    /// Cap on one account's YES plus NO shares
+    pub max_position: Option<Amount>,
+    /// The creator may not buy shares; fixed at creation
+    pub creator_trading_disabled: bool,
+    /// Fee increase near end_time; None for the flat base fee
+    pub fee_ramp: Option<FeeRamp>,
+    /// Resolved without an outcome; holders are refunded their cost basis
+    pub voided: bool,
+    /// Account that resolved the market: the creator, or the admin for an
+    /// abandoned market
+    pub resolved_by: Option<AccountOwner>,
+    /// Last moment ClaimWinnings is accepted, inclusive; set on resolution
+    pub claim_deadline: Option<Timestamp>,
+    pub description: String,
+    pub resolution_source: String,
+    /// Liquidity shares outstanding; trading fees are split pro rata over these
+    pub total_lp_shares: Amount,
+    /// Fees accrued per LP share since creation, scaled by `FEE_SCALE`
+    pub fees_per_lp_share: u128,
+    /// Trading fees charged on AMM trades, held apart from `escrow`
+    pub fees_collected: Amount,
+    /// Fees paid out through ClaimLpFees
+    pub fees_distributed: Amount,
+    /// Set on conditional markets: resolves only after the parent, and is
+    /// voided if the parent resolves any other way
+    pub parent: Option<MarketCondition>,
+    /// Set on members of a market group, which resolve together
+    pub group_id: Option<u64>,
+    /// Accounts voting on the outcome; empty when the creator resolves
+    pub resolvers: Vec<AccountOwner>,
+    /// Matching resolver votes needed to settle
+    pub threshold: u8,
+    /// Sum over time of the YES price in basis points times the microseconds
+    /// it held. Wraps on overflow, so only the wrapping difference between
+    /// two readings is meaningful; at 10_000 bps per microsecond that
+    /// difference stays exact for far longer than any market lives.
+    pub price_cumulative: u128,
+    /// When `price_cumulative` was last brought up to date; None until the
+    /// first update, in which case the creation time is used
+    pub price_updated_at: Option<Timestamp>,
+    /// Tighter cap than `Config::max_probability_move_bps` on how far one
+    /// buy may move the implied probability; None for the global cap.
+    /// Added in version 2.
+    pub max_probability_move_bps: Option<u64>,
+    /// The source the settling resolver cited, if any. Set with the
+    /// outcome, and replaced only by OverrideResolution. Added in version 3.
+    pub resolution_evidence: Option<String>,
+    /// Set by the first payout out of the settled market, after which its
+    /// outcome can no longer be overridden. Added in version 4.
+    pub claims_started: bool,
+    /// Set by CloseMarket, or the first FinalizeExpired sweep to find the
+    /// market past its end time and unresolved; stays set once it resolves.
+    /// Added in version 5.
+    pub closed: bool,
+    /// Whether the admin lists it among `MarketState::featured_markets`.
+    /// Added in version 6.
+    pub featured: bool,
+    /// Most shares one buy or sell may trade, so a single transaction cannot
+    /// swing a thin market; None for no cap. Added in version 7.
+    pub max_trade_shares: Option<Amount>,
+    /// Accounts currently upvoting it. Added in version 8.
+    pub upvotes: u64,
+    /// Identifies it across chains, unlike `id`; see `market_key`. None for
+    /// markets created before version 9.
+    pub key: Option<MarketKey>,
+    /// One entry per account that funded its incentives, in funding order.
+    /// Added in version 10.
+    pub sponsors: Vec<Sponsor>,
+    /// Record layout this market was read with: 0 for markets stored before
+    /// the envelope, `MARKET_VERSION` once upgraded
+    pub version: u8,
+}
+
+versioned_record!(Market, MARKET_VERSION, id, [
+    creator,
+    question,
+    categories,
+    end_time,
+    created_at,
+    yes_pool,
+    no_pool,
+    total_yes_shares,
+    total_no_shares,
+    resolved,
+    outcome,
+    volume,
+    escrow,
+    early_resolution_allowed,
+    resolution_time,
+    seed_shares,
+    refund_mode,
+    max_position_per_account,
+    max_position,
+    creator_trading_disabled,
+    fee_ramp,
+    voided,
+    resolved_by,
+    claim_deadline,
+    description,
+    resolution_source,
+    total_lp_shares,
+    fees_per_lp_share,
+    fees_collected,
+    fees_distributed,
+], [
+    parent: 1,
+    group_id: 1,
+    resolvers: 1,
+    threshold: 1,
+    price_cumulative: 1,
+    price_updated_at: 1,
+    max_probability_move_bps: 2,
+    resolution_evidence: 3,
+    claims_started: 4,
+    closed: 5,
+    featured: 6,
+    max_trade_shares: 7,
+    upvotes: 8,
+    key: 9,
+    sponsors: 10,
+]);
+
+/// Where a market stands at a moment, whether or not anything has been
+/// written to it since its end time passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectiveStatus {
+    /// Before its end time and unresolved: it trades
+    Open,
+    /// At or past its end time and unresolved, whether or not it was marked
+    /// `Market::closed`: it awaits resolution
+    Closed,
+    /// Settled, with an outcome or voided
+    Resolved,
+}
+
+impl EffectiveStatus {
+    /// The status of a market with the given flags and end time at `now`.
+    /// Trading stops at end_time itself.
+    pub fn at(resolved: bool, closed: bool, end_time: Timestamp, now: Timestamp) -> Self {
+        if resolved {
+            EffectiveStatus::Resolved
+        } else if closed || now >= end_time {
+            EffectiveStatus::Closed
+        } else {
+            EffectiveStatus::Open
+        }
+    }
+}
+
+/// `market`'s status at `now`: the one cutoff the contract enforces and the
+/// service shows
+pub fn effective_status(market: &Market, now: Timestamp) -> EffectiveStatus {
+    EffectiveStatus::at(market.resolved, market.closed, market.end_time, now)
+}
+
+/// Refuse a trade on a market that is no longer open at `now`
+pub fn check_open(market: &Market, now: Timestamp) -> Result<(), MarketError> {
+    match effective_status(market, now) {
+        EffectiveStatus::Open => Ok(()),
+        EffectiveStatus::Resolved => Err(MarketError::AlreadyResolved { market_id: market.id }),
+        EffectiveStatus::Closed => Err(MarketError::MarketEnded {
+            market_id: market.id,
+            now_micros: now.micros(),
+            end_micros: market.end_time.micros(),
+        }),
+    }
+}
+
+impl Market {
+    /// Bring a market read from storage up to `MARKET_VERSION`
+    pub fn upgrade(&mut self) {
+        // Fields the stored layout lacked were read as their defaults,
+        // which is all any version so far needs
+        self.version = MARKET_VERSION;
+    }
+
+    /// The cap on one buy's probability move in effect: this market's own,
+    /// else the global one; None for no cap
+    pub fn probability_move_limit_bps(&self, config: &Config) -> Option<u64> {
+        self.max_probability_move_bps.or(config.max_probability_move_bps)
+    }
+
+    /// Implied YES price in basis points: no_pool / (yes_pool + no_pool)
+    pub fn yes_price_bps(&self) -> u64 {
+        let price = price_of(u128::from(self.yes_pool), u128::from(self.no_pool));
+        (price / (PRICE_SCALE / 10_000)) as u64
+    }
+
+    /// Implied NO price in basis points: yes_pool / (yes_pool + no_pool)
+    pub fn no_price_bps(&self) -> u64 {
+        let price = price_of(u128::from(self.no_pool), u128::from(self.yes_pool));
+        (price / (PRICE_SCALE / 10_000)) as u64
+    }
+
+    /// Shares the AMM holds on both sides: yes_pool + no_pool
+    pub fn liquidity(&self) -> Amount {
+        self.yes_pool.saturating_add(self.no_pool)
+    }
+
+    /// Trading fee at `now` in basis points: `base_bps`, ramped up near
+    /// end_time if the market has a `fee_ramp`
+    pub fn fee_bps_at(&self, base_bps: u16, now: Timestamp) -> u64 {
+        FeeRamp::fee_bps(self.fee_ramp, base_bps, self.end_time, now)
+    }
+
+    /// `price_cumulative` extended to `now` at the current price. Frozen once
+    /// the market resolves.
+    pub fn price_cumulative_at(&self, now: Timestamp) -> u128 {
+        let since = self.price_updated_at.unwrap_or(self.created_at);
+        if self.resolved || now <= since {
+            return self.price_cumulative;
+        }
+        let elapsed = u128::from(now.delta_since(since).as_micros());
+        self.price_cumulative.wrapping_add(u128::from(self.yes_price_bps()).wrapping_mul(elapsed))
+    }
+
+    /// Bring `price_cumulative` up to `now`; call before the pools change
+    /// and before resolving.
+    pub fn accumulate_price(&mut self, now: Timestamp) {
+        self.price_cumulative = self.price_cumulative_at(now);
+        self.price_updated_at = Some(now);
+    }
+
+    /// What ClaimWinnings would pay `position` at `now`: the cost basis in
+    /// refund mode, otherwise a pro-rata share of the pools, capped by escrow.
+    pub fn claimable(&self, position: &Position, now: Timestamp) -> Amount {
+        if !self.resolved || position.claimed || self.claim_deadline.is_some_and(|deadline| now > deadline) {
+            return Amount::ZERO;
+        }
+        let amount = if self.refund_mode {
+            position.yes_cost_basis.saturating_add(position.no_cost_basis)
+        } else {
+            let (winning, total_winning) = match self.outcome {
+                Some(true) => (position.yes_shares, self.total_yes_shares),
+                Some(false) => (position.no_shares, self.total_no_shares),
+                None => return Amount::ZERO,
+            };
+            let total_pool = self.yes_pool.saturating_add(self.no_pool);
+            payout_for_shares(u128::from(winning), u128::from(total_winning), u128::from(total_pool))
+                .map(Amount::from_attos)
+                .unwrap_or(Amount::ZERO)
+        };
+        amount.min(self.escrow)
+    }
+
+    /// What `position` is worth at `now`: its shares at the AMM's current
+    /// prices while trading, what it can claim once resolved.
+    pub fn position_value(&self, position: &Position, now: Timestamp) -> Amount {
+        if self.resolved {
+            return self.claimable(position, now);
+        }
+        let total_pool = u128::from(self.yes_pool).saturating_add(u128::from(self.no_pool));
+        if total_pool == 0 {
+            return Amount::ZERO;
+        }
+        // A side's price is the opposite pool's share of both pools
+        let value = |shares: Amount, opposite_pool: Amount| {
+            safe_mul_div(u128::from(shares), u128::from(opposite_pool), total_pool).unwrap_or(0)
+        };
+        let yes = value(position.yes_shares, self.no_pool);
+        let no = value(position.no_shares, self.yes_pool);
+        Amount::from_attos(yes.saturating_add(no))
+    }
+
+    /// Spread `fee` over the outstanding LP shares. The per-share increment
+    /// is floored, so any remainder stays in the pool undistributed; with no
+    /// LP shares the whole fee does.
+    pub fn accrue_fee(&mut self, fee: Amount) {
+        self.fees_collected = self.fees_collected.saturating_add(fee);
+        if self.total_lp_shares == Amount::ZERO {
+            return;
+        }
+        if let Ok(increment) = safe_mul_div(u128::from(fee), FEE_SCALE, u128::from(self.total_lp_shares)) {
+            self.fees_per_lp_share = self.fees_per_lp_share.saturating_add(increment);
+        }
+    }
+
+    /// What the market's LPs could redeem, before fees: the escrow less what
+    /// trader-held shares are worth. Those are marked at the AMM's prices
+    /// while trading and at their payout once resolved; in refund mode the
+    /// traders take back `trader_cost_basis`, their cost basis summed.
+    pub fn lp_value(&self, trader_cost_basis: Amount) -> Amount {
+        let yes = u128::from(self.total_yes_shares.saturating_sub(self.seed_shares));
+        let no = u128::from(self.total_no_shares.saturating_sub(self.seed_shares));
+        let total_pool = u128::from(self.yes_pool).saturating_add(u128::from(self.no_pool));
+        let owed = if !self.resolved {
+            if total_pool == 0 {
+                0
+            } else {
+                // A side's price is the opposite pool's share of both pools
+                let yes = safe_mul_div(yes, u128::from(self.no_pool), total_pool).unwrap_or(0);
+                let no = safe_mul_div(no, u128::from(self.yes_pool), total_pool).unwrap_or(0);
+                yes.saturating_add(no)
+            }
+        } else if self.refund_mode {
+            u128::from(trader_cost_basis)
+        } else {
+            let (winning, total_winning) = match self.outcome {
+                Some(true) => (yes, u128::from(self.total_yes_shares)),
+                Some(false) => (no, u128::from(self.total_no_shares)),
+                None => (0, 0),
+            };
+            payout_for_shares(winning, total_winning, total_pool).unwrap_or(0)
+        };
+        self.escrow.saturating_sub(Amount::from_attos(owed))
+    }
+
+    /// Fees `lp` has accrued since its checkpoint, floored; never more than
+    /// the collected fees not yet paid out.
+    pub fn pending_lp_fees(&self, lp: &LpPosition) -> Amount {
+        let accrued = self.fees_per_lp_share.saturating_sub(lp.fee_checkpoint);
+        let owed = safe_mul_div(u128::from(lp.shares), accrued, FEE_SCALE).unwrap_or(0);
+        Amount::from_attos(owed).min(self.fees_collected.saturating_sub(self.fees_distributed))
+    }
+}
+
+/// One successful operation, as appended to `MarketState::activity`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    /// `Operation::kind` of the operation
+    pub kind: String,
+    /// The market named by the operation, or the first one it created
+    pub market_id: Option<u64>,
+    pub actor: AccountOwner,
+    pub amount: Option<Amount>,
+    pub shares: Option<Amount>,
+    /// The operation's response, which carries costs, payouts and new ids
+    pub result: String,
+    pub timestamp: Timestamp,
+    /// The side traded or ordered
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub is_yes: Option<bool>,
+    /// For an operation that traded: the YES price after it, in basis points
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub yes_price_bps: Option<u64>,
+    /// For an operation that traded: the `trade_seq` of its last trade event
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub trade_seq: Option<u64>,
+    /// For an operation that traded one side: the actor's shares of that
+    /// side after it
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub position_after: Option<Amount>,
+}
+
+/// The fields of a `Market` a listing row shows, kept under the same id so
+/// listings need not decode full records
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketSummary {
+    pub id: u64,
+    pub question: String,
+    pub yes_pool: Amount,
+    pub no_pool: Amount,
+    pub volume: Amount,
+    pub end_time: Timestamp,
+    pub resolved: bool,
+    pub outcome: Option<bool>,
+    pub voided: bool,
+    pub closed: bool,
+}
+
+impl From<&Market> for MarketSummary {
+    fn from(market: &Market) -> Self {
+        MarketSummary {
+            id: market.id,
+            question: market.question.clone(),
+            yes_pool: market.yes_pool,
+            no_pool: market.no_pool,
+            volume: market.volume,
+            end_time: market.end_time,
+            resolved: market.resolved,
+            outcome: market.outcome,
+            voided: market.voided,
+            closed: market.closed,
+        }
+    }
+}
+
+impl MarketSummary {
+    /// Implied YES price in basis points, as `Market::yes_price_bps`
+    pub fn yes_price_bps(&self) -> u64 {
+        let price = price_of(u128::from(self.yes_pool), u128::from(self.no_pool));
+        (price / (PRICE_SCALE / 10_000)) as u64
+    }
+
+    /// Implied NO price in basis points, as `Market::no_price_bps`
+    pub fn no_price_bps(&self) -> u64 {
+        let price = price_of(u128::from(self.no_pool), u128::from(self.yes_pool));
+        (price / (PRICE_SCALE / 10_000)) as u64
+    }
+}
+
+/// What remains of a market once ArchiveMarket has pruned it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedMarket {
+    pub id: u64,
+    pub question: String,
+    /// None for a voided market
+    pub outcome: Option<bool>,
+    pub final_volume: Amount,
+    pub resolved_at: Timestamp,
 }
 
+/// Width of a `MarketState::hourly_volume` bucket
+pub const VOLUME_BUCKET_MICROS: u64 = 60 * 60 * 1_000_000;
+
+/// A trade as kept in `MarketState::recent_trades`, with what a ticker
+/// shows so it needs no further lookups
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentTrade {
+    pub market_id: u64,
+    pub question: String,
+    pub trader: AccountOwner,
+    pub side: OrderSide,
+    pub is_yes: bool,
+    pub shares: Amount,
+    /// Paid for a buy, fee included; received for a sell
+    pub amount: Amount,
+    /// YES price right after the trade
+    pub yes_price_bps: u64,
+    pub timestamp: Timestamp,
+}
+
+/// Slots in a `VolumeRing`: one spare beyond the 24 hours it sums
+pub const VOLUME_RING_SLOTS: u64 = 25;
+
+/// Volume per hour, held in slot `hour % VOLUME_RING_SLOTS` together with
+/// the hour it counts. A slot left holding an older hour is stale: it counts
+/// as zero when read and is reset by the next trade that lands on it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VolumeRing {
+    /// (hours since the Unix epoch, volume in that hour)
+    pub slots: Vec<(u64, Amount)>,
+}
+
+impl VolumeRing {
+    /// Add `amount` to the hour containing `timestamp`
+    pub fn add(&mut self, timestamp: Timestamp, amount: Amount) {
+        let hour = hour_index(timestamp);
+        if self.slots.is_empty() {
+            self.slots = vec![(0, Amount::ZERO); VOLUME_RING_SLOTS as usize];
+        }
+        let slot = &mut self.slots[(hour % VOLUME_RING_SLOTS) as usize];
+        if slot.0 != hour {
+            *slot = (hour, Amount::ZERO);
+        }
+        slot.1 = slot.1.saturating_add(amount);
+    }
+
+    /// Volume in the hour containing `now` and the 23 before it
+    pub fn last_24h(&self, now: Timestamp) -> Amount {
+        let hour = hour_index(now);
+        let first = hour.saturating_sub(23);
+        self.slots.iter()
+            .filter(|(slot_hour, _)| (first..=hour).contains(slot_hour))
+            .fold(Amount::ZERO, |sum, (_, amount)| sum.saturating_add(*amount))
+    }
+}
+
+/// Days an account's `TraderDays` covers: a 30-day window with some slack
+pub const TRADER_DAY_SLOTS: u64 = 35;
+
+/// One day of an account's trading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraderDay {
+    /// Whole UTC days since the Unix epoch
+    pub day: u64,
+    /// Collateral traded before fees
+    pub volume: Amount,
+    /// Realized PnL, in attos
+    pub realized_pnl: i128,
+    pub trade_count: u64,
+}
+
+/// An account's trading per day, held in slot `day % TRADER_DAY_SLOTS`;
+/// stale slots count as zero and are reset, as in `VolumeRing`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraderDays {
+    pub slots: Vec<TraderDay>,
+}
+
+impl TraderDays {
+    /// Add `volume` traded in `trades` trades and `realized_pnl` to the day
+    /// containing `timestamp`
+    pub fn add(&mut self, timestamp: Timestamp, volume: Amount, realized_pnl: i128, trades: u64) {
+        let day = day_index(timestamp);
+        if self.slots.is_empty() {
+            self.slots = vec![TraderDay::default(); TRADER_DAY_SLOTS as usize];
+        }
+        let slot = &mut self.slots[(day % TRADER_DAY_SLOTS) as usize];
+        if slot.day != day {
+            *slot = TraderDay { day, ..TraderDay::default() };
+        }
+        slot.volume = slot.volume.saturating_add(volume);
+        slot.realized_pnl = slot.realized_pnl.saturating_add(realized_pnl);
+        slot.trade_count += trades;
+    }
+
+    /// The day containing `now` and the `days - 1` before it summed, at most
+    /// `TRADER_DAY_SLOTS` days back
+    pub fn window(&self, now: Timestamp, days: u64) -> TraderDay {
+        let today = day_index(now);
+        let first = today.saturating_sub(days.clamp(1, TRADER_DAY_SLOTS) - 1);
+        self.slots.iter().filter(|slot| (first..=today).contains(&slot.day)).fold(
+            TraderDay { day: first, ..TraderDay::default() },
+            |sum, slot| TraderDay {
+                day: first,
+                volume: sum.volume.saturating_add(slot.volume),
+                realized_pnl: sum.realized_pnl.saturating_add(slot.realized_pnl),
+                trade_count: sum.trade_count + slot.trade_count,
+            },
+        )
+    }
+}
+
+/// Most checkpoints kept per market; the oldest are dropped first
+pub const MAX_PRICE_CHECKPOINTS: usize = 1_000;
+
+/// `Market::price_cumulative` as of `timestamp`, and the YES price from
+/// then until the next checkpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceCheckpoint {
+    pub timestamp: Timestamp,
+    pub cumulative: u128,
+    pub yes_price_bps: u64,
+}
+
+impl PriceCheckpoint {
+    /// The accumulator at `at`, no earlier than this checkpoint and no later
+    /// than the next one
+    pub fn cumulative_at(&self, at: Timestamp) -> u128 {
+        let elapsed = u128::from(at.delta_since(self.timestamp).as_micros());
+        self.cumulative.wrapping_add(u128::from(self.yes_price_bps).wrapping_mul(elapsed))
+    }
+}
+
+/// Fixed-point scale of `Market::fees_per_lp_share`
+pub const FEE_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Liquidity an account provides to a market and the fee accumulator value
+/// it was last settled at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LpPosition {
+    pub market_id: u64,
+    pub owner: AccountOwner,
+    pub shares: Amount,
+    pub fee_checkpoint: u128,
+}
+
+/// A creator's bond on one market
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorBond {
+    pub market_id: u64,
+    pub creator: AccountOwner,
+    pub amount: Amount,
+    pub status: BondStatus,
+    /// Of a forfeited bond, what the treasury kept and what was shared
+    /// among the traders; the two add up to `amount`
+    pub to_treasury: Amount,
+    pub to_traders: Amount,
+    /// When it was returned or forfeited
+    pub settled_at: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BondStatus {
+    /// Escrowed; returnable once the market resolves cleanly and its
+    /// outcome can no longer be overridden
+    Held,
+    /// The market was voided through no fault of its creator; returnable now
+    Released,
+    Returned,
+    /// The market was voided by the admin or for want of a resolution
+    Forfeited,
+}
+
+/// Collateral an LP has moved into and out of one market, for its PnL
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LpLedger {
+    pub deposited: Amount,
+    pub withdrawn: Amount,
+    /// LP fees paid out so far
+    pub fees_claimed: Amount,
+    /// Every deposit and withdrawal, oldest first
+    pub history: Vec<LpFlow>,
+    /// What the stake could redeem when the market settled, before fees
+    pub settled_value: Option<Amount>,
+}
+
+/// One deposit into or withdrawal from a market's liquidity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LpFlow {
+    pub amount: Amount,
+    pub deposit: bool,
+    pub timestamp: Timestamp,
+}
+
+impl LpLedger {
+    pub fn record(&mut self, flow: LpFlow) {
+        if flow.deposit {
+            self.deposited = self.deposited.saturating_add(flow.amount);
+        } else {
+            self.withdrawn = self.withdrawn.saturating_add(flow.amount);
+        }
+        self.history.push(flow);
+    }
+
+    /// Profit in attos of a stake now worth `value` that has `pending_fees`
+    /// to claim: value, withdrawals and fees earned less deposits
+    pub fn pnl(&self, value: Amount, pending_fees: Amount) -> i128 {
+        let gain = value.saturating_add(self.withdrawn).saturating_add(self.fees_claimed).saturating_add(pending_fees);
+        pnl(gain.into(), self.deposited.into())
+    }
+}
+
+/// A reward budget streamed linearly to a market's LPs from `start` over
+/// `duration_secs`, split pro rata to their LP shares through a
+/// rewards-per-share accumulator that is brought up to date lazily
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncentiveProgram {
+    pub market_id: u64,
+    pub funder: AccountOwner,
+    pub budget: Amount,
+    pub start: Timestamp,
+    pub duration_secs: u64,
+    /// Rewards streamed per LP share so far, scaled by `FEE_SCALE`
+    pub rewards_per_lp_share: u128,
+    /// Part of the budget streamed into the accumulator so far
+    pub streamed: Amount,
+    /// Paid out to LPs so far
+    pub claimed: Amount,
+    /// What went back to the funders when the market settled, which also
+    /// stopped the stream
+    pub returned: Option<Amount>,
+    /// When a later funding last added to the budget; the rest of the
+    /// budget streams from here to `end`. None until the first top-up.
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub topped_up_at: Option<Timestamp>,
+    /// Budget due by `topped_up_at`
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub streamed_at_top_up: Amount,
+}
+
+impl IncentiveProgram {
+    /// When the whole budget has been streamed
+    pub fn end(&self) -> Timestamp {
+        self.start.saturating_add(TimeDelta::from_secs(self.duration_secs))
+    }
+
+    /// Budget due by `now`: budget * elapsed / duration rounded down, and
+    /// all of it once the duration is over. After a top-up, what was due at
+    /// the top-up plus the rest spread the same way over what remained.
+    pub fn streamed_at(&self, now: Timestamp) -> Amount {
+        let from = self.topped_up_at.unwrap_or(self.start);
+        let elapsed = u128::from(now.delta_since(from).as_micros());
+        let duration = u128::from(self.end().delta_since(from).as_micros());
+        if elapsed >= duration {
+            return self.budget;
+        }
+        let rest = self.budget.saturating_sub(self.streamed_at_top_up);
+        let due = safe_mul_div(u128::from(rest), elapsed, duration).unwrap_or(0);
+        self.streamed_at_top_up.saturating_add(Amount::from_attos(due))
+    }
+
+    /// Add `amount` to the budget at `now` without changing what was due
+    /// before it, streaming the rest until the later of the current end
+    /// and `duration_secs` from now, in whole seconds from `start`
+    pub fn top_up(&mut self, amount: Amount, duration_secs: u64, now: Timestamp) {
+        let due = self.streamed_at(now);
+        let end = self.end().max(now.saturating_add(TimeDelta::from_secs(duration_secs)));
+        self.duration_secs = end.delta_since(self.start).as_duration().as_secs();
+        self.budget = self.budget.saturating_add(amount);
+        self.streamed_at_top_up = due;
+        self.topped_up_at = Some(now);
+    }
+
+    /// Stream what came due by `now` into the accumulator, spread over
+    /// `total_lp_shares`. Must run before any change to the market's LP
+    /// shares. The per-share increment rounds down, so the LPs are owed at
+    /// most what was streamed; the dust stays with the program. A stopped
+    /// program, or one without LP shares to pay, accrues nothing.
+    pub fn accrue(&mut self, total_lp_shares: Amount, now: Timestamp) {
+        if self.returned.is_some() || total_lp_shares == Amount::ZERO {
+            return;
+        }
+        let streamed = self.streamed_at(now);
+        let due = u128::from(streamed.saturating_sub(self.streamed));
+        if let Ok(increment) = safe_mul_div(due, FEE_SCALE, u128::from(total_lp_shares)) {
+            self.rewards_per_lp_share = self.rewards_per_lp_share.saturating_add(increment);
+            self.streamed = streamed;
+        }
+    }
+
+    /// Rewards owed on `shares` LP shares last paid at `checkpoint`, rounded
+    /// down; never more than the budget not yet paid out or returned
+    pub fn pending(&self, shares: Amount, checkpoint: u128) -> Amount {
+        let accrued = self.rewards_per_lp_share.saturating_sub(checkpoint);
+        let owed = safe_mul_div(u128::from(shares), accrued, FEE_SCALE).unwrap_or(0);
+        let left = self.budget.saturating_sub(self.claimed).saturating_sub(self.returned.unwrap_or(Amount::ZERO));
+        Amount::from_attos(owed).min(left)
+    }
+}
+
+/// An account that funded a market's incentives, with what it put in and
+/// the name and link the market shows for it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sponsor {
+    pub account: AccountOwner,
+    /// Total of its fundings of the market
+    pub amount: Amount,
+    /// Empty for funding without branding, or once the admin removed it
+    pub name: String,
+    pub url: String,
+}
+
+/// A market created on another chain, as announced to the registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteMarket {
+    pub market_id: u64,
+    pub origin_chain: ChainId,
+    pub question: String,
+    pub end_time: Timestamp,
+    pub categories: Vec<String>,
+    /// None for announcements from before market keys
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub market_key: Option<MarketKey>,
+}
+
+/// Stored in a versioned envelope; see `versioned_record`
+#[derive(Debug, Clone)]
 pub struct Position {
     pub market_id: u64,
     pub owner: AccountOwner,
     pub yes_shares: Amount,
     pub no_shares: Amount,
     pub claimed: bool,
+    /// Amount paid for the shares currently held, per side
+    pub yes_cost_basis: Amount,
+    pub no_cost_basis: Amount,
+    /// Attos received from sells and claims minus the cost basis they
+    /// closed out; negative for a loss. Added in version 1.
+    pub realized_pnl: i128,
+    /// Record layout this position was read with: 0 for positions stored
+    /// before the envelope, `POSITION_VERSION` once upgraded
+    pub version: u8,
+}
+
+versioned_record!(Position, POSITION_VERSION, market_id, [
+    owner,
+    yes_shares,
+    no_shares,
+    claimed,
+    yes_cost_basis,
+    no_cost_basis,
+], [
+    realized_pnl: 1,
+]);
+
+impl Position {
+    /// Bring a position read from storage up to `POSITION_VERSION`
+    pub fn upgrade(&mut self) {
+        self.version = POSITION_VERSION;
+    }
+
+    /// Add `other`'s shares, cost bases and realized PnL to this position,
+    /// as when a migrated position comes back to an account that traded
+    /// the market again meanwhile
+    pub fn merge(&mut self, other: &Position) {
+        self.yes_shares = self.yes_shares.saturating_add(other.yes_shares);
+        self.no_shares = self.no_shares.saturating_add(other.no_shares);
+        self.yes_cost_basis = self.yes_cost_basis.saturating_add(other.yes_cost_basis);
+        self.no_cost_basis = self.no_cost_basis.saturating_add(other.no_cost_basis);
+        self.realized_pnl = self.realized_pnl.saturating_add(other.realized_pnl);
+    }
+
+    /// Whether its cost basis is unknown: shares held with no basis on their
+    /// side, as positions opened before bases were tracked have. Every buy
+    /// since adds what it paid.
+    pub fn basis_unknown(&self) -> bool {
+        (self.yes_shares > Amount::ZERO && self.yes_cost_basis == Amount::ZERO)
+            || (self.no_shares > Amount::ZERO && self.no_cost_basis == Amount::ZERO)
+    }
+
+    /// Whether it holds no shares on either side
+    pub fn is_empty(&self) -> bool {
+        self.yes_shares == Amount::ZERO && self.no_shares == Amount::ZERO
+    }
+
+    /// Cost basis per share held on one side, in basis points of a token
+    /// rounded down, as trade responses give average prices; None with no
+    /// shares on that side
+    pub fn average_entry_price_bps(&self, is_yes: bool) -> Option<u64> {
+        let (shares, basis) = if is_yes {
+            (self.yes_shares, self.yes_cost_basis)
+        } else {
+            (self.no_shares, self.no_cost_basis)
+        };
+        if shares == Amount::ZERO {
+            return None;
+        }
+        let price = safe_mul_div(basis.into(), 10_000, shares.into()).unwrap_or(u128::MAX);
+        Some(u64::try_from(price).unwrap_or(u64::MAX))
+    }
+
+    /// `value`, the position's current worth, minus the cost basis still
+    /// held, in attos; zero once claimed, when the claim realized it
+    pub fn unrealized_pnl(&self, value: Amount) -> i128 {
+        if self.claimed {
+            return 0;
+        }
+        pnl(value.into(), self.yes_cost_basis.saturating_add(self.no_cost_basis).into())
+    }
+}
+
+/// A position in flight to another chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionTransfer {
+    pub transfer_id: u64,
+    pub market_chain: ChainId,
+    pub destination: ChainId,
+    pub position: Position,
+    pub sent_at: Timestamp,
+    pub expires_at: Timestamp,
+}
+
+/// An account's results summed over every market it traded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraderStats {
+    /// Sum of `Position::realized_pnl`, in attos
+    pub realized_pnl: i128,
+    /// Collateral traded before fees, as market volume counts it
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub volume: Amount,
+    /// Trades, one per operation and market however many fills it took
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub trade_count: u64,
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub markets_traded: u64,
+    /// Resolved markets in which the account held winning shares at
+    /// resolution, claimed yet or not
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub markets_won: u64,
+    /// Resolved markets in which it held only losing shares; voided
+    /// markets count as neither
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub markets_lost: u64,
+    /// Positions holding shares in markets yet to resolve
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub open_positions: u64,
+}
+
+impl TraderStats {
+    /// Markets won out of those won or lost, in basis points rounded down;
+    /// None before any has resolved
+    pub fn win_rate_bps(&self) -> Option<u64> {
+        let decided = self.markets_won.saturating_add(self.markets_lost);
+        (decided > 0).then(|| (u128::from(self.markets_won) * 10_000 / u128::from(decided)) as u64)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -230,10 +2454,14 @@ pub struct LimitOrder {
     pub market_id: u64,
     pub is_yes: bool,
     pub side: OrderSide,
-    pub price: Amount,
-    pub original_amount: Amount,
-    pub filled_amount: Amount,
-    pub duration: OrderDuration,
+    /// Price per share in basis points of one token
+    pub price_bps: u64,
+    pub shares: Amount,
+    pub filled_shares: Amount,
+    /// Unfilled tokens locked by a buy, or unfilled shares locked by a sell
+    pub escrow: Amount,
+    /// Cost basis taken off the seller's position with the locked shares
+    pub escrowed_cost_basis: Amount,
     pub created_at: Timestamp,
     pub status: OrderStatus,
 }
@@ -247,6 +2475,64 @@ pub enum OrderStatus {
     Expired,
 }
 
+/// A sale through the AMM waiting for a side's price to reach a level, or a
+/// purchase waiting for the YES price to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerOrder {
+    pub id: u64,
+    pub owner: AccountOwner,
+    pub market_id: u64,
+    pub is_yes: bool,
+    pub shares: Amount,
+    pub trigger_price_bps: u64,
+    pub direction: TriggerDirection,
+    pub min_proceeds: Amount,
+    /// Escrowed at placement for the keeper, refunded if cancelled
+    pub keeper_fee: Amount,
+    pub created_at: Timestamp,
+    pub status: TriggerStatus,
+    /// What the sale paid the owner, once executed
+    pub proceeds: Amount,
+    /// When it was executed or cancelled
+    pub closed_at: Option<Timestamp>,
+    /// Set on buy orders only: the highest average price a share may cost,
+    /// fee included, in basis points
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub max_price_bps: Option<u64>,
+    /// Tokens a buy order holds for its purchase until executed or cancelled
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub escrow: Amount,
+    /// What the purchase cost the owner, fee included, once executed
+    #[serde(default, deserialize_with = "default_if_missing")]
+    pub cost: Amount,
+}
+
+impl TriggerOrder {
+    pub fn side(&self) -> OrderSide {
+        if self.max_price_bps.is_some() {
+            OrderSide::Buy
+        } else {
+            OrderSide::Sell
+        }
+    }
+
+    /// Whether `market`'s prices meet the order's condition: a sale watches
+    /// the side it sells, a purchase the YES price
+    pub fn is_triggered(&self, market: &Market) -> bool {
+        let price_bps = if self.side() == OrderSide::Buy || self.is_yes { market.yes_price_bps() } else { market.no_price_bps() };
+        self.direction.is_met(price_bps, self.trigger_price_bps)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TriggerStatus {
+    Pending,
+    Executed,
+    /// Withdrawn by the owner, by resolution of its market, or by a keeper
+    /// finding too few shares to sell
+    Cancelled,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Combo {
     pub id: u64,
@@ -295,6 +2581,41 @@ pub struct TradingAgent {
     pub created_at: Timestamp,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketSeries {
+    pub id: u64,
+    pub creator: AccountOwner,
+    pub template: SeriesTemplate,
+    pub interval_secs: u64,
+    pub count: u64,
+    pub created_at: Timestamp,
+    /// Market ids opened so far, in order
+    pub spawned: Vec<u64>,
+    /// Liquidity reserved for instances not yet opened
+    pub escrow: Amount,
+}
+
+/// One resolver's vote on a market's outcome
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolutionVote {
+    pub resolver: AccountOwner,
+    pub outcome: bool,
+    pub timestamp: Timestamp,
+}
+
+/// Markets created together for mutually exclusive outcomes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketGroup {
+    pub id: u64,
+    pub creator: AccountOwner,
+    pub title: String,
+    /// One market per outcome, in the order given at creation
+    pub market_ids: Vec<u64>,
+    pub created_at: Timestamp,
+    /// Member resolved YES, once the group is resolved
+    pub winner: Option<u64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentFollower {
     pub agent_id: u64,
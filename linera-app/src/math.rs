@@ -0,0 +1,284 @@
+// Copyright (c) Chronos Markets
+// AMM pricing and payout math on raw attos
+
+// Plain u128 arithmetic with no SDK types, shared by the contract and the
+// service so both price trades the same way. Each market is a
+// constant-product pair of pools: buying one side takes shares out of its
+// pool and pays tokens into the other, selling does the reverse. Every
+// function rounds in the pools' favour, so their product never shrinks.
+
+/// Fixed-point scale of `price_of`: one whole token
+pub const PRICE_SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// Compute (a * b) / c exactly, widening the product to 256 bits when it
+/// does not fit in u128. Errors only if the quotient itself overflows.
+pub fn safe_mul_div(a: u128, b: u128, c: u128) -> Result<u128, String> {
+    if c == 0 {
+        return Err("Division by zero in AMM calculation".to_string());
+    }
+
+    // Fast path: if a * b fits in u128, do it directly
+    if let Some(product) = a.checked_mul(b) {
+        return Ok(product / c);
+    }
+
+    let (high, low) = widening_mul(a, b);
+    if high >= c {
+        return Err(format!("AMM overflow: {} * {} / {} exceeds u128", a, b, c));
+    }
+
+    // Long division of the 256-bit product by c, one bit at a time.
+    // The remainder stays below c, so only the shifted-out bit can overflow.
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+
+    Ok(quotient)
+}
+
+/// (a * b) / c rounded up.
+fn mul_div_ceil(a: u128, b: u128, c: u128) -> Result<u128, String> {
+    let quotient = safe_mul_div(a, b, c)?;
+    if widening_mul(quotient, c) == widening_mul(a, b) {
+        return Ok(quotient);
+    }
+    quotient
+        .checked_add(1)
+        .ok_or_else(|| format!("AMM overflow: {} * {} / {} exceeds u128", a, b, c))
+}
+
+/// The constant-product invariant `yes_pool * no_pool`, widened to 256 bits
+/// as (high, low) halves. The pair orders as the product does, so two
+/// readings compare without overflow.
+pub fn pool_product(yes_pool: u128, no_pool: u128) -> (u128, u128) {
+    widening_mul(yes_pool, no_pool)
+}
+
+/// Full 256-bit product of two u128 values as (high, low) halves.
+fn widening_mul(a: u128, b: u128) -> (u128, u128) {
+    let mask = u128::from(u64::MAX);
+    let (a_high, a_low) = (a >> 64, a & mask);
+    let (b_high, b_low) = (b >> 64, b & mask);
+
+    let low_low = a_low * b_low;
+    let low_high = a_low * b_high;
+    let high_low = a_high * b_low;
+    let high_high = a_high * b_high;
+
+    let middle = (low_low >> 64) + (low_high & mask) + (high_low & mask);
+    let low = (low_low & mask) | (middle << 64);
+    let high = high_high + (low_high >> 64) + (high_low >> 64) + (middle >> 64);
+    (high, low)
+}
+
+/// Tokens, before fees, that take `shares` out of `pool_out` against
+/// `pool_in`: pool_in * shares / (pool_out - shares), rounded up. The pool
+/// must hold more than `shares`.
+pub fn cost_to_buy(pool_in: u128, pool_out: u128, shares: u128) -> Result<u128, String> {
+    if shares >= pool_out {
+        return Err(format!("Not enough liquidity: {} shares from a pool of {}", shares, pool_out));
+    }
+    mul_div_ceil(pool_in, shares, pool_out - shares)
+}
+
+/// How far the average price of buying `shares` from `pool_out` lies above
+/// the marginal price before the trade, in basis points, rounded up and
+/// saturating at u64::MAX. With cost pool_in * shares / (pool_out - shares)
+/// against a marginal price of pool_in / pool_out this is exactly
+/// shares / (pool_out - shares): taken from the pools rather than from the
+/// rounded cost, so tiny trades come out at 0 or 1 bps instead of the
+/// relative size of one atto of rounding. The pool must hold more than
+/// `shares`.
+pub fn price_impact_bps(pool_out: u128, shares: u128) -> Result<u64, String> {
+    if shares >= pool_out {
+        return Err(format!("Not enough liquidity: {} shares from a pool of {}", shares, pool_out));
+    }
+    // Only a quotient beyond u128 errors here
+    Ok(mul_div_ceil(shares, 10_000, pool_out - shares).map_or(u64::MAX, |bps| u64::try_from(bps).unwrap_or(u64::MAX)))
+}
+
+/// How many basis points buying `shares` out of `pool_out` for `cost` paid
+/// into `pool_in` raises the bought side's implied probability,
+/// `price_of(pool_out, pool_in)`. Taken from the prices at full
+/// `PRICE_SCALE` precision and rounded up, so any move they register
+/// counts as at least 1 bps. At most 10000.
+pub fn probability_move_bps(pool_in: u128, pool_out: u128, shares: u128, cost: u128) -> Result<u64, String> {
+    if shares >= pool_out {
+        return Err(format!("Not enough liquidity: {} shares from a pool of {}", shares, pool_out));
+    }
+    let new_pool_in = pool_in
+        .checked_add(cost)
+        .ok_or_else(|| format!("AMM overflow: pool {} plus cost {}", pool_in, cost))?;
+    let before = price_of(pool_out, pool_in);
+    let after = price_of(pool_out - shares, new_pool_in);
+    // Prices never exceed PRICE_SCALE, so this fits in u64
+    let bps = mul_div_ceil(after.saturating_sub(before), 10_000, PRICE_SCALE)?;
+    Ok(bps as u64)
+}
+
+/// Shares `cost` tokens paid into `pool_in` take out of `pool_out`:
+/// pool_out * cost / (pool_in + cost), rounded down.
+pub fn shares_for_cost(pool_in: u128, pool_out: u128, cost: u128) -> Result<u128, String> {
+    let new_pool_in = pool_in
+        .checked_add(cost)
+        .ok_or_else(|| format!("AMM overflow: pool {} plus cost {}", pool_in, cost))?;
+    if new_pool_in == 0 {
+        return Ok(0);
+    }
+    safe_mul_div(pool_out, cost, new_pool_in)
+}
+
+/// Tokens, before fees, that returning `shares` to `pool_in` takes out of
+/// `pool_out`: pool_out * shares / (pool_in + shares), rounded down.
+pub fn sell_proceeds(pool_in: u128, pool_out: u128, shares: u128) -> Result<u128, String> {
+    let new_pool_in = pool_in
+        .checked_add(shares)
+        .ok_or_else(|| format!("AMM overflow: pool {} plus shares {}", pool_in, shares))?;
+    if new_pool_in == 0 {
+        return Ok(0);
+    }
+    safe_mul_div(pool_out, shares, new_pool_in)
+}
+
+/// How far the average price of selling `shares` into `pool_in` lies below
+/// the marginal price before the trade, in basis points, rounded up. With
+/// proceeds pool_out * shares / (pool_in + shares) against a marginal price
+/// of pool_out / pool_in this is shares / (pool_in + shares), at most 10000.
+pub fn sell_price_impact_bps(pool_in: u128, shares: u128) -> Result<u64, String> {
+    let new_pool_in = pool_in
+        .checked_add(shares)
+        .ok_or_else(|| format!("AMM overflow: pool {} plus shares {}", pool_in, shares))?;
+    if new_pool_in == 0 {
+        return Ok(0);
+    }
+    // shares <= new_pool_in, so the quotient is at most 10000
+    Ok(mul_div_ceil(shares, 10_000, new_pool_in)? as u64)
+}
+
+/// Most shares a sale into `pool_in` can return before its proceeds out of
+/// `pool_out`, as `sell_proceeds` rounds them down, exceed `available`: the
+/// largest s with s * (pool_out - available - 1) < (available + 1) *
+/// pool_in. None when any sale stays within `available`, since proceeds
+/// never reach `pool_out`.
+pub fn max_sell_shares(pool_in: u128, pool_out: u128, available: u128) -> Result<Option<u128>, String> {
+    if pool_out <= available.saturating_add(1) {
+        return Ok(None);
+    }
+    let bound = mul_div_ceil(available + 1, pool_in, pool_out - available - 1)?;
+    Ok(Some(bound.saturating_sub(1)))
+}
+
+/// The pool a side holds when its price is `price_bps`, on the
+/// constant-product curve through `pool` and `other_pool`:
+/// sqrt(pool * other_pool * (10000 - price_bps) / price_bps), rounded up, so
+/// buying down to it never carries the price past `price_bps`. The price
+/// must lie strictly between 0 and 10000.
+pub fn pool_at_price(pool: u128, other_pool: u128, price_bps: u64) -> Result<u128, String> {
+    if price_bps == 0 || price_bps >= 10_000 {
+        return Err(format!("Price {} bps is not strictly between 0 and 10000", price_bps));
+    }
+    let ratio = mul_div_ceil(other_pool, u128::from(10_000 - price_bps), u128::from(price_bps))?;
+    let square = widening_mul(pool, ratio);
+    let root = isqrt(square);
+    Ok(if widening_mul(root, root) == square { root } else { root + 1 })
+}
+
+/// Square root of a 256-bit (high, low) value, rounded down
+fn isqrt(value: (u128, u128)) -> u128 {
+    // Any root of a 256-bit value fits in 128 bits: set each bit from the
+    // top down while the square stays within the value
+    let mut root = 0u128;
+    for bit in (0..128).rev() {
+        let candidate = root | (1 << bit);
+        if widening_mul(candidate, candidate) <= value {
+            root = candidate;
+        }
+    }
+    root
+}
+
+/// Pro-rata share of `pot` owed to `shares` out of `total_shares`, rounded
+/// down so the payouts never add up to more than `pot`.
+pub fn payout_for_shares(shares: u128, total_shares: u128, pot: u128) -> Result<u128, String> {
+    if shares > total_shares {
+        return Err(format!("{} shares exceed the {} outstanding", shares, total_shares));
+    }
+    safe_mul_div(shares, pot, total_shares)
+}
+
+/// Split a taker's `fee` into the maker's rebate, `rebate_bps` of it rounded
+/// down, and the remainder, which together always make up `fee` exactly
+pub fn split_fee(fee: u128, rebate_bps: u64) -> Result<(u128, u128), String> {
+    if rebate_bps > 10_000 {
+        return Err(format!("Rebate of {} bps exceeds the whole fee", rebate_bps));
+    }
+    let rebate = safe_mul_div(fee, u128::from(rebate_bps), 10_000)?;
+    Ok((rebate, fee - rebate))
+}
+
+/// Fee in basis points `remaining_micros` before a market ends: `base_bps`
+/// until the final `window_micros`, then rising linearly to `max_bps` at
+/// the end, rounded down. Never below `base_bps`.
+pub fn ramped_fee_bps(base_bps: u64, max_bps: u64, window_micros: u64, remaining_micros: u64) -> u64 {
+    if remaining_micros >= window_micros || max_bps <= base_bps {
+        return base_bps;
+    }
+    let elapsed = u128::from(window_micros - remaining_micros);
+    let increase = u128::from(max_bps - base_bps) * elapsed / u128::from(window_micros);
+    base_bps + increase as u64
+}
+
+/// `gain` minus `cost` as a signed amount, saturating at the i128 range
+pub fn pnl(gain: u128, cost: u128) -> i128 {
+    if gain >= cost {
+        i128::try_from(gain - cost).unwrap_or(i128::MAX)
+    } else {
+        i128::try_from(cost - gain).map_or(i128::MIN, |loss| -loss)
+    }
+}
+
+/// Price of the side whose pool is `pool`, scaled by `PRICE_SCALE`: the
+/// other pool's share of both, rounded down. Half a token for empty pools.
+pub fn price_of(pool: u128, other_pool: u128) -> u128 {
+    // Halving both pools keeps the ratio when the sum would overflow
+    let (pool, other_pool) = match pool.checked_add(other_pool) {
+        Some(_) => (pool, other_pool),
+        None => (pool / 2, other_pool / 2),
+    };
+    let total = pool + other_pool;
+    if total == 0 {
+        return PRICE_SCALE / 2;
+    }
+    // other_pool <= total, so the quotient is at most PRICE_SCALE
+    safe_mul_div(other_pool, PRICE_SCALE, total).unwrap_or(PRICE_SCALE / 2)
+}
+
+/// Decimal places of an atto amount: shares and tokens alike
+pub const ATTO_DECIMALS: u8 = 18;
+
+/// Attos in one share unit when shares carry `decimals` decimal places.
+/// None past `ATTO_DECIMALS`, which would make a unit smaller than an atto.
+pub fn share_unit_attos(decimals: u8) -> Option<u128> {
+    ATTO_DECIMALS.checked_sub(decimals).map(|exponent| 10u128.pow(u32::from(exponent)))
+}
+
+/// `units` share units at `decimals` decimal places, in attos. None on
+/// overflow or unsupported decimals.
+pub fn shares_from_units(units: u128, decimals: u8) -> Option<u128> {
+    share_unit_attos(decimals)?.checked_mul(units)
+}
+
+/// Share units in `attos` at `decimals` decimal places. None unless `attos`
+/// is a whole number of units, so no precision is ever dropped.
+pub fn shares_to_units(attos: u128, decimals: u8) -> Option<u128> {
+    let unit = share_unit_attos(decimals)?;
+    (attos % unit == 0).then_some(attos / unit)
+}
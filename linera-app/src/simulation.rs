@@ -0,0 +1,453 @@
+// Copyright (c) Chronos Markets
+// Randomized solvency simulation of market accounting on the shared math
+
+// A model of what the contract does to a market's pools, escrow and
+// positions on creation, AMM buys and sells, resolution and claims, with
+// the same `math` and the same order of updates. `run` replays a sequence
+// of steps and checks after each one that the escrow covers what claims
+// would pay, that no pool product shrank and that the balance and volume
+// counters agree. Resting orders, fee tiers, limits and time are left out.
+// Nothing here touches the runtime, so it builds for native targets.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::math::{cost_to_buy, payout_for_shares, pool_product, safe_mul_div, sell_proceeds};
+
+/// Traders a generated scenario trades as; steps name them by index
+pub const TRADERS: usize = 4;
+
+/// How many shares a buy takes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Size {
+    Attos(u128),
+    /// Basis points of the pool the shares come out of
+    PoolBps(u64),
+}
+
+/// One step of a scenario. Markets are named by creation order. A step
+/// naming a market that does not exist, or one the contract would refuse,
+/// changes nothing, so any subsequence of a scenario still replays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    Create { liquidity: u128 },
+    Buy { market: usize, trader: usize, is_yes: bool, size: Size },
+    /// Sell `held_bps` basis points of the trader's shares on one side
+    Sell { market: usize, trader: usize, is_yes: bool, held_bps: u64 },
+    Resolve { market: usize, outcome: bool },
+    /// Every trader with a position in the market claims it
+    ClaimAll { market: usize },
+}
+
+/// A market's accounting, in attos
+#[derive(Clone, Debug, Default)]
+pub struct SimMarket {
+    pub yes_pool: u128,
+    pub no_pool: u128,
+    pub total_yes_shares: u128,
+    pub total_no_shares: u128,
+    /// Shares of each side the creator's liquidity seeded
+    pub seed_shares: u128,
+    pub escrow: u128,
+    pub volume: u128,
+    pub fees_collected: u128,
+    pub outcome: Option<bool>,
+    pub refund_mode: bool,
+}
+
+impl SimMarket {
+    /// Whether resolving to `outcome` refunds every trader, as when nobody
+    /// but the seed holds the winning side
+    fn refunds_on(&self, outcome: bool) -> bool {
+        let winning = if outcome { self.total_yes_shares } else { self.total_no_shares };
+        winning.saturating_sub(self.seed_shares) == 0
+    }
+}
+
+/// A trader's holding in one market, in attos
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimPosition {
+    pub yes_shares: u128,
+    pub no_shares: u128,
+    pub yes_cost_basis: u128,
+    pub no_cost_basis: u128,
+    pub claimed: bool,
+}
+
+/// The state a scenario runs against
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    /// Trading fee on every trade
+    pub fee_bps: u64,
+    pub markets: Vec<SimMarket>,
+    /// By (market, trader)
+    pub positions: BTreeMap<(usize, usize), SimPosition>,
+    /// Volume of buys, as the contract's `total_volume` counts it
+    pub total_volume: u128,
+    pub contract_balance: u128,
+    /// Cost basis refunds left unpaid because the escrow ran short. Refund
+    /// mode pays bases only as far as the escrow goes, so this is reported
+    /// rather than checked.
+    pub refund_shortfall: u128,
+}
+
+/// The first step of a scenario that broke an invariant
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// Index of the step in the scenario
+    pub step: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "step {}: {}", self.step, self.reason)
+    }
+}
+
+impl Model {
+    pub fn new(fee_bps: u64) -> Self {
+        Model { fee_bps, ..Model::default() }
+    }
+
+    /// Apply `step` as the contract would, or return why the contract
+    /// would refuse it
+    pub fn apply(&mut self, step: Step) -> Result<(), String> {
+        match step {
+            Step::Create { liquidity } => {
+                let half = liquidity / 2;
+                self.markets.push(SimMarket {
+                    yes_pool: half,
+                    no_pool: half,
+                    total_yes_shares: half,
+                    total_no_shares: half,
+                    seed_shares: half,
+                    escrow: liquidity,
+                    ..SimMarket::default()
+                });
+                self.contract_balance = self.contract_balance.saturating_add(liquidity);
+                Ok(())
+            }
+            Step::Buy { market: id, trader, is_yes, size } => {
+                let market = self.markets.get_mut(id).ok_or_else(|| format!("Market {} not found", id))?;
+                if market.outcome.is_some() {
+                    return Err(format!("Market {} is already resolved", id));
+                }
+                let (pool_in, pool_out) = if is_yes {
+                    (market.no_pool, market.yes_pool)
+                } else {
+                    (market.yes_pool, market.no_pool)
+                };
+                let shares = match size {
+                    Size::Attos(attos) => attos,
+                    Size::PoolBps(bps) => safe_mul_div(pool_out, u128::from(bps), 10_000)?,
+                };
+                if shares == 0 {
+                    return Err("Shares amount must be greater than zero".to_string());
+                }
+                let cost = cost_to_buy(pool_in, pool_out, shares)?;
+                let fee = safe_mul_div(cost, u128::from(self.fee_bps), 10_000)?;
+                let paid = cost.saturating_add(fee);
+                if paid == 0 {
+                    return Err(format!("Buying {} shares would cost nothing", shares));
+                }
+                if is_yes {
+                    market.no_pool = market.no_pool.saturating_add(cost);
+                    market.yes_pool = market.yes_pool.saturating_sub(shares);
+                    market.total_yes_shares = market.total_yes_shares.saturating_add(shares);
+                } else {
+                    market.yes_pool = market.yes_pool.saturating_add(cost);
+                    market.no_pool = market.no_pool.saturating_sub(shares);
+                    market.total_no_shares = market.total_no_shares.saturating_add(shares);
+                }
+                market.volume = market.volume.saturating_add(cost);
+                market.escrow = market.escrow.saturating_add(cost);
+                market.fees_collected = market.fees_collected.saturating_add(fee);
+                self.total_volume = self.total_volume.saturating_add(cost);
+                self.contract_balance = self.contract_balance.saturating_add(paid);
+
+                let position = self.positions.entry((id, trader)).or_default();
+                if is_yes {
+                    position.yes_shares = position.yes_shares.saturating_add(shares);
+                    position.yes_cost_basis = position.yes_cost_basis.saturating_add(paid);
+                } else {
+                    position.no_shares = position.no_shares.saturating_add(shares);
+                    position.no_cost_basis = position.no_cost_basis.saturating_add(paid);
+                }
+                Ok(())
+            }
+            Step::Sell { market: id, trader, is_yes, held_bps } => {
+                let market = self.markets.get_mut(id).ok_or_else(|| format!("Market {} not found", id))?;
+                if market.outcome.is_some() {
+                    return Err(format!("Market {} is already resolved", id));
+                }
+                let position = self.positions.get_mut(&(id, trader))
+                    .ok_or_else(|| "No position found for this market".to_string())?;
+                let (held, basis) = if is_yes {
+                    (&mut position.yes_shares, &mut position.yes_cost_basis)
+                } else {
+                    (&mut position.no_shares, &mut position.no_cost_basis)
+                };
+                let shares = safe_mul_div(*held, u128::from(held_bps.min(10_000)), 10_000)?;
+                if shares == 0 {
+                    return Err("Shares amount must be greater than zero".to_string());
+                }
+                let (pool_in, pool_out) = if is_yes {
+                    (market.yes_pool, market.no_pool)
+                } else {
+                    (market.no_pool, market.yes_pool)
+                };
+                let gross = sell_proceeds(pool_in, pool_out, shares)?;
+                let fee = safe_mul_div(gross, u128::from(self.fee_bps), 10_000)?;
+                if gross > market.escrow {
+                    return Err(format!("Proceeds {} exceed market {} escrow {}", gross, id, market.escrow));
+                }
+                if is_yes {
+                    market.yes_pool = market.yes_pool.saturating_add(shares);
+                    market.no_pool = market.no_pool.saturating_sub(gross);
+                    market.total_yes_shares = market.total_yes_shares.saturating_sub(shares);
+                } else {
+                    market.no_pool = market.no_pool.saturating_add(shares);
+                    market.yes_pool = market.yes_pool.saturating_sub(gross);
+                    market.total_no_shares = market.total_no_shares.saturating_sub(shares);
+                }
+                market.volume = market.volume.saturating_add(gross);
+                market.escrow = market.escrow.saturating_sub(gross);
+                market.fees_collected = market.fees_collected.saturating_add(fee);
+                self.contract_balance = self.contract_balance.saturating_sub(gross - fee);
+
+                // Basis is relieved in proportion to the shares sold
+                let relief = safe_mul_div(*basis, shares, *held)?;
+                *held -= shares;
+                *basis = basis.saturating_sub(relief);
+                Ok(())
+            }
+            Step::Resolve { market: id, outcome } => {
+                let market = self.markets.get_mut(id).ok_or_else(|| format!("Market {} not found", id))?;
+                if market.outcome.is_some() {
+                    return Err(format!("Market {} is already resolved", id));
+                }
+                market.outcome = Some(outcome);
+                market.refund_mode = market.refunds_on(outcome);
+                Ok(())
+            }
+            Step::ClaimAll { market: id } => {
+                let market = self.markets.get_mut(id).ok_or_else(|| format!("Market {} not found", id))?;
+                let Some(outcome) = market.outcome else {
+                    return Err(format!("Market {} is not yet resolved", id));
+                };
+                for (_, position) in self.positions.range_mut((id, 0)..(id + 1, 0)) {
+                    if position.claimed {
+                        continue;
+                    }
+                    let basis = position.yes_cost_basis.saturating_add(position.no_cost_basis);
+                    let paid = if market.refund_mode {
+                        if basis == 0 {
+                            continue;
+                        }
+                        let refund = basis.min(market.escrow);
+                        self.refund_shortfall = self.refund_shortfall.saturating_add(basis - refund);
+                        refund
+                    } else {
+                        let (winning, total_winning) = if outcome {
+                            (position.yes_shares, market.total_yes_shares)
+                        } else {
+                            (position.no_shares, market.total_no_shares)
+                        };
+                        if winning == 0 {
+                            if position.yes_shares == 0 && position.no_shares == 0 {
+                                continue;
+                            }
+                            // Only losing shares: the claim settles at nothing
+                            position.claimed = true;
+                            continue;
+                        }
+                        if market.escrow == 0 {
+                            continue;
+                        }
+                        let pot = market.yes_pool.saturating_add(market.no_pool);
+                        payout_for_shares(winning, total_winning, pot)?.min(market.escrow)
+                    };
+                    market.escrow -= paid;
+                    self.contract_balance = self.contract_balance.saturating_sub(paid);
+                    position.claimed = true;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// What unclaimed positions in market `id` would be paid if it
+    /// resolved to `outcome`. Refunds are left out: they are capped by
+    /// the escrow by design.
+    pub fn owed(&self, id: usize, outcome: bool) -> Result<u128, String> {
+        let market = &self.markets[id];
+        if market.refunds_on(outcome) {
+            return Ok(0);
+        }
+        let total_winning = if outcome { market.total_yes_shares } else { market.total_no_shares };
+        let pot = market.yes_pool.saturating_add(market.no_pool);
+        let mut owed = 0u128;
+        for (_, position) in self.positions.range((id, 0)..(id + 1, 0)) {
+            if position.claimed {
+                continue;
+            }
+            let winning = if outcome { position.yes_shares } else { position.no_shares };
+            owed = owed.saturating_add(payout_for_shares(winning, total_winning, pot)?);
+        }
+        Ok(owed)
+    }
+
+    /// Check the invariants against the state `before` the last step
+    pub fn check(&self, before: &Model) -> Result<(), String> {
+        for (id, market) in self.markets.iter().enumerate() {
+            let outcomes = match market.outcome {
+                Some(outcome) => vec![outcome],
+                None => vec![true, false],
+            };
+            for outcome in outcomes {
+                let owed = self.owed(id, outcome)?;
+                if owed > market.escrow {
+                    return Err(format!(
+                        "market {} owes {} on outcome {} but holds {} in escrow",
+                        id, owed, outcome, market.escrow
+                    ));
+                }
+            }
+            if let Some(earlier) = before.markets.get(id) {
+                if pool_product(market.yes_pool, market.no_pool) < pool_product(earlier.yes_pool, earlier.no_pool) {
+                    return Err(format!(
+                        "market {}'s pool product shrank: pools {}/{} were {}/{}",
+                        id, market.yes_pool, market.no_pool, earlier.yes_pool, earlier.no_pool
+                    ));
+                }
+            }
+        }
+
+        // Everything the contract took in is still in escrow or with the LPs
+        let held = self.markets.iter().fold(0u128, |sum, m| sum.saturating_add(m.escrow).saturating_add(m.fees_collected));
+        if self.contract_balance != held {
+            return Err(format!("contract balance {} but escrow and fees add up to {}", self.contract_balance, held));
+        }
+        let volume = self.markets.iter().fold(0u128, |sum, m| sum.saturating_add(m.volume));
+        if self.total_volume < before.total_volume || self.total_volume > volume {
+            return Err(format!(
+                "total volume went from {} to {} against {} traded in markets",
+                before.total_volume, self.total_volume, volume
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Replay `steps` from an empty state, checking the invariants after each
+/// one. Refused steps are skipped; a panic counts as a failure.
+pub fn run(steps: &[Step], fee_bps: u64) -> Result<Model, Failure> {
+    let mut model = Model::new(fee_bps);
+    for (index, step) in steps.iter().enumerate() {
+        let before = model.clone();
+        let checked = panic::catch_unwind(AssertUnwindSafe(|| {
+            // A refusal leaves the state unchanged, as the contract's does
+            if model.apply(*step).is_err() {
+                model = before.clone();
+            }
+            model.check(&before)
+        }));
+        let reason = match checked {
+            Ok(Ok(())) => continue,
+            Ok(Err(reason)) => reason,
+            Err(_) => "panicked".to_string(),
+        };
+        return Err(Failure { step: index, reason });
+    }
+    Ok(model)
+}
+
+/// Drop steps from `steps` one at a time while what is left still
+/// `fails`, until no single step can go
+pub fn shrink(mut steps: Vec<Step>, fails: impl Fn(&[Step]) -> bool) -> Vec<Step> {
+    loop {
+        let mut shrunk = false;
+        for index in (0..steps.len()).rev() {
+            let mut candidate = steps.clone();
+            candidate.remove(index);
+            if fails(&candidate) {
+                steps = candidate;
+                shrunk = true;
+            }
+        }
+        if !shrunk {
+            return steps;
+        }
+    }
+}
+
+/// A small deterministic generator (SplitMix64), so a seed always gives
+/// the same scenario
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in 0..bound; `bound` must be nonzero
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Between 1 and 999 times a random power of ten up to 10^max_exponent,
+    /// so sizes range from dust to whole pools
+    fn magnitude(&mut self, max_exponent: u32) -> u128 {
+        let exponent = self.below(u64::from(max_exponent) + 1) as u32;
+        u128::from(self.below(999) + 1) * 10u128.pow(exponent)
+    }
+}
+
+/// A random scenario of `len` steps from `seed`: markets of random
+/// liquidity, buys of every size, partial and full sells, resolutions and
+/// claim rounds, mostly on markets that exist
+pub fn generate(seed: u64, len: usize) -> Vec<Step> {
+    let mut rng = Rng::new(seed);
+    let mut markets = 0usize;
+    let mut steps = Vec::with_capacity(len);
+    while steps.len() < len {
+        let roll = rng.below(100);
+        if markets == 0 || roll < 8 {
+            steps.push(Step::Create { liquidity: rng.magnitude(24) });
+            markets += 1;
+            continue;
+        }
+        let market = rng.below(markets as u64) as usize;
+        let trader = rng.below(TRADERS as u64) as usize;
+        let is_yes = rng.below(2) == 0;
+        steps.push(match roll {
+            8..=54 => {
+                let size = if rng.below(2) == 0 {
+                    Size::Attos(rng.magnitude(24))
+                } else {
+                    Size::PoolBps(rng.below(9_999) + 1)
+                };
+                Step::Buy { market, trader, is_yes, size }
+            }
+            55..=79 => {
+                let held_bps = if rng.below(4) == 0 { 10_000 } else { rng.below(10_000) + 1 };
+                Step::Sell { market, trader, is_yes, held_bps }
+            }
+            80..=87 => Step::Resolve { market, outcome: is_yes },
+            _ => Step::ClaimAll { market },
+        });
+    }
+    steps
+}
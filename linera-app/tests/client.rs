@@ -0,0 +1,137 @@
+// Copyright (c) Chronos Markets
+// Native tests of the client builders, response parser and local quotes
+
+use chronos_market::{
+    client::{decode_response, encode_operation, ClientError, MarketSnapshot, OperationBuilder, OperationResponse, SettlementReceipt},
+    market_key, Operation,
+};
+use linera_sdk::{
+    bcs,
+    linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+};
+
+fn amount(value: &str) -> Amount {
+    value.parse().unwrap()
+}
+
+fn snapshot() -> MarketSnapshot {
+    MarketSnapshot {
+        id: 1,
+        question: "Straddle?".to_string(),
+        yes_pool: Amount::from_tokens(50),
+        no_pool: Amount::from_tokens(50),
+        volume: Amount::ZERO,
+        end_time_micros: 3_600_000_000,
+        resolved: false,
+        outcome: None,
+        is_tradeable: true,
+        fee_bps: 0,
+        yes_price_bps: 5_000,
+        no_price_bps: 5_000,
+    }
+}
+
+#[test]
+fn built_operations_encode_like_hand_written_ones() {
+    let built = OperationBuilder::create_market("Rain?", Timestamp::from(2_000), Amount::from_tokens(100))
+        .category("weather")
+        .creator_trading_disabled()
+        .build();
+    let Operation::CreateMarket { question, categories, creator_trading_disabled, threshold, .. } = &built else {
+        panic!("{built:?}");
+    };
+    assert_eq!(question, "Rain?");
+    assert_eq!(categories, &vec!["weather".to_string()]);
+    assert!(creator_trading_disabled);
+    assert_eq!(*threshold, 0);
+
+    let built = OperationBuilder::buy(3).no().shares(Amount::ONE).max_cost(Amount::ONE).deadline(Timestamp::from(9)).build();
+    let by_hand = Operation::BuyShares {
+        market_id: 3,
+        is_yes: false,
+        shares: Amount::ONE,
+        max_cost: Amount::ONE,
+        deadline: Some(Timestamp::from(9)),
+        max_price_impact_bps: None,
+    };
+    assert_eq!(encode_operation(&built.unwrap()).unwrap(), bcs::to_bytes(&by_hand).unwrap());
+    assert_eq!(OperationBuilder::buy(3).shares(Amount::ONE).build().unwrap_err(), ClientError::MissingField("side"));
+}
+
+#[test]
+fn parses_the_contracts_trade_responses() {
+    let bytes = bcs::to_bytes("StraddlePurchased:20.119047619047619048:12.5:7.619047619047619048:12500:7619:5243:4756:0.").unwrap();
+    let OperationResponse::StraddlePurchased(receipt) = decode_response(&bytes).unwrap() else { panic!() };
+    assert_eq!(receipt.total_cost, amount("20.119047619047619048"));
+    assert_eq!(receipt.no_average_price_bps, 7_619);
+    assert_eq!(receipt.fees, Amount::ZERO);
+
+    let OperationResponse::SharesSold(receipt) = "SharesSold:9.:10.:4500:5500:55.:45.:0.1:100".parse().unwrap() else {
+        panic!()
+    };
+    assert_eq!(receipt.fee, amount("0.1"));
+    assert_eq!(receipt.price_impact_bps, None);
+
+    let response = "PositionClosed:12.375:10.:5000:5000:50.:50.:0.125:100:-250000000000000000";
+    let OperationResponse::PositionClosed { receipt, realized_pnl } = response.parse().unwrap() else { panic!() };
+    assert_eq!(receipt.amount, amount("12.375"));
+    assert_eq!(receipt.price_impact_bps, None);
+    assert_eq!(realized_pnl, -250_000_000_000_000_000);
+
+    let response = "ParlayPurchased:20.:12.5:7.5";
+    let OperationResponse::ParlayPurchased { total_cost, leg_costs } = response.parse().unwrap() else { panic!() };
+    assert_eq!((total_cost, leg_costs), (amount("20"), vec![amount("12.5"), amount("7.5")]));
+
+    // 10 winning YES shares of 60 outstanding, paid from pools of 40 and 62.5
+    let response = "MarketResolved:true:17.083333333333333333:10.:1.708333333333333333:1";
+    let OperationResponse::MarketResolved(settlement) = response.parse().unwrap() else { panic!() };
+    assert!(settlement.outcome);
+    assert_eq!(settlement.total_payout, amount("17.083333333333333333"));
+    assert_eq!(settlement.winning_shares, amount("10"));
+    assert_eq!(settlement.payout_per_share, amount("1.708333333333333333"));
+    assert_eq!(settlement.eligible_positions, 1);
+
+    let response = "MarketsResolved:2:1;0:MarketResolved:true:0.:0.:0.:0;4:ERROR: Market 4 is already resolved;7:MarketVoided";
+    let OperationResponse::MarketsResolved(results) = response.parse().unwrap() else { panic!() };
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results[0], (0, OperationResponse::MarketResolved(SettlementReceipt { outcome: true, .. }))));
+    assert_eq!(results[1], (4, OperationResponse::Error("Market 4 is already resolved".to_string())));
+    assert_eq!(results[2], (7, OperationResponse::Other { kind: "MarketVoided".to_string(), fields: Vec::new() }));
+    assert!(matches!("MarketsResolved:2:0;0:MarketVoided".parse::<OperationResponse>(), Err(ClientError::MalformedResponse(_))));
+
+    let response = "WinningsClaimed:18.:10.:8000000000000000000";
+    let OperationResponse::WinningsClaimed { payout, cost_basis, profit } = response.parse().unwrap() else { panic!() };
+    assert_eq!((payout, cost_basis, profit), (amount("18"), Some(amount("10")), Some(8_000_000_000_000_000_000)));
+    let response = "WinningsClaimed:18.:unknown:unknown";
+    let OperationResponse::WinningsClaimed { cost_basis, profit, .. } = response.parse().unwrap() else { panic!() };
+    assert_eq!((cost_basis, profit), (None, None));
+
+    let other: OperationResponse = "ExpiredFinalized:1:2".parse().unwrap();
+    assert_eq!(other, OperationResponse::Other { kind: "ExpiredFinalized".to_string(), fields: vec!["1".into(), "2".into()] });
+    let market_key = market_key(ChainId(CryptoHash::from([1; 4])), AccountOwner::CHAIN, 0);
+    let created: OperationResponse = format!("MarketCreated:1:{market_key}").parse().unwrap();
+    assert_eq!(created, OperationResponse::MarketCreated { market_id: 1, market_key });
+    // A known kind with missing, malformed or extra fields is refused
+    assert!(matches!("MarketCreated:1".parse::<OperationResponse>(), Err(ClientError::MalformedResponse(_))));
+    assert!(matches!("MarketCreated:1:2".parse::<OperationResponse>(), Err(ClientError::MalformedResponse(_))));
+    assert!(matches!(format!("MarketCreated:1:{market_key}:2").parse::<OperationResponse>(), Err(ClientError::MalformedResponse(_))));
+}
+
+#[test]
+fn local_quotes_price_like_the_contract() {
+    // The straddle's YES leg: 10 shares from 50/50 pools cost 12.5
+    let market = snapshot();
+    let quote = market.buy_quote(true, Amount::from_tokens(10)).unwrap();
+    assert_eq!(quote.cost, amount("12.5"));
+    assert_eq!(quote.price_impact_bps, 2_500);
+
+    let market = MarketSnapshot { fee_bps: 100, ..snapshot() };
+    // 50 * 10 / (50 + 10) before a 1% fee
+    let quote = market.sell_quote(false, Amount::from_tokens(10)).unwrap();
+    assert_eq!(quote.fee, amount("0.083333333333333333"));
+    assert_eq!(quote.proceeds, amount("8.25"));
+
+    assert!(matches!(market.buy_quote(true, Amount::from_tokens(50)), Err(ClientError::Quote(_))));
+    let closed = MarketSnapshot { is_tradeable: false, ..snapshot() };
+    assert_eq!(closed.buy_quote(true, Amount::ONE).unwrap_err(), ClientError::MarketClosed(1));
+}
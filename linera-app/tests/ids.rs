@@ -0,0 +1,48 @@
+// Copyright (c) Chronos Markets
+// Native tests of ID allocation from counter registers, and of the market
+// keys that tell markets apart across chains
+
+use chronos_market::{allocate_id, market_key};
+use linera_sdk::linera_base_types::{AccountOwner, ChainId, CryptoHash};
+use linera_views::{context::MemoryContext, register_view::RegisterView, views::View};
+
+fn counter(value: u64) -> RegisterView<MemoryContext<()>, u64> {
+    let mut counter = RegisterView::new(MemoryContext::new_for_testing(())).unwrap();
+    counter.set(value);
+    counter
+}
+
+#[test]
+fn ids_are_handed_out_in_order() {
+    let mut counter = counter(0);
+    assert_eq!(allocate_id(&mut counter), Some(0));
+    assert_eq!(allocate_id(&mut counter), Some(1));
+    assert_eq!(*counter.get(), 2);
+}
+
+#[test]
+fn an_exhausted_counter_refuses_instead_of_wrapping() {
+    let mut counter = counter(u64::MAX - 2);
+    assert_eq!(allocate_id(&mut counter), Some(u64::MAX - 2));
+    assert_eq!(allocate_id(&mut counter), Some(u64::MAX - 1));
+    // Issuing u64::MAX would leave nothing to advance to but 0
+    assert_eq!(allocate_id(&mut counter), None);
+    assert_eq!(allocate_id(&mut counter), None);
+    assert_eq!(*counter.get(), u64::MAX);
+}
+
+#[test]
+fn market_keys_differ_wherever_ids_collide() {
+    let (chain, other_chain) = (ChainId(CryptoHash::from([1; 4])), ChainId(CryptoHash::from([2; 4])));
+    let (creator, other_creator) = (AccountOwner::CHAIN, AccountOwner::Address32(CryptoHash::from([3; 4])));
+    let key = market_key(chain, creator, 0);
+    // The same inputs always give the same key
+    assert_eq!(market_key(chain, creator, 0), key);
+    // Market 0 of another chain, another creator's first market, and the
+    // creator's next market all get keys of their own
+    let others = [market_key(other_chain, creator, 0), market_key(chain, other_creator, 0), market_key(chain, creator, 1)];
+    for other in others {
+        assert_ne!(other, key);
+    }
+    assert_ne!(others[0], others[1]);
+}
@@ -0,0 +1,175 @@
+// Copyright (c) Chronos Markets
+// Native tests of the LP incentive stream and its rewards-per-share accumulator
+
+use chronos_market::IncentiveProgram;
+use linera_sdk::{
+    bcs,
+    linera_base_types::{AccountOwner, Amount, Timestamp},
+};
+
+const HOUR_MICROS: u64 = 3_600_000_000;
+
+fn program(budget: Amount, duration_secs: u64) -> IncentiveProgram {
+    IncentiveProgram {
+        market_id: 1,
+        funder: AccountOwner::CHAIN,
+        budget,
+        start: Timestamp::from(HOUR_MICROS),
+        duration_secs,
+        rewards_per_lp_share: 0,
+        streamed: Amount::ZERO,
+        claimed: Amount::ZERO,
+        returned: None,
+        topped_up_at: None,
+        streamed_at_top_up: Amount::ZERO,
+    }
+}
+
+fn at_hour(hour: u64) -> Timestamp {
+    Timestamp::from(hour * HOUR_MICROS)
+}
+
+/// An LP's stake, the accumulator value it was last paid at and its total
+struct Lp {
+    shares: Amount,
+    checkpoint: u128,
+    paid: Amount,
+}
+
+impl Lp {
+    fn new(tokens: u128) -> Self {
+        Lp { shares: Amount::from_tokens(tokens), checkpoint: 0, paid: Amount::ZERO }
+    }
+
+    /// Join with the accumulator brought up to `now` under the shares
+    /// staked before, so nothing streamed earlier is owed
+    fn enter(tokens: u128, program: &mut IncentiveProgram, total_lp_shares: Amount, now: Timestamp) -> Self {
+        program.accrue(total_lp_shares, now);
+        Lp { checkpoint: program.rewards_per_lp_share, ..Lp::new(tokens) }
+    }
+
+    fn claim(&mut self, program: &mut IncentiveProgram, total_lp_shares: Amount, now: Timestamp) {
+        program.accrue(total_lp_shares, now);
+        let owed = program.pending(self.shares, self.checkpoint);
+        program.claimed = program.claimed.saturating_add(owed);
+        self.checkpoint = program.rewards_per_lp_share;
+        self.paid = self.paid.saturating_add(owed);
+    }
+}
+
+#[test]
+fn the_budget_streams_linearly_rounding_down() {
+    let program = program(Amount::from_attos(10), 3);
+    assert_eq!(program.end(), Timestamp::from(HOUR_MICROS + 3_000_000));
+    assert_eq!(program.streamed_at(Timestamp::from(0)), Amount::ZERO);
+    // 10 * 1 / 3 and 10 * 2 / 3, rounded down
+    assert_eq!(program.streamed_at(Timestamp::from(HOUR_MICROS + 1_000_000)), Amount::from_attos(3));
+    assert_eq!(program.streamed_at(Timestamp::from(HOUR_MICROS + 2_000_000)), Amount::from_attos(6));
+    assert_eq!(program.streamed_at(program.end()), Amount::from_attos(10));
+    assert_eq!(program.streamed_at(at_hour(100)), Amount::from_attos(10));
+}
+
+#[test]
+fn two_to_one_stakes_earn_a_two_to_one_split() {
+    // An odd budget over 10 hours, so every step rounds
+    let budget = Amount::from_attos(1_000_000_000_000_000_000_007);
+    let mut program = program(budget, 10 * 3_600);
+    let (mut a, mut b) = (Lp::new(200), Lp::new(100));
+    let total = a.shares.saturating_add(b.shares);
+
+    // Claims at unrelated times bring the accumulator up to date lazily
+    a.claim(&mut program, total, at_hour(2));
+    b.claim(&mut program, total, at_hour(4));
+    a.claim(&mut program, total, at_hour(8));
+    b.claim(&mut program, total, at_hour(9));
+    a.claim(&mut program, total, at_hour(20));
+    b.claim(&mut program, total, at_hour(30));
+
+    assert_eq!(program.streamed, budget);
+    assert_eq!(program.claimed, a.paid.saturating_add(b.paid));
+    // Each accrual drops under total / FEE_SCALE = 300 attos and each claim
+    // under one; nothing is ever paid beyond the budget
+    let dust = u128::from(budget) - u128::from(program.claimed);
+    assert!(dust < 4 * 300 + 6, "{dust}");
+    let (a_paid, b_paid) = (u128::from(a.paid), u128::from(b.paid));
+    assert!(a_paid.abs_diff(2 * b_paid) < 4 * 300, "{a_paid} vs {b_paid}");
+    assert!(a_paid > 666_666_666_666_666_666_000);
+
+    // Claiming again finds nothing new
+    a.claim(&mut program, total, at_hour(40));
+    assert_eq!(u128::from(a.paid), a_paid);
+}
+
+#[test]
+fn an_lp_staked_for_half_the_stream_earns_half() {
+    let budget = Amount::from_attos(1_000_000_000_000_000_000_007);
+    let mut program = program(budget, 10 * 3_600);
+    let (mut whole, mut early) = (Lp::new(100), Lp::new(100));
+    let total = whole.shares.saturating_add(early.shares);
+
+    // Halfway through, one LP leaves and an equal one takes its place, so
+    // the total staked never changes
+    early.claim(&mut program, total, at_hour(6));
+    early.shares = Amount::ZERO;
+    let mut late = Lp::enter(100, &mut program, whole.shares, at_hour(6));
+    let total = whole.shares.saturating_add(late.shares);
+    whole.claim(&mut program, total, at_hour(11));
+    late.claim(&mut program, total, at_hour(11));
+
+    let (whole, early, late) = (u128::from(whole.paid), u128::from(early.paid), u128::from(late.paid));
+    // Each accrual drops under total / FEE_SCALE = 200 attos and each claim under one
+    assert!(whole.abs_diff(2 * early) < 3 * 200, "{whole} vs {early}");
+    assert!(early.abs_diff(late) < 3 * 200, "{early} vs {late}");
+    assert!(u128::from(budget) - whole - early - late < 3 * 200 + 3);
+}
+
+#[test]
+fn settled_programs_stop_streaming_and_keep_what_lps_are_owed() {
+    let mut stream = program(Amount::from_tokens(100), 10 * 3_600);
+    let lp = Amount::from_tokens(50);
+    stream.accrue(lp, at_hour(6));
+    assert_eq!(stream.streamed, Amount::from_tokens(50));
+    let owed = stream.pending(lp, 0);
+    assert_eq!(owed, Amount::from_tokens(50));
+
+    // Settling returns the rest; the accumulator no longer moves
+    stream.returned = Some(stream.budget.saturating_sub(owed));
+    stream.accrue(lp, at_hour(20));
+    assert_eq!(stream.streamed, Amount::from_tokens(50));
+    assert_eq!(stream.pending(lp, 0), owed);
+
+    // A program without LP shares waits rather than losing the budget
+    let mut idle = program(Amount::from_tokens(100), 3_600);
+    idle.accrue(Amount::ZERO, at_hour(3));
+    assert_eq!(idle.streamed, Amount::ZERO);
+    assert_eq!(idle.rewards_per_lp_share, 0);
+}
+
+#[test]
+fn a_top_up_streams_over_what_remains_without_changing_what_was_due() {
+    let mut stream = program(Amount::from_tokens(100), 10 * 3_600);
+    stream.top_up(Amount::from_tokens(30), 3_600, at_hour(6));
+    assert_eq!(stream.budget, Amount::from_tokens(130));
+    assert_eq!(stream.end(), at_hour(11));
+    // 50 was due at the top-up; the other 80 spreads over the last 5 hours
+    assert_eq!(stream.streamed_at(at_hour(6)), Amount::from_tokens(50));
+    assert_eq!(stream.streamed_at(at_hour(8)), Amount::from_tokens(82));
+    assert_eq!(stream.streamed_at(at_hour(11)), Amount::from_tokens(130));
+
+    // Funding for longer than remains pushes the end out
+    let mut stream = program(Amount::from_tokens(100), 3_600);
+    stream.top_up(Amount::from_tokens(60), 3 * 3_600, at_hour(2));
+    assert_eq!(stream.end(), at_hour(5));
+    assert_eq!(stream.streamed_at(at_hour(2)), Amount::from_tokens(100));
+    assert_eq!(stream.streamed_at(at_hour(3)), Amount::from_tokens(120));
+}
+
+#[test]
+fn programs_stored_before_top_ups_decode_without_one() {
+    let stream = program(Amount::from_tokens(100), 3_600);
+    let mut bytes = bcs::to_bytes(&stream).unwrap();
+    bytes.truncate(bytes.len() - bcs::to_bytes(&(None::<Timestamp>, Amount::ZERO)).unwrap().len());
+    let decoded: IncentiveProgram = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.topped_up_at, None);
+    assert_eq!(decoded.streamed_at(at_hour(2)), Amount::from_tokens(100));
+}
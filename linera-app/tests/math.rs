@@ -0,0 +1,371 @@
+// Copyright (c) Chronos Markets
+// Native tests of the AMM and payout math
+
+use chronos_market::math::{
+    cost_to_buy, max_sell_shares, payout_for_shares, pool_at_price, pool_product, price_impact_bps, price_of, probability_move_bps, ramped_fee_bps, safe_mul_div,
+    sell_price_impact_bps, sell_proceeds, share_unit_attos, shares_for_cost, split_fee, shares_from_units, shares_to_units, PRICE_SCALE,
+};
+use num_bigint::BigUint;
+use proptest::prelude::*;
+
+const TOKEN: u128 = PRICE_SCALE;
+
+#[test]
+fn safe_mul_div_is_exact_beyond_u128() {
+    assert_eq!(safe_mul_div(6, 7, 4), Ok(10));
+    assert_eq!(safe_mul_div(u128::MAX, u128::MAX, u128::MAX), Ok(u128::MAX));
+    assert_eq!(safe_mul_div(u128::MAX, 3, 6), Ok(u128::MAX / 2));
+    assert!(safe_mul_div(u128::MAX, 2, 1).is_err());
+    assert!(safe_mul_div(1, 1, 0).is_err());
+}
+
+#[test]
+fn buying_and_selling_against_even_pools() {
+    let pool = 50 * TOKEN;
+    assert_eq!(cost_to_buy(pool, pool, 10 * TOKEN), Ok(12_500_000_000_000_000_000));
+    assert_eq!(shares_for_cost(pool, pool, 12_500_000_000_000_000_000), Ok(10 * TOKEN));
+    // Selling the shares straight back returns what they cost
+    assert_eq!(sell_proceeds(40 * TOKEN, 62_500_000_000_000_000_000, 10 * TOKEN), Ok(12_500_000_000_000_000_000));
+    assert!(cost_to_buy(pool, pool, pool).is_err());
+}
+
+#[test]
+fn rounding_favours_the_pools() {
+    // 40 * 1 / 62 is below one atto: buying rounds up, selling rounds down
+    assert_eq!(cost_to_buy(40, 63, 1), Ok(1));
+    assert_eq!(sell_proceeds(62, 40, 1), Ok(0));
+    assert_eq!(shares_for_cost(62, 40, 1), Ok(0));
+    assert_eq!(cost_to_buy(40, 62, 0), Ok(0));
+}
+
+#[test]
+fn payouts_split_the_pot_pro_rata() {
+    assert_eq!(payout_for_shares(1, 4, 100), Ok(25));
+    assert_eq!(payout_for_shares(1, 3, 100), Ok(33));
+    assert_eq!(payout_for_shares(0, 3, 100), Ok(0));
+    assert!(payout_for_shares(4, 3, 100).is_err());
+    assert!(payout_for_shares(0, 0, 100).is_err());
+}
+
+#[test]
+fn pools_at_a_price_stay_on_the_curve() {
+    // Even pools of 50: at 80% a side holds 25 against 100
+    assert_eq!(pool_at_price(50 * TOKEN, 50 * TOKEN, 8_000), Ok(25 * TOKEN));
+    assert_eq!(pool_at_price(50 * TOKEN, 50 * TOKEN, 2_000), Ok(100 * TOKEN));
+    assert_eq!(pool_at_price(50 * TOKEN, 50 * TOKEN, 5_000), Ok(50 * TOKEN));
+    // sqrt(2) rounds up
+    assert_eq!(pool_at_price(1, 2, 5_000), Ok(2));
+    assert_eq!(pool_at_price(0, 50 * TOKEN, 5_000), Ok(0));
+    assert_eq!(pool_at_price(u128::MAX, u128::MAX, 5_000), Ok(u128::MAX));
+    assert!(pool_at_price(TOKEN, TOKEN, 0).is_err());
+    assert!(pool_at_price(TOKEN, TOKEN, 10_000).is_err());
+}
+
+#[test]
+fn prices_of_even_empty_and_huge_pools() {
+    assert_eq!(price_of(50 * TOKEN, 50 * TOKEN), PRICE_SCALE / 2);
+    assert_eq!(price_of(0, 0), PRICE_SCALE / 2);
+    assert_eq!(price_of(0, TOKEN), PRICE_SCALE);
+    assert_eq!(price_of(TOKEN, 0), 0);
+    assert_eq!(price_of(u128::MAX, u128::MAX), PRICE_SCALE / 2);
+    assert_eq!(price_of(40 * TOKEN, 60 * TOKEN), 600_000_000_000_000_000);
+}
+
+#[test]
+fn maker_rebates_and_the_remainder_add_up_to_the_fee() {
+    // A taker paying 1% over partial fills of differing value, 30% of each
+    // fill's fee rebated to its maker
+    let fills = [7 * TOKEN / 3, 10 * TOKEN, 1, 999, 123_456_789];
+    let (mut fees, mut rebates, mut remainder) = (0, 0, 0);
+    for value in fills {
+        let fee = safe_mul_div(value, 100, 10_000).unwrap();
+        let (rebate, rest) = split_fee(fee, 3_000).unwrap();
+        assert_eq!(rebate + rest, fee);
+        fees += fee;
+        rebates += rebate;
+        remainder += rest;
+    }
+    assert_eq!(fees, 23_333_333_333_333_333 + 100_000_000_000_000_000 + 9 + 1_234_567);
+    // Each rebate rounds down, so the odd attos stay with the remainder
+    assert_eq!(rebates, 6_999_999_999_999_999 + 30_000_000_000_000_000 + 2 + 370_370);
+    assert_eq!(rebates + remainder, fees);
+
+    assert_eq!(split_fee(10, 0), Ok((0, 10)));
+    assert_eq!(split_fee(10, 10_000), Ok((10, 0)));
+    assert!(split_fee(10, 10_001).is_err());
+}
+
+#[test]
+fn fees_ramp_linearly_over_the_final_window() {
+    // 100 bps until 600 seconds before the end, 500 bps at the end
+    let window = 600_000_000;
+    assert_eq!(ramped_fee_bps(100, 500, window, 3_600_000_000), 100);
+    assert_eq!(ramped_fee_bps(100, 500, window, window), 100);
+    assert_eq!(ramped_fee_bps(100, 500, window, window / 2), 300);
+    assert_eq!(ramped_fee_bps(100, 500, window, 1), 499);
+    assert_eq!(ramped_fee_bps(100, 500, window, 0), 500);
+    // Never below the base fee, and flat without a window
+    assert_eq!(ramped_fee_bps(100, 50, window, 0), 100);
+    assert_eq!(ramped_fee_bps(100, 500, 0, 0), 100);
+}
+
+#[test]
+fn price_impact_is_the_average_over_the_marginal_price() {
+    // 10 of 50 shares cost 12.5 against a marginal price of 1: 25% above
+    assert_eq!(cost_to_buy(50 * TOKEN, 50 * TOKEN, 10 * TOKEN), Ok(25 * TOKEN / 2));
+    assert_eq!(price_impact_bps(50 * TOKEN, 10 * TOKEN), Ok(2_500));
+    // Rounding makes one atto cost two, double the marginal price, though
+    // the trade moves nothing measurable
+    assert_eq!(cost_to_buy(50 * TOKEN, 50 * TOKEN, 1), Ok(2));
+    assert_eq!(price_impact_bps(50 * TOKEN, 1), Ok(1));
+    assert_eq!(price_impact_bps(50 * TOKEN, 0), Ok(0));
+    assert_eq!(price_impact_bps(u128::MAX, u128::MAX - 1), Ok(u64::MAX));
+    assert!(price_impact_bps(50 * TOKEN, 50 * TOKEN).is_err());
+}
+
+#[test]
+fn sales_price_below_the_marginal_price_and_stop_at_what_is_available() {
+    // 10 shares into 50/50 pools fetch 50 * 10 / 60 against a marginal 1
+    assert_eq!(sell_proceeds(50 * TOKEN, 50 * TOKEN, 10 * TOKEN), Ok(8_333_333_333_333_333_333));
+    assert_eq!(sell_price_impact_bps(50 * TOKEN, 10 * TOKEN), Ok(1_667));
+    assert_eq!(sell_price_impact_bps(50 * TOKEN, 0), Ok(0));
+    assert_eq!(sell_price_impact_bps(0, 0), Ok(0));
+    assert_eq!(sell_price_impact_bps(0, TOKEN), Ok(10_000));
+
+    // Proceeds approach pool_out but never reach it
+    assert_eq!(max_sell_shares(50 * TOKEN, 50 * TOKEN, 50 * TOKEN), Ok(None));
+    assert_eq!(max_sell_shares(50 * TOKEN, 50 * TOKEN, 50 * TOKEN - 1), Ok(None));
+    // About 20 * 50 / (50 - 20) shares pay out 20, to the atto after rounding
+    let max = max_sell_shares(50 * TOKEN, 50 * TOKEN, 20 * TOKEN).unwrap().unwrap();
+    assert_eq!(max, 33_333_333_333_333_333_336);
+    assert_eq!(sell_proceeds(50 * TOKEN, 50 * TOKEN, max), Ok(20 * TOKEN));
+    assert_eq!(sell_proceeds(50 * TOKEN, 50 * TOKEN, max + 1), Ok(20 * TOKEN + 1));
+    // An empty pool_in pays all of pool_out for any sale
+    assert_eq!(max_sell_shares(0, 50 * TOKEN, 20 * TOKEN), Ok(Some(0)));
+}
+
+#[test]
+fn probability_moves_from_the_exact_prices() {
+    // 10 YES out of 50/50 pools for 12.5: 50% to 62.5 / 102.5, about 60.98%
+    assert_eq!(probability_move_bps(50 * TOKEN, 50 * TOKEN, 10 * TOKEN, 25 * TOKEN / 2), Ok(1_098));
+    // Rounded up once visible at all; one atto is below what prices resolve
+    assert_eq!(probability_move_bps(50 * TOKEN, 50 * TOKEN, TOKEN / 1_000_000, TOKEN / 1_000_000), Ok(1));
+    assert_eq!(probability_move_bps(50 * TOKEN, 50 * TOKEN, 1, 2), Ok(0));
+    assert_eq!(probability_move_bps(50 * TOKEN, 50 * TOKEN, 0, 0), Ok(0));
+    assert!(probability_move_bps(50 * TOKEN, 50 * TOKEN, 50 * TOKEN, TOKEN).is_err());
+}
+
+#[test]
+fn share_units_convert_exactly() {
+    assert_eq!(share_unit_attos(18), Some(1));
+    assert_eq!(share_unit_attos(6), Some(1_000_000_000_000));
+    assert_eq!(share_unit_attos(0), Some(TOKEN));
+    assert_eq!(share_unit_attos(19), None);
+
+    // 1.5 shares at six decimal places
+    assert_eq!(shares_from_units(1_500_000, 6), Some(3 * TOKEN / 2));
+    assert_eq!(shares_to_units(3 * TOKEN / 2, 6), Some(1_500_000));
+    // Finer than a unit is rejected rather than rounded
+    assert_eq!(shares_to_units(3 * TOKEN / 2 + 1, 6), None);
+    assert_eq!(shares_to_units(TOKEN / 2, 0), None);
+    assert_eq!(shares_from_units(u128::MAX, 0), None);
+}
+
+/// Pools from one atto to a billion tokens
+fn pool() -> impl Strategy<Value = u128> {
+    1..=1_000_000_000 * TOKEN
+}
+
+/// Operands across the whole range, weighted towards ones whose product
+/// fits in u128 so both paths through `safe_mul_div` get exercised
+fn operand() -> impl Strategy<Value = u128> {
+    prop_oneof![0..=u128::from(u64::MAX), any::<u128>(), 0..=1_000_000_000 * TOKEN]
+}
+
+/// a * b / c computed on unbounded integers: the reference for the u128
+/// helpers, None when it does not fit in u128
+fn reference_mul_div(a: u128, b: u128, c: u128, round_up: bool) -> Option<u128> {
+    let product = BigUint::from(a) * BigUint::from(b);
+    let c = BigUint::from(c);
+    let mut quotient = &product / &c;
+    if round_up && &quotient * &c != product {
+        quotient += 1u32;
+    }
+    u128::try_from(&quotient).ok()
+}
+
+proptest! {
+    #[test]
+    fn price_impact_grows_with_trade_size(pool_out in pool(), fraction in 0.0..0.99f64) {
+        let shares = (pool_out as f64 * fraction) as u128 % pool_out;
+        let smaller = price_impact_bps(pool_out, shares / 2).unwrap();
+        let impact = price_impact_bps(pool_out, shares).unwrap();
+        prop_assert!(smaller <= impact);
+        // Never below the exact value, and at most one bps above it
+        let exact = shares as f64 * 10_000.0 / (pool_out - shares) as f64;
+        prop_assert!(impact as f64 >= exact - 1e-6 && (impact as f64) < exact + 1.0 + 1e-6);
+    }
+
+    #[test]
+    fn max_sell_shares_is_the_last_sale_within_reach(pool_in in pool(), pool_out in pool(), fraction in 0.0..1.0f64) {
+        let available = (pool_out as f64 * fraction) as u128;
+        match max_sell_shares(pool_in, pool_out, available).unwrap() {
+            None => prop_assert!(sell_proceeds(pool_in, pool_out, u128::MAX - pool_in).unwrap() <= available),
+            Some(max) => {
+                prop_assert!(sell_proceeds(pool_in, pool_out, max).unwrap() <= available);
+                prop_assert!(sell_proceeds(pool_in, pool_out, max + 1).unwrap() > available);
+            }
+        }
+    }
+
+    #[test]
+    fn fee_splits_conserve_the_fee(fee in 0..=u128::MAX, rebate_bps in 0..=10_000u64) {
+        let (rebate, rest) = split_fee(fee, rebate_bps).unwrap();
+        prop_assert_eq!(rebate.checked_add(rest), Some(fee));
+    }
+
+    #[test]
+    fn share_units_round_trip(units in 0..=u64::MAX as u128, decimals in 0..=18u8) {
+        let attos = shares_from_units(units, decimals).unwrap();
+        prop_assert_eq!(shares_to_units(attos, decimals), Some(units));
+    }
+
+    #[test]
+    fn buys_never_shrink_the_pool_pool_product(pool_in in pool(), pool_out in pool(), fraction in 0.0..1.0f64) {
+        let shares = (pool_out as f64 * fraction) as u128 % pool_out;
+        let cost = cost_to_buy(pool_in, pool_out, shares).unwrap();
+        prop_assert!(pool_product(pool_in + cost, pool_out - shares) >= pool_product(pool_in, pool_out));
+    }
+
+    #[test]
+    fn spending_never_shrinks_the_pool_pool_product(pool_in in pool(), pool_out in pool(), cost in 0..=1_000_000 * TOKEN) {
+        let shares = shares_for_cost(pool_in, pool_out, cost).unwrap();
+        prop_assert!(shares < pool_out);
+        prop_assert!(pool_product(pool_in + cost, pool_out - shares) >= pool_product(pool_in, pool_out));
+    }
+
+    #[test]
+    fn sells_never_shrink_the_pool_pool_product(pool_in in pool(), pool_out in pool(), shares in 0..=1_000_000 * TOKEN) {
+        let proceeds = sell_proceeds(pool_in, pool_out, shares).unwrap();
+        prop_assert!(proceeds < pool_out);
+        prop_assert!(pool_product(pool_in + shares, pool_out - proceeds) >= pool_product(pool_in, pool_out));
+    }
+
+    #[test]
+    fn a_buy_sell_round_trip_never_profits(pool_in in pool(), pool_out in pool(), fraction in 0.0..1.0f64) {
+        let shares = (pool_out as f64 * fraction) as u128 % pool_out;
+        let cost = cost_to_buy(pool_in, pool_out, shares).unwrap();
+        // Selling returns the shares to the pool they came from
+        let proceeds = sell_proceeds(pool_out - shares, pool_in + cost, shares).unwrap();
+        prop_assert!(proceeds <= cost);
+    }
+
+    #[test]
+    fn prices_stay_in_range_and_sum_to_one(yes_pool in any::<u128>(), no_pool in any::<u128>()) {
+        let yes = price_of(yes_pool, no_pool);
+        let no = price_of(no_pool, yes_pool);
+        prop_assert!(yes <= PRICE_SCALE && no <= PRICE_SCALE);
+        // Each side rounds down by less than one unit
+        prop_assert!(yes + no <= PRICE_SCALE && yes + no >= PRICE_SCALE - 1);
+    }
+
+    #[test]
+    fn payouts_never_exceed_the_pot(holdings in prop::collection::vec(1..=1_000_000 * TOKEN, 1..20), pot in 0..=1_000_000_000 * TOKEN) {
+        let total: u128 = holdings.iter().sum();
+        let paid: u128 = holdings.iter().map(|shares| payout_for_shares(*shares, total, pot).unwrap()).sum();
+        prop_assert!(paid <= pot);
+        // Only rounding is left over, under one atto per holder
+        prop_assert!(pot - paid < holdings.len() as u128);
+    }
+
+    #[test]
+    fn trade_sequences_keep_the_pool_product_and_escrow(
+        liquidity in 2..=1_000_000_000 * TOKEN,
+        trades in prop::collection::vec((any::<bool>(), any::<bool>(), 0.0..0.9f64), 1..50),
+    ) {
+        // Pools and escrow as CreateMarket seeds them and trades move them
+        let half = liquidity / 2;
+        let (mut yes_pool, mut no_pool, mut escrow) = (half, half, liquidity);
+        let (mut yes_held, mut no_held) = (0u128, 0u128);
+        for (is_buy, is_yes, fraction) in trades {
+            let before = pool_product(yes_pool, no_pool);
+            let (pool, other_pool, held) = if is_yes {
+                (&mut yes_pool, &mut no_pool, &mut yes_held)
+            } else {
+                (&mut no_pool, &mut yes_pool, &mut no_held)
+            };
+            if is_buy {
+                let shares = (*pool as f64 * fraction) as u128;
+                let cost = cost_to_buy(*other_pool, *pool, shares).unwrap();
+                *pool -= shares;
+                *other_pool += cost;
+                escrow += cost;
+                *held += shares;
+            } else {
+                let shares = (*held as f64 * fraction) as u128;
+                let proceeds = sell_proceeds(*pool, *other_pool, shares).unwrap();
+                prop_assert!(proceeds <= escrow);
+                *pool += shares;
+                *other_pool -= proceeds;
+                escrow -= proceeds;
+                *held -= shares;
+            }
+            prop_assert!(pool_product(yes_pool, no_pool) >= before);
+            // Escrow covers the pools winners split, with the traders'
+            // shares as the margin
+            prop_assert_eq!(escrow - (yes_pool + no_pool), liquidity - 2 * half + yes_held + no_held);
+        }
+    }
+
+    #[test]
+    fn safe_mul_div_matches_big_integers(a in operand(), b in operand(), c in operand()) {
+        let result = safe_mul_div(a, b, c);
+        if c == 0 {
+            prop_assert!(result.is_err());
+        } else {
+            prop_assert_eq!(result.ok(), reference_mul_div(a, b, c, false));
+        }
+    }
+
+    #[test]
+    fn safe_mul_div_is_monotone(a in operand(), b in operand(), c in 1..=u128::MAX, step in operand()) {
+        let base = safe_mul_div(a, b, c);
+        for larger in [safe_mul_div(a.saturating_add(step), b, c), safe_mul_div(a, b.saturating_add(step), c)] {
+            match (&base, larger) {
+                (Ok(base), Ok(larger)) => prop_assert!(larger >= *base),
+                // A larger quotient may overflow where the smaller did not,
+                // never the other way round
+                (Ok(_), Err(_)) => {}
+                (Err(_), larger) => prop_assert!(larger.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn buy_costs_match_big_integers(pool_in in operand(), pool_out in operand(), shares in operand()) {
+        let cost = cost_to_buy(pool_in, pool_out, shares);
+        if shares >= pool_out {
+            prop_assert!(cost.is_err());
+        } else {
+            prop_assert_eq!(cost.ok(), reference_mul_div(pool_in, shares, pool_out - shares, true));
+        }
+    }
+
+    #[test]
+    fn payouts_conserve_any_pot(
+        holdings in prop::collection::vec(0..=u128::MAX / 64, 1..40),
+        pot in any::<u128>(),
+    ) {
+        let total: u128 = holdings.iter().sum();
+        prop_assume!(total > 0);
+        let mut paid = BigUint::from(0u32);
+        for shares in &holdings {
+            let payout = payout_for_shares(*shares, total, pot).unwrap();
+            prop_assert_eq!(Some(payout), reference_mul_div(*shares, pot, total, false));
+            paid += payout;
+        }
+        // Summed without overflow: never more than the pot
+        prop_assert!(paid <= BigUint::from(pot));
+        prop_assert!(payout_for_shares(total + 1, total, pot).is_err());
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright (c) Chronos Markets
+// Native tests of stored record decoding across layout versions
+
+use chronos_market::{
+    check_open, ActivityEntry, Config, LpFlow, LpLedger, Market, MarketCondition, MarketError, MarketSummary, OrderSide, Position, Sponsor, TraderStats, TriggerDirection, TriggerOrder,
+    TriggerStatus, market_key, DEFAULT_RECENT_TRADES_CAPACITY, MARKET_VERSION, POSITION_VERSION, RECORD_ENVELOPE,
+};
+use linera_sdk::{
+    bcs,
+    linera_base_types::{AccountOwner, Amount, ChainId, CryptoHash, Timestamp},
+};
+use serde::Serialize;
+
+fn market() -> Market {
+    Market {
+        id: 7,
+        creator: AccountOwner::CHAIN,
+        question: "Will it rain?".to_string(),
+        categories: vec!["weather".to_string()],
+        end_time: Timestamp::from(2_000),
+        created_at: Timestamp::from(1_000),
+        yes_pool: Amount::from_tokens(100),
+        no_pool: Amount::from_tokens(100),
+        total_yes_shares: Amount::ZERO,
+        total_no_shares: Amount::ZERO,
+        resolved: false,
+        outcome: None,
+        volume: Amount::ZERO,
+        escrow: Amount::from_tokens(100),
+        early_resolution_allowed: false,
+        resolution_time: None,
+        seed_shares: Amount::from_tokens(100),
+        refund_mode: false,
+        max_position_per_account: None,
+        max_position: None,
+        creator_trading_disabled: false,
+        fee_ramp: None,
+        voided: false,
+        resolved_by: None,
+        claim_deadline: None,
+        description: String::new(),
+        resolution_source: String::new(),
+        total_lp_shares: Amount::from_tokens(100),
+        fees_per_lp_share: 0,
+        fees_collected: Amount::ZERO,
+        fees_distributed: Amount::ZERO,
+        parent: Some(MarketCondition { market_id: 3, outcome: true }),
+        group_id: Some(2),
+        resolvers: vec![AccountOwner::CHAIN],
+        threshold: 1,
+        price_cumulative: 42,
+        price_updated_at: Some(Timestamp::from(1_500)),
+        max_probability_move_bps: Some(250),
+        resolution_evidence: None,
+        claims_started: false,
+        closed: true,
+        featured: true,
+        max_trade_shares: None,
+        upvotes: 3,
+        key: None,
+        sponsors: Vec::new(),
+        version: MARKET_VERSION,
+    }
+}
+
+fn position() -> Position {
+    Position {
+        market_id: 7,
+        owner: AccountOwner::CHAIN,
+        yes_shares: Amount::from_tokens(5),
+        no_shares: Amount::ZERO,
+        claimed: false,
+        yes_cost_basis: Amount::from_tokens(3),
+        no_cost_basis: Amount::ZERO,
+        realized_pnl: -9,
+        version: POSITION_VERSION,
+    }
+}
+
+/// `bytes` without `tail`, the encoding of a record's last fields, as a
+/// build whose struct ended before those fields wrote it
+fn cut<T: Serialize>(mut bytes: Vec<u8>, tail: &T) -> Vec<u8> {
+    bytes.truncate(bytes.len() - bcs::to_bytes(tail).unwrap().len());
+    bytes
+}
+
+/// Length of the envelope that opens a record at `version`
+fn header(version: u8) -> usize {
+    bcs::to_bytes(&(RECORD_ENVELOPE, version)).unwrap().len()
+}
+
+/// `market` as a build at `version` wrote it: the envelope with that
+/// version, then the fields that layout has
+fn market_at(market: &Market, version: u8) -> Vec<u8> {
+    let added = [
+        (2, bcs::to_bytes(&market.max_probability_move_bps).unwrap()),
+        (3, bcs::to_bytes(&market.resolution_evidence).unwrap()),
+        (4, bcs::to_bytes(&market.claims_started).unwrap()),
+        (5, bcs::to_bytes(&market.closed).unwrap()),
+        (6, bcs::to_bytes(&market.featured).unwrap()),
+        (7, bcs::to_bytes(&market.max_trade_shares).unwrap()),
+        (8, bcs::to_bytes(&market.upvotes).unwrap()),
+        (9, bcs::to_bytes(&market.key).unwrap()),
+        (10, bcs::to_bytes(&market.sponsors).unwrap()),
+    ];
+    let mut bytes = bcs::to_bytes(market).unwrap();
+    let later: usize = added.iter().filter(|(since, _)| *since > version).map(|(_, field)| field.len()).sum();
+    bytes.truncate(bytes.len() - later);
+    bytes[header(version) - 1] = version;
+    bytes
+}
+
+/// `market` as stored before the envelope: the version 1 layout, bare
+fn legacy_market(market: &Market) -> Vec<u8> {
+    market_at(market, 1).split_off(header(1))
+}
+
+#[test]
+fn current_records_round_trip() {
+    let bytes = bcs::to_bytes(&market()).unwrap();
+    assert_eq!(bytes[..8], RECORD_ENVELOPE.to_le_bytes());
+    assert_eq!(bytes[8], MARKET_VERSION);
+    let decoded: Market = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.version, MARKET_VERSION);
+    assert_eq!(decoded.price_cumulative, 42);
+    assert_eq!(decoded.parent.map(|p| p.market_id), Some(3));
+    assert_eq!(decoded.max_probability_move_bps, Some(250));
+    assert!(decoded.closed);
+
+    let bytes = bcs::to_bytes(&position()).unwrap();
+    let decoded: Position = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.version, POSITION_VERSION);
+    assert_eq!(decoded.realized_pnl, -9);
+}
+
+#[test]
+fn every_older_layout_reads_later_fields_as_defaults() {
+    let evidence = Some("https://example.com/results".to_string());
+    let key = market_key(ChainId(CryptoHash::from([1; 4])), AccountOwner::CHAIN, 4);
+    let written = Market {
+        resolution_evidence: evidence.clone(),
+        claims_started: true,
+        max_trade_shares: Some(Amount::from_tokens(25)),
+        key: Some(key),
+        sponsors: vec![Sponsor {
+            account: AccountOwner::CHAIN,
+            amount: Amount::from_tokens(500),
+            name: "Acme".to_string(),
+            url: "https://acme.example".to_string(),
+        }],
+        ..market()
+    };
+    for version in 1..=MARKET_VERSION {
+        let mut decoded: Market = bcs::from_bytes(&market_at(&written, version)).unwrap();
+        assert_eq!(decoded.version, version);
+        assert_eq!(decoded.price_cumulative, 42);
+        assert_eq!(decoded.price_updated_at, Some(Timestamp::from(1_500)));
+        assert_eq!(decoded.max_probability_move_bps, (version >= 2).then_some(250));
+        assert_eq!(decoded.resolution_evidence, evidence.clone().filter(|_| version >= 3));
+        assert_eq!(decoded.claims_started, version >= 4);
+        assert_eq!(decoded.closed, version >= 5);
+        assert_eq!(decoded.featured, version >= 6);
+        assert_eq!(decoded.max_trade_shares, written.max_trade_shares.filter(|_| version >= 7));
+        assert_eq!(decoded.upvotes, if version >= 8 { 3 } else { 0 });
+        assert_eq!(decoded.key, written.key.filter(|_| version >= 9));
+        assert_eq!(decoded.sponsors.len(), usize::from(version >= 10));
+        decoded.upgrade();
+        assert_eq!(decoded.version, MARKET_VERSION);
+    }
+}
+
+#[test]
+fn market_from_before_the_envelope_decodes_as_version_zero() {
+    let mut decoded: Market = bcs::from_bytes(&legacy_market(&market())).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert_eq!(decoded.id, 7);
+    assert_eq!(decoded.price_cumulative, 42);
+    assert_eq!(decoded.price_updated_at, Some(Timestamp::from(1_500)));
+    assert_eq!(decoded.group_id, Some(2));
+    assert_eq!(decoded.max_probability_move_bps, None);
+    assert!(!decoded.closed);
+    decoded.upgrade();
+    assert_eq!(decoded.version, MARKET_VERSION);
+}
+
+#[test]
+fn market_without_price_accumulator_fills_defaults() {
+    let written = market();
+    let bytes = cut(legacy_market(&written), &(written.price_cumulative, written.price_updated_at));
+    let decoded: Market = bcs::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.threshold, 1);
+    assert_eq!(decoded.resolvers, vec![AccountOwner::CHAIN]);
+    assert_eq!(decoded.price_cumulative, 0);
+    assert_eq!(decoded.price_updated_at, None);
+    assert_eq!(decoded.version, 0);
+}
+
+#[test]
+fn malformed_market_records_are_rejected() {
+    let written = market();
+    let current = bcs::to_bytes(&written).unwrap();
+
+    // A bare record with bytes past its last field, as once written with
+    // a trailing version
+    let mut bytes = legacy_market(&written);
+    bytes.push(1);
+    assert!(bcs::from_bytes::<Market>(&bytes).is_err());
+
+    // A field every layout has cannot fall back to its default
+    let mut bytes = current.clone();
+    let closed = bytes.len() - bcs::to_bytes(&(written.featured, written.max_trade_shares, written.upvotes, written.key)).unwrap().len() - 1;
+    assert_eq!(bytes[closed], 1);
+    bytes[closed] = 2;
+    assert!(bcs::from_bytes::<Market>(&bytes).is_err());
+
+    // Nor can one its version has
+    assert!(bcs::from_bytes::<Market>(&cut(current.clone(), &written.key)).is_err());
+
+    // Nor does a layout from a later build read as this one
+    let mut bytes = current;
+    bytes[header(MARKET_VERSION) - 1] = MARKET_VERSION + 1;
+    assert!(bcs::from_bytes::<Market>(&bytes).is_err());
+}
+
+#[test]
+fn position_without_realized_pnl_fills_defaults() {
+    let written = position();
+    let bare = bcs::to_bytes(&written).unwrap().split_off(header(POSITION_VERSION));
+    let decoded: Position = bcs::from_bytes(&cut(bare, &written.realized_pnl)).unwrap();
+    assert_eq!(decoded.yes_shares, Amount::from_tokens(5));
+    assert_eq!(decoded.yes_cost_basis, Amount::from_tokens(3));
+    assert_eq!(decoded.realized_pnl, 0);
+    assert_eq!(decoded.version, 0);
+}
+
+#[test]
+fn activity_entry_without_trade_outcome_fills_defaults() {
+    let entry = ActivityEntry {
+        kind: "BuyShares".to_string(),
+        market_id: Some(4),
+        actor: AccountOwner::CHAIN,
+        amount: Some(Amount::from_tokens(2)),
+        shares: Some(Amount::from_tokens(3)),
+        result: "SharesPurchased:2.".to_string(),
+        timestamp: Timestamp::from(7),
+        is_yes: Some(true),
+        yes_price_bps: Some(5_500),
+        trade_seq: Some(11),
+        position_after: Some(Amount::from_tokens(3)),
+    };
+    let decoded: ActivityEntry = bcs::from_bytes(&bcs::to_bytes(&entry).unwrap()).unwrap();
+    assert_eq!((decoded.yes_price_bps, decoded.trade_seq), (Some(5_500), Some(11)));
+
+    let tail = (entry.yes_price_bps, entry.trade_seq, entry.position_after);
+    let decoded: ActivityEntry = bcs::from_bytes(&cut(bcs::to_bytes(&entry).unwrap(), &tail)).unwrap();
+    assert_eq!(decoded.is_yes, Some(true));
+    assert_eq!((decoded.yes_price_bps, decoded.trade_seq, decoded.position_after), (None, None, None));
+
+    // A trailing field that is there but malformed is an error, not a default
+    let mut bytes = bcs::to_bytes(&entry).unwrap();
+    let tag = bytes.len() - bcs::to_bytes(&tail).unwrap().len();
+    bytes[tag] = 2;
+    assert!(bcs::from_bytes::<ActivityEntry>(&bytes).is_err());
+}
+
+#[test]
+fn positions_holding_shares_without_a_basis_report_it_unknown() {
+    let mut held = position();
+    assert!(!held.basis_unknown());
+    held.no_shares = Amount::from_tokens(2);
+    assert!(held.basis_unknown());
+    held.no_cost_basis = Amount::from_tokens(1);
+    assert!(!held.basis_unknown());
+}
+
+#[test]
+fn trader_stats_from_before_the_counts_read_as_zeros() {
+    let stats = TraderStats {
+        realized_pnl: -5,
+        volume: Amount::from_tokens(30),
+        trade_count: 4,
+        markets_traded: 3,
+        markets_won: 2,
+        markets_lost: 1,
+        open_positions: 1,
+    };
+    assert_eq!(stats.win_rate_bps(), Some(6_666));
+
+    let tail = (stats.volume, stats.trade_count, stats.markets_traded, stats.markets_won, stats.markets_lost, stats.open_positions);
+    let decoded: TraderStats = bcs::from_bytes(&cut(bcs::to_bytes(&stats).unwrap(), &tail)).unwrap();
+    assert_eq!(decoded.realized_pnl, -5);
+    assert_eq!((decoded.volume, decoded.trade_count, decoded.markets_won, decoded.open_positions), (Amount::ZERO, 0, 0, 0));
+    assert_eq!(decoded.win_rate_bps(), None);
+}
+
+#[test]
+fn trigger_orders_from_before_buy_orders_decode_as_sales() {
+    let buy = TriggerOrder {
+        id: 2,
+        owner: AccountOwner::CHAIN,
+        market_id: 7,
+        is_yes: false,
+        shares: Amount::from_tokens(10),
+        trigger_price_bps: 5_000,
+        direction: TriggerDirection::Below,
+        min_proceeds: Amount::ZERO,
+        keeper_fee: Amount::ZERO,
+        created_at: Timestamp::from(1_000),
+        status: TriggerStatus::Pending,
+        proceeds: Amount::ZERO,
+        closed_at: None,
+        max_price_bps: Some(6_000),
+        escrow: Amount::from_tokens(6),
+        cost: Amount::ZERO,
+    };
+    // A purchase watches the YES price whichever side it buys
+    assert_eq!(buy.side(), OrderSide::Buy);
+    assert!(buy.is_triggered(&market()));
+
+    let tail = (buy.max_price_bps, buy.escrow, buy.cost);
+    let decoded: TriggerOrder = bcs::from_bytes(&cut(bcs::to_bytes(&buy).unwrap(), &tail)).unwrap();
+    assert_eq!(decoded.side(), OrderSide::Sell);
+    assert_eq!((decoded.max_price_bps, decoded.escrow), (None, Amount::ZERO));
+    let mut sale = decoded;
+    sale.trigger_price_bps = 4_999;
+    assert!(!sale.is_triggered(&market()));
+}
+
+#[test]
+fn upgraded_records_are_written_at_the_latest_version() {
+    let written = market();
+    let bytes = cut(legacy_market(&written), &(written.price_cumulative, written.price_updated_at));
+    let mut decoded: Market = bcs::from_bytes(&bytes).unwrap();
+    decoded.upgrade();
+    let rewritten: Market = bcs::from_bytes(&bcs::to_bytes(&decoded).unwrap()).unwrap();
+    assert_eq!(rewritten.version, MARKET_VERSION);
+    assert_eq!(rewritten.price_updated_at, None);
+
+    let bare = bcs::to_bytes(&position()).unwrap().split_off(header(POSITION_VERSION));
+    let mut decoded: Position = bcs::from_bytes(&cut(bare, &position().realized_pnl)).unwrap();
+    decoded.upgrade();
+    let rewritten: Position = bcs::from_bytes(&bcs::to_bytes(&decoded).unwrap()).unwrap();
+    assert_eq!(rewritten.version, POSITION_VERSION);
+    assert_eq!(rewritten.realized_pnl, 0);
+}
+
+#[test]
+fn config_without_recent_trades_capacity_uses_the_default() {
+    let config = Config { recent_trades_capacity: Some(3), ..Config::default() };
+    let tail = (
+        config.recent_trades_capacity,
+        config.share_decimals,
+        config.max_probability_move_bps,
+        config.keeper_fee,
+        config.maker_rebate_bps,
+        config.creator_bond,
+        config.bond_trader_share_bps,
+        &config.fee_tiers,
+    );
+    let decoded: Config = bcs::from_bytes(&cut(bcs::to_bytes(&config).unwrap(), &tail)).unwrap();
+    assert_eq!(decoded.recent_trades_capacity, None);
+    assert_eq!(decoded.recent_trades_capacity(), DEFAULT_RECENT_TRADES_CAPACITY);
+    assert_eq!(decoded.min_trade_shares, config.min_trade_shares);
+    assert_eq!(decoded.share_decimals(), 18);
+}
+
+#[test]
+fn config_json_rejects_malformed_optional_fields() {
+    let mut json = serde_json::to_value(Config::default()).unwrap();
+    json.as_object_mut().unwrap().remove("fee_tiers");
+    let config: Config = serde_json::from_value(json.clone()).unwrap();
+    assert!(config.fee_tiers.is_empty());
+
+    let mut bad_bond = json.clone();
+    bad_bond["creator_bond"] = "abc".into();
+    assert!(serde_json::from_value::<Config>(bad_bond).is_err());
+
+    let mut bad_tier = json;
+    bad_tier["fee_tiers"] = serde_json::json!([{ "min_volume": "10." }]);
+    assert!(serde_json::from_value::<Config>(bad_tier).is_err());
+}
+
+#[test]
+fn trade_checks_refuse_closed_markets_and_odd_sizes() {
+    let open = Market { closed: false, max_trade_shares: Some(Amount::from_tokens(25)), ..market() };
+    assert!(check_open(&open, Timestamp::from(1_999)).is_ok());
+    let ended = check_open(&open, Timestamp::from(2_000)).unwrap_err();
+    assert_eq!(ended.to_string(), "Market 7 has ended (now=2000, end=2000)");
+    let resolved = Market { resolved: true, ..open.clone() };
+    let resolved = check_open(&resolved, Timestamp::from(1_000)).unwrap_err();
+    assert_eq!(resolved.to_string(), "Market 7 is already resolved");
+
+    let config = Config { min_trade_shares: Amount::ONE, share_decimals: Some(2), ..Config::default() };
+    assert!(config.check_trade_shares(&open, Amount::from_tokens(25)).is_ok());
+    assert!(matches!(config.check_trade_shares(&open, Amount::ZERO), Err(MarketError::ZeroShares)));
+    let small = config.check_trade_shares(&open, Amount::from_millis(500));
+    assert!(matches!(small, Err(MarketError::TradeTooSmall { .. })));
+    let large = config.check_trade_shares(&open, Amount::from_tokens(26));
+    assert!(matches!(large, Err(MarketError::TradeTooLarge { market_id: 7, .. })));
+    let fine = config.check_trade_shares(&open, Amount::from_millis(1_001));
+    assert!(matches!(fine, Err(MarketError::SharePrecision { decimals: 2, .. })));
+}
+
+#[test]
+fn summary_shows_what_the_market_does() {
+    let market = Market { yes_pool: Amount::from_tokens(30), no_pool: Amount::from_tokens(70), ..market() };
+    let summary = MarketSummary::from(&market);
+    assert_eq!(summary.id, market.id);
+    assert_eq!(summary.question, market.question);
+    assert_eq!(summary.yes_price_bps(), market.yes_price_bps());
+    assert_eq!(summary.no_price_bps(), market.no_price_bps());
+    assert_eq!(summary.yes_price_bps(), 7_000);
+    assert!(summary.closed);
+}
+
+#[test]
+fn merged_positions_add_up() {
+    let mut held = position();
+    held.merge(&Position { no_shares: Amount::from_tokens(2), no_cost_basis: Amount::ONE, realized_pnl: 4, ..position() });
+    assert_eq!(held.yes_shares, Amount::from_tokens(10));
+    assert_eq!(held.no_shares, Amount::from_tokens(2));
+    assert_eq!(held.yes_cost_basis, Amount::from_tokens(6));
+    assert_eq!(held.no_cost_basis, Amount::ONE);
+    assert_eq!(held.realized_pnl, -5);
+    assert!(!held.is_empty());
+    assert!(Position { yes_shares: Amount::ZERO, ..position() }.is_empty());
+}
+
+#[test]
+fn entry_prices_and_unrealized_pnl_come_from_the_stored_basis() {
+    let held = Position { no_shares: Amount::from_tokens(3), no_cost_basis: Amount::ONE, ..position() };
+    assert_eq!(held.average_entry_price_bps(true), Some(6_000));
+    // One token over three shares rounds down
+    assert_eq!(held.average_entry_price_bps(false), Some(3_333));
+    assert_eq!(position().average_entry_price_bps(false), None);
+
+    assert_eq!(held.unrealized_pnl(Amount::from_tokens(5)), 10i128.pow(18));
+    assert_eq!(held.unrealized_pnl(Amount::from_tokens(3)), -(10i128.pow(18)));
+    assert_eq!(Position { claimed: true, ..held }.unrealized_pnl(Amount::ZERO), 0);
+}
+
+#[test]
+fn lp_value_is_the_escrow_traders_have_no_claim_on() {
+    // 10 YES bought for 12.5 from pools of 50 each
+    let traded = Market {
+        yes_pool: Amount::from_tokens(40),
+        no_pool: Amount::from_attos(62_500_000_000_000_000_000),
+        total_yes_shares: Amount::from_tokens(60),
+        total_no_shares: Amount::from_tokens(50),
+        seed_shares: Amount::from_tokens(50),
+        escrow: Amount::from_attos(112_500_000_000_000_000_000),
+        ..market()
+    };
+    // Less 10 YES at 62.5 / 102.5
+    assert_eq!(traded.lp_value(Amount::ZERO), Amount::from_attos(106_402_439_024_390_243_903));
+    let resolved = Market { resolved: true, outcome: Some(true), ..traded.clone() };
+    // Less 10 of the 60 YES shares' claim on 102.5
+    assert_eq!(resolved.lp_value(Amount::ZERO), Amount::from_attos(95_416_666_666_666_666_667));
+    let refunded = Market { refund_mode: true, ..resolved };
+    assert_eq!(refunded.lp_value(Amount::from_attos(12_500_000_000_000_000_000)), Amount::from_tokens(100));
+}
+
+#[test]
+fn lp_ledgers_net_every_flow_and_fee() {
+    let mut ledger = LpLedger::default();
+    for (tokens, deposit) in [(100, true), (10, false), (5, true)] {
+        ledger.record(LpFlow { amount: Amount::from_tokens(tokens), deposit, timestamp: Timestamp::from(1_000) });
+    }
+    ledger.fees_claimed = Amount::ONE;
+    assert_eq!(ledger.deposited, Amount::from_tokens(105));
+    assert_eq!(ledger.withdrawn, Amount::from_tokens(10));
+    assert_eq!(ledger.history.len(), 3);
+    // 90 + 10 withdrawn + 1 claimed + 2 pending - 105 deposited
+    assert_eq!(ledger.pnl(Amount::from_tokens(90), Amount::from_tokens(2)), -2 * 10i128.pow(18));
+}
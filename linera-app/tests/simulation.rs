@@ -0,0 +1,103 @@
+// Copyright (c) Chronos Markets
+// Native tests of the solvency model, its invariants and the scenario shrinker
+
+use chronos_market::simulation::{generate, run, shrink, Model, Size, Step};
+
+const TOKEN: u128 = 1_000_000_000_000_000_000;
+
+#[test]
+fn generated_scenarios_keep_every_invariant() {
+    for fee_bps in [0, 100, 1_000] {
+        for seed in 0..25 {
+            let steps = generate(seed, 300);
+            if let Err(failure) = run(&steps, fee_bps) {
+                panic!("seed {seed} at {fee_bps} bps: {failure}");
+            }
+        }
+    }
+    // The same seed always gives the same scenario
+    assert_eq!(generate(7, 50), generate(7, 50));
+    assert_ne!(generate(7, 50), generate(8, 50));
+}
+
+#[test]
+fn claims_pay_winners_from_escrow_and_leave_the_rest_to_the_lp() {
+    let steps = [
+        Step::Create { liquidity: 100 * TOKEN },
+        Step::Buy { market: 0, trader: 0, is_yes: true, size: Size::Attos(10 * TOKEN) },
+        Step::Buy { market: 0, trader: 1, is_yes: false, size: Size::Attos(10 * TOKEN) },
+        Step::Resolve { market: 0, outcome: true },
+        Step::ClaimAll { market: 0 },
+    ];
+    let model = run(&steps, 100).unwrap();
+    let market = &model.markets[0];
+    // 12.5 for the YES buy, then 40 * 10 / 52.5 rounded up for the NO buy
+    let bought = 12_500_000_000_000_000_000 + 7_619_047_619_047_619_048;
+    assert_eq!(model.total_volume, bought);
+    assert_eq!(market.fees_collected, bought / 100);
+    // The winner takes 10 / 60 of both pools
+    let pot = market.yes_pool + market.no_pool;
+    assert_eq!(market.escrow, 100 * TOKEN + bought - 10 * pot / 60);
+    assert!(model.positions.values().all(|position| position.claimed));
+    assert_eq!(model.contract_balance, market.escrow + market.fees_collected);
+}
+
+#[test]
+fn refused_steps_change_nothing() {
+    let mut model = Model::new(100);
+    model.apply(Step::Create { liquidity: 100 * TOKEN }).unwrap();
+    model.apply(Step::Resolve { market: 0, outcome: true }).unwrap();
+    let resolved = format!("{model:?}");
+
+    let refused = [
+        Step::Buy { market: 0, trader: 0, is_yes: true, size: Size::Attos(TOKEN) },
+        Step::Buy { market: 1, trader: 0, is_yes: true, size: Size::Attos(TOKEN) },
+        Step::Sell { market: 0, trader: 0, is_yes: true, held_bps: 10_000 },
+        Step::Resolve { market: 0, outcome: false },
+        Step::ClaimAll { market: 3 },
+    ];
+    for step in refused {
+        assert!(model.apply(step).is_err(), "{step:?}");
+        assert_eq!(format!("{model:?}"), resolved, "{step:?}");
+    }
+
+    // A buy of the whole pool, or of nothing, is refused too
+    let mut model = Model::new(0);
+    model.apply(Step::Create { liquidity: 100 * TOKEN }).unwrap();
+    assert!(model.apply(Step::Buy { market: 0, trader: 0, is_yes: true, size: Size::PoolBps(10_000) }).is_err());
+    assert!(model.apply(Step::Buy { market: 0, trader: 0, is_yes: true, size: Size::Attos(0) }).is_err());
+}
+
+#[test]
+fn refunds_stop_at_the_escrow_and_report_the_shortfall() {
+    // A sells NO at a profit after B pushed its price up, then YES wins
+    // with only the seed holding it, so everyone is refunded their basis
+    let steps = [
+        Step::Create { liquidity: 1_000 },
+        Step::Buy { market: 0, trader: 0, is_yes: false, size: Size::Attos(100) },
+        Step::Buy { market: 0, trader: 1, is_yes: false, size: Size::Attos(300) },
+        Step::Sell { market: 0, trader: 0, is_yes: false, held_bps: 10_000 },
+        Step::Resolve { market: 0, outcome: true },
+        Step::ClaimAll { market: 0 },
+    ];
+    let model = run(&steps, 0).unwrap();
+    assert!(model.markets[0].refund_mode);
+    // B paid 625 * 300 / 100; A's sale took 2500 * 100 / 200 of the
+    // 1000 + 125 + 1875 escrow, leaving 1750 for B's 1875
+    assert_eq!(model.markets[0].escrow, 0);
+    assert_eq!(model.refund_shortfall, 125);
+}
+
+#[test]
+fn shrinking_keeps_only_the_steps_a_failure_needs() {
+    let mut steps = generate(3, 40);
+    steps.insert(10, Step::Resolve { market: 9, outcome: true });
+    steps.insert(30, Step::ClaimAll { market: 9 });
+    // Stands in for an invariant that breaks once market 9 resolves and claims
+    let fails = |steps: &[Step]| {
+        let resolved = steps.iter().position(|step| *step == Step::Resolve { market: 9, outcome: true });
+        resolved.is_some_and(|at| steps[at..].contains(&Step::ClaimAll { market: 9 }))
+    };
+    let minimal = shrink(steps, fails);
+    assert_eq!(minimal, [Step::Resolve { market: 9, outcome: true }, Step::ClaimAll { market: 9 }]);
+}
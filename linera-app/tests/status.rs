@@ -0,0 +1,26 @@
+// Copyright (c) Chronos Markets
+// Native tests of the effective status the contract trades by and the service shows
+
+use chronos_market::EffectiveStatus;
+use linera_sdk::linera_base_types::Timestamp;
+
+const END_MICROS: u64 = 1_000_000;
+
+#[test]
+fn trading_stops_at_the_end_time_itself() {
+    let end = Timestamp::from(END_MICROS);
+    let at = |micros: u64| EffectiveStatus::at(false, false, end, Timestamp::from(micros));
+    assert_eq!(at(999_999), EffectiveStatus::Open);
+    assert_eq!(at(1_000_000), EffectiveStatus::Closed);
+    assert_eq!(at(1_000_001), EffectiveStatus::Closed);
+}
+
+#[test]
+fn stored_flags_override_the_clock() {
+    let (end, before) = (Timestamp::from(END_MICROS), Timestamp::from(0));
+    // Resolution wins over everything, early resolutions included
+    assert_eq!(EffectiveStatus::at(true, false, end, before), EffectiveStatus::Resolved);
+    assert_eq!(EffectiveStatus::at(true, true, end, end), EffectiveStatus::Resolved);
+    // A market recorded closed stays closed
+    assert_eq!(EffectiveStatus::at(false, true, end, before), EffectiveStatus::Closed);
+}
@@ -0,0 +1,103 @@
+// Copyright (c) Chronos Markets
+// Native tests of volume bucketing, the per-market hourly volume ring,
+// per-account daily buckets and the fee tiers volume earns
+
+use chronos_market::{
+    day_index, Config, FeeTier, hour_index, volume_history_start, TraderDays, VolumeRing, DAY_MICROS, TRADER_DAY_SLOTS, VOLUME_BUCKET_MICROS,
+    VOLUME_HISTORY_DAYS, VOLUME_RING_SLOTS,
+};
+use linera_sdk::linera_base_types::{Amount, Timestamp};
+
+fn at_hour(hour: u64) -> Timestamp {
+    Timestamp::from(hour * VOLUME_BUCKET_MICROS + 1)
+}
+
+#[test]
+fn sums_the_current_hour_and_the_23_before() {
+    let mut ring = VolumeRing::default();
+    ring.add(at_hour(100), Amount::from_tokens(1));
+    ring.add(at_hour(100), Amount::from_tokens(2));
+    ring.add(at_hour(110), Amount::from_tokens(4));
+    assert_eq!(ring.slots.len(), VOLUME_RING_SLOTS as usize);
+    assert_eq!(ring.last_24h(at_hour(110)), Amount::from_tokens(7));
+    assert_eq!(ring.last_24h(at_hour(123)), Amount::from_tokens(7));
+    // Hour 100 drops out once it is 24 hours back
+    assert_eq!(ring.last_24h(at_hour(124)), Amount::from_tokens(4));
+    assert_eq!(ring.last_24h(at_hour(134)), Amount::ZERO);
+}
+
+#[test]
+fn a_trade_resets_the_stale_hour_in_its_slot() {
+    let mut ring = VolumeRing::default();
+    ring.add(at_hour(100), Amount::from_tokens(5));
+    // Same slot, one lap later
+    let lap = 100 + VOLUME_RING_SLOTS;
+    ring.add(at_hour(lap), Amount::from_tokens(1));
+    assert_eq!(ring.last_24h(at_hour(lap)), Amount::from_tokens(1));
+    assert_eq!(ring.slots.iter().filter(|(hour, _)| *hour == 100).count(), 0);
+}
+
+#[test]
+fn an_empty_ring_has_no_volume() {
+    assert_eq!(VolumeRing::default().last_24h(at_hour(5)), Amount::ZERO);
+}
+
+#[test]
+fn buckets_split_exactly_at_hour_and_day_boundaries() {
+    let midnight = 20_000 * DAY_MICROS;
+    assert_eq!(day_index(Timestamp::from(midnight - 1)), 19_999);
+    assert_eq!(day_index(Timestamp::from(midnight)), 20_000);
+    assert_eq!(hour_index(Timestamp::from(midnight - 1)), 20_000 * 24 - 1);
+    assert_eq!(hour_index(Timestamp::from(midnight)), 20_000 * 24);
+    assert_eq!(hour_index(Timestamp::from(midnight + VOLUME_BUCKET_MICROS)), 20_000 * 24 + 1);
+}
+
+#[test]
+fn daily_history_keeps_the_last_400_days() {
+    assert_eq!(volume_history_start(20_000), 20_000 - VOLUME_HISTORY_DAYS + 1);
+    assert_eq!(20_000 - volume_history_start(20_000) + 1, 400);
+    // Early days keep everything since the epoch
+    assert_eq!(volume_history_start(10), 0);
+}
+
+#[test]
+fn trader_windows_sum_today_and_the_days_before() {
+    let at_day = |day: u64| Timestamp::from(day * DAY_MICROS + 1);
+    let mut days = TraderDays::default();
+    days.add(at_day(100), Amount::from_tokens(5), 0, 1);
+    days.add(at_day(100), Amount::ZERO, -2, 0);
+    days.add(at_day(106), Amount::from_tokens(3), 7, 2);
+    assert_eq!(days.slots.len(), TRADER_DAY_SLOTS as usize);
+
+    let week = days.window(at_day(106), 7);
+    assert_eq!((week.volume, week.realized_pnl, week.trade_count), (Amount::from_tokens(8), 5, 3));
+    // Day 100 is eight days back from day 107
+    let week = days.window(at_day(107), 7);
+    assert_eq!((week.volume, week.realized_pnl, week.trade_count), (Amount::from_tokens(3), 7, 2));
+
+    // A lap later day 100's slot is reused rather than summed
+    days.add(at_day(100 + TRADER_DAY_SLOTS), Amount::from_tokens(1), 0, 1);
+    let month = days.window(at_day(100 + TRADER_DAY_SLOTS), 30);
+    assert_eq!((month.volume, month.trade_count), (Amount::from_tokens(4), 3));
+}
+
+#[test]
+fn fee_tiers_apply_from_their_exact_threshold() {
+    let tier = |tokens, discount_bps| FeeTier { min_volume: Amount::from_tokens(tokens), discount_bps };
+    let config = Config { fee_tiers: vec![tier(10_000, 2_000), tier(100_000, 4_000)], ..Config::default() };
+    let just_under = |tokens| Amount::from_tokens(tokens).saturating_sub(Amount::from_attos(1));
+
+    assert_eq!(config.fee_discount_bps(Amount::ZERO), 0);
+    assert_eq!(config.fee_discount_bps(just_under(10_000)), 0);
+    assert_eq!(config.fee_discount_bps(Amount::from_tokens(10_000)), 2_000);
+    assert_eq!(config.fee_discount_bps(just_under(100_000)), 2_000);
+    assert_eq!(config.fee_discount_bps(Amount::from_tokens(100_000)), 4_000);
+    assert_eq!(config.fee_discount_bps(Amount::from_tokens(1_000_000)), 4_000);
+
+    assert_eq!(config.discounted_fee_bps(200, just_under(10_000)), 200);
+    assert_eq!(config.discounted_fee_bps(200, Amount::from_tokens(10_000)), 160);
+    assert_eq!(config.discounted_fee_bps(200, Amount::from_tokens(100_000)), 120);
+    // Rounded down, in the trader's favour
+    assert_eq!(config.discounted_fee_bps(3, Amount::from_tokens(100_000)), 1);
+    assert_eq!(Config::default().discounted_fee_bps(200, Amount::from_tokens(100_000)), 200);
+}
@@ -30,8 +30,12 @@ cargo build --release --target wasm32-unknown-unknown
 echo "📦 Publishing and creating application..."
 cd /build
 
+# Key in this wallet that becomes the protocol admin
+ADMIN_OWNER=$(linera keygen)
+INIT_ARGUMENT='{"admin": "'"$ADMIN_OWNER"'", "fee_bps": 0, "min_initial_liquidity": "1", "min_market_duration_secs": 3600, "resolution_grace_period_secs": 604800, "admin_resolution_delay_secs": 259200, "claim_window_secs": 2592000, "min_trade_shares": "0.000001"}'
+
 # Publish and create the application, capture the output
-APP_OUTPUT=$(linera project publish-and-create ./linera-app --json-argument "null" 2>&1)
+APP_OUTPUT=$(linera project publish-and-create ./linera-app --json-argument "$INIT_ARGUMENT" --json-parameters '{"registry_chain": null}' 2>&1)
 echo "$APP_OUTPUT"
 
 # Extract application ID from output